@@ -1,8 +1,10 @@
 use crate::block_processing_utils::{
     BlockPreprocessInfo, BlockProcessingArtifact, BlocksInProcessing, DoneApplyChunkCallback,
+    MAX_PROCESSING_BLOCKS,
 };
 use crate::blocks_delay_tracker::BlocksDelayTracker;
 use crate::crypto_hash_timer::CryptoHashTimer;
+use crate::head_recovery;
 use crate::lightclient::get_epoch_block_producers_view;
 use crate::migrations::check_if_block_is_first_with_chunk_of_version;
 use crate::missing_chunks::{BlockLike, MissingChunksPool};
@@ -38,6 +40,7 @@ use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::merkle::{
     combine_hash, merklize, verify_path, Direction, MerklePath, MerklePathItem, PartialMerkleTree,
 };
+use near_primitives::proof::verify_receipt_proof;
 use near_primitives::receipt::Receipt;
 use near_primitives::sandbox::state_patch::SandboxStatePatch;
 use near_primitives::shard_layout::{
@@ -443,6 +446,10 @@ pub struct Chain {
     apply_chunks_sender: Sender<BlockApplyChunksResult>,
     /// Used to receive apply chunks results
     apply_chunks_receiver: Receiver<BlockApplyChunksResult>,
+    /// Thread pool applying the chunks of the blocks, one job per shard, so that the chunks of
+    /// a block are applied in parallel without competing with the other users of the global
+    /// rayon pool. Not created for the view client chain, which doesn't apply chunks.
+    apply_chunks_pool: Option<Arc<rayon::ThreadPool>>,
     /// Time when head was updated most recently.
     last_time_head_updated: Instant,
 
@@ -465,6 +472,11 @@ pub struct Chain {
     /// Used to store state parts already requested along with elapsed time
     /// to create the parts. This information is used for debugging
     pub(crate) requested_state_parts: StateRequestTracker,
+
+    /// If set, the head is not switched to a fork reverting more blocks than that.
+    pub max_reorg_depth: Option<BlockHeightDelta>,
+    /// Block allowed by the operator, whose fork is followed regardless of `max_reorg_depth`.
+    deep_reorg_allowed_block: Option<CryptoHash>,
 }
 
 impl Drop for Chain {
@@ -525,9 +537,12 @@ impl Chain {
             blocks_delay_tracker: BlocksDelayTracker::default(),
             apply_chunks_sender: sc,
             apply_chunks_receiver: rc,
+            apply_chunks_pool: None,
             last_time_head_updated: StaticClock::instant(),
             invalid_blocks: LruCache::new(INVALID_CHUNKS_POOL_SIZE),
             pending_state_patch: Default::default(),
+            max_reorg_depth: None,
+            deep_reorg_allowed_block: None,
             requested_state_parts: StateRequestTracker::new(),
         })
     }
@@ -542,6 +557,7 @@ impl Chain {
         let (store, state_roots) = runtime_adapter.genesis_state();
         let mut store =
             ChainStore::new(store, chain_genesis.height, chain_config.save_trie_changes);
+        store.set_index_account_activity(chain_config.index_account_activity);
         let genesis_chunks = genesis_chunks(
             state_roots.clone(),
             runtime_adapter.num_shards(&EpochId::default())?,
@@ -678,12 +694,23 @@ impl Chain {
             blocks_delay_tracker: BlocksDelayTracker::default(),
             apply_chunks_sender: sc,
             apply_chunks_receiver: rc,
+            apply_chunks_pool: Some(new_apply_chunks_pool(chain_config.apply_chunks_threads)),
             last_time_head_updated: StaticClock::instant(),
             pending_state_patch: Default::default(),
             requested_state_parts: StateRequestTracker::new(),
+            max_reorg_depth: chain_config.max_reorg_depth,
+            deep_reorg_allowed_block: None,
         })
     }
 
+    /// Allows the head to be switched to the fork containing the given block, even if it reverts
+    /// more than `max_reorg_depth` blocks. The fork is switched to once its next block is
+    /// processed.
+    pub fn allow_deep_reorg(&mut self, block_hash: CryptoHash) {
+        warn!(target: "chain", %block_hash, "Deep reorg to the fork of the block allowed");
+        self.deep_reorg_allowed_block = Some(block_hash);
+    }
+
     #[cfg(feature = "test_features")]
     pub fn adv_disable_doomslug(&mut self) {
         self.doomslug_threshold_mode = DoomslugThresholdMode::NoApprovals
@@ -2048,7 +2075,19 @@ impl Chain {
         Ok(())
     }
 
-    /// Applying chunks async by starting the work at the rayon thread pool
+    /// Rolls the head back if its data has not been fully written, e.g. because of a crash.
+    /// Only the data of the shards tracked by `me` is checked.
+    pub fn recover_head(&mut self, me: Option<&AccountId>) -> Result<(), Error> {
+        let head = head_recovery::recover_head(&mut self.store, &*self.runtime_adapter, me)?;
+        metrics::BLOCK_HEIGHT_HEAD.set(head.height as i64);
+        Ok(())
+    }
+
+    fn apply_chunks_pool(&self) -> &Arc<rayon::ThreadPool> {
+        self.apply_chunks_pool.as_ref().expect("the view client chain doesn't apply chunks")
+    }
+
+    /// Applying chunks async by starting the work at the apply chunks thread pool
     /// `apply_chunks_done_marker`: a marker that will be set to true once applying chunks is finished
     /// `apply_chunks_done_callback`: a callback that will be called once applying chunks is finished
     fn schedule_apply_chunks(
@@ -2060,9 +2099,10 @@ impl Chain {
         apply_chunks_done_callback: DoneApplyChunkCallback,
     ) {
         let sc = self.apply_chunks_sender.clone();
-        spawn(move || {
+        let pool = self.apply_chunks_pool().clone();
+        spawn(self.apply_chunks_pool(), move || {
             // do_apply_chunks runs `work` parallelly, but still waits for all of them to finish
-            let res = do_apply_chunks(block_hash, block_height, work);
+            let res = do_apply_chunks(&pool, block_hash, block_height, work);
             // If we encounter error here, that means the receiver is deallocated and the client
             // thread is already shut down. The node is already crashed, so we can unwrap here
             sc.send((block_hash, res)).unwrap();
@@ -2073,11 +2113,11 @@ impl Chain {
             apply_chunks_done_callback(block_hash);
         });
 
-        /// `rayon::spawn` decorated to propagate `tracing` context across
+        /// `ThreadPool::spawn` decorated to propagate `tracing` context across
         /// threads.
-        fn spawn(f: impl FnOnce() + Send + 'static) {
+        fn spawn(pool: &rayon::ThreadPool, f: impl FnOnce() + Send + 'static) {
             let dispatcher = tracing::dispatcher::get_default(|it| it.clone());
-            rayon::spawn(move || tracing::dispatcher::with_default(&dispatcher, f))
+            pool.spawn(move || tracing::dispatcher::with_default(&dispatcher, f))
         }
     }
 
@@ -2812,23 +2852,23 @@ impl Chain {
             let mut root_proofs_cur = vec![];
             assert_eq!(receipt_proofs.len(), block_header.chunks_included() as usize);
             for receipt_proof in receipt_proofs.iter() {
-                let ReceiptProof(receipts, shard_proof) = receipt_proof;
-                let ShardProof { from_shard_id, to_shard_id: _, proof } = shard_proof;
-                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, receipts));
+                let ReceiptProof(_, ShardProof { from_shard_id, .. }) = receipt_proof;
                 let from_shard_id = *from_shard_id as usize;
 
-                let root_proof = block.chunks()[from_shard_id].outgoing_receipts_root();
-                root_proofs_cur
-                    .push(RootProof(root_proof, block_receipts_proofs[from_shard_id].clone()));
+                let root_proof = RootProof(
+                    block.chunks()[from_shard_id].outgoing_receipts_root(),
+                    block_receipts_proofs[from_shard_id].clone(),
+                );
 
                 // Make sure we send something reasonable.
                 assert_eq!(block_header.chunk_receipts_root(), &block_receipts_root);
-                assert!(verify_path(root_proof, proof, &receipts_hash));
-                assert!(verify_path(
-                    block_receipts_root,
-                    &block_receipts_proofs[from_shard_id],
+                assert!(verify_receipt_proof(
+                    shard_id,
+                    receipt_proof,
                     &root_proof,
+                    &block_receipts_root
                 ));
+                root_proofs_cur.push(root_proof);
             }
             root_proofs.push(root_proofs_cur);
         }
@@ -3075,8 +3115,7 @@ impl Chain {
             // to prove that all receipts were received and no receipts were hidden.
             let mut visited_shard_ids = HashSet::<ShardId>::new();
             for (j, receipt_proof) in receipt_proofs.iter().enumerate() {
-                let ReceiptProof(receipts, shard_proof) = receipt_proof;
-                let ShardProof { from_shard_id, to_shard_id: _, proof } = shard_proof;
+                let ReceiptProof(_, ShardProof { from_shard_id, .. }) = receipt_proof;
                 // 4d. Checking uniqueness for set of `from_shard_id`
                 match visited_shard_ids.get(from_shard_id) {
                     Some(_) => {
@@ -3085,15 +3124,14 @@ impl Chain {
                     }
                     _ => visited_shard_ids.insert(*from_shard_id),
                 };
-                let RootProof(root, block_proof) = &shard_state_header.root_proofs()[i][j];
-                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, receipts));
                 // 4e. Proving the set of receipts is the subset of outgoing_receipts of shard `shard_id`
-                if !verify_path(*root, proof, &receipts_hash) {
-                    byzantine_assert!(false);
-                    return Err(Error::Other("set_shard_state failed: invalid proofs".into()));
-                }
                 // 4f. Proving the outgoing_receipts_root matches that in the block
-                if !verify_path(*block_header.chunk_receipts_root(), block_proof, root) {
+                if !verify_receipt_proof(
+                    shard_id,
+                    receipt_proof,
+                    &shard_state_header.root_proofs()[i][j],
+                    block_header.chunk_receipts_root(),
+                ) {
                     byzantine_assert!(false);
                     return Err(Error::Other("set_shard_state failed: invalid proofs".into()));
                 }
@@ -3380,6 +3418,7 @@ impl Chain {
                 block_hash: pending_block,
                 block_height: block.header().height(),
                 work,
+                apply_chunks_pool: self.apply_chunks_pool().clone(),
             });
         }
 
@@ -4091,6 +4130,8 @@ impl Chain {
             self.runtime_adapter.clone(),
             self.doomslug_threshold_mode,
             self.transaction_validity_period,
+            self.max_reorg_depth,
+            self.deep_reorg_allowed_block,
         )
     }
 
@@ -4480,6 +4521,12 @@ impl Chain {
         self.blocks_in_processing.len()
     }
 
+    /// Returns true if there is room in the processing pool to start processing another block.
+    #[inline]
+    pub fn can_start_processing_block(&self) -> bool {
+        self.blocks_in_processing.len() < MAX_PROCESSING_BLOCKS
+    }
+
     /// Returns number of evicted orphans.
     #[inline]
     pub fn orphans_evicted_len(&self) -> usize {
@@ -4681,6 +4728,8 @@ pub struct ChainUpdate<'a> {
     doomslug_threshold_mode: DoomslugThresholdMode,
     #[allow(unused)]
     transaction_validity_period: BlockHeightDelta,
+    max_reorg_depth: Option<BlockHeightDelta>,
+    deep_reorg_allowed_block: Option<CryptoHash>,
 }
 
 pub struct SameHeightResult {
@@ -4714,12 +4763,16 @@ impl<'a> ChainUpdate<'a> {
         runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
         doomslug_threshold_mode: DoomslugThresholdMode,
         transaction_validity_period: BlockHeightDelta,
+        max_reorg_depth: Option<BlockHeightDelta>,
+        deep_reorg_allowed_block: Option<CryptoHash>,
     ) -> Self {
         let chain_store_update: ChainStoreUpdate<'_> = store.store_update();
         Self::new_impl(
             runtime_adapter,
             doomslug_threshold_mode,
             transaction_validity_period,
+            max_reorg_depth,
+            deep_reorg_allowed_block,
             chain_store_update,
         )
     }
@@ -4728,6 +4781,8 @@ impl<'a> ChainUpdate<'a> {
         runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
         doomslug_threshold_mode: DoomslugThresholdMode,
         transaction_validity_period: BlockHeightDelta,
+        max_reorg_depth: Option<BlockHeightDelta>,
+        deep_reorg_allowed_block: Option<CryptoHash>,
         chain_store_update: ChainStoreUpdate<'a>,
     ) -> Self {
         ChainUpdate {
@@ -4735,6 +4790,8 @@ impl<'a> ChainUpdate<'a> {
             chain_store_update,
             doomslug_threshold_mode,
             transaction_validity_period,
+            max_reorg_depth,
+            deep_reorg_allowed_block,
         }
     }
 
@@ -5231,7 +5288,9 @@ impl<'a> ChainUpdate<'a> {
         header: &BlockHeader,
     ) -> Result<Option<Tip>, Error> {
         let header_head = self.chain_store_update.header_head()?;
-        if header.height() > header_head.height {
+        // The header head defines the canonical chain by height, so it mustn't be switched to a
+        // refused fork either.
+        if header.height() > header_head.height && self.is_reorg_allowed(header, &header_head)? {
             let tip = Tip::from_header(header);
             self.chain_store_update.save_header_head_if_not_challenged(&tip)?;
             debug!(target: "chain", "Header head updated to {} at {}", tip.last_block_hash, tip.height);
@@ -5263,10 +5322,13 @@ impl<'a> ChainUpdate<'a> {
     /// Directly updates the head if we've just appended a new block to it or handle
     /// the situation where the block has higher height to have a fork
     fn update_head(&mut self, header: &BlockHeader) -> Result<Option<Tip>, Error> {
+        let head = self.chain_store_update.head()?;
+        if header.height() > head.height && !self.is_reorg_allowed(header, &head)? {
+            return Ok(None);
+        }
         // if we made a fork with higher height than the head (which should also be true
         // when extending the head), update it
         self.update_final_head_from_block(header)?;
-        let head = self.chain_store_update.head()?;
         if header.height() > head.height {
             let tip = Tip::from_header(header);
 
@@ -5280,6 +5342,62 @@ impl<'a> ChainUpdate<'a> {
         }
     }
 
+    /// Checks that switching the head to `header` doesn't revert more than `max_reorg_depth`
+    /// blocks of the canonical chain, unless the fork has been allowed by the operator.
+    fn is_reorg_allowed(&self, header: &BlockHeader, head: &Tip) -> Result<bool, Error> {
+        let max_depth = match self.max_reorg_depth {
+            Some(max_depth) => max_depth,
+            None => return Ok(true),
+        };
+        let allowed_block = self.deep_reorg_allowed_block;
+        let mut allowed = allowed_block == Some(*header.hash());
+        // Walk back the fork until a block of the canonical chain.
+        let mut hash = *header.prev_hash();
+        let common_ancestor_height = loop {
+            let fork_header = self.chain_store_update.get_block_header(&hash)?;
+            let height = fork_header.height();
+            if height <= head.height
+                && self.chain_store_update.get_block_hash_by_height(height).ok() == Some(hash)
+            {
+                break height;
+            }
+            allowed |= allowed_block == Some(hash);
+            // Unless the operator allowed some fork, there is no need to find out exactly how
+            // deep this one is.
+            if allowed_block.is_none() && height + max_depth < head.height {
+                break height;
+            }
+            hash = *fork_header.prev_hash();
+        };
+        let depth = head.height - common_ancestor_height;
+        if depth <= max_depth {
+            return Ok(true);
+        }
+        if allowed {
+            warn!(
+                target: "chain",
+                head = %head.last_block_hash,
+                new_head = %header.hash(),
+                depth,
+                "Switching to a deep fork allowed by the operator",
+            );
+            return Ok(true);
+        }
+        error!(
+            target: "chain",
+            head = %head.last_block_hash,
+            head_height = head.height,
+            fork_block = %header.hash(),
+            fork_height = header.height(),
+            min_depth = depth,
+            max_reorg_depth = max_depth,
+            "CRITICAL: refusing to switch to a fork reverting more than max_reorg_depth blocks. \
+             If the fork is legitimate, allow it via the /admin/allow_deep_reorg endpoint.",
+        );
+        metrics::DEEP_REORGS_REFUSED.inc();
+        Ok(false)
+    }
+
     /// Marks a block as invalid,
     fn mark_block_as_challenged(
         &mut self,
@@ -5516,7 +5634,20 @@ impl<'a> ChainUpdate<'a> {
     }
 }
 
+/// Creates the thread pool applying the chunks. Zero threads means one per CPU.
+fn new_apply_chunks_pool(num_threads: usize) -> Arc<rayon::ThreadPool> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|i| format!("apply_chunks_{}", i))
+        .build()
+        .expect("failed to start the apply chunks thread pool");
+    Arc::new(pool)
+}
+
+/// Applies the chunks of a block in parallel on the given pool, one job per shard, and waits
+/// for all of them, so the time taken is that of the slowest shard given enough threads.
 pub fn do_apply_chunks(
+    pool: &rayon::ThreadPool,
     block_hash: CryptoHash,
     block_height: BlockHeight,
     work: Vec<Box<dyn FnOnce(&Span) -> Result<ApplyChunkResult, Error> + Send>>,
@@ -5524,13 +5655,16 @@ pub fn do_apply_chunks(
     let parent_span =
         tracing::debug_span!(target: "chain", "do_apply_chunks", block_height, %block_hash)
             .entered();
-    work.into_par_iter()
-        .map(|task| {
-            // As chunks can be processed in parallel, make sure they are all tracked as children of
-            // a single span.
-            task(&parent_span)
-        })
-        .collect::<Vec<_>>()
+    let _timer = metrics::BLOCK_APPLY_CHUNKS_TIME.start_timer();
+    pool.install(|| {
+        work.into_par_iter()
+            .map(|task| {
+                // As chunks can be processed in parallel, make sure they are all tracked as
+                // children of a single span.
+                task(&parent_span)
+            })
+            .collect::<Vec<_>>()
+    })
 }
 
 pub fn collect_receipts<'a, T>(receipt_proofs: T) -> Vec<Receipt>
@@ -5574,6 +5708,8 @@ pub struct BlockCatchUpRequest {
     pub block_hash: CryptoHash,
     pub block_height: BlockHeight,
     pub work: Vec<Box<dyn FnOnce(&Span) -> Result<ApplyChunkResult, Error> + Send>>,
+    /// Pool to apply the chunks on, shared with the block processing.
+    pub apply_chunks_pool: Arc<rayon::ThreadPool>,
 }
 
 #[derive(actix::Message)]
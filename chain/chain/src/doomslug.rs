@@ -211,20 +211,29 @@ impl DoomslugApprovalsTracker {
     /// `ReadySince` if the block has enough approvals to pass the threshold, and since when it
     ///     does
     fn get_block_production_readiness(&mut self, now: Instant) -> DoomslugBlockProductionReadiness {
-        if (self.approved_stake_this_epoch > self.total_stake_this_epoch * 2 / 3
-            && (self.approved_stake_next_epoch > self.total_stake_next_epoch * 2 / 3
-                || self.total_stake_next_epoch == 0))
-            || self.threshold_mode == DoomslugThresholdMode::NoApprovals
-        {
-            if self.time_passed_threshold == None {
-                self.time_passed_threshold = Some(now);
-            }
-            DoomslugBlockProductionReadiness::ReadySince(self.time_passed_threshold.unwrap())
+        if self.has_enough_approvals() && self.time_passed_threshold == None {
+            self.time_passed_threshold = Some(now);
+        }
+        self.peek_block_production_readiness(now)
+    }
+
+    /// Same as `get_block_production_readiness`, but doesn't record the time at which the
+    /// threshold was passed.
+    fn peek_block_production_readiness(&self, now: Instant) -> DoomslugBlockProductionReadiness {
+        if self.has_enough_approvals() {
+            DoomslugBlockProductionReadiness::ReadySince(self.time_passed_threshold.unwrap_or(now))
         } else {
             DoomslugBlockProductionReadiness::NotReady
         }
     }
 
+    fn has_enough_approvals(&self) -> bool {
+        (self.approved_stake_this_epoch > self.total_stake_this_epoch * 2 / 3
+            && (self.approved_stake_next_epoch > self.total_stake_next_epoch * 2 / 3
+                || self.total_stake_next_epoch == 0))
+            || self.threshold_mode == DoomslugThresholdMode::NoApprovals
+    }
+
     // Get witnesses together with their arrival time.
     fn get_witnesses(&self) -> Vec<(AccountId, chrono::DateTime<chrono::Utc>)> {
         self.witness
@@ -360,6 +369,21 @@ impl Doomslug {
         }
     }
 
+    /// Updates the delays of the timer, see `new`. They apply from the next time the timer is
+    /// checked, including to the height currently waited for.
+    pub fn set_timer_delays(
+        &mut self,
+        endorsement_delay: Duration,
+        min_delay: Duration,
+        delay_step: Duration,
+        max_delay: Duration,
+    ) {
+        self.timer.endorsement_delay = endorsement_delay;
+        self.timer.min_delay = min_delay;
+        self.timer.delay_step = delay_step;
+        self.timer.max_delay = max_delay;
+    }
+
     #[cfg(feature = "test_features")]
     pub fn adv_disable(&mut self) {
         self.threshold_mode = DoomslugThresholdMode::NoApprovals
@@ -680,10 +704,7 @@ impl Doomslug {
                             }
                             true
                         } else {
-                            let delay = self.timer.get_delay(
-                                self.timer.height.saturating_sub(self.largest_final_height),
-                            ) / 6;
-
+                            let delay = self.missing_chunks_delay();
                             let ready = now > when + delay;
                             if log_block_production_info {
                                 if ready {
@@ -703,6 +724,38 @@ impl Doomslug {
             false
         }
     }
+
+    /// Same as `ready_to_produce_block`, but read-only: doesn't record the time at which the
+    /// approvals threshold was passed, nor logs, so the readiness can be reported without
+    /// affecting it.
+    pub fn is_ready_to_produce_block(
+        &self,
+        now: Instant,
+        target_height: BlockHeight,
+        has_enough_chunks: bool,
+    ) -> bool {
+        let hash_or_height =
+            ApprovalInner::new(&self.tip.block_hash, self.tip.height, target_height);
+        let approval_tracker = match self
+            .approval_tracking
+            .get(&target_height)
+            .and_then(|trackers| trackers.approval_trackers.get(&hash_or_height))
+        {
+            Some(approval_tracker) => approval_tracker,
+            None => return false,
+        };
+        match approval_tracker.peek_block_production_readiness(now) {
+            DoomslugBlockProductionReadiness::NotReady => false,
+            DoomslugBlockProductionReadiness::ReadySince(when) => {
+                has_enough_chunks || now > when + self.missing_chunks_delay()
+            }
+        }
+    }
+
+    /// How long to wait for the missing chunks after collecting enough approvals.
+    fn missing_chunks_delay(&self) -> Duration {
+        self.timer.get_delay(self.timer.height.saturating_sub(self.largest_final_height)) / 6
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,141 @@
+//! Recovery from a head block with partially written data.
+//!
+//! If the node crashes while saving a block, it may restart with a head whose data is incomplete:
+//! the block body, the chunks it includes or the results of applying them may be missing. Such a
+//! node cannot produce or process the next blocks. On startup the head is checked, and if its data
+//! is incomplete, the head is rolled back to the closest ancestor whose data is complete. The data
+//! of the rolled back blocks is removed, so that they are downloaded and applied again by the
+//! regular block sync. The flat storage deltas of the rolled back blocks are removed as well, and
+//! the flat storage of a shard whose flat head has been rolled back is recreated from scratch.
+use crate::store::GCMode;
+use crate::types::RuntimeWithEpochManagerAdapter;
+use crate::{ChainStore, ChainStoreAccess, Error};
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+use near_primitives::utils::get_block_shard_id;
+use near_store::flat::{store_helper, FlatStorageStatus};
+use near_store::DBCol;
+use tracing::{info, warn};
+
+/// Returns the description of the data of the block which is missing in the store, if any.
+/// Only the shards tracked by the node are checked.
+fn find_missing_data(
+    chain_store: &ChainStore,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    me: Option<&AccountId>,
+    block_hash: &CryptoHash,
+) -> Result<Option<String>, Error> {
+    let block = match chain_store.get_block(block_hash) {
+        Ok(block) => block,
+        Err(Error::DBNotFoundErr(_)) => return Ok(Some("block body".to_string())),
+        Err(err) => return Err(err),
+    };
+    let header = block.header();
+    for chunk_header in block.chunks().iter() {
+        let shard_id = chunk_header.shard_id();
+        if !runtime_adapter.cares_about_shard(me, header.prev_hash(), shard_id, true) {
+            continue;
+        }
+        if chunk_header.height_included() == header.height() {
+            if !chain_store.chunk_exists(&chunk_header.chunk_hash())? {
+                return Ok(Some(format!("chunk of shard {shard_id}")));
+            }
+            let key = get_block_shard_id(block_hash, shard_id);
+            if !chain_store.store().exists(DBCol::OutcomeIds, &key)? {
+                return Ok(Some(format!("outcomes of shard {shard_id}")));
+            }
+        }
+        let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, header.epoch_id())?;
+        match chain_store.get_chunk_extra(block_hash, &shard_uid) {
+            Ok(_) => {}
+            Err(Error::DBNotFoundErr(_)) => {
+                return Ok(Some(format!("chunk extra of shard {shard_id}")))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+/// Rolls the head back to the closest block whose data is complete and returns the new head.
+/// The head is never rolled back below the final head or the tail: the blocks there are assumed
+/// to be complete, as the node could not have moved past them otherwise.
+pub(crate) fn recover_head(
+    chain_store: &mut ChainStore,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    me: Option<&AccountId>,
+) -> Result<Tip, Error> {
+    let head = chain_store.head()?;
+    let min_height = std::cmp::max(chain_store.final_head()?.height, chain_store.tail()?);
+    let mut tip = head.clone();
+    let mut rolled_back = vec![];
+    while tip.height > min_height {
+        let missing =
+            match find_missing_data(chain_store, runtime_adapter, me, &tip.last_block_hash)? {
+                Some(missing) => missing,
+                None => break,
+            };
+        warn!(target: "chain", height = tip.height, block_hash = %tip.last_block_hash, %missing, "Init: head block data is incomplete, rolling it back");
+        rolled_back.push(tip.last_block_hash);
+        tip = Tip::from_header(&chain_store.get_block_header(&tip.prev_block_hash)?);
+    }
+    if rolled_back.is_empty() {
+        return Ok(head);
+    }
+
+    for block_hash in &rolled_back {
+        let epoch_id = chain_store.get_block_header(block_hash)?.epoch_id().clone();
+        let mut flat_store_update = chain_store.store().store_update();
+        if cfg!(feature = "protocol_feature_flat_state") {
+            for shard_id in 0..runtime_adapter.num_shards(&epoch_id)? {
+                let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, &epoch_id)?;
+                store_helper::remove_delta(&mut flat_store_update, shard_uid, *block_hash);
+            }
+        }
+        let mut store_update = chain_store.store_update();
+        // The blocks without a body have no other data to remove, as it is saved together.
+        if store_update.block_exists(block_hash)? {
+            store_update.clear_block_data(
+                runtime_adapter,
+                *block_hash,
+                GCMode::Fork(runtime_adapter.get_tries()),
+            )?;
+        }
+        store_update.merge(flat_store_update);
+        store_update.commit()?;
+    }
+    let mut store_update = chain_store.store_update();
+    store_update.save_body_head(&tip)?;
+    store_update.commit()?;
+    recover_flat_storage(runtime_adapter, me, &tip)?;
+    info!(target: "chain", from_height = head.height, from = %head.last_block_hash, to_height = tip.height, to = %tip.last_block_hash, rolled_back = ?rolled_back, "Init: rolled back the head, the blocks will be synced again");
+    Ok(tip)
+}
+
+/// The flat head follows the final blocks, so it is not expected to be above the recovered head.
+/// If it is anyway, the flat state doesn't match the head, and the flat storage of the shard is
+/// removed, so that it is created again in the background.
+fn recover_flat_storage(
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    me: Option<&AccountId>,
+    tip: &Tip,
+) -> Result<(), Error> {
+    for shard_id in 0..runtime_adapter.num_shards(&tip.epoch_id)? {
+        if !runtime_adapter.cares_about_shard(me, &tip.prev_block_hash, shard_id, true) {
+            continue;
+        }
+        let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, &tip.epoch_id)?;
+        let flat_head = match runtime_adapter.get_flat_storage_status(shard_uid) {
+            FlatStorageStatus::Ready(status) => status.flat_head,
+            _ => continue,
+        };
+        if flat_head.height <= tip.height {
+            continue;
+        }
+        warn!(target: "chain", shard_id, flat_head_height = flat_head.height, flat_head = %flat_head.hash, "Init: flat head is above the recovered head, removing the flat storage");
+        runtime_adapter.create_flat_storage_for_shard(shard_uid);
+        runtime_adapter.remove_flat_storage_for_shard(shard_uid, &tip.epoch_id)?;
+    }
+    Ok(())
+}
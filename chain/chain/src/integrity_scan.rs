@@ -0,0 +1,211 @@
+//! Re-verification of the recent chain data stored on disk.
+//!
+//! Blocks and chunks are validated once, when they are received. If the data gets corrupted on
+//! disk afterwards (bit-rot, partially written records), nothing notices until the node uses it
+//! again, e.g. to serve it to peers or to build on top of it, at which point the corruption
+//! surfaces as a hard to diagnose consensus failure. The integrity scan walks back from the head
+//! and recomputes the merkle roots of the stored blocks, chunks and execution outcomes, so that
+//! such corruption is reported early.
+use crate::types::{ApplyTransactionResult, RuntimeWithEpochManagerAdapter};
+use crate::validate::validate_chunk_proofs;
+use crate::{metrics, ChainStore, ChainStoreAccess, Error};
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ShardChunk;
+use near_primitives::transaction::ExecutionOutcomeWithId;
+use near_primitives::types::{NumBlocks, ShardId};
+use near_store::DBCol;
+
+/// Kind of the data found to be corrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum CorruptedData {
+    Block,
+    Chunk,
+    Outcomes,
+}
+
+/// Corrupted data detected by the integrity scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataCorruption {
+    pub kind: CorruptedData,
+    pub block_hash: CryptoHash,
+    pub details: String,
+}
+
+impl std::fmt::Display for DataCorruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} data of block {} is corrupted: {}",
+            self.kind.as_ref(),
+            self.block_hash,
+            self.details
+        )
+    }
+}
+
+/// Result of a single integrity scan.
+#[derive(Debug, Default)]
+pub struct IntegrityScanResult {
+    /// Number of blocks which were verified.
+    pub num_blocks: NumBlocks,
+    pub corruptions: Vec<DataCorruption>,
+}
+
+/// Verifies up to `num_blocks` blocks of the canonical chain, counting back from the head,
+/// together with the chunks and outcomes of the shards stored locally.
+/// The scan stops early if it reaches the tail of the chain.
+pub fn scan_recent_blocks(
+    chain_store: &ChainStore,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    num_blocks: NumBlocks,
+) -> Result<IntegrityScanResult, Error> {
+    let mut result = IntegrityScanResult::default();
+    let genesis_height = chain_store.get_genesis_height();
+    let mut block_hash = chain_store.head()?.last_block_hash;
+    while result.num_blocks < num_blocks {
+        let header = match chain_store.get_block_header(&block_hash) {
+            Ok(header) => header,
+            // Garbage collected.
+            Err(Error::DBNotFoundErr(_)) => break,
+            Err(err) => {
+                result.corruptions.push(DataCorruption {
+                    kind: CorruptedData::Block,
+                    block_hash,
+                    details: format!("failed to read block header: {err}"),
+                });
+                break;
+            }
+        };
+        let height = header.height();
+        if header.hash() != &block_hash {
+            result.corruptions.push(DataCorruption {
+                kind: CorruptedData::Block,
+                block_hash,
+                details: format!("header at height {height} has hash {}", header.hash()),
+            });
+        }
+        match chain_store.get_block(&block_hash) {
+            // The genesis block is built from the genesis config rather than received, so it
+            // doesn't pass the validity checks of the other blocks.
+            Ok(_) if height <= genesis_height => {}
+            Ok(block) => scan_block(
+                chain_store,
+                runtime_adapter,
+                block_hash,
+                &block,
+                &mut result.corruptions,
+            )?,
+            Err(Error::DBNotFoundErr(_)) => break,
+            Err(err) => result.corruptions.push(DataCorruption {
+                kind: CorruptedData::Block,
+                block_hash,
+                details: format!("failed to read block at height {height}: {err}"),
+            }),
+        }
+        metrics::INTEGRITY_SCAN_BLOCKS_CHECKED.inc();
+        result.num_blocks += 1;
+        if height <= genesis_height {
+            break;
+        }
+        block_hash = *header.prev_hash();
+    }
+    for corruption in &result.corruptions {
+        metrics::INTEGRITY_SCAN_CORRUPTIONS.with_label_values(&[corruption.kind.as_ref()]).inc();
+    }
+    Ok(result)
+}
+
+fn scan_block(
+    chain_store: &ChainStore,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    expected_hash: CryptoHash,
+    block: &Block,
+    corruptions: &mut Vec<DataCorruption>,
+) -> Result<(), Error> {
+    let block_hash = *block.hash();
+    let height = block.header().height();
+    let mut report = |kind, details: String| {
+        corruptions.push(DataCorruption {
+            kind,
+            block_hash: expected_hash,
+            details: format!("at height {height}: {details}"),
+        });
+    };
+
+    if block.hash() != &expected_hash {
+        report(CorruptedData::Block, format!("block has hash {block_hash}"));
+        return Ok(());
+    }
+
+    if let Err(err) = block.check_validity() {
+        report(CorruptedData::Block, format!("{err:?}"));
+        // The chunk headers can't be trusted, so there is no point in checking the chunks.
+        return Ok(());
+    }
+
+    for chunk_header in block.chunks().iter() {
+        // Old chunks were verified when scanning the block which included them.
+        if chunk_header.height_included() != height {
+            continue;
+        }
+        let shard_id = chunk_header.shard_id();
+        // Reading the store directly, as `get_chunk` doesn't distinguish missing chunks from
+        // the ones which failed to deserialize.
+        let chunk_hash = chunk_header.chunk_hash();
+        match chain_store.store().get_ser::<ShardChunk>(DBCol::Chunks, chunk_hash.as_ref()) {
+            Ok(Some(chunk)) => {
+                if chunk.chunk_hash() != chunk_hash
+                    || !validate_chunk_proofs(&chunk, runtime_adapter)?
+                {
+                    report(CorruptedData::Chunk, format!("chunk of shard {shard_id}"));
+                }
+            }
+            // The shard is not tracked.
+            Ok(None) => continue,
+            Err(err) => {
+                report(CorruptedData::Chunk, format!("failed to read chunk: {err}"));
+                continue;
+            }
+        }
+
+        let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, block.header().epoch_id())?;
+        let chunk_extra = match chain_store.get_chunk_extra(&block_hash, &shard_uid) {
+            Ok(chunk_extra) => chunk_extra,
+            // The chunk hasn't been applied, e.g. because the shard is being caught up.
+            Err(Error::DBNotFoundErr(_)) => continue,
+            Err(err) => {
+                report(CorruptedData::Outcomes, format!("failed to read chunk extra: {err}"));
+                continue;
+            }
+        };
+        let outcomes = match get_outcomes(chain_store, &block_hash, shard_id) {
+            Ok(outcomes) => outcomes,
+            Err(err) => {
+                report(CorruptedData::Outcomes, format!("failed to read outcomes: {err}"));
+                continue;
+            }
+        };
+        let outcome_root = ApplyTransactionResult::compute_outcomes_proof(&outcomes).0;
+        if &outcome_root != chunk_extra.outcome_root() {
+            report(CorruptedData::Outcomes, format!("outcome root of shard {shard_id}"));
+        }
+    }
+    Ok(())
+}
+
+fn get_outcomes(
+    chain_store: &ChainStore,
+    block_hash: &CryptoHash,
+    shard_id: ShardId,
+) -> Result<Vec<ExecutionOutcomeWithId>, Error> {
+    let mut outcomes = vec![];
+    for id in chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)? {
+        let outcome = chain_store
+            .get_outcome_by_id_and_block_hash(&id, block_hash)?
+            .ok_or_else(|| Error::DBNotFoundErr(format!("outcome {id}")))?;
+        outcomes.push(ExecutionOutcomeWithId { id, outcome: outcome.outcome });
+    }
+    Ok(outcomes)
+}
@@ -17,6 +17,8 @@ pub mod chunks_store;
 pub mod crypto_hash_timer;
 mod doomslug;
 pub mod flat_storage_creator;
+mod head_recovery;
+pub mod integrity_scan;
 mod lightclient;
 mod metrics;
 pub mod migrations;
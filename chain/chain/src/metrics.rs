@@ -1,6 +1,7 @@
 use near_o11y::metrics::{
-    exponential_buckets, try_create_histogram, try_create_histogram_vec, try_create_int_counter,
-    try_create_int_gauge, Histogram, HistogramVec, IntCounter, IntGauge,
+    exponential_buckets, try_create_histogram, try_create_histogram_vec,
+    try_create_histogram_with_buckets, try_create_int_counter, try_create_int_counter_vec,
+    try_create_int_gauge, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
 };
 use once_cell::sync::Lazy;
 
@@ -28,6 +29,14 @@ pub static APPLYING_CHUNKS_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static BLOCK_APPLY_CHUNKS_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_block_apply_chunks_time",
+        "Time taken to apply all the chunks of a block, which are applied in parallel",
+        exponential_buckets(0.001, 1.6, 20).unwrap(),
+    )
+    .unwrap()
+});
 pub static BLOCK_PREPROCESSING_TIME: Lazy<Histogram> = Lazy::new(|| {
     try_create_histogram("near_block_preprocessing_time", "Time taken to preprocess blocks, only include the time when the preprocessing is successful")
         .unwrap()
@@ -110,3 +119,25 @@ pub static STATE_PART_ELAPSED: Lazy<HistogramVec> = Lazy::new(|| {
 pub static NUM_INVALID_BLOCKS: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_num_invalid_blocks", "Number of invalid blocks").unwrap()
 });
+pub static DEEP_REORGS_REFUSED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_deep_reorgs_refused_total",
+        "Number of times the head wasn't switched to a fork because it reverts more blocks than max_reorg_depth",
+    )
+    .unwrap()
+});
+pub static INTEGRITY_SCAN_BLOCKS_CHECKED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_integrity_scan_blocks_checked_total",
+        "Number of blocks re-verified by the background integrity scan",
+    )
+    .unwrap()
+});
+pub static INTEGRITY_SCAN_CORRUPTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_integrity_scan_corruptions_total",
+        "Number of corrupted records found by the background integrity scan, by kind of data",
+        &["kind"],
+    )
+    .unwrap()
+});
@@ -28,15 +28,15 @@ use near_primitives::transaction::{
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
-    BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId, StateChanges,
-    StateChangesExt, StateChangesForSplitStates, StateChangesKinds, StateChangesKindsExt,
-    StateChangesRequest,
+    AccountId, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId,
+    StateChanges, StateChangesExt, StateChangesForSplitStates, StateChangesKinds,
+    StateChangesKindsExt, StateChangesRequest,
 };
 use near_primitives::utils::{
     get_block_shard_id, get_outcome_id_block_hash, get_outcome_id_block_hash_rev, index_to_bytes,
     to_timestamp,
 };
-use near_primitives::views::LightClientBlockView;
+use near_primitives::views::{AccountActivityView, LightClientBlockView};
 use near_store::{
     DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, WrappedTrieChanges, CHUNK_TAIL_KEY,
     FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
@@ -365,6 +365,30 @@ pub struct ChainStore {
     /// - archive is true, cold_store is configured and migration to split_storage is finished - node
     /// working in split storage mode needs trie changes in order to do garbage collection on hot.
     save_trie_changes: bool,
+    /// Whether to maintain the index of the outcomes by the executing account.
+    index_account_activity: bool,
+}
+
+/// Key of DBCol::AccountActivity. The account id is followed by a separator, which cannot be
+/// part of an account id, so that the rows of an account don't share a prefix with the rows of
+/// the accounts whose ids start with its id. The height is stored inverted, so that the rows of
+/// an account are ordered latest first and the most recent ones are read without a full scan.
+fn get_account_activity_key(
+    account_id: &AccountId,
+    height: BlockHeight,
+    outcome_id: &CryptoHash,
+) -> Vec<u8> {
+    let mut key = get_account_activity_prefix(account_id);
+    key.extend_from_slice(&(!height).to_be_bytes());
+    key.extend_from_slice(outcome_id.as_ref());
+    key
+}
+
+fn get_account_activity_prefix(account_id: &AccountId) -> Vec<u8> {
+    let mut key = Vec::with_capacity(account_id.len() + 1 + 8 + 32);
+    key.extend_from_slice(account_id.as_bytes());
+    key.push(b':');
+    key
 }
 
 fn option_to_not_found<T, F>(res: io::Result<Option<T>>, field_name: F) -> Result<T, Error>
@@ -407,9 +431,16 @@ impl ChainStore {
             block_ordinal_to_hash: CellLruCache::new(CACHE_SIZE),
             processed_block_heights: CellLruCache::new(CACHE_SIZE),
             save_trie_changes,
+            index_account_activity: false,
         }
     }
 
+    /// Enables maintaining the index of the outcomes by the executing account,
+    /// see `get_account_activity`.
+    pub fn set_index_account_activity(&mut self, enabled: bool) {
+        self.index_account_activity = enabled;
+    }
+
     pub fn new_read_only_chunks_store(&self) -> ReadOnlyChunksStore {
         ReadOnlyChunksStore::new(self.store.clone())
     }
@@ -607,6 +638,32 @@ impl ChainStore {
         )?)
     }
 
+    /// Returns the most recent outcomes executed by the account, latest first.
+    /// Only the outcomes of the shards tracked by this node are indexed, and only if the
+    /// index is enabled.
+    pub fn get_account_activity(
+        &self,
+        account_id: &AccountId,
+        limit: usize,
+    ) -> Result<Vec<AccountActivityView>, Error> {
+        let prefix = get_account_activity_prefix(account_id);
+        let mut activity = vec![];
+        // Rows are ordered latest first, so only the first `limit` of them are read.
+        for item in
+            self.store.iter_prefix_ser::<CryptoHash>(DBCol::AccountActivity, &prefix).take(limit)
+        {
+            let (key, block_hash) = item?;
+            let rest = &key[prefix.len()..];
+            if rest.len() != 8 + 32 {
+                return Err(Error::Other(format!("Invalid AccountActivity key: {:?}", key)));
+            }
+            let block_height = !BlockHeight::from_be_bytes(rest[..8].try_into().unwrap());
+            let outcome_id = CryptoHash::try_from(&rest[8..]).unwrap();
+            activity.push(AccountActivityView { outcome_id, block_hash, block_height });
+        }
+        Ok(activity)
+    }
+
     /// Returns a vector of Outcome ids for given block and shard id
     pub fn get_outcomes_by_block_hash_and_shard_id(
         &self,
@@ -1182,6 +1239,8 @@ struct ChainStoreCacheUpdate {
     incoming_receipts: HashMap<(CryptoHash, ShardId), Arc<Vec<ReceiptProof>>>,
     outcomes: HashMap<(CryptoHash, CryptoHash), ExecutionOutcomeWithProof>,
     outcome_ids: HashMap<(CryptoHash, ShardId), Vec<CryptoHash>>,
+    /// (executor account, block hash, outcome id) of the outcomes to index.
+    account_activity: Vec<(AccountId, CryptoHash, CryptoHash)>,
     invalid_chunks: HashMap<ChunkHash, Arc<EncodedShardChunk>>,
     receipt_id_to_shard_id: HashMap<CryptoHash, ShardId>,
     transactions: HashMap<CryptoHash, Arc<SignedTransaction>>,
@@ -1836,6 +1895,13 @@ impl<'a> ChainStoreUpdate<'a> {
         let mut outcome_ids = Vec::with_capacity(outcomes.len());
         for (outcome_with_id, proof) in outcomes.into_iter().zip(proofs.into_iter()) {
             outcome_ids.push(outcome_with_id.id);
+            if self.chain_store.index_account_activity {
+                self.chain_store_cache_update.account_activity.push((
+                    outcome_with_id.outcome.executor_id.clone(),
+                    *block_hash,
+                    outcome_with_id.id,
+                ));
+            }
             self.chain_store_cache_update.outcomes.insert(
                 (outcome_with_id.id, *block_hash),
                 ExecutionOutcomeWithProof { outcome: outcome_with_id.outcome, proof },
@@ -2291,6 +2357,21 @@ impl<'a> ChainStoreUpdate<'a> {
             let outcome_ids =
                 self.chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
             for outcome_id in outcome_ids {
+                if self.chain_store.index_account_activity {
+                    if let Some(outcome) = self
+                        .chain_store
+                        .get_outcome_by_id_and_block_hash(&outcome_id, block_hash)?
+                    {
+                        self.gc_col(
+                            DBCol::AccountActivity,
+                            &get_account_activity_key(
+                                &outcome.outcome.executor_id,
+                                block.header().height(),
+                                &outcome_id,
+                            ),
+                        );
+                    }
+                }
                 self.gc_col(
                     DBCol::TransactionResultForBlock,
                     &get_outcome_id_block_hash(&outcome_id, block_hash),
@@ -2315,6 +2396,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::StateHeaders => {
                 store_update.delete(col, key);
             }
+            DBCol::AccountActivity => {
+                store_update.delete(col, key);
+            }
             DBCol::BlockHeader => {
                 // TODO(#3488) At the moment header sync needs block headers.
                 // However, we want to eventually garbage collect headers.
@@ -2414,6 +2498,12 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::BlockHeight  // block sync needs it + genesis should be accessible
             | DBCol::_Peers
             | DBCol::RecentOutboundConnections
+            | DBCol::RoutedMessageOutbox
+            | DBCol::BannedPeers
+            | DBCol::RoutingEdges
+            | DBCol::SigningJournal
+            | DBCol::DedupCache
+            | DBCol::MetricsSnapshot
             | DBCol::BlockMerkleTree
             | DBCol::AccountAnnouncements
             | DBCol::EpochLightClientBlocks
@@ -2749,6 +2839,16 @@ impl<'a> ChainStoreUpdate<'a> {
                 &ids,
             )?;
         }
+        for (account_id, block_hash, outcome_id) in
+            self.chain_store_cache_update.account_activity.iter()
+        {
+            let height = self.get_block_header(block_hash)?.height();
+            store_update.set_ser(
+                DBCol::AccountActivity,
+                &get_account_activity_key(account_id, height, outcome_id),
+                block_hash,
+            )?;
+        }
         for (receipt_id, shard_id) in self.chain_store_cache_update.receipt_id_to_shard_id.iter() {
             let data = shard_id.try_to_vec()?;
             store_update.increment_refcount(DBCol::ReceiptIdToShardId, receipt_id.as_ref(), &data);
@@ -2914,6 +3014,7 @@ impl<'a> ChainStoreUpdate<'a> {
 
             outcomes: _,
             outcome_ids: _,
+            account_activity: _,
         } = self.chain_store_cache_update;
         for (hash, block) in blocks {
             self.chain_store.blocks.put(hash.into(), block);
@@ -3005,6 +3106,7 @@ mod tests {
     use near_primitives::hash::hash;
     use near_primitives::test_utils::create_test_signer;
     use near_primitives::test_utils::TestBlockBuilder;
+    use near_primitives::transaction::{ExecutionOutcome, ExecutionOutcomeWithId};
     use near_primitives::types::{BlockHeight, EpochId, NumBlocks};
     use near_primitives::utils::index_to_bytes;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
@@ -3510,4 +3612,54 @@ mod tests {
             assert_eq!(store_update.chunk_tail().unwrap(), 0);
         }
     }
+
+    #[test]
+    fn test_account_activity_index() {
+        let mut chain = get_chain();
+        chain.mut_store().set_index_account_activity(true);
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let block = TestBlockBuilder::new(&genesis, signer).build();
+        let outcome = |id: &str, executor_id: &str| ExecutionOutcomeWithId {
+            id: hash(id.as_bytes()),
+            outcome: ExecutionOutcome {
+                executor_id: executor_id.parse().unwrap(),
+                ..Default::default()
+            },
+        };
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_block_header(block.header().clone()).unwrap();
+        store_update.save_outcomes_with_proofs(
+            genesis.hash(),
+            0,
+            vec![outcome("tx1", "alice"), outcome("tx2", "alice.near")],
+            vec![vec![]; 2],
+        );
+        store_update.save_outcomes_with_proofs(
+            block.hash(),
+            0,
+            vec![outcome("r1", "alice")],
+            vec![vec![]],
+        );
+        store_update.commit().unwrap();
+
+        let activity = |account_id: &str, limit| {
+            chain
+                .store()
+                .get_account_activity(&account_id.parse().unwrap(), limit)
+                .unwrap()
+                .into_iter()
+                .map(|a| (a.outcome_id, a.block_height))
+                .collect::<Vec<_>>()
+        };
+        let genesis_height = genesis.header().height();
+        let height = block.header().height();
+        assert_eq!(
+            activity("alice", 10),
+            vec![(hash(b"r1"), height), (hash(b"tx1"), genesis_height)]
+        );
+        assert_eq!(activity("alice", 1), vec![(hash(b"r1"), height)]);
+        assert_eq!(activity("alice.near", 10), vec![(hash(b"tx2"), genesis_height)]);
+        assert_eq!(activity("bob", 10), vec![]);
+    }
 }
@@ -0,0 +1,55 @@
+use crate::test_utils::KeyValueRuntime;
+use crate::types::{ChainConfig, ChainGenesis};
+use crate::{Chain, ChainStoreAccess, DoomslugThresholdMode};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::test_utils::{create_test_signer, TestBlockBuilder};
+use near_store::test_utils::create_test_store;
+use near_store::DBCol;
+use std::sync::Arc;
+
+#[test]
+fn test_recover_head() {
+    init_test_logger();
+    let chain_genesis = ChainGenesis { epoch_length: 1000, ..ChainGenesis::test() };
+    let runtime = KeyValueRuntime::new(create_test_store(), chain_genesis.epoch_length);
+    let new_chain = || {
+        let mut chain = Chain::new(
+            runtime.clone(),
+            &chain_genesis,
+            DoomslugThresholdMode::NoApprovals,
+            ChainConfig::test(),
+        )
+        .unwrap();
+        chain.recover_head(None).unwrap();
+        chain
+    };
+    let signer = Arc::new(create_test_signer("test"));
+    let mut chain = new_chain();
+    let mut blocks = vec![chain.genesis_block().clone()];
+    for _ in 1..6 {
+        let block = TestBlockBuilder::new(blocks.last().unwrap(), signer.clone()).build();
+        chain.process_block_test(&None, block.clone()).unwrap();
+        blocks.push(block);
+    }
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[5].hash());
+
+    tracing::debug!(target: "test", "a complete head is kept");
+    let chain = new_chain();
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[5].hash());
+
+    tracing::debug!(target: "test", "remove the body of the head block");
+    let store = chain.store().store().clone();
+    let mut store_update = store.store_update();
+    store_update.delete(DBCol::Block, blocks[5].hash().as_ref());
+    store_update.commit().unwrap();
+    drop(chain);
+
+    let mut chain = new_chain();
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[4].hash());
+    // The header is kept.
+    assert!(chain.get_block_header(blocks[5].hash()).is_ok());
+
+    tracing::debug!(target: "test", "the rolled back block is processed again");
+    chain.process_block_test(&None, blocks[5].clone()).unwrap();
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[5].hash());
+}
@@ -0,0 +1,48 @@
+use crate::integrity_scan::{scan_recent_blocks, CorruptedData};
+use crate::test_utils::setup;
+use crate::{ChainStore, ChainStoreAccess};
+use borsh::BorshSerialize;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::test_utils::TestBlockBuilder;
+use near_store::DBCol;
+
+#[test]
+fn test_integrity_scan() {
+    init_test_logger();
+    let (mut chain, runtime, signer) = setup();
+    let mut hashes = vec![*chain.genesis().hash()];
+    for _ in 1..6 {
+        let prev = chain.get_block(&chain.head().unwrap().last_block_hash).unwrap();
+        let block = TestBlockBuilder::new(&prev, signer.clone()).build();
+        hashes.push(*block.hash());
+        chain.process_block_test(&None, block).unwrap();
+    }
+    let store = chain.store().store().clone();
+    let genesis_height = chain.store().get_genesis_height();
+
+    let result =
+        scan_recent_blocks(&ChainStore::new(store.clone(), genesis_height, true), &*runtime, 3)
+            .unwrap();
+    assert_eq!(result.num_blocks, 3);
+    assert_eq!(result.corruptions, vec![]);
+
+    tracing::debug!(target: "test", "the scan stops at genesis");
+    let result =
+        scan_recent_blocks(&ChainStore::new(store.clone(), genesis_height, true), &*runtime, 100)
+            .unwrap();
+    assert_eq!(result.num_blocks, 6);
+    assert_eq!(result.corruptions, vec![]);
+
+    tracing::debug!(target: "test", "overwrite the block at height 3 with the block at height 2");
+    let block = chain.get_block(&hashes[2]).unwrap();
+    let mut store_update = store.store_update();
+    store_update.set_raw_bytes(DBCol::Block, hashes[3].as_ref(), &block.try_to_vec().unwrap());
+    store_update.commit().unwrap();
+
+    let result =
+        scan_recent_blocks(&ChainStore::new(store, genesis_height, true), &*runtime, 100).unwrap();
+    assert_eq!(result.num_blocks, 6);
+    assert_eq!(result.corruptions.len(), 1);
+    assert_eq!(result.corruptions[0].kind, CorruptedData::Block);
+    assert_eq!(result.corruptions[0].block_hash, hashes[3]);
+}
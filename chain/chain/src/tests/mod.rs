@@ -1,6 +1,8 @@
 mod challenges;
 mod doomslug;
 mod gc;
+mod head_recovery;
+mod integrity_scan;
 mod simple_chain;
 mod sync_chain;
 
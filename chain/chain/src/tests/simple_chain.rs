@@ -110,6 +110,7 @@ fn build_chain_with_orphans() {
         *last_block.header().next_bp_hash(),
         CryptoHash::default(),
         None,
+        None,
     );
     assert_matches!(chain.process_block_test(&None, block).unwrap_err(), Error::Orphan);
     assert_matches!(
@@ -250,6 +251,42 @@ fn blocks_at_height() {
     assert_eq!(chain.get_block_header_by_height(7).unwrap().hash(), &e_7_hash);
 }
 
+/// Checks that the head isn't switched to a fork reverting more than `max_reorg_depth` blocks,
+/// until the operator allows the fork.
+#[test]
+fn deep_reorg_guard() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    chain.max_reorg_depth = Some(1);
+    let genesis = chain.get_block_by_height(0).unwrap();
+    let b1 = TestBlockBuilder::new(&genesis, signer.clone()).height(1).build();
+    let b2 = TestBlockBuilder::new(&b1, signer.clone()).height(2).build();
+    let c3 = TestBlockBuilder::new(&b1, signer.clone()).height(3).build();
+    let d4 = TestBlockBuilder::new(&genesis, signer.clone()).height(4).build();
+    let d5 = TestBlockBuilder::new(&d4, signer.clone()).height(5).build();
+    let d6 = TestBlockBuilder::new(&d5, signer).height(6).build();
+    let c3_hash = *c3.hash();
+    let d4_hash = *d4.hash();
+    let d6_hash = *d6.hash();
+
+    chain.process_block_test(&None, b1).unwrap();
+    chain.process_block_test(&None, b2).unwrap();
+    // Reverts a single block.
+    chain.process_block_test(&None, c3).unwrap();
+    assert_eq!(chain.head().unwrap().last_block_hash, c3_hash);
+
+    // Reverts two blocks.
+    chain.process_block_test(&None, d4).unwrap();
+    chain.process_block_test(&None, d5).unwrap();
+    assert_eq!(chain.head().unwrap().last_block_hash, c3_hash);
+    assert_eq!(chain.get_block_header_by_height(3).unwrap().hash(), &c3_hash);
+
+    chain.allow_deep_reorg(d4_hash);
+    chain.process_block_test(&None, d6).unwrap();
+    assert_eq!(chain.head().unwrap().last_block_hash, d6_hash);
+    assert_eq!(chain.get_block_header_by_height(4).unwrap().hash(), &d4_hash);
+}
+
 #[test]
 fn next_blocks() {
     init_test_logger();
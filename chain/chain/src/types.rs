@@ -243,11 +243,24 @@ pub struct ChainConfig {
     /// Number of threads to execute background migration work.
     /// Currently used for flat storage background creation.
     pub background_migration_threads: usize,
+    /// If set, the head is not switched to a fork which reverts more than this many blocks of
+    /// the canonical chain, unless the operator allows it.
+    pub max_reorg_depth: Option<BlockHeightDelta>,
+    /// Whether to index the outcomes of the tracked shards by the executing account.
+    pub index_account_activity: bool,
+    /// Number of threads applying the chunks of a block in parallel, zero meaning one per CPU.
+    pub apply_chunks_threads: usize,
 }
 
 impl ChainConfig {
     pub fn test() -> Self {
-        Self { save_trie_changes: true, background_migration_threads: 1 }
+        Self {
+            save_trie_changes: true,
+            background_migration_threads: 1,
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
+        }
     }
 }
 
@@ -509,6 +522,32 @@ pub trait RuntimeAdapter: Send + Sync {
         request: &QueryRequest,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError>;
 
+    /// Same as `query`, but iterating over the state is abandoned with `QueryError::Cancelled`
+    /// once `is_cancelled` returns true.
+    fn query_with_cancellation(
+        &self,
+        shard_uid: ShardUId,
+        state_root: &StateRoot,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_id: &EpochId,
+        request: &QueryRequest,
+        _is_cancelled: &dyn Fn() -> bool,
+    ) -> Result<QueryResponse, near_chain_primitives::error::QueryError> {
+        self.query(
+            shard_uid,
+            state_root,
+            block_height,
+            block_timestamp,
+            prev_block_hash,
+            block_hash,
+            epoch_id,
+            request,
+        )
+    }
+
     /// Get the part of the state from given state root.
     /// `block_hash` is a block whose `prev_state_root` is `state_root`
     fn obtain_state_part(
@@ -0,0 +1,126 @@
+//! Data availability sampling of the chunks of the shards the node doesn't track.
+//!
+//! A node which doesn't track a shard never sees more than the header of its chunks, so it
+//! cannot tell whether the chunk producer withholds the chunk data. Requesting a few random
+//! parts of each chunk from their owners, via the regular partial chunk requests, is cheap, and
+//! if the data is withheld, some of the sampled parts are missing with high probability. The
+//! results are only reported via metrics, as an early warning about the health of the network.
+use crate::metrics;
+use near_chain_configs::ChunkAvailabilitySamplingConfig;
+use near_primitives::merkle::verify_path;
+use near_primitives::sharding::{ChunkHash, PartialEncodedChunkPart, ShardChunkHeader};
+use near_primitives::time;
+use near_primitives::types::{AccountId, ShardId};
+use rand::seq::IteratorRandom;
+use std::collections::{HashMap, HashSet};
+use tracing::debug;
+
+/// Number of the recently sampled chunks remembered, so that the headers of the chunks repeated
+/// in the following blocks are not sampled again.
+const SAMPLED_CHUNKS_CACHE_SIZE: usize = 1000;
+
+struct Sample {
+    shard_id: ShardId,
+    /// Parts which haven't arrived yet.
+    missing_parts: HashSet<u64>,
+    started: time::Instant,
+}
+
+pub(crate) struct AvailabilitySampler {
+    config: ChunkAvailabilitySamplingConfig,
+    pending: HashMap<ChunkHash, Sample>,
+    sampled: lru::LruCache<ChunkHash, ()>,
+}
+
+impl AvailabilitySampler {
+    pub fn new(config: ChunkAvailabilitySamplingConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            sampled: lru::LruCache::new(SAMPLED_CHUNKS_CACHE_SIZE),
+        }
+    }
+
+    /// Whether the chunk has been sampled already.
+    pub fn is_sampled(&self, chunk_hash: &ChunkHash) -> bool {
+        self.sampled.contains(chunk_hash)
+    }
+
+    /// Starts sampling the chunk. Chooses the parts to sample out of `parts`, given along with
+    /// their owners, and returns them.
+    pub fn start(
+        &mut self,
+        now: time::Instant,
+        header: &ShardChunkHeader,
+        parts: Vec<(u64, AccountId)>,
+    ) -> Vec<(u64, AccountId)> {
+        let chunk_hash = header.chunk_hash();
+        self.sampled.put(chunk_hash.clone(), ());
+        let parts = parts
+            .into_iter()
+            .choose_multiple(&mut rand::thread_rng(), self.config.num_parts as usize);
+        if !parts.is_empty() {
+            let missing_parts = parts.iter().map(|(part_ord, _)| *part_ord).collect();
+            self.pending.insert(
+                chunk_hash,
+                Sample { shard_id: header.shard_id(), missing_parts, started: now },
+            );
+        }
+        parts
+    }
+
+    /// Records the parts of a chunk received from the network. Only the parts proven against the
+    /// header of the chunk count as available.
+    pub fn process_parts(
+        &mut self,
+        now: time::Instant,
+        header: &ShardChunkHeader,
+        parts: &[PartialEncodedChunkPart],
+    ) {
+        let chunk_hash = header.chunk_hash();
+        let sample = match self.pending.get_mut(&chunk_hash) {
+            Some(sample) => sample,
+            None => return,
+        };
+        for part in parts {
+            if sample.missing_parts.contains(&part.part_ord)
+                && verify_path(header.encoded_merkle_root(), &part.merkle_proof, &part.part)
+            {
+                sample.missing_parts.remove(&part.part_ord);
+            }
+        }
+        if sample.missing_parts.is_empty() {
+            let shard_id = sample.shard_id.to_string();
+            metrics::CHUNK_AVAILABILITY_SAMPLE_LATENCY
+                .with_label_values(&[&shard_id])
+                .observe((now - sample.started).as_seconds_f64());
+            metrics::CHUNK_AVAILABILITY_SAMPLES.with_label_values(&[&shard_id, "available"]).inc();
+            self.pending.remove(&chunk_hash);
+        }
+    }
+
+    /// Concludes the samples whose parts haven't all arrived within the timeout as unavailable.
+    pub fn expire(&mut self, now: time::Instant) {
+        let timeout = self.config.timeout;
+        self.pending.retain(|chunk_hash, sample| {
+            if now - sample.started < timeout {
+                return true;
+            }
+            debug!(
+                target: "chunks",
+                ?chunk_hash,
+                shard_id = sample.shard_id,
+                missing_parts = ?sample.missing_parts,
+                "Sampled chunk parts didn't arrive in time",
+            );
+            let shard_id = sample.shard_id.to_string();
+            metrics::CHUNK_AVAILABILITY_SAMPLES
+                .with_label_values(&[&shard_id, "unavailable"])
+                .inc();
+            metrics::CHUNK_AVAILABILITY_MISSING_PARTS
+                .with_label_values(&[&shard_id])
+                .inc_by(sample.missing_parts.len() as u64);
+            false
+        });
+    }
+}
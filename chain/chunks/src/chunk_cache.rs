@@ -1,12 +1,19 @@
 use std::collections::{HashMap, HashSet};
 
+use borsh::BorshSerialize;
+use near_chain_configs::ChunkHorizonsConfig;
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunkPart, PartialEncodedChunkV2, ReceiptProof, ShardChunkHeader,
 };
+use near_primitives::time;
 use near_primitives::types::{BlockHeight, BlockHeightDelta, ShardId};
+use near_primitives::views::{CachedChunkView, PrevBlockChunkHeadersView};
 use std::collections::hash_map::Entry::Occupied;
-use tracing::warn;
+use tracing::{debug, warn};
+
+use crate::metrics;
 
 // This file implements EncodedChunksCache, which provides three main functionalities:
 // 1) It stores a map from a chunk hash to all the parts and receipts received so far for the chunk.
@@ -15,7 +22,12 @@ use tracing::warn;
 //    When a PartialEncodedChunk is received, the parts and receipts it contains are merged to the
 //    corresponding chunk entry in the map.
 //    Entries in the map are removed if the chunk is found to be invalid or the chunk goes out of
-//    horizon [chain_head_height - HEIGHT_HORIZON, chain_head_height + MAX_HEIGHTS_AHEAD]
+//    horizon [chain_head_height - height_horizon, chain_head_height + max_heights_ahead]
+//    (see `ChunkHorizonsConfig`). In adaptive mode, max_heights_ahead is widened based on
+//    the observed jitter of block arrival, so that nodes on high-latency links don't discard
+//    chunks which they will need once the corresponding block arrives.
+//    If the size caps are configured, the least recently updated incomplete entries are also
+//    removed once the cache holds too many bytes, or too many incomplete entries of a shard.
 // 2) It stores the set of incomplete chunks, indexed by the block hash of the previous block.
 //    A chunk always starts incomplete. It can be marked as complete through
 //    `mark_entry_complete`. A complete entry means the chunk has all parts and receipts needed.
@@ -23,11 +35,91 @@ use tracing::warn;
 //    This functionality is meant for block producers. When producing a block, the block producer
 //    will only include chunks in the block for which it has received the part it owns.
 //    Users of the data structure are responsible for adding chunk to this map at the right time.
+// Each entry also keeps track of how its parts were collected: when the first of them arrived,
+// how many requests were sent for them and where they came from. These are reported as
+// `ChunkCompletionStats` once the entry is marked as complete.
+
+/// Number of recently discarded chunks remembered, to detect them being requested later.
+const DISCARDED_CHUNKS_CACHE_SIZE: usize = 1000;
+/// Weight of the newest sample in the moving averages of `BlockArrivalJitter`.
+const JITTER_EMA_WEIGHT: f64 = 0.1;
+/// In adaptive mode, the front horizon is widened by this many block intervals
+/// per unit of the observed mean deviation of block arrival.
+const JITTER_HORIZON_MULTIPLIER: f64 = 4.0;
+
+/// Moving statistics of the time between the head height increases.
+#[derive(Default)]
+struct BlockArrivalJitter {
+    last_update: Option<time::Instant>,
+    /// Average time between consecutive heights, in seconds.
+    mean_interval: f64,
+    /// Average absolute difference between the observed and expected time of arrival
+    /// of the next head, in seconds.
+    mean_deviation: f64,
+}
 
-/// A chunk is out of horizon if its height + HEIGHT_HORIZON < largest_seen_height
-const HEIGHT_HORIZON: BlockHeightDelta = 1024;
-/// A chunk is out of horizon if its height > HEIGHT_HORIZON + largest_seen_height
-const MAX_HEIGHTS_AHEAD: BlockHeightDelta = 5;
+impl BlockArrivalJitter {
+    fn observe(&mut self, now: time::Instant, height_delta: BlockHeightDelta) {
+        let last_update = match self.last_update.replace(now) {
+            Some(last_update) => last_update,
+            None => return,
+        };
+        let elapsed = (now - last_update).as_seconds_f64().max(0.);
+        let interval = elapsed / height_delta as f64;
+        if self.mean_interval == 0. {
+            self.mean_interval = interval;
+            return;
+        }
+        let deviation = (elapsed - self.mean_interval * height_delta as f64).abs();
+        self.mean_deviation += JITTER_EMA_WEIGHT * (deviation - self.mean_deviation);
+        self.mean_interval += JITTER_EMA_WEIGHT * (interval - self.mean_interval);
+    }
+
+    /// Number of heights by which the front horizon should be widened to accommodate
+    /// for the observed jitter.
+    fn extra_heights(&self) -> BlockHeightDelta {
+        if self.mean_interval <= 0. {
+            return 0;
+        }
+        (JITTER_HORIZON_MULTIPLIER * self.mean_deviation / self.mean_interval).ceil() as u64
+    }
+}
+
+/// Where the parts and receipts merged into a cache entry came from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkPartSupplier {
+    /// Sent by the chunk producer, or produced by this node.
+    ChunkProducer,
+    /// Forwarded by another validator.
+    Forward,
+    /// Sent by the peer in response to a request.
+    Response(PeerId),
+}
+
+impl ChunkPartSupplier {
+    fn metric_label(&self) -> &'static str {
+        match self {
+            ChunkPartSupplier::ChunkProducer => "chunk_producer",
+            ChunkPartSupplier::Forward => "forward",
+            ChunkPartSupplier::Response(_) => "response",
+        }
+    }
+}
+
+/// How the parts and receipts of a chunk were collected, taken when the chunk became complete.
+#[derive(Clone, Debug)]
+pub struct ChunkCompletionStats {
+    pub chunk_hash: ChunkHash,
+    pub height_created: BlockHeight,
+    pub shard_id: ShardId,
+    /// Time from the first part or receipt being received to the chunk becoming complete;
+    /// None if the chunk became complete without receiving anything.
+    pub completion_time: Option<time::Duration>,
+    /// Number of the requests sent for the parts and receipts of the chunk.
+    pub num_requests: u32,
+    /// Number of the previously unknown parts supplied by each source.
+    pub suppliers: Vec<(ChunkPartSupplier, usize)>,
+}
 
 /// EncodedChunksCacheEntry stores the consolidated parts and receipts received for a chunk
 /// When a PartialEncodedChunk is received, it can be merged to the existing EncodedChunksCacheEntry
@@ -46,15 +138,27 @@ pub struct EncodedChunksCacheEntry {
     /// validated again to make sure they are fully validated.
     /// See comments in `validate_chunk_header` for more context on partial vs full validation
     pub header_fully_validated: bool,
+    /// Size of the parts and receipts stored in the entry.
+    size_bytes: u64,
+    /// When the first part or receipt was merged into the entry.
+    first_part_received: Option<time::Instant>,
+    /// Number of the requests sent for the parts and receipts of the chunk.
+    num_requests: u32,
+    /// Number of the previously unknown parts supplied by each source.
+    suppliers: HashMap<ChunkPartSupplier, usize>,
 }
 
 pub struct EncodedChunksCache {
+    config: ChunkHorizonsConfig,
+    /// Current front horizon; differs from `config.max_heights_ahead` only in adaptive mode.
+    max_heights_ahead: BlockHeightDelta,
+    block_arrival_jitter: BlockArrivalJitter,
     /// Largest seen height from the head of the chain
     largest_seen_height: BlockHeight,
 
     /// A map from a chunk hash to the corresponding EncodedChunksCacheEntry of the chunk
     /// Entries in this map have height in
-    /// [chain_head_height - HEIGHT_HORIZON, chain_head_height + MAX_HEIGHTS_AHEAD]
+    /// [chain_head_height - height_horizon, chain_head_height + max_heights_ahead]
     encoded_chunks: HashMap<ChunkHash, EncodedChunksCacheEntry>,
     /// A map from a block height to chunk hashes at this height for all chunk stored in the cache
     /// This is used to gc chunks that are out of horizon
@@ -65,6 +169,16 @@ pub struct EncodedChunksCache {
     /// A map from a block hash to a set of incomplete chunks (does not have all parts and receipts yet)
     /// whose previous block is the block hash.
     incomplete_chunks: HashMap<CryptoHash, HashSet<ChunkHash>>,
+    /// Chunks recently discarded as out of horizon, used to detect whether the horizons
+    /// are too narrow: such chunks end up being requested once their block arrives.
+    discarded_chunks: lru::LruCache<ChunkHash, ()>,
+    /// Incomplete chunks with their shards, ordered by the time of the last update.
+    /// Used to pick the entries to remove once the size caps are exceeded.
+    incomplete_lru: lru::LruCache<ChunkHash, ShardId>,
+    /// Number of incomplete chunks per shard.
+    incomplete_per_shard: HashMap<ShardId, usize>,
+    /// Total size of the parts and receipts stored in the cache.
+    total_bytes: u64,
 }
 
 impl EncodedChunksCacheEntry {
@@ -76,6 +190,10 @@ impl EncodedChunksCacheEntry {
             complete: false,
             ready_for_inclusion: false,
             header_fully_validated: false,
+            size_bytes: 0,
+            first_part_received: None,
+            num_requests: 0,
+            suppliers: HashMap::new(),
         }
     }
 
@@ -90,26 +208,37 @@ impl EncodedChunksCacheEntry {
             let part_ord = part_info.part_ord;
             self.parts.entry(part_ord).or_insert_with(|| {
                 previously_missing_part_ords.insert(part_ord);
+                self.size_bytes += part_info.part.len() as u64;
                 part_info.clone()
             });
         }
 
         for receipt in partial_encoded_chunk.receipts.iter() {
             let shard_id = receipt.1.to_shard_id;
-            self.receipts.entry(shard_id).or_insert_with(|| receipt.clone());
+            self.receipts.entry(shard_id).or_insert_with(|| {
+                self.size_bytes += receipt.try_to_vec().map_or(0, |data| data.len() as u64);
+                receipt.clone()
+            });
         }
         previously_missing_part_ords
     }
 }
 
 impl EncodedChunksCache {
-    pub fn new() -> Self {
+    pub fn new(config: ChunkHorizonsConfig) -> Self {
         EncodedChunksCache {
+            max_heights_ahead: config.max_heights_ahead,
+            config,
+            block_arrival_jitter: BlockArrivalJitter::default(),
             largest_seen_height: 0,
             encoded_chunks: HashMap::new(),
             height_map: HashMap::new(),
             height_to_shard_to_chunk: HashMap::new(),
             incomplete_chunks: HashMap::new(),
+            discarded_chunks: lru::LruCache::new(DISCARDED_CHUNKS_CACHE_SIZE),
+            incomplete_lru: lru::LruCache::unbounded(),
+            incomplete_per_shard: HashMap::new(),
+            total_bytes: 0,
         }
     }
 
@@ -117,14 +246,53 @@ impl EncodedChunksCache {
         self.encoded_chunks.get(chunk_hash)
     }
 
-    /// Mark an entry as complete, which means it has all parts and receipts needed
-    pub fn mark_entry_complete(&mut self, chunk_hash: &ChunkHash) {
+    /// Mark an entry as complete, which means it has all parts and receipts needed.
+    /// Returns the statistics of collecting the chunk, unless it was complete already.
+    pub fn mark_entry_complete(
+        &mut self,
+        chunk_hash: &ChunkHash,
+        now: time::Instant,
+    ) -> Option<ChunkCompletionStats> {
         if let Some(entry) = self.encoded_chunks.get_mut(chunk_hash) {
+            if entry.complete {
+                return None;
+            }
             entry.complete = true;
+            let stats = ChunkCompletionStats {
+                chunk_hash: chunk_hash.clone(),
+                height_created: entry.header.height_created(),
+                shard_id: entry.header.shard_id(),
+                completion_time: entry.first_part_received.map(|first| now - first),
+                num_requests: entry.num_requests,
+                suppliers: entry.suppliers.iter().map(|(s, parts)| (s.clone(), *parts)).collect(),
+            };
             let previous_block_hash = &entry.header.prev_block_hash().clone();
             self.remove_chunk_from_incomplete_chunks(previous_block_hash, chunk_hash);
+            let shard_label = stats.shard_id.to_string();
+            if let Some(completion_time) = stats.completion_time {
+                metrics::CHUNK_COMPLETION_TIME
+                    .with_label_values(&[&shard_label])
+                    .observe(completion_time.as_seconds_f64());
+            }
+            metrics::CHUNK_COMPLETION_REQUESTS
+                .with_label_values(&[&shard_label])
+                .observe(stats.num_requests as f64);
+            for (supplier, parts) in &stats.suppliers {
+                metrics::CHUNK_COMPLETION_PARTS
+                    .with_label_values(&[&shard_label, supplier.metric_label()])
+                    .inc_by(*parts as u64);
+            }
+            Some(stats)
         } else {
             warn!(target:"chunks", "cannot mark non-existent entry as complete {:?}", chunk_hash);
+            None
+        }
+    }
+
+    /// Records that `num_requests` requests were sent for the parts or receipts of the chunk.
+    pub fn record_requests(&mut self, chunk_hash: &ChunkHash, num_requests: u32) {
+        if let Some(entry) = self.encoded_chunks.get_mut(chunk_hash) {
+            entry.num_requests += num_requests;
         }
     }
 
@@ -144,9 +312,47 @@ impl EncodedChunksCache {
         self.incomplete_chunks.get(prev_block_hash)
     }
 
+    /// Number of the incomplete chunks in the cache, per shard.
+    pub fn num_incomplete_chunks(&self) -> HashMap<ShardId, usize> {
+        self.incomplete_per_shard.iter().filter(|(_, n)| **n > 0).map(|(k, n)| (*k, *n)).collect()
+    }
+
+    /// Summaries of the chunks in the cache, grouped by their previous block and ordered by
+    /// height, for the debug page.
+    pub fn chunk_headers_view(&self) -> Vec<PrevBlockChunkHeadersView> {
+        let mut by_prev_block: HashMap<CryptoHash, Vec<CachedChunkView>> = HashMap::new();
+        for (chunk_hash, entry) in &self.encoded_chunks {
+            by_prev_block.entry(*entry.header.prev_block_hash()).or_default().push(
+                CachedChunkView {
+                    chunk_hash: chunk_hash.clone(),
+                    height_created: entry.header.height_created(),
+                    shard_id: entry.header.shard_id(),
+                    num_parts: entry.parts.len(),
+                    num_receipts: entry.receipts.len(),
+                    complete: entry.complete,
+                    ready_for_inclusion: entry.ready_for_inclusion,
+                    header_fully_validated: entry.header_fully_validated,
+                },
+            );
+        }
+        let mut result: Vec<PrevBlockChunkHeadersView> = by_prev_block
+            .into_iter()
+            .map(|(prev_block_hash, mut chunks)| {
+                chunks.sort_by_key(|chunk| chunk.shard_id);
+                PrevBlockChunkHeadersView { prev_block_hash, chunks }
+            })
+            .collect();
+        result.sort_by_key(|view| {
+            view.chunks.iter().map(|chunk| chunk.height_created).min().unwrap_or_default()
+        });
+        result
+    }
+
     pub fn remove(&mut self, chunk_hash: &ChunkHash) -> Option<EncodedChunksCacheEntry> {
         if let Some(entry) = self.encoded_chunks.remove(chunk_hash) {
             self.remove_chunk_from_incomplete_chunks(entry.header.prev_block_hash(), chunk_hash);
+            self.total_bytes -= entry.size_bytes;
+            metrics::CHUNK_CACHE_SIZE_BYTES.set(self.total_bytes as i64);
             Some(entry)
         } else {
             None
@@ -166,6 +372,13 @@ impl EncodedChunksCache {
                 entry.remove();
             }
         }
+        if let Some(shard_id) = self.incomplete_lru.pop(chunk_hash) {
+            let count = self.incomplete_per_shard.entry(shard_id).or_default();
+            *count -= 1;
+            metrics::CHUNK_CACHE_INCOMPLETE_CHUNKS
+                .with_label_values(&[&shard_id.to_string()])
+                .set(*count as i64);
+        }
     }
 
     // Create an empty entry from the header and insert it if there is no entry for the chunk already
@@ -175,7 +388,8 @@ impl EncodedChunksCache {
         chunk_header: &ShardChunkHeader,
     ) -> &mut EncodedChunksCacheEntry {
         let chunk_hash = chunk_header.chunk_hash();
-        self.encoded_chunks.entry(chunk_hash).or_insert_with_key(|chunk_hash| {
+        if !self.encoded_chunks.contains_key(&chunk_hash) {
+            let shard_id = chunk_header.shard_id();
             self.height_map
                 .entry(chunk_header.height_created())
                 .or_default()
@@ -183,27 +397,94 @@ impl EncodedChunksCache {
             self.height_to_shard_to_chunk
                 .entry(chunk_header.height_created())
                 .or_default()
-                .insert(chunk_header.shard_id(), chunk_hash.clone());
+                .insert(shard_id, chunk_hash.clone());
             self.incomplete_chunks
                 .entry(*chunk_header.prev_block_hash())
                 .or_default()
                 .insert(chunk_hash.clone());
-            EncodedChunksCacheEntry::from_chunk_header(chunk_header.clone())
-        })
+            self.incomplete_lru.put(chunk_hash.clone(), shard_id);
+            let count = self.incomplete_per_shard.entry(shard_id).or_default();
+            *count += 1;
+            metrics::CHUNK_CACHE_INCOMPLETE_CHUNKS
+                .with_label_values(&[&shard_id.to_string()])
+                .set(*count as i64);
+            self.encoded_chunks.insert(
+                chunk_hash.clone(),
+                EncodedChunksCacheEntry::from_chunk_header(chunk_header.clone()),
+            );
+            self.evict_over_caps(shard_id, &chunk_hash);
+        }
+        self.encoded_chunks.get_mut(&chunk_hash).unwrap()
+    }
+
+    /// Removes the least recently updated incomplete entries, other than `keep`, until
+    /// the number of incomplete entries of `shard_id` and the total size of the cache
+    /// are within the caps.
+    fn evict_over_caps(&mut self, shard_id: ShardId, keep: &ChunkHash) {
+        if let Some(max_entries) = self.config.max_incomplete_chunks_per_shard {
+            while self.incomplete_per_shard.get(&shard_id).copied().unwrap_or(0) > max_entries {
+                let victim = self
+                    .incomplete_lru
+                    .iter()
+                    .rev()
+                    .find(|(chunk_hash, shard)| **shard == shard_id && *chunk_hash != keep)
+                    .map(|(chunk_hash, _)| chunk_hash.clone());
+                match victim {
+                    Some(chunk_hash) => self.evict(&chunk_hash, "shard_entries"),
+                    None => break,
+                }
+            }
+        }
+        if let Some(max_bytes) = self.config.max_cache_bytes {
+            while self.total_bytes > max_bytes {
+                let victim = self
+                    .incomplete_lru
+                    .iter()
+                    .rev()
+                    .map(|(chunk_hash, _)| chunk_hash)
+                    .find(|chunk_hash| *chunk_hash != keep)
+                    .cloned();
+                match victim {
+                    Some(chunk_hash) => self.evict(&chunk_hash, "bytes"),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    fn evict(&mut self, chunk_hash: &ChunkHash, reason: &'static str) {
+        debug!(target: "chunks", ?chunk_hash, reason, "Evicting incomplete chunk from the cache");
+        metrics::CHUNK_CACHE_EVICTED.with_label_values(&[reason]).inc();
+        self.remove(chunk_hash);
     }
 
     pub fn height_within_front_horizon(&self, height: BlockHeight) -> bool {
-        height >= self.largest_seen_height && height <= self.largest_seen_height + MAX_HEIGHTS_AHEAD
+        height >= self.largest_seen_height
+            && height <= self.largest_seen_height + self.max_heights_ahead
     }
 
     pub fn height_within_rear_horizon(&self, height: BlockHeight) -> bool {
-        height + HEIGHT_HORIZON >= self.largest_seen_height && height <= self.largest_seen_height
+        height + self.config.height_horizon >= self.largest_seen_height
+            && height <= self.largest_seen_height
     }
 
     pub fn height_within_horizon(&self, height: BlockHeight) -> bool {
         self.height_within_front_horizon(height) || self.height_within_rear_horizon(height)
     }
 
+    /// Records that a chunk at the given height was discarded because it was out of horizon.
+    pub fn record_discarded(&mut self, chunk_hash: ChunkHash, height: BlockHeight) {
+        let reason = if height > self.largest_seen_height { "ahead" } else { "behind" };
+        metrics::CHUNK_CACHE_DISCARDED.with_label_values(&[reason]).inc();
+        self.discarded_chunks.put(chunk_hash, ());
+    }
+
+    /// Returns whether the chunk has been recently discarded as out of horizon,
+    /// forgetting about it.
+    pub fn take_discarded(&mut self, chunk_hash: &ChunkHash) -> bool {
+        self.discarded_chunks.pop(chunk_hash).is_some()
+    }
+
     pub fn get_chunk_hash_by_height_and_shard(
         &self,
         height: BlockHeight,
@@ -217,9 +498,27 @@ impl EncodedChunksCache {
     pub fn merge_in_partial_encoded_chunk(
         &mut self,
         partial_encoded_chunk: &PartialEncodedChunkV2,
+        now: time::Instant,
+        supplier: ChunkPartSupplier,
     ) -> HashSet<u64> {
-        let entry = self.get_or_insert_from_header(&partial_encoded_chunk.header);
-        entry.merge_in_partial_encoded_chunk(partial_encoded_chunk)
+        let header = &partial_encoded_chunk.header;
+        let chunk_hash = header.chunk_hash();
+        let entry = self.get_or_insert_from_header(header);
+        let size_before = entry.size_bytes;
+        let previously_missing_part_ords =
+            entry.merge_in_partial_encoded_chunk(partial_encoded_chunk);
+        if !partial_encoded_chunk.parts.is_empty() || !partial_encoded_chunk.receipts.is_empty() {
+            entry.first_part_received.get_or_insert(now);
+        }
+        if !previously_missing_part_ords.is_empty() {
+            *entry.suppliers.entry(supplier).or_default() += previously_missing_part_ords.len();
+        }
+        self.total_bytes += entry.size_bytes - size_before;
+        metrics::CHUNK_CACHE_SIZE_BYTES.set(self.total_bytes as i64);
+        // Moves the chunk to the front of the eviction order, if it is incomplete.
+        self.incomplete_lru.get(&chunk_hash);
+        self.evict_over_caps(header.shard_id(), &chunk_hash);
+        previously_missing_part_ords
     }
 
     /// Remove a chunk from the cache if it is outside of horizon
@@ -233,15 +532,27 @@ impl EncodedChunksCache {
     }
 
     /// Update largest seen height and removes chunks from the cache that are outside of horizon
+    /// and, in adaptive mode, adjusts the front horizon to the jitter of the head updates.
     pub fn update_largest_seen_height<T>(
         &mut self,
+        now: time::Instant,
         new_height: BlockHeight,
         requested_chunks: &HashMap<ChunkHash, T>,
     ) {
         let old_largest_seen_height = self.largest_seen_height;
         self.largest_seen_height = new_height;
-        for height in old_largest_seen_height.saturating_sub(HEIGHT_HORIZON)
-            ..self.largest_seen_height.saturating_sub(HEIGHT_HORIZON)
+        if let Some(limit) = self.config.adaptive_max_heights_ahead {
+            if new_height > old_largest_seen_height {
+                self.block_arrival_jitter.observe(now, new_height - old_largest_seen_height);
+            }
+            let base = self.config.max_heights_ahead;
+            self.max_heights_ahead =
+                (base + self.block_arrival_jitter.extra_heights()).min(limit).max(base);
+        }
+        metrics::CHUNK_CACHE_MAX_HEIGHTS_AHEAD.set(self.max_heights_ahead as i64);
+        let height_horizon = self.config.height_horizon;
+        for height in old_largest_seen_height.saturating_sub(height_horizon)
+            ..self.largest_seen_height.saturating_sub(height_horizon)
         {
             if let Some(chunks_to_remove) = self.height_map.remove(&height) {
                 for chunk_hash in chunks_to_remove {
@@ -255,10 +566,11 @@ impl EncodedChunksCache {
     }
 
     /// Marks the chunk for inclusion in a block; returns true if we haven't already
-    /// called for this chunk. Requires that the chunk is already in the cache.
+    /// called for this chunk. Requires that the chunk is already in the cache. A chunk whose
+    /// header hasn't been fully validated is never marked.
     pub fn mark_chunk_for_inclusion(&mut self, chunk_hash: &ChunkHash) -> bool {
         let entry = self.encoded_chunks.get_mut(chunk_hash).unwrap();
-        if entry.ready_for_inclusion {
+        if entry.ready_for_inclusion || !entry.header_fully_validated {
             false
         } else {
             entry.ready_for_inclusion = true;
@@ -271,12 +583,17 @@ impl EncodedChunksCache {
 mod tests {
     use std::collections::{HashMap, HashSet};
 
+    use near_chain_configs::ChunkHorizonsConfig;
     use near_crypto::KeyType;
     use near_primitives::hash::CryptoHash;
-    use near_primitives::sharding::{PartialEncodedChunkV2, ShardChunkHeader, ShardChunkHeaderV2};
+    use near_primitives::network::PeerId;
+    use near_primitives::sharding::{
+        PartialEncodedChunkPart, PartialEncodedChunkV2, ShardChunkHeader, ShardChunkHeaderV2,
+    };
+    use near_primitives::time;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
 
-    use crate::chunk_cache::EncodedChunksCache;
+    use crate::chunk_cache::{ChunkPartSupplier, EncodedChunksCache};
     use crate::ChunkRequestInfo;
 
     fn create_chunk_header(height: u64, shard_id: u64) -> ShardChunkHeader {
@@ -302,39 +619,230 @@ mod tests {
 
     #[test]
     fn test_incomplete_chunks() {
-        let mut cache = EncodedChunksCache::new();
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        let clock = time::FakeClock::default();
         let header0 = create_chunk_header(1, 0);
         let header1 = create_chunk_header(1, 1);
         cache.get_or_insert_from_header(&header0);
-        cache.merge_in_partial_encoded_chunk(&PartialEncodedChunkV2 {
-            header: header1.clone(),
-            parts: vec![],
-            receipts: vec![],
-        });
+        cache.merge_in_partial_encoded_chunk(
+            &PartialEncodedChunkV2 { header: header1.clone(), parts: vec![], receipts: vec![] },
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
         assert_eq!(
             cache.get_incomplete_chunks(&CryptoHash::default()).unwrap(),
             &HashSet::from([header0.chunk_hash(), header1.chunk_hash()])
         );
-        cache.mark_entry_complete(&header0.chunk_hash());
+        cache.mark_entry_complete(&header0.chunk_hash(), clock.now());
         assert_eq!(
             cache.get_incomplete_chunks(&CryptoHash::default()).unwrap(),
             &[header1.chunk_hash()].into_iter().collect::<HashSet<_>>()
         );
-        cache.mark_entry_complete(&header1.chunk_hash());
+        cache.mark_entry_complete(&header1.chunk_hash(), clock.now());
         assert_eq!(cache.get_incomplete_chunks(&CryptoHash::default()), None);
     }
 
+    #[test]
+    fn test_inclusion_requires_validated_header() {
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        let header = create_chunk_header(1, 0);
+        cache.get_or_insert_from_header(&header);
+        assert!(!cache.mark_chunk_for_inclusion(&header.chunk_hash()));
+        assert!(!cache.get(&header.chunk_hash()).unwrap().ready_for_inclusion);
+
+        cache.mark_entry_validated(&header.chunk_hash());
+        assert!(cache.mark_chunk_for_inclusion(&header.chunk_hash()));
+        assert!(!cache.mark_chunk_for_inclusion(&header.chunk_hash()));
+    }
+
     #[test]
     fn test_cache_removal() {
-        let mut cache = EncodedChunksCache::new();
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        let clock = time::FakeClock::default();
         let header = create_chunk_header(1, 0);
         let partial_encoded_chunk =
             PartialEncodedChunkV2 { header: header, parts: vec![], receipts: vec![] };
-        cache.merge_in_partial_encoded_chunk(&partial_encoded_chunk);
+        cache.merge_in_partial_encoded_chunk(
+            &partial_encoded_chunk,
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
         assert!(!cache.height_map.is_empty());
 
-        cache.update_largest_seen_height::<ChunkRequestInfo>(2000, &HashMap::default());
+        cache.update_largest_seen_height::<ChunkRequestInfo>(
+            clock.now(),
+            2000,
+            &HashMap::default(),
+        );
         assert!(cache.encoded_chunks.is_empty());
         assert!(cache.height_map.is_empty());
     }
+
+    #[test]
+    fn test_adaptive_horizon() {
+        let config = ChunkHorizonsConfig {
+            max_heights_ahead: 5,
+            adaptive_max_heights_ahead: Some(10),
+            ..ChunkHorizonsConfig::default()
+        };
+        let mut cache = EncodedChunksCache::new(config);
+        let clock = time::FakeClock::default();
+        let requested = HashMap::<_, ChunkRequestInfo>::default();
+
+        // Blocks arriving at a steady pace don't widen the horizon.
+        for height in 1..=10 {
+            clock.advance(time::Duration::seconds(1));
+            cache.update_largest_seen_height(clock.now(), height, &requested);
+        }
+        assert!(cache.height_within_front_horizon(15));
+        assert!(!cache.height_within_front_horizon(16));
+
+        // Blocks arriving in bursts do, but not beyond the limit.
+        for height in (12..=100).step_by(2) {
+            clock.advance(time::Duration::seconds(if height % 4 == 0 { 1 } else { 7 }));
+            cache.update_largest_seen_height(clock.now(), height, &requested);
+        }
+        assert!(cache.height_within_front_horizon(106));
+        assert!(cache.height_within_front_horizon(110));
+        assert!(!cache.height_within_front_horizon(111));
+
+        // Without adaptive mode the horizon stays fixed.
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        cache.update_largest_seen_height(clock.now(), 100, &requested);
+        assert!(cache.height_within_front_horizon(105));
+        assert!(!cache.height_within_front_horizon(106));
+    }
+
+    fn partial_chunk(header: &ShardChunkHeader, part_size: usize) -> PartialEncodedChunkV2 {
+        PartialEncodedChunkV2 {
+            header: header.clone(),
+            parts: vec![PartialEncodedChunkPart {
+                part_ord: 0,
+                part: vec![0; part_size].into_boxed_slice(),
+                merkle_proof: vec![],
+            }],
+            receipts: vec![],
+        }
+    }
+
+    #[test]
+    fn test_max_incomplete_chunks_per_shard() {
+        let config = ChunkHorizonsConfig {
+            max_incomplete_chunks_per_shard: Some(2),
+            ..ChunkHorizonsConfig::default()
+        };
+        let mut cache = EncodedChunksCache::new(config);
+        let clock = time::FakeClock::default();
+        let headers: Vec<_> = (1..=3).map(|height| create_chunk_header(height, 0)).collect();
+        let other_shard = create_chunk_header(1, 1);
+        cache.get_or_insert_from_header(&other_shard);
+        cache.get_or_insert_from_header(&headers[0]);
+        cache.get_or_insert_from_header(&headers[1]);
+        // Updating the first chunk makes the second one the least recently updated.
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&headers[0], 10),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        cache.get_or_insert_from_header(&headers[2]);
+        assert!(cache.get(&headers[0].chunk_hash()).is_some());
+        assert!(cache.get(&headers[1].chunk_hash()).is_none());
+        assert!(cache.get(&headers[2].chunk_hash()).is_some());
+        assert!(cache.get(&other_shard.chunk_hash()).is_some());
+    }
+
+    #[test]
+    fn test_max_cache_bytes() {
+        let config =
+            ChunkHorizonsConfig { max_cache_bytes: Some(250), ..ChunkHorizonsConfig::default() };
+        let mut cache = EncodedChunksCache::new(config);
+        let clock = time::FakeClock::default();
+        let headers: Vec<_> = (1..=4).map(|height| create_chunk_header(height, 0)).collect();
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&headers[0], 100),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&headers[1], 100),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        cache.mark_entry_complete(&headers[1].chunk_hash(), clock.now());
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&headers[2], 100),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        assert_eq!(cache.total_bytes, 200);
+        assert!(cache.get(&headers[0].chunk_hash()).is_none());
+
+        // Complete chunks are never evicted, even if the cap can't be satisfied otherwise.
+        cache.mark_entry_complete(&headers[2].chunk_hash(), clock.now());
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&headers[3], 100),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        assert_eq!(cache.total_bytes, 300);
+        assert!(cache.get(&headers[1].chunk_hash()).is_some());
+        assert!(cache.get(&headers[2].chunk_hash()).is_some());
+        assert!(cache.get(&headers[3].chunk_hash()).is_some());
+    }
+
+    #[test]
+    fn test_completion_stats() {
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        let clock = time::FakeClock::default();
+        let header = create_chunk_header(1, 0);
+        let peer_id = PeerId::random();
+        cache.get_or_insert_from_header(&header);
+        cache.record_requests(&header.chunk_hash(), 2);
+
+        clock.advance(time::Duration::milliseconds(100));
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&header, 10),
+            clock.now(),
+            ChunkPartSupplier::Forward,
+        );
+        // Parts which are known already aren't attributed to their supplier.
+        cache.merge_in_partial_encoded_chunk(
+            &partial_chunk(&header, 10),
+            clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        let mut second_part = partial_chunk(&header, 10);
+        second_part.parts[0].part_ord = 1;
+        clock.advance(time::Duration::milliseconds(250));
+        cache.merge_in_partial_encoded_chunk(
+            &second_part,
+            clock.now(),
+            ChunkPartSupplier::Response(peer_id.clone()),
+        );
+
+        clock.advance(time::Duration::milliseconds(50));
+        let stats = cache.mark_entry_complete(&header.chunk_hash(), clock.now()).unwrap();
+        assert_eq!(stats.shard_id, 0);
+        assert_eq!(stats.completion_time, Some(time::Duration::milliseconds(300)));
+        assert_eq!(stats.num_requests, 2);
+        assert_eq!(
+            stats.suppliers.into_iter().collect::<HashMap<_, _>>(),
+            HashMap::from([
+                (ChunkPartSupplier::Forward, 1),
+                (ChunkPartSupplier::Response(peer_id), 1)
+            ])
+        );
+        // The stats are reported only once.
+        assert!(cache.mark_entry_complete(&header.chunk_hash(), clock.now()).is_none());
+    }
+
+    #[test]
+    fn test_discarded_chunks() {
+        let mut cache = EncodedChunksCache::new(ChunkHorizonsConfig::default());
+        let header = create_chunk_header(100, 0);
+        assert!(!cache.height_within_horizon(header.height_created()));
+        cache.record_discarded(header.chunk_hash(), header.height_created());
+        assert!(cache.take_discarded(&header.chunk_hash()));
+        assert!(!cache.take_discarded(&header.chunk_hash()));
+    }
 }
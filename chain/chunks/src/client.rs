@@ -1,13 +1,19 @@
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 use actix::Message;
 
-use near_pool::{PoolIteratorWrapper, TransactionPool};
+use crate::chunk_cache::ChunkCompletionStats;
+use crate::metrics;
+
+use near_chain_configs::{TransactionPoolLimits, TransactionPoolOrdering};
+use near_crypto::PublicKey;
+use near_pool::{InsertTransactionResult, PoolIteratorWrapper, TransactionPool};
 use near_primitives::{
     epoch_manager::RngSeed,
     sharding::{EncodedShardChunk, PartialEncodedChunk, ShardChunk, ShardChunkHeader},
     transaction::SignedTransaction,
-    types::{AccountId, ShardId},
+    types::{AccountId, Nonce, ShardId},
 };
 
 #[derive(Message, Debug)]
@@ -20,7 +26,12 @@ pub enum ShardsManagerResponse {
     /// shards that we do track. On the other hand if we are tracking the shard this
     /// chunk is in, then being complete does mean having the full chunk, in which
     /// case the shard_chunk is also provided.
-    ChunkCompleted { partial_chunk: PartialEncodedChunk, shard_chunk: Option<ShardChunk> },
+    /// The stats of collecting the chunk are provided the first time it is completed.
+    ChunkCompleted {
+        partial_chunk: PartialEncodedChunk,
+        shard_chunk: Option<ShardChunk>,
+        completion_stats: Option<ChunkCompletionStats>,
+    },
     /// Notifies the client that we have collected a full chunk but the chunk cannot
     /// be properly decoded.
     InvalidChunk(EncodedShardChunk),
@@ -30,35 +41,113 @@ pub enum ShardsManagerResponse {
     ChunkHeaderReadyForInclusion { chunk_header: ShardChunkHeader, chunk_producer: AccountId },
 }
 
+/// Number of transactions in the pool of each shard, shared with the components without access
+/// to the pool, like the view client.
+#[derive(Clone, Default)]
+pub struct TransactionPoolSizes(Arc<RwLock<HashMap<ShardId, u64>>>);
+
+impl TransactionPoolSizes {
+    pub fn get(&self, shard_id: ShardId) -> u64 {
+        self.0.read().unwrap().get(&shard_id).copied().unwrap_or(0)
+    }
+
+    fn set(&self, shard_id: ShardId, len: u64) {
+        self.0.write().unwrap().insert(shard_id, len);
+    }
+}
+
 pub struct ShardedTransactionPool {
     tx_pools: HashMap<ShardId, TransactionPool>,
 
+    /// Sizes of the pools, updated on every change.
+    sizes: TransactionPoolSizes,
+
     /// Useful to make tests deterministic and reproducible,
     /// while keeping the security of randomization of transactions in pool
     rng_seed: RngSeed,
+
+    /// Order in which the transactions are taken from the pools.
+    ordering: TransactionPoolOrdering,
+
+    /// Caps on each of the pools.
+    limits: TransactionPoolLimits,
 }
 
 impl ShardedTransactionPool {
-    pub fn new(rng_seed: RngSeed) -> Self {
+    pub fn new(
+        rng_seed: RngSeed,
+        ordering: TransactionPoolOrdering,
+        limits: TransactionPoolLimits,
+    ) -> Self {
         TransactionPool::init_metrics();
-        Self { tx_pools: HashMap::new(), rng_seed }
+        Self {
+            tx_pools: HashMap::new(),
+            sizes: TransactionPoolSizes::default(),
+            rng_seed,
+            ordering,
+            limits,
+        }
+    }
+
+    /// Makes the pool keep the given sizes up to date, from now on.
+    pub fn share_sizes(&mut self, sizes: TransactionPoolSizes) {
+        for (shard_id, pool) in &self.tx_pools {
+            sizes.set(*shard_id, pool.len() as u64);
+        }
+        self.sizes = sizes;
     }
 
     pub fn get_pool_iterator(&mut self, shard_id: ShardId) -> Option<PoolIteratorWrapper<'_>> {
         self.tx_pools.get_mut(&shard_id).map(|pool| pool.pool_iterator())
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
-        self.pool_for_shard(shard_id).insert_transaction(tx)
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let result = self.pool_for_shard(shard_id).insert_transaction(tx);
+        self.report_len(shard_id);
+        result
     }
 
     pub fn remove_transactions(&mut self, shard_id: ShardId, transactions: &[SignedTransaction]) {
         if let Some(pool) = self.tx_pools.get_mut(&shard_id) {
-            pool.remove_transactions(transactions)
+            pool.remove_transactions(transactions);
+            self.report_len(shard_id);
         }
     }
 
+    /// Nonces of the pooled transactions signed with the given access key, in increasing order.
+    pub fn nonces(
+        &self,
+        shard_id: ShardId,
+        account_id: &AccountId,
+        public_key: &PublicKey,
+    ) -> Vec<Nonce> {
+        self.tx_pools.get(&shard_id).map_or(vec![], |pool| pool.nonces(account_id, public_key))
+    }
+
+    /// Shards with a transaction pool, in increasing order.
+    pub fn shard_ids(&self) -> Vec<ShardId> {
+        let mut shard_ids: Vec<_> = self.tx_pools.keys().copied().collect();
+        shard_ids.sort();
+        shard_ids
+    }
+
+    /// Iterates over the transactions pooled for the shard, in no particular order.
+    pub fn transactions(&self, shard_id: ShardId) -> impl Iterator<Item = &SignedTransaction> {
+        self.tx_pools.get(&shard_id).into_iter().flat_map(|pool| pool.transactions())
+    }
+
+    fn report_len(&self, shard_id: ShardId) {
+        let len = self.tx_pools.get(&shard_id).map_or(0, |pool| pool.len());
+        self.sizes.set(shard_id, len as u64);
+        metrics::TRANSACTION_POOL_SIZE_BY_SHARD
+            .with_label_values(&[&shard_id.to_string()])
+            .set(len as i64);
+    }
+
     /// Computes a deterministic random seed for given `shard_id`.
     /// This seed is used to randomize the transaction pool.
     /// For better security we want the seed to different in each shard.
@@ -71,9 +160,13 @@ impl ShardedTransactionPool {
     }
 
     fn pool_for_shard(&mut self, shard_id: ShardId) -> &mut TransactionPool {
-        self.tx_pools
-            .entry(shard_id)
-            .or_insert_with(|| TransactionPool::new(Self::random_seed(&self.rng_seed, shard_id)))
+        self.tx_pools.entry(shard_id).or_insert_with(|| {
+            TransactionPool::new(
+                Self::random_seed(&self.rng_seed, shard_id),
+                self.ordering,
+                self.limits.clone(),
+            )
+        })
     }
 
     pub fn reintroduce_transactions(
@@ -82,17 +175,57 @@ impl ShardedTransactionPool {
         transactions: &[SignedTransaction],
     ) {
         self.pool_for_shard(shard_id).reintroduce_transactions(transactions.to_vec());
+        self.report_len(shard_id);
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use near_chain_configs::TransactionPoolLimits;
+    use near_crypto::{InMemorySigner, KeyType};
     use near_primitives::epoch_manager::RngSeed;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::SignedTransaction;
 
-    use crate::client::ShardedTransactionPool;
+    use crate::client::{ShardedTransactionPool, TransactionPoolSizes};
 
     const TEST_SEED: RngSeed = [3; 32];
 
+    #[test]
+    fn test_shared_sizes() {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        let txs: Vec<_> = (1..=3)
+            .map(|nonce| {
+                SignedTransaction::send_money(
+                    nonce,
+                    "test0".parse().unwrap(),
+                    "test1".parse().unwrap(),
+                    &signer,
+                    100,
+                    CryptoHash::default(),
+                )
+            })
+            .collect();
+        let mut pool = ShardedTransactionPool::new(
+            TEST_SEED,
+            Default::default(),
+            TransactionPoolLimits::default(),
+        );
+        pool.insert_transaction(0, txs[0].clone());
+
+        // The sizes of the pools existing before sharing are reported too.
+        let sizes = TransactionPoolSizes::default();
+        pool.share_sizes(sizes.clone());
+        assert_eq!(sizes.get(0), 1);
+        assert_eq!(sizes.get(1), 0);
+
+        pool.insert_transaction(0, txs[1].clone());
+        pool.insert_transaction(1, txs[2].clone());
+        assert_eq!((sizes.get(0), sizes.get(1)), (2, 1));
+        pool.remove_transactions(0, &txs[..2]);
+        assert_eq!((sizes.get(0), sizes.get(1)), (0, 1));
+    }
+
     #[test]
     fn test_random_seed_with_shard_id() {
         let seed0 = ShardedTransactionPool::random_seed(&TEST_SEED, 0);
@@ -0,0 +1,16 @@
+use actix::Message;
+use near_primitives::views::ShardsManagerView;
+
+// Debug requests that can be sent to the ShardsManagerActor by HTML pages, via GET.
+pub enum GetDebugStatus {
+    Chunks,
+}
+
+#[derive(actix::MessageResponse, Debug)]
+pub enum DebugStatus {
+    Chunks(ShardsManagerView),
+}
+
+impl Message for GetDebugStatus {
+    type Result = DebugStatus;
+}
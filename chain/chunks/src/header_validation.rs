@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use actix::Message;
+use near_async::messaging::Sender;
+use near_chain::RuntimeWithEpochManagerAdapter;
+use near_chunks_primitives::Error;
+use near_primitives::sharding::{ChunkHash, ShardChunkHeader};
+use near_primitives::types::ShardId;
+
+use crate::logic::validate_chunk_header_in_epoch;
+use crate::metrics;
+
+// This file implements ChunkHeaderValidationPool, which fully validates the headers of the
+// incomplete chunks once their previous block is accepted, off the ShardsManager thread.
+// The headers of each shard are validated by a separate task, in the order of submission,
+// and each result is sent back to the ShardsManager as soon as it is known, so that a shard
+// whose headers take long to validate doesn't delay the chunks of the other shards.
+
+/// The result of the full validation of a chunk header, sent back to the ShardsManager.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct ChunkHeaderValidated {
+    pub header: ShardChunkHeader,
+    pub result: Result<(), Error>,
+}
+
+pub(crate) struct ChunkHeaderValidationPool {
+    runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    thread_pool: rayon::ThreadPool,
+    results_sender: Sender<ChunkHeaderValidated>,
+    /// Chunks whose headers have been submitted and whose results haven't been processed yet.
+    pending: HashSet<ChunkHash>,
+}
+
+impl ChunkHeaderValidationPool {
+    pub fn new(
+        runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+        num_threads: usize,
+        results_sender: Sender<ChunkHeaderValidated>,
+    ) -> Self {
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("chunk_header_validation_{}", i))
+            .build()
+            .expect("failed to start the chunk header validation pool");
+        Self { runtime_adapter, thread_pool, results_sender, pending: HashSet::new() }
+    }
+
+    /// Submits the headers for validation. The previous blocks of the chunks must be accepted.
+    /// The headers which are being validated already are skipped.
+    pub fn submit(&mut self, headers: Vec<ShardChunkHeader>) {
+        let mut headers_by_shard: BTreeMap<ShardId, Vec<ShardChunkHeader>> = BTreeMap::new();
+        for header in headers {
+            if self.pending.insert(header.chunk_hash()) {
+                headers_by_shard.entry(header.shard_id()).or_default().push(header);
+            }
+        }
+        metrics::CHUNK_HEADERS_PENDING_VALIDATION.set(self.pending.len() as i64);
+        for (shard_id, headers) in headers_by_shard {
+            let runtime_adapter = self.runtime_adapter.clone();
+            let results_sender = self.results_sender.clone();
+            self.thread_pool.spawn(move || {
+                let shard_label = shard_id.to_string();
+                for header in headers {
+                    let _timer = metrics::CHUNK_HEADER_VALIDATION_TIME
+                        .with_label_values(&[&shard_label])
+                        .start_timer();
+                    let result = validate_chunk_header(&header, runtime_adapter.as_ref());
+                    results_sender.send(ChunkHeaderValidated { header, result });
+                }
+            });
+        }
+    }
+
+    /// Marks the result of the validation of the chunk header as processed.
+    pub fn finish(&mut self, chunk_hash: &ChunkHash) {
+        self.pending.remove(chunk_hash);
+        metrics::CHUNK_HEADERS_PENDING_VALIDATION.set(self.pending.len() as i64);
+    }
+}
+
+/// Validates the chunk header against the epoch of its previous block, which must be accepted.
+fn validate_chunk_header(
+    header: &ShardChunkHeader,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+) -> Result<(), Error> {
+    let prev_block_hash = header.prev_block_hash();
+    let epoch_id = runtime_adapter.get_epoch_id_from_prev_block(prev_block_hash)?;
+    validate_chunk_header_in_epoch(header, &epoch_id, prev_block_hash, true, runtime_adapter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Mutex};
+
+    use assert_matches::assert_matches;
+    use near_async::messaging::{CanSend, IntoSender};
+
+    use super::{ChunkHeaderValidated, ChunkHeaderValidationPool};
+    use crate::test_utils::ChunkTestFixture;
+
+    struct ResultsSender(Mutex<mpsc::Sender<ChunkHeaderValidated>>);
+
+    impl CanSend<ChunkHeaderValidated> for ResultsSender {
+        fn send(&self, msg: ChunkHeaderValidated) {
+            self.0.lock().unwrap().send(msg).unwrap();
+        }
+    }
+
+    /// Waits until the tasks spawned so far are done. The pool has a single thread, which runs
+    /// the tasks in the order of submission.
+    fn wait_for_spawned_tasks(pool: &ChunkHeaderValidationPool) {
+        pool.thread_pool.install(|| {});
+    }
+
+    #[test]
+    fn test_validation_pool() {
+        let fixture = ChunkTestFixture::default();
+        let (sender, receiver) = mpsc::channel();
+        let mut pool = ChunkHeaderValidationPool::new(
+            fixture.mock_runtime.clone(),
+            1,
+            ResultsSender(Mutex::new(sender)).into_sender(),
+        );
+        let header = fixture.mock_chunk_header;
+        pool.submit(vec![header.clone(), header.clone()]);
+        // A header is validated only once until its result is processed.
+        pool.submit(vec![header.clone()]);
+        wait_for_spawned_tasks(&pool);
+        let results: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].header, header);
+        assert_matches!(results[0].result, Ok(()));
+
+        pool.finish(&header.chunk_hash());
+        pool.submit(vec![header]);
+        wait_for_spawned_tasks(&pool);
+        let results: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(results.len(), 1);
+        assert_matches!(results[0].result, Ok(()));
+    }
+}
@@ -64,6 +64,8 @@
 //! the previous block, when a new block is accepted, client must remember to call
 //! `get_incomplete_chunks` to get the list of incomplete chunks who are waiting on the block
 //! and process them
+//! Such chunks are kept in `orphan_chunks` until the previous block is accepted, together with
+//! the messages whose chunk header couldn't be validated without it, which are processed then.
 //!
 //! ** Validating chunks
 //! Before `process_partial_encoded_chunk` returns HaveAllPartsAndReceipts, it will perform
@@ -77,15 +79,21 @@
 //!
 //! We also guarantee that all entries stored inside ShardsManager::encoded_chunks have the chunk header
 //! at least "partially" validated by `validate_chunk_header` (see the comments there for what "partial"
-//! validation means).
+//! validation means). The full validation of the headers of the incomplete chunks, once their previous
+//! block is accepted, is done by `header_validation_pool`, if it is set, concurrently for every shard.
 
+use crate::availability_sampling::AvailabilitySampler;
+pub use crate::chunk_cache::{ChunkCompletionStats, ChunkPartSupplier};
 use crate::chunk_cache::{EncodedChunksCache, EncodedChunksCacheEntry};
+use crate::header_validation::{ChunkHeaderValidated, ChunkHeaderValidationPool};
 use crate::logic::cares_about_shard_this_or_next_epoch;
+use crate::orphan_chunks::OrphanChunkPool;
 use adapter::ShardsManagerRequestFromClient;
 use client::ShardsManagerResponse;
 use logic::{
     decode_encoded_chunk, make_outgoing_receipts_proofs,
     make_partial_encoded_chunk_from_owned_parts_and_needed_receipts, need_part, need_receipt,
+    validate_chunk_header_in_epoch,
 };
 use metrics::{
     PARTIAL_ENCODED_CHUNK_FORWARD_CACHED_WITHOUT_HEADER,
@@ -93,18 +101,22 @@ use metrics::{
 };
 use near_async::messaging::Sender;
 use near_chain::chunks_store::ReadOnlyChunksStore;
-use near_chain::near_chain_primitives::error::Error::DBNotFoundErr;
-use near_chain::{byzantine_assert, RuntimeWithEpochManagerAdapter};
+use near_chain::{byzantine_assert, Chain, RuntimeWithEpochManagerAdapter};
+use near_chain_configs::{
+    ChunkAvailabilitySamplingConfig, ChunkHorizonsConfig, ChunkRequestHedgingConfig,
+    FullChunkRequestsConfig,
+};
 pub use near_chunks_primitives::Error;
 use near_network::shards_manager::ShardsManagerRequestFromNetwork;
 use near_network::types::{
-    AccountIdOrPeerTrackingShard, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg,
+    AccountIdOrPeerTrackingShard, FullChunkResponseMsg, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
 };
 use near_network::types::{NetworkRequests, PeerManagerMessageRequest};
 use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
-use near_primitives::merkle::{verify_path, MerklePath};
+use near_primitives::merkle::{merklize, verify_path, MerklePath};
+use near_primitives::network::PeerId;
 use near_primitives::receipt::Receipt;
 use near_primitives::sharding::{
     ChunkHash, EncodedShardChunk, EncodedShardChunkBody, PartialEncodedChunk,
@@ -121,6 +133,7 @@ use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::ProtocolVersion;
+use near_primitives::views::{ChunkRequestView, ShardsManagerView};
 use rand::seq::IteratorRandom;
 use rand::Rng;
 use std::collections::{HashMap, HashSet};
@@ -128,10 +141,14 @@ use std::sync::Arc;
 use tracing::{debug, error, warn};
 
 pub mod adapter;
+mod availability_sampling;
 mod chunk_cache;
 pub mod client;
+pub mod debug;
+pub mod header_validation;
 pub mod logic;
 pub mod metrics;
+mod orphan_chunks;
 pub mod shards_manager_actor;
 pub mod test_loop;
 pub mod test_utils;
@@ -140,7 +157,17 @@ pub const CHUNK_REQUEST_RETRY: time::Duration = time::Duration::milliseconds(100
 pub const CHUNK_REQUEST_SWITCH_TO_OTHERS: time::Duration = time::Duration::milliseconds(400);
 pub const CHUNK_REQUEST_SWITCH_TO_FULL_FETCH: time::Duration = time::Duration::seconds(3);
 const CHUNK_REQUEST_RETRY_MAX: time::Duration = time::Duration::seconds(1000);
+// The requests to the accounts whose round trip time is known are retried after
+// CHUNK_REQUEST_TIMEOUT_RTT_MULTIPLIER times it, but not sooner than CHUNK_REQUEST_RETRY_MIN
+// and not later than CHUNK_REQUEST_RETRY. The accounts which wouldn't respond within
+// CHUNK_REQUEST_RETRY are considered slow, and the parts are requested from the peers tracking
+// the shard instead.
+const CHUNK_REQUEST_RETRY_MIN: time::Duration = time::Duration::milliseconds(20);
+const CHUNK_REQUEST_TIMEOUT_RTT_MULTIPLIER: f64 = 3.0;
 const CHUNK_FORWARD_CACHE_SIZE: usize = 1000;
+const HEDGED_CHUNKS_CACHE_SIZE: usize = 1000;
+const SEEN_FORWARDED_PARTS_CACHE_SIZE: usize = 1000;
+const VERIFIED_RECEIPT_PROOFS_CACHE_SIZE: usize = 4000;
 // Only request chunks from peers whose latest height >= chunk_height - CHUNK_REQUEST_PEER_HORIZON
 const CHUNK_REQUEST_PEER_HORIZON: BlockHeightDelta = 5;
 
@@ -175,6 +202,12 @@ struct ChunkRequestInfo {
     shard_id: ShardId,
     added: time::Instant,
     last_requested: time::Instant,
+    // time after which the request is retried, adapted to the targets of the last request
+    retry_duration: time::Duration,
+    // when the request is due to be hedged, if it was sent and hedging is enabled
+    hedge_at: Option<time::Instant>,
+    // number of the hedged requests sent so far
+    hedges: u32,
 }
 
 struct RequestPool {
@@ -219,6 +252,34 @@ impl RequestPool {
         self.requests.remove(chunk_hash);
     }
 
+    pub fn set_retry_duration(&mut self, chunk_hash: &ChunkHash, retry_duration: time::Duration) {
+        if let Some(chunk_request) = self.requests.get_mut(chunk_hash) {
+            chunk_request.retry_duration = retry_duration;
+        }
+    }
+
+    pub fn schedule_hedge(&mut self, chunk_hash: &ChunkHash, hedge_at: time::Instant) {
+        if let Some(chunk_request) = self.requests.get_mut(chunk_hash) {
+            chunk_request.hedge_at = Some(hedge_at);
+        }
+    }
+
+    /// Returns the requests due to be hedged, counting the hedges as sent.
+    pub fn fetch_hedges(
+        &mut self,
+        current_time: time::Instant,
+    ) -> Vec<(ChunkHash, ChunkRequestInfo)> {
+        let mut requests = Vec::new();
+        for (chunk_hash, chunk_request) in self.requests.iter_mut() {
+            if chunk_request.hedge_at.map_or(false, |hedge_at| hedge_at <= current_time) {
+                chunk_request.hedge_at = None;
+                chunk_request.hedges += 1;
+                requests.push((chunk_hash.clone(), chunk_request.clone()));
+            }
+        }
+        requests
+    }
+
     pub fn fetch(&mut self, current_time: time::Instant) -> Vec<(ChunkHash, ChunkRequestInfo)> {
         let mut removed_requests = HashSet::<ChunkHash>::default();
         let mut requests = Vec::new();
@@ -228,7 +289,7 @@ impl RequestPool {
                 removed_requests.insert(chunk_hash.clone());
                 continue;
             }
-            if current_time - chunk_request.last_requested >= self.retry_duration {
+            if current_time - chunk_request.last_requested >= chunk_request.retry_duration {
                 chunk_request.last_requested = current_time;
                 requests.push((chunk_hash.clone(), chunk_request.clone()));
             }
@@ -238,6 +299,28 @@ impl RequestPool {
         }
         requests
     }
+
+    /// Summaries of the pending requests, ordered by height, for the debug page.
+    pub fn view(&self, current_time: time::Instant) -> Vec<ChunkRequestView> {
+        let mut views: Vec<ChunkRequestView> = self
+            .requests
+            .iter()
+            .map(|(chunk_hash, chunk_request)| ChunkRequestView {
+                chunk_hash: chunk_hash.clone(),
+                height_created: chunk_request.height,
+                shard_id: chunk_request.shard_id,
+                prev_block_hash: chunk_request.prev_block_hash,
+                ancestor_hash: chunk_request.ancestor_hash,
+                requested_ms_ago: (current_time - chunk_request.added).whole_milliseconds() as i64,
+                last_requested_ms_ago: (current_time - chunk_request.last_requested)
+                    .whole_milliseconds() as i64,
+                retry_after_ms: chunk_request.retry_duration.whole_milliseconds() as i64,
+                num_hedges: chunk_request.hedges,
+            })
+            .collect();
+        views.sort_by_key(|view| (view.height_created, view.shard_id));
+        views
+    }
 }
 
 pub struct ShardsManager {
@@ -253,6 +336,39 @@ pub struct ShardsManager {
     encoded_chunks: EncodedChunksCache,
     requested_partial_encoded_chunks: RequestPool,
     chunk_forwards_cache: lru::LruCache<ChunkHash, HashMap<u64, PartialEncodedChunkPart>>,
+    /// Ords of the forwarded parts already merged into `encoded_chunks`, per chunk. Every
+    /// validator forwards the parts it owns, so the same part usually arrives several times;
+    /// the repeated ones are dropped before validation, as are the ones still waiting in
+    /// `chunk_forwards_cache`.
+    seen_forwarded_parts: lru::LruCache<ChunkHash, HashSet<u64>>,
+    /// Hashes of the receipt lists whose proofs have been verified, by chunk and receiving shard.
+    /// The chunk hash commits to the outgoing receipts root, so the proof of a receipt list
+    /// with the same hash doesn't need to be verified again. See `verify_receipt_proof`.
+    verified_receipt_proofs: lru::LruCache<(ChunkHash, ShardId), CryptoHash>,
+    /// Chunks waiting for their previous block to be accepted. See `check_incomplete_chunks`.
+    orphan_chunks: OrphanChunkPool,
+    availability_sampler: Option<AvailabilitySampler>,
+    /// Whether the chunks are checked more thoroughly before being reported as ready for
+    /// inclusion in a block. See `try_process_chunk_parts_and_receipts`.
+    pre_validate_chunks: bool,
+    /// Whether the whole chunk bodies are requested instead of the parts and up to what size
+    /// they are served to the peers.
+    full_chunk_requests: FullChunkRequestsConfig,
+    /// If set, the requests for the chunk parts which don't arrive in time are repeated to
+    /// another node tracking the shard. See `send_hedged_chunk_requests`.
+    chunk_request_hedging: Option<ChunkRequestHedgingConfig>,
+    /// Chunks for which hedged requests were sent, used to tell whether the duplicate parts
+    /// received are caused by hedging.
+    hedged_chunks: lru::LruCache<ChunkHash, ()>,
+    /// Round trip times to the owners of the accounts, as measured by the network layer,
+    /// which the chunk request timeouts are adapted to.
+    account_rtts: HashMap<AccountId, time::Duration>,
+    /// If set, the headers of the incomplete chunks are fully validated on a separate thread
+    /// pool once their previous block is accepted. See `check_incomplete_chunks`.
+    header_validation_pool: Option<ChunkHeaderValidationPool>,
+    /// Whether the forwarded parts of the chunks of the shards the node doesn't care about are
+    /// dropped. See `process_partial_encoded_chunk_forward`.
+    chunk_only_producer_lightweight_mode: bool,
 
     // This is a best-effort cache of the chain's head, not the source of truth. The source
     // of truth is in the chain store and written to by the Client.
@@ -275,6 +391,7 @@ impl ShardsManager {
         store: ReadOnlyChunksStore,
         initial_chain_head: Tip,
         initial_chain_header_head: Tip,
+        chunk_horizons: ChunkHorizonsConfig,
     ) -> Self {
         Self {
             clock,
@@ -287,7 +404,7 @@ impl ShardsManager {
                 runtime_adapter.num_data_parts(),
                 runtime_adapter.num_total_parts() - runtime_adapter.num_data_parts(),
             ),
-            encoded_chunks: EncodedChunksCache::new(),
+            encoded_chunks: EncodedChunksCache::new(chunk_horizons),
             requested_partial_encoded_chunks: RequestPool::new(
                 CHUNK_REQUEST_RETRY,
                 CHUNK_REQUEST_SWITCH_TO_OTHERS,
@@ -295,13 +412,85 @@ impl ShardsManager {
                 CHUNK_REQUEST_RETRY_MAX,
             ),
             chunk_forwards_cache: lru::LruCache::new(CHUNK_FORWARD_CACHE_SIZE),
+            seen_forwarded_parts: lru::LruCache::new(SEEN_FORWARDED_PARTS_CACHE_SIZE),
+            verified_receipt_proofs: lru::LruCache::new(VERIFIED_RECEIPT_PROOFS_CACHE_SIZE),
+            orphan_chunks: OrphanChunkPool::default(),
+            availability_sampler: None,
+            pre_validate_chunks: false,
+            full_chunk_requests: FullChunkRequestsConfig {
+                request_full_chunks: false,
+                max_response_size: 0,
+            },
+            chunk_request_hedging: None,
+            hedged_chunks: lru::LruCache::new(HEDGED_CHUNKS_CACHE_SIZE),
+            account_rtts: HashMap::new(),
+            header_validation_pool: None,
+            chunk_only_producer_lightweight_mode: false,
             chain_head: initial_chain_head,
             chain_header_head: initial_chain_header_head,
         }
     }
 
+    /// Enables the data availability sampling of the chunks of the shards this node doesn't
+    /// track.
+    pub fn with_availability_sampling(
+        mut self,
+        config: Option<ChunkAvailabilitySamplingConfig>,
+    ) -> Self {
+        self.availability_sampler = config.map(AvailabilitySampler::new);
+        self
+    }
+
+    /// Makes the chunks ready for inclusion in a block only once their receipt proofs are
+    /// checked and, if the chunk gets decoded, its body is checked against the header.
+    pub fn with_chunk_pre_validation(mut self, enabled: bool) -> Self {
+        self.pre_validate_chunks = enabled;
+        self
+    }
+
+    /// Makes the node request the whole chunk bodies when it needs all of a chunk, and serve
+    /// the bodies of the chunks it has to the peers asking for them.
+    pub fn with_full_chunk_requests(mut self, config: FullChunkRequestsConfig) -> Self {
+        self.full_chunk_requests = config;
+        self
+    }
+
+    /// Makes the node repeat the requests for the chunk parts which haven't arrived shortly
+    /// after being requested to another node tracking the shard.
+    pub fn with_chunk_request_hedging(mut self, config: Option<ChunkRequestHedgingConfig>) -> Self {
+        self.chunk_request_hedging = config;
+        self
+    }
+
+    /// Makes the node validate the headers of the incomplete chunks on a separate pool of
+    /// `num_threads` threads, with the results sent to `results_sender`, to be passed to
+    /// `process_chunk_header_validated`. With no threads, the headers are validated on the
+    /// ShardsManager thread.
+    pub fn with_chunk_header_validation_pool(
+        mut self,
+        num_threads: usize,
+        results_sender: Sender<ChunkHeaderValidated>,
+    ) -> Self {
+        self.header_validation_pool = (num_threads > 0).then(|| {
+            ChunkHeaderValidationPool::new(
+                self.runtime_adapter.clone(),
+                num_threads,
+                results_sender,
+            )
+        });
+        self
+    }
+
+    /// Makes the node drop the forwarded parts of the chunks of the shards it neither tracks
+    /// nor will track, which a chunk-only producer tracking only its own shards has no use for.
+    pub fn with_chunk_only_producer_lightweight_mode(mut self, enabled: bool) -> Self {
+        self.chunk_only_producer_lightweight_mode = enabled;
+        self
+    }
+
     pub fn update_chain_heads(&mut self, head: Tip, header_head: Tip) {
         self.encoded_chunks.update_largest_seen_height(
+            self.clock.now(),
             head.height,
             &self.requested_partial_encoded_chunks.requests,
         );
@@ -318,7 +507,7 @@ impl ShardsManager {
         force_request_full: bool,
         request_own_parts_from_others: bool,
         request_from_archival: bool,
-    ) -> Result<(), near_chain::Error> {
+    ) -> Result<time::Duration, near_chain::Error> {
         let _span = tracing::debug_span!(
             target: "chunks",
             "request_partial_encoded_chunk",
@@ -418,11 +607,65 @@ impl ShardsManager {
         }
 
         let no_account_id = me.is_none();
+
+        // A node which needs the whole chunk may ask a single peer for the chunk body instead of
+        // collecting the parts from their owners. The request lists the missing parts, so that a
+        // peer which can't serve the body responds with the parts instead. Once the request is
+        // switched to the full fetch, the parts are requested from their owners as usual.
+        if self.full_chunk_requests.request_full_chunks
+            && request_full
+            && !force_request_full
+            && (no_account_id || me != shard_representative_target.as_ref())
+        {
+            let mut part_ords: Vec<u64> = bp_to_parts.into_values().flatten().collect();
+            part_ords.sort();
+            debug!(
+                target: "chunks",
+                shard_id,
+                target_account = ?shard_representative_target,
+                "Requesting full chunk",
+            );
+            let request = PartialEncodedChunkRequestMsg {
+                chunk_hash: chunk_hash.clone(),
+                part_ords,
+                tracking_shards: HashSet::new(),
+            };
+            let prefer_peer = request_from_archival
+                || self.is_slow_account(shard_representative_target.as_ref())
+                || rand::thread_rng().gen::<bool>();
+            let retry_duration =
+                self.chunk_request_timeout(shard_representative_target.as_ref(), prefer_peer);
+            let target = AccountIdOrPeerTrackingShard {
+                account_id: shard_representative_target,
+                prefer_peer,
+                shard_id,
+                only_archival: request_from_archival,
+                min_height: height.saturating_sub(CHUNK_REQUEST_PEER_HORIZON),
+            };
+            self.peer_manager_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::FullChunkRequest {
+                    target,
+                    request,
+                    create_time: self.clock.now(),
+                },
+            ));
+            self.encoded_chunks.record_requests(chunk_hash, 1);
+            metrics::CHUNK_REQUEST_TIMEOUT.observe(retry_duration.as_seconds_f64());
+            return Ok(retry_duration);
+        }
+
         debug!(target: "chunks", "Will send {} requests to fetch chunk parts.", bp_to_parts.len());
+        // The request is retried once the slowest of the targets should have responded.
+        let mut retry_duration = None;
+        let mut num_requests = 0;
         for (target_account, part_ords) in bp_to_parts {
             // extra check that we are not sending request to ourselves.
             if no_account_id || me != target_account.as_ref() {
-                let prefer_peer = request_from_archival || rand::thread_rng().gen::<bool>();
+                let prefer_peer = request_from_archival
+                    || self.is_slow_account(target_account.as_ref())
+                    || rand::thread_rng().gen::<bool>();
+                let timeout = self.chunk_request_timeout(target_account.as_ref(), prefer_peer);
+                retry_duration = Some(retry_duration.map_or(timeout, |d| timeout.max(d)));
                 debug!(
                     target: "chunks",
                     ?part_ords,
@@ -456,6 +699,7 @@ impl ShardsManager {
                         create_time: self.clock.now(),
                     },
                 ));
+                num_requests += 1;
             } else {
                 warn!(target: "client", "{:?} requests parts {:?} for chunk {:?} from self",
                     me, part_ords, chunk_hash
@@ -463,7 +707,41 @@ impl ShardsManager {
             }
         }
 
-        Ok(())
+        self.encoded_chunks.record_requests(chunk_hash, num_requests);
+        let retry_duration =
+            retry_duration.unwrap_or(self.requested_partial_encoded_chunks.retry_duration);
+        metrics::CHUNK_REQUEST_TIMEOUT.observe(retry_duration.as_seconds_f64());
+        Ok(retry_duration)
+    }
+
+    /// Time after which a request sent to `target` is retried. The requests to the accounts
+    /// whose round trip time is known time out sooner than the others.
+    fn chunk_request_timeout(
+        &self,
+        target: Option<&AccountId>,
+        prefer_peer: bool,
+    ) -> time::Duration {
+        let retry_duration = self.requested_partial_encoded_chunks.retry_duration;
+        // If the request goes to any peer tracking the shard, the round trip time is unknown.
+        match target.filter(|_| !prefer_peer).and_then(|target| self.account_rtts.get(target)) {
+            Some(rtt) => (*rtt * CHUNK_REQUEST_TIMEOUT_RTT_MULTIPLIER)
+                .clamp(CHUNK_REQUEST_RETRY_MIN, retry_duration),
+            None => retry_duration,
+        }
+    }
+
+    /// Whether the account is not expected to respond before the request is retried anyway.
+    fn is_slow_account(&self, account_id: Option<&AccountId>) -> bool {
+        account_id.and_then(|account_id| self.account_rtts.get(account_id)).map_or(false, |rtt| {
+            *rtt * CHUNK_REQUEST_TIMEOUT_RTT_MULTIPLIER
+                > self.requested_partial_encoded_chunks.retry_duration
+        })
+    }
+
+    /// Sets the round trip times to the owners of the accounts, used to adapt the timeouts
+    /// of the chunk requests.
+    pub fn update_account_rtts(&mut self, account_rtts: HashMap<AccountId, time::Duration>) {
+        self.account_rtts = account_rtts;
     }
 
     /// Get a random shard block producer that is not me.
@@ -587,6 +865,10 @@ impl ShardsManager {
             return;
         }
 
+        if self.encoded_chunks.take_discarded(&chunk_hash) {
+            metrics::CHUNK_CACHE_DISCARDED_THEN_REQUESTED.inc();
+        }
+
         let prev_block_hash = *chunk_header.prev_block_hash();
         self.requested_partial_encoded_chunks.insert(
             chunk_hash.clone(),
@@ -597,6 +879,9 @@ impl ShardsManager {
                 shard_id,
                 last_requested: self.clock.now().into(),
                 added: self.clock.now().into(),
+                retry_duration: self.requested_partial_encoded_chunks.retry_duration,
+                hedge_at: None,
+                hedges: 0,
             },
         );
 
@@ -633,8 +918,15 @@ impl ShardsManager {
                     old_block,
                     fetch_from_archival,
                 );
-                if let Err(err) = request_result {
-                    error!(target: "chunks", "Error during requesting partial encoded chunk: {}", err);
+                match request_result {
+                    Ok(retry_duration) => {
+                        self.requested_partial_encoded_chunks
+                            .set_retry_duration(&chunk_hash, retry_duration);
+                        self.schedule_chunk_request_hedge(&chunk_hash, 0);
+                    }
+                    Err(err) => {
+                        error!(target: "chunks", "Error during requesting partial encoded chunk: {}", err);
+                    }
                 }
             } else {
                 debug!(target: "chunks",should_wait_for_chunk_forwarding, fetch_from_archival, old_block,  "Delaying the chunk request.");
@@ -713,7 +1005,11 @@ impl ShardsManager {
                         >= self.requested_partial_encoded_chunks.switch_to_others_duration,
                 fetch_from_archival,
             ) {
-                Ok(()) => {}
+                Ok(retry_duration) => {
+                    self.requested_partial_encoded_chunks
+                        .set_retry_duration(&chunk_hash, retry_duration);
+                    self.schedule_chunk_request_hedge(&chunk_hash, chunk_request.hedges);
+                }
                 Err(err) => {
                     debug_assert!(false);
                     error!(target: "chunks", "Error during requesting partial encoded chunk: {}", err);
@@ -722,6 +1018,126 @@ impl ShardsManager {
         }
     }
 
+    /// Schedules hedging the request for the chunk, unless hedging is disabled or the request
+    /// has been hedged enough times already.
+    fn schedule_chunk_request_hedge(&mut self, chunk_hash: &ChunkHash, hedges: u32) {
+        let config = match &self.chunk_request_hedging {
+            Some(config) if hedges < config.max_hedges => config,
+            _ => return,
+        };
+        let hedge_at = self.clock.now() + config.delay;
+        self.requested_partial_encoded_chunks.schedule_hedge(chunk_hash, hedge_at);
+    }
+
+    /// Sends the hedged requests for the chunks whose parts haven't arrived within the hedging
+    /// delay since they were requested. The parts still missing are requested once more from
+    /// another node tracking the shard, which has all the parts if it has reconstructed the
+    /// chunk, without waiting for the regular retry. A hedge is cancelled if all the parts
+    /// arrive in the meantime.
+    pub fn send_hedged_chunk_requests(&mut self) {
+        if self.chunk_request_hedging.is_none() {
+            return;
+        }
+        let requests = self.requested_partial_encoded_chunks.fetch_hedges(self.clock.now());
+        for (chunk_hash, chunk_request) in requests {
+            match self.request_partial_encoded_chunk_hedge(&chunk_hash, &chunk_request) {
+                Ok(true) => {
+                    metrics::CHUNK_REQUEST_HEDGES.with_label_values(&["sent"]).inc();
+                    self.hedged_chunks.put(chunk_hash.clone(), ());
+                    self.schedule_chunk_request_hedge(&chunk_hash, chunk_request.hedges);
+                }
+                Ok(false) => {
+                    metrics::CHUNK_REQUEST_HEDGES.with_label_values(&["cancelled"]).inc();
+                }
+                Err(err) => {
+                    error!(target: "chunks", "Error during hedging partial encoded chunk request: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Requests the parts of the chunk which are still missing, and the receipts, from a random
+    /// block producer tracking the shard, or preferably from any peer tracking it. Returns false
+    /// if nothing is missing anymore.
+    fn request_partial_encoded_chunk_hedge(
+        &mut self,
+        chunk_hash: &ChunkHash,
+        chunk_request: &ChunkRequestInfo,
+    ) -> Result<bool, near_chain::Error> {
+        let ancestor_hash = &chunk_request.ancestor_hash;
+        let shard_id = chunk_request.shard_id;
+        let cache_entry = match self.encoded_chunks.get(chunk_hash) {
+            Some(cache_entry) if !cache_entry.complete => cache_entry,
+            _ => return Ok(false),
+        };
+        let request_full = cares_about_shard_this_or_next_epoch(
+            self.me.as_ref(),
+            ancestor_hash,
+            shard_id,
+            true,
+            self.runtime_adapter.as_ref(),
+        );
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(ancestor_hash)?;
+        let mut part_ords = Vec::new();
+        for part_ord in 0..self.rs.total_shard_count() as u64 {
+            if cache_entry.parts.contains_key(&part_ord) {
+                continue;
+            }
+            if request_full
+                || Some(&self.runtime_adapter.get_part_owner(&epoch_id, part_ord)?)
+                    == self.me.as_ref()
+            {
+                part_ords.push(part_ord);
+            }
+        }
+        let tracking_shards: HashSet<ShardId> = if request_full {
+            HashSet::new()
+        } else {
+            self.get_tracking_shards(ancestor_hash)
+                .into_iter()
+                .filter(|shard_id| !cache_entry.receipts.contains_key(shard_id))
+                .collect()
+        };
+        if part_ords.is_empty() && tracking_shards.is_empty() {
+            return Ok(false);
+        }
+
+        let request_from_archival = self.runtime_adapter.chunk_needs_to_be_fetched_from_archival(
+            ancestor_hash,
+            &self.chain_header_head.last_block_hash,
+        )?;
+        let target = AccountIdOrPeerTrackingShard {
+            account_id: self.get_random_target_tracking_shard(ancestor_hash, shard_id)?,
+            prefer_peer: true,
+            shard_id,
+            only_archival: request_from_archival,
+            min_height: chunk_request.height.saturating_sub(CHUNK_REQUEST_PEER_HORIZON),
+        };
+        debug!(
+            target: "chunks",
+            ?chunk_hash,
+            ?part_ords,
+            shard_id,
+            target_account = ?target.account_id,
+            hedges = chunk_request.hedges,
+            "Hedging the request for parts",
+        );
+        let request = PartialEncodedChunkRequestMsg {
+            chunk_hash: chunk_hash.clone(),
+            part_ords,
+            tracking_shards,
+        };
+        self.peer_manager_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::PartialEncodedChunkRequest {
+                target,
+                request,
+                create_time: self.clock.now(),
+            },
+        ));
+        self.encoded_chunks.record_requests(chunk_hash, 1);
+        Ok(true)
+    }
+
     pub fn receipts_recipient_filter<T>(
         from_shard_id: ShardId,
         tracking_shards: T,
@@ -778,6 +1194,50 @@ impl ShardsManager {
         ));
     }
 
+    /// Serves the body of the requested chunk, if this node has it and it is not larger than
+    /// the configured limit. Otherwise serves the requested parts and receipts, the same way
+    /// as for a PartialEncodedChunkRequest.
+    pub fn process_full_chunk_request(
+        &mut self,
+        request: PartialEncodedChunkRequestMsg,
+        route_back: CryptoHash,
+    ) {
+        let started = self.clock.now();
+        let response = match self.prepare_full_chunk_response(&request.chunk_hash) {
+            Some(response) => response,
+            None => {
+                self.process_partial_encoded_chunk_request(request, route_back);
+                return;
+            }
+        };
+        let elapsed = (self.clock.now() - started).as_seconds_f64();
+        metrics::PARTIAL_ENCODED_CHUNK_REQUEST_PROCESSING_TIME
+            .with_label_values(&[
+                PartialEncodedChunkResponseSource::FullChunk.name_for_metrics(),
+                "ok",
+            ])
+            .observe(elapsed);
+        debug!(target: "chunks", chunk_hash = %request.chunk_hash.0, "Serving full chunk");
+        self.peer_manager_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::FullChunkResponse { route_back, response },
+        ));
+    }
+
+    fn prepare_full_chunk_response(&self, chunk_hash: &ChunkHash) -> Option<FullChunkResponseMsg> {
+        // ShardChunk is only stored once the chunk has been reconstructed and checked against
+        // its header.
+        let chunk = self.store.get_chunk(chunk_hash).ok()?;
+        // The encoded length is the size of the serialized transactions and receipts.
+        if chunk.cloned_header().encoded_length() > self.full_chunk_requests.max_response_size {
+            return None;
+        }
+        Some(FullChunkResponseMsg {
+            chunk_hash: chunk_hash.clone(),
+            transactions: chunk.transactions().to_vec(),
+            receipts: chunk.receipts().to_vec(),
+        })
+    }
+
     /// Finds the parts and receipt proofs asked for in the request, and returns a response
     /// containing whatever was found. See comment for PartialEncodedChunkResponseSource for
     /// an explanation of that part of the return value.
@@ -905,84 +1365,107 @@ impl ShardsManager {
         chunk: &ShardChunk,
     ) {
         let header = chunk.cloned_header();
-
-        // Get outgoing receipts for the chunk and construct vector of their
-        // proofs.
-        let outgoing_receipts = chunk.receipts();
-        let present_receipts: HashMap<ShardId, _> = match make_outgoing_receipts_proofs(
-            &header,
-            &outgoing_receipts,
-            self.runtime_adapter.as_ref(),
-        ) {
-            Ok(receipts) => receipts.map(|receipt| (receipt.1.to_shard_id, receipt)).collect(),
-            Err(e) => {
-                warn!(target: "chunks", "Not sending {:?}, failed to make outgoing receipts proofs: {}", chunk.chunk_hash(), e);
-                return;
+        let transactions = chunk.transactions().to_vec();
+        let (parts, receipts) =
+            match self.encode_chunk_body(&header, transactions, chunk.receipts()) {
+                Ok(result) => result,
+                Err(err) => {
+                    warn!(target: "chunks", "Not sending {:?}, {}", chunk.chunk_hash(), err);
+                    return;
+                }
+            };
+        for part in parts {
+            if part_ords.contains(&part.part_ord) {
+                response.parts.push(part);
             }
-        };
+        }
+        for (shard_id, receipt_proof) in receipts {
+            if tracking_shards.contains(&shard_id) {
+                response.receipts.push(receipt_proof);
+            }
+        }
+    }
+
+    /// Encodes the transactions and the outgoing receipts of a chunk into all of its parts and
+    /// receipt proofs, checking the result against the chunk header.
+    fn encode_chunk_body(
+        &mut self,
+        header: &ShardChunkHeader,
+        transactions: Vec<SignedTransaction>,
+        outgoing_receipts: &[Receipt],
+    ) -> Result<(Vec<PartialEncodedChunkPart>, HashMap<ShardId, ReceiptProof>), String> {
+        // The receipts of a full chunk response come from a peer, so they have to be checked
+        // before `make_outgoing_receipts_proofs`, which asserts that they match the header.
+        let shard_layout = self
+            .runtime_adapter
+            .get_shard_layout_from_prev_block(header.prev_block_hash())
+            .map_err(|err| format!("failed to get the shard layout: {}", err))?;
+        let (receipts_root, _) =
+            merklize(&Chain::build_receipts_hashes(outgoing_receipts, &shard_layout));
+        if header.outgoing_receipts_root() != receipts_root {
+            return Err(format!(
+                "expected outgoing receipts root doesn’t match calculated: {} != {}",
+                header.outgoing_receipts_root(),
+                receipts_root
+            ));
+        }
+
+        // Construct vector of the proofs of the outgoing receipts.
+        let receipts =
+            make_outgoing_receipts_proofs(header, outgoing_receipts, self.runtime_adapter.as_ref())
+                .map_err(|err| format!("failed to make outgoing receipts proofs: {}", err))?
+                .map(|receipt| (receipt.1.to_shard_id, receipt))
+                .collect();
 
-        // Construct EncodedShardChunk.  If we earlier determined that we will
-        // need parity parts, instruct the constructor to calculate them as
-        // well.  Otherwise we won’t bother.
-        let (parts, encoded_length) = match EncodedShardChunk::encode_transaction_receipts(
+        // Construct EncodedShardChunk, including the parity parts.
+        let (parts, encoded_length) = EncodedShardChunk::encode_transaction_receipts(
             &mut self.rs,
-            chunk.transactions().to_vec(),
-            &outgoing_receipts,
-        ) {
-            Ok(result) => result,
-            Err(err) => {
-                warn!(target: "chunks",
-                       "Not sending {:?}, failed to encode transactions and receipts: {}",
-                       chunk.chunk_hash(), err);
-                return;
-            }
-        };
+            transactions,
+            outgoing_receipts,
+        )
+        .map_err(|err| format!("failed to encode transactions and receipts: {}", err))?;
         if header.encoded_length() != encoded_length {
-            warn!(target: "chunks",
-                   "Not sending {:?}, expected encoded length doesn’t match calculated: {} != {}",
-                   chunk.chunk_hash(), header.encoded_length(), encoded_length);
-            return;
+            return Err(format!(
+                "expected encoded length doesn’t match calculated: {} != {}",
+                header.encoded_length(),
+                encoded_length
+            ));
         }
 
         let mut content = EncodedShardChunkBody { parts };
-        if let Err(err) = content.reconstruct(&mut self.rs) {
-            warn!(target: "chunks",
-                   "Not sending {:?}, failed to reconstruct RS parity parts: {}",
-                   chunk.chunk_hash(), err);
-            return;
-        }
+        content
+            .reconstruct(&mut self.rs)
+            .map_err(|err| format!("failed to reconstruct RS parity parts: {}", err))?;
 
         let (encoded_merkle_root, merkle_paths) = content.get_merkle_hash_and_paths();
         if header.encoded_merkle_root() != encoded_merkle_root {
-            warn!(target: "chunks",
-                   "Not sending {:?}, expected encoded Merkle root doesn’t match calculated: {} != {}",
-                   chunk.chunk_hash(), header.encoded_merkle_root(), encoded_merkle_root);
-            return;
+            return Err(format!(
+                "expected encoded Merkle root doesn’t match calculated: {} != {}",
+                header.encoded_merkle_root(),
+                encoded_merkle_root
+            ));
         }
         if merkle_paths.len() != content.parts.len() {
-            warn!(target: "chunks",
-                   "Not sending {:?}, expected number of Merkle paths doesn’t match calculated: {} != {}",
-                   chunk.chunk_hash(), merkle_paths.len(), content.parts.len());
-            return;
-        }
-        for (part_ord, (part, merkle_proof)) in
-            content.parts.into_iter().zip(merkle_paths.into_iter()).enumerate()
-        {
-            if let Some(part) = part {
-                if part_ords.contains(&(part_ord as u64)) {
-                    response.parts.push(PartialEncodedChunkPart {
-                        part_ord: part_ord as u64,
-                        part,
-                        merkle_proof,
-                    });
-                }
-            }
-        }
-        for (shard_id, receipt_proof) in present_receipts {
-            if tracking_shards.contains(&shard_id) {
-                response.receipts.push(receipt_proof);
-            }
+            return Err(format!(
+                "expected number of Merkle paths doesn’t match calculated: {} != {}",
+                merkle_paths.len(),
+                content.parts.len()
+            ));
         }
+        let parts = content
+            .parts
+            .into_iter()
+            .zip(merkle_paths.into_iter())
+            .enumerate()
+            .filter_map(|(part_ord, (part, merkle_proof))| {
+                Some(PartialEncodedChunkPart {
+                    part_ord: part_ord as u64,
+                    part: part?,
+                    merkle_proof,
+                })
+            })
+            .collect();
+        Ok((parts, receipts))
     }
 
     // pub for testing
@@ -1144,12 +1627,56 @@ impl ShardsManager {
 
     pub fn process_partial_encoded_chunk_forward(
         &mut self,
-        forward: PartialEncodedChunkForwardMsg,
+        mut forward: PartialEncodedChunkForwardMsg,
     ) -> Result<(), Error> {
+        // Skip the validation of the parts which can't be of any use.
+        if self.encoded_chunks.get(&forward.chunk_hash).map_or(false, |entry| entry.complete) {
+            metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS
+                .with_label_values(&["complete"])
+                .inc_by(forward.parts.len() as u64);
+            return Ok(());
+        }
+        // As whether the node cares about the shard can only be told once the previous block is
+        // known, the parts of the orphan chunks are kept.
+        if self.chunk_only_producer_lightweight_mode
+            && self.runtime_adapter.get_epoch_id_from_prev_block(&forward.prev_block_hash).is_ok()
+            && !cares_about_shard_this_or_next_epoch(
+                self.me.as_ref(),
+                &forward.prev_block_hash,
+                forward.shard_id,
+                true,
+                self.runtime_adapter.as_ref(),
+            )
+        {
+            metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS
+                .with_label_values(&["untracked_shard"])
+                .inc_by(forward.parts.len() as u64);
+            return Ok(());
+        }
+        // A part is only known to be seen while it is merged or cached, so that the parts
+        // evicted from the cache before being merged are accepted again.
+        let seen_part_ords = self.seen_forwarded_parts.peek(&forward.chunk_hash);
+        let cached_parts = self.chunk_forwards_cache.peek(&forward.chunk_hash);
+        if seen_part_ords.is_some() || cached_parts.is_some() {
+            let num_parts = forward.parts.len();
+            forward.parts.retain(|part| {
+                !seen_part_ords.map_or(false, |ords| ords.contains(&part.part_ord))
+                    && !cached_parts.map_or(false, |parts| parts.contains_key(&part.part_ord))
+            });
+            let num_skipped = num_parts - forward.parts.len();
+            if num_skipped > 0 {
+                metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS
+                    .with_label_values(&["seen"])
+                    .inc_by(num_skipped as u64);
+                if forward.parts.is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+
         let maybe_header = self
             .validate_partial_encoded_chunk_forward(&forward)
             .and_then(|_| self.get_partial_encoded_chunk_header(&forward.chunk_hash));
-
         let header = match maybe_header {
             Ok(header) => Ok(header),
             Err(Error::UnknownChunk) => {
@@ -1176,15 +1703,32 @@ impl ShardsManager {
             }
             Err(err) => Err(err),
         }?;
+        let chunk_hash = forward.chunk_hash;
+        let part_ords: Vec<_> = forward.parts.iter().map(|part| part.part_ord).collect();
         let partial_chunk = PartialEncodedChunk::V2(PartialEncodedChunkV2 {
             header,
             parts: forward.parts,
             receipts: Vec::new(),
         });
-        self.process_partial_encoded_chunk(MaybeValidated::from_validated(partial_chunk))?;
+        self.process_partial_encoded_chunk_from(
+            MaybeValidated::from_validated(partial_chunk),
+            ChunkPartSupplier::Forward,
+            None,
+        )?;
+        self.mark_forwarded_parts_seen(chunk_hash, part_ords);
         Ok(())
     }
 
+    /// Remembers the forwarded parts of the chunk which have been merged into `encoded_chunks`.
+    fn mark_forwarded_parts_seen(&mut self, chunk_hash: ChunkHash, part_ords: Vec<u64>) {
+        match self.seen_forwarded_parts.get_mut(&chunk_hash) {
+            Some(seen_part_ords) => seen_part_ords.extend(part_ords),
+            None => {
+                self.seen_forwarded_parts.put(chunk_hash, part_ords.into_iter().collect());
+            }
+        }
+    }
+
     /// Validate a chunk header
     /// 1) check that the chunk header is signed by the correct chunk producer for the chunk at
     ///    the height for the shard
@@ -1202,7 +1746,6 @@ impl ShardsManager {
     // then in `process_partial_encoded_chunk` after checking the previous block is ready
     fn validate_chunk_header(&self, header: &ShardChunkHeader) -> Result<(), Error> {
         let chunk_hash = header.chunk_hash();
-        // 1.  check signature
         // Ideally, validating the chunk header needs the previous block to be accepted already.
         // However, we want to be able to validate chunk header in advance so we can save
         // the corresponding parts and receipts before the previous block is processed
@@ -1237,34 +1780,32 @@ impl ShardsManager {
             }
         };
 
-        match self.runtime_adapter.verify_chunk_header_signature(header, &epoch_id, &ancestor_hash)
-        {
-            Ok(false) => {
-                return if epoch_id_confirmed {
-                    byzantine_assert!(false);
-                    Err(Error::InvalidChunkSignature)
-                } else {
-                    // we are not sure if we are using the correct epoch id for validation, so
-                    // we can't be sure if the chunk header is actually invalid. Let's return
-                    // DbNotFoundError for now, which means we don't have all needed information yet
-                    Err(DBNotFoundErr(format!("block {:?}", header.prev_block_hash())).into())
-                };
-            }
-            Ok(true) => (),
-            Err(chain_error) => {
-                return Err(chain_error.into());
-            }
-        }
+        validate_chunk_header_in_epoch(
+            header,
+            &epoch_id,
+            &ancestor_hash,
+            epoch_id_confirmed,
+            self.runtime_adapter.as_ref(),
+        )
+    }
 
-        // 2. check protocol version
-        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&epoch_id)?;
-        if header.valid_for(protocol_version) {
-            Ok(())
-        } else if epoch_id_confirmed {
-            Err(Error::InvalidChunkHeader)
-        } else {
-            Err(DBNotFoundErr(format!("block {:?}", header.prev_block_hash())).into())
+    /// Checks that the receipts in the proof are the ones the chunk sends to the receiving shard,
+    /// skipping the merkle path verification if the same receipts have been verified already.
+    fn verify_receipt_proof(&mut self, header: &ShardChunkHeader, proof: &ReceiptProof) -> bool {
+        let ReceiptProof(shard_receipts, receipt_proof) = proof;
+        let shard_id = receipt_proof.to_shard_id;
+        let receipt_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, shard_receipts));
+        let key = (header.chunk_hash(), shard_id);
+        if self.verified_receipt_proofs.get(&key) == Some(&receipt_hash) {
+            metrics::RECEIPT_PROOF_CACHE_HITS.inc();
+            return true;
+        }
+        metrics::RECEIPT_PROOF_CACHE_MISSES.inc();
+        if !verify_path(header.outgoing_receipts_root(), &receipt_proof.proof, &receipt_hash) {
+            return false;
         }
+        self.verified_receipt_proofs.put(key, receipt_hash);
+        true
     }
 
     /// Inserts the header if it is not already known, and process the forwarded chunk parts cached
@@ -1279,6 +1820,7 @@ impl ShardsManager {
             return false;
         }
         if let Some(parts) = self.chunk_forwards_cache.pop(&header.chunk_hash()) {
+            self.mark_forwarded_parts_seen(header.chunk_hash(), parts.keys().copied().collect());
             // Note that we don't need any further validation for the forwarded part.
             // The forwarded part was earlier validated via validate_partial_encoded_chunk_forward,
             // which checks the part against the merkle root in the forward message, and the merkle
@@ -1287,11 +1829,15 @@ impl ShardsManager {
             // it is the first time we learn of the header here, because later when we call
             // try_process_chunk_parts_and_receipts, we will perform a header validation if we
             // didn't already.
-            self.encoded_chunks.merge_in_partial_encoded_chunk(&PartialEncodedChunkV2 {
-                header: header.clone(),
-                parts: parts.into_values().collect(),
-                receipts: vec![],
-            });
+            self.encoded_chunks.merge_in_partial_encoded_chunk(
+                &PartialEncodedChunkV2 {
+                    header: header.clone(),
+                    parts: parts.into_values().collect(),
+                    receipts: vec![],
+                },
+                self.clock.now(),
+                ChunkPartSupplier::Forward,
+            );
             return true;
         }
         !header_known_before
@@ -1322,6 +1868,23 @@ impl ShardsManager {
     pub fn process_partial_encoded_chunk(
         &mut self,
         partial_encoded_chunk: MaybeValidated<PartialEncodedChunk>,
+    ) -> Result<ProcessPartialEncodedChunkResult, Error> {
+        self.process_partial_encoded_chunk_from(
+            partial_encoded_chunk,
+            ChunkPartSupplier::ChunkProducer,
+            None,
+        )
+    }
+
+    /// Same as `process_partial_encoded_chunk`, but attributes the newly received parts
+    /// to `supplier` in the stats reported once the chunk is complete. `peer_id` is the peer
+    /// from which the chunk has been received, if any, which the messages kept in the orphan
+    /// chunk pool are accounted to.
+    fn process_partial_encoded_chunk_from(
+        &mut self,
+        partial_encoded_chunk: MaybeValidated<PartialEncodedChunk>,
+        supplier: ChunkPartSupplier,
+        peer_id: Option<PeerId>,
     ) -> Result<ProcessPartialEncodedChunkResult, Error> {
         let partial_encoded_chunk =
             partial_encoded_chunk.map(|chunk| PartialEncodedChunkV2::from(chunk));
@@ -1350,6 +1913,7 @@ impl ShardsManager {
         let chunk_requested = self.requested_partial_encoded_chunks.contains_key(&chunk_hash);
         if !chunk_requested {
             if !self.encoded_chunks.height_within_horizon(header.height_created()) {
+                self.encoded_chunks.record_discarded(chunk_hash, header.height_created());
                 return Err(Error::ChainError(near_chain::Error::InvalidChunkHeight));
             }
             // We shouldn't process unrequested chunk if we have seen one with same (height_created + shard_id) but different chunk_hash
@@ -1370,10 +1934,18 @@ impl ShardsManager {
         {
             Err(Error::ChainError(chain_error)) => match chain_error {
                 // validate_chunk_header returns DBNotFoundError if the previous block is not ready
-                // in this case, we return NeedBlock instead of error
+                // in this case, we keep the chunk until the previous block is accepted and
+                // return NeedBlock instead of error
                 near_chain::Error::DBNotFoundErr(_) => {
-                    debug!(target:"client", "Dropping partial encoded chunk {:?} height {}, shard_id {} because we don't have enough information to validate it",
+                    debug!(target:"client", "Keeping partial encoded chunk {:?} height {}, shard_id {} until its previous block is accepted, because we don't have enough information to validate it",
                            header.chunk_hash(), header.height_created(), header.shard_id());
+                    let message = partial_encoded_chunk.as_ref().into_inner().clone();
+                    self.orphan_chunks.add(
+                        self.clock.now(),
+                        self.chain_head.height,
+                        header,
+                        Some((message, supplier, peer_id)),
+                    );
                     return Ok(ProcessPartialEncodedChunkResult::NeedBlock);
                 }
                 _ => return Err(chain_error.into()),
@@ -1397,10 +1969,7 @@ impl ShardsManager {
             // https://github.com/near/nearcore/issues/5885
             // we can't simply use prev_block_hash to check if the node tracks this shard or not
             // because prev_block_hash may not be ready
-            let shard_id = proof.1.to_shard_id;
-            let ReceiptProof(shard_receipts, receipt_proof) = proof;
-            let receipt_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, shard_receipts));
-            if !verify_path(header.outgoing_receipts_root(), &receipt_proof.proof, &receipt_hash) {
+            if !self.verify_receipt_proof(header, proof) {
                 byzantine_assert!(false);
                 return Err(Error::ChainError(near_chain::Error::InvalidReceiptsProof));
             }
@@ -1408,8 +1977,11 @@ impl ShardsManager {
 
         // 2. Consider it valid; mergeparts and receipts included in the partial encoded chunk
         // into chunk cache
-        let new_part_ords =
-            self.encoded_chunks.merge_in_partial_encoded_chunk(partial_encoded_chunk);
+        let new_part_ords = self.encoded_chunks.merge_in_partial_encoded_chunk(
+            partial_encoded_chunk,
+            self.clock.now(),
+            supplier,
+        );
 
         // 3. Forward my parts to others tracking this chunk's shard
         // It's possible that the previous block has not been processed yet. We will want to
@@ -1452,6 +2024,9 @@ impl ShardsManager {
                 // request the chunk.
                 self.request_chunk_single_mark_only(header);
             }
+            ProcessPartialEncodedChunkResult::NeedBlock => {
+                self.orphan_chunks.add(self.clock.now(), self.chain_head.height, header, None);
+            }
             _ => {}
         }
         Ok(result)
@@ -1460,27 +2035,146 @@ impl ShardsManager {
     pub fn process_partial_encoded_chunk_response(
         &mut self,
         response: PartialEncodedChunkResponseMsg,
+        peer_id: PeerId,
     ) -> Result<(), Error> {
         let header = self.get_partial_encoded_chunk_header(&response.chunk_hash)?;
+        if let Some(entry) = self.encoded_chunks.get(&response.chunk_hash) {
+            let duplicates = response
+                .parts
+                .iter()
+                .filter(|part| entry.parts.contains_key(&part.part_ord))
+                .count();
+            if duplicates > 0 {
+                let hedged = self.hedged_chunks.contains(&response.chunk_hash);
+                metrics::CHUNK_REQUEST_DUPLICATE_PARTS
+                    .with_label_values(&[if hedged { "true" } else { "false" }])
+                    .inc_by(duplicates as u64);
+            }
+        }
+        if let Some(sampler) = self.availability_sampler.as_mut() {
+            sampler.process_parts(self.clock.now(), &header, &response.parts);
+        }
         let partial_chunk = PartialEncodedChunk::new(header, response.parts, response.receipts);
         // We already know the header signature is valid because we read it from the
         // shard manager.
-        self.process_partial_encoded_chunk(MaybeValidated::from_validated(partial_chunk))?;
+        self.process_partial_encoded_chunk_from(
+            MaybeValidated::from_validated(partial_chunk),
+            ChunkPartSupplier::Response(peer_id.clone()),
+            Some(peer_id),
+        )?;
         Ok(())
     }
 
-    /// Let the ShardsManager know about the chunk header, when encountering that chunk header
-    /// from the block and the chunk is possibly not yet known to the ShardsManager.
-    pub fn process_chunk_header_from_block(
+    /// Processes the body of a chunk received in response to a FullChunkRequest. The body is
+    /// encoded into all of the parts and receipt proofs of the chunk, which are processed as if
+    /// they were received as parts.
+    pub fn process_full_chunk_response(
         &mut self,
-        header: &ShardChunkHeader,
+        response: FullChunkResponseMsg,
+        peer_id: PeerId,
     ) -> Result<(), Error> {
-        if self.insert_header_if_not_exists_and_process_cached_chunk_forwards(header) {
-            self.try_process_chunk_parts_and_receipts(header)?;
+        let header = self.get_partial_encoded_chunk_header(&response.chunk_hash)?;
+        let (parts, receipts) = self
+            .encode_chunk_body(&header, response.transactions, &response.receipts)
+            .map_err(|err| {
+                let chunk_hash = &response.chunk_hash;
+                warn!(target: "chunks", ?chunk_hash, %err, "Received invalid full chunk");
+                Error::InvalidChunk
+            })?;
+        let partial_chunk =
+            PartialEncodedChunk::new(header, parts, receipts.into_values().collect());
+        self.process_partial_encoded_chunk_from(
+            MaybeValidated::from_validated(partial_chunk),
+            ChunkPartSupplier::Response(peer_id.clone()),
+            Some(peer_id),
+        )?;
+        Ok(())
+    }
+
+    /// Let the ShardsManager know about the chunk header, when encountering that chunk header
+    /// from the block and the chunk is possibly not yet known to the ShardsManager.
+    pub fn process_chunk_header_from_block(
+        &mut self,
+        header: &ShardChunkHeader,
+    ) -> Result<(), Error> {
+        if let Err(err) = self.sample_chunk_availability(header) {
+            debug!(target: "chunks", ?err, "Failed to sample chunk availability");
+        }
+        if self.insert_header_if_not_exists_and_process_cached_chunk_forwards(header) {
+            self.try_process_chunk_parts_and_receipts(header)?;
+        }
+        Ok(())
+    }
+
+    /// Requests a few random parts of the chunk from their owners, if availability sampling is
+    /// enabled and this node doesn't track the shard of the chunk.
+    fn sample_chunk_availability(
+        &mut self,
+        header: &ShardChunkHeader,
+    ) -> Result<(), near_chain::Error> {
+        let chunk_hash = header.chunk_hash();
+        match &self.availability_sampler {
+            Some(sampler) if !sampler.is_sampled(&chunk_hash) => {}
+            _ => return Ok(()),
+        }
+        // The parts of old chunks might not be kept by their owners anymore.
+        let height = header.height_created();
+        if height + CHUNK_REQUEST_PEER_HORIZON < self.chain_head.height {
+            return Ok(());
+        }
+        let prev_block_hash = header.prev_block_hash();
+        let shard_id = header.shard_id();
+        if cares_about_shard_this_or_next_epoch(
+            self.me.as_ref(),
+            prev_block_hash,
+            shard_id,
+            true,
+            self.runtime_adapter.as_ref(),
+        ) {
+            return Ok(());
+        }
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(prev_block_hash)?;
+        let mut parts = vec![];
+        for part_ord in 0..self.rs.total_shard_count() as u64 {
+            let part_owner = self.runtime_adapter.get_part_owner(&epoch_id, part_ord)?;
+            if Some(&part_owner) != self.me.as_ref() {
+                parts.push((part_ord, part_owner));
+            }
+        }
+        let now = self.clock.now();
+        let sampler = self.availability_sampler.as_mut().unwrap();
+        let mut owner_to_parts = HashMap::<_, Vec<u64>>::new();
+        for (part_ord, part_owner) in sampler.start(now, header, parts) {
+            owner_to_parts.entry(part_owner).or_default().push(part_ord);
+        }
+        for (part_owner, part_ords) in owner_to_parts {
+            debug!(target: "chunks", ?chunk_hash, ?part_ords, ?part_owner, "Sampling chunk parts");
+            let request = PartialEncodedChunkRequestMsg {
+                chunk_hash: chunk_hash.clone(),
+                part_ords,
+                tracking_shards: HashSet::new(),
+            };
+            let target = AccountIdOrPeerTrackingShard {
+                account_id: Some(part_owner),
+                prefer_peer: false,
+                shard_id,
+                only_archival: false,
+                min_height: height.saturating_sub(CHUNK_REQUEST_PEER_HORIZON),
+            };
+            self.peer_manager_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::PartialEncodedChunkRequest { target, request, create_time: now },
+            ));
         }
         Ok(())
     }
 
+    /// Reports the sampled chunks whose parts haven't arrived in time as unavailable.
+    pub fn expire_availability_samples(&mut self) {
+        if let Some(sampler) = self.availability_sampler.as_mut() {
+            sampler.expire(self.clock.now());
+        }
+    }
+
     /// Checks if the chunk has all parts and receipts, if so and if the node cares about the shard,
     /// decodes and persists the full chunk
     /// `header`: header of the chunk. It must be known by `ShardsManager`, either
@@ -1543,17 +2237,6 @@ impl ShardsManager {
             header.shard_id(),
         )?;
 
-        if have_all_parts {
-            if self.encoded_chunks.mark_chunk_for_inclusion(&chunk_hash) {
-                self.client_adapter.send(ShardsManagerResponse::ChunkHeaderReadyForInclusion {
-                    chunk_header: header.clone(),
-                    chunk_producer,
-                });
-            }
-        }
-        // we can safely unwrap here because we already checked that chunk_hash exist in encoded_chunks
-        let entry = self.encoded_chunks.get(&chunk_hash).unwrap();
-
         let cares_about_shard = cares_about_shard_this_or_next_epoch(
             self.me.as_ref(),
             &prev_block_hash,
@@ -1562,6 +2245,21 @@ impl ShardsManager {
             self.runtime_adapter.as_ref(),
         );
 
+        // With pre-validation, a chunk of a tracked shard is ready for inclusion only once it
+        // has been decoded, which checks its transactions and receipts against the header.
+        // A chunk of another shard additionally waits for all the receipts this node needs,
+        // whose proofs have been checked on arrival.
+        let ready_for_inclusion = if self.pre_validate_chunks {
+            have_all_parts && have_all_receipts && !cares_about_shard
+        } else {
+            have_all_parts
+        };
+        if ready_for_inclusion {
+            self.mark_chunk_ready_for_inclusion(header, chunk_producer.clone());
+        }
+        // we can safely unwrap here because we already checked that chunk_hash exist in encoded_chunks
+        let entry = self.encoded_chunks.get(&chunk_hash).unwrap();
+
         if !cares_about_shard && have_all_parts && have_all_receipts {
             // If we don't care about the shard, we only need the parts and the receipts that we
             // own, before marking the chunk as completed.
@@ -1593,8 +2291,19 @@ impl ShardsManager {
             }
 
             let (shard_chunk, partial_chunk) = self
-                .decode_encoded_chunk_if_complete(encoded_chunk)?
+                .decode_encoded_chunk_if_complete(encoded_chunk)
+                .map_err(|err| {
+                    if self.pre_validate_chunks {
+                        metrics::CHUNK_PRE_VALIDATION_FAILURES
+                            .with_label_values(&[&header.shard_id().to_string()])
+                            .inc();
+                    }
+                    err
+                })?
                 .expect("decoding shouldn't fail");
+            if self.pre_validate_chunks {
+                self.mark_chunk_ready_for_inclusion(header, chunk_producer);
+            }
 
             // For consistency, only persist shard_chunk if we actually care about the shard.
             // Don't persist if we don't care about the shard, even if we accidentally got enough
@@ -1609,6 +2318,21 @@ impl ShardsManager {
         Ok(ProcessPartialEncodedChunkResult::NeedMorePartsOrReceipts)
     }
 
+    /// Notifies the client that the chunk can be included in a block, unless it has already
+    /// been notified.
+    fn mark_chunk_ready_for_inclusion(
+        &mut self,
+        header: &ShardChunkHeader,
+        chunk_producer: AccountId,
+    ) {
+        if self.encoded_chunks.mark_chunk_for_inclusion(&header.chunk_hash()) {
+            self.client_adapter.send(ShardsManagerResponse::ChunkHeaderReadyForInclusion {
+                chunk_header: header.clone(),
+                chunk_producer,
+            });
+        }
+    }
+
     /// A helper function to be called after a chunk is considered complete
     fn complete_chunk(
         &mut self,
@@ -1616,12 +2340,15 @@ impl ShardsManager {
         shard_chunk: Option<ShardChunk>,
     ) {
         let chunk_hash = partial_chunk.chunk_hash();
-        self.encoded_chunks.mark_entry_complete(&chunk_hash);
+        let stats = self.encoded_chunks.mark_entry_complete(&chunk_hash, self.clock.now());
         self.encoded_chunks.remove_from_cache_if_outside_horizon(&chunk_hash);
         self.requested_partial_encoded_chunks.remove(&chunk_hash);
-        debug!(target: "chunks", "Completed chunk {:?}", chunk_hash);
-        self.client_adapter
-            .send(ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk });
+        debug!(target: "chunks", ?chunk_hash, ?stats, "Completed chunk");
+        self.client_adapter.send(ShardsManagerResponse::ChunkCompleted {
+            partial_chunk,
+            shard_chunk,
+            completion_stats: stats,
+        });
     }
 
     /// Try to process chunks in the chunk cache whose previous block hash is `prev_block_hash` and
@@ -1629,15 +2356,38 @@ impl ShardsManager {
     /// This function is needed because chunks in chunk cache will only be marked as complete after
     /// the previous block is accepted. So we need to check if there are any chunks can be marked as
     /// complete when a new block is accepted.
+    /// The orphan chunks whose headers couldn't be validated before are processed first.
     pub fn check_incomplete_chunks(&mut self, prev_block_hash: &CryptoHash) {
+        for orphan in self.orphan_chunks.remove_by_prev_hash(self.clock.now(), prev_block_hash) {
+            for message in orphan.messages {
+                debug!(target: "chunks", chunk_hash = ?orphan.chunk_hash, ?prev_block_hash, "Processing orphan chunk");
+                if let Err(err) = self.process_partial_encoded_chunk_from(
+                    MaybeValidated::from(PartialEncodedChunk::V2(message.partial_chunk)),
+                    message.supplier,
+                    message.peer_id,
+                ) {
+                    debug!(target: "chunks", chunk_hash = ?orphan.chunk_hash, ?err, "Failed to process orphan chunk");
+                }
+            }
+        }
         let mut chunks_to_process = vec![];
+        let mut headers_to_validate = vec![];
         if let Some(chunk_hashes) = self.encoded_chunks.get_incomplete_chunks(prev_block_hash) {
             for chunk_hash in chunk_hashes {
                 if let Some(entry) = self.encoded_chunks.get(chunk_hash) {
-                    chunks_to_process.push(entry.header.clone());
+                    if entry.header_fully_validated || self.header_validation_pool.is_none() {
+                        chunks_to_process.push(entry.header.clone());
+                    } else {
+                        headers_to_validate.push(entry.header.clone());
+                    }
                 }
             }
         }
+        // The chunks whose headers still need the full validation are processed once their
+        // results come back, in `process_chunk_header_validated`.
+        if let Some(pool) = self.header_validation_pool.as_mut() {
+            pool.submit(headers_to_validate);
+        }
         for header in chunks_to_process {
             debug!(target:"chunks", "try to process incomplete chunk {:?}, prev_block: {:?}", header.chunk_hash(), prev_block_hash);
             if let Err(err) = self.try_process_chunk_parts_and_receipts(&header) {
@@ -1646,6 +2396,40 @@ impl ShardsManager {
         }
     }
 
+    /// Processes the result of the full validation of a chunk header, done by the validation
+    /// pool after the previous block was accepted, and tries to complete the chunk if the
+    /// header is valid.
+    pub fn process_chunk_header_validated(&mut self, validated: ChunkHeaderValidated) {
+        let ChunkHeaderValidated { header, result } = validated;
+        let chunk_hash = header.chunk_hash();
+        if let Some(pool) = self.header_validation_pool.as_mut() {
+            pool.finish(&chunk_hash);
+        }
+        match self.encoded_chunks.get(&chunk_hash) {
+            Some(entry) if !entry.complete => {}
+            // The chunk has been completed or removed in the meantime.
+            _ => return,
+        }
+        match result {
+            Ok(()) => self.encoded_chunks.mark_entry_validated(&chunk_hash),
+            Err(Error::ChainError(err)) => {
+                error!(target: "chunks", ?chunk_hash, ?err, "Failed to validate chunk header");
+                return;
+            }
+            Err(err) => {
+                // the chunk header is invalid
+                // remove this entry from the cache and remove the request from the request pool
+                debug!(target: "chunks", ?chunk_hash, ?err, "Invalid chunk header");
+                self.encoded_chunks.remove(&chunk_hash);
+                self.requested_partial_encoded_chunks.remove(&chunk_hash);
+                return;
+            }
+        }
+        if let Err(err) = self.try_process_chunk_parts_and_receipts(&header) {
+            error!(target:"chunks", "unexpected error processing orphan chunk {:?}", err)
+        }
+    }
+
     /// Send the parts of the partial_encoded_chunk that are owned by `self.me` to the
     /// other validators that are tracking the shard.
     fn send_partial_encoded_chunk_to_chunk_trackers(
@@ -1891,12 +2675,28 @@ impl ShardsManager {
         }
 
         // Add it to the set of chunks to be included in the next block
-        self.encoded_chunks.merge_in_partial_encoded_chunk(&partial_chunk.into());
+        self.encoded_chunks.merge_in_partial_encoded_chunk(
+            &partial_chunk.into(),
+            self.clock.now(),
+            ChunkPartSupplier::ChunkProducer,
+        );
+        // The header of a chunk produced by this node is valid.
+        self.encoded_chunks.mark_entry_validated(&chunk_header.chunk_hash());
         self.encoded_chunks.mark_chunk_for_inclusion(&chunk_header.chunk_hash());
 
         Ok(())
     }
 
+    /// Dumps the in-flight requests and the contents of the chunk cache, for debugging the
+    /// chunks which are missing for too long.
+    pub fn debug_view(&self) -> ShardsManagerView {
+        ShardsManagerView {
+            requests: self.requested_partial_encoded_chunks.view(self.clock.now()),
+            num_incomplete_chunks: self.encoded_chunks.num_incomplete_chunks(),
+            chunk_headers: self.encoded_chunks.chunk_headers_view(),
+        }
+    }
+
     pub fn handle_client_request(&mut self, request: ShardsManagerRequestFromClient) {
         match request {
             ShardsManagerRequestFromClient::ProcessChunkHeaderFromBlock(chunk_header) => {
@@ -1938,8 +2738,15 @@ impl ShardsManager {
 
     pub fn handle_network_request(&mut self, request: ShardsManagerRequestFromNetwork) {
         match request {
-            ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(partial_encoded_chunk) => {
-                if let Err(e) = self.process_partial_encoded_chunk(partial_encoded_chunk.into()) {
+            ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                partial_encoded_chunk,
+                peer_id,
+            } => {
+                if let Err(e) = self.process_partial_encoded_chunk_from(
+                    partial_encoded_chunk.into(),
+                    ChunkPartSupplier::ChunkProducer,
+                    peer_id,
+                ) {
                     warn!(target: "chunks", "Error processing partial encoded chunk: {:?}", e);
                 }
             }
@@ -1954,12 +2761,13 @@ impl ShardsManager {
             }
             ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
                 partial_encoded_chunk_response,
+                peer_id,
                 received_time,
             } => {
                 PARTIAL_ENCODED_CHUNK_RESPONSE_DELAY
                     .observe((self.clock.now() - received_time).as_seconds_f64());
-                if let Err(e) =
-                    self.process_partial_encoded_chunk_response(partial_encoded_chunk_response)
+                if let Err(e) = self
+                    .process_partial_encoded_chunk_response(partial_encoded_chunk_response, peer_id)
                 {
                     warn!(target: "chunks", "Error processing partial encoded chunk response: {:?}", e);
                 }
@@ -1973,6 +2781,26 @@ impl ShardsManager {
                     route_back,
                 );
             }
+            ShardsManagerRequestFromNetwork::ProcessFullChunkResponse {
+                full_chunk_response,
+                peer_id,
+                received_time,
+            } => {
+                PARTIAL_ENCODED_CHUNK_RESPONSE_DELAY
+                    .observe((self.clock.now() - received_time).as_seconds_f64());
+                if let Err(e) = self.process_full_chunk_response(full_chunk_response, peer_id) {
+                    warn!(target: "chunks", "Error processing full chunk response: {:?}", e);
+                }
+            }
+            ShardsManagerRequestFromNetwork::ProcessFullChunkRequest {
+                full_chunk_request,
+                route_back,
+            } => {
+                self.process_full_chunk_request(full_chunk_request, route_back);
+            }
+            ShardsManagerRequestFromNetwork::UpdateAccountRtts(account_rtts) => {
+                self.update_account_rtts(account_rtts);
+            }
         }
     }
 }
@@ -1989,6 +2817,9 @@ pub enum PartialEncodedChunkResponseSource {
     /// We had to look at the ShardChunk column on disk, and if we found it,
     /// reconstructed the partial chunk from it.
     ShardChunkOnDisk,
+    /// We served the whole chunk body from the ShardChunk column on disk, in response
+    /// to a FullChunkRequest.
+    FullChunk,
 }
 
 impl PartialEncodedChunkResponseSource {
@@ -1999,6 +2830,7 @@ impl PartialEncodedChunkResponseSource {
             PartialEncodedChunkResponseSource::InMemoryCache => "cache",
             PartialEncodedChunkResponseSource::PartialChunkOnDisk => "partial",
             PartialEncodedChunkResponseSource::ShardChunkOnDisk => "chunk",
+            PartialEncodedChunkResponseSource::FullChunk => "full_chunk",
         }
     }
 }
@@ -2051,6 +2883,7 @@ mod test {
             ReadOnlyChunksStore::new(store),
             mock_tip.clone(),
             mock_tip,
+            Default::default(),
         );
         let added = clock.now().into();
         shards_manager.requested_partial_encoded_chunks.insert(
@@ -2062,6 +2895,9 @@ mod test {
                 shard_id: 0,
                 added,
                 last_requested: added,
+                retry_duration: CHUNK_REQUEST_RETRY,
+                hedge_at: None,
+                hedges: 0,
             },
         );
         clock.advance(CHUNK_REQUEST_RETRY * 2);
@@ -2093,6 +2929,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         // process chunk part 0
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
@@ -2145,6 +2982,245 @@ mod test {
         assert_eq!(requested_parts, HashSet::new());
     }
 
+    #[test]
+    fn test_adaptive_chunk_request_timeouts() {
+        // Test that the requests to the accounts with a low round trip time are retried sooner,
+        // and that the parts are requested from any peer tracking the shard instead of the
+        // slow accounts.
+        let fixture = ChunkTestFixture::new(true);
+        let clock = FakeClock::default();
+        let mut shards_manager = ShardsManager::new(
+            clock.clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        );
+        let fast: AccountId = "fast".parse().unwrap();
+        let medium: AccountId = "medium".parse().unwrap();
+        let slow: AccountId = "slow".parse().unwrap();
+        shards_manager.update_account_rtts(HashMap::from([
+            (fast.clone(), time::Duration::milliseconds(1)),
+            (medium.clone(), time::Duration::milliseconds(10)),
+            (slow.clone(), time::Duration::seconds(1)),
+        ]));
+        assert_eq!(
+            shards_manager.chunk_request_timeout(Some(&fast), false),
+            CHUNK_REQUEST_RETRY_MIN
+        );
+        let timeout = shards_manager.chunk_request_timeout(Some(&medium), false);
+        assert!(CHUNK_REQUEST_RETRY_MIN < timeout && timeout < CHUNK_REQUEST_RETRY);
+        assert_eq!(shards_manager.chunk_request_timeout(Some(&slow), false), CHUNK_REQUEST_RETRY);
+        assert_eq!(shards_manager.chunk_request_timeout(Some(&fast), true), CHUNK_REQUEST_RETRY);
+        assert_eq!(shards_manager.chunk_request_timeout(None, false), CHUNK_REQUEST_RETRY);
+        assert!(shards_manager.is_slow_account(Some(&slow)));
+        assert!(!shards_manager.is_slow_account(Some(&medium)));
+
+        // A request is retried after its own timeout.
+        let chunk_hash = fixture.mock_chunk_header.chunk_hash();
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
+        let result = shards_manager
+            .process_partial_encoded_chunk(MaybeValidated::from(partial_encoded_chunk))
+            .unwrap();
+        assert_matches!(result, ProcessPartialEncodedChunkResult::NeedBlock);
+        shards_manager.request_chunk_single(
+            &fixture.mock_chunk_header,
+            CryptoHash::default(),
+            false,
+        );
+        let mut targets = HashSet::new();
+        while let Some(r) = fixture.mock_network.pop() {
+            if let NetworkRequests::PartialEncodedChunkRequest { target, .. } =
+                r.as_network_requests_ref()
+            {
+                targets.extend(target.account_id.clone());
+            }
+        }
+        shards_manager.requested_partial_encoded_chunks.set_retry_duration(&chunk_hash, timeout);
+        clock.advance(timeout);
+        assert_eq!(shards_manager.requested_partial_encoded_chunks.fetch(clock.now()).len(), 1);
+
+        // Once all the targets turn out to be slow, the parts are requested from the peers.
+        shards_manager.update_account_rtts(
+            targets
+                .into_iter()
+                .map(|account_id| (account_id, time::Duration::seconds(1)))
+                .collect(),
+        );
+        clock.advance(CHUNK_REQUEST_RETRY);
+        shards_manager.resend_chunk_requests();
+        let mut num_requests = 0;
+        while let Some(r) = fixture.mock_network.pop() {
+            if let NetworkRequests::PartialEncodedChunkRequest { target, .. } =
+                r.as_network_requests_ref()
+            {
+                assert!(target.prefer_peer);
+                num_requests += 1;
+            }
+        }
+        assert!(num_requests > 0);
+    }
+
+    #[test]
+    fn test_hedged_chunk_requests() {
+        // Test that the parts still missing after the hedging delay are requested once more
+        // from any peer tracking the shard, at most `max_hedges` times.
+        let mut fixture = ChunkTestFixture::new(true);
+        let clock = FakeClock::default();
+        let delay = std::time::Duration::from_millis(20);
+        let mut shards_manager = ShardsManager::new(
+            clock.clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        )
+        .with_chunk_request_hedging(Some(ChunkRequestHedgingConfig { delay, max_hedges: 1 }));
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
+        let result = shards_manager
+            .process_partial_encoded_chunk(MaybeValidated::from(partial_encoded_chunk))
+            .unwrap();
+        assert_matches!(result, ProcessPartialEncodedChunkResult::NeedBlock);
+        shards_manager.request_chunk_single(
+            &fixture.mock_chunk_header,
+            CryptoHash::default(),
+            false,
+        );
+        while fixture.mock_network.pop().is_some() {}
+
+        let collect_hedges = |fixture: &mut ChunkTestFixture| -> Vec<Vec<u64>> {
+            let mut hedges = vec![];
+            while let Some(r) = fixture.mock_network.pop() {
+                match r.as_network_requests_ref() {
+                    NetworkRequests::PartialEncodedChunkRequest { target, request, .. } => {
+                        assert!(target.prefer_peer);
+                        hedges.push(request.part_ords.clone());
+                    }
+                    _ => {}
+                }
+            }
+            hedges
+        };
+
+        // Not due yet.
+        shards_manager.send_hedged_chunk_requests();
+        assert_eq!(collect_hedges(&mut fixture), Vec::<Vec<u64>>::new());
+
+        // The part which arrived in the meantime is not requested again.
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[1]);
+        shards_manager
+            .process_partial_encoded_chunk(MaybeValidated::from(partial_encoded_chunk))
+            .unwrap();
+        clock.advance(time::Duration::try_from(delay).unwrap());
+        shards_manager.send_hedged_chunk_requests();
+        assert_eq!(
+            collect_hedges(&mut fixture),
+            vec![(2..fixture.mock_chunk_parts.len() as u64).collect::<Vec<_>>()]
+        );
+
+        // Only a single hedge is allowed.
+        clock.advance(time::Duration::try_from(delay).unwrap());
+        shards_manager.send_hedged_chunk_requests();
+        assert_eq!(collect_hedges(&mut fixture), Vec::<Vec<u64>>::new());
+    }
+
+    #[test]
+    fn test_debug_view() {
+        // Test that the debug view reports the pending request and the incomplete chunk.
+        let fixture = ChunkTestFixture::new(true);
+        let clock = FakeClock::default();
+        let mut shards_manager = ShardsManager::new(
+            clock.clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        );
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
+        let result = shards_manager
+            .process_partial_encoded_chunk(MaybeValidated::from(partial_encoded_chunk))
+            .unwrap();
+        assert_matches!(result, ProcessPartialEncodedChunkResult::NeedBlock);
+        shards_manager.request_chunk_single(
+            &fixture.mock_chunk_header,
+            CryptoHash::default(),
+            false,
+        );
+        clock.advance(time::Duration::milliseconds(100));
+
+        let header = &fixture.mock_chunk_header;
+        let view = shards_manager.debug_view();
+        assert_eq!(view.requests.len(), 1);
+        assert_eq!(view.requests[0].chunk_hash, header.chunk_hash());
+        assert_eq!(view.requests[0].shard_id, header.shard_id());
+        assert_eq!(view.requests[0].requested_ms_ago, 100);
+        assert_eq!(view.num_incomplete_chunks, HashMap::from([(header.shard_id(), 1)]));
+        assert_eq!(view.chunk_headers.len(), 1);
+        assert_eq!(view.chunk_headers[0].prev_block_hash, *header.prev_block_hash());
+        assert_eq!(view.chunk_headers[0].chunks.len(), 1);
+        let chunk = &view.chunk_headers[0].chunks[0];
+        assert_eq!(chunk.chunk_hash, header.chunk_hash());
+        assert_eq!(chunk.num_parts, 1);
+        assert!(!chunk.complete);
+    }
+
+    #[test]
+    fn test_receipt_proof_cache() {
+        // Test that the verified receipt proofs are cached by chunk and receiving shard, and
+        // that different receipts for the same shard are still verified.
+        let fixture = ChunkTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            FakeClock::default().clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        );
+        let header = &fixture.mock_chunk_header;
+        let proofs: Vec<ReceiptProof> = make_outgoing_receipts_proofs(
+            header,
+            &fixture.mock_outgoing_receipts,
+            fixture.mock_runtime.as_ref(),
+        )
+        .unwrap()
+        .collect();
+        for proof in &proofs {
+            assert!(shards_manager.verify_receipt_proof(header, proof));
+        }
+        assert_eq!(shards_manager.verified_receipt_proofs.len(), proofs.len());
+
+        // The repeated proofs are served from the cache: the merkle path is not checked again.
+        for proof in &proofs {
+            let mut repeated = proof.clone();
+            repeated.1.proof = vec![near_primitives::merkle::MerklePathItem {
+                hash: CryptoHash::default(),
+                direction: near_primitives::merkle::Direction::Left,
+            }];
+            assert!(shards_manager.verify_receipt_proof(header, &repeated));
+        }
+        assert_eq!(shards_manager.verified_receipt_proofs.len(), proofs.len());
+
+        let receipt = Receipt::new_balance_refund(&fixture.mock_shard_tracker, 1);
+        let forged_proof = ReceiptProof(vec![receipt], proofs[0].1.clone());
+        assert!(!shards_manager.verify_receipt_proof(header, &forged_proof));
+    }
+
     #[test]
     fn test_invalid_chunk() {
         // Test that process_partial_encoded_chunk will reject invalid chunk
@@ -2158,6 +3234,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
 
         // part id > num parts
@@ -2187,6 +3264,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let result = shards_manager
@@ -2239,6 +3317,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let count_num_forward_msgs = |fixture: &ChunkTestFixture| {
             fixture
@@ -2306,6 +3385,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         shards_manager.insert_header_if_not_exists_and_process_cached_chunk_forwards(
             &fixture.mock_chunk_header,
@@ -2391,6 +3471,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let _ = shards_manager
@@ -2476,6 +3557,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let (most_parts, other_parts) = {
             let mut most_parts = fixture.mock_chunk_parts.clone();
@@ -2544,6 +3626,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
             &fixture.mock_chunk_header,
@@ -2592,6 +3675,156 @@ mod test {
             .is_none());
     }
 
+    #[test]
+    // Test that the forwarded parts which were seen before, or which belong to a complete chunk,
+    // are dropped without being processed again.
+    fn test_duplicate_forwards_skipped() {
+        let fixture = ChunkTestFixture::default();
+        let clock = FakeClock::default();
+        let mut shards_manager = ShardsManager::new(
+            clock.clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        );
+        let skipped = |reason: &str| {
+            metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS.with_label_values(&[reason]).get()
+        };
+        let seen_before = skipped("seen");
+        let complete_before = skipped("complete");
+        let (most_parts, other_parts) = {
+            let mut most_parts = fixture.mock_chunk_parts.clone();
+            let n = most_parts.len();
+            let other_parts = most_parts.split_off(n - (n / 4));
+            (most_parts, other_parts)
+        };
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &fixture.mock_chunk_header,
+            most_parts.clone(),
+        );
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert_eq!(skipped("seen"), seen_before);
+
+        // Another validator forwards the same parts.
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward).is_ok());
+        assert_eq!(skipped("seen"), seen_before + most_parts.len() as u64);
+
+        // Only the parts which weren't seen yet are kept.
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &fixture.mock_chunk_header,
+            fixture.mock_chunk_parts.clone(),
+        );
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward).is_ok());
+        assert_eq!(skipped("seen"), seen_before + 2 * most_parts.len() as u64);
+
+        shards_manager.insert_header_if_not_exists_and_process_cached_chunk_forwards(
+            &fixture.mock_chunk_header,
+        );
+        let process_result = shards_manager
+            .try_process_chunk_parts_and_receipts(&fixture.mock_chunk_header)
+            .unwrap();
+        assert_matches!(process_result, ProcessPartialEncodedChunkResult::HaveAllPartsAndReceipts);
+
+        // Once the chunk is complete, none of its forwarded parts are needed.
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &fixture.mock_chunk_header,
+            other_parts.clone(),
+        );
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward).is_ok());
+        assert_eq!(skipped("complete"), complete_before + other_parts.len() as u64);
+        assert_eq!(skipped("seen"), seen_before + 2 * most_parts.len() as u64);
+    }
+
+    #[test]
+    // Test that the forwarded parts dropped from the cache before the header of their chunk is
+    // known are accepted again.
+    fn test_forwards_evicted_from_cache_not_skipped() {
+        let fixture = ChunkTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            FakeClock::default().clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        );
+        let skipped = || {
+            metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS.with_label_values(&["seen"]).get()
+        };
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &fixture.mock_chunk_header,
+            fixture.mock_chunk_parts.clone(),
+        );
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert!(shards_manager.chunk_forwards_cache.pop(&forward.chunk_hash).is_some());
+
+        let seen_before = skipped();
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert_eq!(skipped(), seen_before);
+        assert_eq!(
+            shards_manager.chunk_forwards_cache.peek(&forward.chunk_hash).map(|parts| parts.len()),
+            Some(forward.parts.len())
+        );
+
+        // Once merged, the parts are skipped even though the cache no longer has them.
+        shards_manager.insert_header_if_not_exists_and_process_cached_chunk_forwards(
+            &fixture.mock_chunk_header,
+        );
+        assert!(!shards_manager.chunk_forwards_cache.contains(&forward.chunk_hash));
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert_eq!(skipped(), seen_before + forward.parts.len() as u64);
+    }
+
+    #[test]
+    // Test that in the chunk-only producer lightweight mode the forwarded parts are only kept
+    // for the shards the node cares about.
+    fn test_lightweight_mode_drops_untracked_forwards() {
+        let fixture = ChunkTestFixture::default();
+        let make_shards_manager = |me: Option<AccountId>| {
+            ShardsManager::new(
+                FakeClock::default().clock(),
+                me,
+                fixture.mock_runtime.clone(),
+                fixture.mock_network.as_sender(),
+                fixture.mock_client_adapter.as_sender(),
+                fixture.chain_store.new_read_only_chunks_store(),
+                fixture.mock_chain_head.clone(),
+                fixture.mock_chain_head.clone(),
+                Default::default(),
+            )
+            .with_chunk_only_producer_lightweight_mode(true)
+        };
+        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &fixture.mock_chunk_header,
+            fixture.mock_chunk_parts.clone(),
+        );
+        let skipped_before = metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS
+            .with_label_values(&["untracked_shard"])
+            .get();
+
+        let mut shards_manager = make_shards_manager(None);
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert!(!shards_manager.chunk_forwards_cache.contains(&forward.chunk_hash));
+        assert_eq!(
+            metrics::PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS
+                .with_label_values(&["untracked_shard"])
+                .get(),
+            skipped_before + forward.parts.len() as u64
+        );
+
+        let mut shards_manager = make_shards_manager(Some(fixture.mock_shard_tracker.clone()));
+        assert!(shards_manager.process_partial_encoded_chunk_forward(forward.clone()).is_ok());
+        assert!(shards_manager.chunk_forwards_cache.contains(&forward.chunk_hash));
+    }
+
     #[test]
     fn test_chunk_cache_hit_for_produced_chunk() {
         let fixture = ChunkTestFixture::default();
@@ -2604,6 +3837,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
 
         shards_manager
@@ -2637,6 +3871,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
 
         shards_manager
@@ -2667,6 +3902,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
 
         persist_chunk(
@@ -2698,6 +3934,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
 
         let mut update = fixture.chain_store.store_update();
@@ -2716,6 +3953,53 @@ mod test {
         assert_eq!(response.parts.len(), fixture.all_part_ords.len());
     }
 
+    #[test]
+    fn test_full_chunk_response() {
+        let mut fixture = ChunkTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            FakeClock::default().clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        )
+        .with_full_chunk_requests(FullChunkRequestsConfig::default());
+        let chunk_hash = fixture.mock_chunk_header.chunk_hash();
+        assert!(shards_manager.prepare_full_chunk_response(&chunk_hash).is_none());
+
+        let mut update = fixture.chain_store.store_update();
+        let shard_chunk =
+            fixture.mock_encoded_chunk.decode_chunk(fixture.mock_runtime.num_data_parts()).unwrap();
+        update.save_chunk(shard_chunk);
+        update.commit().unwrap();
+
+        // The served body is encoded back into all of the parts of the chunk.
+        let response = shards_manager.prepare_full_chunk_response(&chunk_hash).unwrap();
+        let (parts, _) = shards_manager
+            .encode_chunk_body(
+                &fixture.mock_chunk_header,
+                response.transactions.clone(),
+                &response.receipts,
+            )
+            .unwrap();
+        assert_eq!(parts.len(), fixture.all_part_ords.len());
+
+        // A body which doesn't match the header is rejected.
+        let mut receipts = response.receipts.clone();
+        receipts.push(Receipt::new_balance_refund(&"test".parse().unwrap(), 1));
+        assert!(shards_manager
+            .encode_chunk_body(&fixture.mock_chunk_header, response.transactions, &receipts)
+            .is_err());
+
+        // Chunks larger than the limit are not served.
+        shards_manager.full_chunk_requests.max_response_size = 0;
+        assert!(shards_manager.prepare_full_chunk_response(&chunk_hash).is_none());
+    }
+
     #[test]
     fn test_chunk_response_combining_cache_and_partial_chunks() {
         let mut fixture = ChunkTestFixture::default();
@@ -2728,6 +4012,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         // Split the part ords into two groups.
         assert!(fixture.all_part_ords.len() >= 2);
@@ -2766,6 +4051,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         // Only add half of the parts to the cache.
         assert!(fixture.all_part_ords.len() >= 2);
@@ -2808,6 +4094,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         // Split the part ords into three groups; put one in cache, the second in partial
         // and the third is missing. We should return the first two groups.
@@ -2848,6 +4135,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let (source, response) =
             shards_manager.prepare_partial_encoded_chunk_response(PartialEncodedChunkRequestMsg {
@@ -2871,6 +4159,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let (source, response) =
             shards_manager.prepare_partial_encoded_chunk_response(PartialEncodedChunkRequestMsg {
@@ -2894,6 +4183,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let mut update = fixture.chain_store.store_update();
         let shard_chunk =
@@ -2925,6 +4215,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let mut update = fixture.chain_store.store_update();
         let shard_chunk =
@@ -2954,6 +4245,7 @@ mod test {
             fixture.chain_store.new_read_only_chunks_store(),
             fixture.mock_chain_head.clone(),
             fixture.mock_chain_head.clone(),
+            Default::default(),
         );
         let part = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         shards_manager.process_partial_encoded_chunk(part.clone().into()).unwrap();
@@ -2963,4 +4255,114 @@ mod test {
         shards_manager.process_partial_encoded_chunk(part.into()).unwrap();
         assert_eq!(fixture.count_chunk_ready_for_inclusion_messages(), 0);
     }
+
+    #[test]
+    fn test_pre_validated_chunk_reported_for_inclusion_once_decoded() {
+        let fixture = ChunkTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            FakeClock::default().clock(),
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        )
+        .with_chunk_pre_validation(true);
+        let epoch_id = fixture
+            .mock_runtime
+            .get_epoch_id_from_prev_block(fixture.mock_chunk_header.prev_block_hash())
+            .unwrap();
+        let own_part_ords: Vec<u64> = fixture
+            .all_part_ords
+            .iter()
+            .copied()
+            .filter(|part_ord| {
+                fixture.mock_runtime.get_part_owner(&epoch_id, *part_ord).unwrap()
+                    == fixture.mock_shard_tracker
+            })
+            .collect();
+        // The shard is tracked, so the own parts are not enough, the chunk has to be decoded.
+        let part = fixture.make_partial_encoded_chunk(&own_part_ords);
+        shards_manager.process_partial_encoded_chunk(part.into()).unwrap();
+        assert_eq!(fixture.count_chunk_ready_for_inclusion_messages(), 0);
+
+        let part = fixture.make_partial_encoded_chunk(&fixture.all_part_ords);
+        shards_manager.process_partial_encoded_chunk(part.into()).unwrap();
+        assert_eq!(fixture.count_chunk_ready_for_inclusion_messages(), 1);
+    }
+
+    #[test]
+    fn test_chunk_availability_sampling() {
+        let fixture = ChunkTestFixture::default();
+        let clock = FakeClock::default();
+        let config = ChunkAvailabilitySamplingConfig {
+            num_parts: 2,
+            timeout: std::time::Duration::from_secs(1),
+        };
+        // Doesn't track any shard.
+        let mut shards_manager = ShardsManager::new(
+            clock.clock(),
+            None,
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.as_sender(),
+            fixture.mock_client_adapter.as_sender(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            fixture.mock_chain_head.clone(),
+            fixture.mock_chain_head.clone(),
+            Default::default(),
+        )
+        .with_availability_sampling(Some(config));
+        let shard_id = fixture.mock_chunk_header.shard_id().to_string();
+        let samples = |result| {
+            metrics::CHUNK_AVAILABILITY_SAMPLES.with_label_values(&[&shard_id, result]).get()
+        };
+        let unavailable_before = samples("unavailable");
+
+        shards_manager.handle_client_request(
+            ShardsManagerRequestFromClient::ProcessChunkHeaderFromBlock(
+                fixture.mock_chunk_header.clone(),
+            ),
+        );
+        let mut sampled_part_ords = vec![];
+        while let Some(request) = fixture.mock_network.pop() {
+            if let PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::PartialEncodedChunkRequest { request, .. },
+            ) = request
+            {
+                sampled_part_ords.extend(request.part_ords);
+            }
+        }
+        assert_eq!(sampled_part_ords.len(), 2);
+
+        // The header of the chunk repeated in the next block isn't sampled again.
+        shards_manager.handle_client_request(
+            ShardsManagerRequestFromClient::ProcessChunkHeaderFromBlock(
+                fixture.mock_chunk_header.clone(),
+            ),
+        );
+        assert!(fixture.mock_network.pop().is_none());
+
+        // Only one of the sampled parts arrives.
+        let partial_chunk = fixture.make_partial_encoded_chunk(&sampled_part_ords[..1]);
+        shards_manager.handle_network_request(
+            ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
+                partial_encoded_chunk_response: PartialEncodedChunkResponseMsg {
+                    chunk_hash: fixture.mock_chunk_header.chunk_hash(),
+                    parts: partial_chunk.parts().to_vec(),
+                    receipts: vec![],
+                },
+                peer_id: PeerId::random(),
+                received_time: clock.now().into(),
+            },
+        );
+        shards_manager.expire_availability_samples();
+        assert_eq!(samples("unavailable"), unavailable_before);
+
+        clock.advance(time::Duration::seconds(2));
+        shards_manager.expire_availability_samples();
+        assert_eq!(samples("unavailable"), unavailable_before + 1);
+    }
 }
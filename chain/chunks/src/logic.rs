@@ -1,5 +1,7 @@
+use near_chain::near_chain_primitives::error::Error::DBNotFoundErr;
 use near_chain::{
-    validate::validate_chunk_proofs, Chain, ChainStore, RuntimeWithEpochManagerAdapter,
+    byzantine_assert, validate::validate_chunk_proofs, Chain, ChainStore,
+    RuntimeWithEpochManagerAdapter,
 };
 use near_chunks_primitives::Error;
 use near_primitives::{
@@ -10,7 +12,7 @@ use near_primitives::{
         EncodedShardChunk, PartialEncodedChunk, PartialEncodedChunkPart, PartialEncodedChunkV1,
         PartialEncodedChunkV2, ReceiptProof, ShardChunk, ShardChunkHeader, ShardProof,
     },
-    types::{AccountId, ShardId},
+    types::{AccountId, EpochId, ShardId},
 };
 use tracing::log::{debug, error};
 
@@ -34,6 +36,47 @@ pub fn need_part(
     Ok(Some(&runtime_adapter.get_part_owner(&epoch_id, part_ord)?) == me)
 }
 
+/// Checks the signature and the protocol version of the chunk header in the epoch `epoch_id`,
+/// whose validators are looked up from `ancestor_hash`. If `epoch_id_confirmed` is false, the
+/// epoch may be the wrong one, so a failed check only means that we don't have all the needed
+/// information yet and DBNotFoundErr is returned instead of the header being invalid.
+pub fn validate_chunk_header_in_epoch(
+    header: &ShardChunkHeader,
+    epoch_id: &EpochId,
+    ancestor_hash: &CryptoHash,
+    epoch_id_confirmed: bool,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+) -> Result<(), Error> {
+    // 1. check signature
+    match runtime_adapter.verify_chunk_header_signature(header, epoch_id, ancestor_hash) {
+        Ok(false) => {
+            return if epoch_id_confirmed {
+                byzantine_assert!(false);
+                Err(Error::InvalidChunkSignature)
+            } else {
+                // we are not sure if we are using the correct epoch id for validation, so
+                // we can't be sure if the chunk header is actually invalid. Let's return
+                // DbNotFoundError for now, which means we don't have all needed information yet
+                Err(DBNotFoundErr(format!("block {:?}", header.prev_block_hash())).into())
+            };
+        }
+        Ok(true) => (),
+        Err(chain_error) => {
+            return Err(chain_error.into());
+        }
+    }
+
+    // 2. check protocol version
+    let protocol_version = runtime_adapter.get_epoch_protocol_version(epoch_id)?;
+    if header.valid_for(protocol_version) {
+        Ok(())
+    } else if epoch_id_confirmed {
+        Err(Error::InvalidChunkHeader)
+    } else {
+        Err(DBNotFoundErr(format!("block {:?}", header.prev_block_hash())).into())
+    }
+}
+
 pub fn cares_about_shard_this_or_next_epoch(
     account_id: Option<&AccountId>,
     parent_hash: &CryptoHash,
@@ -1,4 +1,8 @@
-use near_o11y::metrics::{exponential_buckets, try_create_histogram, Counter, Histogram};
+use near_o11y::metrics::{
+    exponential_buckets, try_create_histogram, try_create_histogram_with_buckets,
+    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge,
+    try_create_int_gauge_vec, Counter, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
 use once_cell::sync::Lazy;
 
 pub static PARTIAL_ENCODED_CHUNK_REQUEST_PROCESSING_TIME: Lazy<near_o11y::metrics::HistogramVec> =
@@ -63,3 +67,248 @@ pub static PARTIAL_ENCODED_CHUNK_FORWARD_CACHED_WITHOUT_PREV_BLOCK: Lazy<Counter
     .unwrap()
     },
 );
+
+pub(crate) static CHUNK_CACHE_DISCARDED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_cache_discarded_total",
+        "Number of unrequested partial encoded chunks discarded because their height was outside of the horizon, by whether they were ahead of or behind the head",
+        &["reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_CACHE_DISCARDED_THEN_REQUESTED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_chunk_cache_discarded_then_requested_total",
+        "Number of chunks which were requested after we had discarded a partial encoded chunk for them as out of horizon",
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_CACHE_MAX_HEIGHTS_AHEAD: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_chunk_cache_max_heights_ahead",
+        "Current front horizon of the chunk cache, i.e. how many heights ahead of the head we accept chunks",
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_CACHE_SIZE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_chunk_cache_size_bytes",
+        "Size of the parts and receipts held by the chunk cache",
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_CACHE_INCOMPLETE_CHUNKS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_chunk_cache_incomplete_chunks",
+        "Number of chunks in the chunk cache which are still missing parts or receipts, by shard",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_CACHE_EVICTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_cache_evicted_total",
+        "Number of incomplete chunks dropped from the chunk cache to stay within its caps, by the cap which has been exceeded",
+        &["reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_REQUEST_HEDGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_request_hedges_total",
+        "Number of the hedged chunk part requests, by whether they were sent or cancelled because the parts had arrived",
+        &["result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_REQUEST_DUPLICATE_PARTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_request_duplicate_parts_total",
+        "Number of the chunk parts received in responses which were known already, by whether the chunk was requested with hedging",
+        &["hedged"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_REQUEST_TIMEOUT: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_chunk_request_timeout_seconds",
+        "Time after which the chunk part requests are retried, adapted to the round trip time to their targets",
+        exponential_buckets(0.01, 1.5, 10).unwrap(),
+    )
+    .unwrap()
+});
+
+pub(crate) static TRANSACTION_POOL_SIZE_BY_SHARD: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_transaction_pool_entries_by_shard",
+        "Number of transactions in the pool of the shard",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_PRE_VALIDATION_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_pre_validation_failures_total",
+        "Number of the chunks kept out of the produced blocks, because their body didn't match the header",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_AVAILABILITY_SAMPLES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_availability_samples_total",
+        "Number of the chunks of untracked shards sampled for data availability, by whether all the sampled parts arrived in time",
+        &["shard_id", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_AVAILABILITY_MISSING_PARTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_availability_missing_parts_total",
+        "Number of the sampled chunk parts which didn't arrive in time",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_AVAILABILITY_SAMPLE_LATENCY: Lazy<near_o11y::metrics::HistogramVec> =
+    Lazy::new(|| {
+        near_o11y::metrics::try_create_histogram_vec(
+            "near_chunk_availability_sample_latency_seconds",
+            "Time until all the sampled parts of an available chunk arrived",
+            &["shard_id"],
+            Some(exponential_buckets(0.01, 2.0, 12).unwrap()),
+        )
+        .unwrap()
+    });
+
+pub(crate) static CHUNK_COMPLETION_TIME: Lazy<near_o11y::metrics::HistogramVec> = Lazy::new(|| {
+    near_o11y::metrics::try_create_histogram_vec(
+            "near_chunk_completion_time_seconds",
+            "Time from receiving the first part or receipt of a chunk to having all the needed parts and receipts",
+            &["shard_id"],
+            Some(exponential_buckets(0.01, 2.0, 12).unwrap()),
+        )
+        .unwrap()
+});
+
+pub(crate) static CHUNK_COMPLETION_REQUESTS: Lazy<near_o11y::metrics::HistogramVec> = Lazy::new(
+    || {
+        near_o11y::metrics::try_create_histogram_vec(
+            "near_chunk_completion_requests",
+            "Number of the part requests sent for a chunk until it had all the needed parts and receipts",
+            &["shard_id"],
+            Some(vec![0., 1., 2., 3., 5., 8., 13., 21.]),
+        )
+        .unwrap()
+    },
+);
+
+pub(crate) static CHUNK_COMPLETION_PARTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_completion_parts_total",
+        "Number of the parts of the completed chunks, by where they came from: the chunk producer, a forward from another validator or a response to a request",
+        &["shard_id", "supplier"],
+    )
+    .unwrap()
+});
+
+pub(crate) static PARTIAL_ENCODED_CHUNK_FORWARD_SKIPPED_PARTS: Lazy<IntCounterVec> = Lazy::new(
+    || {
+        try_create_int_counter_vec(
+        "near_partial_encoded_chunk_forward_skipped_parts_total",
+        "Number of the forwarded chunk parts dropped without validation, by whether the chunk was complete already, the same part had been forwarded before or the shard isn't tracked",
+        &["reason"],
+    )
+    .unwrap()
+    },
+);
+
+pub(crate) static ORPHAN_CHUNKS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_orphan_chunks",
+        "Number of chunks waiting for their previous block to be accepted",
+    )
+    .unwrap()
+});
+
+pub(crate) static ORPHAN_CHUNKS_SIZE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_orphan_chunks_size_bytes",
+        "Total size of the partial encoded chunks of orphan chunks waiting for validation",
+    )
+    .unwrap()
+});
+
+pub(crate) static ORPHAN_CHUNKS_EVICTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_orphan_chunks_evicted_total",
+        "Number of chunks removed from the orphan chunk pool before their previous block was accepted, by whether they were there for too long, fell behind the chain head, or the pool had too many chunks or bytes",
+        &["reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static ORPHAN_CHUNK_MESSAGES_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_orphan_chunk_messages_dropped_total",
+        "Number of the partial encoded chunks of orphan chunks dropped, by whether the chunk was behind the chain head, too many of its parts were waiting for validation or the peer sent too many bytes of them",
+        &["reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static ORPHAN_CHUNK_DWELL_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_orphan_chunk_dwell_time_seconds",
+        "Time the chunks spent in the orphan chunk pool until their previous block was accepted",
+        exponential_buckets(0.01, 2.0, 14).unwrap(),
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_HEADER_VALIDATION_TIME: Lazy<near_o11y::metrics::HistogramVec> =
+    Lazy::new(|| {
+        near_o11y::metrics::try_create_histogram_vec(
+            "near_chunk_header_validation_time_seconds",
+            "Time of the full validation of a chunk header, once its previous block is accepted",
+            &["shard_id"],
+            Some(exponential_buckets(0.0001, 2.0, 14).unwrap()),
+        )
+        .unwrap()
+    });
+
+pub(crate) static CHUNK_HEADERS_PENDING_VALIDATION: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_chunk_headers_pending_validation",
+        "Number of the chunk headers submitted to the validation pool whose results haven't been processed yet",
+    )
+    .unwrap()
+});
+
+pub(crate) static RECEIPT_PROOF_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_receipt_proof_cache_hits_total",
+        "Number of the receipt proofs whose verification was skipped because the same receipts of the chunk had been verified already",
+    )
+    .unwrap()
+});
+
+pub(crate) static RECEIPT_PROOF_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_receipt_proof_cache_misses_total",
+        "Number of the receipt proofs verified against the outgoing receipts root of the chunk",
+    )
+    .unwrap()
+});
@@ -0,0 +1,409 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use borsh::BorshSerialize;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::sharding::{ChunkHash, PartialEncodedChunkV2, ShardChunkHeader};
+use near_primitives::time;
+use near_primitives::types::BlockHeight;
+
+use crate::chunk_cache::ChunkPartSupplier;
+use crate::metrics;
+
+// This file implements OrphanChunkPool, which keeps track of the chunks whose previous block
+// hasn't been accepted yet, similarly to the pool of orphan blocks in the chain.
+// Such a chunk can't be completed before its previous block is accepted. Its parts and receipts
+// are merged into the EncodedChunksCache if the header could be validated against the epoch of
+// the chain head. Otherwise, the messages carrying them are kept in the pool, to be validated
+// and processed once the previous block is accepted.
+// When a block is accepted, the chunks building on it are taken out of the pool with
+// `remove_by_prev_hash`.
+// Neither the headers nor the signatures of the kept messages are validated, so the pool is
+// bounded by the number of chunks, by the bytes of the kept messages in total and by the bytes
+// of the messages received from any single peer. Only the chunks above the chain head are kept,
+// as the previous block of any other chunk should be known already. Once the pool is full, the
+// chunks which are in it for too long are removed, then the ones which fell behind the chain
+// head, and then the ones farthest from the next height, as the chunks building on the block
+// which is about to be accepted are the most likely to be needed soon.
+
+/// Maximum number of chunks in the pool.
+const MAX_ORPHAN_CHUNKS: usize = 1024;
+/// Maximum number of the messages waiting for validation which are kept for a chunk.
+const MAX_ORPHAN_CHUNK_MESSAGES: usize = 16;
+/// Maximum total size of the messages waiting for validation.
+const MAX_ORPHAN_CHUNK_BYTES: u64 = 64 * 1024 * 1024;
+/// Maximum total size of the messages waiting for validation received from a single peer.
+/// The messages of which the sender isn't known share a single such budget.
+const MAX_ORPHAN_CHUNK_BYTES_PER_PEER: u64 = 8 * 1024 * 1024;
+/// Chunks which are in the pool for longer than this are removed first once the pool is full.
+const MAX_ORPHAN_CHUNK_AGE: time::Duration = time::Duration::seconds(300);
+
+pub(crate) struct OrphanChunkMessage {
+    pub partial_chunk: PartialEncodedChunkV2,
+    pub supplier: ChunkPartSupplier,
+    /// Peer from which the message was received, if known.
+    pub peer_id: Option<PeerId>,
+    size_bytes: u64,
+}
+
+pub(crate) struct OrphanChunk {
+    pub chunk_hash: ChunkHash,
+    pub height: BlockHeight,
+    pub prev_block_hash: CryptoHash,
+    /// When the chunk was added to the pool.
+    pub added: time::Instant,
+    /// Messages with the parts and receipts of the chunk which couldn't be validated without
+    /// the previous block.
+    pub messages: Vec<OrphanChunkMessage>,
+}
+
+#[derive(Default)]
+pub(crate) struct OrphanChunkPool {
+    orphans: HashMap<ChunkHash, OrphanChunk>,
+    /// A map from block heights to the orphan chunks at the height.
+    /// It's used to evict orphan chunks when the pool is saturated.
+    height_idx: BTreeMap<BlockHeight, HashSet<ChunkHash>>,
+    /// A map from block hashes to the orphan chunks whose previous block is the block.
+    /// It's used to find the orphan chunks which can be processed when a block is accepted.
+    prev_hash_idx: HashMap<CryptoHash, HashSet<ChunkHash>>,
+    /// Total size of the kept messages.
+    total_bytes: u64,
+    /// Size of the kept messages by the peer they were received from.
+    bytes_by_peer: HashMap<Option<PeerId>, u64>,
+}
+
+impl OrphanChunkPool {
+    /// Adds the chunk to the pool, unless it is there already, together with the message
+    /// which couldn't be validated yet, if any. `head_height` is the height of the chain head,
+    /// the chunks at or below which are not kept.
+    pub fn add(
+        &mut self,
+        now: time::Instant,
+        head_height: BlockHeight,
+        header: &ShardChunkHeader,
+        message: Option<(PartialEncodedChunkV2, ChunkPartSupplier, Option<PeerId>)>,
+    ) {
+        if header.height_created() <= head_height {
+            if message.is_some() {
+                metrics::ORPHAN_CHUNK_MESSAGES_DROPPED.with_label_values(&["behind_head"]).inc();
+            }
+            return;
+        }
+        let message = message
+            .map(|(partial_chunk, supplier, peer_id)| {
+                let size_bytes = partial_chunk.try_to_vec().map_or(0, |data| data.len() as u64);
+                OrphanChunkMessage { partial_chunk, supplier, peer_id, size_bytes }
+            })
+            .filter(|message| {
+                let peer_bytes = self.bytes_by_peer.get(&message.peer_id).copied().unwrap_or(0);
+                if peer_bytes + message.size_bytes > MAX_ORPHAN_CHUNK_BYTES_PER_PEER {
+                    metrics::ORPHAN_CHUNK_MESSAGES_DROPPED.with_label_values(&["peer_limit"]).inc();
+                    return false;
+                }
+                true
+            });
+        let chunk_hash = header.chunk_hash();
+        let orphan = match self.orphans.entry(chunk_hash.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                self.height_idx
+                    .entry(header.height_created())
+                    .or_default()
+                    .insert(chunk_hash.clone());
+                self.prev_hash_idx
+                    .entry(*header.prev_block_hash())
+                    .or_default()
+                    .insert(chunk_hash.clone());
+                entry.insert(OrphanChunk {
+                    chunk_hash,
+                    height: header.height_created(),
+                    prev_block_hash: *header.prev_block_hash(),
+                    added: now,
+                    messages: vec![],
+                })
+            }
+        };
+        if let Some(message) = message {
+            if orphan.messages.len() < MAX_ORPHAN_CHUNK_MESSAGES {
+                self.total_bytes += message.size_bytes;
+                *self.bytes_by_peer.entry(message.peer_id.clone()).or_default() +=
+                    message.size_bytes;
+                orphan.messages.push(message);
+            } else {
+                metrics::ORPHAN_CHUNK_MESSAGES_DROPPED.with_label_values(&["chunk_limit"]).inc();
+            }
+        }
+        if self.orphans.len() > MAX_ORPHAN_CHUNKS || self.total_bytes > MAX_ORPHAN_CHUNK_BYTES {
+            self.evict(now, head_height);
+        }
+        metrics::ORPHAN_CHUNKS.set(self.orphans.len() as i64);
+        metrics::ORPHAN_CHUNKS_SIZE_BYTES.set(self.total_bytes as i64);
+    }
+
+    /// Removes the chunks which are in the pool for too long, then the ones which fell behind
+    /// the chain head, and then the ones farthest from the next height, until the pool is
+    /// within the limits.
+    fn evict(&mut self, now: time::Instant, head_height: BlockHeight) {
+        let expired: Vec<ChunkHash> = self
+            .orphans
+            .values()
+            .filter(|orphan| now - orphan.added > MAX_ORPHAN_CHUNK_AGE)
+            .map(|orphan| orphan.chunk_hash.clone())
+            .collect();
+        for chunk_hash in expired {
+            self.remove(&chunk_hash);
+            metrics::ORPHAN_CHUNKS_EVICTED.with_label_values(&["expired"]).inc();
+        }
+        let stale: Vec<ChunkHash> = self
+            .height_idx
+            .range(..=head_height)
+            .flat_map(|(_, hashes)| hashes.iter().cloned())
+            .collect();
+        for chunk_hash in stale {
+            self.remove(&chunk_hash);
+            metrics::ORPHAN_CHUNKS_EVICTED.with_label_values(&["stale"]).inc();
+        }
+        let next_height = head_height + 1;
+        while self.orphans.len() > MAX_ORPHAN_CHUNKS || self.total_bytes > MAX_ORPHAN_CHUNK_BYTES {
+            let lowest = self.height_idx.keys().next().copied();
+            let highest = self.height_idx.keys().next_back().copied();
+            let height = match (lowest, highest) {
+                (Some(lowest), Some(highest)) => {
+                    if next_height.abs_diff(lowest) > highest.abs_diff(next_height) {
+                        lowest
+                    } else {
+                        highest
+                    }
+                }
+                _ => break,
+            };
+            let victim = self.height_idx.get(&height).and_then(|hashes| hashes.iter().next());
+            match victim.cloned() {
+                Some(chunk_hash) => {
+                    let reason =
+                        if self.orphans.len() > MAX_ORPHAN_CHUNKS { "pool_size" } else { "bytes" };
+                    self.remove(&chunk_hash);
+                    metrics::ORPHAN_CHUNKS_EVICTED.with_label_values(&[reason]).inc();
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, chunk_hash: &ChunkHash) -> Option<OrphanChunk> {
+        let orphan = self.orphans.remove(chunk_hash)?;
+        if let Some(hashes) = self.height_idx.get_mut(&orphan.height) {
+            hashes.remove(chunk_hash);
+            if hashes.is_empty() {
+                self.height_idx.remove(&orphan.height);
+            }
+        }
+        if let Some(hashes) = self.prev_hash_idx.get_mut(&orphan.prev_block_hash) {
+            hashes.remove(chunk_hash);
+            if hashes.is_empty() {
+                self.prev_hash_idx.remove(&orphan.prev_block_hash);
+            }
+        }
+        for message in &orphan.messages {
+            self.total_bytes -= message.size_bytes;
+            if let Entry::Occupied(mut entry) = self.bytes_by_peer.entry(message.peer_id.clone()) {
+                *entry.get_mut() -= message.size_bytes;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+        Some(orphan)
+    }
+
+    /// Removes all the chunks whose previous block is `prev_block_hash` and returns them.
+    /// This function is called when the block is accepted, so that the chunks can be processed.
+    pub fn remove_by_prev_hash(
+        &mut self,
+        now: time::Instant,
+        prev_block_hash: &CryptoHash,
+    ) -> Vec<OrphanChunk> {
+        let chunk_hashes = match self.prev_hash_idx.get(prev_block_hash) {
+            Some(chunk_hashes) => chunk_hashes.clone(),
+            None => return vec![],
+        };
+        let adopted: Vec<OrphanChunk> =
+            chunk_hashes.iter().filter_map(|chunk_hash| self.remove(chunk_hash)).collect();
+        for orphan in &adopted {
+            metrics::ORPHAN_CHUNK_DWELL_TIME.observe((now - orphan.added).as_seconds_f64());
+        }
+        metrics::ORPHAN_CHUNKS.set(self.orphans.len() as i64);
+        metrics::ORPHAN_CHUNKS_SIZE_BYTES.set(self.total_bytes as i64);
+        adopted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::KeyType;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::network::PeerId;
+    use near_primitives::sharding::{
+        PartialEncodedChunkPart, PartialEncodedChunkV2, ShardChunkHeader, ShardChunkHeaderV2,
+    };
+    use near_primitives::time;
+    use near_primitives::validator_signer::InMemoryValidatorSigner;
+
+    use super::{
+        OrphanChunkPool, MAX_ORPHAN_CHUNKS, MAX_ORPHAN_CHUNK_BYTES_PER_PEER,
+        MAX_ORPHAN_CHUNK_MESSAGES,
+    };
+    use crate::chunk_cache::ChunkPartSupplier;
+
+    fn create_chunk_header(prev_block_hash: CryptoHash, height: u64) -> ShardChunkHeader {
+        let signer =
+            InMemoryValidatorSigner::from_random("test".parse().unwrap(), KeyType::ED25519);
+        ShardChunkHeader::V2(ShardChunkHeaderV2::new(
+            prev_block_hash,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            1,
+            height,
+            0,
+            0,
+            0,
+            0,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            vec![],
+            &signer,
+        ))
+    }
+
+    fn message(
+        header: &ShardChunkHeader,
+        part_size: usize,
+        peer_id: Option<PeerId>,
+    ) -> (PartialEncodedChunkV2, ChunkPartSupplier, Option<PeerId>) {
+        let parts = if part_size == 0 {
+            vec![]
+        } else {
+            vec![PartialEncodedChunkPart {
+                part_ord: 0,
+                part: vec![0; part_size].into_boxed_slice(),
+                merkle_proof: vec![],
+            }]
+        };
+        let partial_chunk =
+            PartialEncodedChunkV2 { header: header.clone(), parts, receipts: vec![] };
+        (partial_chunk, ChunkPartSupplier::ChunkProducer, peer_id)
+    }
+
+    #[test]
+    fn test_remove_by_prev_hash() {
+        let mut pool = OrphanChunkPool::default();
+        let clock = time::FakeClock::default();
+        let prev_block_hash = hash(b"prev");
+        let header = create_chunk_header(prev_block_hash, 1);
+        let other_header = create_chunk_header(hash(b"other"), 1);
+        pool.add(clock.now(), 0, &header, Some(message(&header, 0, None)));
+        pool.add(clock.now(), 0, &header, None);
+        pool.add(clock.now(), 0, &other_header, None);
+        assert_eq!(pool.orphans.len(), 2);
+
+        clock.advance(time::Duration::seconds(1));
+        let adopted = pool.remove_by_prev_hash(clock.now(), &prev_block_hash);
+        assert_eq!(adopted.len(), 1);
+        assert_eq!(adopted[0].chunk_hash, header.chunk_hash());
+        assert_eq!(adopted[0].messages.len(), 1);
+        assert!(!pool.orphans.contains_key(&header.chunk_hash()));
+        assert!(pool.orphans.contains_key(&other_header.chunk_hash()));
+        assert!(pool.remove_by_prev_hash(clock.now(), &prev_block_hash).is_empty());
+        assert_eq!(pool.total_bytes, 0);
+        assert!(pool.bytes_by_peer.is_empty());
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut pool = OrphanChunkPool::default();
+        let clock = time::FakeClock::default();
+        let head_height = 100;
+        let header = create_chunk_header(hash(b"prev"), head_height + 1);
+        for _ in 0..MAX_ORPHAN_CHUNK_MESSAGES + 5 {
+            pool.add(clock.now(), head_height, &header, Some(message(&header, 0, None)));
+        }
+        assert_eq!(pool.orphans[&header.chunk_hash()].messages.len(), MAX_ORPHAN_CHUNK_MESSAGES);
+
+        // Chunks at or below the chain head are not kept.
+        let old_header = create_chunk_header(hash(b"old"), head_height);
+        pool.add(clock.now(), head_height, &old_header, None);
+        assert!(!pool.orphans.contains_key(&old_header.chunk_hash()));
+
+        // Once the pool is full, the chunks farthest from the next height are evicted.
+        let headers: Vec<_> = (0..MAX_ORPHAN_CHUNKS as u64)
+            .map(|i| create_chunk_header(hash(&i.to_le_bytes()), head_height + 2 + i))
+            .collect();
+        for header in &headers {
+            pool.add(clock.now(), head_height, header, None);
+        }
+        assert_eq!(pool.orphans.len(), MAX_ORPHAN_CHUNKS);
+        assert!(pool.orphans.contains_key(&header.chunk_hash()));
+        assert!(!pool.orphans.contains_key(&headers.last().unwrap().chunk_hash()));
+
+        // Unless there are chunks which are in the pool for too long.
+        clock.advance(time::Duration::seconds(301));
+        let new_header = create_chunk_header(hash(b"new"), head_height + 1);
+        pool.add(clock.now(), head_height, &new_header, None);
+        assert_eq!(pool.orphans.len(), 1);
+        assert!(pool.orphans.contains_key(&new_header.chunk_hash()));
+    }
+
+    #[test]
+    fn test_stale_chunks_evicted_first() {
+        let mut pool = OrphanChunkPool::default();
+        let clock = time::FakeClock::default();
+        let headers: Vec<_> = (0..MAX_ORPHAN_CHUNKS as u64)
+            .map(|i| create_chunk_header(hash(&i.to_le_bytes()), 1 + i))
+            .collect();
+        for header in &headers {
+            pool.add(clock.now(), 0, header, None);
+        }
+        assert_eq!(pool.orphans.len(), MAX_ORPHAN_CHUNKS);
+
+        // Once the head moves on, the chunks left behind it are evicted before the chunks
+        // building on the next blocks.
+        let head_height = 10;
+        let header = create_chunk_header(hash(b"next"), head_height + 1);
+        pool.add(clock.now(), head_height, &header, None);
+        assert!(pool.orphans.contains_key(&header.chunk_hash()));
+        assert!(pool.orphans.contains_key(&headers.last().unwrap().chunk_hash()));
+        for header in &headers[..head_height as usize] {
+            assert!(!pool.orphans.contains_key(&header.chunk_hash()));
+        }
+    }
+
+    #[test]
+    fn test_peer_limit() {
+        let mut pool = OrphanChunkPool::default();
+        let clock = time::FakeClock::default();
+        let spammer = PeerId::random();
+        let peer = PeerId::random();
+        let part_size = 1024 * 1024;
+        let headers: Vec<_> =
+            (0..10u64).map(|i| create_chunk_header(hash(&i.to_le_bytes()), 1 + i)).collect();
+        for header in &headers {
+            pool.add(
+                clock.now(),
+                0,
+                header,
+                Some(message(header, part_size, Some(spammer.clone()))),
+            );
+        }
+        let spammer_bytes = pool.bytes_by_peer[&Some(spammer)];
+        assert!(spammer_bytes <= MAX_ORPHAN_CHUNK_BYTES_PER_PEER);
+        assert!(pool.orphans[&headers.last().unwrap().chunk_hash()].messages.is_empty());
+
+        // The messages of the other peers are still kept.
+        let header = create_chunk_header(hash(b"peer"), 1);
+        pool.add(clock.now(), 0, &header, Some(message(&header, part_size, Some(peer.clone()))));
+        assert_eq!(pool.orphans[&header.chunk_hash()].messages.len(), 1);
+        assert_eq!(pool.total_bytes, spammer_bytes + pool.bytes_by_peer[&Some(peer)]);
+    }
+}
@@ -1,8 +1,12 @@
 use std::{sync::Arc, time::Duration};
 
-use actix::{Actor, Addr, Arbiter, ArbiterHandle, Context, Handler};
-use near_async::messaging::Sender;
+use actix::{Actor, Addr, Arbiter, ArbiterHandle, AsyncContext, Context, Handler};
+use near_async::messaging::{IntoSender, Sender};
 use near_chain::{chunks_store::ReadOnlyChunksStore, types::Tip, RuntimeWithEpochManagerAdapter};
+use near_chain_configs::{
+    ChunkAvailabilitySamplingConfig, ChunkHorizonsConfig, ChunkRequestHedgingConfig,
+    FullChunkRequestsConfig,
+};
 use near_network::{
     shards_manager::ShardsManagerRequestFromNetwork, types::PeerManagerMessageRequest,
 };
@@ -11,21 +15,32 @@ use near_primitives::types::AccountId;
 use near_store::{DBCol, Store, HEADER_HEAD_KEY, HEAD_KEY};
 
 use crate::{
-    adapter::ShardsManagerRequestFromClient, client::ShardsManagerResponse, ShardsManager,
+    adapter::ShardsManagerRequestFromClient,
+    client::ShardsManagerResponse,
+    debug::{DebugStatus, GetDebugStatus},
+    header_validation::ChunkHeaderValidated,
+    ShardsManager,
 };
 
 pub struct ShardsManagerActor {
     shards_mgr: ShardsManager,
     chunk_request_retry_period: Duration,
+    /// How often the requests due to be hedged are checked, if hedging is enabled.
+    chunk_request_hedging_period: Option<Duration>,
 }
 
 impl ShardsManagerActor {
-    fn new(shards_mgr: ShardsManager, chunk_request_retry_period: Duration) -> Self {
-        Self { shards_mgr, chunk_request_retry_period }
+    fn new(
+        shards_mgr: ShardsManager,
+        chunk_request_retry_period: Duration,
+        chunk_request_hedging_period: Option<Duration>,
+    ) -> Self {
+        Self { shards_mgr, chunk_request_retry_period, chunk_request_hedging_period }
     }
 
     fn periodically_resend_chunk_requests(&mut self, ctx: &mut Context<Self>) {
         self.shards_mgr.resend_chunk_requests();
+        self.shards_mgr.expire_availability_samples();
 
         near_performance_metrics::actix::run_later(
             ctx,
@@ -35,6 +50,18 @@ impl ShardsManagerActor {
             },
         );
     }
+
+    fn periodically_send_hedged_chunk_requests(
+        &mut self,
+        ctx: &mut Context<Self>,
+        period: Duration,
+    ) {
+        self.shards_mgr.send_hedged_chunk_requests();
+
+        near_performance_metrics::actix::run_later(ctx, period, move |act, ctx| {
+            act.periodically_send_hedged_chunk_requests(ctx, period);
+        });
+    }
 }
 
 impl Actor for ShardsManagerActor {
@@ -42,6 +69,9 @@ impl Actor for ShardsManagerActor {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         self.periodically_resend_chunk_requests(ctx);
+        if let Some(period) = self.chunk_request_hedging_period {
+            self.periodically_send_hedged_chunk_requests(ctx, period);
+        }
     }
 }
 
@@ -61,6 +91,24 @@ impl Handler<ShardsManagerRequestFromNetwork> for ShardsManagerActor {
     }
 }
 
+impl Handler<ChunkHeaderValidated> for ShardsManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChunkHeaderValidated, _ctx: &mut Context<Self>) {
+        self.shards_mgr.process_chunk_header_validated(msg);
+    }
+}
+
+impl Handler<GetDebugStatus> for ShardsManagerActor {
+    type Result = DebugStatus;
+
+    fn handle(&mut self, msg: GetDebugStatus, _ctx: &mut Context<Self>) -> DebugStatus {
+        match msg {
+            GetDebugStatus::Chunks => DebugStatus::Chunks(self.shards_mgr.debug_view()),
+        }
+    }
+}
+
 pub fn start_shards_manager(
     runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
     network_adapter: Sender<PeerManagerMessageRequest>,
@@ -68,6 +116,13 @@ pub fn start_shards_manager(
     me: Option<AccountId>,
     store: Store,
     chunk_request_retry_period: Duration,
+    chunk_horizons: ChunkHorizonsConfig,
+    chunk_availability_sampling: Option<ChunkAvailabilitySamplingConfig>,
+    pre_validate_chunks: bool,
+    full_chunk_requests: FullChunkRequestsConfig,
+    chunk_request_hedging: Option<ChunkRequestHedgingConfig>,
+    chunk_only_producer_lightweight_mode: bool,
+    chunk_header_validation_threads: usize,
 ) -> (Addr<ShardsManagerActor>, ArbiterHandle) {
     let shards_manager_arbiter = Arbiter::new();
     let shards_manager_arbiter_handle = shards_manager_arbiter.handle();
@@ -81,6 +136,9 @@ pub fn start_shards_manager(
         .unwrap()
         .expect("ShardsManager must be initialized after the chain is initialized");
     let chunks_store = ReadOnlyChunksStore::new(store);
+    // Checking for the due hedges twice per delay keeps them at most half a delay late.
+    let chunk_request_hedging_period =
+        chunk_request_hedging.as_ref().map(|config| config.delay / 2);
     let shards_manager = ShardsManager::new(
         time::Clock::real(),
         me,
@@ -90,10 +148,23 @@ pub fn start_shards_manager(
         chunks_store,
         chain_head,
         chain_header_head,
-    );
+        chunk_horizons,
+    )
+    .with_availability_sampling(chunk_availability_sampling)
+    .with_chunk_pre_validation(pre_validate_chunks)
+    .with_full_chunk_requests(full_chunk_requests)
+    .with_chunk_request_hedging(chunk_request_hedging)
+    .with_chunk_only_producer_lightweight_mode(chunk_only_producer_lightweight_mode);
     let shards_manager_addr =
-        ShardsManagerActor::start_in_arbiter(&shards_manager_arbiter_handle, move |_| {
-            ShardsManagerActor::new(shards_manager, chunk_request_retry_period)
+        ShardsManagerActor::start_in_arbiter(&shards_manager_arbiter_handle, move |ctx| {
+            ShardsManagerActor::new(
+                shards_manager.with_chunk_header_validation_pool(
+                    chunk_header_validation_threads,
+                    ctx.address().into_sender(),
+                ),
+                chunk_request_retry_period,
+                chunk_request_hedging_period,
+            )
         });
     (shards_manager_addr, shards_manager_arbiter_handle)
 }
@@ -46,6 +46,7 @@ fn test_basic() {
         fixture.chain_store.new_read_only_chunks_store(),
         fixture.mock_chain_head.clone(),
         fixture.mock_chain_head.clone(),
+        Default::default(),
     );
     let test_data = TestData { shards_manager, client_events: vec![] };
     let mut test = builder.build(test_data);
@@ -55,9 +56,10 @@ fn test_basic() {
 
     // Have the ShardsManager receive a PartialEncodedChunk with all parts.
     sender.send(TestEvent::NetworkToShardsManager(
-        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-            fixture.make_partial_encoded_chunk(&fixture.all_part_ords),
-        ),
+        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+            partial_encoded_chunk: fixture.make_partial_encoded_chunk(&fixture.all_part_ords),
+            peer_id: None,
+        },
     ));
     test.run(time::Duration::seconds(1));
 
@@ -67,7 +69,7 @@ fn test_basic() {
         _ => panic!(),
     }
     match &test.data.client_events[1] {
-        ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
+        ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk, .. } => {
             assert_eq!(partial_chunk.parts().len(), fixture.all_part_ords.len());
             assert!(shard_chunk.is_some());
         }
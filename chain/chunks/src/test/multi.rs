@@ -58,6 +58,7 @@ fn test_multi() {
                 fixture.chain_store.new_read_only_chunks_store(),
                 fixture.mock_chain_head.clone(),
                 fixture.mock_chain_head.clone(),
+                Default::default(),
             );
             TestData {
                 shards_manager,
@@ -114,7 +115,7 @@ fn test_multi() {
         let data = test.data.get(idx).unwrap();
         assert_eq!(data.client_events.len(), 1);
         match &data.client_events[0] {
-            ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
+            ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk, .. } => {
                 assert_eq!(partial_chunk.parts().len(), fixture.all_part_ords.len());
                 assert!(shard_chunk.is_some());
             }
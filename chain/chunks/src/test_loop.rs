@@ -9,6 +9,7 @@ use near_network::{
     types::{NetworkRequests, PeerManagerMessageRequest},
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 use near_primitives::time;
 
 use crate::{adapter::ShardsManagerRequestFromClient, ShardsManager};
@@ -73,6 +74,7 @@ pub fn route_shards_manager_network_messages<
                             (target_idx,
                             ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
                                 partial_encoded_chunk_response: response,
+                                peer_id: PeerId::random(),
                                 received_time: context.clock.now().into(), // TODO: use clock
                             }.into()),
                             network_delay,
@@ -87,9 +89,10 @@ pub fn route_shards_manager_network_messages<
                             context.sender.send_with_delay(
                                 (
                                     target_idx,
-                                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-                                        partial_encoded_chunk.into(),
-                                    )
+                                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                                        partial_encoded_chunk: partial_encoded_chunk.into(),
+                                        peer_id: None,
+                                    }
                                     .into(),
                                 ),
                                 network_delay,
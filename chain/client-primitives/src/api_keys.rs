@@ -0,0 +1,200 @@
+//! Budgets of the RPC requests per API key.
+//!
+//! An RPC node may recognize API keys, passed in the `X-Api-Key` header, and assign each of them
+//! its own budget: the number of requests per second, the number of concurrently executed heavy
+//! queries and the number of bytes returned by view_state queries per second. The requests
+//! without an API key are not limited. The requests are accounted by near-jsonrpc, the queries
+//! by the view client, which carries the `ApiKey` along with the `Query`.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ApiKeyConfig {
+    /// Name of the key holder, used as the metrics label. The key itself is secret.
+    pub name: String,
+    pub key: String,
+    /// Maximal number of the requests per second. Unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_sec: Option<u32>,
+    /// Maximal number of the heavy queries (view_state, call_function, view_access_key_list)
+    /// executed concurrently. Unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_heavy_queries: Option<usize>,
+    /// Maximal number of the bytes of the keys and values returned by view_state queries
+    /// per second. Unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_state_bytes_per_sec: Option<u64>,
+}
+
+/// Tokens replenished at a constant rate, up to one second worth of them.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self { rate, tokens: rate, updated_at: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated_at = now;
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    requests: Option<TokenBucket>,
+    view_state_bytes: Option<TokenBucket>,
+    heavy_queries: usize,
+}
+
+#[derive(Debug)]
+pub struct ApiKey {
+    config: ApiKeyConfig,
+    state: Mutex<State>,
+}
+
+impl ApiKey {
+    fn new(config: ApiKeyConfig, now: Instant) -> Self {
+        let state = State {
+            requests: config.requests_per_sec.map(|rate| TokenBucket::new(rate as f64, now)),
+            view_state_bytes: config
+                .view_state_bytes_per_sec
+                .map(|rate| TokenBucket::new(rate as f64, now)),
+            heavy_queries: 0,
+        };
+        Self { config, state: Mutex::new(state) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Accounts a request. Returns false if the requests per second budget is exhausted.
+    pub fn try_request(&self, now: Instant) -> bool {
+        match &mut self.state.lock().unwrap().requests {
+            Some(bucket) => {
+                bucket.refill(now);
+                if bucket.tokens < 1. {
+                    return false;
+                }
+                bucket.tokens -= 1.;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Starts a heavy query, which lasts until the returned guard is dropped.
+    /// Returns None if the concurrent heavy queries budget is exhausted.
+    pub fn try_start_heavy_query(self: &Arc<Self>) -> Option<HeavyQueryGuard> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(max) = self.config.max_concurrent_heavy_queries {
+            if state.heavy_queries >= max {
+                return None;
+            }
+        }
+        state.heavy_queries += 1;
+        Some(HeavyQueryGuard(self.clone()))
+    }
+
+    /// Whether a view_state query may be started. The size of its response is charged
+    /// afterwards with `charge_view_state_bytes`, so the budget may be temporarily overdrawn.
+    pub fn has_view_state_budget(&self, now: Instant) -> bool {
+        match &mut self.state.lock().unwrap().view_state_bytes {
+            Some(bucket) => {
+                bucket.refill(now);
+                bucket.tokens > 0.
+            }
+            None => true,
+        }
+    }
+
+    pub fn charge_view_state_bytes(&self, now: Instant, bytes: u64) {
+        if let Some(bucket) = &mut self.state.lock().unwrap().view_state_bytes {
+            bucket.refill(now);
+            bucket.tokens -= bytes as f64;
+        }
+    }
+}
+
+pub struct HeavyQueryGuard(Arc<ApiKey>);
+
+impl Drop for HeavyQueryGuard {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().heavy_queries -= 1;
+    }
+}
+
+/// API keys recognized by the node.
+#[derive(Default)]
+pub struct ApiKeys(HashMap<String, Arc<ApiKey>>);
+
+impl ApiKeys {
+    pub fn new(configs: &[ApiKeyConfig], now: Instant) -> Self {
+        Self(
+            configs
+                .iter()
+                .map(|config| (config.key.clone(), Arc::new(ApiKey::new(config.clone(), now))))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<ApiKey>> {
+        self.0.get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config() -> ApiKeyConfig {
+        ApiKeyConfig {
+            name: "partner".to_string(),
+            key: "secret".to_string(),
+            requests_per_sec: Some(2),
+            max_concurrent_heavy_queries: Some(1),
+            view_state_bytes_per_sec: Some(100),
+        }
+    }
+
+    #[test]
+    fn requests_per_sec() {
+        let now = Instant::now();
+        let keys = ApiKeys::new(&[config()], now);
+        assert!(keys.get("other").is_none());
+        let key = keys.get("secret").unwrap();
+        assert!(key.try_request(now));
+        assert!(key.try_request(now));
+        assert!(!key.try_request(now));
+        assert!(key.try_request(now + Duration::from_millis(500)));
+        assert!(!key.try_request(now + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn heavy_queries() {
+        let key = ApiKeys::new(&[config()], Instant::now()).get("secret").unwrap();
+        let guard = key.try_start_heavy_query().unwrap();
+        assert!(key.try_start_heavy_query().is_none());
+        drop(guard);
+        assert!(key.try_start_heavy_query().is_some());
+    }
+
+    #[test]
+    fn view_state_bytes() {
+        let now = Instant::now();
+        let key = ApiKeys::new(&[config()], now).get("secret").unwrap();
+        assert!(key.has_view_state_budget(now));
+        key.charge_view_state_bytes(now, 150);
+        assert!(!key.has_view_state_budget(now + Duration::from_millis(500)));
+        assert!(key.has_view_state_budget(now + Duration::from_millis(600)));
+    }
+}
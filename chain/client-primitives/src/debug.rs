@@ -2,6 +2,7 @@
 //! without backwards compatibility of JSON encoding.
 use crate::types::StatusError;
 use chrono::DateTime;
+use near_chain_configs::RolloutFlag;
 use near_primitives::types::EpochId;
 use near_primitives::views::{
     CatchupStatusView, ChainProcessingInfo, EpochValidatorInfo, RequestedStatePartsView,
@@ -11,7 +12,7 @@ use near_primitives::{
     block_header::ApprovalInner,
     hash::CryptoHash,
     sharding::ChunkHash,
-    types::{AccountId, BlockHeight},
+    types::{AccountId, BlockHeight, ShardId},
     views::ValidatorInfo,
 };
 use std::collections::HashMap;
@@ -124,6 +125,80 @@ pub struct ChunkCollection {
     pub received_time: Option<DateTime<chrono::Utc>>,
     // Whether the block included a chunk for this shard
     pub chunk_included: bool,
+    // Whether the chunk was ready for inclusion before the block was produced, only after that,
+    // or not at all (so far).
+    pub inclusion_status: ChunkInclusionStatus,
+}
+
+// How the parts of a chunk were collected, from the first one received until the chunk
+// was complete.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ChunkCompletionView {
+    pub chunk_hash: ChunkHash,
+    pub height_created: BlockHeight,
+    pub shard_id: ShardId,
+    // Time from receiving the first part or receipt until having all the needed ones.
+    // None if the chunk was complete without receiving anything.
+    pub completion_time_ms: Option<u64>,
+    // Number of the requests sent for the parts and receipts.
+    pub num_requests: u32,
+    // Number of the parts received from each source.
+    pub suppliers: Vec<ChunkPartSupplierView>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ChunkPartSupplierView {
+    // "chunk_producer", "forward" or the id of the peer which responded to a request.
+    pub supplier: String,
+    pub num_parts: usize,
+}
+
+// The block this node would produce at the next height on top of its head, assembled without
+// being signed or broadcast.
+#[derive(serde::Serialize, Debug)]
+pub struct BlockProductionDryRunView {
+    pub height: BlockHeight,
+    pub prev_block_hash: CryptoHash,
+    pub prev_height: BlockHeight,
+    pub block_producer: AccountId,
+    // Why this node wouldn't produce the block now, None if it would.
+    pub skip_reason: Option<String>,
+    pub chunks: Vec<DryRunChunkView>,
+    // One entry per block approver of the epoch, in the order of the approvals in the block.
+    pub approvals: Vec<DryRunApprovalView>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct DryRunChunkView {
+    pub shard_id: ShardId,
+    pub chunk_producer: AccountId,
+    // The new chunk ready for inclusion, None if it hasn't been received (yet).
+    pub chunk_hash: Option<ChunkHash>,
+    pub received_time: Option<DateTime<chrono::Utc>>,
+    // Whether the block would include the new chunk, rather than the previous chunk of the
+    // shard.
+    pub included: bool,
+    // Whether the new chunk is left out because its producer is banned.
+    pub producer_banned: bool,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct DryRunApprovalView {
+    pub account_id: AccountId,
+    // Time at which the approval for the height was received, None if it wasn't (yet).
+    pub received_time: Option<DateTime<chrono::Utc>>,
+    // Approvals of slashed validators are not included.
+    pub is_slashed: bool,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkInclusionStatus {
+    // The chunk was ready before the block was produced and it was included.
+    Included,
+    // The chunk header arrived after the block was produced, i.e. the chunk producer was late.
+    ArrivedLate,
+    // The chunk header has not arrived (yet), e.g. it was lost by the network or never produced.
+    NotReceived,
 }
 
 // Information about things related to block/chunk production
@@ -184,6 +259,12 @@ pub enum DebugStatus {
     ChainProcessingStatus,
     // The state parts already requested.
     RequestedStateParts,
+    // Effective states of the rollout flags.
+    RolloutFlags,
+    // How the parts of the recently completed chunks were collected.
+    ChunkCompletion,
+    // The block this node would produce right now, without producing it.
+    BlockProductionDryRun,
 }
 
 impl actix::Message for DebugStatus {
@@ -205,4 +286,10 @@ pub enum DebugStatusResponse {
     ChainProcessingStatus(ChainProcessingInfo),
     // The state parts already requested.
     RequestedStateParts(Vec<RequestedStatePartsView>),
+    // Effective states of the rollout flags.
+    RolloutFlags(Vec<RolloutFlag>),
+    // Recently completed chunks, most recent first.
+    ChunkCompletion(Vec<ChunkCompletionView>),
+    // The block this node would produce right now.
+    BlockProductionDryRun(BlockProductionDryRunView),
 }
@@ -1,2 +1,3 @@
+pub mod api_keys;
 pub mod debug;
 pub mod types;
@@ -1,22 +1,25 @@
+use crate::api_keys::ApiKey;
 use actix::Message;
 use chrono::DateTime;
 use chrono::Utc;
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
+use near_crypto::PublicKey;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
+    AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, Nonce, ShardId,
     TransactionOrReceiptId,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, DownloadStatusView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView, ShardSyncDownloadView,
-    SplitStorageInfoView, StateChangesKindsView, StateChangesRequestView, StateChangesView,
-    SyncStatusView,
+    AccessKeyNonceGapsView, AccountActivityView, BlockView, ChunkView, DownloadStatusView,
+    EpochValidatorInfo, ExecutionOutcomeWithIdView, FeeHintView, FinalExecutionOutcomeViewEnum,
+    GasPriceView, HeadChangeView, LightClientBlockLiteView, LightClientBlockView,
+    MaintenanceWindowsView, MessageTrafficView, QueryRequest, QueryResponse, ReceiptView,
+    ShardSyncDownloadView, SplitStorageInfoView, StateChangesFilterView, StateChangesKindsView,
+    StateChangesRequestView, StateChangesView, SyncStatusView, TxPoolStatusView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
 use once_cell::sync::OnceCell;
@@ -373,16 +376,64 @@ impl From<near_chain_primitives::Error> for GetChunkError {
     }
 }
 
+/// Flag telling the view client that the requester is no longer interested in the result
+/// of a request, so that the work on it can be abandoned.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns a guard which cancels the token when dropped, e.g. together with the future
+    /// awaiting the response.
+    pub fn drop_guard(self) -> CancelOnDrop {
+        CancelOnDrop(self)
+    }
+}
+
+pub struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
 /// Queries client for given path / data.
 #[derive(Clone, Debug)]
 pub struct Query {
     pub block_reference: BlockReference,
     pub request: QueryRequest,
+    /// Heavy queries are abandoned once the token is cancelled.
+    pub cancellation: CancellationToken,
+    /// API key of the requester, whose budget the query is accounted to.
+    pub api_key: Option<Arc<ApiKey>>,
 }
 
 impl Query {
     pub fn new(block_reference: BlockReference, request: QueryRequest) -> Self {
-        Query { block_reference, request }
+        Query {
+            block_reference,
+            request,
+            cancellation: CancellationToken::default(),
+            api_key: None,
+        }
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<Arc<ApiKey>>) -> Self {
+        self.api_key = api_key;
+        self
     }
 }
 
@@ -438,6 +489,8 @@ pub enum QueryError {
     },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
     InternalError { error_message: String },
+    #[error("The budget of the API key {api_key} is exhausted: {budget}. Try again later")]
+    ApiKeyBudgetExceeded { api_key: String, budget: String },
     #[error(
         "The data for block #{block_height} is garbage collected on this node, use an archival node to fetch historical data"
     )]
@@ -467,6 +520,8 @@ pub enum StatusError {
     NodeIsSyncing,
     #[error("No blocks for {elapsed:?}")]
     NoNewBlocks { elapsed: std::time::Duration },
+    #[error("Data corruption detected in the store: {error_message}")]
+    DataCorruption { error_message: String },
     #[error("Epoch Out Of Bounds {epoch_id:?}")]
     EpochOutOfBounds { epoch_id: near_primitives::types::EpochId },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
@@ -589,6 +644,9 @@ pub struct PeerInfo {
     pub id: PeerId,
     pub addr: Option<std::net::SocketAddr>,
     pub account_id: Option<AccountId>,
+    /// Traffic exchanged with the peer since the connection has been established,
+    /// by message type.
+    pub traffic_by_message_type: std::collections::BTreeMap<String, MessageTrafficView>,
 }
 
 #[derive(Clone, Debug)]
@@ -682,6 +740,8 @@ impl Message for GetValidatorOrdered {
 pub struct GetStateChanges {
     pub block_hash: CryptoHash,
     pub state_changes_request: StateChangesRequestView,
+    /// Applied by the view client on top of `state_changes_request`.
+    pub filter: StateChangesFilterView,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -728,6 +788,7 @@ impl Message for GetStateChangesInBlock {
 
 pub struct GetStateChangesWithCauseInBlock {
     pub block_hash: CryptoHash,
+    pub filter: StateChangesFilterView,
 }
 
 impl Message for GetStateChangesWithCauseInBlock {
@@ -737,6 +798,7 @@ impl Message for GetStateChangesWithCauseInBlock {
 pub struct GetStateChangesWithCauseInBlockForTrackedShards {
     pub block_hash: CryptoHash,
     pub epoch_id: EpochId,
+    pub filter: StateChangesFilterView,
 }
 
 impl Message for GetStateChangesWithCauseInBlockForTrackedShards {
@@ -945,6 +1007,170 @@ impl From<near_chain_primitives::Error> for GetMaintenanceWindowsError {
     }
 }
 
+/// Recommends the gas price and estimates the inclusion delay of transactions, per shard.
+pub struct GetFeeHint {}
+
+impl Message for GetFeeHint {
+    type Result = Result<FeeHintView, GetFeeHintError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetFeeHintError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetFeeHintError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+/// Returns the changes of the chain head recorded after the change with id `since_id`,
+/// or all the recent changes if `since_id` is None.
+pub struct GetHeadChanges {
+    pub since_id: Option<u64>,
+}
+
+impl Message for GetHeadChanges {
+    type Result = Result<Vec<HeadChangeView>, GetHeadChangesError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetHeadChangesError {
+    #[error("The changes after {since_id} are no longer available, the oldest available change is {oldest_id}")]
+    Evicted { since_id: u64, oldest_id: u64 },
+    #[error("The change {since_id} is unknown, the latest change is {latest_id:?}; the node may have been restarted")]
+    UnknownId { since_id: u64, latest_id: Option<u64> },
+}
+
+/// Allows the chain to switch to the fork containing the given block, even if it reverts more
+/// blocks than the configured `max_reorg_depth`.
+pub struct AllowDeepReorg {
+    pub block_hash: CryptoHash,
+}
+
+impl Message for AllowDeepReorg {
+    type Result = ();
+}
+
+/// Returns the most recent outcomes executed by the account, latest first.
+pub struct GetAccountActivity {
+    pub account_id: AccountId,
+    pub limit: usize,
+}
+
+impl Message for GetAccountActivity {
+    type Result = Result<Vec<AccountActivityView>, GetAccountActivityError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetAccountActivityError {
+    #[error("The account activity index is disabled on this node")]
+    IndexDisabled,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetAccountActivityError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+/// Returns the nonces of the transactions signed with the access key pooled on this node, in
+/// increasing order.
+pub struct GetPooledNonces {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+}
+
+impl Message for GetPooledNonces {
+    type Result = Result<Vec<Nonce>, GetNonceGapsError>;
+}
+
+/// Returns the on-chain nonce of the access key together with the given nonces of its pooled
+/// transactions, see `GetPooledNonces`, and the gaps between them.
+pub struct GetNonceGaps {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub pooled_nonces: Vec<Nonce>,
+}
+
+impl Message for GetNonceGaps {
+    type Result = Result<AccessKeyNonceGapsView, GetNonceGapsError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetNonceGapsError {
+    #[error("Access key for public key {public_key} of account {account_id} does not exist")]
+    UnknownAccessKey { account_id: AccountId, public_key: PublicKey },
+    #[error("The node does not track the shard {requested_shard_id}")]
+    UnavailableShard { requested_shard_id: ShardId },
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetNonceGapsError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl From<near_primitives::errors::EpochError> for GetNonceGapsError {
+    fn from(error: near_primitives::errors::EpochError) -> Self {
+        near_chain_primitives::Error::from(error).into()
+    }
+}
+
+/// Maximal number of the signers and of the transactions listed per shard by `GetTxPoolStatus`.
+pub const MAX_TX_POOL_STATUS_LIMIT: usize = 1000;
+
+/// Returns the size of the transaction pool of every shard and the number of the pooled
+/// transactions of the busiest signers, optionally with the summaries of the transactions.
+pub struct GetTxPoolStatus {
+    pub include_transactions: bool,
+    /// Maximal number of the signers and of the transactions listed per shard.
+    /// Capped by `MAX_TX_POOL_STATUS_LIMIT`.
+    pub limit: usize,
+}
+
+impl Message for GetTxPoolStatus {
+    type Result = Result<TxPoolStatusView, GetTxPoolStatusError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetTxPoolStatusError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetTxPoolStatusError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
 pub struct GetClientConfig {}
 
 impl Message for GetClientConfig {
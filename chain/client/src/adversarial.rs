@@ -1,7 +1,30 @@
 #[cfg(feature = "test_features")]
 mod adv {
+    use near_primitives::types::BlockHeight;
     use std::sync::atomic::Ordering;
 
+    /// Adversarial behaviors of the block and chunk producer. Set by the integration tests
+    /// directly on the Client, or via the `adv_set_config` RPC method.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(default)]
+    pub struct AdversarialConfig {
+        /// Produce chunks with an invalid `gas_used`.
+        pub produce_invalid_chunks: bool,
+        /// Include an invalid transaction in the produced chunks.
+        pub produce_invalid_tx_in_chunks: bool,
+        /// Don't distribute the parts of the produced chunks, but still include their headers
+        /// in the produced blocks.
+        pub withhold_chunks: bool,
+        /// Don't broadcast the produced blocks.
+        pub withhold_blocks: bool,
+        /// Pretend that the chain has reached the given height, so that the node skips to
+        /// producing blocks and approvals at it. Applied by the `adv_set_config` RPC method
+        /// the same way as by `adv_switch_to_height`.
+        pub switch_to_height: Option<BlockHeight>,
+        /// Broadcast a conflicting block, signed with the same key, for every produced block.
+        pub double_sign_blocks: bool,
+    }
+
     #[derive(Default)]
     struct Inner {
         disable_header_sync: std::sync::atomic::AtomicBool,
@@ -59,4 +82,6 @@ mod adv {
     }
 }
 
+#[cfg(feature = "test_features")]
+pub use adv::AdversarialConfig;
 pub use adv::Controls;
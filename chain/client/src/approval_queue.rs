@@ -0,0 +1,116 @@
+//! Priority lane for the approvals received from the network.
+//!
+//! ClientActor processes its messages in order, so under a flood of transactions or state sync
+//! messages an approval could wait in the mailbox for a long time, delaying the next block.
+//! Instead, the approvals are pushed to an `ApprovalQueue` shared with the actor, which drains it
+//! before handling any other message. Hence an approval waits at most for the message which is
+//! being processed at the time it arrives. The actor is additionally woken up with a
+//! `ProcessQueuedApprovals` message, in case it is idle.
+use crate::metrics;
+use crate::ClientActor;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use near_async::messaging::CanSendAsync;
+use near_network::client::BlockApproval;
+use near_o11y::WithSpanContextExt;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Maximal number of queued approvals. It is well above the number of approvals per height,
+/// so it is reached only if the client is stuck, in which case the new approvals are dropped.
+const MAX_QUEUED_APPROVALS: usize = 10_000;
+
+/// Approvals waiting to be processed, together with the time they have been queued.
+#[derive(Clone, Default)]
+pub struct ApprovalQueue(Arc<Mutex<VecDeque<(BlockApproval, Instant)>>>);
+
+impl ApprovalQueue {
+    /// Queues the approval. Returns false if the queue was non-empty, and therefore the actor
+    /// has been woken up already.
+    fn push(&self, approval: BlockApproval) -> bool {
+        let mut queue = self.0.lock().unwrap();
+        if queue.len() >= MAX_QUEUED_APPROVALS {
+            metrics::APPROVALS_DROPPED.inc();
+            return false;
+        }
+        queue.push_back((approval, Instant::now()));
+        metrics::QUEUED_APPROVALS.set(queue.len() as i64);
+        queue.len() == 1
+    }
+
+    /// Removes all the queued approvals.
+    pub(crate) fn take(&self) -> VecDeque<(BlockApproval, Instant)> {
+        let queue = std::mem::take(&mut *self.0.lock().unwrap());
+        metrics::QUEUED_APPROVALS.set(0);
+        queue
+    }
+}
+
+/// Wakes up the ClientActor to drain the ApprovalQueue.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct ProcessQueuedApprovals {
+    pub sent_at: Instant,
+}
+
+/// Sender of the approvals from the network to the ClientActor, via the ApprovalQueue.
+pub(crate) struct ApprovalSender {
+    pub queue: ApprovalQueue,
+    pub client_addr: actix::Addr<ClientActor>,
+}
+
+impl CanSendAsync<BlockApproval, Result<(), ()>> for ApprovalSender {
+    fn send_async(&self, approval: BlockApproval) -> BoxFuture<'static, Result<(), ()>> {
+        if self.queue.push(approval) {
+            let msg = ProcessQueuedApprovals { sent_at: Instant::now() };
+            self.client_addr.do_send(msg.with_span_context());
+        }
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, PublicKey};
+    use near_primitives::block_header::Approval;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::network::PeerId;
+    use near_primitives::types::BlockHeight;
+    use near_primitives::validator_signer::InMemoryValidatorSigner;
+
+    fn approval(target_height: BlockHeight) -> BlockApproval {
+        let signer =
+            InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let parent_height = target_height - 1;
+        let parent_hash = CryptoHash::hash_borsh(parent_height);
+        BlockApproval(
+            Approval::new(parent_hash, parent_height, target_height, &signer),
+            PeerId::new(PublicKey::empty(KeyType::ED25519)),
+        )
+    }
+
+    #[test]
+    fn wakes_up_only_when_empty() {
+        let queue = ApprovalQueue::default();
+        assert!(queue.push(approval(10)));
+        assert!(!queue.push(approval(11)));
+        let heights: Vec<_> = queue.take().into_iter().map(|(a, _)| a.0.target_height).collect();
+        assert_eq!(vec![10, 11], heights);
+        assert!(queue.take().is_empty());
+        assert!(queue.push(approval(12)));
+    }
+
+    #[test]
+    fn drops_approvals_when_full() {
+        let queue = ApprovalQueue::default();
+        for _ in 0..MAX_QUEUED_APPROVALS {
+            queue.push(approval(10));
+        }
+        assert!(!queue.push(approval(11)));
+        let queued = queue.take();
+        assert_eq!(MAX_QUEUED_APPROVALS, queued.len());
+        assert!(queued.iter().all(|(a, _)| a.0.target_height == 10));
+    }
+}
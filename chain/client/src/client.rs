@@ -3,7 +3,12 @@
 
 use crate::adapter::ProcessTxResponse;
 use crate::debug::BlockProductionTracker;
-use crate::debug::PRODUCTION_TIMES_CACHE_SIZE;
+use crate::debug::{CHUNK_COMPLETION_STATS_CACHE_SIZE, PRODUCTION_TIMES_CACHE_SIZE};
+use crate::dedup_cache::DedupCache;
+use crate::double_sign_detector::DoubleSignDetector;
+use crate::forwarded_txs::{ForwardedTx, PendingForwardedTxs};
+use crate::head_watcher::HeadWatcher;
+use crate::signing_journal::SigningJournal;
 use crate::sync::block::BlockSync;
 use crate::sync::epoch::EpochSync;
 use crate::sync::header::HeaderSync;
@@ -29,14 +34,21 @@ use near_chunks::client::ShardedTransactionPool;
 use near_chunks::logic::{
     cares_about_shard_this_or_next_epoch, decode_encoded_chunk, persist_chunk,
 };
-use near_chunks::ShardsManager;
-use near_client_primitives::debug::ChunkProduction;
-use near_client_primitives::types::{Error, ShardSyncDownload, ShardSyncStatus};
+use near_chunks::{ChunkCompletionStats, ShardsManager};
+use near_client_primitives::debug::{
+    BlockProductionDryRunView, ChunkProduction, DryRunApprovalView, DryRunChunkView,
+};
+use near_client_primitives::types::{
+    Error, GetNonceGapsError, GetTxPoolStatusError, ShardSyncDownload, ShardSyncStatus,
+    MAX_TX_POOL_STATUS_LIMIT,
+};
+use near_crypto::PublicKey;
 use near_network::types::{AccountKeys, ChainInfo, PeerManagerMessageRequest, SetChainInfo};
 use near_network::types::{
     HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter, ReasonForBan,
 };
 use near_o11y::log_assert;
+use near_pool::InsertTransactionResult;
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::block_header::ApprovalType;
 use near_primitives::challenge::{Challenge, ChallengeBody};
@@ -53,15 +65,20 @@ use near_primitives::sharding::{
 use near_primitives::static_clock::StaticClock;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::chunk_extra::ChunkExtra;
-use near_primitives::types::{AccountId, ApprovalStake, BlockHeight, EpochId, NumBlocks, ShardId};
+use near_primitives::types::{
+    AccountId, ApprovalStake, BlockHeight, EpochId, Nonce, NumBlocks, ShardId,
+};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::PROTOCOL_VERSION;
-use near_primitives::views::{CatchupStatusView, DroppedReason};
+use near_primitives::views::{
+    AccountPooledTransactionsView, CatchupStatusView, DroppedReason, PooledTransactionView,
+    ShardTxPoolView, TxPoolStatusView,
+};
 use near_store::metadata::DbKind;
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, trace, warn};
@@ -69,6 +86,8 @@ use tracing::{debug, error, info, trace, warn};
 const NUM_REBROADCAST_BLOCKS: usize = 30;
 const CHUNK_HEADERS_FOR_INCLUSION_CACHE_SIZE: usize = 2048;
 const NUM_EPOCH_CHUNK_PRODUCERS_TO_KEEP_IN_BLOCKLIST: usize = 1000;
+/// Maximum number of received blocks waiting for a free slot in the block processing pool.
+const MAX_BLOCKS_WAITING_FOR_PROCESSING: usize = 20;
 
 /// The time we wait for the response to a Epoch Sync request before retrying
 // TODO #3488 set 30_000
@@ -82,6 +101,10 @@ const BLOCK_HORIZON: u64 = 500;
 /// number of blocks at the epoch start for which we will log more detailed info
 pub const EPOCH_START_INFO_BLOCKS: u64 = 500;
 
+/// Maximal number of the forwarded transactions forwarded again on a new head. The others stay
+/// due and are forwarded again on the following heads.
+const MAX_FORWARDED_TXS_RETRIED_PER_HEAD: usize = 1000;
+
 pub struct Client {
     /// Adversarial controls
     #[cfg(feature = "test_features")]
@@ -89,9 +112,7 @@ pub struct Client {
     #[cfg(feature = "test_features")]
     pub adv_produce_blocks_only_valid: bool,
     #[cfg(feature = "test_features")]
-    pub produce_invalid_chunks: bool,
-    #[cfg(feature = "test_features")]
-    pub produce_invalid_tx_in_chunks: bool,
+    pub adv_config: crate::adversarial::AdversarialConfig,
 
     /// Fast Forward accrued delta height used to calculate fast forwarded timestamps for each block.
     #[cfg(feature = "sandbox")]
@@ -101,6 +122,15 @@ pub struct Client {
     pub sync_status: SyncStatus,
     pub chain: Chain,
     pub doomslug: Doomslug,
+    /// Journal of the signed blocks, chunks and approvals. None if disabled in the config.
+    signing_journal: Option<SigningJournal>,
+    /// Detects another node signing with our validator key. None if disabled in the config.
+    double_sign_detector: Option<DoubleSignDetector>,
+    /// Height at which the block production was last withheld for a lack of connectivity. The
+    /// check runs on every tick of the block production loop, this keeps it warning once a height.
+    block_production_withheld_height: Option<BlockHeight>,
+    /// Reports the changes of the head to the subscribers.
+    pub(crate) head_watcher: HeadWatcher,
     pub runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
     pub shards_manager_adapter: Sender<ShardsManagerRequestFromClient>,
     pub sharded_tx_pool: ShardedTransactionPool,
@@ -130,6 +160,17 @@ pub struct Client {
     pub state_sync: StateSync,
     /// List of currently accumulated challenges.
     pub challenges: HashMap<CryptoHash, Challenge>,
+    /// Hashes of the recently processed challenges, which shouldn't be processed again.
+    processed_challenges: DedupCache,
+    /// Hashes of the recently forwarded transactions, which shouldn't be forwarded again.
+    forwarded_txs: DedupCache,
+    /// The recently forwarded transactions whose inclusion hasn't been observed yet, to measure
+    /// how long it takes to include them, and to forward them again if it takes too long.
+    pub(crate) pending_forwarded_txs: PendingForwardedTxs,
+    /// Received blocks which passed the header validation, but couldn't be started, because the
+    /// block processing pool was full, oldest first. They are started as soon as the blocks in
+    /// processing are postprocessed, instead of being dropped.
+    blocks_waiting_for_processing: VecDeque<(MaybeValidated<Block>, Provenance, PeerId)>,
     /// A ReedSolomon instance to reconstruct shard.
     pub rs_for_chunk_production: ReedSolomonWrapper,
     /// Blocks that have been re-broadcast recently. They should not be broadcast again.
@@ -143,17 +184,58 @@ pub struct Client {
     pub block_production_info: BlockProductionTracker,
     /// Chunk production timing information. Used only for debug purposes.
     pub chunk_production_info: lru::LruCache<(BlockHeight, ShardId), ChunkProduction>,
+    /// How the parts of the recently completed chunks were collected, oldest first.
+    /// Used only for debug purposes.
+    pub chunk_completion_stats: VecDeque<ChunkCompletionStats>,
 
-    /// Cached precomputed set of TIER1 accounts.
-    /// See send_network_chain_info().
-    tier1_accounts_cache: Option<(EpochId, Arc<AccountKeys>)>,
+    /// Cached precomputed set of TIER1 accounts and the subset of them which are
+    /// validators of the next epoch. See send_network_chain_info().
+    tier1_accounts_cache: Option<(EpochId, Arc<AccountKeys>, Arc<AccountKeys>)>,
     /// Used when it is needed to create flat storage in background for some shards.
     flat_storage_creator: Option<FlatStorageCreator>,
+    /// Sync block of the last epoch for which the state sync headers have been precomputed.
+    last_precomputed_sync_hash: Option<CryptoHash>,
 }
 
 impl Client {
-    pub(crate) fn update_client_config(&self, update_client_config: UpdateableClientConfig) {
+    pub(crate) fn update_client_config(&mut self, update_client_config: UpdateableClientConfig) {
         self.config.expected_shutdown.update(update_client_config.expected_shutdown);
+        self.update_block_production_delays(&update_client_config);
+    }
+
+    /// Applies the new block production delays to the client and to Doomslug, unless they are
+    /// out of bounds, in which case the previous ones stay in effect.
+    fn update_block_production_delays(&mut self, update_client_config: &UpdateableClientConfig) {
+        let UpdateableClientConfig {
+            min_block_production_delay,
+            max_block_production_delay,
+            max_block_wait_delay,
+            ..
+        } = *update_client_config;
+        if (min_block_production_delay, max_block_production_delay, max_block_wait_delay)
+            == (
+                self.config.min_block_production_delay,
+                self.config.max_block_production_delay,
+                self.config.max_block_wait_delay,
+            )
+        {
+            return;
+        }
+        if let Err(err) = update_client_config.validate_block_production_delays() {
+            warn!(target: "config", %err, "Ignoring the new block production delays");
+            return;
+        }
+        info!(target: "config", ?min_block_production_delay, ?max_block_production_delay, ?max_block_wait_delay, "Updated the block production delays");
+        self.config.min_block_production_delay = min_block_production_delay;
+        self.config.max_block_production_delay = max_block_production_delay;
+        self.config.max_block_wait_delay = max_block_wait_delay;
+        // Same as in `Client::new`.
+        self.doomslug.set_timer_delays(
+            min_block_production_delay,
+            max_block_production_delay,
+            max_block_production_delay / 10,
+            max_block_wait_delay,
+        );
     }
 }
 
@@ -200,14 +282,19 @@ impl Client {
         let chain_config = ChainConfig {
             save_trie_changes: config.save_trie_changes,
             background_migration_threads: config.client_background_migration_threads,
+            max_reorg_depth: config.max_reorg_depth,
+            index_account_activity: config.index_account_activity,
+            apply_chunks_threads: config.apply_chunks_threads,
         };
-        let chain = Chain::new(
+        let mut chain = Chain::new(
             runtime_adapter.clone(),
             &chain_genesis,
             doomslug_threshold_mode,
             chain_config.clone(),
         )?;
         let me = validator_signer.as_ref().map(|x| x.validator_id().clone());
+        // Before the flat storage is loaded, as the recovery might remove it.
+        chain.recover_head(me.as_ref())?;
         // Create flat storage or initiate migration to flat storage.
         let flat_storage_creator = FlatStorageCreator::new(
             me.as_ref(),
@@ -215,7 +302,11 @@ impl Client {
             chain.store(),
             chain_config.background_migration_threads,
         )?;
-        let sharded_tx_pool = ShardedTransactionPool::new(rng_seed);
+        let sharded_tx_pool = ShardedTransactionPool::new(
+            rng_seed,
+            config.transaction_pool_ordering,
+            config.transaction_pool_limits.clone(),
+        );
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
         let epoch_sync = EpochSync::new(
@@ -251,6 +342,38 @@ impl Client {
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
 
+        let signing_journal = if config.signing_journal {
+            let store = chain.store().store().clone();
+            Some(SigningJournal::new(store).map_err(|err| Error::Other(err.to_string()))?)
+        } else {
+            None
+        };
+        let double_sign_detector = if config.double_sign_detection {
+            let store = chain.store().store().clone();
+            Some(
+                DoubleSignDetector::new(store, chain.head()?.height)
+                    .map_err(|err| Error::Other(err.to_string()))?,
+            )
+        } else {
+            None
+        };
+        let dedup_store = config.dedup_caches.persistent.then(|| chain.store().store().clone());
+        let processed_challenges = DedupCache::new(
+            "challenges",
+            config.dedup_caches.challenges_capacity,
+            config.dedup_caches.challenges_ttl,
+            dedup_store.clone(),
+            StaticClock::utc(),
+        );
+        let forwarded_txs = DedupCache::new(
+            "forwarded_txs",
+            config.dedup_caches.forwarded_txs_capacity,
+            config.dedup_caches.forwarded_txs_ttl,
+            dedup_store,
+            StaticClock::utc(),
+        );
+        let pending_forwarded_txs =
+            PendingForwardedTxs::new(config.dedup_caches.forwarded_txs_capacity);
         let doomslug = Doomslug::new(
             chain.store().largest_target_height()?,
             config.min_block_production_delay,
@@ -266,15 +389,17 @@ impl Client {
             #[cfg(feature = "test_features")]
             adv_produce_blocks_only_valid: false,
             #[cfg(feature = "test_features")]
-            produce_invalid_chunks: false,
-            #[cfg(feature = "test_features")]
-            produce_invalid_tx_in_chunks: false,
+            adv_config: Default::default(),
             #[cfg(feature = "sandbox")]
             accrued_fastforward_delta: 0,
             config,
             sync_status,
             chain,
             doomslug,
+            signing_journal,
+            double_sign_detector,
+            block_production_withheld_height: None,
+            head_watcher: HeadWatcher::new(),
             runtime_adapter,
             shards_manager_adapter,
             sharded_tx_pool,
@@ -293,13 +418,19 @@ impl Client {
             block_sync,
             state_sync,
             challenges: Default::default(),
+            processed_challenges,
+            forwarded_txs,
+            pending_forwarded_txs,
+            blocks_waiting_for_processing: VecDeque::new(),
             rs_for_chunk_production: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: lru::LruCache::new(NUM_REBROADCAST_BLOCKS),
             last_time_head_progress_made: StaticClock::instant(),
             block_production_info: BlockProductionTracker::new(),
             chunk_production_info: lru::LruCache::new(PRODUCTION_TIMES_CACHE_SIZE),
+            chunk_completion_stats: VecDeque::new(),
             tier1_accounts_cache: None,
             flat_storage_creator,
+            last_precomputed_sync_hash: None,
         })
     }
 
@@ -366,6 +497,23 @@ impl Client {
         }
     }
 
+    /// Applies the adversarial config. See `AdversarialConfig`.
+    #[cfg(feature = "test_features")]
+    pub fn adv_set_config(&mut self, config: crate::adversarial::AdversarialConfig) {
+        self.adv_config = config;
+    }
+
+    /// Returns a block conflicting with `block`: at the same height and with the same parent,
+    /// but with a different timestamp, and signed with our key.
+    #[cfg(feature = "test_features")]
+    pub fn adv_make_conflicting_block(&self, block: &Block) -> Option<Block> {
+        let signer = self.validator_signer.as_ref()?;
+        let mut block = block.clone();
+        block.mut_header().get_mut().inner_lite.timestamp += 1;
+        block.mut_header().resign(signer.as_ref());
+        Some(block)
+    }
+
     /// Check that this block height is not known yet.
     fn known_block_height(&self, next_height: BlockHeight, known_height: BlockHeight) -> bool {
         #[cfg(feature = "test_features")]
@@ -498,6 +646,10 @@ impl Client {
             .as_ref()
             .ok_or_else(|| Error::BlockProducer("Called without block producer info.".to_string()))?
             .clone();
+        if self.is_signing_halted() {
+            debug!(target: "client", "Not producing block: signing is halted");
+            return Ok(None);
+        }
         let head = self.chain.head()?;
         assert_eq!(
             head.epoch_id,
@@ -698,8 +850,16 @@ impl Client {
             next_bp_hash,
             block_merkle_root,
             timestamp_override,
+            self.config.protocol_version_vote_override,
         );
 
+        if let Some(journal) = &self.signing_journal {
+            journal.record_block(&block).map_err(|err| Error::BlockProducer(err.to_string()))?;
+        }
+        if let Some(detector) = &mut self.double_sign_detector {
+            detector.record_block(&block);
+        }
+
         // Update latest known even before returning block out, to prevent race conditions.
         self.chain.mut_store().save_latest_known(LatestKnown {
             height: next_height,
@@ -711,6 +871,111 @@ impl Client {
         Ok(Some(block))
     }
 
+    /// Assembles what the block at the next height on top of the head would contain, without
+    /// producing, signing or broadcasting it: the chunks it would include or miss, the approvals
+    /// collected so far, and why this node wouldn't produce it now, if so. `num_healthy_peers` is
+    /// the number of the connected peers heard from recently, known to the client actor.
+    pub fn dry_run_block_production(
+        &self,
+        num_healthy_peers: usize,
+    ) -> Result<BlockProductionDryRunView, near_chain::Error> {
+        let head = self.chain.head()?;
+        let known_height = self.chain.store().get_latest_known()?.height;
+        let height = max(head.height, known_height) + 1;
+        let prev_hash = head.last_block_hash;
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&prev_hash)?;
+        let block_producer = self.runtime_adapter.get_block_producer(&epoch_id, height)?;
+        let me = self.validator_signer.as_ref().map(|signer| signer.validator_id());
+
+        // Unlike `get_chunk_headers_ready_for_inclusion`, keeps the chunks of the banned
+        // producers, to report them.
+        let ready_chunks = self
+            .prev_block_to_chunk_headers_ready_for_inclusion
+            .peek(&prev_hash)
+            .cloned()
+            .unwrap_or_default();
+        let mut chunks = vec![];
+        for shard_id in 0..self.runtime_adapter.num_shards(&epoch_id)? {
+            let chunk = match ready_chunks.get(&shard_id) {
+                Some((chunk_header, received_time, chunk_producer)) => {
+                    let producer_banned = self
+                        .do_not_include_chunks_from
+                        .contains(&(epoch_id.clone(), chunk_producer.clone()));
+                    DryRunChunkView {
+                        shard_id,
+                        chunk_producer: chunk_producer.clone(),
+                        chunk_hash: Some(chunk_header.chunk_hash()),
+                        received_time: Some(*received_time),
+                        included: !producer_banned,
+                        producer_banned,
+                    }
+                }
+                None => DryRunChunkView {
+                    shard_id,
+                    chunk_producer: self
+                        .runtime_adapter
+                        .get_chunk_producer(&epoch_id, height, shard_id)?,
+                    chunk_hash: None,
+                    received_time: None,
+                    included: false,
+                    producer_banned: false,
+                },
+            };
+            chunks.push(chunk);
+        }
+
+        let mut witness = self.doomslug.get_witness(&prev_hash, head.height, height);
+        let approvals = self
+            .runtime_adapter
+            .get_epoch_block_approvers_ordered(&prev_hash)?
+            .into_iter()
+            .map(|(ApprovalStake { account_id, .. }, is_slashed)| {
+                let received_time = witness.remove(&account_id).map(|(_, time)| time);
+                DryRunApprovalView { account_id, received_time, is_slashed }
+            })
+            .collect();
+
+        // Same checks as in the client actor before calling `produce_block`, and in it.
+        let have_all_chunks = head.height == 0 || chunks.iter().all(|chunk| chunk.included);
+        let skip_reason = if me.is_none() {
+            Some("not a validator")
+        } else if me != Some(&block_producer) {
+            Some("not the block producer for the height")
+        } else if !self.doomslug.is_ready_to_produce_block(
+            StaticClock::instant(),
+            height,
+            have_all_chunks,
+        ) {
+            Some("not enough approvals, or waiting for the missing chunks")
+        } else if num_healthy_peers < self.config.min_block_production_peers {
+            Some("not enough healthy peers")
+        } else if self.num_block_production_approvers(height, &block_producer)
+            < self.config.min_block_production_approvers
+        {
+            Some("approvals from too few other validators")
+        } else if self.is_signing_halted() {
+            Some("signing is halted")
+        } else if self.runtime_adapter.is_next_block_epoch_start(&prev_hash)?
+            && !self.chain.prev_block_is_caught_up(&head.prev_block_hash, &prev_hash)?
+        {
+            Some("the previous block is not caught up")
+        } else if !self.config.produce_empty_blocks && chunks.iter().all(|chunk| !chunk.included) {
+            Some("no new chunks, and empty blocks are not produced")
+        } else {
+            None
+        };
+
+        Ok(BlockProductionDryRunView {
+            height,
+            prev_block_hash: prev_hash,
+            prev_height: head.height,
+            block_producer,
+            skip_reason: skip_reason.map(str::to_string),
+            chunks,
+            approvals,
+        })
+    }
+
     pub fn produce_chunk(
         &mut self,
         prev_block_hash: CryptoHash,
@@ -728,6 +993,10 @@ impl Client {
             .as_ref()
             .ok_or_else(|| Error::ChunkProducer("Called without block producer info.".to_string()))?
             .clone();
+        if self.is_signing_halted() {
+            debug!(target: "client", shard_id, "Not producing chunk: signing is halted");
+            return Ok(None);
+        }
 
         let chunk_proposer =
             self.runtime_adapter.get_chunk_producer(epoch_id, next_height, shard_id).unwrap();
@@ -769,7 +1038,7 @@ impl Client {
         let transactions = Self::maybe_insert_invalid_transaction(
             transactions,
             prev_block_hash,
-            self.produce_invalid_tx_in_chunks,
+            self.adv_config.produce_invalid_tx_in_chunks,
         );
         let num_filtered_transactions = transactions.len();
         let (tx_root, _) = merklize(&transactions);
@@ -799,7 +1068,7 @@ impl Client {
         let protocol_version = self.runtime_adapter.get_epoch_protocol_version(epoch_id)?;
         let gas_used = chunk_extra.gas_used();
         #[cfg(feature = "test_features")]
-        let gas_used = if self.produce_invalid_chunks { gas_used + 1 } else { gas_used };
+        let gas_used = if self.adv_config.produce_invalid_chunks { gas_used + 1 } else { gas_used };
         let (encoded_chunk, merkle_paths) = ShardsManager::create_encoded_shard_chunk(
             prev_block_hash,
             *chunk_extra.state_root(),
@@ -819,6 +1088,12 @@ impl Client {
             protocol_version,
         )?;
 
+        if let Some(journal) = &self.signing_journal {
+            journal
+                .record_chunk(&encoded_chunk.cloned_header())
+                .map_err(|err| Error::ChunkProducer(err.to_string()))?;
+        }
+
         debug!(
             target: "client",
             height=next_height,
@@ -914,6 +1189,9 @@ impl Client {
         if let Some(validator_signer) = &self.validator_signer {
             for body in challenges {
                 let challenge = Challenge::produce(body, &**validator_signer);
+                if !self.processed_challenges.insert(challenge.hash, StaticClock::utc()) {
+                    continue;
+                }
                 self.challenges.insert(challenge.hash, challenge.clone());
                 self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
                     NetworkRequests::Challenge(challenge),
@@ -994,11 +1272,18 @@ impl Client {
                 .mark_block_dropped(block.hash(), DroppedReason::HeightProcessed);
             return Ok(());
         }
+        self.detect_double_sign_block(&block);
         let prev_hash = *block.header().prev_hash();
         let block = block.into();
         self.verify_and_rebroadcast_block(&block, was_requested, &peer_id)?;
         let provenance =
             if was_requested { near_chain::Provenance::SYNC } else { near_chain::Provenance::NONE };
+        if !self.chain.can_start_processing_block()
+            || !self.blocks_waiting_for_processing.is_empty()
+        {
+            self.wait_for_processing(block, provenance, peer_id);
+            return Ok(());
+        }
         let res = self.start_process_block(block, provenance, apply_chunks_done_callback);
         match &res {
             Err(near_chain::Error::Orphan) => {
@@ -1011,6 +1296,59 @@ impl Client {
         res
     }
 
+    /// Queues a received block until there is room in the block processing pool.
+    /// If the queue is full, the block is dropped, like it would be by the chain.
+    fn wait_for_processing(
+        &mut self,
+        block: MaybeValidated<Block>,
+        provenance: Provenance,
+        peer_id: PeerId,
+    ) {
+        let hash = *block.hash();
+        if self.blocks_waiting_for_processing.iter().any(|(waiting, _, _)| waiting.hash() == &hash)
+        {
+            return;
+        }
+        if self.blocks_waiting_for_processing.len() >= MAX_BLOCKS_WAITING_FOR_PROCESSING {
+            debug!(target: "client", %hash, "Dropping a block, too many blocks are waiting for processing");
+            metrics::BLOCKS_WAITING_FOR_PROCESSING_DROPPED.inc();
+            self.chain
+                .blocks_delay_tracker
+                .mark_block_dropped(&hash, DroppedReason::TooManyProcessingBlocks);
+            return;
+        }
+        debug!(target: "client", %hash, "Block processing pool is full, the block will be processed later");
+        self.blocks_waiting_for_processing.push_back((block, provenance, peer_id));
+        metrics::BLOCKS_WAITING_FOR_PROCESSING.set(self.blocks_waiting_for_processing.len() as i64);
+    }
+
+    /// Starts the processing of the blocks waiting for room in the block processing pool, oldest
+    /// first, for as long as there is room.
+    fn start_process_waiting_blocks(&mut self, apply_chunks_done_callback: DoneApplyChunkCallback) {
+        while self.chain.can_start_processing_block() {
+            let (block, provenance, peer_id) = match self.blocks_waiting_for_processing.pop_front()
+            {
+                Some(waiting) => waiting,
+                None => break,
+            };
+            let hash = *block.hash();
+            let prev_hash = *block.header().prev_hash();
+            match self.start_process_block(block, provenance, apply_chunks_done_callback.clone()) {
+                Ok(()) => {}
+                Err(near_chain::Error::Orphan) => {
+                    if !self.chain.is_orphan(&prev_hash) {
+                        self.request_block(prev_hash, peer_id)
+                    }
+                }
+                Err(err) => {
+                    debug!(target: "client", %hash, %err, "Failed to start processing a block which waited for processing");
+                    self.chain.blocks_delay_tracker.mark_block_errored(&hash, err.to_string());
+                }
+            }
+        }
+        metrics::BLOCKS_WAITING_FOR_PROCESSING.set(self.blocks_waiting_for_processing.len() as i64);
+    }
+
     /// To protect ourselves from spamming, we do some pre-check on block height before we do any
     /// processing. This function returns true if the block height is valid.
     fn check_block_height(
@@ -1148,7 +1486,8 @@ impl Client {
     }
 
     /// Check if there are any blocks that has finished applying chunks, run post processing on these
-    /// blocks.
+    /// blocks. Then start the processing of the blocks which waited for room in the processing
+    /// pool.
     pub fn postprocess_ready_blocks(
         &mut self,
         apply_chunks_done_callback: DoneApplyChunkCallback,
@@ -1162,7 +1501,7 @@ impl Client {
         let (accepted_blocks, errors) = self.chain.postprocess_ready_blocks(
             &me,
             &mut block_processing_artifacts,
-            apply_chunks_done_callback,
+            apply_chunks_done_callback.clone(),
         );
         if accepted_blocks.iter().any(|accepted_block| accepted_block.status.is_new_head()) {
             self.shards_manager_adapter.send(ShardsManagerRequestFromClient::UpdateChainHeads {
@@ -1183,6 +1522,7 @@ impl Client {
         }
         self.last_time_head_progress_made =
             max(self.chain.get_last_time_head_updated(), self.last_time_head_progress_made);
+        self.start_process_waiting_blocks(apply_chunks_done_callback);
         (accepted_blocks_hashes, errors)
     }
 
@@ -1254,6 +1594,14 @@ impl Client {
         }
     }
 
+    /// Remembers how the parts of a completed chunk were collected, for the debug page.
+    pub fn record_chunk_completion_stats(&mut self, stats: ChunkCompletionStats) {
+        if self.chunk_completion_stats.len() >= CHUNK_COMPLETION_STATS_CACHE_SIZE {
+            self.chunk_completion_stats.pop_front();
+        }
+        self.chunk_completion_stats.push_back(stats);
+    }
+
     /// Called asynchronously when the ShardsManager finishes processing a chunk.
     pub fn on_chunk_completed(
         &mut self,
@@ -1288,6 +1636,9 @@ impl Client {
         chunk_header: ShardChunkHeader,
         chunk_producer: AccountId,
     ) {
+        // If the block at this height has already been produced by this node, the chunk is late.
+        self.block_production_info
+            .record_chunk_collected(chunk_header.height_created(), chunk_header.shard_id());
         let prev_block_hash = chunk_header.prev_block_hash();
         self.prev_block_to_chunk_headers_ready_for_inclusion
             .get_or_insert(*prev_block_hash, || HashMap::new());
@@ -1372,11 +1723,114 @@ impl Client {
         chrono::Duration::nanoseconds(ns)
     }
 
+    /// Number of the validators other than `me` whose approvals for the height were collected.
+    pub(crate) fn num_block_production_approvers(
+        &self,
+        height: BlockHeight,
+        me: &AccountId,
+    ) -> usize {
+        (self.doomslug.approval_status_at_height(&height).approvals)
+            .keys()
+            .filter(|account_id| *account_id != me)
+            .count()
+    }
+
+    /// Checks whether the node is connected well enough to the rest of the network to produce a
+    /// block at the height. A validator partitioned away together with a few peers would
+    /// otherwise keep producing blocks on a dead fork. The withheld production is reported once
+    /// per height.
+    pub(crate) fn check_connected_enough_to_produce_block(
+        &mut self,
+        height: BlockHeight,
+        me: &AccountId,
+        num_healthy_peers: usize,
+    ) -> bool {
+        let report = self.block_production_withheld_height != Some(height);
+        let min_peers = self.config.min_block_production_peers;
+        let min_approvers = self.config.min_block_production_approvers;
+        if num_healthy_peers < min_peers {
+            if report {
+                warn!(
+                    target: "client",
+                    height,
+                    num_healthy_peers,
+                    min_block_production_peers = min_peers,
+                    "Withholding block production: not enough healthy peers, this node might be \
+                     partitioned from the network",
+                );
+                metrics::BLOCK_PRODUCTION_WITHHELD.with_label_values(&["peers"]).inc();
+            }
+            self.block_production_withheld_height = Some(height);
+            return false;
+        }
+        let num_approvers = self.num_block_production_approvers(height, me);
+        if num_approvers < min_approvers {
+            if report {
+                warn!(
+                    target: "client",
+                    height,
+                    num_approvers,
+                    min_block_production_approvers = min_approvers,
+                    "Withholding block production: approvals from too few other validators, this \
+                     node might be partitioned from the network",
+                );
+                metrics::BLOCK_PRODUCTION_WITHHELD.with_label_values(&["approvals"]).inc();
+            }
+            self.block_production_withheld_height = Some(height);
+            return false;
+        }
+        true
+    }
+
+    /// Whether signing has been halted, because another node signs with our validator key.
+    fn is_signing_halted(&self) -> bool {
+        self.double_sign_detector.as_ref().map_or(false, |detector| detector.is_halted())
+    }
+
+    /// Passes a received block signed with our validator key to the double sign detector.
+    fn detect_double_sign_block(&mut self, block: &Block) {
+        let (detector, signer) = match (&mut self.double_sign_detector, &self.validator_signer) {
+            (Some(detector), Some(signer)) if !detector.is_halted() => (detector, signer),
+            _ => return,
+        };
+        if block.header().verify_block_producer(&signer.public_key()) {
+            detector.check_block(block);
+        }
+    }
+
+    /// Passes a received approval signed with our validator key to the double sign detector.
+    fn detect_double_sign_approval(&mut self, approval: &Approval) {
+        let (detector, signer) = match (&mut self.double_sign_detector, &self.validator_signer) {
+            (Some(detector), Some(signer)) if !detector.is_halted() => (detector, signer),
+            _ => return,
+        };
+        if &approval.account_id != signer.validator_id() {
+            return;
+        }
+        let data = Approval::get_data_for_sig(&approval.inner, approval.target_height);
+        if approval.signature.verify(&data, &signer.public_key()) {
+            detector.check_approval(approval);
+        }
+    }
+
     pub fn send_approval(
         &mut self,
         parent_hash: &CryptoHash,
         approval: Approval,
     ) -> Result<(), Error> {
+        if self.is_signing_halted() {
+            debug!(target: "client", "Not sending the approval: signing is halted");
+            return Ok(());
+        }
+        if let Some(journal) = &self.signing_journal {
+            if let Err(err) = journal.record_approval(&approval) {
+                error!(target: "client", ?err, "Not sending the approval");
+                return Ok(());
+            }
+        }
+        if let Some(detector) = &mut self.double_sign_detector {
+            detector.record_approval(&approval);
+        }
         let next_epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(parent_hash)?;
         let next_block_producer =
             self.runtime_adapter.get_block_producer(&next_epoch_id, approval.target_height)?;
@@ -1433,6 +1887,14 @@ impl Client {
         }
 
         if status.is_new_head() {
+            let prev_head = match &status {
+                BlockStatus::Reorg(prev_head) => prev_head,
+                _ => block.header().prev_hash(),
+            };
+            if let Err(err) = self.head_watcher.record(&self.chain, prev_head, block.header()) {
+                error!(target: "client", ?err, "Failed to record the head change");
+            }
+
             let last_final_block = block.header().last_final_block();
             let last_finalized_height = if last_final_block == &CryptoHash::default() {
                 self.chain.genesis().height()
@@ -1440,6 +1902,11 @@ impl Client {
                 self.chain.get_block_header(last_final_block).map_or(0, |header| header.height())
             };
             self.chain.blocks_with_missing_chunks.prune_blocks_below_height(last_finalized_height);
+            self.observe_forwarded_txs_inclusion(&block);
+            self.retry_forwarded_txs();
+            if let Err(err) = self.precompute_state_sync_headers(last_final_block) {
+                warn!(target: "sync", ?err, "Failed to precompute the state sync headers");
+            }
 
             {
                 let _span = tracing::debug_span!(
@@ -1592,6 +2059,11 @@ impl Client {
         )?;
         persist_chunk(partial_chunk.clone(), Some(shard_chunk), self.chain.mut_store())?;
         self.on_chunk_header_ready_for_inclusion(encoded_chunk.cloned_header(), validator_id);
+        #[cfg(feature = "test_features")]
+        if self.adv_config.withhold_chunks {
+            info!(target: "adversary", chunk_hash = ?encoded_chunk.chunk_hash(), "Withholding chunk");
+            return Ok(());
+        }
         self.shards_manager_adapter.send(ShardsManagerRequestFromClient::DistributeEncodedChunk {
             partial_chunk,
             encoded_chunk,
@@ -1722,6 +2194,9 @@ impl Client {
     /// * `approval_type`  - whether the approval was just produced by us (in which case skip validation,
     ///                      only check whether we are the next block producer and store in Doomslug)
     pub fn collect_block_approval(&mut self, approval: &Approval, approval_type: ApprovalType) {
+        if let ApprovalType::PeerApproval(_) = approval_type {
+            self.detect_double_sign_approval(approval);
+        }
         let Approval { inner, account_id, target_height, signature } = approval;
 
         let parent_hash = match inner {
@@ -1837,16 +2312,39 @@ impl Client {
     }
 
     /// Forwards given transaction to upcoming validators.
-    fn forward_tx(&self, epoch_id: &EpochId, tx: &SignedTransaction) -> Result<(), Error> {
+    pub(crate) fn forward_tx(
+        &mut self,
+        epoch_id: &EpochId,
+        tx: &SignedTransaction,
+    ) -> Result<(), Error> {
+        if !self.forwarded_txs.insert(tx.get_hash(), StaticClock::utc()) {
+            trace!(target: "client", tx_hash = ?tx.get_hash(), "Transaction forwarded recently, not forwarding it again");
+            return Ok(());
+        }
+        let head = self.chain.head()?;
+        self.pending_forwarded_txs.insert(
+            tx.get_hash(),
+            head.height + self.config.transaction_forwarding.retry_after,
+            ForwardedTx { tx: tx.clone(), forwarded_at: StaticClock::instant(), retries: 0 },
+        );
+        self.send_tx_to_chunk_producers(epoch_id, tx)
+    }
+
+    /// Sends the transaction to the chunk producers of its shard at the next few heights, in
+    /// the given epoch and in the next one if it may start by then.
+    fn send_tx_to_chunk_producers(
+        &mut self,
+        epoch_id: &EpochId,
+        tx: &SignedTransaction,
+    ) -> Result<(), Error> {
         let shard_id =
             self.runtime_adapter.account_id_to_shard_id(&tx.transaction.signer_id, epoch_id)?;
         let head = self.chain.head()?;
         let maybe_next_epoch_id = self.get_next_epoch_id_if_at_boundary(&head)?;
 
+        let forwarding_horizon = self.config.transaction_forwarding.horizon;
         let mut validators = HashSet::new();
-        for horizon in
-            (2..=TX_ROUTING_HEIGHT_HORIZON).chain(vec![TX_ROUTING_HEIGHT_HORIZON * 2].into_iter())
-        {
+        for horizon in (2..=forwarding_horizon).chain(std::iter::once(forwarding_horizon * 2)) {
             let validator =
                 self.chain.find_chunk_producer_for_forwarding(epoch_id, shard_id, horizon)?;
             validators.insert(validator);
@@ -1884,6 +2382,161 @@ impl Client {
         Ok(())
     }
 
+    /// Observes the time since forwarding of the transactions included in the new chunks of
+    /// the block. Only the chunks of the shards tracked by this node are available.
+    fn observe_forwarded_txs_inclusion(&mut self, block: &Block) {
+        if self.pending_forwarded_txs.is_empty() {
+            return;
+        }
+        let now = StaticClock::instant();
+        for (shard_id, chunk_header) in block.chunks().iter().enumerate() {
+            if block.header().height() != chunk_header.height_included() {
+                continue;
+            }
+            let chunk = match self.chain.get_chunk(&chunk_header.chunk_hash()) {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            };
+            for tx in chunk.transactions() {
+                if let Some(forwarded) = self.pending_forwarded_txs.remove(&tx.get_hash()) {
+                    metrics::TRANSACTION_FORWARD_TO_INCLUSION_DELAY
+                        .with_label_values(&[&shard_id.to_string()])
+                        .observe((now - forwarded.forwarded_at).as_secs_f64());
+                    metrics::TRANSACTION_FORWARD_OUTCOME.with_label_values(&["included"]).inc();
+                }
+            }
+        }
+    }
+
+    /// Forwards again the transactions whose inclusion hasn't been observed within
+    /// `retry_after` blocks since they were last forwarded, to the chunk producers of the heights
+    /// following the new head. Once out of retries, or once found in the store of this node, the
+    /// transactions are forgotten. At most `MAX_FORWARDED_TXS_RETRIED_PER_HEAD` of them are
+    /// handled per head.
+    fn retry_forwarded_txs(&mut self) {
+        let head = match self.chain.head() {
+            Ok(head) => head,
+            Err(_) => return,
+        };
+        if !self.pending_forwarded_txs.has_due(head.height) {
+            return;
+        }
+        let config = self.config.transaction_forwarding.clone();
+        let epoch_id = match self
+            .runtime_adapter
+            .get_epoch_id_from_prev_block(&head.last_block_hash)
+        {
+            Ok(epoch_id) => epoch_id,
+            Err(err) => {
+                warn!(target: "client", ?err, "Failed to get the epoch to forward the transactions again");
+                return;
+            }
+        };
+        let due =
+            self.pending_forwarded_txs.pop_due(head.height, MAX_FORWARDED_TXS_RETRIED_PER_HEAD);
+        for (tx_hash, mut forwarded) in due {
+            // The inclusion may have been missed, e.g. if the block was processed during sync.
+            if let Ok(Some(_)) = self.chain.store().get_transaction(&tx_hash) {
+                metrics::TRANSACTION_FORWARD_OUTCOME.with_label_values(&["included"]).inc();
+                continue;
+            }
+            if forwarded.retries >= config.max_retries {
+                metrics::TRANSACTION_FORWARD_OUTCOME.with_label_values(&["abandoned"]).inc();
+                continue;
+            }
+            forwarded.retries += 1;
+            let tx = forwarded.tx.clone();
+            self.pending_forwarded_txs.insert(tx_hash, head.height + config.retry_after, forwarded);
+            metrics::TRANSACTION_FORWARD_OUTCOME.with_label_values(&["retried"]).inc();
+            debug!(target: "client", ?tx_hash, "Inclusion of the forwarded transaction not observed, forwarding it again");
+            if let Err(err) = self.send_tx_to_chunk_producers(&epoch_id, &tx) {
+                warn!(target: "client", ?tx_hash, ?err, "Failed to forward the transaction again");
+            }
+        }
+    }
+
+    /// Nonces of the transactions signed with the access key pooled on this node, in increasing
+    /// order.
+    pub fn get_pooled_nonces(
+        &self,
+        account_id: &AccountId,
+        public_key: &PublicKey,
+    ) -> Result<Vec<Nonce>, GetNonceGapsError> {
+        let head = self.chain.head()?;
+        // Transactions are pooled by the shard of the next block, see `process_tx_internal`.
+        let pool_epoch_id =
+            self.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
+        let pool_shard_id =
+            self.runtime_adapter.account_id_to_shard_id(account_id, &pool_epoch_id)?;
+        Ok(self.sharded_tx_pool.nonces(pool_shard_id, account_id, public_key))
+    }
+
+    /// Summarizes the transaction pool of every shard, so that the operators can see which
+    /// transactions are stuck there. At most `limit` signers and transactions are listed per
+    /// shard, and the limit itself is capped by `MAX_TX_POOL_STATUS_LIMIT`.
+    pub fn get_tx_pool_status(
+        &self,
+        include_transactions: bool,
+        limit: usize,
+    ) -> Result<TxPoolStatusView, GetTxPoolStatusError> {
+        let limit = limit.min(MAX_TX_POOL_STATUS_LIMIT);
+        let head = self.chain.head()?;
+        let shards = self
+            .sharded_tx_pool
+            .shard_ids()
+            .into_iter()
+            .map(|shard_id| {
+                let mut num_transactions = 0;
+                let mut accounts: HashMap<&AccountId, u64> = HashMap::new();
+                for tx in self.sharded_tx_pool.transactions(shard_id) {
+                    num_transactions += 1;
+                    *accounts.entry(&tx.transaction.signer_id).or_default() += 1;
+                }
+                let num_accounts = accounts.len() as u64;
+                let mut accounts: Vec<_> = accounts
+                    .into_iter()
+                    .map(|(account_id, num_transactions)| AccountPooledTransactionsView {
+                        account_id: account_id.clone(),
+                        num_transactions,
+                    })
+                    .collect();
+                accounts.sort_by(|a, b| {
+                    b.num_transactions
+                        .cmp(&a.num_transactions)
+                        .then_with(|| a.account_id.cmp(&b.account_id))
+                });
+                accounts.truncate(limit);
+                let transactions = include_transactions.then(|| {
+                    let mut transactions: Vec<_> = self
+                        .sharded_tx_pool
+                        .transactions(shard_id)
+                        .map(|tx| PooledTransactionView {
+                            hash: tx.get_hash(),
+                            signer_id: tx.transaction.signer_id.clone(),
+                            public_key: tx.transaction.public_key.clone(),
+                            nonce: tx.transaction.nonce,
+                            receiver_id: tx.transaction.receiver_id.clone(),
+                            num_actions: tx.transaction.actions.len() as u64,
+                            prepaid_gas: tx
+                                .transaction
+                                .actions
+                                .iter()
+                                .map(|action| action.get_prepaid_gas())
+                                .fold(0, u64::saturating_add),
+                        })
+                        .collect();
+                    transactions.sort_by(|a, b| {
+                        a.signer_id.cmp(&b.signer_id).then_with(|| a.nonce.cmp(&b.nonce))
+                    });
+                    transactions.truncate(limit);
+                    transactions
+                });
+                ShardTxPoolView { shard_id, num_transactions, num_accounts, accounts, transactions }
+            })
+            .collect();
+        Ok(TxPoolStatusView { block_hash: head.last_block_hash, block_height: head.height, shards })
+    }
+
     pub fn process_tx(
         &mut self,
         tx: SignedTransaction,
@@ -1909,7 +2562,7 @@ impl Client {
                 + self.config.epoch_length;
 
         let epoch_boundary_possible =
-            head.height + TX_ROUTING_HEIGHT_HORIZON >= next_epoch_estimated_height;
+            head.height + self.config.transaction_forwarding.horizon >= next_epoch_estimated_height;
         if epoch_boundary_possible {
             Ok(Some(self.runtime_adapter.get_next_epoch_id_from_prev_block(&head.last_block_hash)?))
         } else {
@@ -1936,6 +2589,10 @@ impl Client {
         is_forwarded: bool,
         check_only: bool,
     ) -> Result<ProcessTxResponse, Error> {
+        if let Err(err) = self.config.transaction_limits.check(tx) {
+            debug!(target: "client", ?err, "Invalid tx: exceeds the transaction limits");
+            return Ok(ProcessTxResponse::InvalidTx(err));
+        }
         let head = self.chain.head()?;
         let me = self.validator_signer.as_ref().map(|vs| vs.validator_id());
         let cur_block_header = self.chain.head_header()?;
@@ -1994,13 +2651,29 @@ impl Client {
             } else if check_only {
                 Ok(ProcessTxResponse::ValidTx)
             } else {
+                if let Some(limit) = self.config.transaction_limits.max_pooled_per_key {
+                    let pooled = self
+                        .sharded_tx_pool
+                        .nonces(shard_id, &tx.transaction.signer_id, &tx.transaction.public_key)
+                        .len();
+                    if pooled as u64 >= limit {
+                        debug!(target: "client", signer_id = %tx.transaction.signer_id, pooled, "Dropping tx: too many pooled transactions of the access key");
+                        metrics::TRANSACTION_DROPPED_POOLED_PER_KEY.inc();
+                        return Ok(ProcessTxResponse::NoResponse);
+                    }
+                }
                 let active_validator = self.active_validator(shard_id)?;
 
                 // TODO #6713: Transactions don't need to be recorded if the node is not a validator
                 // for the shard.
                 // If I'm not an active validator I should forward tx to next validators.
-                self.sharded_tx_pool.insert_transaction(shard_id, tx.clone());
-                trace!(target: "client", shard_id, "Recorded a transaction.");
+                let result = self.sharded_tx_pool.insert_transaction(shard_id, tx.clone());
+                if result == InsertTransactionResult::NoSpaceLeft {
+                    debug!(target: "client", shard_id, "Dropping tx: the transaction pool is full");
+                    return Ok(ProcessTxResponse::NoSpaceLeft);
+                }
+                let replaced = result == InsertTransactionResult::Replaced;
+                trace!(target: "client", shard_id, replaced, "Recorded a transaction.");
 
                 // Active validator:
                 //   possibly forward to next epoch validators
@@ -2014,12 +2687,20 @@ impl Client {
                     if !is_forwarded {
                         self.possibly_forward_tx_to_next_epoch(tx)?;
                     }
-                    Ok(ProcessTxResponse::ValidTx)
+                    if replaced {
+                        Ok(ProcessTxResponse::ReplacedTx)
+                    } else {
+                        Ok(ProcessTxResponse::ValidTx)
+                    }
                 } else if !is_forwarded {
                     trace!(target: "client", shard_id, "Forwarding a transaction.");
                     metrics::TRANSACTION_RECEIVED_NON_VALIDATOR.inc();
                     self.forward_tx(&epoch_id, tx)?;
-                    Ok(ProcessTxResponse::RequestRouted)
+                    if replaced {
+                        Ok(ProcessTxResponse::ReplacedTx)
+                    } else {
+                        Ok(ProcessTxResponse::RequestRouted)
+                    }
                 } else {
                     trace!(target: "client", shard_id, "Non-validator received a forwarded transaction, dropping it.");
                     metrics::TRANSACTION_RECEIVED_NON_VALIDATOR_FORWARDED.inc();
@@ -2178,7 +2859,7 @@ impl Client {
     /// When accepting challenge, we verify that it's valid given signature with current validators.
     pub fn process_challenge(&mut self, _challenge: Challenge) -> Result<(), Error> {
         // TODO(2445): Enable challenges when they are working correctly.
-        //        if self.challenges.contains_key(&challenge.hash) {
+        //        if !self.processed_challenges.insert(challenge.hash, StaticClock::utc()) {
         //            return Ok(());
         //        }
         //        debug!(target: "client", "Received challenge: {:?}", challenge);
@@ -2273,13 +2954,19 @@ impl Client {
     /// and the next epoch, so that the PeerManager can establish the priority connections
     /// in advance (before the epoch starts and they are actually needed).
     ///
+    /// Additionally it returns the TIER1 accounts of the next epoch alone, so that the validators
+    /// joining the active set can also pre-connect to each other over TIER2.
+    ///
     /// The result of the last call to get_tier1_accounts() is cached, so that it is not recomputed
     /// if the current epoch didn't change since the last call. In particular SetChainInfo is being
     /// send after processing each block (order of seconds), while the epoch changes way less
     /// frequently (order of hours).
-    fn get_tier1_accounts(&mut self, tip: &Tip) -> Result<Arc<AccountKeys>, Error> {
+    fn get_tier1_accounts(
+        &mut self,
+        tip: &Tip,
+    ) -> Result<(Arc<AccountKeys>, Arc<AccountKeys>), Error> {
         match &self.tier1_accounts_cache {
-            Some(it) if it.0 == tip.epoch_id => return Ok(it.1.clone()),
+            Some(it) if it.0 == tip.epoch_id => return Ok((it.1.clone(), it.2.clone())),
             _ => {}
         }
 
@@ -2301,14 +2988,16 @@ impl Client {
         // block & chunk producers of the next expoch is too expensive, we can postpone it
         // till almost the end of this epoch.
         let mut account_keys = AccountKeys::new();
+        let mut next_epoch_account_keys = AccountKeys::new();
         for epoch_id in [&tip.epoch_id, &tip.next_epoch_id] {
+            let mut epoch_account_keys = AccountKeys::new();
             // We assume here that calls to get_epoch_chunk_producers and get_epoch_block_producers_ordered
             // are cheaper than block processing (and that they will work with both this and
             // the next epoch). The caching on top of that (in tier1_accounts_cache field) is just
             // a defence in depth, based on the previous experience with expensive
             // RuntimeWithEpochManagerAdapter::get_validators_info call.
             for cp in self.runtime_adapter.get_epoch_chunk_producers(epoch_id)? {
-                account_keys
+                epoch_account_keys
                     .entry(cp.account_id().clone())
                     .or_default()
                     .insert(cp.public_key().clone());
@@ -2317,15 +3006,23 @@ impl Client {
                 .runtime_adapter
                 .get_epoch_block_producers_ordered(epoch_id, &tip.last_block_hash)?
             {
-                account_keys
+                epoch_account_keys
                     .entry(bp.account_id().clone())
                     .or_default()
                     .insert(bp.public_key().clone());
             }
+            for (account_id, keys) in &epoch_account_keys {
+                account_keys.entry(account_id.clone()).or_default().extend(keys.iter().cloned());
+            }
+            if epoch_id == &tip.next_epoch_id {
+                next_epoch_account_keys = epoch_account_keys;
+            }
         }
         let account_keys = Arc::new(account_keys);
-        self.tier1_accounts_cache = Some((tip.epoch_id.clone(), account_keys.clone()));
-        Ok(account_keys)
+        let next_epoch_account_keys = Arc::new(next_epoch_account_keys);
+        self.tier1_accounts_cache =
+            Some((tip.epoch_id.clone(), account_keys.clone(), next_epoch_account_keys.clone()));
+        Ok((account_keys, next_epoch_account_keys))
     }
 
     /// send_network_chain_info sends ChainInfo to PeerManagerActor.
@@ -2356,15 +3053,60 @@ impl Client {
             let num_shards = self.runtime_adapter.num_shards(&tip.epoch_id)?;
             (0..num_shards).collect()
         };
-        let tier1_accounts = self.get_tier1_accounts(&tip)?;
+        let (tier1_accounts, next_epoch_accounts) = self.get_tier1_accounts(&tip)?;
         let block = self.chain.get_block(&tip.last_block_hash)?;
         self.network_adapter.send(SetChainInfo(ChainInfo {
             block,
             tracked_shards,
             tier1_accounts,
+            next_epoch_accounts,
         }));
         Ok(())
     }
+
+    /// Computes and persists the state sync headers of the tracked shards, as soon as the sync
+    /// block (i.e. the first block of an epoch) is final. The StateRequestHeader requests are
+    /// then served from the store, instead of computing the headers on demand when the peers
+    /// start syncing to the new epoch.
+    fn precompute_state_sync_headers(
+        &mut self,
+        last_final_block: &CryptoHash,
+    ) -> Result<(), Error> {
+        if !self.config.precompute_state_sync_headers || last_final_block == &CryptoHash::default()
+        {
+            return Ok(());
+        }
+        let sync_hash = StateSync::get_epoch_start_sync_hash(&self.chain, last_final_block)?;
+        if self.last_precomputed_sync_hash == Some(sync_hash)
+            || &sync_hash == self.chain.genesis().hash()
+        {
+            return Ok(());
+        }
+        self.last_precomputed_sync_hash = Some(sync_hash);
+        let sync_header = self.chain.get_block_header(&sync_hash)?;
+        // The headers contain the state as of the end of the previous epoch, so the shards
+        // which were tracked in the previous epoch are the ones which matter.
+        let prev_header = self.chain.get_block_header(sync_header.prev_hash())?;
+        let prev_epoch_id = prev_header.epoch_id().clone();
+        let prev_prev_hash = *prev_header.prev_hash();
+        let me = self.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+        for shard_id in 0..self.runtime_adapter.num_shards(&prev_epoch_id)? {
+            if !self.runtime_adapter.cares_about_shard(me.as_ref(), &prev_prev_hash, shard_id, true)
+            {
+                continue;
+            }
+            let _span = tracing::debug_span!(
+                target: "sync",
+                "precompute_state_sync_header",
+                ?sync_hash,
+                shard_id)
+            .entered();
+            if let Err(err) = self.chain.get_state_response_header(shard_id, sync_hash) {
+                warn!(target: "sync", ?sync_hash, shard_id, ?err, "Failed to precompute the state sync header");
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Client {
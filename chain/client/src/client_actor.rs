@@ -9,10 +9,14 @@ use crate::adapter::{
     BlockApproval, BlockHeadersResponse, BlockResponse, ProcessTxRequest, ProcessTxResponse,
     RecvChallenge, SetNetworkInfo, StateResponse,
 };
+use crate::approval_queue::{ApprovalQueue, ProcessQueuedApprovals};
 use crate::client::{Client, EPOCH_START_INFO_BLOCKS};
 use crate::config_updater::ConfigUpdater;
 use crate::debug::new_network_info_view;
+use crate::head_watcher::SubscribeHeadChanges;
 use crate::info::{display_sync_status, InfoHelper};
+use crate::integrity_scan::IntegrityScanLoopHandle;
+use crate::metrics_snapshot::MetricsSnapshot;
 use crate::sync::state::{StateSync, StateSyncResult};
 use crate::{metrics, StatusResponse};
 use actix::dev::SendError;
@@ -26,7 +30,6 @@ use near_chain::chain::{
     BlockCatchUpResponse, StateSplitRequest, StateSplitResponse,
 };
 use near_chain::test_utils::format_hash;
-#[cfg(feature = "test_features")]
 use near_chain::ChainStoreAccess;
 use near_chain::{
     byzantine_assert, near_chain_primitives, Block, BlockHeader, BlockProcessingArtifact,
@@ -35,12 +38,14 @@ use near_chain::{
 use near_chain_configs::{ClientConfig, LogSummaryStyle};
 use near_chain_primitives::error::EpochErrorResultToChainError;
 use near_chunks::adapter::ShardsManagerRequestFromClient;
-use near_chunks::client::ShardsManagerResponse;
+use near_chunks::client::{ShardsManagerResponse, TransactionPoolSizes};
 use near_chunks::logic::cares_about_shard_this_or_next_epoch;
 use near_client_primitives::types::{
-    Error, GetClientConfig, GetClientConfigError, GetNetworkInfo, NetworkInfoResponse, Status,
-    StatusError, StatusSyncInfo, SyncStatus,
+    AllowDeepReorg, Error, GetClientConfig, GetClientConfigError, GetHeadChanges,
+    GetHeadChangesError, GetNetworkInfo, GetNonceGapsError, GetPooledNonces, GetTxPoolStatus,
+    GetTxPoolStatusError, NetworkInfoResponse, Status, StatusError, StatusSyncInfo, SyncStatus,
 };
+use near_network::concurrency::rate;
 use near_network::types::ReasonForBan;
 use near_network::types::{
     NetworkInfo, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
@@ -56,12 +61,14 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::state_part::PartId;
 use near_primitives::static_clock::StaticClock;
 use near_primitives::syncing::StatePartKey;
-use near_primitives::types::BlockHeight;
+use near_primitives::types::{BlockHeight, Nonce};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
-use near_primitives::version::PROTOCOL_VERSION;
-use near_primitives::views::{DetailedDebugStatus, ValidatorInfo};
+use near_primitives::version::{get_protocol_version, PROTOCOL_VERSION};
+use near_primitives::views::{
+    DetailedDebugStatus, HeadChangeView, TxPoolStatusView, ValidatorInfo,
+};
 use near_store::DBCol;
 use near_telemetry::TelemetryActor;
 use rand::seq::SliceRandom;
@@ -79,6 +86,10 @@ const STATUS_WAIT_TIME_MULTIPLIER: u64 = 10;
 /// `max_block_production_time` times this multiplier is how long we wait before rebroadcasting
 /// the current `head`
 const HEAD_STALL_MULTIPLIER: u32 = 4;
+/// A connected peer counts as healthy for the purpose of block production only if we've
+/// received a message from it within this time.
+const HEALTHY_PEER_MAX_SILENCE: near_primitives::time::Duration =
+    near_primitives::time::Duration::seconds(60);
 
 pub struct ClientActor {
     /// Adversarial controls
@@ -121,6 +132,16 @@ pub struct ClientActor {
 
     /// Manages updating the config.
     config_updater: Option<ConfigUpdater>,
+
+    /// Background re-verification of the recent chain data, if enabled.
+    integrity_scan: Option<IntegrityScanLoopHandle>,
+
+    /// Persisted counters, if enabled.
+    metrics_snapshot: Option<MetricsSnapshot>,
+    metrics_snapshot_next_attempt: DateTime<Utc>,
+
+    /// Approvals from the network, processed ahead of the messages in the mailbox.
+    approval_queue: ApprovalQueue,
 }
 
 /// Blocks the program until given genesis time arrives.
@@ -156,6 +177,7 @@ impl ClientActor {
         shutdown_signal: Option<broadcast::Sender<()>>,
         adv: crate::adversarial::Controls,
         config_updater: Option<ConfigUpdater>,
+        approval_queue: ApprovalQueue,
     ) -> Result<Self, Error> {
         let state_parts_arbiter = Arbiter::new();
         let self_addr = ctx.address();
@@ -171,6 +193,25 @@ impl ClientActor {
             info!(target: "client", "Starting validator node: {}", vs.validator_id());
         }
         let info_helper = InfoHelper::new(Some(telemetry_actor), &config, validator_signer.clone());
+        let integrity_scan = match config.integrity_scan.clone() {
+            Some(integrity_scan_config) => Some(
+                IntegrityScanLoopHandle::spawn(
+                    integrity_scan_config,
+                    client.chain.store().store().clone(),
+                    client.chain.store().get_genesis_height(),
+                    client.runtime_adapter.clone(),
+                )
+                .map_err(near_chain::Error::from)?,
+            ),
+            None => None,
+        };
+        let metrics_snapshot = match config.metrics_snapshot {
+            Some(_) => Some(
+                MetricsSnapshot::new(client.chain.store().store().clone())
+                    .map_err(near_chain::Error::from)?,
+            ),
+            None => None,
+        };
 
         let now = Utc::now();
         Ok(ClientActor {
@@ -214,6 +255,10 @@ impl ClientActor {
             fastforward_delta: 0,
             shutdown_signal,
             config_updater,
+            integrity_scan,
+            metrics_snapshot,
+            metrics_snapshot_next_attempt: now,
+            approval_queue,
         })
     }
 }
@@ -302,6 +347,7 @@ pub enum NetworkAdversarialMessage {
     AdvDisableDoomslug,
     AdvGetSavedBlocks,
     AdvCheckStorageConsistency,
+    AdvSetConfig(crate::adversarial::AdversarialConfig),
 }
 
 #[cfg(feature = "test_features")]
@@ -373,6 +419,11 @@ impl Handler<WithSpanContext<NetworkAdversarialMessage>> for ClientActor {
                 chain_store_update.commit().expect("adv method should not fail");
                 None
             }
+            NetworkAdversarialMessage::AdvSetConfig(config) => {
+                info!(target: "adversary", ?config, "Setting adversarial config");
+                this.client.adv_set_config(config);
+                None
+            }
             NetworkAdversarialMessage::AdvGetSavedBlocks => {
                 info!(target: "adversary", "Requested number of saved blocks");
                 let store = this.client.chain.store().store();
@@ -509,6 +560,19 @@ impl Handler<WithSpanContext<BlockApproval>> for ClientActor {
     }
 }
 
+impl Handler<WithSpanContext<ProcessQueuedApprovals>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<ProcessQueuedApprovals>, ctx: &mut Context<Self>) {
+        // The queued approvals are processed by `check_triggers` in `wrap`.
+        self.wrap(msg, ctx, "ProcessQueuedApprovals", |_, msg| {
+            metrics::CLIENT_QUEUE_DELAY
+                .with_label_values(&["mailbox"])
+                .observe(msg.sent_at.elapsed().as_secs_f64());
+        })
+    }
+}
+
 /// StateResponse is used during StateSync and catchup.
 /// It contains either StateSync header information (that tells us how many parts there are etc) or a single part.
 impl Handler<WithSpanContext<StateResponse>> for ClientActor {
@@ -651,6 +715,12 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
             if self.client.sync_status.is_syncing() {
                 return Err(StatusError::NodeIsSyncing);
             }
+
+            if let Some(corruption) =
+                self.integrity_scan.as_ref().and_then(|scan| scan.corruption())
+            {
+                return Err(StatusError::DataCorruption { error_message: corruption.to_string() });
+            }
         }
         let validators: Vec<ValidatorInfo> = self
             .client
@@ -679,6 +749,18 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
             None => (None, None),
         };
         let node_key = validator_public_key.clone();
+        let protocol_version_vote = match &self.client.validator_signer {
+            Some(_) => Some(match self.client.config.protocol_version_vote_override {
+                Some(version) => version,
+                None => get_protocol_version(
+                    self.client
+                        .runtime_adapter
+                        .get_epoch_protocol_version(&head.next_epoch_id)
+                        .into_chain_error()?,
+                ),
+            }),
+            None => None,
+        };
 
         let mut earliest_block_hash = None;
         let mut earliest_block_height = None;
@@ -719,6 +801,7 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
             version: self.client.config.version.clone(),
             protocol_version,
             latest_protocol_version: PROTOCOL_VERSION,
+            protocol_version_vote,
             chain_id: self.client.config.chain_id.clone(),
             rpc_addr: self.client.config.rpc_addr.clone(),
             validators,
@@ -733,6 +816,7 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
                 earliest_block_time,
                 epoch_id: Some(head.epoch_id),
                 epoch_start_height,
+                finality: self.info_helper.finality_status(&self.client.chain),
             },
             validator_account_id,
             validator_public_key,
@@ -745,11 +829,19 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
 }
 
 /// Private to public API conversion.
-fn make_peer_info(from: near_network::types::PeerInfo) -> near_client_primitives::types::PeerInfo {
+fn make_peer_info(
+    from: &near_network::types::ConnectedPeerInfo,
+) -> near_client_primitives::types::PeerInfo {
+    let peer_info = &from.full_peer_info.peer_info;
     near_client_primitives::types::PeerInfo {
-        id: from.id,
-        addr: from.addr,
-        account_id: from.account_id,
+        id: peer_info.id.clone(),
+        addr: peer_info.addr,
+        account_id: peer_info.account_id.clone(),
+        traffic_by_message_type: from
+            .traffic_by_message_type
+            .iter()
+            .map(|(msg_type, traffic)| (msg_type.to_string(), (*traffic).into()))
+            .collect(),
     }
 }
 
@@ -780,7 +872,7 @@ impl Handler<WithSpanContext<GetNetworkInfo>> for ClientActor {
 
         Ok(NetworkInfoResponse {
             connected_peers: (self.network_info.connected_peers.iter())
-                .map(|fpi| make_peer_info(fpi.full_peer_info.peer_info.clone()))
+                .map(make_peer_info)
                 .collect(),
             num_connected_peers: self.network_info.num_connected_peers,
             peer_max_count: self.network_info.peer_max_count,
@@ -1065,11 +1157,16 @@ impl ClientActor {
                     || num_chunks as u64
                         == self.client.runtime_adapter.num_shards(&epoch_id).unwrap();
 
+                let num_healthy_peers = self.num_healthy_peers();
                 if self.client.doomslug.ready_to_produce_block(
                     StaticClock::instant(),
                     height,
                     have_all_chunks,
                     log_block_production_info,
+                ) && self.client.check_connected_enough_to_produce_block(
+                    height,
+                    &me,
+                    num_healthy_peers,
                 ) {
                     if let Err(err) = self.produce_block(height) {
                         // If there is an error, report it and let it retry on the next loop step.
@@ -1084,6 +1181,24 @@ impl ClientActor {
         Ok(())
     }
 
+    /// Number of the connected peers we've heard from recently.
+    pub(crate) fn num_healthy_peers(&self) -> usize {
+        (self.network_info.connected_peers.iter())
+            .filter(|peer| peer.last_time_received_message.elapsed() < HEALTHY_PEER_MAX_SILENCE)
+            .count()
+    }
+
+    /// Processes the approvals from the priority lane.
+    fn process_queued_approvals(&mut self) {
+        for (BlockApproval(approval, peer_id), queued_at) in self.approval_queue.take() {
+            metrics::CLIENT_QUEUE_DELAY
+                .with_label_values(&["approval"])
+                .observe(queued_at.elapsed().as_secs_f64());
+            debug!(target: "client", "Receive approval {:?} from peer {:?}", approval, peer_id);
+            self.client.collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id));
+        }
+    }
+
     fn schedule_triggers(&mut self, ctx: &mut Context<Self>) {
         let wait = self.check_triggers(ctx);
 
@@ -1105,10 +1220,32 @@ impl ClientActor {
     /// Returns the delay before the next time `check_triggers` should be called, which is
     /// min(time until the closest trigger, 1 second).
     fn check_triggers(&mut self, ctx: &mut Context<ClientActor>) -> Duration {
+        self.process_queued_approvals();
+
         if let Some(config_updater) = &mut self.config_updater {
-            config_updater.try_update(&|updateable_client_config| {
-                self.client.update_client_config(updateable_client_config)
-            });
+            config_updater.try_update(
+                &mut |updateable_client_config| {
+                    self.client.update_client_config(updateable_client_config)
+                },
+                &|updateable_network_config| {
+                    self.network_adapter.send(PeerManagerMessageRequest::SetRecvLimits {
+                        tier1: rate::Limit {
+                            qps: updateable_network_config.tier1_recv_bytes_per_second,
+                            burst: updateable_network_config.tier1_recv_burst_bytes,
+                        },
+                        tier2: rate::Limit {
+                            qps: updateable_network_config.tier2_recv_bytes_per_second,
+                            burst: updateable_network_config.tier2_recv_burst_bytes,
+                        },
+                    });
+                    self.network_adapter.send(PeerManagerMessageRequest::SetPeersConfig {
+                        max_num_peers: updateable_network_config.max_num_peers,
+                        whitelist_nodes: updateable_network_config.whitelist_nodes,
+                        blacklist: updateable_network_config.blacklist,
+                        boot_nodes: updateable_network_config.boot_nodes,
+                    })
+                },
+            );
         }
 
         // Check block height to trigger expected shutdown
@@ -1196,6 +1333,22 @@ impl ClientActor {
                 .to_std()
                 .unwrap_or(delay),
         );
+        if let Some(period) = self.client.config.metrics_snapshot.as_ref().map(|c| c.period) {
+            self.metrics_snapshot_next_attempt = self.run_timer(
+                period,
+                self.metrics_snapshot_next_attempt,
+                ctx,
+                |act, _ctx| act.snapshot_metrics(),
+                "metrics_snapshot",
+            );
+            delay = core::cmp::min(
+                delay,
+                self.metrics_snapshot_next_attempt
+                    .signed_duration_since(now)
+                    .to_std()
+                    .unwrap_or(delay),
+            );
+        }
         timer.observe_duration();
         delay
     }
@@ -1258,10 +1411,25 @@ impl ClientActor {
     fn produce_block(&mut self, next_height: BlockHeight) -> Result<(), Error> {
         let _span = tracing::debug_span!(target: "client", "produce_block", next_height).entered();
         if let Some(block) = self.client.produce_block(next_height)? {
+            #[cfg(feature = "test_features")]
+            let withhold = self.client.adv_config.withhold_blocks;
+            #[cfg(not(feature = "test_features"))]
+            let withhold = false;
             // If we produced the block, send it out before we apply the block.
-            self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
-                NetworkRequests::Block { block: block.clone() },
-            ));
+            if !withhold {
+                self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+                    NetworkRequests::Block { block: block.clone() },
+                ));
+            }
+            #[cfg(feature = "test_features")]
+            if self.client.adv_config.double_sign_blocks {
+                if let Some(block) = self.client.adv_make_conflicting_block(&block) {
+                    info!(target: "adversary", hash = ?block.hash(), "Broadcasting conflicting block");
+                    self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+                        NetworkRequests::Block { block },
+                    ));
+                }
+            }
             // We’ve produced the block so that counts as validated block.
             let block = MaybeValidated::from_validated(block);
             let res = self.client.start_process_block(
@@ -1339,6 +1507,7 @@ impl ClientActor {
             epoch_height,
             last_final_block_height_in_epoch,
         );
+        self.info_helper.finality_status(&self.client.chain);
     }
 
     /// Process all blocks that were accepted by calling other relevant services.
@@ -1740,6 +1909,14 @@ impl ClientActor {
             &self.config_updater,
         )
     }
+
+    fn snapshot_metrics(&mut self) {
+        if let Some(metrics_snapshot) = &self.metrics_snapshot {
+            if let Err(err) = metrics_snapshot.snapshot() {
+                error!(target: "client", ?err, "Failed to persist the metrics snapshot");
+            }
+        }
+    }
 }
 
 impl Drop for ClientActor {
@@ -1833,7 +2010,8 @@ impl Handler<WithSpanContext<BlockCatchUpRequest>> for SyncJobsActor {
         _: &mut Self::Context,
     ) -> Self::Result {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
-        let results = do_apply_chunks(msg.block_hash, msg.block_height, msg.work);
+        let results =
+            do_apply_chunks(&msg.apply_chunks_pool, msg.block_hash, msg.block_height, msg.work);
 
         self.client_addr.do_send(
             BlockCatchUpResponse { sync_hash: msg.sync_hash, block_hash: msg.block_hash, results }
@@ -1917,7 +2095,14 @@ impl Handler<WithSpanContext<ShardsManagerResponse>> for ClientActor {
     ) -> Self::Result {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         match msg {
-            ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
+            ShardsManagerResponse::ChunkCompleted {
+                partial_chunk,
+                shard_chunk,
+                completion_stats,
+            } => {
+                if let Some(stats) = completion_stats {
+                    self.client.record_chunk_completion_stats(stats);
+                }
                 self.client.on_chunk_completed(
                     partial_chunk,
                     shard_chunk,
@@ -1952,6 +2137,73 @@ impl Handler<WithSpanContext<GetClientConfig>> for ClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetHeadChanges>> for ClientActor {
+    type Result = Result<Vec<HeadChangeView>, GetHeadChangesError>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetHeadChanges>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _d = delay_detector::DelayDetector::new(|| "client get head changes".into());
+
+        self.client.head_watcher.changes_since(msg.since_id)
+    }
+}
+
+impl Handler<WithSpanContext<GetPooledNonces>> for ClientActor {
+    type Result = Result<Vec<Nonce>, GetNonceGapsError>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetPooledNonces>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _d = delay_detector::DelayDetector::new(|| "client get pooled nonces".into());
+
+        self.client.get_pooled_nonces(&msg.account_id, &msg.public_key)
+    }
+}
+
+impl Handler<WithSpanContext<GetTxPoolStatus>> for ClientActor {
+    type Result = Result<TxPoolStatusView, GetTxPoolStatusError>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetTxPoolStatus>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _d = delay_detector::DelayDetector::new(|| "client get tx pool status".into());
+
+        self.client.get_tx_pool_status(msg.include_transactions, msg.limit)
+    }
+}
+
+impl Handler<WithSpanContext<SubscribeHeadChanges>> for ClientActor {
+    type Result = actix::MessageResult<WithSpanContext<SubscribeHeadChanges>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SubscribeHeadChanges>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        actix::MessageResult(self.client.head_watcher.subscribe())
+    }
+}
+
+impl Handler<WithSpanContext<AllowDeepReorg>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<AllowDeepReorg>, _: &mut Context<Self>) {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        self.client.chain.allow_deep_reorg(msg.block_hash);
+    }
+}
+
 /// Returns random seed sampled from the current thread
 pub fn random_seed_from_thread() -> RngSeed {
     let mut rng_seed: RngSeed = [0; 32];
@@ -1972,11 +2224,13 @@ pub fn start_client(
     sender: Option<broadcast::Sender<()>>,
     adv: crate::adversarial::Controls,
     config_updater: Option<ConfigUpdater>,
+    approval_queue: ApprovalQueue,
+    pool_sizes: TransactionPoolSizes,
 ) -> (Addr<ClientActor>, ArbiterHandle) {
     let client_arbiter = Arbiter::new();
     let client_arbiter_handle = client_arbiter.handle();
     wait_until_genesis(&chain_genesis.time);
-    let client = Client::new(
+    let mut client = Client::new(
         client_config.clone(),
         chain_genesis,
         runtime_adapter,
@@ -1987,6 +2241,7 @@ pub fn start_client(
         random_seed_from_thread(),
     )
     .unwrap();
+    client.sharded_tx_pool.share_sizes(pool_sizes);
     let client_addr = ClientActor::start_in_arbiter(&client_arbiter_handle, move |ctx| {
         ClientActor::new(
             client,
@@ -2000,6 +2255,7 @@ pub fn start_client(
             sender,
             adv,
             config_updater,
+            approval_queue,
         )
         .unwrap()
     });
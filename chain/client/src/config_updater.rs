@@ -1,4 +1,4 @@
-use near_chain_configs::UpdateableClientConfig;
+use near_chain_configs::{UpdateableClientConfig, UpdateableNetworkConfig};
 use near_dyn_configs::{UpdateableConfigLoaderError, UpdateableConfigs};
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
@@ -24,7 +24,11 @@ impl ConfigUpdater {
 
     /// Check if any of the configs were updated.
     /// If they did, the receiver (rx_config_update) will contain a clone of the new configs.
-    pub fn try_update(&mut self, update_client_config_fn: &dyn Fn(UpdateableClientConfig)) {
+    pub fn try_update(
+        &mut self,
+        update_client_config_fn: &mut dyn FnMut(UpdateableClientConfig),
+        update_network_config_fn: &dyn Fn(UpdateableNetworkConfig),
+    ) {
         while let Ok(maybe_updateable_configs) = self.rx_config_update.try_recv() {
             match maybe_updateable_configs {
                 Ok(updateable_configs) => {
@@ -32,6 +36,10 @@ impl ConfigUpdater {
                         update_client_config_fn(client_config);
                         tracing::info!(target: "config", "Updated ClientConfig");
                     }
+                    if let Some(network_config) = updateable_configs.network_config {
+                        update_network_config_fn(network_config);
+                        tracing::info!(target: "config", "Updated NetworkConfig");
+                    }
                     self.updateable_configs_error = None;
                 }
                 Err(err) => {
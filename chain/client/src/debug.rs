@@ -1,13 +1,16 @@
 //! Structs in this file are used for debug purposes, and might change at any time
 //! without backwards compatibility.
+use crate::metrics;
 use crate::ClientActor;
 use actix::{Context, Handler};
 use borsh::BorshSerialize;
 use itertools::Itertools;
 use near_chain::crypto_hash_timer::CryptoHashTimer;
 use near_chain::{near_chain_primitives, Chain, ChainStoreAccess, RuntimeWithEpochManagerAdapter};
+use near_chunks::ChunkPartSupplier;
 use near_client_primitives::debug::{
-    ApprovalAtHeightStatus, BlockProduction, ChunkCollection, DebugBlockStatusData, DebugStatus,
+    ApprovalAtHeightStatus, BlockProduction, ChunkCollection, ChunkCompletionView,
+    ChunkInclusionStatus, ChunkPartSupplierView, DebugBlockStatusData, DebugStatus,
     DebugStatusResponse, MissedHeightInfo, ProductionAtHeight, ValidatorStatus,
 };
 use near_client_primitives::types::Error;
@@ -50,6 +53,9 @@ const DEBUG_MAX_PRODUCTION_BLOCKS_TO_SHOW: u64 = 1000;
 /// Number of blocks (and chunks) for which to keep the detailed timing information for debug purposes.
 pub const PRODUCTION_TIMES_CACHE_SIZE: usize = 1000;
 
+/// Number of recently completed chunks for which to keep the stats of collecting their parts.
+pub const CHUNK_COMPLETION_STATS_CACHE_SIZE: usize = 100;
+
 pub struct BlockProductionTracker(lru::LruCache<BlockHeight, BlockProduction>);
 
 impl BlockProductionTracker {
@@ -92,6 +98,15 @@ impl BlockProductionTracker {
         height: BlockHeight,
         chunk_collections: Vec<ChunkCollection>,
     ) {
+        for (shard_id, chunk_collection) in chunk_collections.iter().enumerate() {
+            metrics::CHUNK_INCLUSION_CUTOFF_TOTAL
+                .with_label_values(&[
+                    &shard_id.to_string(),
+                    chunk_collection.chunk_producer.as_str(),
+                    if chunk_collection.chunk_included { "true" } else { "false" },
+                ])
+                .inc();
+        }
         if let Some(block_production) = self.0.get_mut(&height) {
             block_production.block_production_time = Some(StaticClock::utc());
             block_production.chunks_collection_time = chunk_collections;
@@ -99,6 +114,7 @@ impl BlockProductionTracker {
     }
 
     /// Record chunk collected after a block is produced if the block didn't include a chunk for the shard.
+    /// Such a chunk is attributed to its producer as late in the metrics.
     /// If called before the block was produced, nothing happens.
     pub(crate) fn record_chunk_collected(&mut self, height: BlockHeight, shard_id: ShardId) {
         if let Some(block_production) = self.0.get_mut(&height) {
@@ -106,7 +122,21 @@ impl BlockProductionTracker {
             // Check that chunk_collection is set and we haven't received this chunk yet.
             if let Some(chunk_collection) = chunk_collections.get_mut(shard_id as usize) {
                 if chunk_collection.received_time.is_none() {
-                    chunk_collection.received_time = Some(StaticClock::utc());
+                    let now = StaticClock::utc();
+                    chunk_collection.received_time = Some(now);
+                    chunk_collection.inclusion_status = ChunkInclusionStatus::ArrivedLate;
+                    let shard_label = shard_id.to_string();
+                    metrics::CHUNK_ARRIVED_AFTER_CUTOFF_TOTAL
+                        .with_label_values(&[
+                            &shard_label,
+                            chunk_collection.chunk_producer.as_str(),
+                        ])
+                        .inc();
+                    if let Some(cutoff) = block_production.block_production_time {
+                        metrics::CHUNK_DELAY_AFTER_CUTOFF
+                            .with_label_values(&[&shard_label])
+                            .observe((now - cutoff).num_milliseconds() as f64 / 1000.);
+                    }
                 }
             }
             // Otherwise, it means chunk_collections is not set yet, which means the block wasn't produced.
@@ -128,6 +158,7 @@ impl BlockProductionTracker {
                     chunk_producer: chunk_producer.clone(),
                     received_time: Some(*chunk_time),
                     chunk_included: true,
+                    inclusion_status: ChunkInclusionStatus::Included,
                 });
             } else {
                 let chunk_producer =
@@ -136,6 +167,7 @@ impl BlockProductionTracker {
                     chunk_producer,
                     received_time: None,
                     chunk_included: false,
+                    inclusion_status: ChunkInclusionStatus::NotReceived,
                 });
             }
         }
@@ -178,11 +210,49 @@ impl Handler<WithSpanContext<DebugStatus>> for ClientActor {
             DebugStatus::ChainProcessingStatus => Ok(DebugStatusResponse::ChainProcessingStatus(
                 self.client.chain.get_chain_processing_info(),
             )),
+            DebugStatus::RolloutFlags => {
+                Ok(DebugStatusResponse::RolloutFlags(self.client.config.rollout_flags.clone()))
+            }
+            DebugStatus::ChunkCompletion => {
+                Ok(DebugStatusResponse::ChunkCompletion(self.get_chunk_completion_stats()))
+            }
+            DebugStatus::BlockProductionDryRun => Ok(DebugStatusResponse::BlockProductionDryRun(
+                self.client.dry_run_block_production(self.num_healthy_peers())?,
+            )),
         }
     }
 }
 
 impl ClientActor {
+    fn get_chunk_completion_stats(&self) -> Vec<ChunkCompletionView> {
+        self.client
+            .chunk_completion_stats
+            .iter()
+            .rev()
+            .map(|stats| ChunkCompletionView {
+                chunk_hash: stats.chunk_hash.clone(),
+                height_created: stats.height_created,
+                shard_id: stats.shard_id,
+                completion_time_ms: stats
+                    .completion_time
+                    .map(|time| time.whole_milliseconds().max(0) as u64),
+                num_requests: stats.num_requests,
+                suppliers: stats
+                    .suppliers
+                    .iter()
+                    .map(|(supplier, num_parts)| ChunkPartSupplierView {
+                        supplier: match supplier {
+                            ChunkPartSupplier::ChunkProducer => "chunk_producer".to_string(),
+                            ChunkPartSupplier::Forward => "forward".to_string(),
+                            ChunkPartSupplier::Response(peer_id) => peer_id.to_string(),
+                        },
+                        num_parts: *num_parts,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
     // Gets a list of block producers and chunk-only producers for a given epoch.
     fn get_producers_for_epoch(
         &self,
@@ -673,6 +743,11 @@ fn new_peer_info_view(chain: &Chain, connected_peer_info: &ConnectedPeerInfo) ->
             .whole_milliseconds() as u64,
         is_outbound_peer: connected_peer_info.peer_type == PeerType::Outbound,
         nonce: connected_peer_info.nonce,
+        traffic_by_message_type: connected_peer_info
+            .traffic_by_message_type
+            .iter()
+            .map(|(msg_type, traffic)| (msg_type.to_string(), (*traffic).into()))
+            .collect(),
     }
 }
 
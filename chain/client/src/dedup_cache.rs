@@ -0,0 +1,167 @@
+//! Bounded caches of the recently processed challenges and forwarded transactions.
+//!
+//! The client shouldn't process the same challenge, or forward the same transaction, over and
+//! over again. A `DedupCache` remembers the hashes it has seen for `ttl`, but at most `capacity`
+//! of them, evicting the least recently seen ones first. Optionally the cache is persisted in
+//! DBCol::DedupCache, so that the hashes seen before a restart are not reprocessed after it.
+use crate::metrics;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use near_primitives::hash::CryptoHash;
+use near_store::{DBCol, Store};
+
+pub(crate) struct DedupCache {
+    /// Name of the cache, used as the metrics label and as the key prefix in the store.
+    name: &'static str,
+    ttl: Duration,
+    /// Time at which each hash has been seen.
+    entries: lru::LruCache<CryptoHash, DateTime<Utc>>,
+    store: Option<Store>,
+}
+
+impl DedupCache {
+    /// Creates the cache. If `store` is set, the unexpired entries persisted before the restart
+    /// are loaded from it and the expired ones are removed.
+    pub fn new(
+        name: &'static str,
+        capacity: usize,
+        ttl: std::time::Duration,
+        store: Option<Store>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut this = Self {
+            name,
+            ttl: Duration::from_std(ttl).unwrap_or(Duration::max_value()),
+            entries: lru::LruCache::new(capacity),
+            store,
+        };
+        if let Err(err) = this.load(now) {
+            tracing::error!(target: "client", name, ?err, "Failed to load the dedup cache");
+        }
+        this.update_size_metric();
+        this
+    }
+
+    /// Records that `hash` has been seen at `now`.
+    /// Returns false if it has already been seen within the last `ttl`, in which case it
+    /// shouldn't be processed again.
+    pub fn insert(&mut self, hash: CryptoHash, now: DateTime<Utc>) -> bool {
+        if let Some(seen) = self.entries.get(&hash) {
+            if now < *seen + self.ttl {
+                metrics::DEDUP_CACHE_HITS.with_label_values(&[self.name]).inc();
+                return false;
+            }
+        }
+        let evicted = match self.entries.push(hash, now) {
+            Some((evicted, _)) if evicted != hash => Some(evicted),
+            _ => None,
+        };
+        if let Err(err) = self.persist(&hash, now, evicted) {
+            let name = self.name;
+            tracing::error!(target: "client", name, ?err, "Failed to persist the dedup cache");
+        }
+        self.update_size_metric();
+        true
+    }
+
+    fn load(&mut self, now: DateTime<Utc>) -> std::io::Result<()> {
+        let store = match &self.store {
+            Some(store) => store.clone(),
+            None => return Ok(()),
+        };
+        let prefix = key_prefix(self.name);
+        let mut entries = vec![];
+        let mut update = store.store_update();
+        for item in store.iter_prefix_ser::<i64>(DBCol::DedupCache, &prefix) {
+            let (key, seen) = item?;
+            let seen = Utc.timestamp_millis_opt(seen).single();
+            match (seen, CryptoHash::try_from(&key[prefix.len()..])) {
+                (Some(seen), Ok(hash)) if now < seen + self.ttl => entries.push((seen, hash)),
+                _ => update.delete(DBCol::DedupCache, &key),
+            }
+        }
+        // Insert the oldest entries first, so that they are evicted first.
+        entries.sort();
+        for (seen, hash) in entries {
+            if let Some((evicted, _)) = self.entries.push(hash, seen) {
+                update.delete(DBCol::DedupCache, &key(self.name, &evicted));
+            }
+        }
+        update.commit()
+    }
+
+    fn persist(
+        &self,
+        hash: &CryptoHash,
+        now: DateTime<Utc>,
+        evicted: Option<CryptoHash>,
+    ) -> std::io::Result<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        let mut update = store.store_update();
+        update.set_ser(DBCol::DedupCache, &key(self.name, hash), &now.timestamp_millis())?;
+        if let Some(evicted) = evicted {
+            update.delete(DBCol::DedupCache, &key(self.name, &evicted));
+        }
+        update.commit()
+    }
+
+    fn update_size_metric(&self) {
+        metrics::DEDUP_CACHE_SIZE.with_label_values(&[self.name]).set(self.entries.len() as i64);
+    }
+}
+
+fn key_prefix(name: &str) -> Vec<u8> {
+    let mut prefix = name.as_bytes().to_vec();
+    prefix.push(b':');
+    prefix
+}
+
+fn key(name: &str, hash: &CryptoHash) -> Vec<u8> {
+    let mut key = key_prefix(name);
+    key.extend_from_slice(hash.as_ref());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(1000).unwrap()
+    }
+
+    #[test]
+    fn expires_after_ttl() {
+        let ttl = std::time::Duration::from_secs(10);
+        let mut cache = DedupCache::new("test", 10, ttl, None, now());
+        let hash = CryptoHash::hash_bytes(b"tx");
+        assert!(cache.insert(hash, now()));
+        assert!(!cache.insert(hash, now() + Duration::seconds(9)));
+        assert!(cache.insert(hash, now() + Duration::seconds(10)));
+    }
+
+    #[test]
+    fn persists_across_restarts() {
+        let store = near_store::test_utils::create_test_store();
+        let ttl = std::time::Duration::from_secs(10);
+        let hashes: Vec<_> = (0..3u8).map(|i| CryptoHash::hash_bytes(&[i])).collect();
+        let mut cache = DedupCache::new("test", 2, ttl, Some(store.clone()), now());
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(cache.insert(*hash, now() + Duration::milliseconds(i as i64)));
+        }
+
+        // The evicted entry is not restored, the others are.
+        let mut cache = DedupCache::new("test", 2, ttl, Some(store.clone()), now());
+        assert!(cache.insert(hashes[0], now() + Duration::milliseconds(3)));
+        let mut cache = DedupCache::new("test", 2, ttl, Some(store.clone()), now());
+        assert!(!cache.insert(hashes[0], now()));
+        assert!(!cache.insert(hashes[2], now()));
+
+        // Expired entries are removed from the store.
+        let later = now() + Duration::seconds(11);
+        DedupCache::new("test", 2, ttl, Some(store.clone()), later);
+        assert_eq!(0, store.iter(DBCol::DedupCache).count());
+    }
+}
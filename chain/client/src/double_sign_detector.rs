@@ -0,0 +1,159 @@
+//! Detection of another node signing with the validator key of this node.
+//!
+//! Operators of test networks sometimes run two nodes with the same validator key by accident.
+//! Both nodes then sign blocks and approvals at the same heights, which will get the validator
+//! slashed once slashing is live. Such a node receives blocks and approvals signed with its own
+//! key, which this process hasn't signed. When that happens, the detector halts all further
+//! signing by this node and raises a critical alert, until the node is restarted.
+//!
+//! The largest height signed is persisted, so that after a restart the messages signed before it
+//! (possibly well above the head, e.g. the skip approvals) are not taken for foreign ones.
+use crate::metrics;
+use near_primitives::block::{Approval, Block};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockHeightDelta};
+use near_store::{DBCol, Store, LAST_SIGNED_HEIGHT_KEY};
+
+/// Number of the signed blocks and approvals remembered by the detector.
+const MAX_SIGNED: usize = 1024;
+/// The node might have crashed after signing a message, but before persisting its height, so the
+/// messages a few heights above the persisted one are not considered foreign after a restart.
+const RESTART_GRACE_HEIGHTS: BlockHeightDelta = 5;
+
+pub(crate) struct DoubleSignDetector {
+    /// Hashes of the blocks and approvals signed by this process.
+    signed: lru::LruCache<CryptoHash, ()>,
+    store: Store,
+    /// The largest height signed, as persisted in the store.
+    last_signed_height: BlockHeight,
+    /// Messages at or below this height might have been signed before the restart.
+    min_height: BlockHeight,
+    halted: bool,
+}
+
+impl DoubleSignDetector {
+    pub fn new(store: Store, head_height: BlockHeight) -> std::io::Result<Self> {
+        let last_signed_height =
+            store.get_ser(DBCol::BlockMisc, LAST_SIGNED_HEIGHT_KEY)?.unwrap_or_default();
+        Ok(Self {
+            signed: lru::LruCache::new(MAX_SIGNED),
+            store,
+            last_signed_height,
+            min_height: std::cmp::max(head_height, last_signed_height) + RESTART_GRACE_HEIGHTS,
+            halted: false,
+        })
+    }
+
+    /// Whether another node signing with our key has been detected. If so, this node must not
+    /// sign anything anymore.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn record_block(&mut self, block: &Block) {
+        self.record(block.header().height(), *block.hash());
+    }
+
+    pub fn record_approval(&mut self, approval: &Approval) {
+        self.record(approval.target_height, approval_hash(approval));
+    }
+
+    fn record(&mut self, height: BlockHeight, hash: CryptoHash) {
+        self.signed.put(hash, ());
+        if height <= self.last_signed_height {
+            return;
+        }
+        self.last_signed_height = height;
+        let mut update = self.store.store_update();
+        if let Err(err) = update
+            .set_ser(DBCol::BlockMisc, LAST_SIGNED_HEIGHT_KEY, &height)
+            .and_then(|()| update.commit())
+        {
+            tracing::warn!(target: "client", ?err, height, "Failed to persist the last signed height");
+        }
+    }
+
+    /// Checks a block signed with our key, received from the network.
+    /// Returns false if this process hasn't signed it.
+    pub fn check_block(&mut self, block: &Block) -> bool {
+        self.check("block", block.header().height(), *block.hash())
+    }
+
+    /// Checks an approval signed with our key, received from the network.
+    /// Returns false if this process hasn't signed it.
+    pub fn check_approval(&mut self, approval: &Approval) -> bool {
+        self.check("approval", approval.target_height, approval_hash(approval))
+    }
+
+    fn check(&mut self, kind: &str, height: BlockHeight, hash: CryptoHash) -> bool {
+        if height <= self.min_height || self.signed.contains(&hash) {
+            return true;
+        }
+        if !self.halted {
+            tracing::error!(
+                target: "client",
+                kind,
+                height,
+                %hash,
+                "CRITICAL: received a {kind} signed with our validator key, which this node \
+                 hasn't signed. Another node is running with the same key. Halting signing to \
+                 avoid double signing; stop the other node and restart this one.",
+            );
+            metrics::DOUBLE_SIGN_DETECTED.set(1);
+            self.halted = true;
+        }
+        false
+    }
+}
+
+fn approval_hash(approval: &Approval) -> CryptoHash {
+    CryptoHash::hash_bytes(&Approval::get_data_for_sig(&approval.inner, approval.target_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::validator_signer::InMemoryValidatorSigner;
+
+    fn approval(parent_height: BlockHeight, target_height: BlockHeight) -> Approval {
+        let signer = InMemoryValidatorSigner::from_seed(
+            "test".parse().unwrap(),
+            near_crypto::KeyType::ED25519,
+            "test",
+        );
+        let parent_hash = CryptoHash::hash_borsh(parent_height);
+        Approval::new(parent_hash, parent_height, target_height, &signer)
+    }
+
+    #[test]
+    fn halts_on_foreign_approval() {
+        let store = near_store::test_utils::create_test_store();
+        let mut detector = DoubleSignDetector::new(store, 10).unwrap();
+        // Might have been signed before the restart.
+        assert!(detector.check_approval(&approval(14, 15)));
+        let own = approval(19, 20);
+        detector.record_approval(&own);
+        assert!(detector.check_approval(&own));
+        assert!(!detector.is_halted());
+
+        assert!(!detector.check_approval(&approval(18, 20)));
+        assert!(detector.is_halted());
+    }
+
+    #[test]
+    fn skips_heights_signed_before_restart() {
+        let store = near_store::test_utils::create_test_store();
+        let mut detector = DoubleSignDetector::new(store.clone(), 10).unwrap();
+        detector.record_approval(&approval(49, 50));
+
+        // The head didn't move, but the approvals up to the last signed height might have been
+        // signed before the restart.
+        let mut detector = DoubleSignDetector::new(store, 10).unwrap();
+        assert!(detector.check_approval(&approval(48, 50)));
+        assert!(detector.check_approval(&approval(50, 50 + RESTART_GRACE_HEIGHTS)));
+        assert!(!detector.is_halted());
+
+        assert!(!detector.check_approval(&approval(50, 51 + RESTART_GRACE_HEIGHTS)));
+        assert!(detector.is_halted());
+    }
+}
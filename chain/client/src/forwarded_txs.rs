@@ -0,0 +1,132 @@
+//! Transactions forwarded by this node whose inclusion in a chunk hasn't been observed yet.
+//!
+//! Each transaction is due to be forwarded again at some height, unless its inclusion is observed
+//! before. The transactions are indexed by that height, so that finding the due ones on a new
+//! head doesn't walk all of them. At most `capacity` transactions are kept, the ones due the
+//! soonest are forgotten first.
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::BlockHeight;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Instant;
+
+pub(crate) struct ForwardedTx {
+    pub tx: SignedTransaction,
+    /// When the transaction was forwarded for the first time.
+    pub forwarded_at: Instant,
+    /// Number of times the transaction was forwarded again.
+    pub retries: u32,
+}
+
+pub(crate) struct PendingForwardedTxs {
+    capacity: usize,
+    /// The transactions and the heights at which they are due to be forwarded again.
+    txs: HashMap<CryptoHash, (BlockHeight, ForwardedTx)>,
+    /// Due height and hash of each transaction, the earliest due first.
+    due: BTreeSet<(BlockHeight, CryptoHash)>,
+}
+
+impl PendingForwardedTxs {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, txs: HashMap::new(), due: BTreeSet::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Adds the transaction, due to be forwarded again at `due_height`, replacing the previous
+    /// entry of the same hash. When full, the transaction due the soonest is forgotten.
+    pub fn insert(&mut self, tx_hash: CryptoHash, due_height: BlockHeight, forwarded: ForwardedTx) {
+        self.remove(&tx_hash);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.txs.len() >= self.capacity {
+            if let Some(&(_, oldest)) = self.due.iter().next() {
+                self.remove(&oldest);
+            }
+        }
+        self.due.insert((due_height, tx_hash));
+        self.txs.insert(tx_hash, (due_height, forwarded));
+    }
+
+    pub fn remove(&mut self, tx_hash: &CryptoHash) -> Option<ForwardedTx> {
+        let (due_height, forwarded) = self.txs.remove(tx_hash)?;
+        self.due.remove(&(due_height, *tx_hash));
+        Some(forwarded)
+    }
+
+    /// Whether any transaction is due at `height` or before.
+    pub fn has_due(&self, height: BlockHeight) -> bool {
+        self.due.iter().next().map_or(false, |&(due_height, _)| due_height <= height)
+    }
+
+    /// Removes and returns at most `limit` transactions due at `height` or before, the earliest
+    /// due first. The others stay due and are returned by the following calls.
+    pub fn pop_due(&mut self, height: BlockHeight, limit: usize) -> Vec<(CryptoHash, ForwardedTx)> {
+        let hashes: Vec<CryptoHash> = self
+            .due
+            .iter()
+            .take_while(|&&(due_height, _)| due_height <= height)
+            .take(limit)
+            .map(|&(_, tx_hash)| tx_hash)
+            .collect();
+        hashes.into_iter().filter_map(|tx_hash| Some((tx_hash, self.remove(&tx_hash)?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{InMemorySigner, KeyType};
+
+    fn forwarded_tx(nonce: u64) -> (CryptoHash, ForwardedTx) {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        let tx = SignedTransaction::send_money(
+            nonce,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        (tx.get_hash(), ForwardedTx { tx, forwarded_at: Instant::now(), retries: 0 })
+    }
+
+    #[test]
+    fn pops_due_earliest_first_up_to_limit() {
+        let mut pending = PendingForwardedTxs::new(10);
+        let mut hashes = vec![];
+        for (nonce, due_height) in [(1, 12), (2, 10), (3, 11), (4, 20)] {
+            let (tx_hash, forwarded) = forwarded_tx(nonce);
+            pending.insert(tx_hash, due_height, forwarded);
+            hashes.push(tx_hash);
+        }
+        assert!(!pending.has_due(9));
+        assert!(pending.has_due(10));
+
+        let popped: Vec<_> = pending.pop_due(15, 2).into_iter().map(|(h, _)| h).collect();
+        assert_eq!(popped, vec![hashes[1], hashes[2]]);
+        let popped: Vec<_> = pending.pop_due(15, 2).into_iter().map(|(h, _)| h).collect();
+        assert_eq!(popped, vec![hashes[0]]);
+        assert!(!pending.has_due(15));
+        assert!(!pending.is_empty());
+    }
+
+    #[test]
+    fn forgets_earliest_due_when_full() {
+        let mut pending = PendingForwardedTxs::new(2);
+        let (first, forwarded) = forwarded_tx(1);
+        pending.insert(first, 10, forwarded);
+        let (second, forwarded) = forwarded_tx(2);
+        pending.insert(second, 5, forwarded);
+        let (third, forwarded) = forwarded_tx(3);
+        pending.insert(third, 20, forwarded);
+
+        assert!(pending.remove(&second).is_none());
+        assert!(pending.remove(&first).is_some());
+        assert!(pending.remove(&third).is_some());
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Notifications about the changes of the chain head, including reorgs.
+//!
+//! Indexers following the head otherwise have to infer reorgs from height regressions, which
+//! misses the reorgs to a chain of the same or greater height, and doesn't tell which blocks to
+//! roll back. The client reports every change of its head, with the common ancestor and the
+//! abandoned blocks in case of a reorg. The changes are delivered as a stream to the
+//! subscribers (`SubscribeHeadChanges`) and are also kept in a log of the recent changes, which
+//! can be polled by id (`GetHeadChanges`, exposed as the `EXPERIMENTAL_head_changes` RPC).
+use near_chain::{Chain, Error};
+use near_client_primitives::types::GetHeadChangesError;
+use near_primitives::block::BlockHeader;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::views::{HeadChangeView, ReorgView};
+use std::collections::VecDeque;
+use tokio::sync::broadcast;
+
+/// Number of the recent head changes kept in the log. The subscribers which fall further behind
+/// miss some changes, which they can detect by a gap in the ids.
+const MAX_RECENT_CHANGES: usize = 1000;
+
+/// Subscribes to the changes of the chain head.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "broadcast::Receiver<HeadChangeView>")]
+pub struct SubscribeHeadChanges;
+
+pub(crate) struct HeadWatcher {
+    next_id: u64,
+    recent: VecDeque<HeadChangeView>,
+    sender: broadcast::Sender<HeadChangeView>,
+}
+
+impl HeadWatcher {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(MAX_RECENT_CHANGES);
+        Self { next_id: 0, recent: VecDeque::new(), sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<HeadChangeView> {
+        self.sender.subscribe()
+    }
+
+    /// Records the change of the head from `prev_head` to `new_head`.
+    pub fn record(
+        &mut self,
+        chain: &Chain,
+        prev_head: &CryptoHash,
+        new_head: &BlockHeader,
+    ) -> Result<(), Error> {
+        let reorg = if new_head.prev_hash() == prev_head {
+            None
+        } else {
+            Some(find_reorg(chain, prev_head, new_head)?)
+        };
+        self.push(*new_head.hash(), new_head.height(), *new_head.prev_hash(), reorg);
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        block_hash: CryptoHash,
+        block_height: BlockHeight,
+        prev_block_hash: CryptoHash,
+        reorg: Option<ReorgView>,
+    ) {
+        let change =
+            HeadChangeView { id: self.next_id, block_hash, block_height, prev_block_hash, reorg };
+        self.next_id += 1;
+        if self.recent.len() == MAX_RECENT_CHANGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(change.clone());
+        // Sending fails only if there are no subscribers.
+        let _ = self.sender.send(change);
+    }
+
+    /// Returns the changes recorded after the change with id `since_id`, or all the recent
+    /// changes if `since_id` is None.
+    pub fn changes_since(
+        &self,
+        since_id: Option<u64>,
+    ) -> Result<Vec<HeadChangeView>, GetHeadChangesError> {
+        let since_id = match since_id {
+            Some(since_id) => since_id,
+            None => return Ok(self.recent.iter().cloned().collect()),
+        };
+        if since_id >= self.next_id {
+            return Err(GetHeadChangesError::UnknownId {
+                since_id,
+                latest_id: self.next_id.checked_sub(1),
+            });
+        }
+        let oldest_id = self.recent.front().map_or(self.next_id, |change| change.id);
+        if since_id + 1 < oldest_id {
+            return Err(GetHeadChangesError::Evicted { since_id, oldest_id });
+        }
+        Ok(self.recent.iter().filter(|change| change.id > since_id).cloned().collect())
+    }
+}
+
+/// Walks back from the previous and the new head to their common ancestor.
+fn find_reorg(
+    chain: &Chain,
+    prev_head: &CryptoHash,
+    new_head: &BlockHeader,
+) -> Result<ReorgView, Error> {
+    let mut abandoned = chain.get_block_header(prev_head)?;
+    let mut adopted = new_head.clone();
+    let mut abandoned_block_hashes = vec![];
+    while abandoned.hash() != adopted.hash() {
+        if abandoned.height() >= adopted.height() {
+            abandoned_block_hashes.push(*abandoned.hash());
+            abandoned = chain.get_block_header(abandoned.prev_hash())?;
+        } else {
+            adopted = chain.get_block_header(adopted.prev_hash())?;
+        }
+    }
+    Ok(ReorgView {
+        common_ancestor_hash: *abandoned.hash(),
+        common_ancestor_height: abandoned.height(),
+        abandoned_block_hashes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_blocks(watcher: &mut HeadWatcher, heights: std::ops::Range<BlockHeight>) {
+        for height in heights {
+            let hash = CryptoHash::hash_borsh(height);
+            watcher.push(hash, height, CryptoHash::hash_borsh(height - 1), None);
+        }
+    }
+
+    #[test]
+    fn changes_since() {
+        let mut watcher = HeadWatcher::new();
+        let mut subscriber = watcher.subscribe();
+        assert!(watcher.changes_since(None).unwrap().is_empty());
+        assert!(matches!(
+            watcher.changes_since(Some(0)),
+            Err(GetHeadChangesError::UnknownId { since_id: 0, latest_id: None })
+        ));
+
+        push_blocks(&mut watcher, 1..4);
+        let ids = |changes: Vec<HeadChangeView>| changes.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids(watcher.changes_since(None).unwrap()), vec![0, 1, 2]);
+        assert_eq!(ids(watcher.changes_since(Some(0)).unwrap()), vec![1, 2]);
+        assert!(watcher.changes_since(Some(2)).unwrap().is_empty());
+        assert_eq!(subscriber.try_recv().unwrap().block_height, 1);
+
+        push_blocks(&mut watcher, 4..(MAX_RECENT_CHANGES as BlockHeight + 4));
+        assert!(matches!(
+            watcher.changes_since(Some(1)),
+            Err(GetHeadChangesError::Evicted { since_id: 1, oldest_id: 3 })
+        ));
+        assert_eq!(watcher.changes_since(Some(2)).unwrap().len(), MAX_RECENT_CHANGES);
+    }
+}
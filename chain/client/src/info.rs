@@ -2,6 +2,7 @@ use crate::config_updater::ConfigUpdater;
 use crate::{metrics, rocksdb_metrics, SyncStatus};
 use actix::Addr;
 use itertools::Itertools;
+use near_chain::Chain;
 use near_chain_configs::{ClientConfig, LogSummaryStyle};
 use near_network::types::NetworkInfo;
 use near_primitives::block::Tip;
@@ -19,7 +20,7 @@ use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::Version;
 use near_primitives::views::{
     CatchupStatusView, ChunkProcessingStatus, CurrentEpochValidatorInfo, EpochValidatorInfo,
-    ValidatorKickoutView,
+    FinalityStatusView, ValidatorKickoutView,
 };
 use near_store::db::StoreStatistics;
 use near_telemetry::{telemetry, TelemetryActor};
@@ -62,6 +63,8 @@ pub struct InfoHelper {
     log_summary_style: LogSummaryStyle,
     /// Timestamp of starting the client.
     pub boot_time_seconds: i64,
+    /// Height of the last final block observed and the time at which it has been observed.
+    last_final_block: Option<(BlockHeight, Instant)>,
 }
 
 impl InfoHelper {
@@ -84,9 +87,38 @@ impl InfoHelper {
             validator_signer,
             log_summary_style: client_config.log_summary_style,
             boot_time_seconds: StaticClock::utc().timestamp(),
+            last_final_block: None,
         }
     }
 
+    /// Computes how far behind the head the finality is, and updates the related metrics.
+    pub fn finality_status(&mut self, chain: &Chain) -> Option<FinalityStatusView> {
+        let head = chain.head().ok()?;
+        let final_head = chain.final_head().ok()?;
+        let now = StaticClock::instant();
+        let advanced_at = match self.last_final_block {
+            Some((height, at)) if height >= final_head.height => at,
+            _ => {
+                self.last_final_block = Some((final_head.height, now));
+                now
+            }
+        };
+        let num_forks_at_head = (chain.store())
+            .get_all_block_hashes_by_height(head.height)
+            .map_or(0, |blocks| blocks.values().map(|hashes| hashes.len()).sum());
+        let status = FinalityStatusView {
+            final_block_hash: final_head.last_block_hash,
+            final_block_height: final_head.height,
+            final_head_lag: head.height.saturating_sub(final_head.height),
+            num_forks_at_head,
+            seconds_since_finality_advanced: (now - advanced_at).as_secs(),
+        };
+        metrics::FINAL_HEAD_LAG.set(status.final_head_lag as i64);
+        metrics::NUM_FORKS_AT_HEAD.set(status.num_forks_at_head as i64);
+        metrics::SECONDS_SINCE_FINALITY_ADVANCED.set((now - advanced_at).as_secs_f64());
+        Some(status)
+    }
+
     pub fn chunk_processed(&mut self, shard_id: ShardId, gas_used: Gas, balance_burnt: Balance) {
         metrics::TGAS_USAGE_HIST
             .with_label_values(&[&shard_id.to_string()])
@@ -191,6 +223,7 @@ impl InfoHelper {
         network_info: &NetworkInfo,
         config_updater: &Option<ConfigUpdater>,
     ) {
+        self.finality_status(&client.chain);
         let is_syncing = client.sync_status.is_syncing();
         let head = unwrap_or_return!(client.chain.head());
         let validator_info = if !is_syncing {
@@ -803,4 +836,29 @@ mod tests {
             Some(_)
         );
     }
+
+    #[test]
+    fn finality_status() {
+        let config = ClientConfig::test(false, 1230, 2340, 50, false, true, true);
+        let mut info_helper = InfoHelper::new(None, &config, None);
+
+        let store = near_store::test_utils::create_test_store();
+        let vs =
+            ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test".parse().unwrap()]]);
+        let runtime = KeyValueRuntime::new_with_validators_and_no_gc(store, vs, 123, false);
+        let chain_genesis = ChainGenesis::test();
+        let chain = Chain::new(
+            runtime,
+            &chain_genesis,
+            DoomslugThresholdMode::TwoThirds,
+            ChainConfig::test(),
+        )
+        .unwrap();
+
+        let status = info_helper.finality_status(&chain).unwrap();
+        assert_eq!(chain.genesis().hash(), &status.final_block_hash);
+        assert_eq!(0, status.final_head_lag);
+        assert_eq!(1, status.num_forks_at_head);
+        assert_eq!(0, status.seconds_since_finality_advanced);
+    }
 }
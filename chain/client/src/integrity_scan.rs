@@ -0,0 +1,102 @@
+//! Background loop periodically running `near_chain::integrity_scan` over the recent blocks.
+use near_chain::integrity_scan::{scan_recent_blocks, DataCorruption};
+use near_chain::types::RuntimeWithEpochManagerAdapter;
+use near_chain::ChainStore;
+use near_chain_configs::IntegrityScanConfig;
+use near_primitives::types::BlockHeight;
+use near_store::Store;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the loop checks whether it should stop while waiting for the next scan.
+const STOP_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// A handle to the integrity scan loop. The loop is stopped when the handle is dropped.
+pub(crate) struct IntegrityScanLoopHandle {
+    join_handle: Option<std::thread::JoinHandle<()>>,
+    keep_going: Arc<AtomicBool>,
+    /// First corruption found by the scans. Corrupted data doesn't recover by itself, so it is
+    /// reported until the node is restarted, even if it's no longer within the scanned range.
+    corruption: Arc<Mutex<Option<DataCorruption>>>,
+}
+
+impl IntegrityScanLoopHandle {
+    /// Spawns the loop on a dedicated thread, so that it doesn't compete with the actors.
+    pub fn spawn(
+        config: IntegrityScanConfig,
+        store: Store,
+        genesis_height: BlockHeight,
+        runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    ) -> std::io::Result<Self> {
+        let keep_going = Arc::new(AtomicBool::new(true));
+        let corruption = Arc::new(Mutex::new(None));
+        let join_handle =
+            std::thread::Builder::new().name("integrity_scan".to_string()).spawn({
+                let keep_going = keep_going.clone();
+                let corruption = corruption.clone();
+                move || {
+                    integrity_scan_loop(
+                        config,
+                        store,
+                        genesis_height,
+                        runtime_adapter,
+                        keep_going,
+                        corruption,
+                    )
+                }
+            })?;
+        Ok(Self { join_handle: Some(join_handle), keep_going, corruption })
+    }
+
+    pub fn corruption(&self) -> Option<DataCorruption> {
+        self.corruption.lock().unwrap().clone()
+    }
+}
+
+impl Drop for IntegrityScanLoopHandle {
+    fn drop(&mut self) {
+        self.keep_going.store(false, Ordering::Relaxed);
+        if let Some(join_handle) = self.join_handle.take() {
+            if join_handle.join().is_err() {
+                tracing::error!(target: "client", "Failed to join the integrity scan thread");
+            }
+        }
+    }
+}
+
+fn integrity_scan_loop(
+    config: IntegrityScanConfig,
+    store: Store,
+    genesis_height: BlockHeight,
+    runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    keep_going: Arc<AtomicBool>,
+    corruption: Arc<Mutex<Option<DataCorruption>>>,
+) {
+    let mut next_scan = Instant::now() + config.period;
+    while keep_going.load(Ordering::Relaxed) {
+        if Instant::now() < next_scan {
+            std::thread::sleep(STOP_CHECK_PERIOD);
+            continue;
+        }
+        next_scan = Instant::now() + config.period;
+        // A new ChainStore for every scan, so that the data is read from disk rather than
+        // from the caches.
+        let chain_store = ChainStore::new(store.clone(), genesis_height, true);
+        match scan_recent_blocks(&chain_store, runtime_adapter.as_ref(), config.num_blocks) {
+            Ok(result) => {
+                tracing::debug!(target: "client", num_blocks = result.num_blocks, num_corruptions = result.corruptions.len(), "Integrity scan finished");
+                for c in &result.corruptions {
+                    tracing::error!(target: "client", "Integrity scan: {c}");
+                }
+                let mut corruption = corruption.lock().unwrap();
+                if corruption.is_none() {
+                    *corruption = result.corruptions.into_iter().next();
+                }
+            }
+            Err(err) => {
+                tracing::warn!(target: "client", ?err, "Integrity scan failed");
+            }
+        }
+    }
+}
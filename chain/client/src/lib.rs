@@ -1,11 +1,12 @@
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetClientConfig, GetExecutionOutcome, GetExecutionOutcomeResponse,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
-    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetSplitStorageInfo, GetStateChanges,
-    GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    CancellationToken, Error, GetAccountActivity, GetBlock, GetBlockProof, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunk, GetClientConfig, GetExecutionOutcome,
+    GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetFeeHint, GetGasPrice,
+    GetHeadChanges, GetMaintenanceWindows, GetNetworkInfo, GetNextLightClientBlock,
+    GetProtocolConfig, GetReceipt, GetSplitStorageInfo, GetStateChanges, GetStateChangesInBlock,
+    GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockForTrackedShards,
+    GetValidatorInfo, GetValidatorOrdered, Query, QueryError, Status, StatusResponse, SyncStatus,
+    TxStatus, TxStatusError,
 };
 
 pub use near_client_primitives::debug::DebugStatus;
@@ -13,22 +14,33 @@ pub use near_client_primitives::debug::DebugStatus;
 pub use crate::adapter::{
     BlockApproval, BlockResponse, ProcessTxRequest, ProcessTxResponse, SetNetworkInfo,
 };
+pub use crate::approval_queue::ApprovalQueue;
 pub use crate::client::Client;
 #[cfg(feature = "test_features")]
 pub use crate::client_actor::NetworkAdversarialMessage;
 pub use crate::client_actor::{start_client, ClientActor};
 pub use crate::config_updater::ConfigUpdater;
+pub use crate::head_watcher::SubscribeHeadChanges;
 pub use crate::view_client::{start_view_client, ViewClientActor};
 
 pub mod adapter;
 pub mod adversarial;
+mod approval_queue;
 mod client;
 mod client_actor;
 mod config_updater;
 pub mod debug;
+mod dedup_cache;
+mod double_sign_detector;
+mod forwarded_txs;
+mod head_watcher;
 mod info;
+mod integrity_scan;
 mod metrics;
+mod metrics_snapshot;
+mod query_cache;
 mod rocksdb_metrics;
+mod signing_journal;
 pub mod sync;
 pub mod test_utils;
 #[cfg(test)]
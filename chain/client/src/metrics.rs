@@ -22,6 +22,64 @@ pub(crate) static CHUNK_PRODUCED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static SIGNING_JOURNAL_CONFLICTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_signing_journal_conflicts_total",
+        "Number of blocks, chunks and approvals which were not broadcast, because they conflict \
+         with the ones signed earlier according to the signing journal",
+        &["kind"],
+    )
+    .unwrap()
+});
+
+pub(crate) static DOUBLE_SIGN_DETECTED: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_double_sign_detected",
+        "Set to 1 when another node signing with the validator key of this node has been \
+         detected, in which case this node stops signing",
+    )
+    .unwrap()
+});
+
+pub(crate) static BLOCK_PRODUCTION_WITHHELD: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_block_production_withheld_total",
+        "Number of times this node didn't produce a block it was due to, because it didn't have \
+         enough healthy peers or approvals from enough other validators",
+        &["reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static DEDUP_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_dedup_cache_hits_total",
+        "Number of challenges and forwarded transactions skipped, because they have been \
+         processed recently",
+        &["cache"],
+    )
+    .unwrap()
+});
+
+pub(crate) static DEDUP_CACHE_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_dedup_cache_size",
+        "Number of hashes remembered by the cache of processed challenges or forwarded transactions",
+        &["cache"],
+    )
+    .unwrap()
+});
+
+pub(crate) static PERSISTED_COUNTER: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_persisted_counter",
+        "Values of the counters persisted across restarts: accumulated before the last restart \
+         (restarted=\"true\") and since then (restarted=\"false\")",
+        &["counter", "shard_id", "restarted"],
+    )
+    .unwrap()
+});
+
 pub(crate) static IS_VALIDATOR: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge(
         "near_is_validator",
@@ -165,6 +223,37 @@ pub(crate) static CHUNK_SKIPPED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Chunks which were (not) ready for inclusion when this node produced the block at their height.
+/// Together with CHUNK_ARRIVED_AFTER_CUTOFF_TOTAL it allows to tell apart the chunks which
+/// were missed because their producer was late from the ones which never arrived.
+pub(crate) static CHUNK_INCLUSION_CUTOFF_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_inclusion_cutoff_total",
+        "Number of chunks which were or were not ready for inclusion at the time this node produced a block",
+        &["shard_id", "chunk_producer", "included"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_ARRIVED_AFTER_CUTOFF_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_arrived_after_cutoff_total",
+        "Number of chunks which became ready for inclusion only after this node produced the block at their height",
+        &["shard_id", "chunk_producer"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CHUNK_DELAY_AFTER_CUTOFF: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_chunk_delay_after_cutoff_seconds",
+        "Time between producing a block and receiving a chunk at its height which the block missed",
+        &["shard_id"],
+        Some(exponential_buckets(0.001, 2.0, 16).unwrap()),
+    )
+    .unwrap()
+});
+
 pub(crate) static CHUNK_PRODUCER_BANNED_FOR_EPOCH: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter(
         "near_chunk_producer_banned_for_epoch",
@@ -258,6 +347,30 @@ pub(crate) static FINAL_DOOMSLUG_BLOCK_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static FINAL_HEAD_LAG: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_final_head_lag",
+        "Difference between the heights of the head and the last final block",
+    )
+    .unwrap()
+});
+
+pub(crate) static NUM_FORKS_AT_HEAD: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_num_forks_at_head",
+        "Number of known blocks at the height of the head. More than 1 means that the chain forked",
+    )
+    .unwrap()
+});
+
+pub(crate) static SECONDS_SINCE_FINALITY_ADVANCED: Lazy<Gauge> = Lazy::new(|| {
+    try_create_gauge(
+        "near_seconds_since_finality_advanced",
+        "Time since the node has last observed a new final block",
+    )
+    .unwrap()
+});
+
 static NODE_DB_VERSION: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_node_db_version", "DB version used by the node").unwrap()
 });
@@ -293,6 +406,24 @@ pub(crate) static TRANSACTION_RECEIVED_NON_VALIDATOR_FORWARDED: Lazy<IntGauge> =
     .unwrap()
 });
 
+pub(crate) static TRANSACTION_DROPPED_POOLED_PER_KEY: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_transaction_dropped_pooled_per_key",
+        "Transactions dropped because their access key had too many transactions in the pool",
+    )
+    .unwrap()
+});
+
+pub(crate) static TRANSACTION_FORWARD_OUTCOME: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_transaction_forward_outcome_total",
+        "Outcomes of the forwarding of the transactions: included if the inclusion was observed, \
+         retried when forwarded again, abandoned once out of retries",
+        &["outcome"],
+    )
+    .unwrap()
+});
+
 pub(crate) static NODE_PROTOCOL_VERSION: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_node_protocol_version", "Max protocol version supported by the node")
         .unwrap()
@@ -325,6 +456,50 @@ pub static VIEW_CLIENT_MESSAGE_TIME: Lazy<near_o11y::metrics::HistogramVec> = La
     .unwrap()
 });
 
+pub(crate) static VIEW_CLIENT_QUERIES_CANCELLED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_view_client_queries_cancelled_total",
+        "Number of queries abandoned by the view client, because the requester gave up",
+    )
+    .unwrap()
+});
+
+pub(crate) static VIEW_CLIENT_API_KEY_QUERIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_client_api_key_queries_total",
+        "Number of queries by API key, and whether they have been rejected because of \
+         exceeding the budget of the key",
+        &["api_key", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static VIEW_CLIENT_API_KEY_VIEW_STATE_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_client_api_key_view_state_bytes_total",
+        "Number of bytes returned by view_state queries by API key",
+        &["api_key"],
+    )
+    .unwrap()
+});
+
+pub(crate) static VIEW_QUERY_CACHE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_query_cache_requests_total",
+        "Number of the cacheable view queries, by query type and whether they hit the cache",
+        &["query", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static VIEW_QUERY_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_view_query_cache_size_bytes",
+        "Approximate memory taken by the responses in the view query cache",
+    )
+    .unwrap()
+});
+
 pub static PRODUCE_AND_DISTRIBUTE_CHUNK_TIME: Lazy<near_o11y::metrics::HistogramVec> =
     Lazy::new(|| {
         try_create_histogram_vec(
@@ -354,3 +529,59 @@ pub(crate) fn export_version(neard_version: &near_primitives::version::Version)
         ])
         .inc();
 }
+
+pub(crate) static QUEUED_APPROVALS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_client_queued_approvals",
+        "Number of approvals received from the network which wait in the priority lane",
+    )
+    .unwrap()
+});
+
+pub(crate) static APPROVALS_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_client_approvals_dropped_total",
+        "Number of approvals received from the network which were dropped, because the priority \
+         lane was full",
+    )
+    .unwrap()
+});
+
+pub(crate) static CLIENT_QUEUE_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_client_queue_delay_seconds",
+        "Time the messages waited before being handled by ClientActor: 'approval' for the \
+         approvals in the priority lane and 'mailbox' for the messages in the actor mailbox",
+        &["class"],
+        Some(exponential_buckets(0.0001, 2.0, 18).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static BLOCKS_WAITING_FOR_PROCESSING: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_blocks_waiting_for_processing",
+        "Number of received blocks waiting for a free slot in the block processing pool",
+    )
+    .unwrap()
+});
+
+pub(crate) static BLOCKS_WAITING_FOR_PROCESSING_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_blocks_waiting_for_processing_dropped_total",
+        "Number of received blocks which were dropped, because the queue of the blocks waiting \
+         for the block processing pool was full",
+    )
+    .unwrap()
+});
+
+pub(crate) static TRANSACTION_FORWARD_TO_INCLUSION_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_transaction_forward_to_inclusion_delay_seconds",
+        "Time between forwarding a transaction to the chunk producers and the inclusion of the \
+         transaction in a chunk, observed for the shards tracked by this node",
+        &["shard_id"],
+        Some(exponential_buckets(0.1, 1.5, 16).unwrap()),
+    )
+    .unwrap()
+});
@@ -0,0 +1,144 @@
+//! Counters which survive restarts of the node.
+//!
+//! Prometheus counters start from zero whenever the node is restarted, which breaks the
+//! dashboards aggregating them over an epoch, e.g. the number of produced or skipped chunks.
+//! When enabled, `MetricsSnapshot` periodically persists the selected counters in
+//! DBCol::MetricsSnapshot and loads them back on startup. Both parts are exported as the
+//! `near_persisted_counter` gauge: the values accumulated before the last restart with
+//! `restarted="true"` and the values counted since the start with `restarted="false"`.
+//! Their sum is the total over the lifetime of the database.
+use crate::metrics;
+use near_o11y::metrics::prometheus::core::Collector;
+use near_store::{DBCol, Store};
+use std::collections::HashMap;
+
+/// A single series of a persisted counter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Series {
+    counter: String,
+    /// Empty for the counters without a shard label.
+    shard_id: String,
+}
+
+impl Series {
+    fn key(&self) -> Vec<u8> {
+        format!("{}:{}", self.counter, self.shard_id).into_bytes()
+    }
+
+    fn from_key(key: &[u8]) -> Option<Self> {
+        let (counter, shard_id) = std::str::from_utf8(key).ok()?.split_once(':')?;
+        Some(Self { counter: counter.to_string(), shard_id: shard_id.to_string() })
+    }
+
+    fn export(&self, restarted: &str, value: u64) {
+        metrics::PERSISTED_COUNTER
+            .with_label_values(&[&self.counter, &self.shard_id, restarted])
+            .set(value as i64);
+    }
+}
+
+/// Current values of the persisted counters, as counted since the start of the node.
+fn collect() -> Vec<(Series, u64)> {
+    let collectors: [&dyn Collector; 3] = [
+        &*metrics::BLOCK_PRODUCED_TOTAL,
+        &*metrics::CHUNK_PRODUCED_TOTAL,
+        &*metrics::CHUNK_SKIPPED_TOTAL,
+    ];
+    let mut values = vec![];
+    for family in collectors.iter().flat_map(|collector| collector.collect()) {
+        for metric in family.get_metric() {
+            let shard_id = metric
+                .get_label()
+                .iter()
+                .find(|label| label.get_name() == "shard_id")
+                .map_or("", |label| label.get_value());
+            let series =
+                Series { counter: family.get_name().to_string(), shard_id: shard_id.to_string() };
+            values.push((series, metric.get_counter().get_value() as u64));
+        }
+    }
+    values
+}
+
+pub(crate) struct MetricsSnapshot {
+    store: Store,
+    /// Values persisted before the restart.
+    restored: HashMap<Series, u64>,
+}
+
+impl MetricsSnapshot {
+    /// Loads the values persisted before the restart.
+    pub fn new(store: Store) -> std::io::Result<Self> {
+        let mut restored = HashMap::new();
+        for item in store.iter_prefix_ser::<u64>(DBCol::MetricsSnapshot, &[]) {
+            let (key, value) = item?;
+            match Series::from_key(&key) {
+                Some(series) => {
+                    series.export("true", value);
+                    restored.insert(series, value);
+                }
+                None => tracing::warn!(target: "client", ?key, "Invalid metrics snapshot key"),
+            }
+        }
+        Ok(Self { store, restored })
+    }
+
+    /// Persists the totals: the values restored on startup plus the values counted since then.
+    pub fn snapshot(&self) -> std::io::Result<()> {
+        let mut update = self.store.store_update();
+        for (series, value) in collect() {
+            series.export("false", value);
+            let total = self.restored.get(&series).copied().unwrap_or(0) + value;
+            update.set_ser(DBCol::MetricsSnapshot, &series.key(), &total)?;
+        }
+        update.commit()
+    }
+}
+
+impl Drop for MetricsSnapshot {
+    fn drop(&mut self) {
+        if let Err(err) = self.snapshot() {
+            tracing::error!(target: "client", ?err, "Failed to persist the metrics snapshot");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_persisted_values() {
+        let store = near_store::test_utils::create_test_store();
+        let series =
+            Series { counter: "near_block_produced_total".to_string(), shard_id: "".to_string() };
+        let mut update = store.store_update();
+        update.set_ser(DBCol::MetricsSnapshot, &series.key(), &1000u64).unwrap();
+        update.commit().unwrap();
+
+        let snapshot = MetricsSnapshot::new(store.clone()).unwrap();
+        assert_eq!(Some(&1000), snapshot.restored.get(&series));
+        let restored = metrics::PERSISTED_COUNTER.with_label_values(&[
+            &series.counter,
+            &series.shard_id,
+            "true",
+        ]);
+        assert_eq!(1000, restored.get());
+
+        // Other tests may produce blocks concurrently, so only bound the persisted total.
+        let before = metrics::BLOCK_PRODUCED_TOTAL.get();
+        metrics::BLOCK_PRODUCED_TOTAL.inc();
+        drop(snapshot);
+        let after = metrics::BLOCK_PRODUCED_TOTAL.get();
+        let total: u64 = store.get_ser(DBCol::MetricsSnapshot, &series.key()).unwrap().unwrap();
+        assert!(1000 + before < total && total <= 1000 + after, "total = {total}");
+    }
+
+    #[test]
+    fn key_roundtrip() {
+        let series =
+            Series { counter: "near_chunk_skipped_total".to_string(), shard_id: "3".to_string() };
+        assert_eq!(Some(series.clone()), Series::from_key(&series.key()));
+        assert_eq!(None, Series::from_key(b"no_separator"));
+    }
+}
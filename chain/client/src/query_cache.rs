@@ -0,0 +1,203 @@
+//! Cache of the responses to the cheap and frequently repeated view queries.
+//!
+//! Public RPC nodes answer the same `view_account` and `view_access_key` queries many times per
+//! block. The state at a given block never changes, so the responses are cached per block hash
+//! and served without reading the trie again. The cache is bounded by the approximate size of
+//! the cached responses and by a TTL. Besides, the responses at the blocks below the last final
+//! block are dropped, since the queries refer mostly to the latest blocks.
+use crate::metrics;
+use near_chain_configs::ViewQueryCacheConfig;
+use near_crypto::PublicKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::views::{
+    AccessKeyPermissionView, QueryRequest, QueryResponse, QueryResponseKind,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Query which can be answered from the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CachedQuery {
+    ViewAccount(AccountId),
+    ViewAccessKey(AccountId, PublicKey),
+}
+
+impl CachedQuery {
+    /// Returns None for the queries which are not cached.
+    pub fn from_request(request: &QueryRequest) -> Option<Self> {
+        match request {
+            QueryRequest::ViewAccount { account_id } => Some(Self::ViewAccount(account_id.clone())),
+            QueryRequest::ViewAccessKey { account_id, public_key } => {
+                Some(Self::ViewAccessKey(account_id.clone(), public_key.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::ViewAccount(_) => "view_account",
+            Self::ViewAccessKey(..) => "view_access_key",
+        }
+    }
+}
+
+/// Approximate memory taken by a cache entry.
+fn entry_size(query: &CachedQuery, response: &QueryResponse) -> usize {
+    let query_size = match query {
+        CachedQuery::ViewAccount(account_id) => account_id.len(),
+        CachedQuery::ViewAccessKey(account_id, public_key) => account_id.len() + public_key.len(),
+    };
+    let response_size = match &response.kind {
+        QueryResponseKind::AccessKey(view) => match &view.permission {
+            AccessKeyPermissionView::FunctionCall { receiver_id, method_names, .. } => {
+                receiver_id.len() + method_names.iter().map(|name| name.len()).sum::<usize>()
+            }
+            AccessKeyPermissionView::FullAccess => 0,
+        },
+        _ => 0,
+    };
+    std::mem::size_of::<CachedQuery>()
+        + std::mem::size_of::<QueryResponse>()
+        + query_size
+        + response_size
+}
+
+struct BlockEntry {
+    height: BlockHeight,
+    /// When the first response at this block has been cached.
+    inserted: Instant,
+    responses: HashMap<CachedQuery, QueryResponse>,
+    size: usize,
+}
+
+pub struct QueryCache {
+    config: ViewQueryCacheConfig,
+    blocks: HashMap<CryptoHash, BlockEntry>,
+    /// Total size of the cached responses.
+    size: usize,
+}
+
+impl QueryCache {
+    pub fn new(config: ViewQueryCacheConfig) -> Self {
+        Self { config, blocks: HashMap::new(), size: 0 }
+    }
+
+    pub fn get(
+        &self,
+        now: Instant,
+        block_hash: &CryptoHash,
+        query: &CachedQuery,
+    ) -> Option<QueryResponse> {
+        let response = self
+            .blocks
+            .get(block_hash)
+            .filter(|block| now < block.inserted + self.config.ttl)
+            .and_then(|block| block.responses.get(query))
+            .cloned();
+        let result = if response.is_some() { "hit" } else { "miss" };
+        metrics::VIEW_QUERY_CACHE_REQUESTS.with_label_values(&[query.label(), result]).inc();
+        response
+    }
+
+    /// Caches the response to the query at the given block. At the same time drops the expired
+    /// responses, the responses at the blocks below `final_height`, and then the responses at
+    /// the lowest blocks until the cache fits into its byte budget.
+    pub fn insert(
+        &mut self,
+        now: Instant,
+        final_height: BlockHeight,
+        query: CachedQuery,
+        response: QueryResponse,
+    ) {
+        let ttl = self.config.ttl;
+        self.blocks.retain(|_, block| block.height >= final_height && now < block.inserted + ttl);
+        if response.block_height >= final_height {
+            let size = entry_size(&query, &response);
+            let block = self.blocks.entry(response.block_hash).or_insert_with(|| BlockEntry {
+                height: response.block_height,
+                inserted: now,
+                responses: HashMap::new(),
+                size: 0,
+            });
+            if let Some(old) = block.responses.insert(query.clone(), response) {
+                block.size -= entry_size(&query, &old);
+            }
+            block.size += size;
+        }
+        self.size = self.blocks.values().map(|block| block.size).sum();
+        while self.size > self.config.max_bytes {
+            let lowest = match self.blocks.iter().min_by_key(|(_, block)| block.height) {
+                Some((hash, _)) => *hash,
+                None => break,
+            };
+            self.size -= self.blocks.remove(&lowest).unwrap().size;
+        }
+        metrics::VIEW_QUERY_CACHE_SIZE.set(self.size as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::views::AccountView;
+    use std::time::Duration;
+
+    fn account_response(height: BlockHeight) -> QueryResponse {
+        QueryResponse {
+            kind: QueryResponseKind::ViewAccount(AccountView {
+                amount: height as u128,
+                locked: 0,
+                code_hash: CryptoHash::default(),
+                storage_usage: 0,
+                storage_paid_at: 0,
+            }),
+            block_height: height,
+            block_hash: CryptoHash::hash_borsh(height),
+        }
+    }
+
+    fn query(account_id: &str) -> CachedQuery {
+        CachedQuery::ViewAccount(account_id.parse().unwrap())
+    }
+
+    #[test]
+    fn caches_responses_per_block() {
+        let ttl = Duration::from_secs(10);
+        let mut cache = QueryCache::new(ViewQueryCacheConfig { ttl, max_bytes: 1_000_000 });
+        let now = Instant::now();
+        let response = account_response(10);
+        assert_eq!(None, cache.get(now, &response.block_hash, &query("alice")));
+        cache.insert(now, 10, query("alice"), response.clone());
+        assert_eq!(Some(&response), cache.get(now, &response.block_hash, &query("alice")).as_ref());
+        assert_eq!(None, cache.get(now, &response.block_hash, &query("bob")));
+        assert_eq!(None, cache.get(now, &account_response(11).block_hash, &query("alice")));
+
+        // Expires after ttl.
+        assert_eq!(None, cache.get(now + ttl, &response.block_hash, &query("alice")));
+
+        // Responses below the final block are dropped.
+        cache.insert(now, 11, query("alice"), account_response(11));
+        assert_eq!(None, cache.get(now, &response.block_hash, &query("alice")));
+        assert!(cache.get(now, &account_response(11).block_hash, &query("alice")).is_some());
+    }
+
+    #[test]
+    fn evicts_lowest_blocks_over_budget() {
+        let entry = entry_size(&query("alice"), &account_response(10));
+        let mut cache = QueryCache::new(ViewQueryCacheConfig {
+            ttl: Duration::from_secs(10),
+            max_bytes: 2 * entry,
+        });
+        let now = Instant::now();
+        for height in [12, 10, 11] {
+            cache.insert(now, 0, query("alice"), account_response(height));
+        }
+        assert_eq!(None, cache.get(now, &account_response(10).block_hash, &query("alice")));
+        for height in [11, 12] {
+            let hash = account_response(height).block_hash;
+            assert!(cache.get(now, &hash, &query("alice")).is_some());
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Journal of the blocks, chunks and approvals signed by this node.
+//!
+//! A validator which crashes right after broadcasting a signed block, but before persisting it,
+//! may sign a different block at the same height after a restart, which is a double sign.
+//! To prevent that, the client records the hash of everything it signs in DBCol::SigningJournal
+//! before broadcasting it, and refuses to broadcast a signature conflicting with one which has
+//! already been journaled (possibly before the restart).
+use crate::metrics;
+use near_primitives::block::{Approval, Block};
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ShardChunkHeader;
+use near_primitives::types::{BlockHeight, ShardId};
+use near_store::{DBCol, Store};
+use std::collections::HashMap;
+
+/// Number of heights for which the journal entries are kept.
+const JOURNAL_RETENTION_HEIGHTS: BlockHeight = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::AsRefStr, strum::Display)]
+pub(crate) enum Signed {
+    Block,
+    Chunk,
+    Approval,
+}
+
+impl Signed {
+    const ALL: [Signed; 3] = [Signed::Block, Signed::Chunk, Signed::Approval];
+
+    fn tag(&self) -> u8 {
+        match self {
+            Signed::Block => b'B',
+            Signed::Chunk => b'C',
+            Signed::Approval => b'A',
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Error {
+    #[error("already signed {kind} {signed} at height {height} shard {shard_id}, refusing to sign {new}")]
+    Conflict {
+        kind: Signed,
+        height: BlockHeight,
+        shard_id: ShardId,
+        signed: CryptoHash,
+        new: CryptoHash,
+    },
+    #[error("store: {0}")]
+    Store(#[from] std::io::Error),
+}
+
+pub(crate) struct SigningJournal {
+    store: Store,
+}
+
+impl SigningJournal {
+    /// Opens the journal and reports the heights signed before the restart.
+    pub fn new(store: Store) -> Result<Self, Error> {
+        let mut last_signed = HashMap::new();
+        for item in store.iter(DBCol::SigningJournal) {
+            let (key, _) = item?;
+            if let Some((kind, height, _)) = decode_key(&key) {
+                let last = last_signed.entry(kind).or_insert(height);
+                *last = std::cmp::max(*last, height);
+            }
+        }
+        for (kind, height) in last_signed {
+            tracing::info!(target: "client", kind = %kind, height, "Found signing journal entries");
+        }
+        Ok(Self { store })
+    }
+
+    pub fn record_block(&self, block: &Block) -> Result<(), Error> {
+        self.record(Signed::Block, block.header().height(), 0, &[], *block.hash())
+    }
+
+    /// If the chain forks, the chunk producer produces a chunk on top of each of the blocks at
+    /// the fork height, so the chunks are identified by their previous block as well.
+    pub fn record_chunk(&self, header: &ShardChunkHeader) -> Result<(), Error> {
+        self.record(
+            Signed::Chunk,
+            header.height_created(),
+            header.shard_id(),
+            header.prev_block_hash().as_ref(),
+            header.chunk_hash().0,
+        )
+    }
+
+    /// Approvals are identified by the target height. Signing the same approval twice is fine,
+    /// but an endorsement and a skip (or two different endorsements) are not.
+    pub fn record_approval(&self, approval: &Approval) -> Result<(), Error> {
+        self.record(
+            Signed::Approval,
+            approval.target_height,
+            0,
+            &[],
+            CryptoHash::hash_borsh(&approval.inner),
+        )
+    }
+
+    fn record(
+        &self,
+        kind: Signed,
+        height: BlockHeight,
+        shard_id: ShardId,
+        context: &[u8],
+        hash: CryptoHash,
+    ) -> Result<(), Error> {
+        let key = encode_key(kind, height, shard_id, context);
+        match self.store.get_ser::<CryptoHash>(DBCol::SigningJournal, &key)? {
+            Some(signed) if signed == hash => return Ok(()),
+            Some(signed) => {
+                metrics::SIGNING_JOURNAL_CONFLICTS_TOTAL.with_label_values(&[kind.as_ref()]).inc();
+                return Err(Error::Conflict { kind, height, shard_id, signed, new: hash });
+            }
+            None => {}
+        }
+        let mut update = self.store.store_update();
+        update.set_ser(DBCol::SigningJournal, &key, &hash)?;
+        if height > JOURNAL_RETENTION_HEIGHTS {
+            update.delete_range(
+                DBCol::SigningJournal,
+                &encode_key(kind, 0, 0, &[]),
+                &encode_key(kind, height - JOURNAL_RETENTION_HEIGHTS, 0, &[]),
+            );
+        }
+        update.commit()?;
+        Ok(())
+    }
+}
+
+/// The keys are ordered by kind, then height, so that old entries can be removed with a single
+/// range deletion.
+fn encode_key(kind: Signed, height: BlockHeight, shard_id: ShardId, context: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(17 + context.len());
+    key.push(kind.tag());
+    key.extend_from_slice(&height.to_be_bytes());
+    key.extend_from_slice(&shard_id.to_be_bytes());
+    key.extend_from_slice(context);
+    key
+}
+
+fn decode_key(key: &[u8]) -> Option<(Signed, BlockHeight, ShardId)> {
+    if key.len() < 17 {
+        return None;
+    }
+    let kind = Signed::ALL.into_iter().find(|kind| kind.tag() == key[0])?;
+    let height = BlockHeight::from_be_bytes(key[1..9].try_into().unwrap());
+    let shard_id = ShardId::from_be_bytes(key[9..17].try_into().unwrap());
+    Some((kind, height, shard_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::validator_signer::InMemoryValidatorSigner;
+
+    fn approval(parent_height: BlockHeight, target_height: BlockHeight) -> Approval {
+        let signer = InMemoryValidatorSigner::from_seed(
+            "test".parse().unwrap(),
+            near_crypto::KeyType::ED25519,
+            "test",
+        );
+        let parent_hash = CryptoHash::hash_borsh(parent_height);
+        Approval::new(parent_hash, parent_height, target_height, &signer)
+    }
+
+    #[test]
+    fn conflicting_approvals_are_refused() {
+        let store = near_store::test_utils::create_test_store();
+        let journal = SigningJournal::new(store.clone()).unwrap();
+        let skip = approval(8, 10);
+        let endorsement = approval(9, 10);
+        journal.record_approval(&skip).unwrap();
+        journal.record_approval(&skip).unwrap();
+        assert!(matches!(journal.record_approval(&endorsement), Err(Error::Conflict { .. })));
+
+        // The journal survives a restart.
+        let journal = SigningJournal::new(store).unwrap();
+        assert!(matches!(journal.record_approval(&endorsement), Err(Error::Conflict { .. })));
+        journal.record_approval(&approval(8, 11)).unwrap();
+    }
+
+    #[test]
+    fn old_entries_are_removed() {
+        let store = near_store::test_utils::create_test_store();
+        let journal = SigningJournal::new(store.clone()).unwrap();
+        journal.record_approval(&approval(0, 1)).unwrap();
+        journal.record_approval(&approval(0, 2 + JOURNAL_RETENTION_HEIGHTS)).unwrap();
+        let key = encode_key(Signed::Approval, 1, 0, &[]);
+        assert!(!store.exists(DBCol::SigningJournal, &key).unwrap());
+        assert_eq!(Some((Signed::Approval, 1, 0)), decode_key(&key));
+    }
+}
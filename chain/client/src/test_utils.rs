@@ -21,7 +21,7 @@ use once_cell::sync::OnceCell;
 use rand::{thread_rng, Rng};
 use tracing::info;
 
-use crate::{start_view_client, Client, ClientActor, SyncStatus, ViewClientActor};
+use crate::{start_view_client, ApprovalQueue, Client, ClientActor, SyncStatus, ViewClientActor};
 use chrono::Utc;
 use near_chain::chain::{do_apply_chunks, BlockCatchUpRequest, StateSplitRequest};
 use near_chain::test_utils::{
@@ -35,7 +35,7 @@ use near_chain::{
 };
 use near_chain_configs::ClientConfig;
 use near_chunks::adapter::ShardsManagerRequestFromClient;
-use near_chunks::client::ShardsManagerResponse;
+use near_chunks::client::{ShardsManagerResponse, TransactionPoolSizes};
 use near_chunks::test_utils::{MockClientAdapterForShardsManager, SynchronousShardsManagerAdapter};
 use near_client_primitives::types::Error;
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signer};
@@ -228,7 +228,13 @@ pub fn setup(
         runtime.clone(),
         &chain_genesis,
         doomslug_threshold_mode,
-        ChainConfig { save_trie_changes: true, background_migration_threads: 1 },
+        ChainConfig {
+            save_trie_changes: true,
+            background_migration_threads: 1,
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
+        },
     )
     .unwrap();
     let genesis_block = chain.get_block(&chain.genesis().hash().clone()).unwrap();
@@ -246,6 +252,7 @@ pub fn setup(
     );
 
     let adv = crate::adversarial::Controls::default();
+    let pool_sizes = TransactionPoolSizes::default();
 
     let view_client_addr = start_view_client(
         Some(signer.validator_id().clone()),
@@ -253,6 +260,7 @@ pub fn setup(
         runtime.clone(),
         network_adapter.clone(),
         config.clone(),
+        pool_sizes.clone(),
         adv.clone(),
     );
 
@@ -263,10 +271,17 @@ pub fn setup(
         Some(account_id),
         store,
         config.chunk_request_retry_period,
+        config.chunk_horizons.clone(),
+        config.chunk_availability_sampling.clone(),
+        config.pre_validate_chunks,
+        config.full_chunk_requests.clone(),
+        config.chunk_request_hedging.clone(),
+        config.chunk_only_producer_lightweight_mode,
+        config.chunk_header_validation_threads,
     );
     let shards_manager_adapter = Arc::new(shards_manager_addr);
 
-    let client = Client::new(
+    let mut client = Client::new(
         config.clone(),
         chain_genesis,
         runtime,
@@ -277,6 +292,7 @@ pub fn setup(
         TEST_SEED,
     )
     .unwrap();
+    client.sharded_tx_pool.share_sizes(pool_sizes);
     let client_actor = ClientActor::new(
         client,
         ctx.address(),
@@ -289,6 +305,7 @@ pub fn setup(
         None,
         adv,
         None,
+        ApprovalQueue::default(),
     )
     .unwrap();
     (genesis_block, client_actor, view_client_addr, shards_manager_adapter.into())
@@ -333,7 +350,13 @@ pub fn setup_only_view(
         runtime.clone(),
         &chain_genesis,
         doomslug_threshold_mode,
-        ChainConfig { save_trie_changes: true, background_migration_threads: 1 },
+        ChainConfig {
+            save_trie_changes: true,
+            background_migration_threads: 1,
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
+        },
     )
     .unwrap();
 
@@ -357,6 +380,7 @@ pub fn setup_only_view(
         runtime,
         network_adapter,
         config,
+        TransactionPoolSizes::default(),
         adv,
     )
 }
@@ -712,6 +736,7 @@ pub fn setup_mock_all_validators(
                                 connection_established_time: near_primitives::time::Instant::now(),
                                 peer_type: PeerType::Outbound,
                                 nonce: 3,
+                                traffic_by_message_type: Default::default(),
                             })
                             .collect();
                         let peers2 = peers
@@ -781,7 +806,29 @@ pub fn setup_mock_all_validators(
                                 route_back,
                                 drop_chunks,
                                 |c| {
-                                    c.send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse { partial_encoded_chunk_response: response.clone(), received_time: Instant::now() });
+                                    c.send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse { partial_encoded_chunk_response: response.clone(), peer_id: PeerId::random(), received_time: Instant::now() });
+                                },
+                            );
+                        }
+                        NetworkRequests::FullChunkRequest { target, request, .. } => {
+                            send_chunks(
+                                connectors1,
+                                validators_clone2.iter().map(|s| Some(s.clone())).enumerate(),
+                                target.account_id.as_ref().map(|s| s.clone()),
+                                drop_chunks,
+                                |c| {
+                                    c.send(ShardsManagerRequestFromNetwork::ProcessFullChunkRequest { full_chunk_request: request.clone(), route_back: my_address });
+                                },
+                            );
+                        }
+                        NetworkRequests::FullChunkResponse { route_back, response } => {
+                            send_chunks(
+                                connectors1,
+                                addresses.iter().enumerate(),
+                                route_back,
+                                drop_chunks,
+                                |c| {
+                                    c.send(ShardsManagerRequestFromNetwork::ProcessFullChunkResponse { full_chunk_response: response.clone(), peer_id: PeerId::random(), received_time: Instant::now() });
                                 },
                             );
                         }
@@ -795,7 +842,10 @@ pub fn setup_mock_all_validators(
                                 account_id.clone(),
                                 drop_chunks,
                                 |c| {
-                                    c.send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(partial_encoded_chunk.clone().into()));
+                                    c.send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                                        partial_encoded_chunk: partial_encoded_chunk.clone().into(),
+                                        peer_id: None,
+                                    });
                                 },
                             );
                         }
@@ -1191,7 +1241,13 @@ pub fn setup_synchronous_shards_manager(
         runtime_adapter.clone(),
         chain_genesis,
         DoomslugThresholdMode::TwoThirds, // irrelevant
-        ChainConfig { save_trie_changes: true, background_migration_threads: 1 }, // irrelevant
+        ChainConfig {
+            save_trie_changes: true,
+            background_migration_threads: 1,
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
+        }, // irrelevant
     )
     .unwrap();
     let chain_head = chain.head().unwrap();
@@ -1205,6 +1261,7 @@ pub fn setup_synchronous_shards_manager(
         chain.store().new_read_only_chunks_store(),
         chain_head,
         chain_header_head,
+        Default::default(),
     );
     Arc::new(SynchronousShardsManagerAdapter::new(shards_manager)).into()
 }
@@ -1565,9 +1622,10 @@ impl TestEnv {
                 ) = request
                 {
                     self.shards_manager(&account_id).send(
-                        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-                            PartialEncodedChunk::from(partial_encoded_chunk),
-                        ),
+                        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                            partial_encoded_chunk: PartialEncodedChunk::from(partial_encoded_chunk),
+                            peer_id: None,
+                        },
                     );
                 }
             }
@@ -1598,6 +1656,7 @@ impl TestEnv {
                 self.shards_manager_adapters[id].send(
                     ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
                         partial_encoded_chunk_response: response,
+                        peer_id: PeerId::random(),
                         received_time: Instant::now(),
                     },
                 );
@@ -1639,7 +1698,14 @@ impl TestEnv {
         let mut any_processed = false;
         while let Some(msg) = self.client_adapters[id].pop() {
             match msg {
-                ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
+                ShardsManagerResponse::ChunkCompleted {
+                    partial_chunk,
+                    shard_chunk,
+                    completion_stats,
+                } => {
+                    if let Some(stats) = completion_stats {
+                        self.clients[id].record_chunk_completion_stats(stats);
+                    }
                     self.clients[id].on_chunk_completed(
                         partial_chunk,
                         shard_chunk,
@@ -1868,8 +1934,10 @@ impl TestEnv {
         match response {
             ProcessTxResponse::NoResponse
             | ProcessTxResponse::RequestRouted
-            | ProcessTxResponse::ValidTx => (),
+            | ProcessTxResponse::ValidTx
+            | ProcessTxResponse::ReplacedTx => (),
             ProcessTxResponse::InvalidTx(e) => return Err(e),
+            ProcessTxResponse::NoSpaceLeft => panic!("the transaction pool is full"),
             ProcessTxResponse::DoesNotTrackShard => panic!("test setup is buggy"),
         }
         let max_iters = 100;
@@ -2037,6 +2105,7 @@ pub fn create_chunk(
         *last_block.header().next_bp_hash(),
         block_merkle_tree.root(),
         None,
+        None,
     );
     (chunk, merkle_paths, receipts, block)
 }
@@ -2071,7 +2140,8 @@ pub fn run_catchup(
         )?;
         let mut catchup_done = true;
         for msg in block_messages.write().unwrap().drain(..) {
-            let results = do_apply_chunks(msg.block_hash, msg.block_height, msg.work);
+            let results =
+                do_apply_chunks(&msg.apply_chunks_pool, msg.block_hash, msg.block_height, msg.work);
             if let Some((_, _, blocks_catch_up_state)) =
                 client.catchup_state_syncs.get_mut(&msg.sync_hash)
             {
@@ -92,9 +92,12 @@ fn repro_1183() {
                             for (i, name) in validators.iter().enumerate() {
                                 if name == account_id {
                                     connectors1.write().unwrap()[i].shards_manager_adapter.send(
-                                        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-                                            partial_encoded_chunk.clone().into(),
-                                        ),
+                                        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                                            partial_encoded_chunk: partial_encoded_chunk
+                                                .clone()
+                                                .into(),
+                                            peer_id: None,
+                                        },
                                     );
                                 }
                             }
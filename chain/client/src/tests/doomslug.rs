@@ -1,10 +1,15 @@
+use crate::metrics;
 use crate::test_utils::TestEnv;
 use near_chain::{ChainGenesis, Provenance};
 use near_crypto::KeyType;
 use near_o11y::testonly::init_test_logger;
-use near_primitives::block::{Approval, ApprovalType};
+use near_primitives::block::{Approval, ApprovalType, Block};
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::static_clock::StaticClock;
+use near_primitives::types::AccountId;
 use near_primitives::validator_signer::InMemoryValidatorSigner;
+use std::time::{Duration, Instant};
 
 /// This file contains tests that test the interaction of client and doomslug, including how client handles approvals, etc.
 /// It does not include the unit tests for the Doomslug class. That is located in chain/chain/src/doomslug.rs
@@ -32,3 +37,114 @@ fn test_processing_skips_on_forks() {
     env.clients[1].collect_block_approval(&approval, ApprovalType::SelfApproval);
     assert!(!env.clients[1].doomslug.approval_status_at_height(&3).approvals.is_empty());
 }
+
+/// Records the approval of the next block by `test0` as received by its doomslug at `now`.
+fn record_own_approval(env: &mut TestEnv, prev_block: &Block, now: Instant) {
+    let signer =
+        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let approval = Approval::new(
+        *prev_block.hash(),
+        prev_block.header().height(),
+        prev_block.header().height() + 1,
+        &signer,
+    );
+    let stakes = env.clients[0]
+        .runtime_adapter
+        .get_epoch_block_approvers_ordered(prev_block.hash())
+        .unwrap();
+    env.clients[0].doomslug.on_approval_message(now, &approval, &stakes);
+}
+
+// Tests that the dry run of the block production reports the block producer of the next height
+// and the approvals it collected, without producing the block.
+#[test]
+fn test_block_production_dry_run() {
+    init_test_logger();
+
+    let mut env =
+        TestEnv::builder(ChainGenesis::test()).clients_count(2).validator_seats(2).build();
+    let b1 = env.clients[1].produce_block(1).unwrap().unwrap();
+    env.process_block(0, b1.clone(), Provenance::NONE);
+    env.process_block(1, b1.clone(), Provenance::NONE);
+    // The doomslug tip is updated by the client actor, which `TestEnv` doesn't run.
+    env.clients[0].check_and_update_doomslug_tip().unwrap();
+    // The own approval, sent by the client actor as well, passes the approvals threshold. It is
+    // recorded long enough ago not to wait for the missing chunks.
+    record_own_approval(&mut env, &b1, StaticClock::instant() - Duration::from_secs(10));
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+    let approval = Approval::new(*b1.hash(), 1, 2, &validator_signer);
+    env.clients[0].collect_block_approval(&approval, ApprovalType::PeerApproval(PeerId::random()));
+
+    let dry_run = env.clients[0].dry_run_block_production(0).unwrap();
+    assert_eq!(dry_run.height, 2);
+    assert_eq!(dry_run.prev_block_hash, *b1.hash());
+    assert_eq!(dry_run.block_producer.as_ref(), "test0");
+    assert_eq!(dry_run.skip_reason, None);
+    assert_eq!(dry_run.chunks.len(), 1);
+    assert_eq!(dry_run.approvals.len(), 2);
+    let approval =
+        dry_run.approvals.iter().find(|approval| approval.account_id.as_ref() == "test1");
+    assert!(approval.unwrap().received_time.is_some());
+    // Nothing was produced.
+    assert_eq!(env.clients[0].chain.head().unwrap().height, 1);
+
+    let dry_run = env.clients[1].dry_run_block_production(0).unwrap();
+    assert_eq!(dry_run.skip_reason.as_deref(), Some("not the block producer for the height"));
+}
+
+// Tests that the dry run of the block production reports the checks done by the client actor
+// before producing the block: approvals from enough other validators and enough healthy peers.
+#[test]
+fn test_block_production_dry_run_too_few_approvals() {
+    init_test_logger();
+
+    let mut env =
+        TestEnv::builder(ChainGenesis::test()).clients_count(2).validator_seats(2).build();
+    let b1 = env.clients[1].produce_block(1).unwrap().unwrap();
+    env.process_block(0, b1.clone(), Provenance::NONE);
+    env.clients[0].check_and_update_doomslug_tip().unwrap();
+    record_own_approval(&mut env, &b1, StaticClock::instant() - Duration::from_secs(10));
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+    let approval = Approval::new(*b1.hash(), 1, 2, &validator_signer);
+    env.clients[0].collect_block_approval(&approval, ApprovalType::PeerApproval(PeerId::random()));
+
+    env.clients[0].config.min_block_production_approvers = 2;
+    let dry_run = env.clients[0].dry_run_block_production(0).unwrap();
+    assert_eq!(dry_run.skip_reason.as_deref(), Some("approvals from too few other validators"));
+
+    env.clients[0].config.min_block_production_approvers = 1;
+    env.clients[0].config.min_block_production_peers = 1;
+    let dry_run = env.clients[0].dry_run_block_production(0).unwrap();
+    assert_eq!(dry_run.skip_reason.as_deref(), Some("not enough healthy peers"));
+    let dry_run = env.clients[0].dry_run_block_production(1).unwrap();
+    assert_eq!(dry_run.skip_reason, None);
+    // Nothing was produced.
+    assert_eq!(env.clients[0].chain.head().unwrap().height, 1);
+}
+
+// Tests that the block production loop, checking the connectivity on every tick, reports the
+// withheld block production once per height.
+#[test]
+fn test_block_production_withheld_reported_once_per_height() {
+    init_test_logger();
+
+    let mut env =
+        TestEnv::builder(ChainGenesis::test()).clients_count(2).validator_seats(2).build();
+    let me: AccountId = "test0".parse().unwrap();
+    env.clients[0].config.min_block_production_approvers = 1;
+    let withheld = metrics::BLOCK_PRODUCTION_WITHHELD.with_label_values(&["approvals"]);
+    let withheld_before = withheld.get();
+
+    for _ in 0..3 {
+        assert!(!env.clients[0].check_connected_enough_to_produce_block(2, &me, 0));
+    }
+    assert_eq!(withheld.get() - withheld_before, 1);
+    assert!(!env.clients[0].check_connected_enough_to_produce_block(3, &me, 0));
+    assert!(!env.clients[0].check_connected_enough_to_produce_block(3, &me, 0));
+    assert_eq!(withheld.get() - withheld_before, 2);
+
+    env.clients[0].config.min_block_production_approvers = 0;
+    assert!(env.clients[0].check_connected_enough_to_produce_block(3, &me, 0));
+}
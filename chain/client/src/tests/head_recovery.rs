@@ -0,0 +1,82 @@
+use crate::test_utils::TestEnv;
+use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
+use near_chain::types::RuntimeWithEpochManagerAdapter;
+use near_chain::{ChainGenesis, ChainStoreAccess};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::Block;
+use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+use near_primitives::utils::get_block_shard_id;
+use near_store::test_utils::create_test_store;
+use near_store::{DBCol, Store};
+use std::sync::Arc;
+
+/// Produces blocks with new chunks, removes some data of the head block with `remove` and checks
+/// that the head is rolled back to its parent on restart. Unless `tracks_all_shards`, the node
+/// tracks the shard only as its chunk producer.
+fn check_head_rolled_back(tracks_all_shards: bool, remove: impl FnOnce(&Store, &Block, ShardUId)) {
+    init_test_logger();
+    let chain_genesis = ChainGenesis::test();
+    let validators = vec!["test0".parse().unwrap()];
+    let vs = ValidatorSchedule::new().block_producers_per_epoch(vec![validators]);
+    // The head recovery only checks the chunks of the tracked shards.
+    let runtime = KeyValueRuntime::new_with_validators_and_no_gc_and_tracking(
+        create_test_store(),
+        vs,
+        chain_genesis.epoch_length,
+        false,
+        tracks_all_shards,
+    );
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(vec![runtime as Arc<dyn RuntimeWithEpochManagerAdapter>])
+        .build();
+    for height in 1..6 {
+        env.produce_block(0, height);
+    }
+    let head = env.clients[0].chain.get_block_by_height(5).unwrap();
+    let parent = env.clients[0].chain.get_block_by_height(4).unwrap();
+    assert_eq!(head.chunks()[0].height_included(), head.header().height());
+    let shard_uid =
+        env.clients[0].runtime_adapter.shard_id_to_uid(0, head.header().epoch_id()).unwrap();
+
+    remove(env.clients[0].chain.store().store(), &head, shard_uid);
+    env.restart(0);
+    assert_eq!(env.clients[0].chain.head().unwrap().last_block_hash, *parent.hash());
+    // The header is kept, the block is synced again.
+    assert!(env.clients[0].chain.get_block_header(head.hash()).is_ok());
+}
+
+#[test]
+fn test_recover_head_missing_chunk() {
+    check_head_rolled_back(true, |store, head, _| {
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::Chunks, head.chunks()[0].chunk_hash().as_ref());
+        store_update.commit().unwrap();
+    });
+}
+
+#[test]
+fn test_recover_head_missing_outcomes() {
+    check_head_rolled_back(true, |store, head, _| {
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::OutcomeIds, &get_block_shard_id(head.hash(), 0));
+        store_update.commit().unwrap();
+    });
+}
+
+#[test]
+fn test_recover_head_missing_chunk_extra() {
+    check_head_rolled_back(true, |store, head, shard_uid| {
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::ChunkExtra, &get_block_shard_uid(head.hash(), &shard_uid));
+        store_update.commit().unwrap();
+    });
+}
+
+#[test]
+fn test_recover_head_missing_chunk_of_validator_shard() {
+    check_head_rolled_back(false, |store, head, _| {
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::Chunks, head.chunks()[0].chunk_hash().as_ref());
+        store_update.commit().unwrap();
+    });
+}
@@ -4,6 +4,9 @@ mod chunks_management;
 mod consensus;
 mod cross_shard_tx;
 mod doomslug;
+mod head_recovery;
 mod maintenance_windows;
 mod process_blocks;
 mod query_client;
+mod tx_forwarding;
+mod tx_pool;
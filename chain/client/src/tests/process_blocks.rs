@@ -4,6 +4,7 @@ use near_crypto::{KeyType, PublicKey};
 use near_primitives::network::PeerId;
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::types::validator_stake::ValidatorStake;
+use near_primitives::version::PROTOCOL_VERSION;
 use std::sync::Arc;
 
 /// Only process one block per height
@@ -37,3 +38,46 @@ fn test_not_process_height_twice() {
     // check that we didn't rebroadcast the second block
     assert!(env.network_adapters[0].pop().is_none());
 }
+
+/// Test that the produced blocks vote for the overridden protocol version.
+#[test]
+fn test_protocol_version_vote_override() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    let block = env.clients[0].produce_block(1).unwrap().unwrap();
+    assert_eq!(block.header().latest_protocol_version(), PROTOCOL_VERSION);
+    env.process_block(0, block, Provenance::PRODUCED);
+
+    env.clients[0].config.protocol_version_vote_override = Some(PROTOCOL_VERSION + 1);
+    let block = env.clients[0].produce_block(2).unwrap().unwrap();
+    assert_eq!(block.header().latest_protocol_version(), PROTOCOL_VERSION + 1);
+}
+
+/// Test that the blocks received while the block processing pool is full are not dropped, but
+/// processed once there is room in the pool.
+#[test]
+fn test_blocks_wait_for_processing() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).clients_count(2).build();
+    // Blocks at different heights on top of genesis, so that all of them can be processed at once.
+    let blocks: Vec<_> =
+        (1..=7).map(|height| env.clients[0].produce_block(height).unwrap().unwrap()).collect();
+    for block in &blocks {
+        env.clients[1]
+            .receive_block_impl(
+                block.clone(),
+                PeerId::new(PublicKey::empty(KeyType::ED25519)),
+                true,
+                Arc::new(|_| {}),
+            )
+            .unwrap();
+    }
+    assert!(!env.clients[1].chain.can_start_processing_block());
+    for block in &blocks[5..] {
+        assert!(!test_utils::is_block_in_processing(&env.clients[1].chain, block.hash()));
+    }
+
+    let accepted_blocks = env.clients[1].finish_blocks_in_processing();
+    assert_eq!(accepted_blocks.len(), blocks.len());
+    for block in &blocks {
+        assert!(env.clients[1].chain.block_exists(block.hash()).unwrap());
+    }
+}
@@ -9,11 +9,12 @@ use std::time::Duration;
 use crate::adapter::{BlockResponse, ProcessTxRequest, ProcessTxResponse, StateRequestHeader};
 use crate::test_utils::{setup_mock_all_validators, setup_no_network, setup_only_view};
 use crate::{
-    GetBlock, GetBlockWithMerkleTree, GetExecutionOutcomesForBlock, Query, QueryError, Status,
-    TxStatus,
+    CancellationToken, GetBlock, GetBlockWithMerkleTree, GetExecutionOutcomesForBlock, Query,
+    QueryError, Status, TxStatus,
 };
 use near_actix_test_utils::run_actix;
 use near_chain_configs::DEFAULT_GC_NUM_EPOCHS_TO_KEEP;
+use near_client_primitives::api_keys::{ApiKeyConfig, ApiKeys};
 use near_crypto::{InMemorySigner, KeyType};
 use near_network::test_utils::MockPeerManagerAdapter;
 use near_network::types::PeerInfo;
@@ -58,6 +59,72 @@ fn query_client() {
     });
 }
 
+/// Heavy queries are executed on the query pool, unless they have been cancelled.
+#[test]
+fn query_client_heavy_query_cancellation() {
+    init_test_logger();
+    run_actix(async {
+        let actor_handles =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, true);
+        let query = Query::new(
+            BlockReference::latest(),
+            QueryRequest::ViewAccessKeyList { account_id: "test".parse().unwrap() },
+        );
+        let res = actor_handles.view_client_actor.send(query.clone().with_span_context()).await;
+        match res.unwrap().unwrap().kind {
+            QueryResponseKind::AccessKeyList(_) => (),
+            _ => panic!("Invalid response"),
+        }
+
+        let cancellation = CancellationToken::default();
+        cancellation.cancel();
+        let res = actor_handles
+            .view_client_actor
+            .send(query.with_cancellation(cancellation).with_span_context())
+            .await;
+        match res.unwrap() {
+            Err(QueryError::InternalError { .. }) => (),
+            res => panic!("Unexpected response: {:?}", res),
+        }
+        System::current().stop();
+    });
+}
+
+/// Heavy queries over the budget of the API key are rejected with a distinct error.
+#[test]
+fn query_client_api_key_budget_exceeded() {
+    init_test_logger();
+    run_actix(async {
+        let actor_handles =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, true);
+        let api_key = ApiKeys::new(
+            &[ApiKeyConfig {
+                name: "partner".to_string(),
+                key: "secret".to_string(),
+                requests_per_sec: None,
+                max_concurrent_heavy_queries: Some(0),
+                view_state_bytes_per_sec: None,
+            }],
+            std::time::Instant::now(),
+        )
+        .get("secret");
+        let query = Query::new(
+            BlockReference::latest(),
+            QueryRequest::ViewAccessKeyList { account_id: "test".parse().unwrap() },
+        )
+        .with_api_key(api_key);
+        let res = actor_handles.view_client_actor.send(query.with_span_context()).await;
+        match res.unwrap() {
+            Err(QueryError::ApiKeyBudgetExceeded { api_key, budget }) => {
+                assert_eq!(api_key, "partner");
+                assert_eq!(budget, "heavy_queries");
+            }
+            res => panic!("Unexpected response: {:?}", res),
+        }
+        System::current().stop();
+    });
+}
+
 /// When we receive health check and the latest block's timestamp is in the future, the client
 /// should not crash.
 #[test]
@@ -96,6 +163,7 @@ fn query_status_not_crash() {
                 block.header.next_bp_hash,
                 block_merkle_tree.root(),
                 None,
+                None,
             );
             next_block.mut_header().get_mut().inner_lite.timestamp =
                 to_timestamp(next_block.header().timestamp() + chrono::Duration::seconds(60));
@@ -0,0 +1,67 @@
+use crate::forwarded_txs::ForwardedTx;
+use crate::metrics;
+use crate::test_utils::TestEnv;
+use near_chain::{ChainGenesis, ChainStoreAccess};
+use near_crypto::{InMemorySigner, KeyType};
+use near_primitives::transaction::SignedTransaction;
+use std::time::Instant;
+
+fn send_money_tx(env: &TestEnv, nonce: u64) -> SignedTransaction {
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    SignedTransaction::send_money(
+        nonce,
+        "test0".parse().unwrap(),
+        "test1".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    )
+}
+
+/// Test that the delay between forwarding a transaction and its inclusion is observed, once a
+/// chunk including the transaction is accepted.
+#[test]
+fn test_forwarded_tx_inclusion_observed() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    let tx = send_money_tx(&env, 1);
+    let head = env.clients[0].chain.head().unwrap();
+    let epoch_id =
+        env.clients[0].runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash).unwrap();
+    env.clients[0].forward_tx(&epoch_id, &tx).unwrap();
+
+    let delay = metrics::TRANSACTION_FORWARD_TO_INCLUSION_DELAY.with_label_values(&["0"]);
+    let observed = delay.get_sample_count();
+    env.clients[0].process_tx(tx, false, false);
+    for height in 1..=4 {
+        env.produce_block(0, height);
+    }
+    assert!(delay.get_sample_count() > observed);
+}
+
+/// Test that a forwarded transaction which is already in the store isn't forwarded again, even
+/// if its inclusion hasn't been observed.
+#[test]
+fn test_included_forwarded_tx_not_retried() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    let tx = send_money_tx(&env, 1);
+    env.clients[0].process_tx(tx.clone(), false, false);
+    for height in 1..=4 {
+        env.produce_block(0, height);
+    }
+    assert!(env.clients[0].chain.store().get_transaction(&tx.get_hash()).unwrap().is_some());
+
+    // The transaction was forwarded recently, so forwarding it again is a no-op. Add it to the
+    // pending ones directly, as if its inclusion had been missed.
+    let head = env.clients[0].chain.head().unwrap();
+    let retry_after = env.clients[0].config.transaction_forwarding.retry_after;
+    env.clients[0].pending_forwarded_txs.insert(
+        tx.get_hash(),
+        head.height + retry_after,
+        ForwardedTx { tx, forwarded_at: Instant::now(), retries: 0 },
+    );
+    for height in head.height + 1..=head.height + retry_after {
+        env.produce_block(0, height);
+    }
+    assert!(env.clients[0].pending_forwarded_txs.is_empty());
+}
@@ -0,0 +1,73 @@
+use crate::test_utils::TestEnv;
+use near_chain::ChainGenesis;
+use near_crypto::{InMemorySigner, KeyType};
+use near_primitives::transaction::SignedTransaction;
+
+fn send_money_tx(env: &TestEnv, signer_id: &str, nonce: u64) -> SignedTransaction {
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed(signer_id.parse().unwrap(), KeyType::ED25519, signer_id);
+    SignedTransaction::send_money(
+        nonce,
+        signer_id.parse().unwrap(),
+        "test0".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    )
+}
+
+/// Test that the status of the transaction pool counts all the pooled transactions, but lists
+/// only the requested number of the busiest signers and of the transactions.
+#[test]
+fn test_tx_pool_status() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    for (signer_id, num_transactions) in [("test1", 3), ("test2", 1), ("test3", 2)] {
+        for nonce in 1..=num_transactions {
+            let tx = send_money_tx(&env, signer_id, nonce);
+            env.clients[0].sharded_tx_pool.insert_transaction(0, tx);
+        }
+    }
+
+    let status = env.clients[0].get_tx_pool_status(false, 100).unwrap();
+    assert_eq!(status.shards.len(), 1);
+    let shard = &status.shards[0];
+    assert_eq!(shard.num_transactions, 6);
+    assert_eq!(shard.num_accounts, 3);
+    let accounts: Vec<_> =
+        shard.accounts.iter().map(|a| (a.account_id.as_str(), a.num_transactions)).collect();
+    assert_eq!(accounts, vec![("test1", 3), ("test3", 2), ("test2", 1)]);
+    assert_eq!(shard.transactions, None);
+
+    let status = env.clients[0].get_tx_pool_status(true, 2).unwrap();
+    let shard = &status.shards[0];
+    assert_eq!(shard.num_transactions, 6);
+    assert_eq!(shard.num_accounts, 3);
+    let accounts: Vec<_> = shard.accounts.iter().map(|a| a.account_id.as_str()).collect();
+    assert_eq!(accounts, vec!["test1", "test3"]);
+    let transactions: Vec<_> = shard
+        .transactions
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|tx| (tx.signer_id.as_str(), tx.nonce))
+        .collect();
+    assert_eq!(transactions, vec![("test1", 1), ("test1", 2)]);
+}
+
+/// Test that the pooled nonces of an access key are listed in increasing order, without the
+/// nonces of the other keys.
+#[test]
+fn test_pooled_nonces() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    for nonce in [7, 3, 5] {
+        let tx = send_money_tx(&env, "test1", nonce);
+        env.clients[0].sharded_tx_pool.insert_transaction(0, tx);
+    }
+    let tx = send_money_tx(&env, "test2", 4);
+    env.clients[0].sharded_tx_pool.insert_transaction(0, tx);
+
+    let public_key =
+        InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1").public_key;
+    let nonces = env.clients[0].get_pooled_nonces(&"test1".parse().unwrap(), &public_key).unwrap();
+    assert_eq!(nonces, vec![3, 5, 7]);
+}
@@ -5,6 +5,7 @@ use actix::{Actor, Addr, Handler, SyncArbiter, SyncContext};
 use near_async::messaging::CanSend;
 use near_chain::types::Tip;
 use near_chain_primitives::error::EpochErrorResultToChainError;
+use near_chunks::client::TransactionPoolSizes;
 use near_primitives::receipt::Receipt;
 use near_primitives::static_clock::StaticClock;
 use near_store::{DBCol, COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY};
@@ -22,11 +23,12 @@ use near_chain::{
 };
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
-    GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError, GetMaintenanceWindows,
-    GetMaintenanceWindowsError, GetNextLightClientBlockError, GetProtocolConfig,
-    GetProtocolConfigError, GetReceipt, GetReceiptError, GetSplitStorageInfo,
+    CancellationToken, Error, GetAccountActivity, GetAccountActivityError, GetBlock, GetBlockError,
+    GetBlockProof, GetBlockProofError, GetBlockProofResponse, GetBlockWithMerkleTree,
+    GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError, GetExecutionOutcomesForBlock,
+    GetFeeHint, GetFeeHintError, GetGasPrice, GetGasPriceError, GetMaintenanceWindows,
+    GetMaintenanceWindowsError, GetNextLightClientBlockError, GetNonceGaps, GetNonceGapsError,
+    GetProtocolConfig, GetProtocolConfigError, GetReceipt, GetReceiptError, GetSplitStorageInfo,
     GetSplitStorageInfoError, GetStateChangesError, GetStateChangesWithCauseInBlock,
     GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
     TxStatus, TxStatusError,
@@ -42,27 +44,32 @@ use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
+use near_primitives::proof::{verify_block_proof, verify_outcome_proof};
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
     ShardStateSyncResponseV2,
 };
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockId, BlockReference, EpochReference, Finality, MaybeBlockId,
-    ShardId, SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
+    AccountId, Balance, BlockHeight, BlockHeightDelta, BlockId, BlockReference, EpochReference,
+    Finality, MaybeBlockId, Nonce, ShardId, StateChangesFilter, SyncCheckpoint,
+    TransactionOrReceiptId, ValidatorInfoIdentifier,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockView,
-    MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView, SplitStorageInfoView,
-    StateChangesKindsView, StateChangesView,
+    AccessKeyNonceGapsView, AccountActivityView, BlockView, ChunkView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FeeHintView, FinalExecutionOutcomeView,
+    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockView, MaintenanceWindowsView,
+    NonceGapView, QueryRequest, QueryResponse, QueryResponseKind, ReceiptView, ShardFeeHintView,
+    SplitStorageInfoView, StateChangesKindsView, StateChangesView,
 };
+use num_rational::Rational32;
 
 use crate::adapter::{
     AnnounceAccountRequest, BlockHeadersRequest, BlockRequest, StateRequestHeader,
     StateRequestPart, StateResponse, TxStatusRequest, TxStatusResponse,
 };
+use crate::query_cache::{CachedQuery, QueryCache};
 use crate::{
     metrics, sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
     GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
@@ -75,6 +82,16 @@ const REQUEST_WAIT_TIME: u64 = 1000;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
+/// How often a view client thread waiting for a query executed on the query pool checks whether
+/// the query has been cancelled.
+const QUERY_CANCELLATION_CHECK_PERIOD: Duration = Duration::from_millis(50);
+
+/// Number of the most recent blocks the fee hint is computed from.
+const FEE_HINT_NUM_BLOCKS: usize = 10;
+/// Maximal number of blocks of the gas price growth accounted for by the recommended gas price,
+/// so that a deep transaction pool doesn't make a fee hint request arbitrarily expensive.
+const MAX_FEE_HINT_BLOCKS: BlockHeightDelta = 100;
+
 /// Request and response manager across all instances of ViewClientActor.
 pub struct ViewClientRequestManager {
     /// Transaction query that needs to be forwarded to other shards
@@ -101,6 +118,13 @@ pub struct ViewClientActor {
     pub config: ClientConfig,
     request_manager: Arc<RwLock<ViewClientRequestManager>>,
     state_request_cache: Arc<Mutex<VecDeque<Instant>>>,
+    /// Pool executing the heavy queries, shared across all instances of ViewClientActor.
+    query_pool: Arc<rayon::ThreadPool>,
+    /// Cache of the responses to the cheap queries, shared across all instances of
+    /// ViewClientActor. None if disabled in the config.
+    query_cache: Option<Arc<Mutex<QueryCache>>>,
+    /// Sizes of the transaction pools of the client.
+    pool_sizes: TransactionPoolSizes,
 }
 
 impl ViewClientRequestManager {
@@ -126,6 +150,9 @@ impl ViewClientActor {
         network_adapter: PeerManagerAdapter,
         config: ClientConfig,
         request_manager: Arc<RwLock<ViewClientRequestManager>>,
+        query_pool: Arc<rayon::ThreadPool>,
+        query_cache: Option<Arc<Mutex<QueryCache>>>,
+        pool_sizes: TransactionPoolSizes,
         adv: crate::adversarial::Controls,
     ) -> Result<Self, Error> {
         // TODO: should we create shared ChainStore that is passed to both Client and ViewClient?
@@ -144,6 +171,9 @@ impl ViewClientActor {
             config,
             request_manager,
             state_request_cache: Arc::new(Mutex::new(VecDeque::default())),
+            query_pool,
+            query_cache,
+            pool_sizes,
         })
     }
 
@@ -250,6 +280,160 @@ impl ViewClientActor {
         }
     }
 
+    /// Compares the given nonces of the transactions of the access key pooled on the client with
+    /// the nonce of the key at the head of the chain.
+    fn get_nonce_gaps(
+        &self,
+        msg: GetNonceGaps,
+    ) -> Result<AccessKeyNonceGapsView, GetNonceGapsError> {
+        let GetNonceGaps { account_id, public_key, mut pooled_nonces } = msg;
+        let head = self.chain.head()?;
+        let header = self.chain.get_block_header(&head.last_block_hash)?;
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(&account_id, &head.epoch_id)?;
+        if !self.runtime_adapter.cares_about_shard(
+            self.validator_account_id.as_ref(),
+            &head.last_block_hash,
+            shard_id,
+            true,
+        ) {
+            return Err(GetNonceGapsError::UnavailableShard { requested_shard_id: shard_id });
+        }
+        let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &head.epoch_id)?;
+        let chunk_extra = self.chain.get_chunk_extra(&head.last_block_hash, &shard_uid)?;
+        let request = QueryRequest::ViewAccessKey {
+            account_id: account_id.clone(),
+            public_key: public_key.clone(),
+        };
+        let response = self
+            .runtime_adapter
+            .query(
+                shard_uid,
+                chunk_extra.state_root(),
+                header.height(),
+                header.raw_timestamp(),
+                header.prev_hash(),
+                header.hash(),
+                header.epoch_id(),
+                &request,
+            )
+            .map_err(|err| match err {
+                near_chain::near_chain_primitives::error::QueryError::UnknownAccount { .. }
+                | near_chain::near_chain_primitives::error::QueryError::UnknownAccessKey {
+                    ..
+                } => GetNonceGapsError::UnknownAccessKey { account_id, public_key },
+                err => GetNonceGapsError::Unreachable(err.to_string()),
+            })?;
+        let chain_nonce = match response.kind {
+            QueryResponseKind::AccessKey(access_key) => access_key.nonce,
+            kind => {
+                return Err(GetNonceGapsError::Unreachable(format!(
+                    "unexpected response to an access key query: {kind:?}"
+                )))
+            }
+        };
+        pooled_nonces.sort();
+        let gaps = nonce_gaps(chain_nonce, &pooled_nonces);
+        Ok(AccessKeyNonceGapsView {
+            block_hash: head.last_block_hash,
+            block_height: head.height,
+            chain_nonce,
+            pooled_nonces,
+            gaps,
+        })
+    }
+
+    /// Recommends a gas price per shard from the recent gas prices, the gas utilization of the
+    /// recent chunks and the depth of the transaction pool.
+    fn get_fee_hint(&self) -> Result<FeeHintView, near_chain::Error> {
+        let head = self.chain.head()?;
+        let head_block = self.chain.get_block(&head.last_block_hash)?;
+        let num_shards = self.runtime_adapter.num_shards(&head.epoch_id)?;
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&head.epoch_id)?;
+
+        // Per shard: the number of new chunks, the sum of their gas utilizations, and the number
+        // of chunks with an available body along with their transactions.
+        let mut num_chunks = vec![0u64; num_shards as usize];
+        let mut utilization = vec![0f64; num_shards as usize];
+        let mut num_chunk_bodies = vec![0u64; num_shards as usize];
+        let mut num_transactions = vec![0u64; num_shards as usize];
+        let mut block = head_block.clone();
+        for _ in 0..FEE_HINT_NUM_BLOCKS {
+            for chunk in block.chunks().iter() {
+                let shard_id = chunk.shard_id() as usize;
+                if chunk.height_included() != block.header().height()
+                    || shard_id >= num_chunks.len()
+                {
+                    continue;
+                }
+                num_chunks[shard_id] += 1;
+                if chunk.gas_limit() > 0 {
+                    utilization[shard_id] += chunk.gas_used() as f64 / chunk.gas_limit() as f64;
+                }
+                if let Ok(chunk) = self.chain.get_chunk(&chunk.chunk_hash()) {
+                    num_chunk_bodies[shard_id] += 1;
+                    num_transactions[shard_id] += chunk.transactions().len() as u64;
+                }
+            }
+            if block.header().height() == self.chain.genesis().height() {
+                break;
+            }
+            block = match self.chain.get_block(block.header().prev_hash()) {
+                Ok(block) => block,
+                // The older blocks might have been garbage collected.
+                Err(near_chain::Error::DBNotFoundErr(_)) => break,
+                Err(err) => return Err(err),
+            };
+        }
+
+        let gas_price = head_block.header().gas_price();
+        let economics = &self.chain.block_economics_config;
+        let shards = (0..num_shards)
+            .map(|shard_id| {
+                let index = shard_id as usize;
+                let gas_utilization = if num_chunks[index] > 0 {
+                    utilization[index] / num_chunks[index] as f64
+                } else {
+                    0.0
+                };
+                // Only the pool of a tracked shard receives its transactions.
+                let pool_size = self
+                    .runtime_adapter
+                    .cares_about_shard(
+                        self.validator_account_id.as_ref(),
+                        &head.last_block_hash,
+                        shard_id,
+                        true,
+                    )
+                    .then(|| self.pool_sizes.get(shard_id));
+                let expected_inclusion_delay = expected_inclusion_delay(
+                    pool_size,
+                    num_transactions[index],
+                    num_chunk_bodies[index],
+                );
+                let recommended_gas_price = recommended_gas_price(
+                    gas_price,
+                    expected_inclusion_delay.unwrap_or(FEE_HINT_NUM_BLOCKS as u64),
+                    economics.gas_price_adjustment_rate(protocol_version),
+                    economics.min_gas_price(protocol_version),
+                    economics.max_gas_price(protocol_version),
+                );
+                ShardFeeHintView {
+                    shard_id,
+                    gas_utilization,
+                    pool_size,
+                    expected_inclusion_delay,
+                    recommended_gas_price,
+                }
+            })
+            .collect();
+        Ok(FeeHintView {
+            block_hash: head.last_block_hash,
+            block_height: head.height,
+            gas_price,
+            shards,
+        })
+    }
+
     /// Returns maintenance windows by account.
     fn get_maintenance_windows(
         &self,
@@ -299,7 +483,45 @@ impl ViewClientActor {
         Ok(windows)
     }
 
+    /// Runs `f` on the query pool and waits for the result.
+    /// Returns None if `cancellation` gets cancelled in the meantime, in which case the view
+    /// client thread is released right away, and `f` is skipped unless it has started already.
+    fn run_on_query_pool<T: Send + 'static>(
+        &self,
+        cancellation: &CancellationToken,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Option<T> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let pool_cancellation = cancellation.clone();
+        self.query_pool.spawn(move || {
+            if pool_cancellation.is_cancelled() {
+                return;
+            }
+            // The receiver is gone if the query has been cancelled while executing.
+            let _ = sender.send(f());
+        });
+        loop {
+            match receiver.recv_timeout(QUERY_CANCELLATION_CHECK_PERIOD) {
+                Ok(result) => return Some(result),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    if cancellation.is_cancelled() {
+                        return None;
+                    }
+                }
+                // The task has been skipped, because the query had been cancelled.
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<QueryResponse, QueryError> {
+        let cancelled = || {
+            metrics::VIEW_CLIENT_QUERIES_CANCELLED.inc();
+            QueryError::InternalError { error_message: "The query has been cancelled".to_string() }
+        };
+        if msg.cancellation.is_cancelled() {
+            return Err(cancelled());
+        }
         let header = self.get_block_header_by_reference(&msg.block_reference);
         let header = match header {
             Ok(Some(header)) => Ok(header),
@@ -354,18 +576,96 @@ impl ViewClientActor {
                 _ => QueryError::Unreachable { error_message: err.to_string() },
             })?;
 
-        let state_root = chunk_extra.state_root();
-        match self.runtime_adapter.query(
-            shard_uid,
-            state_root,
-            header.height(),
-            header.raw_timestamp(),
-            header.prev_hash(),
-            header.hash(),
-            header.epoch_id(),
-            &msg.request,
-        ) {
-            Ok(query_response) => Ok(query_response),
+        let cached_query = match &self.query_cache {
+            Some(_) => CachedQuery::from_request(&msg.request),
+            None => None,
+        };
+        if let (Some(cache), Some(query)) = (&self.query_cache, &cached_query) {
+            let cache = cache.lock().expect(POISONED_LOCK_ERR);
+            if let Some(response) = cache.get(StaticClock::instant(), header.hash(), query) {
+                return Ok(response);
+            }
+        }
+
+        let state_root = *chunk_extra.state_root();
+        // Queries which iterate over the state or execute contract code may take long.
+        let is_heavy = matches!(
+            msg.request,
+            QueryRequest::ViewState { .. }
+                | QueryRequest::CallFunction { .. }
+                | QueryRequest::ViewAccessKeyList { .. }
+        );
+        let _heavy_query = match &msg.api_key {
+            Some(api_key) => {
+                let is_view_state = matches!(msg.request, QueryRequest::ViewState { .. });
+                let heavy_query =
+                    if is_view_state && !api_key.has_view_state_budget(StaticClock::instant()) {
+                        Err("view_state_bytes")
+                    } else if is_heavy {
+                        api_key.try_start_heavy_query().map(Some).ok_or("heavy_queries")
+                    } else {
+                        Ok(None)
+                    };
+                let result = match heavy_query {
+                    Ok(_) => "accepted",
+                    Err("view_state_bytes") => "view_state_bytes_exceeded",
+                    Err(_) => "heavy_queries_exceeded",
+                };
+                metrics::VIEW_CLIENT_API_KEY_QUERIES
+                    .with_label_values(&[api_key.name(), result])
+                    .inc();
+                heavy_query.map_err(|budget| QueryError::ApiKeyBudgetExceeded {
+                    api_key: api_key.name().to_string(),
+                    budget: budget.to_string(),
+                })?
+            }
+            None => None,
+        };
+        let runtime_adapter = self.runtime_adapter.clone();
+        let request = msg.request;
+        let cancellation = msg.cancellation.clone();
+        let query = move || {
+            runtime_adapter.query_with_cancellation(
+                shard_uid,
+                &state_root,
+                header.height(),
+                header.raw_timestamp(),
+                header.prev_hash(),
+                header.hash(),
+                header.epoch_id(),
+                &request,
+                &|| cancellation.is_cancelled(),
+            )
+        };
+        let result = if is_heavy {
+            self.run_on_query_pool(&msg.cancellation, query).ok_or_else(cancelled)?
+        } else {
+            query()
+        };
+        match result {
+            Ok(query_response) => {
+                if let (Some(api_key), QueryResponseKind::ViewState(state)) =
+                    (&msg.api_key, &query_response.kind)
+                {
+                    let bytes: usize =
+                        state.values.iter().map(|item| item.key.len() + item.value.len()).sum();
+                    api_key.charge_view_state_bytes(StaticClock::instant(), bytes as u64);
+                    metrics::VIEW_CLIENT_API_KEY_VIEW_STATE_BYTES
+                        .with_label_values(&[api_key.name()])
+                        .inc_by(bytes as u64);
+                }
+                if let (Some(cache), Some(query)) = (&self.query_cache, cached_query) {
+                    // Responses at the blocks below the last final block are not cached.
+                    let final_height = self.chain.final_head().map_or(0, |tip| tip.height);
+                    cache.lock().expect(POISONED_LOCK_ERR).insert(
+                        StaticClock::instant(),
+                        final_height,
+                        query,
+                        query_response.clone(),
+                    );
+                }
+                Ok(query_response)
+            }
             Err(query_error) => Err(match query_error {
                 near_chain::near_chain_primitives::error::QueryError::InternalError {
                     error_message,
@@ -409,6 +709,9 @@ impl ViewClientActor {
                     block_height,
                     block_hash,
                 },
+                near_chain::near_chain_primitives::error::QueryError::Cancelled { .. } => {
+                    cancelled()
+                }
             }),
         }
     }
@@ -759,11 +1062,13 @@ impl Handler<WithSpanContext<GetStateChanges>> for ViewClientActor {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         let _timer =
             metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetStateChanges"]).start_timer();
+        let filter = StateChangesFilter::from(msg.filter);
         Ok(self
             .chain
             .store()
             .get_state_changes(&msg.block_hash, &msg.state_changes_request.into())?
             .into_iter()
+            .filter(|state_change| filter.matches(&state_change.value))
             .map(Into::into)
             .collect())
     }
@@ -783,11 +1088,13 @@ impl Handler<WithSpanContext<GetStateChangesWithCauseInBlock>> for ViewClientAct
         let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
             .with_label_values(&["GetStateChangesWithCauseInBlock"])
             .start_timer();
+        let filter = StateChangesFilter::from(msg.filter);
         Ok(self
             .chain
             .store()
             .get_state_changes_with_cause_in_block(&msg.block_hash)?
             .into_iter()
+            .filter(|state_change| filter.matches(&state_change.value))
             .map(Into::into)
             .collect())
     }
@@ -810,10 +1117,14 @@ impl Handler<WithSpanContext<GetStateChangesWithCauseInBlockForTrackedShards>> f
             .start_timer();
         let state_changes_with_cause_in_block =
             self.chain.store().get_state_changes_with_cause_in_block(&msg.block_hash)?;
+        let filter = StateChangesFilter::from(msg.filter);
 
         let mut state_changes_with_cause_split_by_shard_id: HashMap<ShardId, StateChangesView> =
             HashMap::new();
         for state_change_with_cause in state_changes_with_cause_in_block {
+            if !filter.matches(&state_change_with_cause.value) {
+                continue;
+            }
             let account_id = state_change_with_cause.value.affected_account_id();
             let shard_id = match self
                 .runtime_adapter
@@ -927,9 +1238,8 @@ impl Handler<WithSpanContext<GetExecutionOutcome>> for ViewClientActor {
                     outcome_proof.block_hash = h;
                     // Here we assume the number of shards is small so this reconstruction
                     // should be fast
-                    let outcome_roots = self
-                        .chain
-                        .get_block(&h)?
+                    let block = self.chain.get_block(&h)?;
+                    let outcome_roots = block
                         .chunks()
                         .iter()
                         .map(|header| header.outcome_root())
@@ -940,11 +1250,26 @@ impl Handler<WithSpanContext<GetExecutionOutcome>> for ViewClientActor {
                             execution_outcome_shard_id: target_shard_id,
                         });
                     }
-                    Ok(GetExecutionOutcomeResponse {
+                    let response = GetExecutionOutcomeResponse {
                         outcome_proof: outcome_proof.into(),
                         outcome_root_proof: merklize(&outcome_roots).1[target_shard_id as usize]
                             .clone(),
-                    })
+                    };
+                    // The clients verify the proof with the same function, a proof failing it
+                    // would be rejected by them anyway.
+                    if !verify_outcome_proof(
+                        &response.outcome_proof,
+                        &response.outcome_root_proof,
+                        block.header().outcome_root(),
+                    ) {
+                        return Err(GetExecutionOutcomeError::InternalError {
+                            error_message: format!(
+                                "invalid outcome proof of {id} in block {}",
+                                block.hash()
+                            ),
+                        });
+                    }
+                    Ok(response)
                 } else {
                     Err(GetExecutionOutcomeError::NotConfirmed { transaction_or_receipt_id: id })
                 }
@@ -1034,6 +1359,17 @@ impl Handler<WithSpanContext<GetBlockProof>> for ViewClientActor {
         self.chain.check_blocks_final_and_canonical(&[&block_header, &head_block_header])?;
         let block_header_lite = block_header.into();
         let proof = self.chain.get_block_proof(&msg.block_hash, &msg.head_block_hash)?;
+        // The head block doesn't commit to itself, its proof is empty.
+        if msg.block_hash != msg.head_block_hash
+            && !verify_block_proof(&msg.block_hash, &proof, head_block_header.block_merkle_root())
+        {
+            return Err(GetBlockProofError::InternalError {
+                error_message: format!(
+                    "invalid proof of block {} in block {}",
+                    msg.block_hash, msg.head_block_hash
+                ),
+            });
+        }
         Ok(GetBlockProofResponse { block_header_lite, proof })
     }
 }
@@ -1411,6 +1747,58 @@ impl Handler<WithSpanContext<GetGasPrice>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetFeeHint>> for ViewClientActor {
+    type Result = Result<FeeHintView, GetFeeHintError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetFeeHint>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetFeeHint"]).start_timer();
+        Ok(self.get_fee_hint()?)
+    }
+}
+
+impl Handler<WithSpanContext<GetAccountActivity>> for ViewClientActor {
+    type Result = Result<Vec<AccountActivityView>, GetAccountActivityError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetAccountActivity>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetAccountActivity"])
+            .start_timer();
+        if !self.config.index_account_activity {
+            return Err(GetAccountActivityError::IndexDisabled);
+        }
+        Ok(self.chain.store().get_account_activity(&msg.account_id, msg.limit)?)
+    }
+}
+
+impl Handler<WithSpanContext<GetNonceGaps>> for ViewClientActor {
+    type Result = Result<AccessKeyNonceGapsView, GetNonceGapsError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetNonceGaps>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetNonceGaps"]).start_timer();
+        self.get_nonce_gaps(msg)
+    }
+}
+
 impl Handler<WithSpanContext<GetMaintenanceWindows>> for ViewClientActor {
     type Result = Result<MaintenanceWindowsView, GetMaintenanceWindowsError>;
 
@@ -1453,15 +1841,84 @@ impl Handler<WithSpanContext<GetSplitStorageInfo>> for ViewClientActor {
 }
 
 /// Starts the View Client in a new arbiter (thread).
+/// Ranges of the nonces missing between the on-chain nonce of an access key and the nonces of its
+/// pooled transactions, given in increasing order.
+fn nonce_gaps(chain_nonce: Nonce, pooled_nonces: &[Nonce]) -> Vec<NonceGapView> {
+    let mut gaps = vec![];
+    let mut next_nonce = chain_nonce.saturating_add(1);
+    for &nonce in pooled_nonces {
+        if nonce > next_nonce {
+            gaps.push(NonceGapView { start: next_nonce, end: nonce - 1 });
+        }
+        next_nonce = next_nonce.max(nonce.saturating_add(1));
+    }
+    gaps
+}
+
+/// Number of blocks until a transaction submitted now is expected to be included, from the size
+/// of the pool of its shard and the number of transactions of its recent chunks with an available
+/// body. None if the pool isn't tracked, or no transactions were included recently.
+fn expected_inclusion_delay(
+    pool_size: Option<u64>,
+    num_transactions: u64,
+    num_chunk_bodies: u64,
+) -> Option<BlockHeightDelta> {
+    match pool_size {
+        Some(0) => Some(1),
+        Some(pool_size) if num_transactions > 0 => {
+            let transactions_per_chunk = num_transactions as f64 / num_chunk_bodies as f64;
+            Some(1 + (pool_size as f64 / transactions_per_chunk) as BlockHeightDelta)
+        }
+        _ => None,
+    }
+}
+
+/// The price can grow by at most half the adjustment rate per block, so a transaction paying
+/// the maximal growth until its expected inclusion, plus a block for good measure, is never
+/// rejected for its gas price. The growth is accounted for at most `MAX_FEE_HINT_BLOCKS`.
+fn recommended_gas_price(
+    gas_price: Balance,
+    expected_inclusion_delay: BlockHeightDelta,
+    gas_price_adjustment_rate: Rational32,
+    min_gas_price: Balance,
+    max_gas_price: Balance,
+) -> Balance {
+    let mut recommended_gas_price = gas_price;
+    for _ in 0..=expected_inclusion_delay.min(MAX_FEE_HINT_BLOCKS) {
+        if recommended_gas_price >= max_gas_price {
+            break;
+        }
+        recommended_gas_price = Block::compute_new_gas_price(
+            recommended_gas_price,
+            1,
+            1,
+            gas_price_adjustment_rate,
+            min_gas_price,
+            max_gas_price,
+        );
+    }
+    recommended_gas_price
+}
+
 pub fn start_view_client(
     validator_account_id: Option<AccountId>,
     chain_genesis: ChainGenesis,
     runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
     network_adapter: PeerManagerAdapter,
     config: ClientConfig,
+    pool_sizes: TransactionPoolSizes,
     adv: crate::adversarial::Controls,
 ) -> Addr<ViewClientActor> {
     let request_manager = Arc::new(RwLock::new(ViewClientRequestManager::new()));
+    let query_pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config.view_client_query_threads)
+            .thread_name(|i| format!("view_client_query_{}", i))
+            .build()
+            .expect("failed to start the view client query pool"),
+    );
+    let query_cache =
+        config.view_query_cache.clone().map(|config| Arc::new(Mutex::new(QueryCache::new(config))));
     SyncArbiter::start(config.view_client_threads, move || {
         // ViewClientActor::start_in_arbiter(&Arbiter::current(), move |_ctx| {
         let validator_account_id1 = validator_account_id.clone();
@@ -1476,8 +1933,58 @@ pub fn start_view_client(
             network_adapter1,
             config1,
             request_manager1,
+            query_pool.clone(),
+            query_cache.clone(),
+            pool_sizes.clone(),
             adv.clone(),
         )
         .unwrap()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_inclusion_delay, nonce_gaps, recommended_gas_price, MAX_FEE_HINT_BLOCKS};
+    use near_primitives::views::NonceGapView;
+    use num_rational::Rational32;
+
+    #[test]
+    fn test_nonce_gaps() {
+        let gap = |start, end| NonceGapView { start, end };
+        assert!(nonce_gaps(5, &[]).is_empty());
+        assert!(nonce_gaps(5, &[6, 7, 8]).is_empty());
+        assert_eq!(nonce_gaps(5, &[7, 8, 11]), vec![gap(6, 6), gap(9, 10)]);
+        // The pooled transactions with already used nonces don't matter.
+        assert_eq!(nonce_gaps(5, &[3, 5, 6, 9]), vec![gap(7, 8)]);
+        assert!(nonce_gaps(u64::MAX - 1, &[u64::MAX]).is_empty());
+    }
+
+    #[test]
+    fn test_expected_inclusion_delay() {
+        // Not tracking the shard.
+        assert_eq!(expected_inclusion_delay(None, 100, 10), None);
+        // An empty pool is drained by the next chunk.
+        assert_eq!(expected_inclusion_delay(Some(0), 0, 0), Some(1));
+        // No recent transactions to estimate the throughput from.
+        assert_eq!(expected_inclusion_delay(Some(5), 0, 10), None);
+        // 10 transactions per chunk: 25 pooled transactions take 2 more chunks.
+        assert_eq!(expected_inclusion_delay(Some(25), 100, 10), Some(3));
+        assert_eq!(expected_inclusion_delay(Some(5), 100, 10), Some(1));
+    }
+
+    #[test]
+    fn test_recommended_gas_price() {
+        let rate = Rational32::new(1, 100);
+        let price =
+            |gas_price, delay| recommended_gas_price(gas_price, delay, rate, 100, 1_000_000);
+        // Grows by half a percent per block, for the delay plus a block.
+        assert_eq!(price(10_000, 0), 10_050);
+        assert_eq!(price(10_000, 1), 10_100);
+        // The growth is accounted for at most MAX_FEE_HINT_BLOCKS.
+        assert_eq!(price(100, u64::MAX), price(100, MAX_FEE_HINT_BLOCKS));
+        assert!(price(100, MAX_FEE_HINT_BLOCKS) < 1_000_000);
+        // Never above the maximal gas price.
+        assert_eq!(price(999_999, u64::MAX), 1_000_000);
+        assert_eq!(price(1_000_000, u64::MAX), 1_000_000);
+    }
+}
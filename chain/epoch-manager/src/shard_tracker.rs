@@ -37,6 +37,9 @@ type BitMask = Vec<bool>;
 #[derive(Clone)]
 pub struct ShardTracker {
     tracked_config: TrackedConfig,
+    /// If set and the node is a chunk-only producer in the epoch, it only tracks the shards
+    /// it produces chunks for, regardless of `tracked_config`.
+    chunk_only_producer_lightweight_mode: bool,
     /// Stores shard tracking information by epoch, only useful if TrackedState == Accounts
     tracking_shards_cache: Arc<SyncLruCache<EpochId, BitMask>>,
     epoch_manager: Arc<dyn EpochManagerAdapter>,
@@ -46,6 +49,7 @@ impl ShardTracker {
     pub fn new(tracked_config: TrackedConfig, epoch_manager: Arc<dyn EpochManagerAdapter>) -> Self {
         ShardTracker {
             tracked_config,
+            chunk_only_producer_lightweight_mode: false,
             // 1024 epochs on mainnet is about 512 days which is more than enough,
             // and this is a cache anyway. The data size is pretty small as well,
             // only one bit per shard per epoch.
@@ -54,6 +58,24 @@ impl ShardTracker {
         }
     }
 
+    pub fn with_chunk_only_producer_lightweight_mode(mut self, enabled: bool) -> Self {
+        self.chunk_only_producer_lightweight_mode = enabled;
+        self
+    }
+
+    /// Whether `account_id` is a validator which doesn't produce blocks in the given epoch.
+    fn is_chunk_only_producer(
+        &self,
+        account_id: &AccountId,
+        epoch_id: Result<EpochId, EpochError>,
+    ) -> Result<bool, EpochError> {
+        let epoch_info = self.epoch_manager.get_epoch_info(&epoch_id?)?;
+        Ok(match epoch_info.get_validator_id(account_id) {
+            Some(validator_id) => !epoch_info.block_producers_settlement().contains(validator_id),
+            None => false,
+        })
+    }
+
     fn tracks_shard_at_epoch(
         &self,
         shard_id: ShardId,
@@ -99,6 +121,15 @@ impl ShardTracker {
                 return account_cares_about_shard;
             } else if account_cares_about_shard {
                 return true;
+            } else if self.chunk_only_producer_lightweight_mode
+                && self
+                    .is_chunk_only_producer(
+                        account_id,
+                        self.epoch_manager.get_epoch_id_from_prev_block(parent_hash),
+                    )
+                    .unwrap_or(false)
+            {
+                return false;
             }
         }
         matches!(self.tracked_config, TrackedConfig::AllShards)
@@ -125,6 +156,15 @@ impl ShardTracker {
                 return account_cares_about_shard;
             } else if account_cares_about_shard {
                 return true;
+            } else if self.chunk_only_producer_lightweight_mode
+                && self
+                    .is_chunk_only_producer(
+                        account_id,
+                        self.epoch_manager.get_next_epoch_id_from_prev_block(parent_hash),
+                    )
+                    .unwrap_or(false)
+            {
+                return false;
             }
         }
         matches!(self.tracked_config, TrackedConfig::AllShards)
@@ -144,7 +184,9 @@ mod tests {
     use near_primitives::hash::CryptoHash;
     use near_primitives::shard_layout::ShardLayout;
     use near_primitives::types::validator_stake::ValidatorStake;
-    use near_primitives::types::{BlockHeight, EpochId, NumShards, ProtocolVersion, ShardId};
+    use near_primitives::types::{
+        AccountId, BlockHeight, EpochId, NumShards, ProtocolVersion, ShardId,
+    };
     use near_primitives::version::ProtocolFeature::SimpleNightshade;
     use near_primitives::version::PROTOCOL_VERSION;
     use near_store::test_utils::create_test_store;
@@ -158,6 +200,26 @@ mod tests {
         genesis_protocol_version: ProtocolVersion,
         num_shards: NumShards,
         use_production_config: bool,
+    ) -> EpochManagerHandle {
+        get_epoch_manager_with_validators(
+            genesis_protocol_version,
+            num_shards,
+            use_production_config,
+            vec![stake("test", 100)],
+        )
+    }
+
+    fn stake(account_id: &str, amount: u128) -> ValidatorStake {
+        ValidatorStake::new(account_id.parse().unwrap(), PublicKey::empty(KeyType::ED25519), amount)
+    }
+
+    /// With a single block producer seat, the validators other than the one with the most stake
+    /// are chunk-only producers.
+    fn get_epoch_manager_with_validators(
+        genesis_protocol_version: ProtocolVersion,
+        num_shards: NumShards,
+        use_production_config: bool,
+        validators: Vec<ValidatorStake>,
     ) -> EpochManagerHandle {
         let store = create_test_store();
         let initial_epoch_config = EpochConfig {
@@ -191,11 +253,7 @@ mod tests {
             AllEpochConfig::new(use_production_config, initial_epoch_config),
             genesis_protocol_version,
             reward_calculator,
-            vec![ValidatorStake::new(
-                "test".parse().unwrap(),
-                PublicKey::empty(KeyType::ED25519),
-                100,
-            )],
+            validators,
         )
         .unwrap()
         .into_handle()
@@ -293,6 +351,160 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lightweight_mode_keeps_tracking_for_block_producers() {
+        let num_shards = 4;
+        let epoch_manager = get_epoch_manager(PROTOCOL_VERSION, num_shards, false);
+        let tracker = ShardTracker::new(TrackedConfig::AllShards, Arc::new(epoch_manager))
+            .with_chunk_only_producer_lightweight_mode(true);
+        let parent_hash = CryptoHash::default();
+
+        // Neither the block producer nor a node which isn't a validator is affected by the mode.
+        for account_id in ["test".parse().unwrap(), "other".parse().unwrap()] {
+            for shard_id in 0..num_shards {
+                assert!(tracker.care_about_shard(Some(&account_id), &parent_hash, shard_id, true));
+                assert!(tracker.will_care_about_shard(
+                    Some(&account_id),
+                    &parent_hash,
+                    shard_id,
+                    true
+                ));
+            }
+        }
+    }
+
+    /// Shards the account cares about according to the tracker, in this and in the next epoch.
+    fn get_shards_account_cares_about(
+        tracker: &ShardTracker,
+        account_id: &AccountId,
+        num_shards: NumShards,
+        parent_hash: &CryptoHash,
+    ) -> (HashSet<ShardId>, HashSet<ShardId>) {
+        let cares = (0..num_shards)
+            .filter(|&shard_id| {
+                tracker.care_about_shard(Some(account_id), parent_hash, shard_id, true)
+            })
+            .collect();
+        let will_care = (0..num_shards)
+            .filter(|&shard_id| {
+                tracker.will_care_about_shard(Some(account_id), parent_hash, shard_id, true)
+            })
+            .collect();
+        (cares, will_care)
+    }
+
+    /// Shards the account produces chunks for, in this and in the next epoch.
+    fn get_shards_account_produces(
+        epoch_manager: &EpochManagerHandle,
+        account_id: &AccountId,
+        num_shards: NumShards,
+        parent_hash: &CryptoHash,
+    ) -> (HashSet<ShardId>, HashSet<ShardId>) {
+        let epoch_manager = epoch_manager.read();
+        let produces = (0..num_shards)
+            .filter(|&shard_id| {
+                epoch_manager
+                    .cares_about_shard_from_prev_block(parent_hash, account_id, shard_id)
+                    .unwrap()
+            })
+            .collect();
+        let will_produce = (0..num_shards)
+            .filter(|&shard_id| {
+                epoch_manager
+                    .cares_about_shard_next_epoch_from_prev_block(parent_hash, account_id, shard_id)
+                    .unwrap()
+            })
+            .collect();
+        (produces, will_produce)
+    }
+
+    #[test]
+    fn test_lightweight_mode_drops_shards_of_chunk_only_producers() {
+        let num_shards = 4;
+        let epoch_manager = get_epoch_manager_with_validators(
+            PROTOCOL_VERSION,
+            num_shards,
+            false,
+            vec![stake("test", 100), stake("cop", 50)],
+        );
+        let cop: AccountId = "cop".parse().unwrap();
+        let parent_hash = CryptoHash::default();
+        let (produces, will_produce) =
+            get_shards_account_produces(&epoch_manager, &cop, num_shards, &parent_hash);
+        assert!(!produces.is_empty() && produces.len() < num_shards as usize, "{produces:?}");
+
+        // Without the lightweight mode, the chunk-only producer tracks all shards.
+        let tracker = ShardTracker::new(TrackedConfig::AllShards, Arc::new(epoch_manager));
+        let all_shards: HashSet<_> = (0..num_shards).collect();
+        assert_eq!(
+            get_shards_account_cares_about(&tracker, &cop, num_shards, &parent_hash),
+            (all_shards.clone(), all_shards)
+        );
+
+        // With it, only the shards it produces chunks for.
+        let tracker = tracker.with_chunk_only_producer_lightweight_mode(true);
+        assert_eq!(
+            get_shards_account_cares_about(&tracker, &cop, num_shards, &parent_hash),
+            (produces, will_produce)
+        );
+    }
+
+    /// Check that in the lightweight mode a chunk-only producer, whose shards change in the next
+    /// epoch, already cares about its new shards, so that it can catch up with their state.
+    #[test]
+    fn test_lightweight_mode_epoch_transition() {
+        let num_shards = 4;
+        let epoch_manager = get_epoch_manager_with_validators(
+            PROTOCOL_VERSION,
+            num_shards,
+            false,
+            vec![stake("test", 100), stake("cop", 50)],
+        );
+        let cop: AccountId = "cop".parse().unwrap();
+        let tracker = ShardTracker::new(TrackedConfig::AllShards, Arc::new(epoch_manager.clone()))
+            .with_chunk_only_producer_lightweight_mode(true);
+
+        let h = hash_range(8);
+        {
+            let mut epoch_manager = epoch_manager.write();
+            record_block(
+                &mut epoch_manager,
+                CryptoHash::default(),
+                h[0],
+                0,
+                vec![],
+                PROTOCOL_VERSION,
+            );
+            for i in 1..8 {
+                // Another chunk producer joins, which changes the shards assigned to `cop`.
+                let proposals = if i == 1 { vec![stake("new", 75)] } else { vec![] };
+                record_block(
+                    &mut epoch_manager,
+                    h[i - 1],
+                    h[i],
+                    i as u64,
+                    proposals,
+                    PROTOCOL_VERSION,
+                );
+            }
+        }
+
+        let mut num_transitions = 0;
+        for i in 1..8 {
+            let (produces, will_produce) =
+                get_shards_account_produces(&epoch_manager, &cop, num_shards, &h[i - 1]);
+            assert!(!produces.is_empty() && produces.len() < num_shards as usize, "{produces:?}");
+            let (cares, will_care) =
+                get_shards_account_cares_about(&tracker, &cop, num_shards, &h[i - 1]);
+            assert_eq!(cares, produces);
+            assert_eq!(will_care, will_produce);
+            if cares != will_care {
+                num_transitions += 1;
+            }
+        }
+        assert!(num_transitions > 0);
+    }
+
     #[test]
     fn test_track_shards_shard_layout_change() {
         let simple_nightshade_version = SimpleNightshade.protocol_version();
@@ -81,6 +81,9 @@ pub struct IndexerConfig {
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
     /// Tells whether to validate the genesis file before starting
     pub validate_genesis: bool,
+    /// Filter applied to the state changes of the streamed blocks. By default all the state
+    /// changes are streamed.
+    pub state_changes_filter: near_primitives::views::StateChangesFilterView,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -170,5 +173,6 @@ pub fn indexer_init_configs(
         params.download_config_url.as_deref(),
         params.boot_nodes.as_deref(),
         params.max_gas_burnt_view,
+        None,
     )
 }
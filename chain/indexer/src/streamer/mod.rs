@@ -70,6 +70,7 @@ fn test_problematic_blocks_hash() {
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    state_changes_filter: &views::StateChangesFilterView,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let _timer = metrics::BUILD_STREAMER_MESSAGE_TIME.start_timer();
     let chunks = fetch_block_chunks(&client, &block).await?;
@@ -83,6 +84,7 @@ async fn build_streamer_message(
         &client,
         block.header.hash,
         near_primitives::types::EpochId(block.header.epoch_id),
+        state_changes_filter.clone(),
     )
     .await?;
     let mut indexer_shards = (0..num_shards)
@@ -349,7 +351,12 @@ pub(crate) async fn start(
         for block_height in start_syncing_block_height..=latest_block_height {
             metrics::CURRENT_BLOCK_HEIGHT.set(block_height as i64);
             if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
+                let response = build_streamer_message(
+                    &view_client,
+                    block,
+                    &indexer_config.state_changes_filter,
+                )
+                .await;
 
                 match response {
                     Ok(streamer_message) => {
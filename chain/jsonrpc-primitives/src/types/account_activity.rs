@@ -0,0 +1,52 @@
+use near_primitives::types::AccountId;
+use near_primitives::views::AccountActivityView;
+use serde_json::Value;
+
+/// Number of the outcomes returned if the request doesn't specify the limit.
+pub const DEFAULT_ACCOUNT_ACTIVITY_LIMIT: usize = 100;
+/// Maximal number of the outcomes returned by a single request.
+pub const MAX_ACCOUNT_ACTIVITY_LIMIT: usize = 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcAccountActivityRequest {
+    pub account_id: AccountId,
+    /// Maximal number of the outcomes to return.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcAccountActivityResponse {
+    /// The most recent outcomes executed by the account, latest first.
+    pub activity: Vec<AccountActivityView>,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcAccountActivityError {
+    #[error("The account activity index is disabled on this node")]
+    IndexDisabled,
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcAccountActivityError> for crate::errors::RpcError {
+    fn from(error: RpcAccountActivityError) -> Self {
+        let error_data = match &error {
+            RpcAccountActivityError::IndexDisabled => Some(Value::String(error.to_string())),
+            RpcAccountActivityError::InternalError { .. } => Some(Value::String(error.to_string())),
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcAccountActivityError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
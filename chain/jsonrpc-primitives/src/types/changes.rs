@@ -16,6 +16,9 @@ pub struct RpcStateChangesInBlockByTypeRequest {
     pub block_reference: near_primitives::types::BlockReference,
     #[serde(flatten)]
     pub state_changes_request: near_primitives::views::StateChangesRequestView,
+    /// Additional filter applied to the requested changes.
+    #[serde(default)]
+    pub filter: near_primitives::views::StateChangesFilterView,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
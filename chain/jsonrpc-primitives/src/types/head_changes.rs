@@ -0,0 +1,50 @@
+use near_primitives::views::HeadChangeView;
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcHeadChangesRequest {
+    /// Id of the last change seen by the caller. If not set, all the recent changes are returned.
+    #[serde(default)]
+    pub since_id: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcHeadChangesResponse {
+    pub changes: Vec<HeadChangeView>,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcHeadChangesError {
+    #[error("Some of the changes after {since_id} are not available: {error_message}")]
+    ChangesUnavailable {
+        since_id: u64,
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcHeadChangesError> for crate::errors::RpcError {
+    fn from(error: RpcHeadChangesError) -> Self {
+        let error_data = match &error {
+            RpcHeadChangesError::ChangesUnavailable { .. } => {
+                Some(Value::String(error.to_string()))
+            }
+            RpcHeadChangesError::InternalError { .. } => Some(Value::String(error.to_string())),
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcHeadChangesError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
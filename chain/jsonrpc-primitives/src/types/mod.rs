@@ -1,16 +1,21 @@
+pub mod account_activity;
 pub mod blocks;
 pub mod changes;
 pub mod chunks;
 pub mod client_config;
 pub mod config;
+pub mod fee_hint;
 pub mod gas_price;
+pub mod head_changes;
 pub mod light_client;
 pub mod maintenance;
 pub mod network_info;
+pub mod nonce_gaps;
 pub mod query;
 pub mod receipts;
 pub mod sandbox;
 pub mod split_storage;
 pub mod status;
 pub mod transactions;
+pub mod tx_pool;
 pub mod validator;
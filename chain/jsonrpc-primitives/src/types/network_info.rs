@@ -1,5 +1,7 @@
 use near_primitives::network::PeerId;
 use near_primitives::types::AccountId;
+use near_primitives::views::MessageTrafficView;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -7,6 +9,10 @@ pub struct RpcPeerInfo {
     pub id: PeerId,
     pub addr: Option<SocketAddr>,
     pub account_id: Option<AccountId>,
+    /// Traffic exchanged with the peer since the connection has been established,
+    /// by message type.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub traffic_by_message_type: BTreeMap<String, MessageTrafficView>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -0,0 +1,49 @@
+use near_crypto::PublicKey;
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views::AccessKeyNonceGapsView;
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcNonceGapsRequest {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcNonceGapsResponse {
+    #[serde(flatten)]
+    pub nonce_gaps: AccessKeyNonceGapsView,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcNonceGapsError {
+    #[error("Access key for public key {public_key} of account {account_id} does not exist")]
+    UnknownAccessKey { account_id: AccountId, public_key: PublicKey },
+    #[error("The node does not track the shard {requested_shard_id}")]
+    UnavailableShard { requested_shard_id: ShardId },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcNonceGapsError> for crate::errors::RpcError {
+    fn from(error: RpcNonceGapsError) -> Self {
+        let error_data = match &error {
+            RpcNonceGapsError::UnknownAccessKey { .. } => Some(Value::String(error.to_string())),
+            RpcNonceGapsError::UnavailableShard { .. } => Some(Value::String(error.to_string())),
+            RpcNonceGapsError::InternalError { .. } => Some(Value::String(error.to_string())),
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcNonceGapsError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
@@ -62,6 +62,8 @@ pub enum RpcQueryError {
     },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
     InternalError { error_message: String },
+    #[error("The budget of the API key {api_key} is exhausted: {budget}. Try again later")]
+    ApiKeyBudgetExceeded { api_key: String, budget: String },
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
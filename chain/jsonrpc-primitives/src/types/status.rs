@@ -1,11 +1,13 @@
 #[cfg(feature = "debug_types")]
 use near_client_primitives::debug::{
-    DebugBlockStatusData, EpochInfoView, TrackedShardsView, ValidatorStatus,
+    BlockProductionDryRunView, ChunkCompletionView, DebugBlockStatusData, EpochInfoView,
+    TrackedShardsView, ValidatorStatus,
 };
 #[cfg(feature = "debug_types")]
 use near_primitives::views::{
-    CatchupStatusView, ChainProcessingInfo, NetworkGraphView, PeerStoreView,
-    RecentOutboundConnectionsView, RequestedStatePartsView, SyncStatusView,
+    BlockPropagationView, CatchupStatusView, ChainProcessingInfo, DialerView, NetworkGraphView,
+    PeerStoreView, PingView, RecentOutboundConnectionsView, RequestedStatePartsView, RouteInfoView,
+    RoutedMessageTraceView, ShardsManagerView, SyncStatusView,
 };
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -32,6 +34,23 @@ pub enum DebugStatusResponse {
     RequestedStateParts(Vec<RequestedStatePartsView>),
     NetworkGraph(NetworkGraphView),
     RecentOutboundConnections(RecentOutboundConnectionsView),
+    RoutedMessageTrace(RoutedMessageTraceView),
+    // Backoff of the outbound connection attempts.
+    Dialer(DialerView),
+    // First sightings of the recent blocks and the propagation latency.
+    BlockPropagation(BlockPropagationView),
+    // Round-trip times of the pings sent through the routing layer.
+    Ping(PingView),
+    // Current route to a peer.
+    Route(RouteInfoView),
+    // Effective states of the rollout flags.
+    RolloutFlags(Vec<near_chain_configs::RolloutFlag>),
+    // Recently completed chunks, most recent first.
+    ChunkCompletion(Vec<ChunkCompletionView>),
+    // The block this node would produce right now.
+    BlockProductionDryRun(BlockProductionDryRunView),
+    // In-flight chunk requests and the contents of the chunk cache.
+    Chunks(ShardsManagerView),
 }
 
 #[cfg(feature = "debug_types")]
@@ -50,6 +69,8 @@ pub enum RpcStatusError {
     NodeIsSyncing,
     #[error("No blocks for {elapsed:?}")]
     NoNewBlocks { elapsed: std::time::Duration },
+    #[error("Data corruption detected in the store: {error_message}")]
+    DataCorruption { error_message: String },
     #[error("Epoch Out Of Bounds {epoch_id:?}")]
     EpochOutOfBounds { epoch_id: near_primitives::types::EpochId },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
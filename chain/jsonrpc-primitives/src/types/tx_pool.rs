@@ -0,0 +1,49 @@
+use near_primitives::views::TxPoolStatusView;
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxPoolStatusRequest {
+    /// Whether the summaries of the pooled transactions are included in the response.
+    #[serde(default)]
+    pub include_transactions: bool,
+    /// Maximal number of the signers and of the transactions listed per shard.
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxPoolStatusResponse {
+    #[serde(flatten)]
+    pub status: TxPoolStatusView,
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcTxPoolStatusError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcTxPoolStatusError> for crate::errors::RpcError {
+    fn from(error: RpcTxPoolStatusError) -> Self {
+        let error_data = match &error {
+            RpcTxPoolStatusError::InternalError { .. } => Some(Value::String(error.to_string())),
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcTxPoolStatusError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
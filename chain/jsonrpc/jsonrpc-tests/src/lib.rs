@@ -43,9 +43,11 @@ pub fn start_all_with_validity_period_and_no_epoch_sync(
     start_http(
         RpcConfig::new(addr),
         TEST_GENESIS_CONFIG.clone(),
+        Default::default(),
         actor_handles.client_actor,
         actor_handles.view_client_actor.clone(),
         None,
+        None,
     );
     (actor_handles.view_client_actor, addr)
 }
@@ -11,7 +11,7 @@ use near_network::test_utils::WaitOrTimeoutActor;
 use near_o11y::testonly::{init_integration_logger, init_test_logger};
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::serialize::to_base64;
-use near_primitives::transaction::SignedTransaction;
+use near_primitives::transaction::{Action, SignedTransaction, TransferAction};
 use near_primitives::types::BlockReference;
 use near_primitives::views::FinalExecutionStatus;
 
@@ -224,3 +224,30 @@ fn test_check_invalid_tx() {
         }
     });
 }
+
+#[test]
+fn test_check_tx_exceeding_limits() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let actions = (0..101).map(|_| Action::Transfer(TransferAction { deposit: 1 })).collect();
+        let tx = SignedTransaction::from_actions(
+            1,
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+            &signer,
+            actions,
+            hash(&[1]),
+        );
+        let bytes = tx.try_to_vec().unwrap();
+        match client.EXPERIMENTAL_check_tx(to_base64(&bytes)).await {
+            Err(e) => {
+                let s = serde_json::to_string(&e.data.unwrap()).unwrap();
+                assert_eq!(
+                    s,
+                    "{\"TxExecutionError\":{\"InvalidTxError\":{\"ActionsValidation\":{\"TotalNumberOfActionsExceeded\":{\"total_number_of_actions\":101,\"limit\":100}}}}}"
+                );
+            }
+            Ok(_) => panic!("transaction should not succeed"),
+        }
+    });
+}
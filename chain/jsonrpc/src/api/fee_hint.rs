@@ -0,0 +1,35 @@
+use near_client_primitives::types::GetFeeHintError;
+use near_jsonrpc_primitives::{
+    errors::RpcParseError,
+    types::fee_hint::{RpcFeeHintError, RpcFeeHintRequest},
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcFeeHintRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcFeeHintError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetFeeHintError> for RpcFeeHintError {
+    fn rpc_from(error: GetFeeHintError) -> Self {
+        match error {
+            GetFeeHintError::IOError(error_message) => Self::InternalError { error_message },
+            GetFeeHintError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcFeeHintError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
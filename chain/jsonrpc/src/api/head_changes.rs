@@ -0,0 +1,31 @@
+use near_client_primitives::types::GetHeadChangesError;
+use near_jsonrpc_primitives::{
+    errors::RpcParseError,
+    types::head_changes::{RpcHeadChangesError, RpcHeadChangesRequest},
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcHeadChangesRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcHeadChangesError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetHeadChangesError> for RpcHeadChangesError {
+    fn rpc_from(error: GetHeadChangesError) -> Self {
+        match error {
+            GetHeadChangesError::Evicted { since_id, .. }
+            | GetHeadChangesError::UnknownId { since_id, .. } => {
+                Self::ChangesUnavailable { since_id, error_message: error.to_string() }
+            }
+        }
+    }
+}
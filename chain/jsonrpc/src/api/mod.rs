@@ -3,21 +3,26 @@ use serde_json::Value;
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::errors::{RpcError, ServerError};
 
+mod account_activity;
 mod blocks;
 mod changes;
 mod chunks;
 mod client_config;
 mod config;
+mod fee_hint;
 mod gas_price;
+mod head_changes;
 mod light_client;
 mod maintenance;
 mod network_info;
+mod nonce_gaps;
 mod query;
 mod receipts;
 mod sandbox;
 mod split_storage;
 mod status;
 mod transactions;
+mod tx_pool;
 mod validator;
 
 pub(crate) trait RpcRequest: Sized {
@@ -0,0 +1,41 @@
+use near_client_primitives::types::GetNonceGapsError;
+use near_jsonrpc_primitives::{
+    errors::RpcParseError,
+    types::nonce_gaps::{RpcNonceGapsError, RpcNonceGapsRequest},
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcNonceGapsRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcNonceGapsError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetNonceGapsError> for RpcNonceGapsError {
+    fn rpc_from(error: GetNonceGapsError) -> Self {
+        match error {
+            GetNonceGapsError::UnknownAccessKey { account_id, public_key } => {
+                Self::UnknownAccessKey { account_id, public_key }
+            }
+            GetNonceGapsError::UnavailableShard { requested_shard_id } => {
+                Self::UnavailableShard { requested_shard_id }
+            }
+            GetNonceGapsError::IOError(error_message) => Self::InternalError { error_message },
+            GetNonceGapsError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcNonceGapsError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
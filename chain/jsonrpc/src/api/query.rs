@@ -96,6 +96,9 @@ impl RpcFrom<QueryError> for RpcQueryError {
     fn rpc_from(error: QueryError) -> Self {
         match error {
             QueryError::InternalError { error_message } => Self::InternalError { error_message },
+            QueryError::ApiKeyBudgetExceeded { api_key, budget } => {
+                Self::ApiKeyBudgetExceeded { api_key, budget }
+            }
             QueryError::NoSyncedBlocks => Self::NoSyncedBlocks,
             QueryError::UnavailableShard { requested_shard_id } => {
                 Self::UnavailableShard { requested_shard_id }
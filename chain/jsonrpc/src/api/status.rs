@@ -49,6 +49,17 @@ impl RpcFrom<near_client_primitives::debug::DebugStatusResponse>
                     x,
                 )
             }
+            near_client_primitives::debug::DebugStatusResponse::RolloutFlags(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::RolloutFlags(x)
+            }
+            near_client_primitives::debug::DebugStatusResponse::ChunkCompletion(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::ChunkCompletion(x)
+            }
+            near_client_primitives::debug::DebugStatusResponse::BlockProductionDryRun(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::BlockProductionDryRun(
+                    x,
+                )
+            }
         }
     }
 }
@@ -67,6 +78,27 @@ impl RpcFrom<near_network::debug::DebugStatus>
             near_network::debug::DebugStatus::RecentOutboundConnections(x) => {
                 near_jsonrpc_primitives::types::status::DebugStatusResponse::RecentOutboundConnections(x)
             }
+            near_network::debug::DebugStatus::RoutedMessageTrace(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::RoutedMessageTrace(x)
+            }
+            near_network::debug::DebugStatus::Dialer(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::Dialer(x)
+            }
+            near_network::debug::DebugStatus::BlockPropagation(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::BlockPropagation(x)
+            }
+        }
+    }
+}
+
+impl RpcFrom<near_chunks::debug::DebugStatus>
+    for near_jsonrpc_primitives::types::status::DebugStatusResponse
+{
+    fn rpc_from(response: near_chunks::debug::DebugStatus) -> Self {
+        match response {
+            near_chunks::debug::DebugStatus::Chunks(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::Chunks(x)
+            }
         }
     }
 }
@@ -83,6 +115,7 @@ impl RpcFrom<StatusError> for RpcStatusError {
             StatusError::InternalError { error_message } => Self::InternalError { error_message },
             StatusError::NodeIsSyncing => Self::NodeIsSyncing,
             StatusError::NoNewBlocks { elapsed } => Self::NoNewBlocks { elapsed },
+            StatusError::DataCorruption { error_message } => Self::DataCorruption { error_message },
             StatusError::EpochOutOfBounds { epoch_id } => Self::EpochOutOfBounds { epoch_id },
             StatusError::Unreachable { ref error_message } => {
                 tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
@@ -0,0 +1,35 @@
+use near_client_primitives::types::GetTxPoolStatusError;
+use near_jsonrpc_primitives::{
+    errors::RpcParseError,
+    types::tx_pool::{RpcTxPoolStatusError, RpcTxPoolStatusRequest},
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcTxPoolStatusRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcTxPoolStatusError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetTxPoolStatusError> for RpcTxPoolStatusError {
+    fn rpc_from(error: GetTxPoolStatusError) -> Self {
+        match error {
+            GetTxPoolStatusError::IOError(error_message) => Self::InternalError { error_message },
+            GetTxPoolStatusError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcTxPoolStatusError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
@@ -9,30 +9,45 @@ use api::RpcRequest;
 pub use api::{RpcFrom, RpcInto};
 use futures::Future;
 use futures::FutureExt;
-use near_chain_configs::GenesisConfig;
+use near_chain_configs::{GenesisConfig, TransactionLimitsConfig};
+use near_chunks::shards_manager_actor::ShardsManagerActor;
 use near_client::{
-    ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetClientConfig,
-    GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
+    CancellationToken, ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk,
+    GetClientConfig, GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
     GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
     GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, ProcessTxRequest,
     ProcessTxResponse, Query, Status, TxStatus, ViewClientActor,
 };
-use near_client_primitives::types::GetSplitStorageInfo;
+use near_client_primitives::api_keys::{ApiKey, ApiKeyConfig, ApiKeys};
+use near_client_primitives::types::{
+    AllowDeepReorg, GetAccountActivity, GetFeeHint, GetHeadChanges, GetNonceGaps, GetPooledNonces,
+    GetSplitStorageInfo, GetTxPoolStatus,
+};
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
 use near_jsonrpc_primitives::message::{Message, Request};
+use near_jsonrpc_primitives::types::account_activity::{
+    RpcAccountActivityResponse, DEFAULT_ACCOUNT_ACTIVITY_LIMIT, MAX_ACCOUNT_ACTIVITY_LIMIT,
+};
 use near_jsonrpc_primitives::types::config::RpcProtocolConfigResponse;
+use near_jsonrpc_primitives::types::fee_hint::RpcFeeHintResponse;
+use near_jsonrpc_primitives::types::head_changes::RpcHeadChangesResponse;
+use near_jsonrpc_primitives::types::nonce_gaps::RpcNonceGapsResponse;
 use near_jsonrpc_primitives::types::split_storage::RpcSplitStorageInfoResponse;
+use near_jsonrpc_primitives::types::tx_pool::RpcTxPoolStatusResponse;
 use near_network::tcp;
+use near_network::types::{PeerManagerMessageRequest, PeerManagerMessageResponse};
 use near_network::PeerManagerActor;
 use near_o11y::metrics::{prometheus, Encoder, TextEncoder};
 use near_o11y::{WithSpanContext, WithSpanContextExt};
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, BlockHeight};
 use near_primitives::views::FinalExecutionOutcomeViewEnum;
 use serde_json::{json, Value};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::{sleep, timeout};
 use tracing::info;
@@ -88,6 +103,14 @@ pub struct RpcConfig {
     // be read from this directory, instead of the contents compiled into the binary. This allows
     // for quick iterative development.
     pub experimental_debug_pages_src_path: Option<String>,
+    // If set, enables the admin endpoints (like the one to disconnect or ban a peer), which
+    // require the `Authorization: Bearer <admin_token>` header. Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+    // API keys recognized in the `X-Api-Key` header, each with its own budget of requests.
+    // The requests without an API key are not limited.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<ApiKeyConfig>,
 }
 
 impl Default for RpcConfig {
@@ -100,6 +123,8 @@ impl Default for RpcConfig {
             limits_config: Default::default(),
             enable_debug_rpc: false,
             experimental_debug_pages_src_path: None,
+            admin_token: None,
+            api_keys: vec![],
         }
     }
 }
@@ -221,18 +246,26 @@ struct JsonRpcHandler {
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
+    shards_manager_addr: Option<Addr<ShardsManagerActor>>,
     polling_config: RpcPollingConfig,
     genesis_config: GenesisConfig,
+    transaction_limits: TransactionLimitsConfig,
     enable_debug_rpc: bool,
     debug_pages_src_path: Option<PathBuf>,
+    admin_token: Option<String>,
+    api_keys: Arc<ApiKeys>,
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+    pub async fn process(
+        &self,
+        message: Message,
+        api_key: Option<Arc<ApiKey>>,
+    ) -> Result<Message, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+                Ok(Message::response(id, self.process_request(request, api_key).await))
             }
             _ => Ok(Message::error(RpcError::parse_error(
                 "JSON RPC Request format was expected".to_owned(),
@@ -242,11 +275,15 @@ impl JsonRpcHandler {
 
     // `process_request` increments affected metrics but the request processing is done by
     // `process_request_internal`.
-    async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
+    async fn process_request(
+        &self,
+        request: Request,
+        api_key: Option<Arc<ApiKey>>,
+    ) -> Result<Value, RpcError> {
         let timer = Instant::now();
 
         let request_method = request.method.clone();
-        let response = self.process_request_internal(request).await;
+        let response = self.process_request_internal(request, api_key).await;
 
         let request_method = match &response {
             Err(err) if err.code == -32_601 => "UNSUPPORTED_METHOD",
@@ -268,7 +305,11 @@ impl JsonRpcHandler {
     }
 
     /// Processes the request without updating any metrics.
-    async fn process_request_internal(&self, request: Request) -> Result<Value, RpcError> {
+    async fn process_request_internal(
+        &self,
+        request: Request,
+        api_key: Option<Arc<ApiKey>>,
+    ) -> Result<Value, RpcError> {
         let request = match self.process_adversarial_request_internal(request).await {
             Ok(response) => return response,
             Err(request) => request,
@@ -279,8 +320,7 @@ impl JsonRpcHandler {
             "block" => process_method_call(request, |params| self.block(params)).await,
             "broadcast_tx_async" => {
                 process_method_call(request, |params| async {
-                    let tx = self.send_tx_async(params).await.to_string();
-                    Result::<_, std::convert::Infallible>::Ok(tx)
+                    self.send_tx_async(params).await.map(|hash| hash.to_string())
                 })
                 .await
             }
@@ -305,7 +345,7 @@ impl JsonRpcHandler {
             }
             "query" => {
                 let params = RpcRequest::parse(request.params)?;
-                let query_response = self.query(params).await;
+                let query_response = self.query(params, api_key).await;
                 process_query_response(query_response)
             }
             "status" => process_method_call(request, |_params: ()| self.status()).await,
@@ -358,6 +398,23 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_split_storage_info" => {
                 process_method_call(request, |params| self.split_storage_info(params)).await
             }
+            "EXPERIMENTAL_fee_hint" => {
+                process_method_call(request, |params| self.fee_hint(params)).await
+            }
+            "EXPERIMENTAL_head_changes" => {
+                process_method_call(request, |params| self.head_changes(params)).await
+            }
+            "EXPERIMENTAL_account_activity" => {
+                process_method_call(request, |params| self.account_activity(params)).await
+            }
+            "EXPERIMENTAL_nonce_gaps" => {
+                process_method_call(request, |params| self.nonce_gaps(params)).await
+            }
+            // The status lists the transactions of the pool, so it's only available with
+            // the debug RPC.
+            "EXPERIMENTAL_tx_pool_status" if self.enable_debug_rpc => {
+                process_method_call(request, |params| self.tx_pool_status(params)).await
+            }
             #[cfg(feature = "sandbox")]
             "sandbox_patch_state" => {
                 process_method_call(request, |params| self.sandbox_patch_state(params)).await
@@ -397,6 +454,7 @@ impl JsonRpcHandler {
             "adv_switch_to_height" => self.adv_switch_to_height(request.params).await,
             "adv_get_saved_blocks" => self.adv_get_saved_blocks(request.params).await,
             "adv_check_store" => self.adv_check_store(request.params).await,
+            "adv_set_config" => self.adv_set_config(request.params).await,
             _ => return Err(request),
         })
     }
@@ -444,11 +502,39 @@ impl JsonRpcHandler {
         }
     }
 
+    async fn shards_manager_send<M, T, E>(&self, msg: M) -> Result<T, E>
+    where
+        ShardsManagerActor: actix::Handler<M>,
+        M: actix::Message<Result = T> + Send + 'static,
+        M::Result: Send,
+        E: RpcFrom<actix::MailboxError>,
+    {
+        match &self.shards_manager_addr {
+            Some(shards_manager_addr) => {
+                shards_manager_addr.send(msg).await.map_err(RpcFrom::rpc_from)
+            }
+            None => Err(RpcFrom::rpc_from(MailboxError::Closed)),
+        }
+    }
+
+    /// Rejects the transactions exceeding the configured limits before they reach the client.
+    fn check_transaction_limits(
+        &self,
+        tx: &SignedTransaction,
+    ) -> Result<(), near_jsonrpc_primitives::types::transactions::RpcTransactionError> {
+        self.transaction_limits.check(tx).map_err(|context| {
+            near_jsonrpc_primitives::types::transactions::RpcTransactionError::InvalidTransaction {
+                context,
+            }
+        })
+    }
+
     async fn send_tx_async(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
-    ) -> CryptoHash {
+    ) -> Result<CryptoHash, near_jsonrpc_primitives::types::transactions::RpcTransactionError> {
         let tx = request_data.signed_transaction;
+        self.check_transaction_limits(&tx)?;
         let hash = tx.get_hash();
         self.client_addr.do_send(
             ProcessTxRequest {
@@ -458,7 +544,7 @@ impl JsonRpcHandler {
             }
             .with_span_context(),
         );
-        hash
+        Ok(hash)
     }
 
     async fn tx_exists(
@@ -613,6 +699,7 @@ impl JsonRpcHandler {
         check_only: bool,
     ) -> Result<ProcessTxResponse, near_jsonrpc_primitives::types::transactions::RpcTransactionError>
     {
+        self.check_transaction_limits(&tx)?;
         let tx_hash = tx.get_hash();
         let signer_account_id = tx.transaction.signer_id.clone();
         let response = self
@@ -647,7 +734,7 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::transactions::RpcTransactionError,
     > {
         match self.send_tx(request_data.clone().signed_transaction, false).await? {
-            ProcessTxResponse::ValidTx => {
+            ProcessTxResponse::ValidTx | ProcessTxResponse::ReplacedTx => {
                 Ok(near_jsonrpc_primitives::types::transactions::RpcBroadcastTxSyncResponse {
                     transaction_hash: request_data.signed_transaction.get_hash(),
                 })
@@ -673,7 +760,7 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::transactions::RpcTransactionError,
     > {
         match self.send_tx(request_data.clone().signed_transaction, true).await? {
-            ProcessTxResponse::ValidTx => {
+            ProcessTxResponse::ValidTx | ProcessTxResponse::ReplacedTx => {
                 Ok(near_jsonrpc_primitives::types::transactions::RpcBroadcastTxSyncResponse {
                     transaction_hash: request_data.signed_transaction.get_hash(),
                 })
@@ -699,6 +786,7 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::transactions::RpcTransactionError,
     > {
         let tx = request_data.signed_transaction;
+        self.check_transaction_limits(&tx)?;
         match self
             .tx_status_fetch(
                 near_jsonrpc_primitives::types::transactions::TransactionInfo::Transaction(
@@ -721,7 +809,9 @@ impl JsonRpcHandler {
             _ => {}
         }
         match self.send_tx(tx.clone(), false).await? {
-            ProcessTxResponse::ValidTx | ProcessTxResponse::RequestRouted => {
+            ProcessTxResponse::ValidTx
+            | ProcessTxResponse::ReplacedTx
+            | ProcessTxResponse::RequestRouted => {
                 self.tx_polling(near_jsonrpc_primitives::types::transactions::TransactionInfo::Transaction(tx)).await
             }
             network_client_response=> {
@@ -803,6 +893,19 @@ impl JsonRpcHandler {
                     "/debug/api/requested_state_parts" => {
                         self.client_send(DebugStatus::RequestedStateParts).await?.rpc_into()
                     }
+                    "/debug/api/rollout_flags" => {
+                        self.client_send(DebugStatus::RolloutFlags).await?.rpc_into()
+                    }
+                    "/debug/api/chunk_completion" => {
+                        self.client_send(DebugStatus::ChunkCompletion).await?.rpc_into()
+                    }
+                    "/debug/api/block_production_dry_run" => {
+                        self.client_send(DebugStatus::BlockProductionDryRun).await?.rpc_into()
+                    }
+                    "/debug/api/chunks" => self
+                        .shards_manager_send(near_chunks::debug::GetDebugStatus::Chunks)
+                        .await?
+                        .rpc_into(),
                     "/debug/api/peer_store" => self
                         .peer_manager_send(near_network::debug::GetDebugStatus::PeerStore)
                         .await?
@@ -817,6 +920,18 @@ impl JsonRpcHandler {
                         )
                         .await?
                         .rpc_into(),
+                    "/debug/api/routed_message_trace" => self
+                        .peer_manager_send(near_network::debug::GetDebugStatus::RoutedMessageTrace)
+                        .await?
+                        .rpc_into(),
+                    "/debug/api/dialer" => self
+                        .peer_manager_send(near_network::debug::GetDebugStatus::Dialer)
+                        .await?
+                        .rpc_into(),
+                    "/debug/api/block_propagation" => self
+                        .peer_manager_send(near_network::debug::GetDebugStatus::BlockPropagation)
+                        .await?
+                        .rpc_into(),
                     _ => return Ok(None),
                 };
             return Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
@@ -845,6 +960,56 @@ impl JsonRpcHandler {
         }
     }
 
+    pub async fn debug_ping(
+        &self,
+        targets: Vec<String>,
+        timeout: near_primitives::time::Duration,
+    ) -> Result<
+        Option<near_jsonrpc_primitives::types::status::RpcDebugStatusResponse>,
+        near_jsonrpc_primitives::types::status::RpcStatusError,
+    > {
+        if self.enable_debug_rpc {
+            let view =
+                self.peer_manager_send(near_network::debug::PingPeers { targets, timeout }).await?;
+            return Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
+                status_response: near_jsonrpc_primitives::types::status::DebugStatusResponse::Ping(
+                    view,
+                ),
+            }));
+        } else {
+            return Ok(None);
+        }
+    }
+
+    pub async fn debug_route(
+        &self,
+        target: String,
+    ) -> Result<
+        Option<near_jsonrpc_primitives::types::status::RpcDebugStatusResponse>,
+        near_jsonrpc_primitives::types::status::RpcStatusError,
+    > {
+        if self.enable_debug_rpc {
+            let msg = PeerManagerMessageRequest::GetRouteInfo { target };
+            let view = match self.peer_manager_send(msg.with_span_context()).await? {
+                PeerManagerMessageResponse::GetRouteInfo(view) => view,
+                resp => {
+                    return Err(
+                        near_jsonrpc_primitives::types::status::RpcStatusError::InternalError {
+                            error_message: format!("unexpected response: {resp:?}"),
+                        },
+                    )
+                }
+            };
+            return Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
+                status_response: near_jsonrpc_primitives::types::status::DebugStatusResponse::Route(
+                    view,
+                ),
+            }));
+        } else {
+            return Ok(None);
+        }
+    }
+
     pub async fn protocol_config(
         &self,
         request_data: near_jsonrpc_primitives::types::config::RpcProtocolConfigRequest,
@@ -860,13 +1025,19 @@ impl JsonRpcHandler {
     async fn query(
         &self,
         request_data: near_jsonrpc_primitives::types::query::RpcQueryRequest,
+        api_key: Option<Arc<ApiKey>>,
     ) -> Result<
         near_jsonrpc_primitives::types::query::RpcQueryResponse,
         near_jsonrpc_primitives::types::query::RpcQueryError,
     > {
-        let query_response = self
-            .view_client_send(Query::new(request_data.block_reference, request_data.request))
-            .await?;
+        let cancellation = CancellationToken::default();
+        // The future is dropped if the HTTP client disconnects, in which case the view client
+        // gives up on the query.
+        let _cancel_on_drop = cancellation.clone().drop_guard();
+        let query = Query::new(request_data.block_reference, request_data.request)
+            .with_cancellation(cancellation)
+            .with_api_key(api_key);
+        let query_response = self.view_client_send(query).await?;
         Ok(query_response.rpc_into())
     }
 
@@ -961,6 +1132,7 @@ impl JsonRpcHandler {
             .view_client_send(GetStateChanges {
                 block_hash,
                 state_changes_request: request.state_changes_request,
+                filter: request.filter,
             })
             .await?;
 
@@ -1122,6 +1294,85 @@ impl JsonRpcHandler {
         let split_storage = self.view_client_send(GetSplitStorageInfo {}).await?;
         Ok(RpcSplitStorageInfoResponse { result: split_storage })
     }
+
+    pub async fn fee_hint(
+        &self,
+        _request_data: near_jsonrpc_primitives::types::fee_hint::RpcFeeHintRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::fee_hint::RpcFeeHintResponse,
+        near_jsonrpc_primitives::types::fee_hint::RpcFeeHintError,
+    > {
+        let fee_hint = self.view_client_send(GetFeeHint {}).await?;
+        Ok(RpcFeeHintResponse { result: fee_hint })
+    }
+
+    pub async fn head_changes(
+        &self,
+        request_data: near_jsonrpc_primitives::types::head_changes::RpcHeadChangesRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::head_changes::RpcHeadChangesResponse,
+        near_jsonrpc_primitives::types::head_changes::RpcHeadChangesError,
+    > {
+        let changes = self.client_send(GetHeadChanges { since_id: request_data.since_id }).await?;
+        Ok(RpcHeadChangesResponse { changes })
+    }
+
+    pub async fn account_activity(
+        &self,
+        request_data: near_jsonrpc_primitives::types::account_activity::RpcAccountActivityRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::account_activity::RpcAccountActivityResponse,
+        near_jsonrpc_primitives::types::account_activity::RpcAccountActivityError,
+    > {
+        let limit = request_data
+            .limit
+            .unwrap_or(DEFAULT_ACCOUNT_ACTIVITY_LIMIT)
+            .min(MAX_ACCOUNT_ACTIVITY_LIMIT);
+        let activity = self
+            .view_client_send(GetAccountActivity { account_id: request_data.account_id, limit })
+            .await?;
+        Ok(RpcAccountActivityResponse { activity })
+    }
+
+    pub async fn nonce_gaps(
+        &self,
+        request_data: near_jsonrpc_primitives::types::nonce_gaps::RpcNonceGapsRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::nonce_gaps::RpcNonceGapsResponse,
+        near_jsonrpc_primitives::types::nonce_gaps::RpcNonceGapsError,
+    > {
+        // Only the pool is read on the client, the state is read on the view client.
+        let pooled_nonces = self
+            .client_send(GetPooledNonces {
+                account_id: request_data.account_id.clone(),
+                public_key: request_data.public_key.clone(),
+            })
+            .await?;
+        let nonce_gaps = self
+            .view_client_send(GetNonceGaps {
+                account_id: request_data.account_id,
+                public_key: request_data.public_key,
+                pooled_nonces,
+            })
+            .await?;
+        Ok(RpcNonceGapsResponse { nonce_gaps })
+    }
+
+    pub async fn tx_pool_status(
+        &self,
+        request_data: near_jsonrpc_primitives::types::tx_pool::RpcTxPoolStatusRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::tx_pool::RpcTxPoolStatusResponse,
+        near_jsonrpc_primitives::types::tx_pool::RpcTxPoolStatusError,
+    > {
+        let status = self
+            .client_send(GetTxPoolStatus {
+                include_transactions: request_data.include_transactions,
+                limit: request_data.limit,
+            })
+            .await?;
+        Ok(RpcTxPoolStatusResponse { status })
+    }
 }
 
 #[cfg(feature = "sandbox")]
@@ -1301,6 +1552,23 @@ impl JsonRpcHandler {
         Ok(Value::String(String::new()))
     }
 
+    async fn adv_set_config(&self, params: Value) -> Result<Value, RpcError> {
+        let config: near_client::adversarial::AdversarialConfig =
+            crate::api::Params::parse(params)?;
+        if let Some(height) = config.switch_to_height {
+            self.adv_switch_to_height(json!([height])).await?;
+        }
+        actix::spawn(
+            self.client_addr
+                .send(
+                    near_client::NetworkAdversarialMessage::AdvSetConfig(config)
+                        .with_span_context(),
+                )
+                .map(|_| ()),
+        );
+        Ok(Value::String(String::new()))
+    }
+
     async fn adv_get_saved_blocks(&self, _params: Value) -> Result<Value, RpcError> {
         match self
             .client_addr
@@ -1334,11 +1602,34 @@ impl JsonRpcHandler {
 }
 
 fn rpc_handler(
+    req: HttpRequest,
     message: web::Json<Message>,
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    // `HttpRequest` isn't `Send`, so only the header is moved into the future.
+    let api_key_header =
+        req.headers().get("X-Api-Key").map(|key| key.to_str().ok().map(str::to_owned));
     let response = async move {
-        let message = handler.process(message.0).await?;
+        let api_key = match api_key_header {
+            None => None,
+            Some(key) => match key.and_then(|key| handler.api_keys.get(&key)) {
+                Some(api_key) => Some(api_key),
+                None => return Ok(HttpResponse::Unauthorized().body("unknown API key")),
+            },
+        };
+        if let Some(api_key) = &api_key {
+            let accepted = api_key.try_request(Instant::now());
+            metrics::RPC_API_KEY_REQUESTS
+                .with_label_values(&[
+                    api_key.name(),
+                    if accepted { "accepted" } else { "rejected" },
+                ])
+                .inc();
+            if !accepted {
+                return Ok(HttpResponse::TooManyRequests().finish());
+            }
+        }
+        let message = handler.process(message.0, api_key).await?;
         Ok(HttpResponse::Ok().json(&message))
     };
     response.boxed()
@@ -1388,6 +1679,170 @@ async fn debug_block_status_handler(
     }
 }
 
+async fn debug_route_handler(
+    path: web::Path<String>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.debug_route(path.into_inner()).await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+/// Maximal number of the targets of a single /debug/api/ping request.
+const MAX_PING_TARGETS: usize = 100;
+/// Maximal time to wait for the pongs in a /debug/api/ping request.
+const MAX_PING_TIMEOUT_MS: u64 = 60_000;
+
+fn default_ping_timeout_ms() -> u64 {
+    5_000
+}
+
+#[derive(serde::Deserialize)]
+struct DebugPingQuery {
+    /// Comma-separated PeerIds or AccountIds.
+    targets: String,
+    #[serde(default = "default_ping_timeout_ms")]
+    timeout_ms: u64,
+}
+
+/// Pings the given peers through the routing layer and reports the round-trip times,
+/// like a traceroute for the overlay network.
+async fn debug_ping_handler(
+    query: web::Query<DebugPingQuery>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let targets: Vec<String> = query
+        .targets
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    if targets.len() > MAX_PING_TARGETS {
+        return Ok(HttpResponse::BadRequest()
+            .body(format!("at most {MAX_PING_TARGETS} targets can be pinged at once")));
+    }
+    let timeout = near_primitives::time::Duration::milliseconds(
+        query.timeout_ms.min(MAX_PING_TIMEOUT_MS) as i64,
+    );
+    match handler.debug_ping(targets, timeout).await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+/// Checks the `Authorization: Bearer <token>` header of an admin request.
+/// Returns the response to send instead if the request is not authorized.
+fn check_admin_token(req: &HttpRequest, handler: &JsonRpcHandler) -> Option<HttpResponse> {
+    let want = match &handler.admin_token {
+        Some(token) => token.as_bytes(),
+        // Admin endpoints are disabled.
+        None => return Some(HttpResponse::NotFound().finish()),
+    };
+    let got = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::as_bytes)
+        .unwrap_or_default();
+    // Compare in constant time, so that the token cannot be guessed from the response latency.
+    let equal =
+        got.len() == want.len() && got.iter().zip(want).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0;
+    if equal {
+        None
+    } else {
+        Some(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Disconnects (`action` = "disconnect") or bans (`action` = "ban") a peer at runtime.
+async fn admin_peer_handler(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    if let Some(response) = check_admin_token(&req, &handler) {
+        return Ok(response);
+    }
+    let (peer_id, action) = path.into_inner();
+    let ban = match action.as_str() {
+        "disconnect" => false,
+        "ban" => true,
+        _ => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let peer_id = match peer_id.parse::<near_crypto::PublicKey>() {
+        Ok(key) => PeerId::new(key),
+        Err(err) => return Ok(HttpResponse::BadRequest().body(err.to_string())),
+    };
+    let msg = PeerManagerMessageRequest::DisconnectPeer { peer_id: peer_id.clone(), ban };
+    let response: Result<_, MailboxError> =
+        handler.peer_manager_send(msg.with_span_context()).await;
+    match response {
+        Ok(PeerManagerMessageResponse::DisconnectPeer(connected)) => {
+            info!(target: "jsonrpc", peer_id = %peer_id, ban, connected, "Admin request to disconnect peer");
+            Ok(HttpResponse::Ok().json(&json!({ "peer_id": peer_id, "connected": connected })))
+        }
+        Ok(_) | Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct AdminReconnectAllQuery {
+    /// Whether to close the TIER1 connections as well.
+    #[serde(default)]
+    tier1: bool,
+}
+
+/// Closes all the connections and immediately dials new peers, to rebuild the peer set without
+/// a restart (e.g. after fixing the firewall).
+async fn admin_reconnect_all_handler(
+    req: HttpRequest,
+    query: web::Query<AdminReconnectAllQuery>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    if let Some(response) = check_admin_token(&req, &handler) {
+        return Ok(response);
+    }
+    let tier1 = query.tier1;
+    let msg = PeerManagerMessageRequest::ReconnectAll { tier1 };
+    let response: Result<_, MailboxError> =
+        handler.peer_manager_send(msg.with_span_context()).await;
+    match response {
+        Ok(PeerManagerMessageResponse::ReconnectAll(closed)) => {
+            info!(target: "jsonrpc", tier1, closed, "Admin request to reconnect all peers");
+            Ok(HttpResponse::Ok().json(&json!({ "closed_connections": closed })))
+        }
+        Ok(_) | Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+/// Allows the node to switch to the fork containing the given block, even if it reverts more
+/// blocks than the configured `max_reorg_depth`.
+async fn admin_allow_deep_reorg_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    if let Some(response) = check_admin_token(&req, &handler) {
+        return Ok(response);
+    }
+    let block_hash = match path.parse::<CryptoHash>() {
+        Ok(block_hash) => block_hash,
+        Err(err) => return Ok(HttpResponse::BadRequest().body(err.to_string())),
+    };
+    match handler.client_addr.send(AllowDeepReorg { block_hash }.with_span_context()).await {
+        Ok(()) => {
+            info!(target: "jsonrpc", block_hash = %block_hash, "Admin request to allow a deep reorg");
+            Ok(HttpResponse::Ok().json(&json!({ "block_hash": block_hash })))
+        }
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
 fn health_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
@@ -1520,9 +1975,11 @@ async fn display_debug_html(
 pub fn start_http(
     config: RpcConfig,
     genesis_config: GenesisConfig,
+    transaction_limits: TransactionLimitsConfig,
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
+    shards_manager_addr: Option<Addr<ShardsManagerActor>>,
 ) -> Vec<(&'static str, actix_web::dev::ServerHandle)> {
     let RpcConfig {
         addr,
@@ -1532,7 +1989,10 @@ pub fn start_http(
         limits_config,
         enable_debug_rpc,
         experimental_debug_pages_src_path: debug_pages_src_path,
+        admin_token,
+        api_keys,
     } = config;
+    let api_keys = Arc::new(ApiKeys::new(&api_keys, Instant::now()));
     let prometheus_addr = prometheus_addr.filter(|it| it != &addr.to_string());
     let cors_allowed_origins_clone = cors_allowed_origins.clone();
     info!(target:"network", "Starting http server at {}", addr);
@@ -1544,10 +2004,14 @@ pub fn start_http(
                 client_addr: client_addr.clone(),
                 view_client_addr: view_client_addr.clone(),
                 peer_manager_addr: peer_manager_addr.clone(),
+                shards_manager_addr: shards_manager_addr.clone(),
                 polling_config,
                 genesis_config: genesis_config.clone(),
+                transaction_limits: transaction_limits.clone(),
                 enable_debug_rpc,
                 debug_pages_src_path: debug_pages_src_path.clone().map(Into::into),
+                admin_token: admin_token.clone(),
+                api_keys: api_keys.clone(),
             }))
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
@@ -1568,14 +2032,31 @@ pub fn start_http(
                     .route(web::get().to(tier1_network_info_handler)),
             )
             .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
+            .service(web::resource("/debug/api/ping").route(web::get().to(debug_ping_handler)))
             .service(web::resource("/debug/api/{api}").route(web::get().to(debug_handler)))
             .service(
                 web::resource("/debug/api/block_status/{starting_height}")
                     .route(web::get().to(debug_block_status_handler)),
             )
+            .service(
+                web::resource("/debug/api/route/{target}")
+                    .route(web::get().to(debug_route_handler)),
+            )
             .service(
                 web::resource("/debug/client_config").route(web::get().to(client_config_handler)),
             )
+            .service(
+                web::resource("/admin/peers/{peer_id}/{action}")
+                    .route(web::post().to(admin_peer_handler)),
+            )
+            .service(
+                web::resource("/admin/reconnect_all")
+                    .route(web::post().to(admin_reconnect_all_handler)),
+            )
+            .service(
+                web::resource("/admin/allow_deep_reorg/{block_hash}")
+                    .route(web::post().to(admin_allow_deep_reorg_handler)),
+            )
             .service(debug_html)
             .service(display_debug_html)
     })
@@ -55,3 +55,12 @@ pub static RPC_UNREACHABLE_ERROR_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static RPC_API_KEY_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter_vec(
+        "near_rpc_api_key_requests_total",
+        "Total count of HTTP RPC requests by API key, and whether they have been rejected \
+         because of exceeding the requests per second budget of the key",
+        &["api_key", "result"],
+    )
+    .unwrap()
+});
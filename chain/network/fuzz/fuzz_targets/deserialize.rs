@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use near_network::fuzzing;
+use near_network::types::Encoding;
+
+fuzz_target!(|bytes: &[u8]| {
+    fuzzing::deserialize(Encoding::Borsh, bytes);
+    fuzzing::deserialize(Encoding::Proto, bytes);
+});
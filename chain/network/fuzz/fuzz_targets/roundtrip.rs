@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use near_network::fuzzing;
+use near_network::types::Encoding;
+
+fuzz_target!(|input: (fuzzing::ArbitraryPeerMessage, Encoding, Vec<(usize, u8)>)| {
+    let (msg, enc, corruption) = input;
+    fuzzing::roundtrip(&msg, enc, &corruption);
+});
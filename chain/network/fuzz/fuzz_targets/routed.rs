@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use near_network::fuzzing;
+
+fuzz_target!(|input: (fuzzing::ArbitraryRoutedMessage, Vec<(usize, u8)>)| {
+    let (msg, corruption) = input;
+    fuzzing::routed(&msg, &corruption);
+});
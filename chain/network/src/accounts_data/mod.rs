@@ -24,13 +24,25 @@
 //!       discarding the progress
 //!     - banning a peer wouldn't help since peers are anonymous, so a single attacker can act as a
 //!       lot of peers
+//!
+//! Garbage collection:
+//! - the data of the accounts which are no longer important is dropped in `Cache::set_keys`.
+//! - an important account may still keep broadcasting the same stale data (for example if
+//!   its node has been turned off). Since every TIER1 node refreshes its AccountData every
+//!   `advertise_proxies_interval` (much shorter than an epoch), at the start of every epoch
+//!   we drop the data which hasn't been refreshed since the start of the previous epoch and
+//!   refuse to accept it again. See `Cache::set_epoch`.
+//! - the number of entries is bounded by `MAX_CACHE_SIZE` in case the set of important accounts
+//!   is unexpectedly large.
 use crate::concurrency;
 use crate::concurrency::arc_mutex::ArcMutex;
 use crate::network_protocol;
 use crate::network_protocol::{AccountData, SignedAccountData, VersionedAccountData};
+use crate::stats::metrics;
 use crate::types::AccountKeys;
 use near_crypto::PublicKey;
 use near_primitives::time;
+use near_primitives::types::EpochId;
 use near_primitives::validator_signer::ValidatorSigner;
 use rayon::iter::ParallelBridge;
 use std::collections::HashMap;
@@ -39,6 +51,9 @@ use std::sync::Arc;
 #[cfg(test)]
 mod tests;
 
+/// Maximal number of AccountData entries stored in the cache.
+const MAX_CACHE_SIZE: usize = 2000;
+
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub(crate) enum Error {
     #[error("found an invalid signature")]
@@ -77,6 +92,14 @@ pub struct CacheSnapshot {
     pub data: im::HashMap<PublicKey, Arc<SignedAccountData>>,
 
     pub local: Option<LocalData>,
+
+    /// Epoch reported by the last `Cache::set_epoch` call.
+    epoch_id: Option<EpochId>,
+    /// Time at which this node has observed the start of `epoch_id`.
+    /// None if the epoch had already been ongoing when the node has started.
+    epoch_started_at: Option<time::Utc>,
+    /// AccountData signed before this time is considered stale and is not accepted.
+    min_timestamp: Option<time::Utc>,
 }
 
 impl CacheSnapshot {
@@ -110,12 +133,18 @@ impl CacheSnapshot {
     ///   the whole storage.
     fn is_new(&self, d: &SignedAccountData) -> bool {
         self.keys.contains(&d.account_key)
+            && !self.is_stale(d)
             && match self.data.get(&d.account_key) {
                 Some(old) if (old.version, old.timestamp) >= (d.version, d.timestamp) => false,
                 _ => true,
             }
     }
 
+    /// Checks if `d` has been signed before the start of the previous epoch.
+    fn is_stale(&self, d: &SignedAccountData) -> bool {
+        self.min_timestamp.map_or(false, |min| d.timestamp < min)
+    }
+
     /// Inserts d into self.data, if
     /// * `d.account_data` is in self.keys AND
     /// * `d.version > self.data[d.account_data].version`.
@@ -132,6 +161,10 @@ impl CacheSnapshot {
         if !self.is_new(&d) {
             return None;
         }
+        if self.data.len() >= MAX_CACHE_SIZE && !self.data.contains_key(&d.account_key) {
+            metrics::ACCOUNTS_DATA_REJECTED.with_label_values(&["cache_full"]).inc();
+            return None;
+        }
         let d = match &self.local {
             Some(local) if d.account_key == local.signer.public_key() => Arc::new(
                 VersionedAccountData {
@@ -199,9 +232,16 @@ impl Cache {
             keys: im::HashSet::new(),
             data: im::HashMap::new(),
             local: None,
+            epoch_id: None,
+            epoch_started_at: None,
+            min_timestamp: None,
         }))
     }
 
+    fn update_metrics(&self) {
+        metrics::ACCOUNTS_DATA_CACHE_SIZE.set(self.0.load().data.len() as i64);
+    }
+
     /// Updates the set of important accounts and their public keys.
     /// The AccountData which is no longer important is dropped.
     /// Returns true iff the set of accounts actually changed.
@@ -210,7 +250,8 @@ impl Cache {
     ///   so a call to set_local afterwards is required to do that. For now it is fine because
     ///   the Cache owner is expected to call set_local periodically anyway.
     pub fn set_keys(&self, keys_by_id: Arc<AccountKeys>) -> bool {
-        self.0
+        let changed = self
+            .0
             .try_update(|mut inner| {
                 // Skip further processing if the key set didn't change.
                 // NOTE: if T implements Eq, then Arc<T> short circuits equality for x == x.
@@ -222,7 +263,41 @@ impl Cache {
                 inner.data.retain(|k, _| inner.keys.contains(k));
                 Ok(((), inner))
             })
-            .is_ok()
+            .is_ok();
+        self.update_metrics();
+        changed
+    }
+
+    /// Updates the current epoch. When a new epoch starts, the AccountData signed before the
+    /// start of the previous epoch (as observed by this node) is dropped, except for the data
+    /// of this node. Such data won't be accepted anymore either.
+    /// Returns the number of dropped entries.
+    pub fn set_epoch(&self, clock: &time::Clock, epoch_id: &EpochId) -> usize {
+        let dropped = self.0.update(|mut inner| {
+            if inner.epoch_id.as_ref() == Some(epoch_id) {
+                return (0, inner);
+            }
+            // On the first call we don't know when the current epoch has started.
+            let first = inner.epoch_id.is_none();
+            let mut dropped = 0;
+            if let Some(min_timestamp) = inner.epoch_started_at {
+                inner.min_timestamp = Some(min_timestamp);
+                let local_key = inner.local.as_ref().map(|l| l.signer.public_key());
+                let before = inner.data.len();
+                inner
+                    .data
+                    .retain(|k, d| d.timestamp >= min_timestamp || Some(k) == local_key.as_ref());
+                dropped = before - inner.data.len();
+            }
+            inner.epoch_id = Some(epoch_id.clone());
+            inner.epoch_started_at = if first { None } else { Some(clock.now_utc()) };
+            (dropped, inner)
+        });
+        if dropped > 0 {
+            tracing::debug!(target: "network", dropped, "dropped stale accounts data");
+        }
+        self.update_metrics();
+        dropped
     }
 
     /// Selects new data and verifies the signatures.
@@ -249,6 +324,12 @@ impl Cache {
             if new_data.contains_key(&d.account_key) {
                 return (vec![], Some(Error::SingleAccountMultipleData));
             }
+            // Stale data may be still broadcasted by the peers which haven't garbage collected
+            // it yet.
+            if inner.is_stale(&d) {
+                metrics::ACCOUNTS_DATA_REJECTED.with_label_values(&["stale"]).inc();
+                continue;
+            }
             // It is fine to broadcast data we already know about.
             // It is fine to broadcast account data that we don't care about.
             if inner.is_new(&d) {
@@ -278,10 +359,12 @@ impl Cache {
         clock: &time::Clock,
         local: LocalData,
     ) -> Option<Arc<SignedAccountData>> {
-        self.0.update(|mut inner| {
+        let data = self.0.update(|mut inner| {
             let data = inner.set_local(clock, local);
             (data, inner)
-        })
+        });
+        self.update_metrics();
+        data
     }
 
     /// Verifies the signatures and inserts verified data to the cache.
@@ -300,6 +383,7 @@ impl Cache {
             let inserted = data.into_iter().filter_map(|d| inner.try_insert(clock, d)).collect();
             (inserted, inner)
         });
+        self.update_metrics();
         // Return the inserted data.
         (inserted, err)
     }
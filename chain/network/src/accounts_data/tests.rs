@@ -3,7 +3,9 @@ use crate::network_protocol::testonly as data;
 use crate::network_protocol::SignedAccountData;
 use crate::testonly::{assert_is_superset, make_rng, AsSet as _, Rng};
 use near_o11y::testonly::init_test_logger;
+use near_primitives::hash::CryptoHash;
 use near_primitives::time;
+use near_primitives::types::EpochId;
 use near_primitives::validator_signer::InMemoryValidatorSigner;
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
@@ -281,3 +283,46 @@ async fn set_local() {
     assert_eq!(None, cache.set_local(&clock.clock(), local));
     assert_eq!([&a1, &got].as_set(), cache.load().data.values().collect());
 }
+
+#[tokio::test]
+async fn epoch_gc() {
+    init_test_logger();
+    let mut rng = make_rng(2384927349);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let epoch = |i: u64| EpochId(CryptoHash::hash_borsh(i));
+
+    let signers: Vec<_> = make_signers(rng, 3);
+    let e = Arc::new(data::make_account_keys(&signers));
+    let cache = Arc::new(Cache::new());
+    cache.set_keys(e);
+    // The start of the first epoch is unknown, so nothing can be dropped.
+    assert_eq!(0, cache.set_epoch(&clock.clock(), &epoch(0)));
+    let a0 = Arc::new(make_account_data(rng, &clock.clock(), 1, &signers[0]));
+    let a1 = Arc::new(make_account_data(rng, &clock.clock(), 1, &signers[1]));
+    unwrap(&cache.clone().insert(&clock.clock(), vec![a0.clone(), a1.clone()]).await);
+    clock.advance(time::Duration::hours(1));
+    assert_eq!(0, cache.set_epoch(&clock.clock(), &epoch(1)));
+    assert_eq!([&a0, &a1].as_set(), cache.load().data.values().collect());
+
+    // Only a1 gets refreshed during epoch 1. The local data is never dropped.
+    clock.advance(time::Duration::hours(1));
+    let a1 = Arc::new(make_account_data(rng, &clock.clock(), 2, &signers[1]));
+    unwrap(&cache.clone().insert(&clock.clock(), vec![a1.clone()]).await);
+    let local = LocalData {
+        signer: Arc::new(signers[2].clone()),
+        data: Arc::new(make_account_data(rng, &clock.clock(), 1, &signers[2]).data.clone()),
+    };
+    let a2 = cache.set_local(&clock.clock(), local).unwrap();
+    // Repeated call for the same epoch is a noop.
+    assert_eq!(0, cache.set_epoch(&clock.clock(), &epoch(1)));
+    assert_eq!([&a0, &a1, &a2].as_set(), cache.load().data.values().collect());
+
+    // a0 hasn't been refreshed since the start of epoch 1, so it is dropped once epoch 2 starts.
+    clock.advance(time::Duration::hours(1));
+    assert_eq!(1, cache.set_epoch(&clock.clock(), &epoch(2)));
+    assert_eq!([&a1, &a2].as_set(), cache.load().data.values().collect());
+    // Stale data is not accepted again.
+    assert!(unwrap(&cache.clone().insert(&clock.clock(), vec![a0.clone()]).await).is_empty());
+    assert_eq!([&a1, &a2].as_set(), cache.load().data.values().collect());
+}
@@ -1,6 +1,14 @@
 use std::collections::HashSet;
 use std::net;
 
+#[derive(thiserror::Error, Debug)]
+pub enum ParseEntryError {
+    #[error(transparent)]
+    Addr(#[from] net::AddrParseError),
+    #[error("invalid prefix length {0:?}")]
+    PrefixLen(String),
+}
+
 /// Only IPv6 addresses are stored.  IPv4 addresses are mapped to IPv6 before being added.
 ///
 /// Without the mapping, we could blacklist an IPv4 and still interact with that address if
@@ -11,31 +19,68 @@ use std::net;
 pub enum Entry {
     Ip(net::Ipv6Addr),
     IpPort(net::Ipv6Addr, u16),
+    /// All the addresses with the given prefix, i.e. a CIDR range.
+    /// The bits of the address beyond the prefix length are always zero.
+    Cidr(net::Ipv6Addr, u8),
+}
+
+fn to_ipv6(ip: net::IpAddr) -> net::Ipv6Addr {
+    match ip {
+        net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        net::IpAddr::V6(ip) => ip,
+    }
+}
+
+fn prefix_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
 }
 
 impl Entry {
     pub fn from_ip(ip: net::IpAddr) -> Entry {
-        Entry::Ip(match ip {
-            net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
-            net::IpAddr::V6(ip) => ip,
-        })
+        Entry::Ip(to_ipv6(ip))
     }
 
     pub fn from_addr(addr: net::SocketAddr) -> Entry {
-        Entry::IpPort(
-            match addr.ip() {
-                net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
-                net::IpAddr::V6(ip) => ip,
-            },
-            addr.port(),
-        )
+        Entry::IpPort(to_ipv6(addr.ip()), addr.port())
+    }
+
+    /// Range of the addresses starting with the first `prefix_len` bits of `ip`.
+    /// For IPv4 addresses the prefix length is in IPv4 bits, i.e. at most 32.
+    pub fn from_cidr(ip: net::IpAddr, prefix_len: u8) -> Entry {
+        let prefix_len = match ip {
+            net::IpAddr::V4(_) => 96 + prefix_len.min(32),
+            net::IpAddr::V6(_) => prefix_len.min(128),
+        };
+        let ip = u128::from(to_ipv6(ip)) & prefix_mask(prefix_len);
+        Entry::Cidr(ip.into(), prefix_len)
+    }
+
+    /// Returns whether the entry covers all the ports of `ip`.
+    fn contains_ip(&self, ip: net::Ipv6Addr) -> bool {
+        match *self {
+            Entry::Ip(entry_ip) => entry_ip == ip,
+            Entry::IpPort(..) => false,
+            Entry::Cidr(prefix, prefix_len) => {
+                u128::from(ip) & prefix_mask(prefix_len) == u128::from(prefix)
+            }
+        }
     }
 }
 
 impl std::str::FromStr for Entry {
-    type Err = std::net::AddrParseError;
+    type Err = ParseEntryError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((ip, prefix_len)) = s.split_once('/') {
+            let ip = ip.parse::<net::IpAddr>()?;
+            let max_prefix_len = if ip.is_ipv4() { 32 } else { 128 };
+            return match prefix_len.parse::<u8>() {
+                Ok(prefix_len) if prefix_len <= max_prefix_len => {
+                    Ok(Entry::from_cidr(ip, prefix_len))
+                }
+                _ => Err(ParseEntryError::PrefixLen(prefix_len.to_string())),
+            };
+        }
         match s.parse::<std::net::IpAddr>() {
             Ok(ip) => Ok(Entry::from_ip(ip)),
             Err(_) => Ok(Entry::from_addr(s.parse::<net::SocketAddr>()?)),
@@ -44,7 +89,8 @@ impl std::str::FromStr for Entry {
 }
 
 /// A blacklist for socket addresses.  Supports adding individual IP:port tuples
-/// to the blacklist or entire IPs.
+/// to the blacklist, entire IPs or CIDR ranges.
+/// The same type is used for the allowlist, see `peer_store::Config::allowlist`.
 #[derive(Debug, Default, Clone)]
 pub struct Blacklist(HashSet<Entry>);
 
@@ -59,7 +105,25 @@ impl FromIterator<Entry> for Blacklist {
 impl Blacklist {
     /// Returns whether given address is on the blacklist.
     pub fn contains(&self, addr: net::SocketAddr) -> bool {
-        self.0.contains(&Entry::from_ip(addr.ip())) || self.0.contains(&Entry::from_addr(addr))
+        self.contains_ip(addr.ip()) || self.0.contains(&Entry::from_addr(addr))
+    }
+
+    /// Returns whether all the ports of the given IP are on the blacklist.
+    /// Used for the inbound connections, whose listening port is not known yet.
+    pub fn contains_ip(&self, ip: net::IpAddr) -> bool {
+        let ip = to_ipv6(ip);
+        self.0.contains(&Entry::Ip(ip)) || self.cidrs().any(|e| e.contains_ip(ip))
+    }
+
+    /// Returns whether any port of the given IP is on the blacklist.
+    pub fn contains_any_port(&self, ip: net::IpAddr) -> bool {
+        let ip = to_ipv6(ip);
+        self.contains_ip(ip.into())
+            || self.0.iter().any(|e| matches!(e, Entry::IpPort(entry_ip, _) if *entry_ip == ip))
+    }
+
+    fn cidrs(&self) -> impl Iterator<Item = &Entry> {
+        self.0.iter().filter(|e| matches!(e, Entry::Cidr(..)))
     }
 }
 
@@ -78,7 +142,9 @@ mod test {
 
         assert_eq!(None, parse("foo"));
         assert_eq!(None, parse("192.0.2.*"));
-        assert_eq!(None, parse("192.0.2.0/24"));
+        assert_eq!(None, parse("192.0.2.0/33"));
+        assert_eq!(None, parse("192.0.2.0/"));
+        assert_eq!(None, parse("2001:db8::/129"));
         assert_eq!(None, parse("192.0.2.4.5"));
         assert_eq!(None, parse("192.0.2.4:424242"));
 
@@ -94,6 +160,10 @@ mod test {
             Entry::from_addr(net::SocketAddr::new(LO4, 42)),
             parse("[::ffff:127.0.0.1]:42").unwrap()
         );
+
+        assert_eq!(parse("192.0.2.0/24").unwrap(), parse("192.0.2.77/24").unwrap());
+        assert_eq!(parse("::ffff:192.0.2.0/120").unwrap(), parse("192.0.2.0/24").unwrap());
+        assert_eq!(Entry::Cidr("2001:db8::".parse().unwrap(), 32), parse("2001:db8::/32").unwrap());
     }
 
     #[test]
@@ -123,5 +193,32 @@ mod test {
         assert!(blacklist.contains(SocketAddr::new(mapped_lo4, 8080)));
         assert!(blacklist.contains(SocketAddr::new(mapped_ip, 42)));
         assert!(!blacklist.contains(SocketAddr::new(mapped_ip, 8080)));
+
+        assert!(blacklist.contains_ip(LO4));
+        assert!(!blacklist.contains_ip(ip));
+        assert!(blacklist.contains_any_port(ip));
+        assert!(blacklist.contains_any_port(mapped_ip));
+        assert!(!blacklist.contains_any_port(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 5))));
+    }
+
+    #[test]
+    fn test_blacklist_cidr() {
+        use std::net::*;
+
+        let blacklist: Blacklist =
+            ["192.0.2.0/24", "2001:db8::/32"].iter().map(|e| e.parse().unwrap()).collect();
+
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert!(blacklist.contains(SocketAddr::new(v4(192, 0, 2, 0), 42)));
+        assert!(blacklist.contains(SocketAddr::new(v4(192, 0, 2, 255), 42)));
+        assert!(!blacklist.contains(SocketAddr::new(v4(192, 0, 3, 0), 42)));
+        assert!(blacklist.contains_ip("::ffff:192.0.2.4".parse().unwrap()));
+        assert!(blacklist.contains_ip("2001:db8:ffff::1".parse().unwrap()));
+        assert!(!blacklist.contains_ip("2001:db9::1".parse().unwrap()));
+        assert!(!blacklist.contains_ip(LO4));
+
+        let everything: Blacklist = ["0.0.0.0/0"].iter().map(|e| e.parse().unwrap()).collect();
+        assert!(everything.contains_ip(v4(203, 0, 113, 1)));
+        assert!(!everything.contains_ip(LO6));
     }
 }
@@ -2,14 +2,159 @@ use crate::network_protocol::StateResponseInfo;
 
 use crate::types::{NetworkInfo, ReasonForBan};
 
+use near_async::messaging::AsyncSender;
 use near_primitives::block::{Approval, Block, BlockHeader};
 use near_primitives::challenge::Challenge;
+use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
 
+// Messages exchanged between the network and the client, one request (and response) type per
+// flow. Adding a new flow or a new variant of a response is backward compatible, so that
+// client and network can be changed (and tested against mocks) independently.
+
+/// Transaction status query
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Option<Box<FinalExecutionOutcomeView>>")]
+pub struct TxStatusRequest {
+    pub tx_hash: CryptoHash,
+    pub signer_account_id: AccountId,
+}
+
+/// Transaction status response
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct TxStatusResponse(pub Box<FinalExecutionOutcomeView>);
+
+/// Request a block.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Option<Box<Block>>")]
+pub struct BlockRequest(pub CryptoHash);
+
+/// Block response.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct BlockResponse {
+    pub block: Block,
+    pub peer_id: PeerId,
+    pub was_requested: bool,
+}
+
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct BlockApproval(pub Approval, pub PeerId);
+
+/// Request headers.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Option<Vec<BlockHeader>>")]
+pub struct BlockHeadersRequest(pub Vec<CryptoHash>);
+
+/// Headers response.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Result<(),ReasonForBan>")]
+pub struct BlockHeadersResponse(pub Vec<BlockHeader>, pub PeerId);
+
+/// State request header.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Option<StateResponse>")]
+pub struct StateRequestHeader {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+}
+
+/// State request part.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Option<StateResponse>")]
+pub struct StateRequestPart {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    pub part_id: u64,
+}
+
+/// Response to state request.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct StateResponse(pub Box<StateResponseInfo>);
+
+/// Account announcements that needs to be validated before being processed.
+/// They are paired with last epoch id known to this announcement, in order to accept only
+/// newer announcements.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Result<Vec<AnnounceAccount>,ReasonForBan>")]
+pub struct AnnounceAccountRequest(pub Vec<(AnnounceAccount, Option<EpochId>)>);
+
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct SetNetworkInfo(pub NetworkInfo);
+
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct RecvChallenge(pub Challenge);
+
+#[derive(actix::Message, Debug)]
+#[rtype(result = "ProcessTxResponse")]
+pub struct ProcessTxRequest {
+    pub transaction: SignedTransaction,
+    pub is_forwarded: bool,
+    pub check_only: bool,
+}
+
+#[derive(actix::MessageResponse, Debug, PartialEq, Eq)]
+pub enum ProcessTxResponse {
+    /// No response.
+    NoResponse,
+    /// Valid transaction inserted into mempool as response to Transaction.
+    ValidTx,
+    /// Valid transaction inserted into mempool in place of the pooled transactions with the
+    /// same signer, access key and nonce, which had less gas attached.
+    ReplacedTx,
+    /// Invalid transaction inserted into mempool as response to Transaction.
+    InvalidTx(InvalidTxError),
+    /// The request is routed to other shards
+    RequestRouted,
+    /// Valid transaction dropped, because the transaction pool is full.
+    NoSpaceLeft,
+    /// The node being queried does not track the shard needed and therefore cannot provide userful
+    /// response.
+    DoesNotTrackShard,
+}
+
+/// Sender of a message to the client, which fails if the message couldn't be delivered
+/// (for example because the client actor has been stopped).
+pub type ClientSender<M> = AsyncSender<M, Result<<M as actix::Message>::Result, ()>>;
+
+/// Senders of all the messages which the network sends to the client, one per flow.
+/// Unlike a single enum of messages, it allows client and network to be implemented (and
+/// mocked) independently: every flow can be served by a different component.
+#[derive(Clone)]
+pub struct ClientSenderForNetwork {
+    pub tx_status_request: ClientSender<TxStatusRequest>,
+    pub tx_status_response: ClientSender<TxStatusResponse>,
+    pub state_request_header: ClientSender<StateRequestHeader>,
+    pub state_request_part: ClientSender<StateRequestPart>,
+    pub state_response: ClientSender<StateResponse>,
+    pub block_approval: ClientSender<BlockApproval>,
+    pub transaction: ClientSender<ProcessTxRequest>,
+    pub block_request: ClientSender<BlockRequest>,
+    pub block_headers_request: ClientSender<BlockHeadersRequest>,
+    pub block: ClientSender<BlockResponse>,
+    pub block_headers: ClientSender<BlockHeadersResponse>,
+    pub challenge: ClientSender<RecvChallenge>,
+    pub network_info: ClientSender<SetNetworkInfo>,
+    pub announce_account: ClientSender<AnnounceAccountRequest>,
+}
+
+/// Returns the response, or `default` if the message couldn't be delivered to the client.
+fn response_or<T>(response: Result<T, ()>, default: T) -> T {
+    response.unwrap_or_else(|()| {
+        tracing::error!(target: "network", "failed to deliver a message to the client");
+        default
+    })
+}
+
 /// A strongly typed asynchronous API for the Client logic.
 /// It abstracts away the fact that client is implemented using actix
 /// actors.
@@ -130,3 +275,96 @@ impl Client for Noop {
         Ok(vec![])
     }
 }
+
+#[async_trait::async_trait]
+impl Client for ClientSenderForNetwork {
+    async fn tx_status_request(
+        &self,
+        account_id: AccountId,
+        tx_hash: CryptoHash,
+    ) -> Option<Box<FinalExecutionOutcomeView>> {
+        let msg = TxStatusRequest { tx_hash, signer_account_id: account_id };
+        response_or(self.tx_status_request.send_async(msg).await, None)
+    }
+
+    async fn tx_status_response(&self, tx_result: FinalExecutionOutcomeView) {
+        let msg = TxStatusResponse(Box::new(tx_result));
+        response_or(self.tx_status_response.send_async(msg).await, ())
+    }
+
+    async fn state_request_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        let msg = StateRequestHeader { shard_id, sync_hash };
+        Ok(response_or(self.state_request_header.send_async(msg).await, None).map(|r| *r.0))
+    }
+
+    async fn state_request_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        part_id: u64,
+    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        let msg = StateRequestPart { shard_id, sync_hash, part_id };
+        Ok(response_or(self.state_request_part.send_async(msg).await, None).map(|r| *r.0))
+    }
+
+    async fn state_response(&self, info: StateResponseInfo) {
+        let msg = StateResponse(Box::new(info));
+        response_or(self.state_response.send_async(msg).await, ())
+    }
+
+    async fn block_approval(&self, approval: Approval, peer_id: PeerId) {
+        response_or(self.block_approval.send_async(BlockApproval(approval, peer_id)).await, ())
+    }
+
+    async fn transaction(&self, transaction: SignedTransaction, is_forwarded: bool) {
+        let msg = ProcessTxRequest { transaction, is_forwarded, check_only: false };
+        let response =
+            response_or(self.transaction.send_async(msg).await, ProcessTxResponse::NoResponse);
+        if let ProcessTxResponse::InvalidTx(err) = response {
+            tracing::warn!(target: "network", ?err, "Received invalid tx");
+            // TODO: count as malicious behavior?
+        }
+    }
+
+    async fn block_request(&self, hash: CryptoHash) -> Option<Box<Block>> {
+        response_or(self.block_request.send_async(BlockRequest(hash)).await, None)
+    }
+
+    async fn block_headers_request(&self, hashes: Vec<CryptoHash>) -> Option<Vec<BlockHeader>> {
+        response_or(self.block_headers_request.send_async(BlockHeadersRequest(hashes)).await, None)
+    }
+
+    async fn block(&self, block: Block, peer_id: PeerId, was_requested: bool) {
+        let msg = BlockResponse { block, peer_id, was_requested };
+        response_or(self.block.send_async(msg).await, ())
+    }
+
+    async fn block_headers(
+        &self,
+        headers: Vec<BlockHeader>,
+        peer_id: PeerId,
+    ) -> Result<(), ReasonForBan> {
+        let msg = BlockHeadersResponse(headers, peer_id);
+        response_or(self.block_headers.send_async(msg).await, Ok(()))
+    }
+
+    async fn challenge(&self, challenge: Challenge) {
+        response_or(self.challenge.send_async(RecvChallenge(challenge)).await, ())
+    }
+
+    async fn network_info(&self, info: NetworkInfo) {
+        response_or(self.network_info.send_async(SetNetworkInfo(info)).await, ())
+    }
+
+    async fn announce_account(
+        &self,
+        accounts: Vec<(AnnounceAccount, Option<EpochId>)>,
+    ) -> Result<Vec<AnnounceAccount>, ReasonForBan> {
+        let msg = AnnounceAccountRequest(accounts);
+        response_or(self.announce_account.send_async(msg).await, Ok(vec![]))
+    }
+}
@@ -0,0 +1,70 @@
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// Fixed-size pool of actix arbiters (event loop threads), on which many actors can be
+/// scheduled, instead of starting a new arbiter per actor. Every actor is assigned to the
+/// arbiter with the fewest actors at the moment.
+/// A pool of size 0 starts a dedicated arbiter per actor.
+pub(crate) struct ArbiterPool {
+    size: usize,
+    /// Arbiters are started lazily, since an actix::System is required to start them.
+    /// Strong count of the Arc - 1 is the number of actors currently assigned to the arbiter.
+    arbiters: OnceCell<Vec<(actix::ArbiterHandle, Arc<()>)>>,
+}
+
+/// Arbiter assigned to an actor.
+pub(crate) enum Arbiter {
+    Dedicated(actix::ArbiterHandle),
+    Pooled { handle: actix::ArbiterHandle, _assignment: Arc<()> },
+}
+
+impl Arbiter {
+    pub fn handle(&self) -> &actix::ArbiterHandle {
+        match self {
+            Self::Dedicated(handle) => handle,
+            Self::Pooled { handle, .. } => handle,
+        }
+    }
+
+    /// Releases the arbiter once the actor has stopped.
+    /// A dedicated arbiter is stopped, a pooled one gets one actor less to run.
+    pub fn release(self) {
+        if let Self::Dedicated(handle) = self {
+            handle.stop();
+        }
+    }
+}
+
+impl ArbiterPool {
+    pub fn new(size: usize) -> Self {
+        Self { size, arbiters: OnceCell::new() }
+    }
+
+    /// Chooses an arbiter to run a new actor on.
+    pub fn assign(&self) -> Arbiter {
+        if self.size == 0 {
+            return Arbiter::Dedicated(actix::Arbiter::new().handle());
+        }
+        let arbiters = self.arbiters.get_or_init(|| {
+            (0..self.size).map(|_| (actix::Arbiter::new().handle(), Arc::new(()))).collect()
+        });
+        let (handle, load) =
+            arbiters.iter().min_by_key(|(_, load)| Arc::strong_count(load)).unwrap();
+        Arbiter::Pooled { handle: handle.clone(), _assignment: load.clone() }
+    }
+
+    /// Number of actors currently assigned to each of the pooled arbiters.
+    #[cfg(test)]
+    pub fn load(&self) -> Vec<usize> {
+        self.arbiters.get().map_or(vec![], |arbiters| {
+            arbiters.iter().map(|(_, load)| Arc::strong_count(load) - 1).collect()
+        })
+    }
+
+    /// Stops all the pooled arbiters, together with the actors running on them.
+    pub fn stop(&self) {
+        for (handle, _) in self.arbiters.get().into_iter().flatten() {
+            handle.stop();
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod arbiter_pool;
 pub mod arc_mutex;
 mod asyncfn;
 pub mod atomic_cell;
@@ -1,3 +1,5 @@
+use near_primitives::time;
+
 /// Config of a rate limiter algorithm, which behaves like a semaphore
 /// - with maximal capacity `burst`
 /// - with a new ticket added automatically every 1/qps seconds (qps stands for "queries per
@@ -6,7 +8,7 @@
 /// letting through requests at frequency `qps`.
 /// In case a number of requests come after a period of inactivity, semaphore will immediately
 /// let through up to `burst` requests, before going into the previous mode.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Limit {
     pub burst: u64,
     pub qps: f64,
@@ -25,3 +27,59 @@ impl Limit {
         Ok(())
     }
 }
+
+/// Token bucket admitting tokens at the rate specified by a Limit.
+/// The Limit is passed to every call rather than stored, so that it can be changed at any time.
+pub(crate) struct Bucket {
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl Bucket {
+    /// Constructs a full bucket.
+    pub fn new(now: time::Instant, limit: &Limit) -> Self {
+        Self { tokens: limit.burst as f64, updated: now }
+    }
+
+    /// Takes `n` tokens if available. Otherwise returns how long to wait until they are.
+    /// A request for more than `limit.burst` tokens is admitted once the bucket is full,
+    /// leaving the bucket in debt, so that large requests are delayed rather than rejected.
+    pub fn try_acquire(
+        &mut self,
+        now: time::Instant,
+        limit: &Limit,
+        n: u64,
+    ) -> Result<(), time::Duration> {
+        let elapsed = (now - self.updated).as_seconds_f64().max(0.);
+        self.tokens = (self.tokens + elapsed * limit.qps).min(limit.burst as f64);
+        self.updated = now;
+        let want = n.min(limit.burst) as f64;
+        if self.tokens < want {
+            return Err(time::Duration::seconds_f64((want - self.tokens) / limit.qps));
+        }
+        self.tokens -= n as f64;
+        Ok(())
+    }
+
+    /// Whether the bucket would be full at `now`.
+    pub fn is_full(&self, now: time::Instant, limit: &Limit) -> bool {
+        let elapsed = (now - self.updated).as_seconds_f64().max(0.);
+        self.tokens + elapsed * limit.qps >= limit.burst as f64
+    }
+
+    /// Waits until `n` tokens are available and takes them.
+    /// `limit` is reevaluated after every wait. Returns whether it had to wait at all.
+    pub async fn acquire(
+        &mut self,
+        clock: &time::Clock,
+        limit: impl Fn() -> Limit,
+        n: u64,
+    ) -> bool {
+        let mut waited = false;
+        while let Err(wait) = self.try_acquire(clock.now(), &limit(), n) {
+            clock.sleep(wait).await;
+            waited = true;
+        }
+        waited
+    }
+}
@@ -1,6 +1,8 @@
+use crate::concurrency::arbiter_pool::ArbiterPool;
 use crate::concurrency::arc_mutex::ArcMutex;
 use crate::concurrency::demux;
 use crate::concurrency::rate;
+use near_primitives::time;
 
 #[tokio::test]
 async fn test_demux() {
@@ -23,6 +25,48 @@ async fn test_demux() {
     }
 }
 
+#[test]
+fn test_bucket() {
+    let clock = time::FakeClock::default();
+    let limit = rate::Limit { qps: 10., burst: 100 };
+    let mut bucket = rate::Bucket::new(clock.now(), &limit);
+    assert_eq!(Ok(()), bucket.try_acquire(clock.now(), &limit, 60));
+    assert_eq!(Err(time::Duration::seconds(2)), bucket.try_acquire(clock.now(), &limit, 60));
+    clock.advance(time::Duration::seconds(2));
+    assert_eq!(Ok(()), bucket.try_acquire(clock.now(), &limit, 60));
+    // Requests larger than the burst wait only until the bucket is full.
+    assert_eq!(Err(time::Duration::seconds(10)), bucket.try_acquire(clock.now(), &limit, 150));
+    clock.advance(time::Duration::seconds(10));
+    assert_eq!(Ok(()), bucket.try_acquire(clock.now(), &limit, 150));
+    // The debt has to be paid off.
+    assert_eq!(Err(time::Duration::seconds(6)), bucket.try_acquire(clock.now(), &limit, 10));
+    // Changing the limit takes effect immediately.
+    let limit = rate::Limit { qps: 20., burst: 100 };
+    assert_eq!(Err(time::Duration::seconds(3)), bucket.try_acquire(clock.now(), &limit, 10));
+}
+
+#[test]
+fn arbiter_pool_balances_load() {
+    let system = actix::System::new();
+    system.block_on(async {
+        let pool = ArbiterPool::new(2);
+        let a0 = pool.assign();
+        let a1 = pool.assign();
+        let a2 = pool.assign();
+        assert_eq!(vec![2, 1], pool.load());
+        a0.release();
+        a1.release();
+        assert_eq!(vec![1, 0], pool.load());
+        // The least loaded arbiter is chosen.
+        let a3 = pool.assign();
+        assert_eq!(vec![1, 1], pool.load());
+        a2.release();
+        a3.release();
+        assert_eq!(vec![0, 0], pool.load());
+        pool.stop();
+    });
+}
+
 #[test]
 fn demux_runtime_dropped_before_call() {
     let r1 = tokio::runtime::Runtime::new().unwrap();
@@ -2,7 +2,9 @@ use crate::blacklist;
 use crate::concurrency::rate;
 use crate::network_protocol::PeerAddr;
 use crate::network_protocol::PeerInfo;
-use crate::peer_manager::peer_manager_actor::Event;
+use crate::network_protocol::SignedKeyTransition;
+use crate::peer::NETWORK_MESSAGE_MAX_SIZE_BYTES;
+use crate::peer_manager::peer_manager_actor::{Event, EventKind};
 use crate::peer_manager::peer_store;
 use crate::sink::Sink;
 use crate::stun;
@@ -15,7 +17,9 @@ use near_primitives::test_utils::create_test_signer;
 use near_primitives::time;
 use near_primitives::types::AccountId;
 use near_primitives::validator_signer::ValidatorSigner;
-use std::collections::HashSet;
+use near_primitives::version::ProtocolVersion;
+use std::collections::{HashMap, HashSet};
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::Arc;
 
 /// How much height horizon to give to consider peer up to date.
@@ -38,7 +42,8 @@ pub const PEERS_RESPONSE_MAX_PEERS: u32 = 512;
 ///
 /// Once other TIER1 nodes learn the list of proxies, they will maintain a connection to a random
 /// proxy on this list. This way a message from any TIER1 node to this node will require at most 2
-/// hops.
+/// hops. A proxy relays only the messages addressed to the TIER1 validators connected to it,
+/// so a validator without a public IP can still be reached over TIER1 through its proxies.
 ///
 /// neard supports 2 modes for configuring proxy addresses:
 /// * [recommended] `Static` list of proxies (public SocketAddr + PeerId), supports up to 10 proxies.
@@ -86,18 +91,165 @@ pub struct Tier1 {
     /// - a node will try to start outbound TIER1 connections iff `enable_outbound` is true.
     pub enable_inbound: bool,
     pub enable_outbound: bool,
+    /// Whether the chunk part forwards are sent over TIER1, and the chunk parts and forwards
+    /// sent over TIER1 are no longer duplicated over TIER2. Receiving the forwards over TIER1
+    /// is always enabled, but the nodes running an older version close the TIER1 connections
+    /// they arrive on, so sending them is enabled only once the network has been upgraded.
+    pub send_chunk_parts: bool,
+    /// Whether the forwarded transactions are sent over TIER1, in addition to TIER2. Like the
+    /// chunk part forwards, they are accepted over TIER1, but not sent there by default, as the
+    /// nodes running an older version close the TIER1 connections they arrive on.
+    pub send_forward_tx: bool,
+}
+
+/// Config of the outbox of routed messages to unreachable validators.
+/// See near_network::peer_manager::outbox for details.
+#[derive(Clone, Debug)]
+pub struct RoutedMessageOutbox {
+    /// Messages older than ttl are dropped from the outbox.
+    pub ttl: time::Duration,
+    /// Maximal number of messages stored per target account.
+    /// The oldest messages are evicted first.
+    pub max_messages_per_account: usize,
+    /// Maximal number of target accounts with pending messages.
+    /// Messages to new accounts are dropped once the limit is reached.
+    pub max_accounts: usize,
+}
+
+/// Limit on the expensive requests (BlockRequest, BlockHeadersRequest, StateRequestHeader,
+/// StateRequestPart) received over a single connection.
+/// See near_network::peer::expensive_requests for details.
+#[derive(Clone, Debug)]
+pub struct ExpensiveRequestsLimit {
+    /// Maximal number of expensive requests from a peer processed concurrently.
+    /// Requests over the limit are dropped.
+    pub max_in_flight: usize,
+    /// A peer which sends more than `max_rejected` requests over the limit within
+    /// `rejected_window` gets banned.
+    pub max_rejected: usize,
+    pub rejected_window: time::Duration,
+}
+
+/// Limits on the inbound connections from a single IP address.
+/// See near_network::peer_manager::inbound_ip_limiter for details.
+#[derive(Clone, Debug)]
+pub struct InboundIpLimit {
+    /// Maximal number of connections from an IP open at the same time,
+    /// including the ones which haven't completed the handshake yet.
+    pub max_connections: usize,
+    /// Maximal rate at which the new connections from an IP are accepted.
+    pub connections_rate_limit: rate::Limit,
+    /// For how long the new connections from an IP are dropped after it exceeded the rate limit.
+    pub throttle_duration: time::Duration,
+}
+
+/// Pacing of the outbound TIER2 connection attempts.
+/// See near_network::peer_manager::dialer for details.
+#[derive(Clone)]
+pub struct Dialer {
+    /// Backoff after a failed connection attempt to a peer.
+    /// It doubles after every consecutive failure, up to `max_backoff`.
+    pub min_backoff: time::Duration,
+    pub max_backoff: time::Duration,
+    /// Limit on the outbound connection attempts, across all peers.
+    pub attempts_rate_limit: rate::Limit,
+}
+
+/// Network faults simulated on the messages received from a peer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultProfile {
+    /// Delay of every message.
+    pub latency: time::Duration,
+    /// Upper bound of the random delay added on top of `latency`.
+    pub jitter: time::Duration,
+    /// Probability of dropping a message.
+    pub drop_probability: f64,
+    /// Probability of delaying a message additionally by `reorder_delay`, so that the messages
+    /// received after it overtake it.
+    pub reorder_probability: f64,
+    pub reorder_delay: time::Duration,
+}
+
+impl FaultProfile {
+    /// Whether the profile doesn't affect the messages at all.
+    pub fn is_noop(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Decides the fate of a single message: returns None if the message should be dropped,
+    /// and the delay of its delivery otherwise.
+    pub fn sample(&self, rng: &mut impl rand::Rng) -> Option<time::Duration> {
+        if rng.gen_bool(self.drop_probability) {
+            return None;
+        }
+        let mut delay = self.latency;
+        if self.jitter > time::Duration::ZERO {
+            delay += self.jitter * rng.gen_range(0. ..1.);
+        }
+        if rng.gen_bool(self.reorder_probability) {
+            delay += self.reorder_delay;
+        }
+        Some(delay)
+    }
+
+    fn from_json(cfg: &crate::config_json::FaultProfile) -> anyhow::Result<Self> {
+        Ok(Self {
+            latency: cfg.latency.try_into()?,
+            jitter: cfg.jitter.try_into()?,
+            drop_probability: cfg.drop_probability,
+            reorder_probability: cfg.reorder_probability,
+            reorder_delay: cfg.reorder_delay.try_into()?,
+        })
+    }
+
+    fn verify(&self) -> anyhow::Result<()> {
+        for (name, p) in [
+            ("drop_probability", self.drop_probability),
+            ("reorder_probability", self.reorder_probability),
+        ] {
+            if !(0. ..=1.).contains(&p) {
+                anyhow::bail!("{name}({p}) has to be within [0,1]");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Simulation of an unreliable network, for the integration tests.
+/// The faults are injected into the messages received over the connections, once
+/// the handshake is completed.
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjection {
+    /// Profile of the peers not listed in `peers`.
+    pub default: FaultProfile,
+    pub peers: HashMap<PeerId, FaultProfile>,
+}
+
+impl FaultInjection {
+    pub fn profile(&self, peer_id: &PeerId) -> &FaultProfile {
+        self.peers.get(peer_id).unwrap_or(&self.default)
+    }
 }
 
 /// Validated configuration for the peer-to-peer manager.
 #[derive(Clone)]
 pub struct NetworkConfig {
     pub node_addr: Option<tcp::ListenerAddr>,
+    /// Addresses to listen on for incoming connections, on top of `node_addr`.
+    /// All of them are advertised to the peers in the Handshake.
+    pub additional_node_addrs: Vec<tcp::ListenerAddr>,
     pub node_key: SecretKey,
+    /// Transition from the previous node key to `node_key`, gossiped to the network
+    /// so that the peers recognize this node under the new PeerId.
+    /// See `neard rotate-node-key`.
+    pub key_transition: Option<SignedKeyTransition>,
     pub validator: Option<ValidatorConfig>,
 
     pub peer_store: peer_store::Config,
     pub whitelist_nodes: Vec<PeerInfo>,
     pub handshake_timeout: time::Duration,
+    /// Timeout of establishing an outbound TCP connection.
+    pub tcp_connect_timeout: time::Duration,
 
     /// Whether to re-establish connection to known reliable peers from previous neard run(s).
     /// See near_network::peer_manager::connection_store for details.
@@ -124,12 +276,22 @@ pub struct NetworkConfig {
     pub max_send_peers: u32,
     /// Duration for checking on stats from the peers.
     pub peer_stats_period: time::Duration,
+    /// Period of the keep-alive pings sent over each connection. None disables the pings.
+    pub keep_alive_period: Option<time::Duration>,
+    /// Number of the consecutive unanswered keep-alive pings after which the connection is closed.
+    pub keep_alive_max_missed: u32,
     /// Time to persist Accounts Id in the router without removing them.
     pub ttl_account_id_router: time::Duration,
     /// Number of hops a message is allowed to travel before being dropped.
     /// This is used to avoid infinite loop because of inconsistent view of the network
     /// by different nodes.
     pub routed_message_ttl: u8,
+    /// Routed messages with the same hash received over a single connection within this period
+    /// are dropped as duplicates.
+    pub routed_message_dedup_period: time::Duration,
+    /// Number of the recently received routed messages remembered per connection, to detect
+    /// the duplicates.
+    pub routed_message_dedup_cache_size: usize,
     /// Maximum number of routes that we should keep track for each Account id in the Routing Table.
     pub max_routes_to_store: usize,
     /// Height horizon for highest height peers
@@ -145,14 +307,73 @@ pub struct NetworkConfig {
     pub outbound_disabled: bool,
     /// Flag to disable inbound connections. When true, all the incoming handshake/connection requests will be rejected.
     pub inbound_disabled: bool,
+    /// If set, outbound TCP connections are established through this SOCKS5 proxy.
+    pub socks5_proxy: Option<std::net::SocketAddr>,
     /// Whether this is an archival node.
     pub archive: bool,
     /// Maximal rate at which SyncAccountsData can be broadcasted.
     pub accounts_data_broadcast_rate_limit: rate::Limit,
     /// Maximal rate at which RoutingTable can be recomputed.
     pub routing_table_update_rate_limit: rate::Limit,
+    /// Maximal rate at which key transitions are accepted from a single connection.
+    /// A peer sends all the transitions it knows right after the handshake, so `burst` should
+    /// cover `peer_states_cache_size`.
+    pub key_transitions_rate_limit: rate::Limit,
     /// Config of the TIER1 network.
     pub tier1: Option<Tier1>,
+    /// Config of the outbox of routed messages to unreachable validators.
+    /// If None, such messages are dropped.
+    pub routed_message_outbox: Option<RoutedMessageOutbox>,
+    /// Limit on the expensive requests received over a single connection.
+    /// If None, the requests are not limited.
+    pub expensive_requests_limit: Option<ExpensiveRequestsLimit>,
+    /// Limits on the inbound connections from a single IP address.
+    /// If None, the connections are not limited per IP.
+    pub inbound_ip_limit: Option<InboundIpLimit>,
+    /// Backoff and rate limit of the outbound connection attempts.
+    pub dialer: Dialer,
+    /// Maximal rate (in bytes per second) at which data is read from a single TIER1 connection.
+    /// Can be updated at runtime.
+    pub tier1_recv_limit: rate::Limit,
+    /// Maximal rate (in bytes per second) at which data is read from a single TIER2 connection.
+    /// Inbound connections are subject to this limit until the handshake is completed.
+    /// Can be updated at runtime.
+    pub tier2_recv_limit: rate::Limit,
+    /// Number of arbiters (event loop threads) running the PeerActors.
+    /// 0 means a dedicated arbiter per connection.
+    pub peer_arbiters: usize,
+    /// A peer which sends more messages per minute than that gets banned as abusive.
+    /// The messages used for syncing the chain, the state and the network data are not counted,
+    /// since they come in bursts from honest peers as well. See `peer::tracker::is_rate_limited`.
+    /// If None, the peers are never banned for the number of messages.
+    pub max_peer_msg_per_min: Option<usize>,
+    /// Whether to accept zstd-compressed messages and to compress the large messages sent to
+    /// the peers which accept them.
+    pub zstd_compression: bool,
+    /// Whether to assign trace ids to the routed messages sent to accounts, and to log and
+    /// record the events concerning the traced messages. See peer_manager::routed_trace.
+    pub trace_routed_messages: bool,
+    /// Whether to record when and from whom each block has been received for the first time,
+    /// to measure the block propagation latency. See peer_manager::block_propagation.
+    pub measure_block_propagation: bool,
+    /// Whether to verify the signatures of the routed messages in batches on the rayon
+    /// threadpool, rather than one by one on the PeerActor threads. Note that the routed
+    /// messages received from a single peer may get processed out of order then.
+    pub batch_verify_routed_messages: bool,
+    /// Whether to refuse the peers which don't support protocol buffers encoding, and hence
+    /// would communicate using Borsh. See `near_peer_handshakes_by_encoding_total` metric for
+    /// how many such peers are still around.
+    pub reject_borsh_peers: bool,
+    /// Newest protocol version which may communicate using Borsh. The peers running a newer
+    /// protocol version support protocol buffers, so their Borsh handshakes are refused. Once
+    /// the node's own protocol version is above it, the node stops sending its handshake
+    /// in both encodings and sends it only in protocol buffers. See
+    /// `near_peer_borsh_handshakes_by_protocol_version_total` metric for the protocol versions
+    /// of the peers still using Borsh.
+    pub borsh_max_protocol_version: Option<ProtocolVersion>,
+    /// If set, the node simulates an unreliable network by delaying, reordering and dropping
+    /// the messages received from the peers. TEST-ONLY.
+    pub fault_injection: Option<FaultInjection>,
 
     // Whether to ignore tombstones some time after startup.
     //
@@ -165,6 +386,9 @@ pub struct NetworkConfig {
     /// TODO(gprusak): make it pub(crate), once all integration tests
     /// are merged into near_network.
     pub event_sink: Sink<Event>,
+    /// Kinds of events which are not pushed to `event_sink`. Such events are not even
+    /// constructed, which saves cloning the messages they contain.
+    pub disabled_events: HashSet<EventKind>,
 }
 
 impl NetworkConfig {
@@ -205,8 +429,18 @@ impl NetworkConfig {
                 }
             }
         }
+        let (mut boot_nodes, boot_node_hosts) =
+            parse_boot_nodes(&cfg.boot_nodes).context("boot_nodes")?;
+        // Resolve the boot nodes given by hostname right away, so that they can be dialed
+        // on startup.
+        for host in &boot_node_hosts {
+            if let Some(peer_info) = boot_nodes.iter_mut().find(|p| p.id == host.peer_id) {
+                peer_info.addr = resolve_host(&host.host);
+            }
+        }
         let this = Self {
             node_key,
+            key_transition: None,
             validator: validator_signer.map(|signer| ValidatorConfig {
                 signer,
                 proxies: if cfg.public_addrs.len() > 0 {
@@ -221,44 +455,41 @@ impl NetworkConfig {
                     addr.parse().context("Failed to parse SocketAddr")?,
                 )),
             },
+            additional_node_addrs: cfg
+                .additional_addrs
+                .into_iter()
+                .map(tcp::ListenerAddr::new)
+                .collect(),
             peer_store: peer_store::Config {
-                boot_nodes: if cfg.boot_nodes.is_empty() {
-                    vec![]
+                boot_nodes,
+                boot_node_hosts,
+                blacklist: parse_blacklist(&cfg.blacklist)?,
+                allowlist: if cfg.allowlist.is_empty() {
+                    None
                 } else {
-                    cfg.boot_nodes
-                        .split(',')
-                        .map(|chunk| chunk.parse())
-                        .collect::<Result<_, _>>()
-                        .context("boot_nodes")?
+                    Some(
+                        cfg.allowlist
+                            .iter()
+                            .map(|e| e.parse())
+                            .collect::<Result<_, _>>()
+                            .context("failed to parse allowlist")?,
+                    )
                 },
-                blacklist: cfg
-                    .blacklist
-                    .iter()
-                    .map(|e| e.parse())
-                    .collect::<Result<_, _>>()
-                    .context("failed to parse blacklist")?,
                 peer_states_cache_size: cfg.peer_states_cache_size,
                 connect_only_to_boot_nodes: cfg.experimental.connect_only_to_boot_nodes,
                 ban_window: cfg.ban_window.try_into()?,
-                peer_expiration_duration: cfg.peer_expiration_duration.try_into()?,
-            },
-            whitelist_nodes: if cfg.whitelist_nodes.is_empty() {
-                vec![]
-            } else {
-                cfg.whitelist_nodes
-                    .split(',')
-                    .map(|peer| match peer.parse::<PeerInfo>() {
-                        Ok(peer) if peer.addr.is_none() => anyhow::bail!(
-                            "whitelist_nodes are required to specify both PeerId and IP:port"
-                        ),
-                        Ok(peer) => Ok(peer),
-                        Err(err) => Err(err.into()),
-                    })
+                ban_window_by_reason: cfg
+                    .ban_window_by_reason
+                    .iter()
+                    .map(|(reason, window)| Ok((*reason, (*window).try_into()?)))
                     .collect::<anyhow::Result<_>>()
-                    .context("whitelist_nodes")?
+                    .context("ban_window_by_reason")?,
+                peer_expiration_duration: cfg.peer_expiration_duration.try_into()?,
             },
+            whitelist_nodes: parse_whitelist_nodes(&cfg.whitelist_nodes)?,
             connect_to_reliable_peers_on_startup: true,
             handshake_timeout: cfg.handshake_timeout.try_into()?,
+            tcp_connect_timeout: cfg.tcp_connect_timeout.try_into()?,
             monitor_peers_max_period: cfg.monitor_peers_max_period.try_into()?,
             max_num_peers: cfg.max_num_peers,
             minimum_outbound_peers: cfg.minimum_outbound_peers,
@@ -269,8 +500,16 @@ impl NetworkConfig {
             archival_peer_connections_lower_bound: cfg.archival_peer_connections_lower_bound,
             max_send_peers: PEERS_RESPONSE_MAX_PEERS,
             peer_stats_period: cfg.peer_stats_period.try_into()?,
+            keep_alive_period: if cfg.keep_alive_period.is_zero() {
+                None
+            } else {
+                Some(cfg.keep_alive_period.try_into()?)
+            },
+            keep_alive_max_missed: cfg.keep_alive_max_missed,
             ttl_account_id_router: cfg.ttl_account_id_router.try_into()?,
             routed_message_ttl: ROUTED_MESSAGE_TTL,
+            routed_message_dedup_period: cfg.experimental.routed_message_dedup_period.try_into()?,
+            routed_message_dedup_cache_size: cfg.experimental.routed_message_dedup_cache_size,
             max_routes_to_store: MAX_ROUTES_TO_STORE,
             highest_peer_horizon: HIGHEST_PEER_HORIZON,
             push_info_period: time::Duration::milliseconds(100),
@@ -278,20 +517,117 @@ impl NetworkConfig {
             archive,
             accounts_data_broadcast_rate_limit: rate::Limit { qps: 0.1, burst: 1 },
             routing_table_update_rate_limit: rate::Limit { qps: 1., burst: 1 },
+            key_transitions_rate_limit: rate::Limit {
+                qps: 1.,
+                burst: cfg.peer_states_cache_size.into(),
+            },
             tier1: Some(Tier1 {
                 connect_interval: cfg.experimental.tier1_connect_interval.try_into()?,
                 new_connections_per_attempt: cfg.experimental.tier1_new_connections_per_attempt,
                 advertise_proxies_interval: time::Duration::minutes(15),
                 enable_inbound: cfg.experimental.tier1_enable_inbound,
                 enable_outbound: cfg.experimental.tier1_enable_outbound,
+                send_chunk_parts: cfg.experimental.tier1_send_chunk_parts,
+                send_forward_tx: cfg.experimental.tier1_send_forward_tx,
             }),
+            routed_message_outbox: if cfg
+                .experimental
+                .routed_message_outbox_max_messages_per_account
+                > 0
+            {
+                Some(RoutedMessageOutbox {
+                    ttl: cfg.experimental.routed_message_outbox_ttl.try_into()?,
+                    max_messages_per_account: cfg
+                        .experimental
+                        .routed_message_outbox_max_messages_per_account,
+                    max_accounts: cfg.experimental.routed_message_outbox_max_accounts,
+                })
+            } else {
+                None
+            },
+            expensive_requests_limit: if cfg.experimental.expensive_requests_max_in_flight > 0 {
+                Some(ExpensiveRequestsLimit {
+                    max_in_flight: cfg.experimental.expensive_requests_max_in_flight,
+                    max_rejected: cfg.experimental.expensive_requests_max_rejected,
+                    rejected_window: cfg
+                        .experimental
+                        .expensive_requests_rejected_window
+                        .try_into()?,
+                })
+            } else {
+                None
+            },
+            inbound_ip_limit: if cfg.experimental.inbound_max_connections_per_ip > 0 {
+                Some(InboundIpLimit {
+                    max_connections: cfg.experimental.inbound_max_connections_per_ip,
+                    connections_rate_limit: rate::Limit {
+                        qps: cfg.experimental.inbound_connections_per_ip_per_second,
+                        burst: cfg.experimental.inbound_connections_per_ip_burst,
+                    },
+                    throttle_duration: cfg.experimental.inbound_ip_throttle_duration.try_into()?,
+                })
+            } else {
+                None
+            },
+            dialer: Dialer {
+                min_backoff: cfg.experimental.dialer_min_backoff.try_into()?,
+                max_backoff: cfg.experimental.dialer_max_backoff.try_into()?,
+                attempts_rate_limit: rate::Limit {
+                    qps: cfg.experimental.dialer_attempts_per_second,
+                    burst: cfg.experimental.dialer_attempts_burst,
+                },
+            },
+            tier1_recv_limit: rate::Limit {
+                qps: cfg.experimental.tier1_recv_bytes_per_second,
+                burst: cfg.experimental.tier1_recv_burst_bytes,
+            },
+            tier2_recv_limit: rate::Limit {
+                qps: cfg.experimental.tier2_recv_bytes_per_second,
+                burst: cfg.experimental.tier2_recv_burst_bytes,
+            },
+            peer_arbiters: cfg.experimental.peer_arbiters,
+            max_peer_msg_per_min: if cfg.experimental.max_peer_msg_per_min > 0 {
+                Some(cfg.experimental.max_peer_msg_per_min)
+            } else {
+                None
+            },
+            zstd_compression: cfg.experimental.zstd_compression,
+            trace_routed_messages: cfg.experimental.trace_routed_messages,
+            measure_block_propagation: cfg.experimental.measure_block_propagation,
+            batch_verify_routed_messages: cfg.experimental.batch_verify_routed_messages,
+            reject_borsh_peers: cfg.experimental.reject_borsh_peers,
+            borsh_max_protocol_version: cfg.experimental.borsh_max_protocol_version,
+            fault_injection: match &cfg.experimental.fault_injection {
+                Some(fi) => {
+                    tracing::warn!(
+                        target: "network",
+                        "Fault injection is enabled: messages from the peers will be delayed \
+                         and dropped. It is meant only for tests."
+                    );
+                    Some(FaultInjection {
+                        default: FaultProfile::from_json(&fi.default)
+                            .context("fault_injection.default")?,
+                        peers: fi
+                            .peers
+                            .iter()
+                            .map(|(peer_id, p)| {
+                                FaultProfile::from_json(p).map(|p| (peer_id.clone(), p))
+                            })
+                            .collect::<anyhow::Result<_>>()
+                            .context("fault_injection.peers")?,
+                    })
+                }
+                None => None,
+            },
             inbound_disabled: cfg.experimental.inbound_disabled,
+            socks5_proxy: cfg.socks5_proxy,
             skip_tombstones: if cfg.experimental.skip_sending_tombstones_seconds > 0 {
                 Some(time::Duration::seconds(cfg.experimental.skip_sending_tombstones_seconds))
             } else {
                 None
             },
             event_sink: Sink::null(),
+            disabled_events: HashSet::new(),
         };
         Ok(this)
     }
@@ -300,6 +636,11 @@ impl NetworkConfig {
         PeerId::new(self.node_key.public_key())
     }
 
+    /// All the addresses on which the node listens for incoming connections.
+    pub fn listen_addrs(&self) -> impl Iterator<Item = &tcp::ListenerAddr> {
+        self.node_addr.iter().chain(self.additional_node_addrs.iter())
+    }
+
     /// TEST-ONLY: Returns network config with given seed used for peer id.
     pub fn from_seed(seed: &str, node_addr: tcp::ListenerAddr) -> Self {
         let node_key = SecretKey::from_seed(KeyType::ED25519, seed);
@@ -312,18 +653,24 @@ impl NetworkConfig {
         };
         NetworkConfig {
             node_addr: Some(node_addr),
+            additional_node_addrs: vec![],
             node_key,
+            key_transition: None,
             validator: Some(validator),
             peer_store: peer_store::Config {
                 boot_nodes: vec![],
+                boot_node_hosts: vec![],
                 blacklist: blacklist::Blacklist::default(),
+                allowlist: None,
                 peer_states_cache_size: 1000,
                 ban_window: time::Duration::seconds(1),
+                ban_window_by_reason: Default::default(),
                 peer_expiration_duration: time::Duration::seconds(60 * 60),
                 connect_only_to_boot_nodes: false,
             },
             whitelist_nodes: vec![],
             handshake_timeout: time::Duration::seconds(5),
+            tcp_connect_timeout: time::Duration::seconds(1),
             connect_to_reliable_peers_on_startup: true,
             monitor_peers_max_period: time::Duration::seconds(100),
             max_num_peers: 40,
@@ -335,16 +682,22 @@ impl NetworkConfig {
             archival_peer_connections_lower_bound: 10,
             max_send_peers: PEERS_RESPONSE_MAX_PEERS,
             peer_stats_period: time::Duration::seconds(5),
+            keep_alive_period: None,
+            keep_alive_max_missed: 3,
             ttl_account_id_router: time::Duration::seconds(60 * 60),
             routed_message_ttl: ROUTED_MESSAGE_TTL,
+            routed_message_dedup_period: time::Duration::milliseconds(50),
+            routed_message_dedup_cache_size: 1000,
             max_routes_to_store: 1,
             highest_peer_horizon: 5,
             push_info_period: time::Duration::milliseconds(100),
             outbound_disabled: false,
             inbound_disabled: false,
+            socks5_proxy: None,
             archive: false,
             accounts_data_broadcast_rate_limit: rate::Limit { qps: 100., burst: 1000000 },
             routing_table_update_rate_limit: rate::Limit { qps: 10., burst: 1 },
+            key_transitions_rate_limit: rate::Limit { qps: 100., burst: 1000 },
             tier1: Some(Tier1 {
                 // Interval is very large, so that it doesn't happen spontaneously in tests.
                 // It should rather be triggered manually in tests.
@@ -353,13 +706,75 @@ impl NetworkConfig {
                 advertise_proxies_interval: time::Duration::hours(1000),
                 enable_inbound: true,
                 enable_outbound: true,
+                send_chunk_parts: true,
+                send_forward_tx: true,
             }),
+            routed_message_outbox: Some(RoutedMessageOutbox {
+                ttl: time::Duration::seconds(10),
+                max_messages_per_account: 100,
+                max_accounts: 100,
+            }),
+            expensive_requests_limit: Some(ExpensiveRequestsLimit {
+                max_in_flight: 100,
+                max_rejected: 1000,
+                rejected_window: time::Duration::seconds(10),
+            }),
+            // All the nodes in tests connect from the loopback address.
+            inbound_ip_limit: None,
+            // Tests reconnect to the peers as soon as they want to.
+            dialer: Dialer {
+                min_backoff: time::Duration::ZERO,
+                max_backoff: time::Duration::ZERO,
+                attempts_rate_limit: rate::Limit { qps: 1000., burst: 1000000 },
+            },
+            tier1_recv_limit: rate::Limit {
+                qps: NETWORK_MESSAGE_MAX_SIZE_BYTES as f64,
+                burst: NETWORK_MESSAGE_MAX_SIZE_BYTES as u64,
+            },
+            tier2_recv_limit: rate::Limit {
+                qps: NETWORK_MESSAGE_MAX_SIZE_BYTES as f64,
+                burst: NETWORK_MESSAGE_MAX_SIZE_BYTES as u64,
+            },
+            // Tests don't stop the arbiters of the NetworkStates created without a PeerManager.
+            peer_arbiters: 0,
+            max_peer_msg_per_min: None,
+            zstd_compression: true,
+            trace_routed_messages: false,
+            measure_block_propagation: false,
+            batch_verify_routed_messages: false,
+            reject_borsh_peers: false,
+            borsh_max_protocol_version: None,
+            fault_injection: None,
             skip_tombstones: None,
             event_sink: Sink::null(),
+            disabled_events: HashSet::new(),
+        }
+    }
+
+    /// Checks whether the events of the given kind are observed, i.e. whether they need to
+    /// be constructed at all.
+    pub fn event_enabled(&self, kind: EventKind) -> bool {
+        !self.event_sink.is_null() && !self.disabled_events.contains(&kind)
+    }
+
+    /// Pushes the event to `event_sink`, unless events of its kind are disabled.
+    pub fn push_event(&self, event: Event) {
+        if self.event_enabled(EventKind::from(&event)) {
+            self.event_sink.push(event);
         }
     }
 
     pub fn verify(self) -> anyhow::Result<VerifiedConfig> {
+        if self.handshake_timeout <= time::Duration::ZERO {
+            anyhow::bail!("handshake_timeout has to be positive");
+        }
+        if self.tcp_connect_timeout <= time::Duration::ZERO {
+            anyhow::bail!("tcp_connect_timeout has to be positive");
+        }
+        if self.keep_alive_period.is_some() && self.keep_alive_max_missed == 0 {
+            anyhow::bail!("keep_alive_max_missed has to be positive");
+        }
+
         if !(self.ideal_connections_lo <= self.ideal_connections_hi) {
             anyhow::bail!(
                 "Invalid ideal_connections values. lo({}) > hi({}).",
@@ -404,6 +819,85 @@ impl NetworkConfig {
         self.routing_table_update_rate_limit
             .validate()
             .context("routing_table_update_rate_limit")?;
+        self.key_transitions_rate_limit.validate().context("key_transitions_rate_limit")?;
+
+        if let Some(t) = &self.key_transition {
+            if t.new_peer_id != self.node_id() {
+                anyhow::bail!(
+                    "key_transition.new_peer_id({}) doesn't match the node key ({})",
+                    t.new_peer_id,
+                    self.node_id()
+                );
+            }
+            if !t.verify() {
+                anyhow::bail!("key_transition has invalid signatures");
+            }
+        }
+        if self.routed_message_dedup_cache_size == 0 {
+            anyhow::bail!("routed_message_dedup_cache_size has to be positive");
+        }
+        if let Some(outbox) = &self.routed_message_outbox {
+            if outbox.ttl <= time::Duration::ZERO {
+                anyhow::bail!("routed_message_outbox.ttl({}) has to be positive", outbox.ttl);
+            }
+            if outbox.max_messages_per_account == 0 || outbox.max_accounts == 0 {
+                anyhow::bail!("routed_message_outbox limits have to be positive");
+            }
+        }
+        if let Some(limit) = &self.expensive_requests_limit {
+            if limit.rejected_window <= time::Duration::ZERO {
+                anyhow::bail!(
+                    "expensive_requests_limit.rejected_window({}) has to be positive",
+                    limit.rejected_window
+                );
+            }
+            if limit.max_in_flight == 0 {
+                anyhow::bail!("expensive_requests_limit.max_in_flight has to be positive");
+            }
+        }
+        if let Some(limit) = &self.inbound_ip_limit {
+            if limit.max_connections == 0 {
+                anyhow::bail!("inbound_ip_limit.max_connections has to be positive");
+            }
+            limit
+                .connections_rate_limit
+                .validate()
+                .context("inbound_ip_limit.connections_rate_limit")?;
+            if limit.throttle_duration < time::Duration::ZERO {
+                anyhow::bail!(
+                    "inbound_ip_limit.throttle_duration({}) has to be non-negative",
+                    limit.throttle_duration
+                );
+            }
+        }
+        if self.dialer.min_backoff < time::Duration::ZERO
+            || self.dialer.max_backoff < self.dialer.min_backoff
+        {
+            anyhow::bail!(
+                "dialer backoff has to satisfy 0 <= min_backoff({}) <= max_backoff({})",
+                self.dialer.min_backoff,
+                self.dialer.max_backoff
+            );
+        }
+        self.dialer.attempts_rate_limit.validate().context("dialer.attempts_rate_limit")?;
+        self.tier1_recv_limit.validate().context("tier1_recv_limit")?;
+        self.tier2_recv_limit.validate().context("tier2_recv_limit")?;
+        if let Some(fi) = &self.fault_injection {
+            for profile in std::iter::once(&fi.default).chain(fi.peers.values()) {
+                profile.verify().context("fault_injection")?;
+            }
+        }
+        if !self.additional_node_addrs.is_empty() {
+            if self.node_addr.is_none() {
+                anyhow::bail!("additional_addrs require addr to be set");
+            }
+            let mut addrs = std::collections::HashSet::new();
+            for addr in self.listen_addrs() {
+                if !addrs.insert(**addr) {
+                    anyhow::bail!("listen address {addr} is set more than once");
+                }
+            }
+        }
         Ok(VerifiedConfig { node_id: self.node_id(), inner: self })
     }
 }
@@ -434,13 +928,80 @@ impl std::ops::Deref for VerifiedConfig {
     }
 }
 
+/// Parses a boot node. If its address is given by a hostname rather than an IP, the hostname is
+/// returned as well, to be re-resolved periodically. A hostname which can't be resolved at
+/// startup is not an error, the boot node just stays without an address until it resolves.
+/// Parses a comma-separated list of boot nodes, in the format of `boot_nodes` in config.json.
+/// The boot nodes given by hostname are returned without an address: the hostnames are not
+/// resolved.
+pub(crate) fn parse_boot_nodes(
+    s: &str,
+) -> anyhow::Result<(Vec<PeerInfo>, Vec<peer_store::BootNodeHost>)> {
+    let mut boot_nodes = vec![];
+    let mut boot_node_hosts = vec![];
+    if !s.is_empty() {
+        for chunk in s.split(',') {
+            let (peer_info, host) = parse_boot_node(chunk)?;
+            boot_nodes.push(peer_info);
+            boot_node_hosts.extend(host);
+        }
+    }
+    Ok((boot_nodes, boot_node_hosts))
+}
+
+/// Parses a comma-separated list of whitelisted nodes, in the format of `whitelist_nodes`
+/// in config.json.
+pub(crate) fn parse_whitelist_nodes(s: &str) -> anyhow::Result<Vec<PeerInfo>> {
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    s.split(',')
+        .map(|peer| peer.parse::<PeerInfo>())
+        .collect::<Result<_, _>>()
+        .context("whitelist_nodes")
+}
+
+pub(crate) fn parse_blacklist(entries: &[String]) -> anyhow::Result<blacklist::Blacklist> {
+    entries.iter().map(|e| e.parse()).collect::<Result<_, _>>().context("failed to parse blacklist")
+}
+
+fn resolve_host(host: &str) -> Option<SocketAddr> {
+    match host.to_socket_addrs() {
+        Ok(mut addrs) => addrs.next(),
+        Err(err) => {
+            tracing::warn!(target: "network", host, %err, "Failed to resolve boot node");
+            None
+        }
+    }
+}
+
+fn parse_boot_node(s: &str) -> anyhow::Result<(PeerInfo, Option<peer_store::BootNodeHost>)> {
+    let chunks: Vec<&str> = s.split('@').collect();
+    let host = match chunks.get(1) {
+        Some(addr) if addr.parse::<SocketAddr>().is_err() && is_host_port(addr) => *addr,
+        _ => return Ok((s.parse()?, None)),
+    };
+    if chunks.len() > 3 {
+        anyhow::bail!("invalid boot node {s:?}");
+    }
+    let id = PeerId::new(chunks[0].parse().context("peer id")?);
+    let account_id = chunks.get(2).map(|c| c.parse()).transpose().context("account id")?;
+    let boot_node_host = peer_store::BootNodeHost { peer_id: id.clone(), host: host.to_string() };
+    Ok((PeerInfo { id, addr: None, account_id }, Some(boot_node_host)))
+}
+
+/// Whether `s` looks like "hostname:port".
+fn is_host_port(s: &str) -> bool {
+    s.rsplit_once(':').map_or(false, |(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
 #[cfg(test)]
 mod test {
     use super::UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE;
     use crate::config;
     use crate::network_protocol;
     use crate::network_protocol::testonly as data;
-    use crate::network_protocol::{AccountData, VersionedAccountData};
+    use crate::network_protocol::{AccountData, ProxyHint, VersionedAccountData};
     use crate::tcp;
     use crate::testonly::make_rng;
     use near_primitives::time;
@@ -467,6 +1028,61 @@ mod test {
         assert!(nc.verify().is_err());
     }
 
+    #[test]
+    fn peer_msg_limit_enabled_by_default() {
+        let node_key = near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, "123");
+        let nc = config::NetworkConfig::new(Default::default(), node_key, None, false).unwrap();
+        assert_eq!(
+            Some(crate::config_json::default_max_peer_msg_per_min()),
+            nc.max_peer_msg_per_min
+        );
+    }
+
+    #[test]
+    fn fault_profile_sample() {
+        let mut rng = make_rng(9823745);
+        let profile = config::FaultProfile {
+            latency: time::Duration::milliseconds(100),
+            jitter: time::Duration::milliseconds(50),
+            drop_probability: 0.5,
+            reorder_probability: 0.5,
+            reorder_delay: time::Duration::seconds(1),
+        };
+        let samples: Vec<_> = (0..1000).map(|_| profile.sample(&mut rng)).collect();
+        assert!(samples.iter().any(|s| s.is_none()));
+        let delays: Vec<_> = samples.into_iter().flatten().collect();
+        assert!(delays.iter().all(|d| *d >= profile.latency));
+        assert!(delays.iter().any(|d| *d < profile.reorder_delay));
+        assert!(delays.iter().any(|d| *d >= profile.reorder_delay));
+
+        let mut nc = config::NetworkConfig::from_seed("123", tcp::ListenerAddr::reserve_for_test());
+        nc.fault_injection = Some(config::FaultInjection {
+            default: config::FaultProfile { drop_probability: 1.5, ..Default::default() },
+            peers: Default::default(),
+        });
+        assert!(nc.verify().is_err());
+    }
+
+    #[test]
+    fn parse_boot_node() {
+        let id = "ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw";
+        let (peer_info, host) =
+            super::parse_boot_node(&format!("{id}@31.192.22.209:24567")).unwrap();
+        assert_eq!(peer_info.addr, Some("31.192.22.209:24567".parse().unwrap()));
+        assert_eq!(host, None);
+
+        // A hostname which doesn't resolve at the moment.
+        let (peer_info, host) =
+            super::parse_boot_node(&format!("{id}@boot.invalid:24567@test.near")).unwrap();
+        assert_eq!(peer_info.addr, None);
+        assert_eq!(peer_info.account_id, Some("test.near".parse().unwrap()));
+        let host = host.unwrap();
+        assert_eq!(host.peer_id, peer_info.id);
+        assert_eq!(host.host, "boot.invalid:24567");
+
+        assert!(super::parse_boot_node(&format!("{id}@boot.invalid:port")).is_err());
+    }
+
     // Check that MAX_PEER_ADDRS limit is consistent with the
     // network_protocol::MAX_ACCOUNT_DATA_SIZE_BYTES limit
     #[test]
@@ -475,15 +1091,20 @@ mod test {
         let clock = time::FakeClock::default();
         let signer = data::make_validator_signer(&mut rng);
 
+        let proxies: Vec<_> = (0..config::MAX_PEER_ADDRS)
+            .map(|_| {
+                // Using IPv6 gives maximal size of the resulting config.
+                let ip = data::make_ipv6(&mut rng);
+                data::make_peer_addr(&mut rng, ip)
+            })
+            .collect();
         let ad = VersionedAccountData {
             data: AccountData {
-                proxies: (0..config::MAX_PEER_ADDRS)
-                    .map(|_| {
-                        // Using IPv6 gives maximal size of the resulting config.
-                        let ip = data::make_ipv6(&mut rng);
-                        data::make_peer_addr(&mut rng, ip)
-                    })
+                proxy_hints: proxies
+                    .iter()
+                    .map(|p| ProxyHint { peer_id: p.peer_id.clone(), latency: time::Duration::MAX })
                     .collect(),
+                proxies,
                 peer_id: data::make_peer_id(&mut rng),
             },
             account_key: signer.public_key(),
@@ -1,10 +1,18 @@
 use crate::network_protocol::PeerAddr;
+use crate::peer::NETWORK_MESSAGE_MAX_SIZE_BYTES;
 use crate::stun;
+use crate::types::ReasonForBan;
+use near_primitives::version::ProtocolVersion;
 use std::time::Duration;
 
 /// Time to persist Accounts Id in the router without removing them in seconds.
 pub const TTL_ACCOUNT_ID_ROUTER: u64 = 60 * 60;
 
+/// Accepts null as an empty address.
+fn deserialize_addr<'de, D: serde::Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(<Option<String> as serde::Deserialize>::deserialize(d)?.unwrap_or_default())
+}
+
 /// Maximum number of active peers. Hard limit.
 fn default_max_num_peers() -> u32 {
     40
@@ -22,6 +30,17 @@ fn default_ideal_connections_hi() -> u32 {
     35
 }
 /// Peers which last message is was within this period of time are considered active recent peers.
+fn default_handshake_timeout() -> Duration {
+    Duration::from_secs(20)
+}
+
+/// The `connect` may take several minutes, when the `SYN` packet gets silently dropped, in which
+/// case the default TCP timeout is applied. We shorten it to one second by default, which was
+/// hard-coded in a library we used before.
+fn default_tcp_connect_timeout() -> Duration {
+    Duration::from_secs(1)
+}
+
 fn default_peer_recent_time_window() -> Duration {
     Duration::from_secs(600)
 }
@@ -43,6 +62,14 @@ fn default_ttl_account_id_router() -> Duration {
 fn default_peer_stats_period() -> Duration {
     Duration::from_secs(5)
 }
+/// Period of the keep-alive pings sent over each connection.
+fn default_keep_alive_period() -> Duration {
+    Duration::from_secs(10)
+}
+/// Number of the consecutive unanswered keep-alive pings after which the connection is closed.
+fn default_keep_alive_max_missed() -> u32 {
+    3
+}
 /// Period to update the list of peers we connect to.
 fn default_monitor_peers_max_period() -> Duration {
     Duration::from_secs(60)
@@ -79,16 +106,28 @@ fn default_trusted_stun_servers() -> Vec<stun::ServerAddr> {
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Config {
     /// Local address to listen for incoming connections.
+    /// Empty (or null) for a node which doesn't accept connections and only dials out,
+    /// for example a node behind a firewall. Such a node is not advertised to other peers.
+    #[serde(deserialize_with = "deserialize_addr")]
     pub addr: String,
+    /// Additional local addresses to listen for incoming connections, for example
+    /// "[::]:24567" to accept IPv6 connections next to the IPv4 ones accepted at `addr`.
+    #[serde(default)]
+    pub additional_addrs: Vec<std::net::SocketAddr>,
     /// Comma separated list of nodes to connect to.
     /// Examples:
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@31.192.22.209:24567
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@nearnode.com:24567
+    /// Hostnames are resolved at startup and then re-resolved periodically, so that boot nodes
+    /// can run behind DNS with rotating IPs.
     pub boot_nodes: String,
     /// Comma separated list of whitelisted nodes. Inbound connections from the nodes on
-    /// the whitelist are accepted even if the limit of the inbound connection has been reached.
-    /// For each whitelisted node specifying both PeerId and one of IP:port or Host:port is required:
+    /// the whitelist are accepted even if the limit of the inbound connection has been reached,
+    /// and the connections to them are never evicted. Each whitelisted node is specified by its
+    /// PeerId, optionally followed by IP:port or Host:port. If the address is specified, the node
+    /// is whitelisted only when connecting from its IP (the port is ignored).
     /// Examples:
+    ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@31.192.22.209:24567
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@nearnode.com:24567
     #[serde(default)]
@@ -116,22 +155,45 @@ pub struct Config {
     /// if we are an archival node.
     #[serde(default = "default_archival_peer_connections_lower_bound")]
     pub archival_peer_connections_lower_bound: u32,
-    /// Handshake timeout.
+    /// Time within which the handshake with a peer has to complete, counted from establishing
+    /// the TCP connection. Connections which don't complete the handshake in time are closed.
+    #[serde(default = "default_handshake_timeout")]
     pub handshake_timeout: Duration,
+    /// Timeout of establishing an outbound TCP connection.
+    #[serde(default = "default_tcp_connect_timeout")]
+    pub tcp_connect_timeout: Duration,
     /// Skip waiting for peers before starting node.
     pub skip_sync_wait: bool,
     /// Ban window for peers who misbehave.
     pub ban_window: Duration,
+    /// Ban windows for specific ban reasons, overriding `ban_window`.
+    /// For example `{"Abusive": {"secs": 86400, "nanos": 0}}` keeps abusive peers banned for a day.
+    /// Bans are persisted, so they stay in effect across restarts until they expire.
+    #[serde(default)]
+    pub ban_window_by_reason: std::collections::HashMap<ReasonForBan, Duration>,
     /// List of addresses that will not be accepted as valid neighbors.
-    /// It can be IP:Port or IP (to blacklist all connections coming from this address).
+    /// It can be IP:Port, IP (to blacklist all connections coming from this address)
+    /// or a CIDR range like 192.0.2.0/24 (to blacklist all the addresses in the range).
     #[serde(default)]
     pub blacklist: Vec<String>,
+    /// If not empty, only the addresses on this list will be accepted as valid neighbors,
+    /// unless they are also blacklisted. The entries have the same format as in `blacklist`.
+    /// Note that the boot nodes have to be on the list as well.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowlist: Vec<String>,
     /// Time to persist Accounts Id in the router without removing them in seconds.
     #[serde(default = "default_ttl_account_id_router")]
     pub ttl_account_id_router: Duration,
     /// Period to check on peer status
     #[serde(default = "default_peer_stats_period")]
     pub peer_stats_period: Duration,
+    /// Period of the keep-alive pings sent over each connection, used to measure the round
+    /// trip time and to detect unresponsive peers. Zero disables the pings.
+    #[serde(default = "default_keep_alive_period")]
+    pub keep_alive_period: Duration,
+    /// The connection is closed once that many consecutive keep-alive pings stay unanswered.
+    #[serde(default = "default_keep_alive_max_missed")]
+    pub keep_alive_max_missed: u32,
     // Period to monitor peers (connect to new ones etc).
     #[serde(default = "default_monitor_peers_max_period")]
     pub monitor_peers_max_period: Duration,
@@ -188,6 +250,11 @@ pub struct Config {
     /// such a case.
     #[serde(default = "default_trusted_stun_servers")]
     pub trusted_stun_servers: Vec<stun::ServerAddr>,
+    /// Address of a SOCKS5 proxy (for example a local Tor daemon at 127.0.0.1:9050),
+    /// through which all the outbound TCP connections should be established.
+    /// Only the proxies which don't require authentication are supported.
+    #[serde(default)]
+    pub socks5_proxy: Option<std::net::SocketAddr>,
     // Experimental part of the JSON config. Regular users/validators should not have to set any values there.
     // Field names in here can change/disappear at any moment without warning.
     #[serde(default)]
@@ -202,6 +269,14 @@ fn default_tier1_enable_inbound() -> bool {
 fn default_tier1_enable_outbound() -> bool {
     false
 }
+/// Disabled until all the nodes accept the chunk part forwards over TIER1.
+fn default_tier1_send_chunk_parts() -> bool {
+    false
+}
+/// Disabled until all the nodes accept the forwarded transactions over TIER1.
+fn default_tier1_send_forward_tx() -> bool {
+    false
+}
 
 fn default_tier1_connect_interval() -> Duration {
     Duration::from_secs(60)
@@ -211,6 +286,89 @@ fn default_tier1_new_connections_per_attempt() -> u64 {
     50
 }
 
+fn default_routed_message_dedup_period() -> Duration {
+    Duration::from_millis(50)
+}
+
+fn default_routed_message_dedup_cache_size() -> usize {
+    1000
+}
+
+fn default_routed_message_outbox_ttl() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_routed_message_outbox_max_messages_per_account() -> usize {
+    100
+}
+
+fn default_routed_message_outbox_max_accounts() -> usize {
+    300
+}
+
+fn default_expensive_requests_max_in_flight() -> usize {
+    32
+}
+
+fn default_expensive_requests_max_rejected() -> usize {
+    256
+}
+
+fn default_expensive_requests_rejected_window() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_inbound_max_connections_per_ip() -> usize {
+    16
+}
+
+fn default_inbound_connections_per_ip_per_second() -> f64 {
+    1.
+}
+
+fn default_inbound_connections_per_ip_burst() -> u64 {
+    20
+}
+
+fn default_inbound_ip_throttle_duration() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_dialer_min_backoff() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_dialer_max_backoff() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_dialer_attempts_per_second() -> f64 {
+    1.
+}
+
+fn default_dialer_attempts_burst() -> u64 {
+    10
+}
+
+/// By default the receive rate is effectively unlimited.
+fn default_recv_bytes_per_second() -> f64 {
+    NETWORK_MESSAGE_MAX_SIZE_BYTES as f64
+}
+
+fn default_recv_burst_bytes() -> u64 {
+    NETWORK_MESSAGE_MAX_SIZE_BYTES as u64
+}
+
+fn default_peer_arbiters() -> usize {
+    4
+}
+
+/// The sync messages don't count towards the limit, so an honest peer stays far below it even
+/// when it relays the chunk parts and the approvals of the whole network.
+pub(crate) fn default_max_peer_msg_per_min() -> usize {
+    100_000
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ExperimentalConfig {
     // If true - don't allow any inbound connections.
@@ -236,6 +394,14 @@ pub struct ExperimentalConfig {
     #[serde(default = "default_tier1_enable_outbound")]
     pub tier1_enable_outbound: bool,
 
+    /// See `near_network::config::Tier1::send_chunk_parts`.
+    #[serde(default = "default_tier1_send_chunk_parts")]
+    pub tier1_send_chunk_parts: bool,
+
+    /// See `near_network::config::Tier1::send_forward_tx`.
+    #[serde(default = "default_tier1_send_forward_tx")]
+    pub tier1_send_forward_tx: bool,
+
     /// See `near_network::config::Tier1::connect_interval`.
     #[serde(default = "default_tier1_connect_interval")]
     pub tier1_connect_interval: Duration,
@@ -243,6 +409,153 @@ pub struct ExperimentalConfig {
     /// See `near_network::config::Tier1::new_connections_per_attempt`.
     #[serde(default = "default_tier1_new_connections_per_attempt")]
     pub tier1_new_connections_per_attempt: u64,
+
+    /// See `near_network::config::NetworkConfig::routed_message_dedup_period`.
+    #[serde(default = "default_routed_message_dedup_period")]
+    pub routed_message_dedup_period: Duration,
+
+    /// See `near_network::config::NetworkConfig::routed_message_dedup_cache_size`.
+    #[serde(default = "default_routed_message_dedup_cache_size")]
+    pub routed_message_dedup_cache_size: usize,
+
+    /// See `near_network::config::RoutedMessageOutbox::ttl`.
+    #[serde(default = "default_routed_message_outbox_ttl")]
+    pub routed_message_outbox_ttl: Duration,
+
+    /// See `near_network::config::RoutedMessageOutbox::max_messages_per_account`.
+    /// Setting it to 0 disables the outbox.
+    #[serde(default = "default_routed_message_outbox_max_messages_per_account")]
+    pub routed_message_outbox_max_messages_per_account: usize,
+
+    /// See `near_network::config::RoutedMessageOutbox::max_accounts`.
+    #[serde(default = "default_routed_message_outbox_max_accounts")]
+    pub routed_message_outbox_max_accounts: usize,
+
+    /// See `near_network::config::ExpensiveRequestsLimit::max_in_flight`.
+    /// Setting it to 0 disables the limit.
+    #[serde(default = "default_expensive_requests_max_in_flight")]
+    pub expensive_requests_max_in_flight: usize,
+
+    /// See `near_network::config::ExpensiveRequestsLimit::max_rejected`.
+    #[serde(default = "default_expensive_requests_max_rejected")]
+    pub expensive_requests_max_rejected: usize,
+
+    /// See `near_network::config::ExpensiveRequestsLimit::rejected_window`.
+    #[serde(default = "default_expensive_requests_rejected_window")]
+    pub expensive_requests_rejected_window: Duration,
+
+    /// See `near_network::config::InboundIpLimit::max_connections`.
+    /// Setting it to 0 disables the per-IP limits.
+    #[serde(default = "default_inbound_max_connections_per_ip")]
+    pub inbound_max_connections_per_ip: usize,
+
+    /// See `near_network::config::InboundIpLimit::connections_rate_limit`.
+    #[serde(default = "default_inbound_connections_per_ip_per_second")]
+    pub inbound_connections_per_ip_per_second: f64,
+
+    /// See `near_network::config::InboundIpLimit::connections_rate_limit`.
+    #[serde(default = "default_inbound_connections_per_ip_burst")]
+    pub inbound_connections_per_ip_burst: u64,
+
+    /// See `near_network::config::InboundIpLimit::throttle_duration`.
+    #[serde(default = "default_inbound_ip_throttle_duration")]
+    pub inbound_ip_throttle_duration: Duration,
+
+    /// See `near_network::config::Dialer::min_backoff`.
+    #[serde(default = "default_dialer_min_backoff")]
+    pub dialer_min_backoff: Duration,
+
+    /// See `near_network::config::Dialer::max_backoff`.
+    #[serde(default = "default_dialer_max_backoff")]
+    pub dialer_max_backoff: Duration,
+
+    /// See `near_network::config::Dialer::attempts_rate_limit`.
+    #[serde(default = "default_dialer_attempts_per_second")]
+    pub dialer_attempts_per_second: f64,
+
+    /// See `near_network::config::Dialer::attempts_rate_limit`.
+    #[serde(default = "default_dialer_attempts_burst")]
+    pub dialer_attempts_burst: u64,
+
+    /// See `near_network::config::NetworkConfig::tier1_recv_limit`.
+    #[serde(default = "default_recv_bytes_per_second")]
+    pub tier1_recv_bytes_per_second: f64,
+
+    /// See `near_network::config::NetworkConfig::tier1_recv_limit`.
+    #[serde(default = "default_recv_burst_bytes")]
+    pub tier1_recv_burst_bytes: u64,
+
+    /// See `near_network::config::NetworkConfig::tier2_recv_limit`.
+    #[serde(default = "default_recv_bytes_per_second")]
+    pub tier2_recv_bytes_per_second: f64,
+
+    /// See `near_network::config::NetworkConfig::tier2_recv_limit`.
+    #[serde(default = "default_recv_burst_bytes")]
+    pub tier2_recv_burst_bytes: u64,
+
+    /// See `near_network::config::NetworkConfig::peer_arbiters`.
+    #[serde(default = "default_peer_arbiters")]
+    pub peer_arbiters: usize,
+
+    /// See `near_network::config::NetworkConfig::max_peer_msg_per_min`.
+    /// 0 disables the limit.
+    #[serde(default = "default_max_peer_msg_per_min")]
+    pub max_peer_msg_per_min: usize,
+
+    /// See `near_network::config::NetworkConfig::zstd_compression`.
+    #[serde(default)]
+    pub zstd_compression: bool,
+
+    /// See `near_network::config::NetworkConfig::trace_routed_messages`.
+    #[serde(default)]
+    pub trace_routed_messages: bool,
+
+    /// See `near_network::config::NetworkConfig::measure_block_propagation`.
+    #[serde(default)]
+    pub measure_block_propagation: bool,
+
+    /// See `near_network::config::NetworkConfig::batch_verify_routed_messages`.
+    #[serde(default)]
+    pub batch_verify_routed_messages: bool,
+
+    /// See `near_network::config::NetworkConfig::reject_borsh_peers`.
+    #[serde(default)]
+    pub reject_borsh_peers: bool,
+
+    /// See `near_network::config::NetworkConfig::borsh_max_protocol_version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub borsh_max_protocol_version: Option<ProtocolVersion>,
+
+    /// See `near_network::config::NetworkConfig::fault_injection`.
+    /// Meant only for the integration tests, never set it on a real node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault_injection: Option<FaultInjectionConfig>,
+}
+
+/// See `near_network::config::FaultInjection`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FaultInjectionConfig {
+    /// Faults injected into the messages from the peers not listed in `peers`.
+    #[serde(default)]
+    pub default: FaultProfile,
+    /// Faults injected into the messages from the specific peers.
+    #[serde(default)]
+    pub peers: std::collections::HashMap<near_primitives::network::PeerId, FaultProfile>,
+}
+
+/// See `near_network::config::FaultProfile`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FaultProfile {
+    #[serde(default)]
+    pub latency: Duration,
+    #[serde(default)]
+    pub jitter: Duration,
+    #[serde(default)]
+    pub drop_probability: f64,
+    #[serde(default)]
+    pub reorder_probability: f64,
+    #[serde(default)]
+    pub reorder_delay: Duration,
 }
 
 impl Default for ExperimentalConfig {
@@ -253,8 +566,40 @@ impl Default for ExperimentalConfig {
             skip_sending_tombstones_seconds: default_skip_tombstones(),
             tier1_enable_inbound: default_tier1_enable_inbound(),
             tier1_enable_outbound: default_tier1_enable_outbound(),
+            tier1_send_chunk_parts: default_tier1_send_chunk_parts(),
+            tier1_send_forward_tx: default_tier1_send_forward_tx(),
             tier1_connect_interval: default_tier1_connect_interval(),
             tier1_new_connections_per_attempt: default_tier1_new_connections_per_attempt(),
+            routed_message_dedup_period: default_routed_message_dedup_period(),
+            routed_message_dedup_cache_size: default_routed_message_dedup_cache_size(),
+            routed_message_outbox_ttl: default_routed_message_outbox_ttl(),
+            routed_message_outbox_max_messages_per_account:
+                default_routed_message_outbox_max_messages_per_account(),
+            routed_message_outbox_max_accounts: default_routed_message_outbox_max_accounts(),
+            expensive_requests_max_in_flight: default_expensive_requests_max_in_flight(),
+            expensive_requests_max_rejected: default_expensive_requests_max_rejected(),
+            expensive_requests_rejected_window: default_expensive_requests_rejected_window(),
+            inbound_max_connections_per_ip: default_inbound_max_connections_per_ip(),
+            inbound_connections_per_ip_per_second: default_inbound_connections_per_ip_per_second(),
+            inbound_connections_per_ip_burst: default_inbound_connections_per_ip_burst(),
+            inbound_ip_throttle_duration: default_inbound_ip_throttle_duration(),
+            dialer_min_backoff: default_dialer_min_backoff(),
+            dialer_max_backoff: default_dialer_max_backoff(),
+            dialer_attempts_per_second: default_dialer_attempts_per_second(),
+            dialer_attempts_burst: default_dialer_attempts_burst(),
+            tier1_recv_bytes_per_second: default_recv_bytes_per_second(),
+            tier1_recv_burst_bytes: default_recv_burst_bytes(),
+            tier2_recv_bytes_per_second: default_recv_bytes_per_second(),
+            tier2_recv_burst_bytes: default_recv_burst_bytes(),
+            peer_arbiters: default_peer_arbiters(),
+            max_peer_msg_per_min: default_max_peer_msg_per_min(),
+            zstd_compression: false,
+            trace_routed_messages: false,
+            measure_block_propagation: false,
+            batch_verify_routed_messages: false,
+            reject_borsh_peers: false,
+            borsh_max_protocol_version: None,
+            fault_injection: None,
         }
     }
 }
@@ -263,6 +608,7 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             addr: "0.0.0.0:24567".to_string(),
+            additional_addrs: vec![],
             boot_nodes: "".to_string(),
             whitelist_nodes: "".to_string(),
             max_num_peers: default_max_num_peers(),
@@ -272,18 +618,24 @@ impl Default for Config {
             peer_recent_time_window: default_peer_recent_time_window(),
             safe_set_size: default_safe_set_size(),
             archival_peer_connections_lower_bound: default_archival_peer_connections_lower_bound(),
-            handshake_timeout: Duration::from_secs(20),
+            handshake_timeout: default_handshake_timeout(),
+            tcp_connect_timeout: default_tcp_connect_timeout(),
             skip_sync_wait: false,
             peer_states_cache_size: default_peer_states_cache_size(),
             ban_window: Duration::from_secs(3 * 60 * 60),
+            ban_window_by_reason: Default::default(),
             blacklist: vec![],
+            allowlist: vec![],
             ttl_account_id_router: default_ttl_account_id_router(),
             peer_stats_period: default_peer_stats_period(),
+            keep_alive_period: default_keep_alive_period(),
+            keep_alive_max_missed: default_keep_alive_max_missed(),
             monitor_peers_max_period: default_monitor_peers_max_period(),
             peer_expiration_duration: default_peer_expiration_duration(),
             public_addrs: vec![],
             allow_private_ip_in_public_addrs: false,
             trusted_stun_servers: default_trusted_stun_servers(),
+            socks5_proxy: None,
             experimental: Default::default(),
         }
     }
@@ -1,11 +1,18 @@
 use ::actix::Message;
-use near_primitives::views::{NetworkGraphView, PeerStoreView, RecentOutboundConnectionsView};
+use near_primitives::time;
+use near_primitives::views::{
+    BlockPropagationView, DialerView, NetworkGraphView, PeerStoreView, PingView,
+    RecentOutboundConnectionsView, RoutedMessageTraceView,
+};
 
 // Different debug requests that can be sent by HTML pages, via GET.
 pub enum GetDebugStatus {
     PeerStore,
     Graph,
     RecentOutboundConnections,
+    RoutedMessageTrace,
+    Dialer,
+    BlockPropagation,
 }
 
 #[derive(actix::MessageResponse, Debug)]
@@ -13,8 +20,22 @@ pub enum DebugStatus {
     PeerStore(PeerStoreView),
     Graph(NetworkGraphView),
     RecentOutboundConnections(RecentOutboundConnectionsView),
+    RoutedMessageTrace(RoutedMessageTraceView),
+    Dialer(DialerView),
+    BlockPropagation(BlockPropagationView),
 }
 
 impl Message for GetDebugStatus {
     type Result = DebugStatus;
 }
+
+/// Pings the given targets (PeerIds or AccountIds) through the routing layer,
+/// waiting at most `timeout` for the pongs.
+pub struct PingPeers {
+    pub targets: Vec<String>,
+    pub timeout: time::Duration,
+}
+
+impl Message for PingPeers {
+    type Result = PingView;
+}
@@ -0,0 +1,69 @@
+//! Entry points of the fuzz targets defined in `chain/network/fuzz`.
+//!
+//! The frames received from the peers are parsed before the peer is authenticated in any way,
+//! so the parsing has to handle arbitrary input gracefully. The checks are implemented here
+//! rather than in the fuzz crate, because the encoding API is internal to near_network.
+use crate::network_protocol::{Encoding, PeerMessage};
+
+pub use crate::network_protocol::testonly::{ArbitraryPeerMessage, ArbitraryRoutedMessage};
+
+/// Applies the (offset, xor mask) pairs to `data`.
+fn corrupt(data: &mut [u8], corruption: &[(usize, u8)]) {
+    if data.is_empty() {
+        return;
+    }
+    for (offset, mask) in corruption {
+        let n = data.len();
+        data[offset % n] ^= mask;
+    }
+}
+
+/// Parses `data` in the given encoding. A successfully parsed message has to survive
+/// serialization and parsing again.
+pub fn deserialize(enc: Encoding, data: &[u8]) {
+    if let Ok(msg) = PeerMessage::deserialize(enc, data) {
+        let got = PeerMessage::deserialize(enc, &msg.serialize(enc).unwrap()).unwrap();
+        assert_eq!(msg, got);
+    }
+}
+
+/// Checks that a valid message parses back from its encoding, and that the encodings are
+/// not mutually parsable. Then parses the encoding corrupted according to `corruption`.
+pub fn roundtrip(msg: &ArbitraryPeerMessage, enc: Encoding, corruption: &[(usize, u8)]) {
+    let msg = &msg.0;
+    // Some of the messages (like Tier1Handshake) are not supported by Borsh.
+    let mut data = match msg.serialize(enc) {
+        Some(data) => data,
+        None => return,
+    };
+    let got = PeerMessage::deserialize(enc, &data).unwrap();
+    // Conversion to Borsh drops the fields introduced after Proto.
+    if enc == Encoding::Proto {
+        assert_eq!(*msg, got);
+    }
+    let other = match enc {
+        Encoding::Proto => Encoding::Borsh,
+        Encoding::Borsh => Encoding::Proto,
+    };
+    assert!(PeerMessage::deserialize(other, &data).is_err());
+    corrupt(&mut data, corruption);
+    let _ = PeerMessage::deserialize(enc, &data);
+}
+
+/// Checks the signature verification of the routed messages: a corrupted message which still
+/// passes the verification has to carry the same signed content as the original.
+pub fn routed(msg: &ArbitraryRoutedMessage, corruption: &[(usize, u8)]) {
+    let msg = &msg.0;
+    assert!(msg.verify());
+    for enc in [Encoding::Proto, Encoding::Borsh] {
+        let mut data = PeerMessage::Routed(Box::new(msg.clone())).serialize(enc).unwrap();
+        corrupt(&mut data, corruption);
+        if let Ok(PeerMessage::Routed(got)) = PeerMessage::deserialize(enc, &data) {
+            if got.verify() {
+                assert_eq!(msg.target, got.target);
+                assert_eq!(msg.author, got.author);
+                assert_eq!(msg.body, got.body);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-pub use crate::peer_manager::peer_manager_actor::{Event, PeerManagerActor};
+pub use crate::peer_manager::peer_manager_actor::{Event, EventKind, PeerManagerActor};
 
 mod accounts_data;
 mod network_protocol;
@@ -27,6 +27,9 @@ pub mod types;
 #[cfg(test)]
 pub(crate) mod testonly;
 
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+
 // TODO(gprusak): these should be testonly, once all network integration tests are moved to near_network.
 pub mod broadcast;
 pub mod sink;
@@ -14,6 +14,11 @@ impl From<&net::Handshake> for mem::Handshake {
             sender_chain_info: x.sender_chain_info.clone(),
             partial_edge_info: x.partial_edge_info.clone(),
             owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: mem::EncodingSet::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
         }
     }
 }
@@ -78,12 +83,13 @@ impl From<&mem::HandshakeFailureReason> for net::HandshakeFailureReason {
 
 impl From<net::RoutingTableUpdate> for mem::RoutingTableUpdate {
     fn from(x: net::RoutingTableUpdate) -> Self {
-        Self { edges: x.edges, accounts: x.accounts }
+        Self::new(x.edges, x.accounts)
     }
 }
 
 impl From<mem::RoutingTableUpdate> for net::RoutingTableUpdate {
     fn from(x: mem::RoutingTableUpdate) -> Self {
+        // Key transitions are not supported by the borsh encoding, they are dropped.
         Self { edges: x.edges, accounts: x.accounts }
     }
 }
@@ -136,6 +142,7 @@ impl TryFrom<&net::PeerMessage> for mem::PeerMessage {
                 msg: *r,
                 created_at: None,
                 num_hops: Some(0),
+                trace_id: None,
             })),
             net::PeerMessage::Disconnect => mem::PeerMessage::Disconnect(mem::Disconnect {
                 // This flag is used by the disconnecting peer to advise the other peer that there
@@ -160,13 +167,18 @@ impl TryFrom<&net::PeerMessage> for mem::PeerMessage {
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("{0} is not supported in Borsh encoding")]
+pub struct UnsupportedMessageError(&'static str);
+
 // We are working on deprecating Borsh support for network messages altogether,
 // so any new message variants are simply unsupported.
-impl From<&mem::PeerMessage> for net::PeerMessage {
-    fn from(x: &mem::PeerMessage) -> Self {
-        match x.clone() {
+impl TryFrom<&mem::PeerMessage> for net::PeerMessage {
+    type Error = UnsupportedMessageError;
+    fn try_from(x: &mem::PeerMessage) -> Result<Self, Self::Error> {
+        Ok(match x.clone() {
             mem::PeerMessage::Tier1Handshake(_) => {
-                panic!("Tier1Handshake is not supported in Borsh encoding")
+                return Err(UnsupportedMessageError("Tier1Handshake"))
             }
             mem::PeerMessage::Tier2Handshake(h) => net::PeerMessage::Handshake((&h).into()),
             mem::PeerMessage::HandshakeFailure(pi, hfr) => {
@@ -195,6 +207,11 @@ impl From<&mem::PeerMessage> for net::PeerMessage {
             mem::PeerMessage::Routed(r) => net::PeerMessage::Routed(Box::new(r.msg.clone())),
             mem::PeerMessage::Disconnect(_) => net::PeerMessage::Disconnect,
             mem::PeerMessage::Challenge(c) => net::PeerMessage::Challenge(c),
-        }
+
+            // Keep-alives are sent only to the peers supporting protobuf encoding.
+            mem::PeerMessage::KeepAlive(_) | mem::PeerMessage::KeepAliveResponse(_) => {
+                return Err(UnsupportedMessageError("KeepAlive"))
+            }
+        })
     }
 }
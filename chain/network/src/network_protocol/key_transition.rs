@@ -0,0 +1,99 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_crypto::{SecretKey, Signature};
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+
+/// Statement that the node identified by `old_peer_id` has rotated its node key
+/// and is identified by `new_peer_id` from now on.
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+)]
+pub struct KeyTransition {
+    pub old_peer_id: PeerId,
+    pub new_peer_id: PeerId,
+    /// UNIX timestamp (in seconds) of the rotation. If a node has rotated its key
+    /// multiple times, the newest transition from a given PeerId wins.
+    pub timestamp: i64,
+}
+
+impl KeyTransition {
+    /// Hash signed by both keys. The domain tag prevents reusing the signatures
+    /// for other messages signed with the node key (in particular, for edges).
+    fn hash(&self) -> CryptoHash {
+        CryptoHash::hash_borsh((
+            "KeyTransition",
+            &self.old_peer_id,
+            &self.new_peer_id,
+            self.timestamp,
+        ))
+    }
+
+    /// Signs the transition with both the old and the new node key, proving that the owner
+    /// of the old key approves the new one and that the owner of the new key claims the old
+    /// identity. Panics if the keys don't match the PeerIds.
+    pub fn sign(self, old_key: &SecretKey, new_key: &SecretKey) -> SignedKeyTransition {
+        assert_eq!(self.old_peer_id.public_key(), &old_key.public_key());
+        assert_eq!(self.new_peer_id.public_key(), &new_key.public_key());
+        let hash = self.hash();
+        SignedKeyTransition {
+            old_signature: old_key.sign(hash.as_ref()),
+            new_signature: new_key.sign(hash.as_ref()),
+            transition: self,
+        }
+    }
+}
+
+/// KeyTransition signed by both of the keys involved. It is gossiped together with the
+/// routing table, so that the peers can carry over what they know about `old_peer_id`
+/// (the address, the bans, the whitelist entries) to `new_peer_id`.
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+)]
+pub struct SignedKeyTransition {
+    pub transition: KeyTransition,
+    pub old_signature: Signature,
+    pub new_signature: Signature,
+}
+
+impl std::ops::Deref for SignedKeyTransition {
+    type Target = KeyTransition;
+    fn deref(&self) -> &Self::Target {
+        &self.transition
+    }
+}
+
+impl SignedKeyTransition {
+    /// Constructs a transition between the given keys, timestamped with the current time.
+    pub fn new(clock: &time::Clock, old_key: &SecretKey, new_key: &SecretKey) -> Self {
+        KeyTransition {
+            old_peer_id: PeerId::new(old_key.public_key()),
+            new_peer_id: PeerId::new(new_key.public_key()),
+            timestamp: clock.now_utc().unix_timestamp(),
+        }
+        .sign(old_key, new_key)
+    }
+
+    pub fn verify(&self) -> bool {
+        let hash = self.transition.hash();
+        self.old_peer_id != self.new_peer_id
+            && self.old_signature.verify(hash.as_ref(), self.old_peer_id.public_key())
+            && self.new_signature.verify(hash.as_ref(), self.new_peer_id.public_key())
+    }
+}
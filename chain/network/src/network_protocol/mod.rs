@@ -3,12 +3,16 @@
 mod borsh_;
 mod borsh_conv;
 mod edge;
+mod key_transition;
 mod peer;
 mod proto_conv;
 pub use edge::*;
+pub use key_transition::*;
 pub use peer::*;
 
-#[cfg(test)]
+// The fuzz targets use only a part of the test helpers.
+#[cfg(any(test, feature = "fuzzing"))]
+#[cfg_attr(not(test), allow(dead_code))]
 pub(crate) mod testonly;
 #[cfg(test)]
 mod tests;
@@ -19,9 +23,11 @@ mod _proto {
 
 pub use _proto::network as proto;
 
+use crate::network_protocol::proto::peer_message::Message_type as ProtoMT;
 use crate::network_protocol::proto_conv::trace_context::{
     extract_span_context, inject_trace_context,
 };
+use crate::stats::metrics;
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
 use near_crypto::PublicKey;
 use near_crypto::Signature;
@@ -31,6 +37,7 @@ use near_primitives::challenge::Challenge;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::combine_hash;
 use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::receipt::Receipt;
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart, ReceiptProof, ShardChunkHeader,
 };
@@ -45,9 +52,16 @@ use protobuf::Message as _;
 use std::collections::HashSet;
 use std::fmt;
 use std::fmt::Debug;
+use std::io::Read as _;
 use std::sync::Arc;
 use tracing::Span;
 
+/// zstd compression level used for the compressed PeerMessages.
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+/// Maximal size of a decompressed PeerMessage, same as the maximal size of
+/// an uncompressed message on the wire. Protects against decompression bombs.
+const MAX_DECOMPRESSED_SIZE_BYTES: usize = 512 * bytesize::MIB as usize;
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct PeerAddr {
     pub addr: std::net::SocketAddr,
@@ -108,6 +122,25 @@ pub struct AccountData {
     /// TIER1 nodes should connect to one of the proxies to sent TIER1
     /// messages to the validator.
     pub proxies: Vec<PeerAddr>,
+    /// Latencies between the validator and its proxies, which allow the TIER1 nodes
+    /// to choose the best proxy. A proxy without a hint is considered worse than all
+    /// the proxies with hints.
+    pub proxy_hints: Vec<ProxyHint>,
+}
+
+impl AccountData {
+    /// Latency between the validator and the proxy, as advertised by the validator.
+    pub fn proxy_latency(&self, peer_id: &PeerId) -> Option<time::Duration> {
+        self.proxy_hints.iter().find(|h| &h.peer_id == peer_id).map(|h| h.latency)
+    }
+}
+
+/// Latency between a validator and one of its TIER1 proxies, as measured by the validator.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ProxyHint {
+    pub peer_id: PeerId,
+    /// Round trip time of the handshake of the TIER1 connection from the validator to the proxy.
+    pub latency: time::Duration,
 }
 
 /// Wrapper of the AccountData which adds metadata to it.
@@ -273,19 +306,25 @@ impl SignedOwnedAccount {
 pub struct RoutingTableUpdate {
     pub edges: Vec<Edge>,
     pub accounts: Vec<AnnounceAccount>,
+    /// Node key rotations. Not supported by the borsh encoding.
+    pub key_transitions: Vec<SignedKeyTransition>,
 }
 
 impl RoutingTableUpdate {
     pub(crate) fn from_edges(edges: Vec<Edge>) -> Self {
-        Self { edges, accounts: Vec::new() }
+        Self { edges, accounts: Vec::new(), key_transitions: Vec::new() }
     }
 
     pub fn from_accounts(accounts: Vec<AnnounceAccount>) -> Self {
-        Self { edges: Vec::new(), accounts }
+        Self { edges: Vec::new(), accounts, key_transitions: Vec::new() }
+    }
+
+    pub(crate) fn from_key_transitions(key_transitions: Vec<SignedKeyTransition>) -> Self {
+        Self { edges: Vec::new(), accounts: Vec::new(), key_transitions }
     }
 
     pub(crate) fn new(edges: Vec<Edge>, accounts: Vec<AnnounceAccount>) -> Self {
-        Self { edges, accounts }
+        Self { edges, accounts, key_transitions: Vec::new() }
     }
 }
 /// Structure representing handshake between peers.
@@ -307,6 +346,17 @@ pub struct Handshake {
     pub(crate) partial_edge_info: PartialEdgeInfo,
     /// Account owned by the sender.
     pub(crate) owned_account: Option<SignedOwnedAccount>,
+    /// Whether the sender accepts zstd-compressed messages.
+    pub(crate) supports_zstd_compression: bool,
+    /// All the addrs on which the sender is listening. Empty if the sender
+    /// listens only on `sender_listen_port`.
+    pub(crate) sender_listen_addrs: Vec<std::net::SocketAddr>,
+    /// Encodings in which the sender accepts messages.
+    pub(crate) supported_encodings: EncodingSet,
+    /// Whether the sender accepts messages split into multiple frames.
+    pub(crate) supports_message_fragmentation: bool,
+    /// Whether the sender can decode `RoutedMessageBody::FullChunkRequest`.
+    pub(crate) supports_full_chunk_requests: bool,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr)]
@@ -351,6 +401,12 @@ pub struct Disconnect {
     pub remove_from_connection_store: bool,
 }
 
+/// See KeepAlive in network_protocol/network.proto.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct KeepAlive {
+    pub nonce: u64,
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr, strum::EnumVariantNames)]
 #[allow(clippy::large_enum_variant)]
 pub enum PeerMessage {
@@ -380,6 +436,9 @@ pub enum PeerMessage {
     /// Gracefully disconnect from other peer.
     Disconnect(Disconnect),
     Challenge(Challenge),
+
+    KeepAlive(KeepAlive),
+    KeepAliveResponse(KeepAlive),
 }
 
 impl fmt::Display for PeerMessage {
@@ -389,11 +448,55 @@ impl fmt::Display for PeerMessage {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, strum::IntoStaticStr)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 pub enum Encoding {
     Borsh,
     Proto,
 }
 
+impl Encoding {
+    fn bit(&self) -> u32 {
+        match self {
+            Encoding::Proto => 1 << 0,
+            Encoding::Borsh => 1 << 1,
+        }
+    }
+}
+
+/// Set of encodings, advertised in the Handshake as a bitmap.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct EncodingSet(u32);
+
+impl EncodingSet {
+    pub fn new(encodings: &[Encoding]) -> Self {
+        Self(encodings.iter().fold(0, |bits, e| bits | e.bit()))
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, e: Encoding) -> bool {
+        self.0 & e.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Compression of the messages sent to a peer, negotiated during the handshake.
+/// Only Proto encoded messages can be compressed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParsePeerMessageError {
     #[error("BorshDecode")]
@@ -404,26 +507,99 @@ pub enum ParsePeerMessageError {
     ProtoDecode(#[source] protobuf::Error),
     #[error("ProtoConv")]
     ProtoConv(#[source] proto_conv::ParsePeerMessageError),
+    #[error("compressed message, but compression wasn't negotiated")]
+    CompressionNotNegotiated,
+    #[error("Decompress")]
+    Decompress(#[source] std::io::Error),
+    #[error("decompressed message too large: want <={want_max_bytes}B")]
+    DecompressedTooLarge { want_max_bytes: usize },
 }
 
 impl PeerMessage {
     /// Serializes a message in the given encoding.
     /// If the encoding is `Proto`, then also attaches current Span's context to the message.
-    pub(crate) fn serialize(&self, enc: Encoding) -> Vec<u8> {
-        match enc {
-            Encoding::Borsh => borsh_::PeerMessage::from(self).try_to_vec().unwrap(),
+    /// Returns None if the message is not supported in the encoding.
+    pub(crate) fn serialize(&self, enc: Encoding) -> Option<Vec<u8>> {
+        self.serialize_with_compression(enc, Compression::None)
+    }
+
+    /// Same as `serialize()`, but additionally compresses the message, if it is large enough
+    /// (see `compression_threshold()`). Compression is applied only to the Proto encoding.
+    pub(crate) fn serialize_with_compression(
+        &self,
+        enc: Encoding,
+        compression: Compression,
+    ) -> Option<Vec<u8>> {
+        Some(match enc {
+            Encoding::Borsh => match borsh_::PeerMessage::try_from(self) {
+                Ok(msg) => msg.try_to_vec().unwrap(),
+                Err(err) => {
+                    tracing::debug!(target: "network", %err, "message not serialized");
+                    return None;
+                }
+            },
             Encoding::Proto => {
                 let mut msg = proto::PeerMessage::from(self);
                 let cx = Span::current().context();
                 msg.trace_context = inject_trace_context(&cx);
-                msg.write_to_bytes().unwrap()
+                let bytes = msg.write_to_bytes().unwrap();
+                if compression == Compression::None
+                    || self.compression_threshold().map_or(true, |min_len| bytes.len() < min_len)
+                {
+                    return Some(bytes);
+                }
+                let zstd = match zstd::bulk::compress(&bytes, ZSTD_COMPRESSION_LEVEL) {
+                    Ok(zstd) => zstd,
+                    Err(err) => {
+                        tracing::warn!(target: "network", ?err, "failed to compress a message, sending it uncompressed");
+                        return Some(bytes);
+                    }
+                };
+                metrics::PEER_MESSAGE_COMPRESSION_RATIO
+                    .with_label_values(&[self.msg_variant()])
+                    .observe(zstd.len() as f64 / bytes.len() as f64);
+                proto::PeerMessage {
+                    message_type: Some(ProtoMT::Compressed(proto::Compressed {
+                        zstd,
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }
+                .write_to_bytes()
+                .unwrap()
             }
+        })
+    }
+
+    /// Minimal size of the serialized message, from which on it is worth compressing.
+    /// None for the message types which are never compressed.
+    fn compression_threshold(&self) -> Option<usize> {
+        const KIB: usize = bytesize::KIB as usize;
+        match self {
+            PeerMessage::Block(_) | PeerMessage::BlockHeaders(_) => Some(64 * KIB),
+            PeerMessage::SyncRoutingTable(_) => Some(16 * KIB),
+            PeerMessage::Routed(msg) => match msg.body {
+                RoutedMessageBody::StateResponse(_)
+                | RoutedMessageBody::VersionedStateResponse(_) => Some(64 * KIB),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
     pub(crate) fn deserialize(
         enc: Encoding,
         data: &[u8],
+    ) -> Result<PeerMessage, ParsePeerMessageError> {
+        Self::deserialize_with_compression(enc, data, Compression::None)
+    }
+
+    /// Same as `deserialize()`, but additionally accepts messages compressed with the
+    /// given compression. Compressed messages are rejected if it is `Compression::None`.
+    pub(crate) fn deserialize_with_compression(
+        enc: Encoding,
+        data: &[u8],
+        compression: Compression,
     ) -> Result<PeerMessage, ParsePeerMessageError> {
         let span = tracing::trace_span!(target: "network", "deserialize").entered();
         Ok(match enc {
@@ -432,8 +608,16 @@ impl PeerMessage {
                 .try_into()
                 .map_err(ParsePeerMessageError::BorshConv)?,
             Encoding::Proto => {
-                let proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
+                let mut proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
                     .map_err(ParsePeerMessageError::ProtoDecode)?;
+                if let Some(ProtoMT::Compressed(compressed)) = &proto_msg.message_type {
+                    if compression != Compression::Zstd {
+                        return Err(ParsePeerMessageError::CompressionNotNegotiated);
+                    }
+                    let data = decompress(&compressed.zstd)?;
+                    proto_msg = proto::PeerMessage::parse_from_bytes(&data)
+                        .map_err(ParsePeerMessageError::ProtoDecode)?;
+                }
                 if let Ok(extracted_span_context) = extract_span_context(&proto_msg.trace_context) {
                     span.clone().or_current().add_link(extracted_span_context);
                 }
@@ -450,6 +634,23 @@ impl PeerMessage {
     }
 }
 
+/// Decompresses a zstd-compressed message, rejecting messages which decompress to more
+/// than MAX_DECOMPRESSED_SIZE_BYTES.
+fn decompress(zstd: &[u8]) -> Result<Vec<u8>, ParsePeerMessageError> {
+    let mut data = vec![];
+    zstd::stream::read::Decoder::new(zstd)
+        .map_err(ParsePeerMessageError::Decompress)?
+        .take(MAX_DECOMPRESSED_SIZE_BYTES as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(ParsePeerMessageError::Decompress)?;
+    if data.len() > MAX_DECOMPRESSED_SIZE_BYTES {
+        return Err(ParsePeerMessageError::DecompressedTooLarge {
+            want_max_bytes: MAX_DECOMPRESSED_SIZE_BYTES,
+        });
+    }
+    Ok(data)
+}
+
 // TODO(#1313): Use Box
 #[derive(
     borsh::BorshSerialize, borsh::BorshDeserialize, PartialEq, Eq, Clone, strum::IntoStaticStr,
@@ -493,6 +694,10 @@ pub enum RoutedMessageBody {
     VersionedPartialEncodedChunk(PartialEncodedChunk),
     VersionedStateResponse(StateResponseInfo),
     PartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
+    /// Request for the whole body of the chunk. If the receiver can't serve it, it responds
+    /// with the requested parts and receipts instead, as to PartialEncodedChunkRequest.
+    FullChunkRequest(PartialEncodedChunkRequestMsg),
+    FullChunkResponse(FullChunkResponseMsg),
 }
 
 impl RoutedMessageBody {
@@ -566,6 +771,16 @@ impl fmt::Debug for RoutedMessageBody {
             ),
             RoutedMessageBody::Ping(_) => write!(f, "Ping"),
             RoutedMessageBody::Pong(_) => write!(f, "Pong"),
+            RoutedMessageBody::FullChunkRequest(request) => {
+                write!(f, "FullChunkRequest({:?})", request.chunk_hash)
+            }
+            RoutedMessageBody::FullChunkResponse(response) => write!(
+                f,
+                "FullChunkResponse({:?}, {} txs, {} receipts)",
+                response.chunk_hash,
+                response.transactions.len(),
+                response.receipts.len()
+            ),
         }
     }
 }
@@ -603,6 +818,9 @@ pub struct RoutedMessageV2 {
     /// Number of peers this routed message travelled through.
     /// Doesn't include the peers that are the source and the destination of the message.
     pub num_hops: Option<i32>,
+    /// Correlation id assigned by `author`, if routed message tracing is enabled.
+    /// See peer_manager::routed_trace.
+    pub trace_id: Option<u64>,
 }
 
 impl std::ops::Deref for RoutedMessageV2 {
@@ -651,6 +869,7 @@ impl RoutedMessage {
                 | RoutedMessageBody::StateRequestHeader(_, _)
                 | RoutedMessageBody::StateRequestPart(_, _, _)
                 | RoutedMessageBody::PartialEncodedChunkRequest(_)
+                | RoutedMessageBody::FullChunkRequest(_)
                 | RoutedMessageBody::ReceiptOutcomeRequest(_)
         )
     }
@@ -738,6 +957,15 @@ pub struct PartialEncodedChunkResponseMsg {
     pub receipts: Vec<ReceiptProof>,
 }
 
+/// Body of a chunk, i.e. its transactions and outgoing receipts. The receiver checks it
+/// against the encoded merkle root from the chunk header.
+#[derive(Clone, Debug, Eq, PartialEq, borsh::BorshSerialize, borsh::BorshDeserialize)]
+pub struct FullChunkResponseMsg {
+    pub chunk_hash: ChunkHash,
+    pub transactions: Vec<SignedTransaction>,
+    pub receipts: Vec<Receipt>,
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, borsh::BorshSerialize, borsh::BorshDeserialize)]
 pub struct StateResponseInfoV1 {
     pub shard_id: ShardId,
@@ -824,6 +1052,7 @@ impl RawRoutedMessage {
             },
             created_at: now,
             num_hops: Some(0),
+            trace_id: None,
         }
     }
 }
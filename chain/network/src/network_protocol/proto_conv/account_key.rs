@@ -4,11 +4,39 @@ use super::*;
 use crate::network_protocol::proto;
 use crate::network_protocol::proto::account_key_payload::Payload_type as ProtoPT;
 use crate::network_protocol::{
-    AccountData, AccountKeySignedPayload, OwnedAccount, SignedAccountData, SignedOwnedAccount,
-    VersionedAccountData,
+    AccountData, AccountKeySignedPayload, OwnedAccount, ProxyHint, SignedAccountData,
+    SignedOwnedAccount, VersionedAccountData,
 };
 use protobuf::{Message as _, MessageField as MF};
 
+#[derive(thiserror::Error, Debug)]
+pub enum ParseProxyHintError {
+    #[error("peer_id: {0}")]
+    PeerId(ParseRequiredError<ParsePublicKeyError>),
+}
+
+impl From<&ProxyHint> for proto::ProxyHint {
+    fn from(x: &ProxyHint) -> Self {
+        Self {
+            peer_id: MF::some((&x.peer_id).into()),
+            latency_us: x.latency.whole_microseconds().clamp(0, u64::MAX as i128) as u64,
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::ProxyHint> for ProxyHint {
+    type Error = ParseProxyHintError;
+    fn try_from(x: &proto::ProxyHint) -> Result<Self, Self::Error> {
+        Ok(Self {
+            peer_id: try_from_required(&x.peer_id).map_err(Self::Error::PeerId)?,
+            latency: near_primitives::time::Duration::microseconds(
+                x.latency_us.min(i64::MAX as u64) as i64,
+            ),
+        })
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseAccountDataError {
     #[error("bad payload type")]
@@ -19,6 +47,8 @@ pub enum ParseAccountDataError {
     AccountKey(ParseRequiredError<ParsePublicKeyError>),
     #[error("peers: {0}")]
     Peers(ParseVecError<ParsePeerAddrError>),
+    #[error("proxy_hints: {0}")]
+    ProxyHints(ParseVecError<ParseProxyHintError>),
     #[error("timestamp: {0}")]
     Timestamp(ParseRequiredError<ParseTimestampError>),
 }
@@ -31,6 +61,7 @@ impl From<&VersionedAccountData> for proto::AccountKeyPayload {
                 peer_id: MF::some((&x.peer_id).into()),
                 account_key: MF::some((&x.account_key).into()),
                 proxies: x.proxies.iter().map(Into::into).collect(),
+                proxy_hints: x.proxy_hints.iter().map(Into::into).collect(),
                 version: x.version,
                 timestamp: MF::some(utc_to_proto(&x.timestamp)),
                 ..Default::default()
@@ -51,6 +82,7 @@ impl TryFrom<&proto::AccountKeyPayload> for VersionedAccountData {
             data: AccountData {
                 peer_id: try_from_required(&x.peer_id).map_err(Self::Error::PeerId)?,
                 proxies: try_from_slice(&x.proxies).map_err(Self::Error::Peers)?,
+                proxy_hints: try_from_slice(&x.proxy_hints).map_err(Self::Error::ProxyHints)?,
             },
             account_key: try_from_required(&x.account_key).map_err(Self::Error::AccountKey)?,
             version: x.version,
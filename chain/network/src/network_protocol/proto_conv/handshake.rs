@@ -2,7 +2,7 @@
 use super::*;
 
 use crate::network_protocol::proto;
-use crate::network_protocol::{Handshake, HandshakeFailureReason};
+use crate::network_protocol::{EncodingSet, Handshake, HandshakeFailureReason};
 use crate::network_protocol::{PeerChainInfoV2, PeerInfo};
 use near_primitives::block::GenesisId;
 use protobuf::MessageField as MF;
@@ -77,6 +77,8 @@ pub enum ParseHandshakeError {
     PartialEdgeInfo(ParseRequiredError<ParsePartialEdgeInfoError>),
     #[error("owned_account {0}")]
     OwnedAccount(ParseSignedOwnedAccountError),
+    #[error("sender_listen_addrs {0}")]
+    SenderListenAddrs(ParseVecError<ParseSocketAddrError>),
 }
 
 impl From<&Handshake> for proto::Handshake {
@@ -90,6 +92,11 @@ impl From<&Handshake> for proto::Handshake {
             sender_chain_info: MF::some((&x.sender_chain_info).into()),
             partial_edge_info: MF::some((&x.partial_edge_info).into()),
             owned_account: x.owned_account.as_ref().map(Into::into).into(),
+            supports_zstd_compression: x.supports_zstd_compression,
+            sender_listen_addrs: x.sender_listen_addrs.iter().map(Into::into).collect(),
+            supported_encodings: x.supported_encodings.bits(),
+            supports_message_fragmentation: x.supports_message_fragmentation,
+            supports_full_chunk_requests: x.supports_full_chunk_requests,
             ..Self::default()
         }
     }
@@ -120,6 +127,12 @@ impl TryFrom<&proto::Handshake> for Handshake {
                 .map_err(Self::Error::PartialEdgeInfo)?,
             owned_account: try_from_optional(&p.owned_account)
                 .map_err(Self::Error::OwnedAccount)?,
+            supports_zstd_compression: p.supports_zstd_compression,
+            sender_listen_addrs: try_from_slice(&p.sender_listen_addrs)
+                .map_err(Self::Error::SenderListenAddrs)?,
+            supported_encodings: EncodingSet::from_bits(p.supported_encodings),
+            supports_message_fragmentation: p.supports_message_fragmentation,
+            supports_full_chunk_requests: p.supports_full_chunk_requests,
         })
     }
 }
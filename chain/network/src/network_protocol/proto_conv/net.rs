@@ -3,7 +3,7 @@ use super::*;
 
 use crate::network_protocol::proto;
 use crate::network_protocol::PeerAddr;
-use crate::network_protocol::{Edge, PartialEdgeInfo, PeerInfo};
+use crate::network_protocol::{Edge, PartialEdgeInfo, PeerInfo, SignedKeyTransition};
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
 use near_primitives::network::AnnounceAccount;
 use protobuf::MessageField as MF;
@@ -140,3 +140,20 @@ impl TryFrom<&proto::AnnounceAccount> for AnnounceAccount {
         Self::try_from_slice(&x.borsh)
     }
 }
+
+////////////////////////////////////////
+
+pub type ParseKeyTransitionError = borsh::maybestd::io::Error;
+
+impl From<&SignedKeyTransition> for proto::KeyTransition {
+    fn from(x: &SignedKeyTransition) -> Self {
+        Self { borsh: x.try_to_vec().unwrap(), ..Self::default() }
+    }
+}
+
+impl TryFrom<&proto::KeyTransition> for SignedKeyTransition {
+    type Error = ParseKeyTransitionError;
+    fn try_from(x: &proto::KeyTransition) -> Result<Self, Self::Error> {
+        Self::try_from_slice(&x.borsh)
+    }
+}
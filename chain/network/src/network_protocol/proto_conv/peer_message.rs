@@ -4,7 +4,8 @@ use super::*;
 use crate::network_protocol::proto;
 use crate::network_protocol::proto::peer_message::Message_type as ProtoMT;
 use crate::network_protocol::{
-    Disconnect, PeerMessage, PeersRequest, PeersResponse, RoutingTableUpdate, SyncAccountsData,
+    Disconnect, KeepAlive, PeerMessage, PeersRequest, PeersResponse, RoutingTableUpdate,
+    SyncAccountsData,
 };
 use crate::network_protocol::{RoutedMessage, RoutedMessageV2};
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
@@ -21,6 +22,8 @@ pub enum ParseRoutingTableUpdateError {
     Edges(ParseVecError<ParseEdgeError>),
     #[error("accounts {0}")]
     Accounts(ParseVecError<ParseAnnounceAccountError>),
+    #[error("key_transitions {0}")]
+    KeyTransitions(ParseVecError<ParseKeyTransitionError>),
 }
 
 impl From<&RoutingTableUpdate> for proto::RoutingTableUpdate {
@@ -28,6 +31,7 @@ impl From<&RoutingTableUpdate> for proto::RoutingTableUpdate {
         Self {
             edges: x.edges.iter().map(Into::into).collect(),
             accounts: x.accounts.iter().map(Into::into).collect(),
+            key_transitions: x.key_transitions.iter().map(Into::into).collect(),
             ..Default::default()
         }
     }
@@ -39,6 +43,8 @@ impl TryFrom<&proto::RoutingTableUpdate> for RoutingTableUpdate {
         Ok(Self {
             edges: try_from_slice(&x.edges).map_err(Self::Error::Edges)?,
             accounts: try_from_slice(&x.accounts).map_err(Self::Error::Accounts)?,
+            key_transitions: try_from_slice(&x.key_transitions)
+                .map_err(Self::Error::KeyTransitions)?,
         })
     }
 }
@@ -149,6 +155,7 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     borsh: r.msg.try_to_vec().unwrap(),
                     created_at: MF::from_option(r.created_at.as_ref().map(utc_to_proto)),
                     num_hops: r.num_hops,
+                    trace_id: r.trace_id,
                     ..Default::default()
                 }),
                 PeerMessage::Disconnect(r) => ProtoMT::Disconnect(proto::Disconnect {
@@ -159,6 +166,13 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     borsh: r.try_to_vec().unwrap(),
                     ..Default::default()
                 }),
+                PeerMessage::KeepAlive(k) => {
+                    ProtoMT::KeepAlive(proto::KeepAlive { nonce: k.nonce, ..Default::default() })
+                }
+                PeerMessage::KeepAliveResponse(k) => ProtoMT::KeepAliveResponse(proto::KeepAlive {
+                    nonce: k.nonce,
+                    ..Default::default()
+                }),
             }),
             ..Default::default()
         }
@@ -208,6 +222,8 @@ pub enum ParsePeerMessageError {
     RoutedCreatedAtTimestamp(ComponentRange),
     #[error("sync_accounts_data: {0}")]
     SyncAccountsData(ParseVecError<ParseSignedAccountDataError>),
+    #[error("compressed message has to be decompressed before conversion")]
+    Compressed,
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -235,12 +251,9 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
                     .map_err(Self::Error::UpdateNonceRequest)?,
             ),
             ProtoMT::UpdateNonceResponse(unr) => {
-                PeerMessage::SyncRoutingTable(RoutingTableUpdate {
-                    edges: vec![
-                        try_from_required(&unr.edge).map_err(Self::Error::UpdateNonceResponse)?
-                    ],
-                    accounts: vec![],
-                })
+                PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_edges(vec![
+                    try_from_required(&unr.edge).map_err(Self::Error::UpdateNonceResponse)?,
+                ]))
             }
             ProtoMT::SyncAccountsData(msg) => PeerMessage::SyncAccountsData(SyncAccountsData {
                 accounts_data: try_from_slice(&msg.accounts_data)
@@ -284,6 +297,7 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
                     .transpose()
                     .map_err(Self::Error::RoutedCreatedAtTimestamp)?,
                 num_hops: r.num_hops,
+                trace_id: r.trace_id,
             })),
             ProtoMT::Disconnect(d) => PeerMessage::Disconnect(Disconnect {
                 remove_from_connection_store: d.remove_from_connection_store,
@@ -291,6 +305,11 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::Challenge(c) => PeerMessage::Challenge(
                 Challenge::try_from_slice(&c.borsh).map_err(Self::Error::Challenge)?,
             ),
+            ProtoMT::Compressed(_) => return Err(Self::Error::Compressed),
+            ProtoMT::KeepAlive(k) => PeerMessage::KeepAlive(KeepAlive { nonce: k.nonce }),
+            ProtoMT::KeepAliveResponse(k) => {
+                PeerMessage::KeepAliveResponse(KeepAlive { nonce: k.nonce })
+            }
         })
     }
 }
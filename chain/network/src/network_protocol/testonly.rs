@@ -69,6 +69,7 @@ pub fn make_block(
         CryptoHash::default(), // block_merkle_root
         // TODO: migrate to clock.now()
         Some(chrono::Utc::now()), // timestamp_override
+        None,                     // protocol_version_vote_override
     )
 }
 
@@ -103,6 +104,10 @@ pub fn make_peer_info<R: Rng>(rng: &mut R) -> PeerInfo {
     }
 }
 
+pub fn make_key_transition<R: Rng>(rng: &mut R, clock: &time::Clock) -> SignedKeyTransition {
+    SignedKeyTransition::new(clock, &make_secret_key(rng), &make_secret_key(rng))
+}
+
 pub fn make_announce_account<R: Rng>(rng: &mut R) -> AnnounceAccount {
     let peer_id = make_peer_id(rng);
     let validator_signer = make_validator_signer(rng);
@@ -155,6 +160,7 @@ pub fn make_routing_table<R: Rng>(rng: &mut R) -> RoutingTableUpdate {
             }
             e
         },
+        key_transitions: vec![],
     }
 }
 
@@ -287,6 +293,7 @@ impl Chain {
             tracked_shards: Default::default(),
             block: self.blocks.last().unwrap().clone(),
             tier1_accounts: Arc::new(self.get_tier1_accounts()),
+            next_epoch_accounts: Default::default(),
         }
     }
 
@@ -346,6 +353,11 @@ pub fn make_handshake<R: Rng>(rng: &mut R, chain: &Chain) -> Handshake {
         sender_chain_info: chain.get_peer_chain_info(),
         partial_edge_info: make_partial_edge(rng),
         owned_account: None,
+        supports_zstd_compression: false,
+        sender_listen_addrs: vec![],
+        supported_encodings: Default::default(),
+        supports_message_fragmentation: false,
+        supports_full_chunk_requests: false,
     }
 }
 
@@ -381,26 +393,28 @@ pub fn make_account_data(
     account_key: PublicKey,
     peer_id: PeerId,
 ) -> VersionedAccountData {
-    VersionedAccountData {
-        data: AccountData {
-            proxies: vec![
-                // Can't inline make_ipv4/ipv6 calls, because 2-phase borrow
-                // doesn't work.
-                {
-                    let ip = make_ipv4(rng);
-                    make_peer_addr(rng, ip)
-                },
-                {
-                    let ip = make_ipv4(rng);
-                    make_peer_addr(rng, ip)
-                },
-                {
-                    let ip = make_ipv6(rng);
-                    make_peer_addr(rng, ip)
-                },
-            ],
-            peer_id,
+    let proxies = vec![
+        // Can't inline make_ipv4/ipv6 calls, because 2-phase borrow
+        // doesn't work.
+        {
+            let ip = make_ipv4(rng);
+            make_peer_addr(rng, ip)
+        },
+        {
+            let ip = make_ipv4(rng);
+            make_peer_addr(rng, ip)
+        },
+        {
+            let ip = make_ipv6(rng);
+            make_peer_addr(rng, ip)
         },
+    ];
+    let proxy_hints = vec![ProxyHint {
+        peer_id: proxies[0].peer_id.clone(),
+        latency: time::Duration::microseconds(rng.gen_range(0..1_000_000)),
+    }];
+    VersionedAccountData {
+        data: AccountData { proxies, proxy_hints, peer_id },
         account_key,
         version,
         timestamp,
@@ -422,3 +436,107 @@ impl SignedAccountData {
         &mut self.payload.signature
     }
 }
+
+/// PeerMessage generated out of the fuzzer input.
+/// Arbitrary can't be derived for PeerMessage, since most of the nested types are defined in
+/// near_primitives. Instead the input selects the variant and seeds the rng passed to the
+/// make_* functions, so that the generated messages have valid hashes and signatures.
+#[cfg(feature = "fuzzing")]
+#[derive(Debug)]
+pub struct ArbitraryPeerMessage(pub PeerMessage);
+
+#[cfg(feature = "fuzzing")]
+impl<'a> arbitrary::Arbitrary<'a> for ArbitraryPeerMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut rng: rand_xorshift::XorShiftRng = rand::SeedableRng::seed_from_u64(u.arbitrary()?);
+        let rng = &mut rng;
+        let mut clock = time::FakeClock::default();
+        let chain = Chain::make(&mut clock, rng, u.int_in_range(2..=4)?);
+        let block = u.choose(&chain.blocks)?;
+        Ok(Self(match u.int_in_range(0..=16)? {
+            0 => PeerMessage::Tier1Handshake(make_handshake(rng, &chain)),
+            1 => PeerMessage::Tier2Handshake(make_handshake(rng, &chain)),
+            2 => PeerMessage::HandshakeFailure(
+                make_peer_info(rng),
+                HandshakeFailureReason::InvalidTarget,
+            ),
+            3 => PeerMessage::LastEdge(make_edge(
+                &make_secret_key(rng),
+                &make_secret_key(rng),
+                u.int_in_range(1..=u64::MAX)?,
+            )),
+            4 => PeerMessage::SyncRoutingTable(make_routing_table(rng)),
+            5 => PeerMessage::RequestUpdateNonce(make_partial_edge(rng)),
+            6 => PeerMessage::SyncAccountsData(SyncAccountsData {
+                accounts_data: chain.make_tier1_data(rng, &clock.clock()),
+                requesting_full_sync: u.arbitrary()?,
+                incremental: u.arbitrary()?,
+            }),
+            7 => PeerMessage::PeersRequest(PeersRequest {
+                max_peers: u.arbitrary()?,
+                max_direct_peers: u.arbitrary()?,
+            }),
+            8 => PeerMessage::PeersResponse(PeersResponse {
+                peers: (0..u.int_in_range(0..=5)?).map(|_| make_peer_info(rng)).collect(),
+                direct_peers: vec![],
+            }),
+            9 => PeerMessage::BlockHeadersRequest(chain.blocks.iter().map(|b| *b.hash()).collect()),
+            10 => PeerMessage::BlockHeaders(chain.get_block_headers()),
+            11 => PeerMessage::BlockRequest(*block.hash()),
+            12 => PeerMessage::Block(block.clone()),
+            13 => PeerMessage::Transaction(make_signed_transaction(rng)),
+            14 => {
+                let body = arbitrary_routed_message_body(u, rng, &chain)?;
+                PeerMessage::Routed(Box::new(make_routed_message(rng, body)))
+            }
+            15 => {
+                PeerMessage::Disconnect(Disconnect { remove_from_connection_store: u.arbitrary()? })
+            }
+            _ => PeerMessage::Challenge(make_challenge(rng)),
+        }))
+    }
+}
+
+/// Signed RoutedMessageV2 generated out of the fuzzer input. See ArbitraryPeerMessage.
+#[cfg(feature = "fuzzing")]
+#[derive(Debug)]
+pub struct ArbitraryRoutedMessage(pub RoutedMessageV2);
+
+#[cfg(feature = "fuzzing")]
+impl<'a> arbitrary::Arbitrary<'a> for ArbitraryRoutedMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut rng: rand_xorshift::XorShiftRng = rand::SeedableRng::seed_from_u64(u.arbitrary()?);
+        let rng = &mut rng;
+        let mut clock = time::FakeClock::default();
+        let chain = Chain::make(&mut clock, rng, 2);
+        let body = arbitrary_routed_message_body(u, rng, &chain)?;
+        Ok(Self(make_routed_message(rng, body)))
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+fn arbitrary_routed_message_body<R: Rng>(
+    u: &mut arbitrary::Unstructured<'_>,
+    rng: &mut R,
+    chain: &Chain,
+) -> arbitrary::Result<RoutedMessageBody> {
+    let block = u.choose(&chain.blocks)?;
+    let chunk_hash = block.chunks()[0].chunk_hash();
+    Ok(match u.int_in_range(0..=6)? {
+        0 => RoutedMessageBody::Ping(Ping { nonce: u.arbitrary()?, source: make_peer_id(rng) }),
+        1 => RoutedMessageBody::Pong(Pong { nonce: u.arbitrary()?, source: make_peer_id(rng) }),
+        2 => RoutedMessageBody::TxStatusRequest(make_account_id(rng), make_hash(rng)),
+        3 => RoutedMessageBody::ForwardTx(make_signed_transaction(rng)),
+        4 => RoutedMessageBody::StateRequestPart(u.arbitrary()?, *block.hash(), u.arbitrary()?),
+        5 => RoutedMessageBody::PartialEncodedChunkRequest(PartialEncodedChunkRequestMsg {
+            chunk_hash,
+            part_ords: u.arbitrary()?,
+            tracking_shards: Default::default(),
+        }),
+        _ => RoutedMessageBody::PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg {
+            parts: make_chunk_parts(chain.chunks[&chunk_hash].clone()),
+            chunk_hash,
+            receipts: vec![],
+        }),
+    })
+}
@@ -5,8 +5,18 @@ use crate::testonly::make_rng;
 use crate::types::{Disconnect, HandshakeFailureReason, PeerMessage};
 use crate::types::{PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg};
 use anyhow::{bail, Context as _};
+use chrono::TimeZone as _;
 use itertools::Itertools as _;
+use near_primitives::block::genesis_chunks;
+use near_primitives::num_rational::Ratio;
+use near_primitives::syncing::ShardStateSyncResponseV2;
 use near_primitives::time;
+use near_primitives::types::{EpochId, ProtocolVersion, StateRoot};
+use near_primitives::views::{
+    ExecutionMetadataView, ExecutionOutcomeView, ExecutionOutcomeWithIdView, ExecutionStatusView,
+    FinalExecutionStatus,
+};
+use protobuf::Message as _;
 use rand::Rng as _;
 
 #[test]
@@ -32,6 +42,30 @@ fn deduplicate_edges() {
     }
 }
 
+#[test]
+fn key_transition_signatures() {
+    let mut rng = make_rng(48294710395);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let a = data::make_secret_key(rng);
+    let b = data::make_secret_key(rng);
+    let c = data::make_secret_key(rng);
+    let ab = SignedKeyTransition::new(&clock.clock(), &a, &b);
+    assert!(ab.verify());
+    // Claiming a key without the consent of its owner.
+    let mut ac = ab.clone();
+    ac.transition.new_peer_id = PeerId::new(c.public_key());
+    assert!(!ac.verify());
+    // Rotating a key without the consent of its owner.
+    let cb = KeyTransition { old_peer_id: PeerId::new(c.public_key()), ..ab.transition.clone() };
+    let cb = SignedKeyTransition { transition: cb, ..ab.clone() };
+    assert!(!cb.verify());
+    // The signatures of a transition are not valid for any other transition.
+    let mut ab2 = ab;
+    ab2.transition.timestamp += 1;
+    assert!(!ab2.verify());
+}
+
 #[test]
 fn bad_account_data_size() {
     let mut rng = make_rng(19385389);
@@ -47,6 +81,7 @@ fn bad_account_data_size() {
                     data::make_peer_addr(&mut rng, ip)
                 })
                 .collect(),
+            proxy_hints: vec![],
             peer_id: data::make_peer_id(&mut rng),
         },
         account_key: signer.public_key(),
@@ -62,7 +97,10 @@ fn serialize_deserialize_protobuf_only() {
     let mut clock = time::FakeClock::default();
     let chain = data::Chain::make(&mut clock, &mut rng, 12);
     let msgs = [
-        PeerMessage::Tier1Handshake(data::make_handshake(&mut rng, &chain)),
+        PeerMessage::Tier1Handshake(Handshake {
+            supported_encodings: EncodingSet::new(&[Encoding::Proto]),
+            ..data::make_handshake(&mut rng, &chain)
+        }),
         PeerMessage::SyncAccountsData(SyncAccountsData {
             accounts_data: (0..4)
                 .map(|_| Arc::new(data::make_signed_account_data(&mut rng, &clock.clock())))
@@ -70,15 +108,87 @@ fn serialize_deserialize_protobuf_only() {
             incremental: true,
             requesting_full_sync: true,
         }),
+        PeerMessage::SyncRoutingTable(RoutingTableUpdate {
+            key_transitions: (0..3)
+                .map(|_| data::make_key_transition(&mut rng, &clock.clock()))
+                .collect(),
+            ..data::make_routing_table(&mut rng)
+        }),
+        PeerMessage::KeepAlive(KeepAlive { nonce: 7 }),
+        PeerMessage::KeepAliveResponse(KeepAlive { nonce: 7 }),
     ];
     for m in msgs {
-        let m2 = PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto))
+        let m2 = PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto).unwrap())
             .with_context(|| m.to_string())
             .unwrap();
         assert_eq!(m, m2);
     }
 }
 
+#[test]
+fn keep_alive_not_encoded_in_borsh() {
+    assert_eq!(None, PeerMessage::KeepAlive(KeepAlive { nonce: 7 }).serialize(Encoding::Borsh));
+    assert_eq!(
+        None,
+        PeerMessage::KeepAliveResponse(KeepAlive { nonce: 7 }).serialize(Encoding::Borsh)
+    );
+}
+
+#[test]
+fn serialize_deserialize_compressed() {
+    let mut rng = make_rng(51203983475);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 12);
+    let signers: Vec<_> = (0..20).map(|_| data::make_secret_key(&mut rng)).collect();
+    let mut edges = vec![];
+    for i in 0..signers.len() {
+        for j in 0..i {
+            edges.push(data::make_edge(&signers[i], &signers[j], 1));
+        }
+    }
+    let large = PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_edges(edges));
+    let small = PeerMessage::Tier2Handshake(Handshake {
+        supports_zstd_compression: true,
+        ..data::make_handshake(&mut rng, &chain)
+    });
+    let is_compressed = |bytes: &[u8]| {
+        matches!(
+            proto::PeerMessage::parse_from_bytes(bytes).unwrap().message_type,
+            Some(ProtoMT::Compressed(_))
+        )
+    };
+    for (m, want_compressed) in [(large, true), (small, false)] {
+        let bytes = m.serialize_with_compression(Encoding::Proto, Compression::Zstd).unwrap();
+        assert_eq!(want_compressed, is_compressed(&bytes), "{m}");
+        assert!(!is_compressed(&m.serialize(Encoding::Proto).unwrap()));
+        let m2 =
+            PeerMessage::deserialize_with_compression(Encoding::Proto, &bytes, Compression::Zstd)
+                .with_context(|| m.to_string())
+                .unwrap();
+        assert_eq!(m, m2);
+        // With the compression negotiated, uncompressed messages are still accepted.
+        let m2 = PeerMessage::deserialize_with_compression(
+            Encoding::Proto,
+            &m.serialize(Encoding::Proto).unwrap(),
+            Compression::Zstd,
+        )
+        .unwrap();
+        assert_eq!(m, m2);
+        // Borsh messages are never compressed.
+        assert_eq!(
+            m.serialize(Encoding::Borsh),
+            m.serialize_with_compression(Encoding::Borsh, Compression::Zstd)
+        );
+        // Compressed messages are rejected unless the compression has been negotiated.
+        if want_compressed {
+            assert!(matches!(
+                PeerMessage::deserialize(Encoding::Proto, &bytes),
+                Err(ParsePeerMessageError::CompressionNotNegotiated)
+            ));
+        }
+    }
+}
+
 #[test]
 fn serialize_deserialize() -> anyhow::Result<()> {
     let mut rng = make_rng(89028037453);
@@ -135,7 +245,7 @@ fn serialize_deserialize() -> anyhow::Result<()> {
     for enc in [Encoding::Proto, Encoding::Borsh] {
         for m in &msgs {
             (|| {
-                let m2 = PeerMessage::deserialize(enc, &m.serialize(enc))
+                let m2 = PeerMessage::deserialize(enc, &m.serialize(enc).unwrap())
                     .with_context(|| m.to_string())?;
                 if *m != m2 {
                     bail!("deserialize(serialize({m}) = {m2}");
@@ -149,16 +259,16 @@ fn serialize_deserialize() -> anyhow::Result<()> {
     // Test the unambiguous parsing argument described in
     // https://docs.google.com/document/d/1gCWmt9O-h_-5JDXIqbKxAaSS3Q9pryB1f9DDY1mMav4/edit#heading=h.x1awbr2acslb
     for m in &msgs {
-        let x = m.serialize(Encoding::Proto);
+        let x = m.serialize(Encoding::Proto).unwrap();
         assert!(x[0] >= 32, "serialize({},PROTO)[0] = {:?}, want >= 32", m, x.get(0));
-        let y = m.serialize(Encoding::Borsh);
+        let y = m.serialize(Encoding::Borsh).unwrap();
         assert!(y[0] <= 21, "serialize({},BORSH)[0] = {:?}, want <= 21", m, y.get(0));
     }
 
     // Encodings should never be compatible.
     for (from, to) in [(Encoding::Proto, Encoding::Borsh), (Encoding::Borsh, Encoding::Proto)] {
         for m in &msgs {
-            let bytes = &m.serialize(from);
+            let bytes = &m.serialize(from).unwrap();
             match PeerMessage::deserialize(to, bytes) {
                 Err(_) => {}
                 Ok(m2) => {
@@ -170,3 +280,213 @@ fn serialize_deserialize() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Protocol version of the blocks and chunks in `golden_messages()`. It is fixed, so that the
+/// golden encodings don't change with every protocol upgrade.
+const GOLDEN_PROTOCOL_VERSION: ProtocolVersion = 60;
+
+/// Messages with deterministic content, covering every variant of PeerMessage and
+/// RoutedMessageBody.
+fn golden_messages() -> Vec<(String, PeerMessage)> {
+    let mut rng = make_rng(60519837215);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let timestamp = |secs| chrono::Utc.timestamp_opt(secs, 0).unwrap();
+
+    let chunks = genesis_chunks(vec![StateRoot::new()], 2, 1000, 0, GOLDEN_PROTOCOL_VERSION);
+    let chunk_headers: Vec<_> = chunks.iter().map(|c| c.cloned_header()).collect();
+    let genesis = Block::genesis(
+        GOLDEN_PROTOCOL_VERSION,
+        chunk_headers.clone(),
+        timestamp(1_600_000_000),
+        0,                     // height
+        1000,                  // initial_gas_price
+        1000,                  // initial_total_supply
+        CryptoHash::default(), // next_bp_hash
+    );
+    let signer = data::make_validator_signer(rng);
+    let block = Block::produce(
+        GOLDEN_PROTOCOL_VERSION,
+        GOLDEN_PROTOCOL_VERSION,
+        genesis.header(),
+        1,
+        genesis.header().block_ordinal() + 1,
+        chunk_headers.clone(),
+        EpochId::default(),
+        EpochId::default(),
+        None,
+        vec![],
+        Ratio::from_integer(0),
+        0,
+        0,
+        Some(0),
+        vec![],
+        vec![],
+        &signer,
+        CryptoHash::default(),
+        CryptoHash::default(),
+        Some(timestamp(1_600_000_001)),
+        None,
+    );
+    let chain = data::Chain {
+        genesis_id: GenesisId { chain_id: "golden".to_string(), hash: *genesis.hash() },
+        blocks: vec![genesis.clone(), block.clone()],
+        tier1_accounts: vec![],
+        chunks: Default::default(),
+    };
+    let handshake = |rng: &mut _| Handshake {
+        protocol_version: GOLDEN_PROTOCOL_VERSION,
+        oldest_supported_version: GOLDEN_PROTOCOL_VERSION - 2,
+        ..data::make_handshake(rng, &chain)
+    };
+    let chunk_parts = data::make_chunk_parts(chunks[0].clone());
+    let hash = data::make_hash(rng);
+    let tx = data::make_signed_transaction(rng);
+    let peer_id = data::make_peer_id(rng);
+    let a = data::make_secret_key(rng);
+    let b = data::make_secret_key(rng);
+
+    let bodies = vec![
+        RoutedMessageBody::BlockApproval(Approval::new(*block.hash(), 1, 2, &signer)),
+        RoutedMessageBody::ForwardTx(tx.clone()),
+        RoutedMessageBody::TxStatusRequest(tx.transaction.signer_id.clone(), tx.get_hash()),
+        RoutedMessageBody::TxStatusResponse(FinalExecutionOutcomeView {
+            status: FinalExecutionStatus::Started,
+            transaction: tx.clone().into(),
+            transaction_outcome: ExecutionOutcomeWithIdView {
+                proof: vec![],
+                block_hash: *block.hash(),
+                id: tx.get_hash(),
+                outcome: ExecutionOutcomeView {
+                    logs: vec!["log".to_string()],
+                    receipt_ids: vec![hash],
+                    gas_burnt: 100,
+                    tokens_burnt: 1000,
+                    executor_id: tx.transaction.signer_id.clone(),
+                    status: ExecutionStatusView::SuccessReceiptId(hash),
+                    metadata: ExecutionMetadataView::default(),
+                },
+            },
+            receipts_outcome: vec![],
+        }),
+        RoutedMessageBody::_UnusedQueryRequest,
+        RoutedMessageBody::_UnusedQueryResponse,
+        RoutedMessageBody::ReceiptOutcomeRequest(hash),
+        RoutedMessageBody::_UnusedReceiptOutcomeResponse,
+        RoutedMessageBody::StateRequestHeader(1, hash),
+        RoutedMessageBody::StateRequestPart(1, hash, 7),
+        RoutedMessageBody::StateResponse(StateResponseInfoV1 {
+            shard_id: 1,
+            sync_hash: hash,
+            state_response: ShardStateSyncResponseV1 { header: None, part: None },
+        }),
+        RoutedMessageBody::PartialEncodedChunkRequest(PartialEncodedChunkRequestMsg {
+            chunk_hash: chunks[0].chunk_hash(),
+            part_ords: vec![0, 3],
+            tracking_shards: [0, 1].into_iter().collect(),
+        }),
+        RoutedMessageBody::PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg {
+            chunk_hash: chunks[0].chunk_hash(),
+            parts: chunk_parts[..2].to_vec(),
+            receipts: vec![],
+        }),
+        RoutedMessageBody::_UnusedPartialEncodedChunk,
+        RoutedMessageBody::Ping(Ping { nonce: 5, source: peer_id.clone() }),
+        RoutedMessageBody::Pong(Pong { nonce: 5, source: peer_id }),
+        RoutedMessageBody::VersionedPartialEncodedChunk(PartialEncodedChunk::new(
+            chunk_headers[0].clone(),
+            chunk_parts[..2].to_vec(),
+            vec![],
+        )),
+        RoutedMessageBody::VersionedStateResponse(StateResponseInfo::V2(StateResponseInfoV2 {
+            shard_id: 1,
+            sync_hash: hash,
+            state_response: ShardStateSyncResponse::V2(ShardStateSyncResponseV2 {
+                header: None,
+                part: Some((3, vec![1, 2, 3])),
+            }),
+        })),
+        RoutedMessageBody::PartialEncodedChunkForward(
+            PartialEncodedChunkForwardMsg::from_header_and_parts(
+                &chunk_headers[0],
+                chunk_parts[2..4].to_vec(),
+            ),
+        ),
+        RoutedMessageBody::FullChunkRequest(PartialEncodedChunkRequestMsg {
+            chunk_hash: chunks[0].chunk_hash(),
+            part_ords: vec![0, 3],
+            tracking_shards: [0, 1].into_iter().collect(),
+        }),
+        RoutedMessageBody::FullChunkResponse(FullChunkResponseMsg {
+            chunk_hash: chunks[0].chunk_hash(),
+            transactions: vec![tx.clone()],
+            receipts: vec![],
+        }),
+    ];
+
+    let mut msgs = vec![
+        PeerMessage::Tier1Handshake(Handshake {
+            supported_encodings: EncodingSet::new(&[Encoding::Proto]),
+            ..handshake(rng)
+        }),
+        PeerMessage::Tier2Handshake(Handshake {
+            supported_encodings: EncodingSet::new(&[Encoding::Proto, Encoding::Borsh]),
+            ..handshake(rng)
+        }),
+        PeerMessage::HandshakeFailure(
+            data::make_peer_info(rng),
+            HandshakeFailureReason::ProtocolVersionMismatch {
+                version: GOLDEN_PROTOCOL_VERSION,
+                oldest_supported_version: GOLDEN_PROTOCOL_VERSION - 2,
+            },
+        ),
+        PeerMessage::LastEdge(data::make_edge(&a, &b, 3)),
+        PeerMessage::SyncRoutingTable(data::make_routing_table(rng)),
+        PeerMessage::RequestUpdateNonce(data::make_partial_edge(rng)),
+        PeerMessage::SyncAccountsData(SyncAccountsData {
+            accounts_data: (0..2)
+                .map(|_| Arc::new(data::make_signed_account_data(rng, &clock.clock())))
+                .collect(),
+            incremental: true,
+            requesting_full_sync: false,
+        }),
+        PeerMessage::PeersRequest(PeersRequest { max_peers: Some(10), max_direct_peers: None }),
+        PeerMessage::PeersResponse(PeersResponse {
+            peers: (0..3).map(|_| data::make_peer_info(rng)).collect(),
+            direct_peers: vec![],
+        }),
+        PeerMessage::BlockHeadersRequest(vec![*genesis.hash(), *block.hash()]),
+        PeerMessage::BlockHeaders(chain.get_block_headers()),
+        PeerMessage::BlockRequest(*block.hash()),
+        PeerMessage::Block(block.clone()),
+        PeerMessage::Transaction(tx),
+        PeerMessage::Disconnect(Disconnect { remove_from_connection_store: true }),
+        PeerMessage::Challenge(data::make_challenge(rng)),
+        PeerMessage::KeepAlive(KeepAlive { nonce: 7 }),
+        PeerMessage::KeepAliveResponse(KeepAlive { nonce: 7 }),
+    ]
+    .into_iter()
+    .map(|msg| (<&'static str>::from(&msg).to_string(), msg))
+    .collect::<Vec<_>>();
+    for body in bodies {
+        let name = format!("Routed_{}", <&'static str>::from(&body));
+        msgs.push((name, PeerMessage::Routed(Box::new(data::make_routed_message(rng, body)))));
+    }
+    msgs
+}
+
+/// Compares the encodings of the messages against the golden files in `snapshots/`, so that
+/// accidental changes of the wire format are caught at review time.
+/// If a change is intentional, update the golden files with `cargo insta review`.
+#[test]
+fn golden_encodings() {
+    for (name, msg) in golden_messages() {
+        let proto = msg.serialize(Encoding::Proto).unwrap();
+        let mut encodings = vec![format!("proto: {}", hex::encode(proto))];
+        // Some of the messages (like Tier1Handshake) are not supported in Borsh encoding.
+        if let Some(borsh) = msg.serialize(Encoding::Borsh) {
+            encodings.push(format!("borsh: {}", hex::encode(borsh)));
+        }
+        insta::assert_snapshot!(name, encodings.join("\n"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Limit on the expensive requests received over a single connection.
+//!
+//! Serving BlockRequest, BlockHeadersRequest, StateRequestHeader and StateRequestPart
+//! involves disk IO in the view client, so a single peer issuing hundreds of them concurrently
+//! could starve everyone else. PeerActor admits at most `max_in_flight` such requests at a time
+//! and drops the excess. A peer which keeps sending requests over the limit is banned.
+use crate::config;
+use crate::network_protocol::{PeerMessage, RoutedMessageBody};
+use near_primitives::time;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Checks whether serving `msg` is expensive.
+pub(crate) fn is_expensive(msg: &PeerMessage) -> bool {
+    match msg {
+        PeerMessage::BlockRequest(_) | PeerMessage::BlockHeadersRequest(_) => true,
+        PeerMessage::Routed(msg) => matches!(
+            msg.body,
+            RoutedMessageBody::StateRequestHeader(..) | RoutedMessageBody::StateRequestPart(..)
+        ),
+        _ => false,
+    }
+}
+
+pub(crate) enum Admission {
+    /// The request should be processed. The permit should be held until the response is ready.
+    Accepted(tokio::sync::OwnedSemaphorePermit),
+    /// The request should be dropped.
+    Rejected,
+    /// The peer persistently exceeds the limit and should be banned.
+    Abusive,
+}
+
+pub(crate) struct Limiter {
+    config: config::ExpensiveRequestsLimit,
+    in_flight: Arc<tokio::sync::Semaphore>,
+    /// Times at which the recent requests of the peer were rejected, oldest first.
+    /// Only the times within `config.rejected_window` are kept.
+    rejected: Mutex<VecDeque<time::Instant>>,
+}
+
+impl Limiter {
+    pub fn new(config: config::ExpensiveRequestsLimit) -> Self {
+        Self {
+            in_flight: Arc::new(tokio::sync::Semaphore::new(config.max_in_flight)),
+            rejected: Mutex::new(VecDeque::new()),
+            config,
+        }
+    }
+
+    /// Decides whether a new expensive request should be processed.
+    /// `sent_by_peer` indicates whether the request has been authored by the peer itself, rather
+    /// than routed through it. Only the rejected requests authored by the peer count towards a ban,
+    /// so that an honest peer is not banned for forwarding requests of somebody else.
+    pub fn admit(&self, now: time::Instant, sent_by_peer: bool) -> Admission {
+        if let Ok(permit) = self.in_flight.clone().try_acquire_owned() {
+            return Admission::Accepted(permit);
+        }
+        if !sent_by_peer {
+            return Admission::Rejected;
+        }
+        let mut rejected = self.rejected.lock();
+        while rejected.front().map_or(false, |t| *t + self.config.rejected_window <= now) {
+            rejected.pop_front();
+        }
+        rejected.push_back(now);
+        if rejected.len() > self.config.max_rejected {
+            return Admission::Abusive;
+        }
+        Admission::Rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> Limiter {
+        Limiter::new(config::ExpensiveRequestsLimit {
+            max_in_flight: 2,
+            max_rejected: 3,
+            rejected_window: time::Duration::seconds(10),
+        })
+    }
+
+    #[test]
+    fn test_in_flight_limit() {
+        let clock = time::FakeClock::default();
+        let limiter = limiter();
+        let p1 = limiter.admit(clock.now(), true);
+        let p2 = limiter.admit(clock.now(), true);
+        assert!(matches!(p1, Admission::Accepted(_)));
+        assert!(matches!(p2, Admission::Accepted(_)));
+        assert!(matches!(limiter.admit(clock.now(), true), Admission::Rejected));
+        // Finishing a request releases its slot.
+        drop(p1);
+        assert!(matches!(limiter.admit(clock.now(), true), Admission::Accepted(_)));
+    }
+
+    #[test]
+    fn test_ban_after_persistent_rejections() {
+        let clock = time::FakeClock::default();
+        let limiter = limiter();
+        let _p1 = limiter.admit(clock.now(), true);
+        let _p2 = limiter.admit(clock.now(), true);
+        // Routed requests of other nodes never count towards a ban.
+        for _ in 0..10 {
+            assert!(matches!(limiter.admit(clock.now(), false), Admission::Rejected));
+        }
+        for _ in 0..3 {
+            assert!(matches!(limiter.admit(clock.now(), true), Admission::Rejected));
+        }
+        // Rejections older than the window are forgotten.
+        clock.advance(time::Duration::seconds(10));
+        for _ in 0..3 {
+            assert!(matches!(limiter.admit(clock.now(), true), Admission::Rejected));
+        }
+        assert!(matches!(limiter.admit(clock.now(), true), Admission::Abusive));
+    }
+}
@@ -1,8 +1,11 @@
+mod expensive_requests;
 pub(crate) mod peer_actor;
 mod stream;
 mod tracker;
 mod transfer_stats;
 
+pub(crate) use stream::{RecvLimits, NETWORK_MESSAGE_MAX_SIZE_BYTES};
+
 #[cfg(test)]
 pub(crate) mod testonly;
 #[cfg(test)]
@@ -1,18 +1,24 @@
 use crate::accounts_data;
+use crate::concurrency::arbiter_pool;
 use crate::concurrency::atomic_cell::AtomicCell;
 use crate::concurrency::demux;
+use crate::concurrency::rate;
 use crate::config::PEERS_RESPONSE_MAX_PEERS;
 use crate::network_protocol::{
-    Edge, EdgeState, Encoding, OwnedAccount, ParsePeerMessageError, PartialEdgeInfo,
-    PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeersRequest, PeersResponse, RawRoutedMessage,
-    RoutedMessageBody, RoutedMessageV2, RoutingTableUpdate, StateResponseInfo, SyncAccountsData,
+    Compression, Edge, EdgeState, Encoding, EncodingSet, KeepAlive, OwnedAccount,
+    ParsePeerMessageError, PartialEdgeInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeersRequest,
+    PeersResponse, RawRoutedMessage, RoutedMessageBody, RoutedMessageV2, RoutingTableUpdate,
+    StateResponseInfo, SyncAccountsData,
 };
+use crate::peer::expensive_requests;
 use crate::peer::stream;
 use crate::peer::tracker::Tracker;
 use crate::peer_manager::connection;
+use crate::peer_manager::inbound_ip_limiter;
 use crate::peer_manager::network_state::{NetworkState, PRUNE_EDGES_AFTER};
-use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_manager_actor::MAX_TIER2_PEERS;
+use crate::peer_manager::peer_manager_actor::{Event, EventKind};
+use crate::peer_manager::routed_trace;
 use crate::private_actix::{RegisterPeerError, SendMessage};
 use crate::routing::edge::verify_nonce;
 use crate::shards_manager::ShardsManagerRequestFromNetwork;
@@ -24,7 +30,6 @@ use crate::types::{
 use actix::fut::future::wrap_future;
 use actix::{Actor as _, ActorContext as _, ActorFutureExt as _, AsyncContext as _};
 use lru::LruCache;
-use near_crypto::Signature;
 use near_o11y::{handler_debug_span, log_assert, pretty, OpenTelemetrySpanExt, WithSpanContext};
 use near_performance_metrics_macros::perf;
 use near_primitives::hash::CryptoHash;
@@ -56,14 +61,11 @@ const MAX_CLOCK_SKEW: time::Duration = time::Duration::minutes(30);
 /// The purpose of this constant is to ensure we do not spend too much time deserializing and
 /// dispatching transactions when we should be focusing on consensus-related messages.
 const MAX_TRANSACTIONS_PER_BLOCK_MESSAGE: usize = 1000;
-/// Limit cache size of 1000 messages
-const ROUTED_MESSAGE_CACHE_SIZE: usize = 1000;
-/// Duplicated messages will be dropped if routed through the same peer multiple times.
-pub(crate) const DROP_DUPLICATED_MESSAGES_PERIOD: time::Duration = time::Duration::milliseconds(50);
 /// How often to send the latest block to peers.
 const SYNC_LATEST_BLOCK_INTERVAL: time::Duration = time::Duration::seconds(60);
+/// Maximal number of listen addrs of a peer accepted from its Handshake.
+const MAX_PEER_LISTEN_ADDRS: usize = 8;
 /// How often to perform a full sync of AccountsData with the peer.
-const ACCOUNTS_DATA_FULL_SYNC_INTERVAL: time::Duration = time::Duration::minutes(10);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConnectionClosedEvent {
@@ -83,6 +85,28 @@ pub struct HandshakeCompletedEvent {
     pub(crate) tier: tcp::Tier,
 }
 
+/// Reason for which the connection has been closed before the handshake was completed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum HandshakeFailedReason {
+    /// Handshake hasn't been completed within `handshake_timeout`.
+    Timeout,
+    ProtocolVersionMismatch,
+    GenesisMismatch,
+    /// Outbound only: the peer is not the one we tried to connect to.
+    PeerIdMismatch,
+    /// Outbound only: the peer has responded with a Handshake of a different TIER.
+    TierMismatch,
+    /// Outbound only: the peer has responded with a different nonce than the one we proposed.
+    NonceMismatch,
+    /// Inbound only: the proposed nonce is not a valid timestamp.
+    InvalidNonce,
+    /// Inbound only: the Handshake was addressed to a different node.
+    InvalidTarget,
+    /// Outbound only: the peer has rejected our nonce, presenting an invalid edge as evidence.
+    InvalidLastEdge,
+}
+
 #[derive(thiserror::Error, Clone, PartialEq, Eq, Debug)]
 pub(crate) enum ClosingReason {
     #[error("too many inbound connections in connecting state")]
@@ -92,8 +116,8 @@ pub(crate) enum ClosingReason {
 
     #[error("peer banned: {0:?}")]
     Ban(ReasonForBan),
-    #[error("handshake failed")]
-    HandshakeFailed,
+    #[error("handshake failed: {0:?}")]
+    HandshakeFailed(HandshakeFailedReason),
     #[error("rejected by PeerManager: {0:?}")]
     RejectedByPeerManager(RegisterPeerError),
     #[error("stream error")]
@@ -110,6 +134,10 @@ pub(crate) enum ClosingReason {
     TooLargeClockSkew,
     #[error("owned_account.peer_id doesn't match handshake.sender_peer_id")]
     OwnedAccountMismatch,
+    #[error("peer doesn't support protocol buffers and Borsh peers are rejected")]
+    BorshNotAllowed,
+    #[error("peer stopped answering the keep-alive pings")]
+    KeepAliveTimeout,
     #[error("PeerActor stopped NOT via PeerActor::stop()")]
     Unknown,
 }
@@ -123,7 +151,7 @@ impl ClosingReason {
             ClosingReason::TooManyInbound => false, // outbound may be still be OK
             ClosingReason::OutboundNotAllowed(_) => true, // outbound not allowed
             ClosingReason::Ban(_) => true,          // banned
-            ClosingReason::HandshakeFailed => false, // handshake may simply time out
+            ClosingReason::HandshakeFailed(_) => false, // handshake may simply time out
             ClosingReason::RejectedByPeerManager(_) => true, // rejected by peer manager
             ClosingReason::StreamError => false,    // connection issue
             ClosingReason::DisallowedMessage => true, // misbehaving peer
@@ -131,6 +159,8 @@ impl ClosingReason {
             ClosingReason::DisconnectMessage => false, // graceful disconnect
             ClosingReason::TooLargeClockSkew => true, // reconnect will fail for the same reason
             ClosingReason::OwnedAccountMismatch => true, // misbehaving peer
+            ClosingReason::BorshNotAllowed => true, // reconnect will fail for the same reason
+            ClosingReason::KeepAliveTimeout => false, // peer may be temporarily overloaded
             ClosingReason::Unknown => false,        // only happens in tests
         }
     }
@@ -158,13 +188,36 @@ pub(crate) struct PeerActor {
     tracker: Arc<Mutex<Tracker>>,
     /// Network bandwidth stats.
     stats: Arc<connection::Stats>,
-    /// Cache of recently routed messages, this allows us to drop duplicates
-    routed_message_cache: LruCache<(PeerId, PeerIdOrHash, Signature), time::Instant>,
-    /// Whether we detected support for protocol buffers during handshake.
-    protocol_buffers_supported: bool,
-    /// Whether the PeerActor should skip protobuf support detection and use
+    /// Cache of recently routed messages, by the message hash, this allows us to drop duplicates.
+    /// The hash doesn't cover the signature and the TTL, so the copies of the message which
+    /// differ only in those are considered duplicates as well.
+    routed_message_cache: LruCache<CryptoHash, time::Instant>,
+    /// Limit on the expensive requests from the peer processed concurrently.
+    expensive_requests: Option<expensive_requests::Limiter>,
+    /// Rate limit on the key transitions received from the peer. Each of them costs
+    /// two signature verifications.
+    key_transitions_bucket: rate::Bucket,
+    /// Encoding of the last message received before the handshake was completed.
+    /// A peer which doesn't advertise the encodings it supports, gets the one
+    /// in which its Handshake has been received.
+    received_encoding: Option<Encoding>,
+    /// Encoding of the connection, negotiated during the handshake.
+    negotiated_encoding: Option<Encoding>,
+    /// Whether the PeerActor should skip the encoding negotiation and use
     /// a given encoding right away.
     force_encoding: Option<Encoding>,
+    /// Compression of the messages exchanged with the peer, negotiated during handshake.
+    /// Compressed messages received before it has been negotiated are rejected.
+    compression: Compression,
+    /// Time at which our Handshake has been sent, for the outbound connections.
+    handshake_sent_at: Option<time::Instant>,
+    /// Inbound only: reason for which we have rejected the peer's Handshake (with a
+    /// HandshakeFailure message). Reported when the handshake eventually times out.
+    handshake_rejection: Option<HandshakeFailedReason>,
+    /// Arbiter the actor is running on, released when the actor stops.
+    arbiter: Option<arbiter_pool::Arbiter>,
+    /// Inbound only: slot of the connection in the limit of its source IP.
+    _ip_permit: Option<inbound_ip_limiter::Permit>,
 
     /// Peer status.
     peer_status: PeerStatus,
@@ -194,8 +247,42 @@ struct HandshakeSpec {
 type HandshakeSignalSender = tokio::sync::oneshot::Sender<std::convert::Infallible>;
 pub type HandshakeSignal = tokio::sync::oneshot::Receiver<std::convert::Infallible>;
 
+/// Priority of the send queue lane to which `msg` is pushed.
+fn send_priority(msg: &PeerMessage) -> stream::Priority {
+    match msg {
+        PeerMessage::Tier1Handshake(_)
+        | PeerMessage::Tier2Handshake(_)
+        | PeerMessage::HandshakeFailure(..)
+        | PeerMessage::LastEdge(_)
+        | PeerMessage::Disconnect(_)
+        | PeerMessage::Block(_) => stream::Priority::Consensus,
+        // Keep-alives bypass the bulk traffic, so that the queues don't inflate the RTT.
+        PeerMessage::KeepAlive(_) | PeerMessage::KeepAliveResponse(_) => {
+            stream::Priority::Consensus
+        }
+        PeerMessage::BlockHeadersRequest(_)
+        | PeerMessage::BlockHeaders(_)
+        | PeerMessage::BlockRequest(_) => stream::Priority::Sync,
+        PeerMessage::Routed(msg) => match msg.body {
+            RoutedMessageBody::BlockApproval(_) => stream::Priority::Consensus,
+            RoutedMessageBody::PartialEncodedChunkRequest(_)
+            | RoutedMessageBody::PartialEncodedChunkResponse(_)
+            | RoutedMessageBody::FullChunkRequest(_)
+            | RoutedMessageBody::FullChunkResponse(_)
+            | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            | RoutedMessageBody::PartialEncodedChunkForward(_) => stream::Priority::Chunks,
+            RoutedMessageBody::StateRequestHeader(..)
+            | RoutedMessageBody::StateRequestPart(..)
+            | RoutedMessageBody::StateResponse(_)
+            | RoutedMessageBody::VersionedStateResponse(_) => stream::Priority::Sync,
+            _ => stream::Priority::Misc,
+        },
+        _ => stream::Priority::Misc,
+    }
+}
+
 impl PeerActor {
-    /// Spawns a PeerActor on a separate actix::Arbiter and awaits for the
+    /// Spawns a PeerActor on an actix::Arbiter and awaits for the
     /// handshake to succeed/fail. The actual result is not returned because
     /// actix makes everything complicated.
     pub(crate) async fn spawn_and_handshake(
@@ -204,30 +291,35 @@ impl PeerActor {
         force_encoding: Option<Encoding>,
         network_state: Arc<NetworkState>,
     ) -> anyhow::Result<actix::Addr<Self>> {
-        let (addr, handshake_signal) = Self::spawn(clock, stream, force_encoding, network_state)?;
+        let (addr, handshake_signal) =
+            Self::spawn(clock, stream, force_encoding, None, network_state)?;
         // Await for the handshake to complete, by awaiting the handshake_signal channel.
         // This is a receiver of Infallible, so it only completes when the channel is closed.
         handshake_signal.await.err().unwrap();
         Ok(addr)
     }
 
-    /// Spawns a PeerActor on a separate actix arbiter.
+    /// Spawns a PeerActor on an arbiter from the pool of the NetworkState.
     /// Returns the actor address and a HandshakeSignal: an asynchronous channel
     /// which will be closed as soon as the handshake is finished (successfully or not).
     /// You can asynchronously await the returned HandshakeSignal.
+    /// `ip_permit` is the slot of an inbound connection in the limit of its source IP,
+    /// held until the actor is dropped.
     pub(crate) fn spawn(
         clock: time::Clock,
         stream: tcp::Stream,
         force_encoding: Option<Encoding>,
+        ip_permit: Option<inbound_ip_limiter::Permit>,
         network_state: Arc<NetworkState>,
     ) -> anyhow::Result<(actix::Addr<Self>, HandshakeSignal)> {
         let stream_id = stream.id();
-        match Self::spawn_inner(clock, stream, force_encoding, network_state.clone()) {
+        match Self::spawn_inner(clock, stream, force_encoding, ip_permit, network_state.clone()) {
             Ok(it) => Ok(it),
             Err(reason) => {
-                network_state.config.event_sink.push(Event::ConnectionClosed(
-                    ConnectionClosedEvent { stream_id, reason: reason.clone() },
-                ));
+                network_state.config.push_event(Event::ConnectionClosed(ConnectionClosedEvent {
+                    stream_id,
+                    reason: reason.clone(),
+                }));
                 Err(reason.into())
             }
         }
@@ -237,6 +329,7 @@ impl PeerActor {
         clock: time::Clock,
         stream: tcp::Stream,
         force_encoding: Option<Encoding>,
+        ip_permit: Option<inbound_ip_limiter::Permit>,
         network_state: Arc<NetworkState>,
     ) -> Result<(actix::Addr<Self>, HandshakeSignal), ClosingReason> {
         let connecting_status = match &stream.type_ {
@@ -292,14 +385,31 @@ impl PeerActor {
         // recv is the HandshakeSignal returned by this spawn_inner() call.
         let (send, recv): (HandshakeSignalSender, HandshakeSignal) =
             tokio::sync::oneshot::channel();
-        // Start PeerActor on separate thread.
+        // Start PeerActor on one of the arbiters of the pool.
+        let arbiter = network_state.peer_arbiters.assign();
         Ok((
-            Self::start_in_arbiter(&actix::Arbiter::new().handle(), move |ctx| {
+            Self::start_in_arbiter(&arbiter.handle().clone(), move |ctx| {
                 let stream_id = stream.id();
                 let peer_addr = stream.peer_addr;
                 let stream_type = stream.type_.clone();
                 let stats = Arc::new(connection::Stats::default());
-                let framed = stream::FramedStream::spawn(ctx, stream, stats.clone());
+                // The tier of an inbound connection is known only after the handshake.
+                let tier = match &stream_type {
+                    tcp::StreamType::Inbound => tcp::Tier::T2,
+                    tcp::StreamType::Outbound { tier, .. } => *tier,
+                };
+                let key_transitions_bucket = rate::Bucket::new(
+                    clock.now(),
+                    &network_state.config.key_transitions_rate_limit,
+                );
+                let framed = stream::FramedStream::spawn(
+                    ctx,
+                    clock.clone(),
+                    stream,
+                    tier,
+                    network_state.recv_limits.clone(),
+                    stats.clone(),
+                );
                 Self {
                     closing_reason: None,
                     clock,
@@ -314,9 +424,23 @@ impl PeerActor {
                     framed,
                     tracker: Default::default(),
                     stats,
-                    routed_message_cache: LruCache::new(ROUTED_MESSAGE_CACHE_SIZE),
-                    protocol_buffers_supported: false,
+                    routed_message_cache: LruCache::new(
+                        network_state.config.routed_message_dedup_cache_size,
+                    ),
+                    expensive_requests: network_state
+                        .config
+                        .expensive_requests_limit
+                        .clone()
+                        .map(expensive_requests::Limiter::new),
+                    key_transitions_bucket,
+                    received_encoding: None,
+                    negotiated_encoding: None,
                     force_encoding,
+                    compression: Compression::None,
+                    handshake_sent_at: None,
+                    handshake_rejection: None,
+                    arbiter: Some(arbiter),
+                    _ip_permit: ip_permit,
                     peer_info: match &stream_type {
                         tcp::StreamType::Inbound => None,
                         tcp::StreamType::Outbound { peer_id, .. } => Some(PeerInfo {
@@ -334,31 +458,62 @@ impl PeerActor {
     }
 
     // Determines the encoding to use for communication with the peer.
-    // It can be None while Handshake with the peer has not been finished yet.
+    // It is None until the encoding is negotiated in the Handshake.
     // In case it is None, both encodings are attempted for parsing, and each message
-    // is sent twice.
+    // is sent in every encoding we accept.
     fn encoding(&self) -> Option<Encoding> {
-        if self.force_encoding.is_some() {
-            return self.force_encoding;
+        self.force_encoding.or(self.negotiated_encoding)
+    }
+
+    /// Encodings in which this node accepts messages.
+    fn supported_encodings(&self) -> EncodingSet {
+        if let Some(e) = self.force_encoding {
+            return EncodingSet::new(&[e]);
+        }
+        if !self.borsh_allowed(PROTOCOL_VERSION) {
+            return EncodingSet::new(&[Encoding::Proto]);
+        }
+        EncodingSet::new(&[Encoding::Proto, Encoding::Borsh])
+    }
+
+    /// Whether Borsh may be used by a node running `protocol_version`.
+    fn borsh_allowed(&self, protocol_version: ProtocolVersion) -> bool {
+        let config = &self.network_state.config;
+        !config.reject_borsh_peers
+            && config.borsh_max_protocol_version.map_or(true, |max| protocol_version <= max)
+    }
+
+    /// Picks the encoding of the connection, given the peer's Handshake received
+    /// in `received` encoding. Proto is preferred, if both sides support it.
+    fn negotiate_encoding(&self, received: Encoding, theirs: EncodingSet) -> Encoding {
+        if let Some(e) = self.force_encoding {
+            return e;
         }
-        if self.protocol_buffers_supported {
-            return Some(Encoding::Proto);
+        // The peer doesn't advertise the encodings, so it is an older node which
+        // uses the encoding it has managed to parse.
+        if theirs.is_empty() {
+            return received;
         }
-        match self.peer_status {
-            PeerStatus::Connecting { .. } => None,
-            PeerStatus::Ready { .. } => Some(Encoding::Borsh),
+        if theirs.contains(Encoding::Proto) {
+            Encoding::Proto
+        } else {
+            Encoding::Borsh
         }
     }
 
     fn parse_message(&mut self, msg: &[u8]) -> Result<PeerMessage, ParsePeerMessageError> {
         if let Some(e) = self.encoding() {
-            return PeerMessage::deserialize(e, msg);
+            return PeerMessage::deserialize_with_compression(e, msg, self.compression);
         }
+        // Borsh is parsed even if we don't accept it, so that Borsh-only peers can be
+        // explicitly rejected in process_handshake.
         if let Ok(msg) = PeerMessage::deserialize(Encoding::Proto, msg) {
-            self.protocol_buffers_supported = true;
+            self.received_encoding = Some(Encoding::Proto);
             return Ok(msg);
         }
-        return PeerMessage::deserialize(Encoding::Borsh, msg);
+        let msg = PeerMessage::deserialize(Encoding::Borsh, msg)?;
+        self.received_encoding = Some(Encoding::Borsh);
+        Ok(msg)
     }
 
     fn send_message_or_log(&self, msg: &PeerMessage) {
@@ -372,12 +527,17 @@ impl PeerActor {
         if let Some(enc) = self.encoding() {
             return self.send_message_with_encoding(msg, enc);
         }
-        self.send_message_with_encoding(msg, Encoding::Proto);
-        self.send_message_with_encoding(msg, Encoding::Borsh);
+        // Until the encoding is negotiated, the message is sent in every encoding we accept.
+        // Borsh is dropped once our protocol version is above `borsh_max_protocol_version`.
+        for enc in [Encoding::Proto, Encoding::Borsh] {
+            if self.supported_encodings().contains(enc) {
+                self.send_message_with_encoding(msg, enc);
+            }
+        }
     }
 
     fn send_message_with_encoding(&self, msg: &PeerMessage, enc: Encoding) {
-        let msg_type: &str = msg.msg_variant();
+        let msg_type: &'static str = msg.msg_variant();
         let _span = tracing::trace_span!(
             target: "network",
             "send_message_with_encoding",
@@ -401,11 +561,20 @@ impl PeerActor {
             _ => (),
         };
 
-        let bytes = msg.serialize(enc);
+        // The messages not supported in the encoding (like the keep-alives in Borsh) are
+        // dropped. The peers using that encoding don't expect them.
+        let bytes = match msg.serialize_with_compression(enc, self.compression) {
+            Some(bytes) => bytes,
+            None => {
+                metrics::MessageDropped::UnsupportedEncoding.inc_peer_msg(msg);
+                return;
+            }
+        };
         self.tracker.lock().increment_sent(&self.clock, bytes.len() as u64);
         let bytes_len = bytes.len();
         tracing::trace!(target: "network", msg_len = bytes_len);
-        self.framed.send(stream::Frame(bytes));
+        self.framed.send(send_priority(msg), stream::Frame(bytes));
+        self.stats.record_sent(msg_type, bytes_len as u64);
         metrics::PEER_DATA_SENT_BYTES.inc_by(bytes_len as u64);
         metrics::PEER_MESSAGE_SENT_BY_TYPE_TOTAL.with_label_values(&[msg_type]).inc();
         metrics::PEER_MESSAGE_SENT_BY_TYPE_BYTES
@@ -442,12 +611,31 @@ impl PeerActor {
                 }
                 .sign(vc.signer.as_ref())
             }),
+            supports_zstd_compression: self.network_state.config.zstd_compression,
+            supports_message_fragmentation: true,
+            supports_full_chunk_requests: true,
+            supported_encodings: self.supported_encodings(),
+            sender_listen_addrs: if self.network_state.config.additional_node_addrs.is_empty() {
+                vec![]
+            } else {
+                self.network_state.config.listen_addrs().map(|a| **a).collect()
+            },
         };
         let msg = match spec.tier {
             tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
             tcp::Tier::T2 => PeerMessage::Tier2Handshake(handshake),
         };
-        self.send_message_or_log(&msg);
+        // The handshake is sent in a single encoding: the negotiated one, the one the peer has
+        // used so far, or else Proto if we accept it. The Handshake tells the peer which
+        // encodings we accept, so a peer which accepts Proto doesn't need a Borsh copy of it.
+        let encoding = self.encoding().or(self.received_encoding).unwrap_or_else(|| {
+            if self.supported_encodings().contains(Encoding::Proto) {
+                Encoding::Proto
+            } else {
+                Encoding::Borsh
+            }
+        });
+        self.send_message_with_encoding(&msg, encoding);
     }
 
     fn stop(&mut self, ctx: &mut actix::Context<PeerActor>, reason: ClosingReason) {
@@ -479,21 +667,64 @@ impl PeerActor {
             PeerStatus::Connecting(_, it) => it,
             _ => panic!("process_handshake called in non-connecting state"),
         };
+        let received = self.received_encoding.unwrap_or(Encoding::Proto);
+        let encoding = self.negotiate_encoding(received, handshake.supported_encodings);
+        self.negotiated_encoding = Some(encoding);
+        if encoding == Encoding::Borsh {
+            let accepted = self.borsh_allowed(handshake.protocol_version);
+            let result = if accepted { "accepted" } else { "rejected" };
+            // The protocol version is not authenticated yet, so only the supported ones
+            // get a label of their own, to keep the number of the time series bounded.
+            let protocol_version = if (PEER_MIN_ALLOWED_PROTOCOL_VERSION..=PROTOCOL_VERSION)
+                .contains(&handshake.protocol_version)
+            {
+                handshake.protocol_version.to_string()
+            } else {
+                "unsupported".to_string()
+            };
+            metrics::PEER_BORSH_HANDSHAKES_BY_PROTOCOL_VERSION
+                .with_label_values(&[&protocol_version, result])
+                .inc();
+            if !accepted {
+                let peer_id = &handshake.sender_peer_id;
+                let protocol_version = handshake.protocol_version;
+                tracing::debug!(target: "network", %peer_id, protocol_version, "Rejecting Borsh-only peer");
+                metrics::PEER_HANDSHAKES_BY_ENCODING
+                    .with_label_values(&[encoding.into(), "rejected"])
+                    .inc();
+                self.stop(ctx, ClosingReason::BorshNotAllowed);
+                return;
+            }
+        }
+        metrics::PEER_HANDSHAKES_BY_ENCODING
+            .with_label_values(&[encoding.into(), "accepted"])
+            .inc();
         match cs {
             ConnectingStatus::Outbound { handshake_spec: spec, .. } => {
                 if handshake.protocol_version != spec.protocol_version {
                     tracing::warn!(target: "network", "Protocol version mismatch. Disconnecting peer {}", handshake.sender_peer_id);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(
+                            HandshakeFailedReason::ProtocolVersionMismatch,
+                        ),
+                    );
                     return;
                 }
                 if handshake.sender_chain_info.genesis_id != self.network_state.genesis_id {
                     tracing::warn!(target: "network", "Genesis mismatch. Disconnecting peer {}", handshake.sender_peer_id);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::GenesisMismatch),
+                    );
                     return;
                 }
                 if handshake.sender_peer_id != spec.peer_id {
                     tracing::warn!(target: "network", "PeerId mismatch. Disconnecting peer {}", handshake.sender_peer_id);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::PeerIdMismatch),
+                    );
                     return;
                 }
                 // This can happen only in case of a malicious node.
@@ -501,12 +732,18 @@ impl PeerActor {
                 // confirm the TIER or drop connection. TIER is not negotiable during handshake.
                 if tier != spec.tier {
                     tracing::warn!(target: "network", "Connection TIER mismatch. Disconnecting peer {}", handshake.sender_peer_id);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::TierMismatch),
+                    );
                     return;
                 }
                 if handshake.partial_edge_info.nonce != spec.partial_edge_info.nonce {
                     tracing::warn!(target: "network", "Nonce mismatch. Disconnecting peer {}", handshake.sender_peer_id);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::NonceMismatch),
+                    );
                     return;
                 }
             }
@@ -525,6 +762,7 @@ impl PeerActor {
                             oldest_supported_version: PEER_MIN_ALLOWED_PROTOCOL_VERSION,
                         },
                     ));
+                    self.handshake_rejection = Some(HandshakeFailedReason::ProtocolVersionMismatch);
                     return;
                 }
                 let genesis_id = self.network_state.genesis_id.clone();
@@ -534,6 +772,7 @@ impl PeerActor {
                         self.my_node_info.clone(),
                         HandshakeFailureReason::GenesisMismatch(genesis_id),
                     ));
+                    self.handshake_rejection = Some(HandshakeFailedReason::GenesisMismatch);
                     return;
                 }
                 if handshake.target_peer_id != self.my_node_info.id {
@@ -542,13 +781,17 @@ impl PeerActor {
                         self.my_node_info.clone(),
                         HandshakeFailureReason::InvalidTarget,
                     ));
+                    self.handshake_rejection = Some(HandshakeFailedReason::InvalidTarget);
                     return;
                 }
 
                 // Verify if nonce is sane.
                 if let Err(err) = verify_nonce(&self.clock, handshake.partial_edge_info.nonce) {
                     tracing::debug!(target: "network", nonce=?handshake.partial_edge_info.nonce, my_node_id = ?self.my_node_id(), peer_id=?handshake.sender_peer_id, "bad nonce, disconnecting: {err}");
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::InvalidNonce),
+                    );
                     return;
                 }
                 // Check that the received nonce is greater than the current nonce of this connection.
@@ -581,6 +824,22 @@ impl PeerActor {
             }
         }
 
+        // Compress the messages sent to the peer iff both sides support compression.
+        if self.network_state.config.zstd_compression && handshake.supports_zstd_compression {
+            self.compression = Compression::Zstd;
+        }
+        // Split the large messages sent to the peer iff the peer can reassemble them.
+        if handshake.supports_message_fragmentation {
+            self.framed.enable_fragmentation();
+        }
+        self.framed.set_recv_tier(tier);
+        if let Some(fi) = &self.network_state.config.fault_injection {
+            let profile = fi.profile(&handshake.sender_peer_id);
+            if !profile.is_noop() {
+                self.framed.inject_faults(profile.clone());
+            }
+        }
+
         // Merge partial edges.
         let nonce = handshake.partial_edge_info.nonce;
         let partial_edge_info = match cs {
@@ -599,11 +858,9 @@ impl PeerActor {
             handshake.partial_edge_info.signature.clone(),
         );
 
-        // TODO(gprusak): not enabling a port for listening is also a valid setup.
-        // In that case peer_info.addr should be None (same as now), however
-        // we still should do the check against the PeerStore::blacklist.
-        // Currently PeerManager is rejecting connections with peer_info.addr == None
-        // preemptively.
+        // A peer which doesn't listen for connections doesn't send its port, in which case
+        // peer_info.addr is None and the blacklist is checked against the address of
+        // the connection instead.
         let peer_info = PeerInfo {
             id: handshake.sender_peer_id.clone(),
             addr: handshake
@@ -611,16 +868,32 @@ impl PeerActor {
                 .map(|port| SocketAddr::new(self.peer_addr.ip(), port)),
             account_id: None,
         };
+        // The other addresses on which the peer is listening. Addresses with an unspecified IP
+        // are reachable at the IP of this connection, if it is of the same family.
+        let listen_addrs = (handshake.sender_listen_addrs.iter())
+            .filter_map(|addr| {
+                if !addr.ip().is_unspecified() {
+                    return Some(tcp::canonical_addr(*addr));
+                }
+                let ip = self.peer_addr.ip();
+                (ip.is_ipv4() == addr.is_ipv4()).then(|| SocketAddr::new(ip, addr.port()))
+            })
+            .filter(|addr| Some(*addr) != peer_info.addr)
+            .take(MAX_PEER_LISTEN_ADDRS)
+            .collect();
 
         let now = self.clock.now();
         let conn = Arc::new(connection::Connection {
             tier,
             addr: ctx.address(),
             peer_info: peer_info.clone(),
+            peer_addr: self.peer_addr,
+            listen_addrs,
             owned_account: handshake.owned_account.clone(),
             genesis_id: handshake.sender_chain_info.genesis_id.clone(),
             tracked_shards: handshake.sender_chain_info.tracked_shards.clone(),
             archival: handshake.sender_chain_info.archival,
+            supports_full_chunk_requests: handshake.supports_full_chunk_requests,
             last_block: Default::default(),
             peer_type: self.peer_type,
             stats: self.stats.clone(),
@@ -631,6 +904,7 @@ impl PeerActor {
             last_time_peer_requested: AtomicCell::new(None),
             last_time_received_message: AtomicCell::new(now),
             established_time: now,
+            handshake_rtt: self.handshake_sent_at.map(|sent_at| now - sent_at),
             send_accounts_data_demux: demux::Demux::new(
                 self.network_state.config.accounts_data_broadcast_rate_limit,
             ),
@@ -638,6 +912,7 @@ impl PeerActor {
 
         let tracker = self.tracker.clone();
         let clock = self.clock.clone();
+        let max_peer_msg_per_min = self.network_state.config.max_peer_msg_per_min;
 
         let mut interval =
             time::Interval::new(clock.now(), self.network_state.config.peer_stats_period);
@@ -652,10 +927,58 @@ impl PeerActor {
                         .received_bytes_per_sec
                         .store(received.bytes_per_min / 60, Ordering::Relaxed);
                     conn.stats.sent_bytes_per_sec.store(sent.bytes_per_min / 60, Ordering::Relaxed);
+                    if let Some(max_peer_msg_per_min) = max_peer_msg_per_min {
+                        let received_msgs =
+                            tracker.lock().rate_limited_messages.minute_stats(&clock).count_per_min;
+                        if received_msgs > max_peer_msg_per_min {
+                            tracing::warn!(
+                                target: "network",
+                                peer_id = %conn.peer_info.id,
+                                received_msgs,
+                                max_peer_msg_per_min,
+                                "Banning peer for sending too many messages"
+                            );
+                            metrics::PEER_ABUSIVE_BANS.inc();
+                            conn.stop(Some(ReasonForBan::Abusive));
+                            return;
+                        }
+                    }
                 }
             })
         });
 
+        // Peers running an older version can't parse the keep-alives, and Borsh can't encode them.
+        if let (Some(period), Some(Encoding::Proto)) =
+            (self.network_state.config.keep_alive_period, self.encoding())
+        {
+            let clock = self.clock.clone();
+            let max_missed = self.network_state.config.keep_alive_max_missed;
+            let mut interval = time::Interval::new(clock.now() + period, period);
+            ctx.spawn(
+                wrap_future({
+                    let conn = conn.clone();
+                    async move {
+                        let mut nonce = 0;
+                        loop {
+                            interval.tick(&clock).await;
+                            nonce += 1;
+                            if let Some(missed) = conn.stats.keep_alive_sent(nonce, clock.now()) {
+                                if missed >= max_missed {
+                                    tracing::info!(target: "network", peer_id = %conn.peer_info.id, missed, "Peer stopped answering the keep-alive pings");
+                                    metrics::PEER_KEEP_ALIVE_TIMEOUTS.inc();
+                                    return;
+                                }
+                            }
+                            conn.send_message(Arc::new(PeerMessage::KeepAlive(KeepAlive {
+                                nonce,
+                            })));
+                        }
+                    }
+                })
+                .map(|(), act: &mut PeerActor, ctx| act.stop(ctx, ClosingReason::KeepAliveTimeout)),
+            );
+        }
+
         // This time is used to figure out when the first run of the callbacks it run.
         // It is important that it is set here (rather than calling clock.now() within the future) - as it makes testing a lot easier (and more deterministic).
 
@@ -689,32 +1012,18 @@ impl PeerActor {
                         // TIER1 is strictly reserved for BFT consensensus messages,
                         // so all kinds of periodical syncs happen only on TIER2 connections.
                         if tier==tcp::Tier::T2 {
-                            // Trigger a full accounts data sync periodically.
+                            // Trigger the initial full accounts data sync. Subsequent full
+                            // syncs are performed periodically by NetworkState, for all
+                            // connections at once (see accounts_data_full_sync()).
                             // Note that AccountsData is used to establish TIER1 network,
                             // it is broadcasted over TIER2 network. This is a bootstrapping
                             // mechanism, because TIER2 is established before TIER1.
-                            //
-                            // TODO(gprusak): consider whether it wouldn't be more uniform to just
-                            // send full sync from both sides of the connection independently. Or
-                            // perhaps make the full sync request a separate message which doesn't
-                            // carry the accounts_data at all.
                             if conn.peer_type == PeerType::Outbound {
-                                ctx.spawn(wrap_future({
-                                    let clock = act.clock.clone();
-                                    let conn = conn.clone();
-                                    let network_state = act.network_state.clone();
-                                    let mut interval = time::Interval::new(clock.now(),ACCOUNTS_DATA_FULL_SYNC_INTERVAL);
-                                    async move {
-                                        loop {
-                                            interval.tick(&clock).await;
-                                            conn.send_message(Arc::new(PeerMessage::SyncAccountsData(SyncAccountsData{
-                                                accounts_data: network_state.accounts_data.load().data.values().cloned().collect(),
-                                                incremental: false,
-                                                requesting_full_sync: true,
-                                            })));
-                                        }
-                                    }
-                                }));
+                                conn.send_message(Arc::new(PeerMessage::SyncAccountsData(SyncAccountsData{
+                                    accounts_data: act.network_state.accounts_data.load().data.values().cloned().collect(),
+                                    incremental: false,
+                                    requesting_full_sync: true,
+                                })));
                             }
                             // Exchange peers periodically.
                             ctx.spawn(wrap_future({
@@ -779,7 +1088,7 @@ impl PeerActor {
                             act.sync_routing_table();
                         }
 
-                        act.network_state.config.event_sink.push(Event::HandshakeCompleted(HandshakeCompletedEvent{
+                        act.network_state.config.push_event(Event::HandshakeCompleted(HandshakeCompletedEvent{
                             stream_id: act.stream_id,
                             edge,
                             tier: conn.tier,
@@ -803,10 +1112,11 @@ impl PeerActor {
             metrics::EDGE_TOMBSTONE_SENDING_SKIPPED.inc();
         }
         let known_accounts = self.network_state.graph.routing_table.get_announce_accounts();
-        self.send_message_or_log(&PeerMessage::SyncRoutingTable(RoutingTableUpdate::new(
-            known_edges,
-            known_accounts,
-        )));
+        self.send_message_or_log(&PeerMessage::SyncRoutingTable(RoutingTableUpdate {
+            edges: known_edges,
+            accounts: known_accounts,
+            key_transitions: self.network_state.peer_store.key_transitions(),
+        }));
     }
 
     fn handle_msg_connecting(&mut self, ctx: &mut actix::Context<Self>, msg: PeerMessage) {
@@ -818,7 +1128,10 @@ impl PeerActor {
                 match reason {
                     HandshakeFailureReason::GenesisMismatch(genesis) => {
                         tracing::warn!(target: "network", "Attempting to connect to a node ({}) with a different genesis block. Our genesis: {:?}, their genesis: {:?}", peer_info, self.network_state.genesis_id, genesis);
-                        self.stop(ctx, ClosingReason::HandshakeFailed);
+                        self.stop(
+                            ctx,
+                            ClosingReason::HandshakeFailed(HandshakeFailedReason::GenesisMismatch),
+                        );
                     }
                     HandshakeFailureReason::ProtocolVersionMismatch {
                         version,
@@ -830,7 +1143,12 @@ impl PeerActor {
                             || common_version < PEER_MIN_ALLOWED_PROTOCOL_VERSION
                         {
                             tracing::warn!(target: "network", "Unable to connect to a node ({}) due to a network protocol version mismatch. Our version: {:?}, their: {:?}", peer_info, (PROTOCOL_VERSION, PEER_MIN_ALLOWED_PROTOCOL_VERSION), (version, oldest_supported_version));
-                            self.stop(ctx, ClosingReason::HandshakeFailed);
+                            self.stop(
+                                ctx,
+                                ClosingReason::HandshakeFailed(
+                                    HandshakeFailedReason::ProtocolVersionMismatch,
+                                ),
+                            );
                             return;
                         }
                         handshake_spec.protocol_version = common_version;
@@ -843,7 +1161,10 @@ impl PeerActor {
                     HandshakeFailureReason::InvalidTarget => {
                         tracing::debug!(target: "network", "Peer found was not what expected. Updating peer info with {:?}", peer_info);
                         self.network_state.peer_store.add_direct_peer(&self.clock, peer_info);
-                        self.stop(ctx, ClosingReason::HandshakeFailed);
+                        self.stop(
+                            ctx,
+                            ClosingReason::HandshakeFailed(HandshakeFailedReason::InvalidTarget),
+                        );
                     }
                 }
             }
@@ -871,7 +1192,10 @@ impl PeerActor {
                 // Disconnect if neighbor sent an invalid edge.
                 if !ok {
                     tracing::info!(target: "network", "{:?}: Peer {:?} sent invalid edge. Disconnect.", self.my_node_id(), self.peer_addr);
-                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    self.stop(
+                        ctx,
+                        ClosingReason::HandshakeFailed(HandshakeFailedReason::InvalidLastEdge),
+                    );
                     return;
                 }
                 // Recreate the edge with a newer nonce.
@@ -955,15 +1279,38 @@ impl PeerActor {
                 network_state.shards_manager_adapter.send(
                     ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
                         partial_encoded_chunk_response: response,
+                        peer_id,
+                        received_time: clock.now().into(),
+                    },
+                );
+                None
+            }
+            RoutedMessageBody::FullChunkRequest(request) => {
+                network_state.shards_manager_adapter.send(
+                    ShardsManagerRequestFromNetwork::ProcessFullChunkRequest {
+                        full_chunk_request: request,
+                        route_back: msg_hash,
+                    },
+                );
+                None
+            }
+            RoutedMessageBody::FullChunkResponse(response) => {
+                network_state.shards_manager_adapter.send(
+                    ShardsManagerRequestFromNetwork::ProcessFullChunkResponse {
+                        full_chunk_response: response,
+                        peer_id,
                         received_time: clock.now().into(),
                     },
                 );
                 None
             }
             RoutedMessageBody::VersionedPartialEncodedChunk(chunk) => {
-                network_state
-                    .shards_manager_adapter
-                    .send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(chunk));
+                network_state.shards_manager_adapter.send(
+                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                        partial_encoded_chunk: chunk,
+                        peer_id: Some(peer_id),
+                    },
+                );
                 None
             }
             RoutedMessageBody::PartialEncodedChunkForward(msg) => {
@@ -987,19 +1334,40 @@ impl PeerActor {
     }
 
     fn receive_message(
-        &self,
+        &mut self,
         ctx: &mut actix::Context<Self>,
         conn: &connection::Connection,
         msg: PeerMessage,
     ) {
         let _span = tracing::trace_span!(target: "network", "receive_message").entered();
-        // This is a fancy way to clone the message iff event_sink is non-null.
-        // If you have a better idea on how to achieve that, feel free to improve this.
+        let permit = match &self.expensive_requests {
+            Some(limiter) if expensive_requests::is_expensive(&msg) => {
+                let sent_by_peer = match &msg {
+                    PeerMessage::Routed(msg) => msg.author == conn.peer_info.id,
+                    _ => true,
+                };
+                match limiter.admit(self.clock.now(), sent_by_peer) {
+                    expensive_requests::Admission::Accepted(permit) => Some(permit),
+                    expensive_requests::Admission::Rejected => {
+                        metrics::MessageDropped::ExpensiveRequestsLimitExceeded.inc_peer_msg(&msg);
+                        tracing::debug!(target: "network", peer_id = ?conn.peer_info.id, "Dropping {} over the limit of expensive requests", msg.msg_variant());
+                        return;
+                    }
+                    expensive_requests::Admission::Abusive => {
+                        tracing::info!(target: "network", peer_id = ?conn.peer_info.id, "Peer persistently exceeds the limit of expensive requests. Ban.");
+                        self.stop(ctx, ClosingReason::Ban(ReasonForBan::Abusive));
+                        return;
+                    }
+                }
+            }
+            _ => None,
+        };
+        // Clone the message only if the event is observed.
         let message_processed_event = self
             .network_state
             .config
-            .event_sink
-            .delayed_push(|| Event::MessageProcessed(conn.tier, msg.clone()));
+            .event_enabled(EventKind::MessageProcessed)
+            .then(|| Event::MessageProcessed(conn.tier, msg.clone()));
         let was_requested = match &msg {
             PeerMessage::Block(block) => {
                 self.network_state.txns_since_last_block.store(0, Ordering::Release);
@@ -1014,7 +1382,14 @@ impl PeerActor {
                 });
                 let mut tracker = self.tracker.lock();
                 tracker.push_received(hash);
-                tracker.has_request(&hash)
+                let was_requested = tracker.has_request(&hash);
+                // The requested blocks are not propagated, but synced.
+                if let (Some(recorder), false) =
+                    (&self.network_state.block_propagation, was_requested)
+                {
+                    recorder.record(&self.clock, block, &conn.peer_info.id);
+                }
+                was_requested
             }
             _ => false,
         };
@@ -1022,6 +1397,8 @@ impl PeerActor {
         let network_state = self.network_state.clone();
         let peer_id = conn.peer_info.id.clone();
         ctx.spawn(wrap_future(async move {
+            // Held until the response is ready.
+            let _permit = permit;
             Ok(match msg {
                 PeerMessage::Routed(msg) => {
                     let msg_hash = msg.hash();
@@ -1068,7 +1445,9 @@ impl PeerActor {
                     Ok(None) => {}
                     Err(ban_reason) => act.stop(ctx, ClosingReason::Ban(ban_reason)),
                 }
-                message_processed_event();
+                if let Some(event) = message_processed_event {
+                    act.network_state.config.push_event(event);
+                }
             }),
         );
     }
@@ -1079,16 +1458,19 @@ impl PeerActor {
         }
         tracing::trace!(target: "network", route_back = ?msg.clone(), "Received peer message that requires response");
         let from = &conn.peer_info.id;
+        let expires_at = self.network_state.route_back_expires_at(msg);
         match conn.tier {
-            tcp::Tier::T1 => self.network_state.tier1_route_back.lock().insert(
+            tcp::Tier::T1 => self.network_state.tier1_route_back.lock().insert_with_expiry(
                 &self.clock,
                 msg.hash(),
                 from.clone(),
+                expires_at,
             ),
             tcp::Tier::T2 => self.network_state.graph.routing_table.add_route_back(
                 &self.clock,
                 msg.hash(),
                 from.clone(),
+                expires_at,
             ),
         }
     }
@@ -1120,6 +1502,16 @@ impl PeerActor {
                 // Received handshake after already have seen handshake from this peer.
                 tracing::debug!(target: "network", "Duplicate handshake from {}", self.peer_info);
             }
+            PeerMessage::KeepAlive(keep_alive) => {
+                self.send_message_or_log(&PeerMessage::KeepAliveResponse(keep_alive));
+                self.network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
+            }
+            PeerMessage::KeepAliveResponse(KeepAlive { nonce }) => {
+                if let Some(rtt) = conn.stats.keep_alive_received(nonce, self.clock.now()) {
+                    metrics::PEER_KEEP_ALIVE_RTT.observe(rtt.as_seconds_f64());
+                }
+                self.network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
+            }
             PeerMessage::PeersRequest(PeersRequest { max_peers, max_direct_peers }) => {
                 let mut num_peers = self.network_state.config.max_send_peers;
                 if let Some(max_peers) = max_peers {
@@ -1127,7 +1519,9 @@ impl PeerActor {
                 }
                 let peers = self.network_state.peer_store.healthy_peers(num_peers as usize);
 
+                // The peers which don't listen for connections are not advertised.
                 let mut direct_peers = self.network_state.get_direct_peers();
+                direct_peers.retain(|peer_info| peer_info.addr.is_some());
                 if let Some(max_direct_peers) = max_direct_peers {
                     if direct_peers.len() > max_direct_peers as usize {
                         direct_peers = direct_peers
@@ -1143,10 +1537,7 @@ impl PeerActor {
                         direct_peers,
                     }));
                 }
-                self.network_state
-                    .config
-                    .event_sink
-                    .push(Event::MessageProcessed(conn.tier, peer_msg));
+                self.network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
             }
             PeerMessage::PeersResponse(PeersResponse { peers, direct_peers }) => {
                 tracing::debug!(target: "network", "Received peers from {}: {} peers and {} direct peers.", self.peer_info, peers.len(), direct_peers.len());
@@ -1173,10 +1564,7 @@ impl PeerActor {
                     direct_peers.into_iter().filter(|peer_info| peer_info.id != node_id),
                 );
 
-                self.network_state
-                    .config
-                    .event_sink
-                    .push(Event::MessageProcessed(conn.tier, peer_msg));
+                self.network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
             }
             PeerMessage::RequestUpdateNonce(edge_info) => {
                 let clock = self.clock.clone();
@@ -1203,23 +1591,28 @@ impl PeerActor {
                             }
                         }
                     };
-                    network_state
-                        .config
-                        .event_sink
-                        .push(Event::MessageProcessed(conn.tier, peer_msg));
+                    network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
                 }));
             }
-            PeerMessage::SyncRoutingTable(rtu) => {
+            PeerMessage::SyncRoutingTable(mut rtu) => {
+                if !rtu.key_transitions.is_empty() {
+                    let limit = self.network_state.config.key_transitions_rate_limit;
+                    let n = rtu.key_transitions.len() as u64;
+                    // The peer sends the dropped transitions again with the next full sync.
+                    if self.key_transitions_bucket.try_acquire(self.clock.now(), &limit, n).is_err()
+                    {
+                        metrics::MessageDropped::KeyTransitionsRateLimitExceeded
+                            .inc_peer_msg(&peer_msg);
+                        rtu.key_transitions.clear();
+                    }
+                }
                 let clock = self.clock.clone();
                 let conn = conn.clone();
                 let network_state = self.network_state.clone();
                 ctx.spawn(wrap_future(async move {
                     Self::handle_sync_routing_table(&clock, &network_state, conn.clone(), rtu)
                         .await;
-                    network_state
-                        .config
-                        .event_sink
-                        .push(Event::MessageProcessed(conn.tier, peer_msg));
+                    network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
                 }));
             }
             PeerMessage::SyncAccountsData(msg) => {
@@ -1248,10 +1641,7 @@ impl PeerActor {
                 }
                 // Early exit, if there is no data in the message.
                 if msg.accounts_data.is_empty() {
-                    network_state
-                        .config
-                        .event_sink
-                        .push(Event::MessageProcessed(conn.tier, peer_msg));
+                    network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
                     return;
                 }
                 let network_state = self.network_state.clone();
@@ -1270,19 +1660,25 @@ impl PeerActor {
                             }
                         }));
                     }
-                    network_state
-                        .config
-                        .event_sink
-                        .push(Event::MessageProcessed(conn.tier, peer_msg));
+                    network_state.config.push_event(Event::MessageProcessed(conn.tier, peer_msg));
                 }));
             }
-            PeerMessage::Routed(mut msg) => {
+            PeerMessage::Routed(msg) => {
                 tracing::trace!(
                     target: "network",
                     "Received routed message from {} to {:?}.",
                     self.peer_info,
                     msg.target);
                 let for_me = self.network_state.message_for_me(&msg.target);
+                self.network_state.trace_routed_message(
+                    &self.clock,
+                    &msg,
+                    routed_trace::Event::Received {
+                        from: conn.peer_info.id.clone(),
+                        tier: conn.tier,
+                        for_me,
+                    },
+                );
                 if for_me {
                     // Check if we have already received this message.
                     let fastest = self
@@ -1295,13 +1691,13 @@ impl PeerActor {
                     metrics::record_routed_msg_metrics(&self.clock, &msg, conn.tier, fastest);
                 }
 
-                // Drop duplicated messages routed within DROP_DUPLICATED_MESSAGES_PERIOD ms
-                let key = (msg.author.clone(), msg.target.clone(), msg.signature.clone());
+                // Drop duplicated messages routed within routed_message_dedup_period.
+                let key = msg.hash();
                 let now = self.clock.now();
                 if let Some(&t) = self.routed_message_cache.get(&key) {
-                    if now <= t + DROP_DUPLICATED_MESSAGES_PERIOD {
+                    if now <= t + self.network_state.config.routed_message_dedup_period {
                         metrics::MessageDropped::Duplicate.inc(&msg.body);
-                        self.network_state.config.event_sink.push(Event::RoutedMessageDropped);
+                        self.network_state.config.push_event(Event::RoutedMessageDropped);
                         tracing::debug!(target: "network", "Dropping duplicated message from {} to {:?}", msg.author, msg.target);
                         return;
                     }
@@ -1321,54 +1717,82 @@ impl PeerActor {
                 }
                 self.routed_message_cache.put(key, now);
 
+                if self.network_state.config.batch_verify_routed_messages {
+                    let network_state = self.network_state.clone();
+                    ctx.spawn(
+                        wrap_future(async move { network_state.verify_routed_message(msg).await })
+                            .map(move |res, act: &mut PeerActor, ctx| match res {
+                                Some((msg, true)) => {
+                                    act.handle_verified_routed_message(ctx, &conn, msg, for_me)
+                                }
+                                Some((_, false)) => act
+                                    .stop(ctx, ClosingReason::Ban(ReasonForBan::InvalidSignature)),
+                                // NetworkState is being dropped.
+                                None => {}
+                            }),
+                    );
+                    return;
+                }
                 if !msg.verify() {
                     // Received invalid routed message from peer.
                     self.stop(ctx, ClosingReason::Ban(ReasonForBan::InvalidSignature));
                     return;
                 }
+                self.handle_verified_routed_message(ctx, &conn, msg, for_me);
+            }
+            msg => self.receive_message(ctx, &conn, msg),
+        }
+    }
 
-                self.add_route_back(&conn, msg.as_ref());
-                if for_me {
-                    // Handle Ping and Pong message if they are for us without sending to client.
-                    // i.e. Return false in case of Ping and Pong
-                    match &msg.body {
-                        RoutedMessageBody::Ping(ping) => {
-                            self.network_state.send_pong(
-                                &self.clock,
-                                conn.tier,
-                                ping.nonce,
-                                msg.hash(),
-                            );
-                            // TODO(gprusak): deprecate Event::Ping/Pong in favor of
-                            // MessageProcessed.
-                            self.network_state.config.event_sink.push(Event::Ping(ping.clone()));
-                            self.network_state
-                                .config
-                                .event_sink
-                                .push(Event::MessageProcessed(conn.tier, PeerMessage::Routed(msg)));
-                        }
-                        RoutedMessageBody::Pong(pong) => {
-                            self.network_state.config.event_sink.push(Event::Pong(pong.clone()));
-                            self.network_state
-                                .config
-                                .event_sink
-                                .push(Event::MessageProcessed(conn.tier, PeerMessage::Routed(msg)));
-                        }
-                        _ => self.receive_message(ctx, &conn, PeerMessage::Routed(msg)),
+    /// Handles a routed message with a valid signature: either processes it, if it is for us,
+    /// or forwards it.
+    fn handle_verified_routed_message(
+        &mut self,
+        ctx: &mut actix::Context<Self>,
+        conn: &connection::Connection,
+        mut msg: Box<RoutedMessageV2>,
+        for_me: bool,
+    ) {
+        self.add_route_back(conn, msg.as_ref());
+        if for_me {
+            // Handle Ping and Pong message if they are for us without sending to client.
+            // i.e. Return false in case of Ping and Pong
+            match &msg.body {
+                RoutedMessageBody::Ping(ping) => {
+                    self.network_state.send_pong(&self.clock, conn.tier, ping.nonce, msg.hash());
+                    // TODO(gprusak): deprecate Event::Ping/Pong in favor of
+                    // MessageProcessed.
+                    if self.network_state.config.event_enabled(EventKind::Ping) {
+                        self.network_state.config.push_event(Event::Ping(ping.clone()));
                     }
-                } else {
-                    if msg.decrease_ttl() {
-                        self.network_state.send_message_to_peer(&self.clock, conn.tier, msg);
-                    } else {
-                        self.network_state.config.event_sink.push(Event::RoutedMessageDropped);
-                        tracing::warn!(target: "network", ?msg, from = ?conn.peer_info.id, "Message dropped because TTL reached 0.");
-                        metrics::ROUTED_MESSAGE_DROPPED
-                            .with_label_values(&[msg.body_variant()])
-                            .inc();
+                    self.network_state
+                        .config
+                        .push_event(Event::MessageProcessed(conn.tier, PeerMessage::Routed(msg)));
+                }
+                RoutedMessageBody::Pong(pong) => {
+                    self.network_state.pinger.pong_received(&self.clock, pong, msg.ttl);
+                    if self.network_state.config.event_enabled(EventKind::Pong) {
+                        self.network_state.config.push_event(Event::Pong(pong.clone()));
                     }
+                    self.network_state
+                        .config
+                        .push_event(Event::MessageProcessed(conn.tier, PeerMessage::Routed(msg)));
                 }
+                _ => self.receive_message(ctx, conn, PeerMessage::Routed(msg)),
+            }
+        } else {
+            if msg.decrease_ttl() {
+                self.network_state.send_message_to_peer(&self.clock, conn.tier, msg);
+            } else {
+                self.network_state.config.push_event(Event::RoutedMessageDropped);
+                self.network_state.trace_routed_message(
+                    &self.clock,
+                    &msg,
+                    routed_trace::Event::Dropped { reason: "ttl" },
+                );
+                tracing::warn!(target: "network", ?msg, from = ?conn.peer_info.id, "Message dropped because TTL reached 0.");
+                metrics::ROUTED_MESSAGE_DROPPED.with_label_values(&[msg.body_variant()]).inc();
             }
-            msg => self.receive_message(ctx, &conn, msg),
         }
     }
 
@@ -1382,6 +1806,9 @@ impl PeerActor {
         if let Err(ban_reason) = network_state.add_edges(&clock, rtu.edges).await {
             conn.stop(Some(ban_reason));
         }
+        if let Err(ban_reason) = network_state.add_key_transitions(rtu.key_transitions).await {
+            conn.stop(Some(ban_reason));
+        }
         // For every announce we received, we fetch the last announce with the same account_id
         // that we already broadcasted. Client actor will both verify signatures of the received announces
         // as well as filter out those which are older than the fetched ones (to avoid overriding
@@ -1419,7 +1846,10 @@ impl actix::Actor for PeerActor {
             move |act, ctx| match act.peer_status {
                 PeerStatus::Connecting { .. } => {
                     tracing::info!(target: "network", "Handshake timeout expired for {}", act.peer_info);
-                    act.stop(ctx, ClosingReason::HandshakeFailed);
+                    // If we have rejected the peer's Handshake, report why rather than the
+                    // timeout: the peer is expected to close the connection on its own.
+                    let reason = act.handshake_rejection.unwrap_or(HandshakeFailedReason::Timeout);
+                    act.stop(ctx, ClosingReason::HandshakeFailed(reason));
                 }
                 _ => {}
             },
@@ -1430,11 +1860,11 @@ impl actix::Actor for PeerActor {
             &self.peer_status
         {
             self.send_handshake(handshake_spec.clone());
+            self.handshake_sent_at = Some(self.clock.now());
         }
-        self.network_state
-            .config
-            .event_sink
-            .push(Event::HandshakeStarted(HandshakeStartedEvent { stream_id: self.stream_id }));
+        self.network_state.config.push_event(Event::HandshakeStarted(HandshakeStartedEvent {
+            stream_id: self.stream_id,
+        }));
     }
 
     fn stopping(&mut self, _: &mut Self::Context) -> actix::Running {
@@ -1470,10 +1900,19 @@ impl actix::Actor for PeerActor {
             // it was not registered in the NetworkState,
             // so there is nothing to be done.
             PeerStatus::Connecting(..) => {
+                if let Some(ClosingReason::HandshakeFailed(reason)) = &self.closing_reason {
+                    let direction = match self.peer_type {
+                        PeerType::Inbound => "inbound",
+                        PeerType::Outbound => "outbound",
+                    };
+                    metrics::PEER_HANDSHAKE_FAILURES
+                        .with_label_values(&[direction, reason.into()])
+                        .inc();
+                }
                 // TODO(gprusak): reporting ConnectionClosed event is quite scattered right now and
                 // it is very ugly: it may happen here, in spawn_inner, or in NetworkState::unregister().
                 // Centralize it, once we get rid of actix.
-                self.network_state.config.event_sink.push(Event::ConnectionClosed(
+                self.network_state.config.push_event(Event::ConnectionClosed(
                     ConnectionClosedEvent {
                         stream_id: self.stream_id,
                         reason: self.closing_reason.clone().unwrap_or(ClosingReason::Unknown),
@@ -1493,7 +1932,9 @@ impl actix::Actor for PeerActor {
                 );
             }
         }
-        actix::Arbiter::current().stop();
+        if let Some(arbiter) = self.arbiter.take() {
+            arbiter.release();
+        }
     }
 }
 
@@ -1572,6 +2013,12 @@ impl actix::Handler<stream::Frame> for PeerActor {
             metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES
                 .with_label_values(&labels)
                 .inc_by(msg.len() as u64);
+            self.tracker.lock().increment_received_message(
+                &self.clock,
+                &peer_msg,
+                msg.len() as u64,
+            );
+            self.stats.record_received(peer_msg.msg_variant(), msg.len() as u64);
         }
         match &self.peer_status {
             PeerStatus::Connecting { .. } => self.handle_msg_connecting(ctx, peer_msg),
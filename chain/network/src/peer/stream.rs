@@ -1,21 +1,39 @@
+use crate::concurrency::atomic_cell::AtomicCell;
+use crate::concurrency::rate;
+use crate::config::FaultProfile;
 use crate::peer_manager::connection;
 use crate::stats::metrics;
 use crate::tcp;
 use actix::fut::future::wrap_future;
 use actix::AsyncContext as _;
 use bytesize::{GIB, MIB};
+use near_primitives::time;
+use once_cell::sync::OnceCell;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::io::AsyncReadExt as _;
 use tokio::io::AsyncWriteExt as _;
 
 /// Maximum size of network message in encoded format.
 /// We encode length as `u32`, and therefore maximum size can't be larger than `u32::MAX`.
-const NETWORK_MESSAGE_MAX_SIZE_BYTES: usize = 512 * MIB as usize;
+pub(crate) const NETWORK_MESSAGE_MAX_SIZE_BYTES: usize = 512 * MIB as usize;
 /// Maximum capacity of write buffer in bytes.
 const MAX_WRITE_BUFFER_CAPACITY_BYTES: usize = GIB as usize;
+/// Size of the frames into which the large messages are split, if the peer supports message
+/// fragmentation.
+pub(crate) const MESSAGE_FRAGMENT_SIZE_BYTES: usize = 16 * MIB as usize;
+/// Maximum size of a message split into multiple frames. It bounds the size of the buffer in
+/// which a connection reassembles the message out of its frames.
+const FRAGMENTED_MESSAGE_MAX_SIZE_BYTES: usize = 768 * MIB as usize;
+/// Bit of the length of a frame marking that more frames of the same message follow.
+/// Lengths of the frames never exceed NETWORK_MESSAGE_MAX_SIZE_BYTES, so the bit is otherwise
+/// unused.
+const MORE_FRAGMENTS_FLAG: u32 = 1 << 31;
+
+type QueueSend = tokio::sync::mpsc::UnboundedSender<Frame>;
+type QueueRecv = tokio::sync::mpsc::UnboundedReceiver<Frame>;
 
 type ReadHalf = tokio::io::ReadHalf<tokio::net::TcpStream>;
 type WriteHalf = tokio::io::WriteHalf<tokio::net::TcpStream>;
@@ -40,6 +58,43 @@ pub(crate) enum RecvError {
 #[rtype(result = "()")]
 pub(crate) struct Frame(pub Vec<u8>);
 
+/// Priority of an outgoing frame. The send queue consists of a separate lane per priority
+/// and a frame is written to the socket only once the lanes of higher priority are empty,
+/// so that e.g. approvals and chunk parts don't wait behind multi-megabyte state responses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::AsRefStr)]
+pub(crate) enum Priority {
+    /// Handshakes, blocks and approvals.
+    Consensus,
+    /// Partial encoded chunks, their requests and forwards.
+    Chunks,
+    /// Block, header and state sync.
+    Sync,
+    Misc,
+}
+
+impl Priority {
+    /// All the priorities, from the highest to the lowest.
+    pub const ALL: [Priority; 4] =
+        [Priority::Consensus, Priority::Chunks, Priority::Sync, Priority::Misc];
+
+    /// Maximal number of bytes queued in the lane of this priority. Frames which would exceed
+    /// the budget are dropped, unless the lane is empty, so that a single frame larger than the
+    /// budget can still be sent. Consensus frames are never dropped, they are subject only to
+    /// MAX_WRITE_BUFFER_CAPACITY_BYTES, like all the other frames.
+    fn budget_bytes(self) -> Option<usize> {
+        match self {
+            Priority::Consensus => None,
+            Priority::Chunks => Some(128 * MIB as usize),
+            Priority::Sync => Some(512 * MIB as usize),
+            Priority::Misc => Some(256 * MIB as usize),
+        }
+    }
+
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
 /// Stream critical error.
 /// Actor is responsible for calling ctx.stop() after receiving stream::Error.
 /// Actor might receive more than 1 stream::Error, but should call ctx.stop() just after the
@@ -56,8 +111,42 @@ pub(crate) enum Error {
     Recv(#[source] RecvError),
 }
 
+/// Limits on the rate at which the bytes are read from a single connection, per tier.
+/// Shared by all the connections, so that they can be updated at runtime.
+pub(crate) struct RecvLimits {
+    tier1: AtomicCell<rate::Limit>,
+    tier2: AtomicCell<rate::Limit>,
+}
+
+impl RecvLimits {
+    pub fn new(tier1: rate::Limit, tier2: rate::Limit) -> Self {
+        Self { tier1: AtomicCell::new(tier1), tier2: AtomicCell::new(tier2) }
+    }
+
+    pub fn get(&self, tier: tcp::Tier) -> rate::Limit {
+        match tier {
+            tcp::Tier::T1 => self.tier1.load(),
+            tcp::Tier::T2 => self.tier2.load(),
+        }
+    }
+
+    pub fn set(&self, tier1: rate::Limit, tier2: rate::Limit) {
+        self.tier1.store(tier1);
+        self.tier2.store(tier2);
+    }
+}
+
 pub(crate) struct FramedStream<Actor: actix::Actor> {
-    queue_send: tokio::sync::mpsc::UnboundedSender<Frame>,
+    /// Send queue lanes, indexed by Priority::lane().
+    queue_send: Vec<QueueSend>,
+    /// Number of bytes queued in each lane.
+    lane_bytes: Arc<[AtomicUsize; Priority::ALL.len()]>,
+    /// Whether the peer accepts messages split into multiple frames.
+    fragmentation: Arc<AtomicBool>,
+    /// Network faults simulated on the received messages, if any.
+    faults: Arc<OnceCell<FaultProfile>>,
+    /// Tier of the connection, which determines the receive rate limit.
+    recv_tier: Arc<AtomicCell<tcp::Tier>>,
     stats: Arc<connection::Stats>,
     send_buf_size_metric: Arc<metrics::IntGaugeGuard>,
     addr: actix::Addr<Actor>,
@@ -69,13 +158,23 @@ where
         + actix::Handler<Error>
         + actix::Handler<Frame>,
 {
+    /// Spawns the send and receive loops of the stream. The received bytes are throttled
+    /// according to the `recv_limits` of the given tier, until `set_recv_tier` is called.
     pub fn spawn(
         ctx: &mut actix::Context<Actor>,
+        clock: time::Clock,
         stream: tcp::Stream,
+        tier: tcp::Tier,
+        recv_limits: Arc<RecvLimits>,
         stats: Arc<connection::Stats>,
     ) -> Self {
         let (tcp_recv, tcp_send) = tokio::io::split(stream.stream);
-        let (queue_send, queue_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (queue_send, queue_recv): (Vec<_>, Vec<_>) =
+            Priority::ALL.iter().map(|_| tokio::sync::mpsc::unbounded_channel()).unzip();
+        let lane_bytes: Arc<[AtomicUsize; Priority::ALL.len()]> = Arc::default();
+        let fragmentation = Arc::new(AtomicBool::new(false));
+        let faults = Arc::new(OnceCell::new());
+        let recv_tier = Arc::new(AtomicCell::new(tier));
         let send_buf_size_metric = Arc::new(metrics::MetricGuard::new(
             &*metrics::PEER_DATA_WRITE_BUFFER_SIZE,
             vec![stream.peer_addr.to_string()],
@@ -83,9 +182,14 @@ where
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
+            let lane_bytes = lane_bytes.clone();
+            let fragmentation = fragmentation.clone();
             let m = send_buf_size_metric.clone();
             async move {
-                if let Err(err) = Self::run_send_loop(tcp_send, queue_recv, stats, m).await {
+                if let Err(err) =
+                    Self::run_send_loop(tcp_send, queue_recv, lane_bytes, fragmentation, stats, m)
+                        .await
+                {
                     addr.do_send(Error::Send(SendError::IO(err)));
                 }
             }
@@ -93,23 +197,71 @@ where
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
+            let faults = faults.clone();
+            let recv_tier = recv_tier.clone();
             async move {
-                if let Err(err) =
-                    Self::run_recv_loop(stream.peer_addr, tcp_recv, addr.clone(), stats).await
+                if let Err(err) = Self::run_recv_loop(
+                    &clock,
+                    stream.peer_addr,
+                    tcp_recv,
+                    addr.clone(),
+                    faults,
+                    recv_tier,
+                    recv_limits,
+                    stats,
+                )
+                .await
                 {
                     addr.do_send(Error::Recv(err));
                 }
             }
         }));
-        Self { queue_send, stats, send_buf_size_metric, addr: ctx.address() }
+        Self {
+            queue_send,
+            lane_bytes,
+            fragmentation,
+            faults,
+            recv_tier,
+            stats,
+            send_buf_size_metric,
+            addr: ctx.address(),
+        }
+    }
+
+    /// Makes the stream split the messages larger than MESSAGE_FRAGMENT_SIZE_BYTES into multiple
+    /// frames, which allows sending messages larger than NETWORK_MESSAGE_MAX_SIZE_BYTES.
+    /// Should be called once the peer has declared that it supports message fragmentation.
+    pub fn enable_fragmentation(&self) {
+        self.fragmentation.store(true, Ordering::Relaxed);
+    }
+
+    /// Makes the stream drop and delay the received messages according to `profile`,
+    /// to simulate an unreliable network. Only the first call has an effect.
+    pub fn inject_faults(&self, profile: FaultProfile) {
+        let _ = self.faults.set(profile);
     }
 
-    /// Pushes `msg` to the send queue.
+    /// Sets the tier whose receive rate limit applies to the stream.
+    /// Should be called once the tier of the connection is known, i.e. after the handshake.
+    pub fn set_recv_tier(&self, tier: tcp::Tier) {
+        self.recv_tier.store(tier);
+    }
+
+    /// Pushes `msg` to the lane of the send queue with the given priority.
     /// Silently drops message if the connection has been closed.
+    /// Drops the message if the byte budget of the lane would be exceeded.
     /// If the message is too large, it will be silently dropped inside run_send_loop.
     /// Emits a critical error to Actor if send queue is full.
-    pub fn send(&self, frame: Frame) {
+    pub fn send(&self, priority: Priority, frame: Frame) {
         let msg = &frame.0;
+        let lane_bytes = &self.lane_bytes[priority.lane()];
+        let queued = lane_bytes.fetch_add(msg.len(), Ordering::Acquire) + msg.len();
+        if queued > msg.len() && priority.budget_bytes().map_or(false, |budget| queued > budget) {
+            lane_bytes.fetch_sub(msg.len(), Ordering::Release);
+            tracing::debug!(target: "network", ?priority, len = msg.len(), "send queue lane is full, dropping message");
+            metrics::MessageDropped::SendQueueLaneFull.inc_unknown_msg();
+            return;
+        }
         let mut buf_size =
             self.stats.bytes_to_send.fetch_add(msg.len() as u64, Ordering::Acquire) as usize;
         buf_size += msg.len();
@@ -125,7 +277,12 @@ where
                 want_max_bytes: MAX_WRITE_BUFFER_CAPACITY_BYTES,
             }));
         }
-        let _ = self.queue_send.send(frame);
+        metrics::PEER_SEND_QUEUE_DEPTH.with_label_values(&[priority.as_ref()]).inc();
+        if let Err(err) = self.queue_send[priority.lane()].send(frame) {
+            // The send loop has already stopped, so it won't account for the frame.
+            lane_bytes.fetch_sub(err.0 .0.len(), Ordering::Release);
+            metrics::PEER_SEND_QUEUE_DEPTH.with_label_values(&[priority.as_ref()]).dec();
+        }
     }
 
     /// Event loop receiving and processing messages.
@@ -134,12 +291,22 @@ where
     /// then the loop will start reading the next message before the subhandler returns.
     /// Loop uses a fixed small buffer allocated by BufReader.
     /// For each message it allocates a Vec with exact size of the message.
+    /// The frames of a message split into multiple frames are accumulated in a separate buffer,
+    /// until the last one arrives.
+    /// Once faults are injected, a delayed message is delivered by a separate task, so the loop
+    /// doesn't wait for it to be processed.
+    /// Before reading a frame, the loop waits until the receive rate limit of the current tier
+    /// admits it. The limit is reevaluated while waiting, so that updates apply immediately.
     // TODO(gprusak): once borsh support is dropped, we can parse a proto
     // directly from the stream.
     async fn run_recv_loop(
+        clock: &time::Clock,
         peer_addr: SocketAddr,
         read: ReadHalf,
         addr: actix::Addr<Actor>,
+        faults: Arc<OnceCell<FaultProfile>>,
+        recv_tier: Arc<AtomicCell<tcp::Tier>>,
+        recv_limits: Arc<RecvLimits>,
         stats: Arc<connection::Stats>,
     ) -> Result<(), RecvError> {
         const READ_BUFFER_CAPACITY: usize = 8 * 1024;
@@ -151,23 +318,68 @@ where
             &metrics::PEER_DATA_READ_BUFFER_SIZE,
             vec![peer_addr.to_string()],
         );
+        let mut bucket = rate::Bucket::new(clock.now(), &recv_limits.get(recv_tier.load()));
+        // Frames of the message being reassembled.
+        let mut fragments = vec![];
         loop {
-            let n = read.read_u32_le().await.map_err(RecvError::IO)? as usize;
+            let len = read.read_u32_le().await.map_err(RecvError::IO)?;
+            let more_fragments = len & MORE_FRAGMENTS_FLAG != 0;
+            let n = (len & !MORE_FRAGMENTS_FLAG) as usize;
             if n > NETWORK_MESSAGE_MAX_SIZE_BYTES {
                 return Err(RecvError::MessageTooLarge {
                     got_bytes: n,
                     want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
                 });
             }
-            msg_size_metric.observe(n as f64);
-            buf_size_metric.set(n as i64);
+            let fragmented = more_fragments || !fragments.is_empty();
+            if fragmented && fragments.len() + n > FRAGMENTED_MESSAGE_MAX_SIZE_BYTES {
+                return Err(RecvError::MessageTooLarge {
+                    got_bytes: fragments.len() + n,
+                    want_max_bytes: FRAGMENTED_MESSAGE_MAX_SIZE_BYTES,
+                });
+            }
+            let tier = recv_tier.load();
+            if bucket.acquire(clock, || recv_limits.get(recv_tier.load()), n as u64).await {
+                metrics::PEER_RECV_THROTTLED.with_label_values(&[tier.as_ref()]).inc();
+            }
+            buf_size_metric.set((fragments.len() + n) as i64);
             let mut buf = vec![0; n];
             let t = metrics::PEER_MSG_READ_LATENCY.start_timer();
             read.read_exact(&mut buf[..]).await.map_err(RecvError::IO)?;
             t.observe_duration();
+            stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
+            if fragmented {
+                metrics::PEER_MESSAGE_FRAGMENTS.with_label_values(&["received"]).inc();
+                fragments.extend_from_slice(&buf);
+                if more_fragments {
+                    continue;
+                }
+                metrics::PEER_FRAGMENTED_MESSAGES.with_label_values(&["received"]).inc();
+                buf = std::mem::take(&mut fragments);
+            }
+            msg_size_metric.observe(buf.len() as f64);
             buf_size_metric.set(0);
             stats.received_messages.fetch_add(1, Ordering::Relaxed);
-            stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
+            if let Some(faults) = faults.get() {
+                match faults.sample(&mut rand::thread_rng()) {
+                    None => {
+                        metrics::PEER_INJECTED_FAULTS.with_label_values(&["dropped"]).inc();
+                        continue;
+                    }
+                    Some(delay) if delay > time::Duration::ZERO => {
+                        metrics::PEER_INJECTED_FAULTS.with_label_values(&["delayed"]).inc();
+                        let addr = addr.clone();
+                        let clock = clock.clone();
+                        let deadline = clock.now() + delay;
+                        tokio::spawn(async move {
+                            clock.sleep_until(deadline).await;
+                            addr.do_send(Frame(buf));
+                        });
+                        continue;
+                    }
+                    Some(_) => {}
+                }
+            }
             if let Err(_) = addr.send(Frame(buf)).await {
                 // We got mailbox error, which means that Actor has stopped,
                 // so we should just close the stream.
@@ -177,19 +389,40 @@ where
     }
     async fn run_send_loop(
         tcp_send: WriteHalf,
-        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<Frame>,
+        mut queue_recv: Vec<QueueRecv>,
+        lane_bytes: Arc<[AtomicUsize; Priority::ALL.len()]>,
+        fragmentation: Arc<AtomicBool>,
         stats: Arc<connection::Stats>,
         buf_size_metric: Arc<metrics::IntGaugeGuard>,
     ) -> io::Result<()> {
         const WRITE_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut writer = tokio::io::BufWriter::with_capacity(WRITE_BUFFER_CAPACITY, tcp_send);
-        while let Some(Frame(mut msg)) = queue_recv.recv().await {
+        while let Some((mut priority, Frame(mut msg))) = next_frame(&mut queue_recv).await {
             // Try writing a batch of messages and flush once at the end.
             loop {
+                lane_bytes[priority.lane()].fetch_sub(msg.len(), Ordering::Release);
+                metrics::PEER_SEND_QUEUE_DEPTH.with_label_values(&[priority.as_ref()]).dec();
+                let fragmentation = fragmentation.load(Ordering::Relaxed);
+                let max_size = if fragmentation {
+                    FRAGMENTED_MESSAGE_MAX_SIZE_BYTES
+                } else {
+                    NETWORK_MESSAGE_MAX_SIZE_BYTES
+                };
                 // TODO(gprusak): sending a too large message should probably be treated as a bug,
                 // since dropping messages may lead to hard-to-debug high-level issues.
-                if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                if msg.len() > max_size {
                     metrics::MessageDropped::InputTooLong.inc_unknown_msg();
+                } else if fragmentation && msg.len() > MESSAGE_FRAGMENT_SIZE_BYTES {
+                    // The frames are written one after another, so that the peer doesn't need
+                    // to reassemble more than one message at a time.
+                    let mut chunks = msg.chunks(MESSAGE_FRAGMENT_SIZE_BYTES).peekable();
+                    while let Some(chunk) = chunks.next() {
+                        let flag = if chunks.peek().is_some() { MORE_FRAGMENTS_FLAG } else { 0 };
+                        writer.write_u32_le(chunk.len() as u32 | flag).await?;
+                        writer.write_all(chunk).await?;
+                        metrics::PEER_MESSAGE_FRAGMENTS.with_label_values(&["sent"]).inc();
+                    }
+                    metrics::PEER_FRAGMENTED_MESSAGES.with_label_values(&["sent"]).inc();
                 } else {
                     writer.write_u32_le(msg.len() as u32).await?;
                     writer.write_all(&msg[..]).await?;
@@ -197,9 +430,9 @@ where
                 stats.messages_to_send.fetch_sub(1, Ordering::Release);
                 stats.bytes_to_send.fetch_sub(msg.len() as u64, Ordering::Release);
                 buf_size_metric.sub(msg.len() as i64);
-                msg = match queue_recv.try_recv() {
-                    Ok(Frame(it)) => it,
-                    Err(_) => break,
+                (priority, Frame(msg)) = match try_next_frame(&mut queue_recv) {
+                    Some(it) => it,
+                    None => break,
                 };
             }
             // This is an unconditional flush, which means that even if new messages
@@ -213,3 +446,28 @@ where
         Ok(())
     }
 }
+
+/// Pops the frame of the highest priority, without waiting.
+fn try_next_frame(queue_recv: &mut [QueueRecv]) -> Option<(Priority, Frame)> {
+    Priority::ALL
+        .iter()
+        .zip(queue_recv.iter_mut())
+        .find_map(|(priority, recv)| Some((*priority, recv.try_recv().ok()?)))
+}
+
+/// Pops the frame of the highest priority, waiting for one if all the lanes are empty.
+/// Returns None once all the lanes are closed and empty.
+pub(super) async fn next_frame(queue_recv: &mut [QueueRecv]) -> Option<(Priority, Frame)> {
+    if let Some(it) = try_next_frame(queue_recv) {
+        return Some(it);
+    }
+    let (frame, lane, _) =
+        futures_util::future::select_all(queue_recv.iter_mut().map(|recv| Box::pin(recv.recv())))
+            .await;
+    match frame {
+        Some(frame) => Some((Priority::ALL[lane], frame)),
+        // All the lanes are closed together, when FramedStream is dropped,
+        // but the other lanes may still contain frames.
+        None => try_next_frame(queue_recv),
+    }
+}
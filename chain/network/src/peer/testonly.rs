@@ -103,7 +103,8 @@ impl PeerHandle {
         let network_state = Arc::new(NetworkState::new(
             &clock,
             store.clone(),
-            peer_store::PeerStore::new(&clock, network_cfg.peer_store.clone()).unwrap(),
+            peer_store::PeerStore::new(&clock, network_cfg.peer_store.clone(), store.clone())
+                .unwrap(),
             network_cfg.verify().unwrap(),
             cfg.chain.genesis_id.clone(),
             fc.clone(),
@@ -113,7 +114,9 @@ impl PeerHandle {
         let actix = ActixSystem::spawn({
             let clock = clock.clone();
             let cfg = cfg.clone();
-            move || PeerActor::spawn(clock, stream, cfg.force_encoding, network_state).unwrap().0
+            move || {
+                PeerActor::spawn(clock, stream, cfg.force_encoding, None, network_state).unwrap().0
+            }
         })
         .await;
         Self { actix, cfg, events: recv, edge: None }
@@ -1,7 +1,8 @@
+use crate::config::NetworkConfig;
 use crate::network_protocol::testonly as data;
 use crate::network_protocol::{
     Encoding, Handshake, HandshakeFailureReason, PartialEdgeInfo, PeerMessage, PeersRequest,
-    PeersResponse, RoutedMessageBody,
+    PeersResponse, RoutedMessageBody, RoutingTableUpdate,
 };
 use crate::peer::testonly::{Event, PeerConfig, PeerHandle};
 use crate::peer_manager::peer_manager_actor::Event as PME;
@@ -13,7 +14,9 @@ use anyhow::Context as _;
 use assert_matches::assert_matches;
 use near_o11y::testonly::init_test_logger;
 use near_primitives::time;
-use near_primitives::version::{PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use near_primitives::version::{
+    ProtocolVersion, PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
 use std::sync::Arc;
 
 async fn test_peer_communication(
@@ -171,6 +174,11 @@ async fn peer_communication() -> anyhow::Result<()> {
                     continue;
                 }
             }
+            // A node accepting Proto sends its Handshake in Proto only, so it can't dial
+            // a Borsh-only peer. Such a peer can still dial it.
+            if (outbound, inbound) == (&None, &Some(Encoding::Borsh)) {
+                continue;
+            }
             test_peer_communication(*outbound, *inbound)
                 .await
                 .with_context(|| format!("(outbound={outbound:?},inbound={inbound:?})"))?;
@@ -179,6 +187,60 @@ async fn peer_communication() -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn test_compression(outbound_zstd: bool, inbound_zstd: bool) {
+    tracing::info!("test_compression({outbound_zstd},{inbound_zstd})");
+
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+
+    let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+    let mut inbound_network = chain.make_config(&mut rng);
+    inbound_network.zstd_compression = inbound_zstd;
+    let mut outbound_network = chain.make_config(&mut rng);
+    outbound_network.zstd_compression = outbound_zstd;
+    let inbound_cfg =
+        PeerConfig { chain: chain.clone(), network: inbound_network, force_encoding: None };
+    let outbound_cfg =
+        PeerConfig { chain: chain.clone(), network: outbound_network, force_encoding: None };
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let mut inbound = PeerHandle::start_endpoint(clock.clock(), inbound_cfg, inbound_stream).await;
+    let mut outbound =
+        PeerHandle::start_endpoint(clock.clock(), outbound_cfg, outbound_stream).await;
+
+    outbound.complete_handshake().await;
+    inbound.complete_handshake().await;
+
+    // Large enough to be compressed, if both peers support the compression.
+    let signers: Vec<_> = (0..20).map(|_| data::make_secret_key(&mut rng)).collect();
+    let mut edges = vec![];
+    for i in 0..signers.len() {
+        for j in 0..i {
+            edges.push(data::make_edge(&signers[i], &signers[j], 1));
+        }
+    }
+    let mut events = inbound.events.from_now();
+    let want = PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_edges(edges));
+    outbound.send(want.clone()).await;
+    events
+        .recv_until(|ev| match ev {
+            Event::Network(PME::MessageProcessed(_, got)) if got == want => Some(()),
+            _ => None,
+        })
+        .await;
+}
+
+#[tokio::test]
+// Verifies that large messages are delivered whether or not zstd compression is negotiated.
+async fn compression() {
+    init_test_logger();
+    for outbound_zstd in [false, true] {
+        for inbound_zstd in [false, true] {
+            test_compression(outbound_zstd, inbound_zstd).await;
+        }
+    }
+}
+
 async fn test_handshake(outbound_encoding: Option<Encoding>, inbound_encoding: Option<Encoding>) {
     let mut rng = make_rng(89028037453);
     let mut clock = time::FakeClock::default();
@@ -210,6 +272,11 @@ async fn test_handshake(outbound_encoding: Option<Encoding>, inbound_encoding: O
         sender_chain_info: outbound_cfg.chain.get_peer_chain_info(),
         partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
         owned_account: None,
+        supports_zstd_compression: false,
+        sender_listen_addrs: vec![],
+        supported_encodings: Default::default(),
+        supports_message_fragmentation: false,
+        supports_full_chunk_requests: false,
     };
     // We will also introduce chain_id mismatch, but ProtocolVersionMismatch is expected to take priority.
     handshake.sender_chain_info.genesis_id.chain_id = "unknown_chain".to_string();
@@ -266,3 +333,87 @@ async fn handshake() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+/// Sends a Handshake in `encoding` to a node with the config adjusted by `configure`,
+/// and returns whether the node responds with a Handshake.
+async fn handshake_accepted(
+    configure: impl FnOnce(&mut NetworkConfig),
+    encoding: Encoding,
+    protocol_version: ProtocolVersion,
+) -> bool {
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+
+    let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+    let mut network = chain.make_config(&mut rng);
+    configure(&mut network);
+    let inbound_cfg = PeerConfig { network, chain: chain.clone(), force_encoding: None };
+    let outbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: Some(encoding),
+    };
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let inbound = PeerHandle::start_endpoint(clock.clock(), inbound_cfg, inbound_stream).await;
+    let outbound_port = outbound_stream.local_addr.port();
+    let mut outbound = Stream::new(Some(encoding), outbound_stream);
+
+    let handshake = Handshake {
+        protocol_version,
+        oldest_supported_version: PEER_MIN_ALLOWED_PROTOCOL_VERSION,
+        sender_peer_id: outbound_cfg.id(),
+        target_peer_id: inbound.cfg.id(),
+        sender_listen_port: Some(outbound_port),
+        sender_chain_info: outbound_cfg.chain.get_peer_chain_info(),
+        partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
+        owned_account: None,
+        supports_zstd_compression: false,
+        sender_listen_addrs: vec![],
+        supported_encodings: Default::default(),
+        supports_message_fragmentation: false,
+        supports_full_chunk_requests: false,
+    };
+    outbound.write(&PeerMessage::Tier2Handshake(handshake)).await;
+    // A rejected peer gets the connection closed without a Handshake response.
+    while let Ok(msg) = outbound.read().await {
+        if let PeerMessage::Tier2Handshake(_) = msg {
+            return true;
+        }
+    }
+    false
+}
+
+#[tokio::test]
+// Verifies that Borsh-only peers are refused, if configured so.
+async fn reject_borsh_peers() {
+    init_test_logger();
+    for (encoding, accepted) in [(Encoding::Proto, true), (Encoding::Borsh, false)] {
+        let got = handshake_accepted(
+            |network| network.reject_borsh_peers = true,
+            encoding,
+            PROTOCOL_VERSION,
+        )
+        .await;
+        assert_eq!(accepted, got, "encoding = {encoding:?}");
+    }
+}
+
+#[tokio::test]
+// Verifies that Borsh is refused from the peers running a protocol version above the cutoff.
+async fn borsh_max_protocol_version() {
+    init_test_logger();
+    for (encoding, protocol_version, accepted) in [
+        (Encoding::Proto, PROTOCOL_VERSION, true),
+        (Encoding::Borsh, PEER_MIN_ALLOWED_PROTOCOL_VERSION, true),
+        (Encoding::Borsh, PROTOCOL_VERSION, false),
+    ] {
+        let got = handshake_accepted(
+            |network| network.borsh_max_protocol_version = Some(PEER_MIN_ALLOWED_PROTOCOL_VERSION),
+            encoding,
+            protocol_version,
+        )
+        .await;
+        assert_eq!(accepted, got, "encoding = {encoding:?}, protocol_version = {protocol_version}");
+    }
+}
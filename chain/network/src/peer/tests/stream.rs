@@ -1,11 +1,16 @@
 use crate::actix::ActixSystem;
+use crate::concurrency::rate;
+use crate::config::FaultProfile;
 use crate::network_protocol::testonly as data;
 use crate::peer::stream;
+use crate::peer_manager::connection;
 use crate::tcp;
 use crate::testonly::make_rng;
 use actix::Actor as _;
 use actix::ActorContext as _;
+use near_primitives::time;
 use rand::Rng as _;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
@@ -25,7 +30,29 @@ struct SendFrame(stream::Frame);
 impl actix::Handler<SendFrame> for Actor {
     type Result = ();
     fn handle(&mut self, SendFrame(frame): SendFrame, _ctx: &mut Self::Context) {
-        self.stream.send(frame);
+        self.stream.send(stream::Priority::Misc, frame);
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype("()")]
+struct EnableFragmentation;
+
+impl actix::Handler<EnableFragmentation> for Actor {
+    type Result = ();
+    fn handle(&mut self, _: EnableFragmentation, _ctx: &mut Self::Context) {
+        self.stream.enable_fragmentation();
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype("()")]
+struct InjectFaults(FaultProfile);
+
+impl actix::Handler<InjectFaults> for Actor {
+    type Result = ();
+    fn handle(&mut self, InjectFaults(profile): InjectFaults, _ctx: &mut Self::Context) {
+        self.stream.inject_faults(profile);
     }
 }
 
@@ -45,17 +72,32 @@ impl actix::Handler<stream::Error> for Actor {
 
 struct Handler {
     queue_recv: mpsc::UnboundedReceiver<stream::Frame>,
+    stats: Arc<connection::Stats>,
     system: ActixSystem<Actor>,
 }
 
 impl Actor {
-    async fn spawn(s: tcp::Stream) -> Handler {
+    async fn spawn(clock: time::Clock, s: tcp::Stream) -> Handler {
         let (queue_send, queue_recv) = mpsc::unbounded_channel();
+        let stats = Arc::new(connection::Stats::default());
         Handler {
             queue_recv,
+            stats: stats.clone(),
             system: ActixSystem::spawn(|| {
                 Actor::create(|ctx| {
-                    let stream = stream::FramedStream::spawn(ctx, s, Arc::default());
+                    let limit = rate::Limit {
+                        qps: stream::NETWORK_MESSAGE_MAX_SIZE_BYTES as f64,
+                        burst: stream::NETWORK_MESSAGE_MAX_SIZE_BYTES as u64,
+                    };
+                    let recv_limits = Arc::new(stream::RecvLimits::new(limit, limit));
+                    let stream = stream::FramedStream::spawn(
+                        ctx,
+                        clock,
+                        s,
+                        tcp::Tier::T2,
+                        recv_limits,
+                        stats,
+                    );
                     Self { stream, queue_send }
                 })
             })
@@ -68,8 +110,8 @@ impl Actor {
 async fn send_recv() {
     let mut rng = make_rng(98324532);
     let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
-    let a1 = Actor::spawn(s1).await;
-    let mut a2 = Actor::spawn(s2).await;
+    let a1 = Actor::spawn(time::Clock::real(), s1).await;
+    let mut a2 = Actor::spawn(time::Clock::real(), s2).await;
 
     for _ in 0..5 {
         let n = rng.gen_range(1..10);
@@ -90,3 +132,88 @@ async fn send_recv() {
         }
     }
 }
+
+#[tokio::test]
+async fn send_recv_fragmented() {
+    let mut rng = make_rng(2387452);
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(time::Clock::real(), s1).await;
+    let mut a2 = Actor::spawn(time::Clock::real(), s2).await;
+    a1.system.addr.send(EnableFragmentation).await.unwrap();
+
+    let sizes = [
+        stream::MESSAGE_FRAGMENT_SIZE_BYTES,
+        stream::MESSAGE_FRAGMENT_SIZE_BYTES + 1,
+        10,
+        2 * stream::MESSAGE_FRAGMENT_SIZE_BYTES + 12345,
+    ];
+    let msgs: Vec<_> = sizes
+        .iter()
+        .map(|size| {
+            let mut msg = vec![0; *size];
+            rng.fill(&mut msg[..]);
+            stream::Frame(msg)
+        })
+        .collect();
+    for msg in &msgs {
+        a1.system.addr.send(SendFrame(msg.clone())).await.unwrap();
+    }
+    for want in &msgs {
+        let got = a2.queue_recv.recv().await.unwrap();
+        assert_eq!(&got, want);
+    }
+}
+
+#[tokio::test]
+async fn recv_delayed_by_injected_faults() {
+    let mut rng = make_rng(7234852);
+    let clock = time::FakeClock::default();
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(clock.clock(), s1).await;
+    let mut a2 = Actor::spawn(clock.clock(), s2).await;
+    let latency = time::Duration::seconds(1);
+    a2.system
+        .addr
+        .send(InjectFaults(FaultProfile { latency, ..Default::default() }))
+        .await
+        .unwrap();
+
+    let want = stream::Frame(vec![1, 2, 3]);
+    a1.system.addr.send(SendFrame(want.clone())).await.unwrap();
+    while a2.stats.received_messages.load(Ordering::Relaxed) == 0 {
+        tokio::task::yield_now().await;
+    }
+    // The received message is held back until the latency passes.
+    clock.advance(latency - time::Duration::milliseconds(1));
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(a2.queue_recv.try_recv().is_err());
+    clock.advance(time::Duration::milliseconds(1));
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), want);
+}
+
+#[tokio::test]
+async fn next_frame_by_priority() {
+    let (send, mut recv): (Vec<_>, Vec<_>) =
+        stream::Priority::ALL.iter().map(|_| mpsc::unbounded_channel()).unzip();
+    let lane = |p: stream::Priority| stream::Priority::ALL.iter().position(|x| *x == p).unwrap();
+    send[lane(stream::Priority::Misc)].send(stream::Frame(vec![3])).unwrap();
+    send[lane(stream::Priority::Sync)].send(stream::Frame(vec![2])).unwrap();
+    send[lane(stream::Priority::Consensus)].send(stream::Frame(vec![0])).unwrap();
+    send[lane(stream::Priority::Consensus)].send(stream::Frame(vec![1])).unwrap();
+    drop(send);
+    let mut got = vec![];
+    while let Some((priority, frame)) = stream::next_frame(&mut recv).await {
+        got.push((priority, frame.0[0]));
+    }
+    assert_eq!(
+        got,
+        vec![
+            (stream::Priority::Consensus, 0),
+            (stream::Priority::Consensus, 1),
+            (stream::Priority::Sync, 2),
+            (stream::Priority::Misc, 3),
+        ]
+    );
+}
@@ -1,3 +1,4 @@
+use crate::network_protocol::{PeerMessage, RoutedMessageBody};
 use crate::peer::transfer_stats::TransferStats;
 use near_primitives::hash::CryptoHash;
 use near_primitives::time;
@@ -38,6 +39,29 @@ impl CircularUniqueQueue {
     }
 }
 
+/// Checks whether `msg` counts towards the limit on the number of messages received from a peer.
+/// The messages used for syncing the chain, the state and the network data are exempt, since
+/// honest peers send them in large bursts when we (or they) are catching up.
+pub(crate) fn is_rate_limited(msg: &PeerMessage) -> bool {
+    match msg {
+        PeerMessage::SyncRoutingTable(_)
+        | PeerMessage::SyncAccountsData(_)
+        | PeerMessage::PeersResponse(_)
+        | PeerMessage::BlockHeadersRequest(_)
+        | PeerMessage::BlockHeaders(_)
+        | PeerMessage::BlockRequest(_)
+        | PeerMessage::Block(_) => false,
+        PeerMessage::Routed(msg) => !matches!(
+            msg.body,
+            RoutedMessageBody::StateRequestHeader(..)
+                | RoutedMessageBody::StateRequestPart(..)
+                | RoutedMessageBody::StateResponse(_)
+                | RoutedMessageBody::VersionedStateResponse(_)
+        ),
+        _ => true,
+    }
+}
+
 /// Keeps track of requests and received hashes of transactions and blocks.
 /// Also keeps track of number of bytes sent and received from this peer to prevent abuse.
 pub(crate) struct Tracker {
@@ -45,6 +69,8 @@ pub(crate) struct Tracker {
     pub(crate) sent_bytes: TransferStats,
     /// Bytes we've received.
     pub(crate) received_bytes: TransferStats,
+    /// Received messages which count towards `NetworkConfig::max_peer_msg_per_min`.
+    pub(crate) rate_limited_messages: TransferStats,
     /// Sent requests.
     requested: CircularUniqueQueue,
     /// Received elements.
@@ -56,6 +82,7 @@ impl Default for Tracker {
         Tracker {
             sent_bytes: TransferStats::default(),
             received_bytes: TransferStats::default(),
+            rate_limited_messages: TransferStats::default(),
             requested: CircularUniqueQueue::new(MAX_TRACK_SIZE),
             received: CircularUniqueQueue::new(MAX_TRACK_SIZE),
         }
@@ -67,6 +94,17 @@ impl Tracker {
         self.received_bytes.record(clock, size);
     }
 
+    pub(crate) fn increment_received_message(
+        &mut self,
+        clock: &time::Clock,
+        msg: &PeerMessage,
+        size: u64,
+    ) {
+        if is_rate_limited(msg) {
+            self.rate_limited_messages.record(clock, size);
+        }
+    }
+
     pub(crate) fn increment_sent(&mut self, clock: &time::Clock, size: u64) {
         self.sent_bytes.record(clock, size);
     }
@@ -0,0 +1,109 @@
+//! Measurement of the block propagation latency.
+//!
+//! When enabled, the node records for every block it receives (without requesting it) the time
+//! it has seen the block for the first time and the peer which has sent it. The propagation
+//! latency is the time elapsed since the block has been produced, according to the timestamp in
+//! the block header. It is exported as the `near_block_propagation_latency` histogram, and the
+//! recent blocks together with the latency percentiles are available at the
+//! /debug/api/block_propagation endpoint. Collecting these from many nodes allows studying the
+//! block propagation across the whole network without any external instrumentation.
+//!
+//! Note that the latency includes the clock skew between the producer and this node.
+use crate::stats::metrics;
+use lru::LruCache;
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+use near_primitives::types::BlockHeight;
+use near_primitives::views::{BlockPropagationEntryView, BlockPropagationView};
+use parking_lot::Mutex;
+
+#[cfg(test)]
+mod tests;
+
+/// Maximal number of the recent blocks kept for the debug endpoint.
+const MAX_BLOCKS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    /// Peer from which the block has been received first.
+    pub source: PeerId,
+    /// Timestamp from the block header.
+    pub produced_at: time::Utc,
+    pub first_seen: time::Utc,
+}
+
+impl Entry {
+    pub fn latency(&self) -> time::Duration {
+        self.first_seen - self.produced_at
+    }
+}
+
+/// First sightings of the recent blocks.
+pub(crate) struct Recorder {
+    blocks: Mutex<LruCache<CryptoHash, Entry>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self { blocks: Mutex::new(LruCache::new(MAX_BLOCKS)) }
+    }
+
+    /// Records the block received from `source`, unless it has been seen already.
+    pub fn record(&self, clock: &time::Clock, block: &Block, source: &PeerId) {
+        let mut blocks = self.blocks.lock();
+        if blocks.contains(block.hash()) {
+            return;
+        }
+        let produced_at =
+            match time::Utc::from_unix_timestamp_nanos(block.header().raw_timestamp() as i128) {
+                Ok(t) => t,
+                Err(_) => return,
+            };
+        let entry = Entry {
+            hash: *block.hash(),
+            height: block.header().height(),
+            source: source.clone(),
+            produced_at,
+            first_seen: clock.now_utc(),
+        };
+        metrics::BLOCK_PROPAGATION_LATENCY.observe(entry.latency().as_seconds_f64().max(0.));
+        blocks.put(entry.hash, entry);
+    }
+
+    /// Recent blocks, ordered by the time of the first sighting.
+    pub fn blocks(&self) -> Vec<Entry> {
+        let mut blocks: Vec<_> = self.blocks.lock().iter().map(|(_, e)| e.clone()).collect();
+        blocks.sort_by_key(|e| e.first_seen);
+        blocks
+    }
+
+    pub fn view(&self) -> BlockPropagationView {
+        let blocks = self.blocks();
+        let mut latencies: Vec<_> = blocks.iter().map(|e| e.latency()).collect();
+        latencies.sort();
+        let percentile = |p: usize| {
+            (!latencies.is_empty())
+                .then(|| latencies[(latencies.len() - 1) * p / 100].whole_milliseconds() as i64)
+        };
+        BlockPropagationView {
+            latency_p50_ms: percentile(50),
+            latency_p90_ms: percentile(90),
+            latency_p99_ms: percentile(99),
+            blocks: blocks
+                .into_iter()
+                .map(|e| BlockPropagationEntryView {
+                    block_hash: e.hash,
+                    height: e.height,
+                    source: e.source.clone(),
+                    produced_at_ms: (e.produced_at.unix_timestamp_nanos() / 1_000_000) as i64,
+                    first_seen_ms: (e.first_seen.unix_timestamp_nanos() / 1_000_000) as i64,
+                    latency_ms: e.latency().whole_milliseconds() as i64,
+                })
+                .collect(),
+        }
+    }
+}
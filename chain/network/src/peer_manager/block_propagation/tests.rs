@@ -0,0 +1,51 @@
+use super::*;
+use crate::network_protocol::testonly as data;
+use crate::testonly::make_rng;
+use std::collections::HashMap;
+
+#[test]
+fn records_first_sighting() {
+    let mut rng = make_rng(2349872384);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 5);
+    let peers: Vec<_> = (0..2).map(|_| data::make_peer_id(&mut rng)).collect();
+    let recorder = Recorder::new();
+    let mut want_latency_ms = HashMap::new();
+    for (i, block) in chain.blocks.iter().enumerate() {
+        let latency = time::Duration::milliseconds(100 * (i as i64 + 1));
+        let produced_at =
+            time::Utc::from_unix_timestamp_nanos(block.header().raw_timestamp() as i128).unwrap();
+        clock.set_utc(produced_at + latency);
+        recorder.record(&clock.clock(), block, &peers[0]);
+        want_latency_ms.insert(*block.hash(), latency.whole_milliseconds() as i64);
+        // Only the first sighting counts.
+        clock.advance(time::Duration::seconds(1));
+        recorder.record(&clock.clock(), block, &peers[1]);
+    }
+    let view = recorder.view();
+    assert_eq!(chain.blocks.len(), view.blocks.len());
+    for b in &view.blocks {
+        assert_eq!(peers[0], b.source);
+        assert_eq!(want_latency_ms[&b.block_hash], b.latency_ms);
+    }
+    assert_eq!(Some(300), view.latency_p50_ms);
+    assert_eq!(Some(400), view.latency_p90_ms);
+    assert_eq!(Some(400), view.latency_p99_ms);
+}
+
+#[test]
+fn orders_by_first_sighting() {
+    let mut rng = make_rng(9823749823);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 3);
+    let peer_id = data::make_peer_id(&mut rng);
+    let recorder = Recorder::new();
+    assert_eq!(None, recorder.view().latency_p50_ms);
+    for block in chain.blocks.iter().rev() {
+        recorder.record(&clock.clock(), block, &peer_id);
+        clock.advance(time::Duration::seconds(1));
+    }
+    let got: Vec<_> = recorder.blocks().into_iter().map(|e| e.hash).collect();
+    let want: Vec<_> = chain.blocks.iter().rev().map(|b| *b.hash()).collect();
+    assert_eq!(want, got);
+}
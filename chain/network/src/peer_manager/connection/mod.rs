@@ -10,7 +10,9 @@ use crate::peer::peer_actor::PeerActor;
 use crate::private_actix::SendMessage;
 use crate::stats::metrics;
 use crate::tcp;
-use crate::types::{BlockInfo, FullPeerInfo, PeerChainInfo, PeerType, ReasonForBan};
+use crate::types::{
+    BlockInfo, FullPeerInfo, MessageTraffic, PeerChainInfo, PeerType, ReasonForBan,
+};
 use arc_swap::ArcSwap;
 use near_crypto::PublicKey;
 use near_o11y::WithSpanContextExt;
@@ -18,6 +20,7 @@ use near_primitives::block::GenesisId;
 use near_primitives::network::PeerId;
 use near_primitives::time;
 use near_primitives::types::ShardId;
+use std::cmp::Reverse;
 use std::collections::{hash_map::Entry, HashMap};
 use std::fmt;
 use std::future::Future;
@@ -38,6 +41,7 @@ impl tcp::Tier {
             PeerMessage::Tier2Handshake(_) => self == tcp::Tier::T2,
             PeerMessage::HandshakeFailure(_, _) => true,
             PeerMessage::LastEdge(_) => true,
+            PeerMessage::KeepAlive(_) | PeerMessage::KeepAliveResponse(_) => true,
             PeerMessage::Routed(msg) => self.is_allowed_routed(&msg.body),
             _ => self == tcp::Tier::T2,
         }
@@ -47,11 +51,33 @@ impl tcp::Tier {
         match body {
             RoutedMessageBody::BlockApproval(..) => true,
             RoutedMessageBody::VersionedPartialEncodedChunk(..) => true,
+            RoutedMessageBody::PartialEncodedChunkForward(..) => true,
+            // Transactions are forwarded to the upcoming chunk producers, so the sooner they
+            // arrive, the sooner they are included. Sending them over TIER1 is enabled by
+            // `Tier1::send_forward_tx`.
+            RoutedMessageBody::ForwardTx(..) => true,
             _ => self == tcp::Tier::T2,
         }
     }
 }
 
+/// Weight of the latest sample in the average round trip time.
+const RTT_EWMA_WEIGHT: f64 = 0.2;
+
+/// State of the keep-alive pings sent over a connection.
+#[derive(Default)]
+struct KeepAliveState {
+    /// Ping awaiting the response: its nonce and the time it has been sent.
+    pending: Option<(u64, time::Instant)>,
+    /// Number of the consecutive pings which haven't been answered.
+    missed: u32,
+    /// Whether any ping has been answered. Peers running an older version ignore the pings,
+    /// so they are never considered unresponsive.
+    answered: bool,
+    /// Exponentially weighted moving average of the round trip time.
+    rtt: Option<time::Duration>,
+}
+
 #[derive(Default)]
 pub(crate) struct Stats {
     /// Number of messages received since the last reset of the counter.
@@ -67,6 +93,62 @@ pub(crate) struct Stats {
     pub messages_to_send: AtomicU64,
     /// Number of bytes (sum of message sizes) in the buffer to send.
     pub bytes_to_send: AtomicU64,
+
+    /// Traffic since the connection has been established, by PeerMessage variant.
+    pub traffic_by_message_type: parking_lot::Mutex<HashMap<&'static str, MessageTraffic>>,
+
+    keep_alive: parking_lot::Mutex<KeepAliveState>,
+}
+
+impl Stats {
+    pub fn record_sent(&self, msg_type: &'static str, bytes: u64) {
+        let mut traffic = self.traffic_by_message_type.lock();
+        let t = traffic.entry(msg_type).or_default();
+        t.sent_messages += 1;
+        t.sent_bytes += bytes;
+    }
+
+    pub fn record_received(&self, msg_type: &'static str, bytes: u64) {
+        let mut traffic = self.traffic_by_message_type.lock();
+        let t = traffic.entry(msg_type).or_default();
+        t.received_messages += 1;
+        t.received_bytes += bytes;
+    }
+
+    /// Records a keep-alive ping sent at `now`. Returns the number of the consecutive pings
+    /// which haven't been answered, or None if the peer has never answered any.
+    pub fn keep_alive_sent(&self, nonce: u64, now: time::Instant) -> Option<u32> {
+        let mut keep_alive = self.keep_alive.lock();
+        if keep_alive.pending.is_some() {
+            keep_alive.missed += 1;
+        }
+        keep_alive.pending = Some((nonce, now));
+        keep_alive.answered.then_some(keep_alive.missed)
+    }
+
+    /// Records a keep-alive response received at `now`. Returns the round trip time,
+    /// unless the response doesn't match the pending ping.
+    pub fn keep_alive_received(&self, nonce: u64, now: time::Instant) -> Option<time::Duration> {
+        let mut keep_alive = self.keep_alive.lock();
+        let sent_at = match keep_alive.pending {
+            Some((pending_nonce, sent_at)) if pending_nonce == nonce => sent_at,
+            _ => return None,
+        };
+        let rtt = now - sent_at;
+        keep_alive.pending = None;
+        keep_alive.missed = 0;
+        keep_alive.answered = true;
+        keep_alive.rtt = Some(match keep_alive.rtt {
+            Some(avg) => avg * (1. - RTT_EWMA_WEIGHT) + rtt * RTT_EWMA_WEIGHT,
+            None => rtt,
+        });
+        Some(rtt)
+    }
+
+    /// Average round trip time measured by the keep-alive pings.
+    pub fn keep_alive_rtt(&self) -> Option<time::Duration> {
+        self.keep_alive.lock().rtt
+    }
 }
 
 /// Contains information relevant to a connected peer.
@@ -78,6 +160,11 @@ pub(crate) struct Connection {
     pub addr: actix::Addr<PeerActor>,
 
     pub peer_info: PeerInfo,
+    /// Address of the remote end of the connection. Unlike `peer_info.addr` it is known
+    /// also for the peers which don't listen for connections.
+    pub peer_addr: std::net::SocketAddr,
+    /// Addresses on which the peer listens, other than `peer_info.addr`.
+    pub listen_addrs: Vec<std::net::SocketAddr>,
     /// AccountKey ownership proof.
     pub owned_account: Option<SignedOwnedAccount>,
     /// Chain Id and hash of genesis block.
@@ -86,12 +173,17 @@ pub(crate) struct Connection {
     pub tracked_shards: Vec<ShardId>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
+    /// Whether the peer can decode `RoutedMessageBody::FullChunkRequest`.
+    pub supports_full_chunk_requests: bool,
     pub last_block: ArcSwap<Option<BlockInfo>>,
 
     /// Who started connection. Inbound (other) or Outbound (us).
     pub peer_type: PeerType,
     /// Time where the connection was established.
     pub established_time: time::Instant,
+    /// Time between sending our Handshake and receiving the peer's one.
+    /// Known only for the outbound connections.
+    pub handshake_rtt: Option<time::Duration>,
 
     /// Last time requested peers.
     pub last_time_peer_requested: AtomicCell<Option<time::Instant>>,
@@ -127,6 +219,12 @@ impl Connection {
         FullPeerInfo { peer_info: self.peer_info.clone(), chain_info }
     }
 
+    /// Round trip time to the peer: the average measured by the keep-alive pings, or
+    /// the duration of the handshake until any ping has been answered.
+    pub fn rtt(&self) -> Option<time::Duration> {
+        self.stats.keep_alive_rtt().or(self.handshake_rtt)
+    }
+
     pub fn stop(&self, ban_reason: Option<ReasonForBan>) {
         self.addr.do_send(peer_actor::Stop { ban_reason }.with_span_context());
     }
@@ -271,6 +369,19 @@ pub(crate) enum PoolError {
     PermitDropped,
 }
 
+/// Value of a TIER2 connection to this node, used to choose which connection to drop
+/// when the connection limit has been reached. The fields are compared lexicographically:
+/// validators are preferred over archival nodes, which are preferred over the other nodes,
+/// and the ties are broken by the latency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct ConnectionValue {
+    pub validator: bool,
+    pub archival: bool,
+    /// Handshake RTT. Unknown RTT (in particular of the inbound connections) is treated
+    /// as the highest one.
+    pub latency: Reverse<time::Duration>,
+}
+
 impl Pool {
     pub fn new(me: PeerId) -> Pool {
         Self(Arc::new(ArcMutex::new(PoolSnapshot {
@@ -417,6 +528,24 @@ impl Pool {
         });
     }
 
+    /// Chooses a ready connection to evict in favor of a new connection of the given `value`.
+    /// Returns the connection of the lowest value, as long as it is strictly lower than `value`.
+    /// Among the connections of the same value, the most recently established one is chosen.
+    /// Connections for which `value_fn` returns None are never evicted.
+    pub fn eviction_candidate(
+        &self,
+        value: ConnectionValue,
+        value_fn: impl Fn(&Connection) -> Option<ConnectionValue>,
+    ) -> Option<Arc<Connection>> {
+        self.load()
+            .ready
+            .values()
+            .filter_map(|conn| Some((value_fn(conn)?, conn)))
+            .filter(|(v, _)| *v < value)
+            .min_by_key(|(v, conn)| (*v, Reverse(conn.established_time)))
+            .map(|(_, conn)| conn.clone())
+    }
+
     /// Send message to peer that belongs to our active set
     /// Return whether the message is sent or not.
     pub fn send_message(&self, peer_id: PeerId, msg: Arc<PeerMessage>) -> bool {
@@ -5,6 +5,7 @@ use crate::peer_manager::connection;
 use crate::private_actix::RegisterPeerError;
 use crate::tcp;
 use crate::testonly::make_rng;
+use crate::types::MessageTraffic;
 use near_o11y::testonly::init_test_logger;
 use near_primitives::time;
 use std::sync::Arc;
@@ -120,3 +121,51 @@ async fn duplicate_connections() {
     );
     drop(conn1);
 }
+
+#[test]
+fn traffic_by_message_type() {
+    let stats = connection::Stats::default();
+    stats.record_sent("Block", 100);
+    stats.record_sent("Block", 50);
+    stats.record_received("Block", 10);
+    stats.record_received("BlockApproval", 7);
+    let traffic = stats.traffic_by_message_type.lock().clone();
+    assert_eq!(
+        traffic["Block"],
+        MessageTraffic {
+            sent_messages: 2,
+            sent_bytes: 150,
+            received_messages: 1,
+            received_bytes: 10
+        }
+    );
+    assert_eq!(
+        traffic["BlockApproval"],
+        MessageTraffic { sent_messages: 0, sent_bytes: 0, received_messages: 1, received_bytes: 7 }
+    );
+}
+
+#[test]
+fn keep_alive_rtt() {
+    let clock = time::FakeClock::default();
+    let stats = connection::Stats::default();
+    // A peer which has never answered is not considered unresponsive.
+    assert_eq!(None, stats.keep_alive_sent(1, clock.now()));
+    assert_eq!(None, stats.keep_alive_sent(2, clock.now()));
+    clock.advance(time::Duration::milliseconds(100));
+    // Response to an outdated ping is ignored.
+    assert_eq!(None, stats.keep_alive_received(1, clock.now()));
+    assert_eq!(Some(time::Duration::milliseconds(100)), stats.keep_alive_received(2, clock.now()));
+    assert_eq!(Some(time::Duration::milliseconds(100)), stats.keep_alive_rtt());
+
+    assert_eq!(Some(0), stats.keep_alive_sent(3, clock.now()));
+    clock.advance(time::Duration::milliseconds(200));
+    stats.keep_alive_received(3, clock.now());
+    // The average moves towards the latest sample.
+    let rtt = stats.keep_alive_rtt().unwrap();
+    assert!((rtt - time::Duration::milliseconds(120)).abs() < time::Duration::milliseconds(1));
+
+    assert_eq!(Some(0), stats.keep_alive_sent(4, clock.now()));
+    assert_eq!(Some(1), stats.keep_alive_sent(5, clock.now()));
+    assert_eq!(Some(2), stats.keep_alive_sent(6, clock.now()));
+}
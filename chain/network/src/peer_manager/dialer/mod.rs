@@ -0,0 +1,195 @@
+//! Pacing of the outbound TIER2 connection attempts.
+//!
+//! The peer manager periodically picks a random unconnected peer from the peer store and tries
+//! to connect to it. Peers which are down or unreachable would otherwise be retried as eagerly as
+//! the healthy ones, wasting the attempts (and the file descriptors) of a node that has few
+//! connections. The dialer keeps track of the consecutive failed attempts to every peer and
+//! skips the peer until its backoff, doubled after every failure (up to a limit) and randomized
+//! to avoid synchronized retries, expires. Independently, the total number of outbound
+//! connection attempts is limited by a token bucket.
+//! The state is exposed at the /debug/api/dialer endpoint.
+use crate::concurrency::rate;
+use crate::config;
+use crate::stats::metrics;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+use near_primitives::views::{DialerPeerView, DialerView};
+use parking_lot::Mutex;
+use rand::Rng as _;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests;
+
+/// Consecutive failed connection attempts to a peer.
+#[derive(Clone, Debug)]
+pub(crate) struct Backoff {
+    pub failures: u32,
+    pub last_error: String,
+    pub last_failure: time::Utc,
+    /// The peer is not dialed before `last_failure + backoff`.
+    pub backoff: time::Duration,
+}
+
+impl Backoff {
+    pub fn retry_at(&self) -> time::Utc {
+        self.last_failure + self.backoff
+    }
+}
+
+/// Token bucket limiting the rate of the outbound connection attempts.
+struct Bucket {
+    limit: rate::Limit,
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl Bucket {
+    fn try_acquire(&mut self, now: time::Instant) -> bool {
+        let elapsed = (now - self.updated).as_seconds_f64().max(0.);
+        self.tokens = (self.tokens + elapsed * self.limit.qps).min(self.limit.burst as f64);
+        self.updated = now;
+        if self.tokens < 1. {
+            return false;
+        }
+        self.tokens -= 1.;
+        true
+    }
+}
+
+struct Inner {
+    peers: HashMap<PeerId, Backoff>,
+    bucket: Bucket,
+}
+
+pub(crate) struct Dialer {
+    config: config::Dialer,
+    inner: Mutex<Inner>,
+}
+
+impl Dialer {
+    pub fn new(clock: &time::Clock, config: config::Dialer) -> Self {
+        let bucket = Bucket {
+            limit: config.attempts_rate_limit,
+            tokens: config.attempts_rate_limit.burst as f64,
+            updated: clock.now(),
+        };
+        Self { config, inner: Mutex::new(Inner { peers: HashMap::new(), bucket }) }
+    }
+
+    /// Whether the peer should not be dialed yet, because the recent attempts to connect to it
+    /// have failed.
+    pub fn is_backing_off(&self, clock: &time::Clock, peer_id: &PeerId) -> bool {
+        let now = clock.now_utc();
+        self.inner.lock().peers.get(peer_id).map_or(false, |b| now < b.retry_at())
+    }
+
+    /// Takes a token for a new outbound connection attempt.
+    /// Returns false if the attempts rate limit has been exceeded.
+    pub fn try_start_attempt(&self, clock: &time::Clock) -> bool {
+        let ok = self.inner.lock().bucket.try_acquire(clock.now());
+        if !ok {
+            metrics::DIALER_RATE_LIMITED_ATTEMPTS.inc();
+        }
+        ok
+    }
+
+    /// Records the result of an outbound connection attempt. A success resets the backoff of the
+    /// peer, a failure doubles it.
+    pub fn record_attempt(
+        &self,
+        clock: &time::Clock,
+        peer_id: &PeerId,
+        result: &anyhow::Result<()>,
+    ) {
+        let mut inner = self.inner.lock();
+        let err = match result {
+            Ok(()) => {
+                inner.peers.remove(peer_id);
+                metrics::DIALER_PEERS_IN_BACKOFF.set(inner.peers.len() as i64);
+                return;
+            }
+            Err(err) => err,
+        };
+        let failures = inner.peers.get(peer_id).map_or(0, |b| b.failures) + 1;
+        let backoff = self.backoff(failures);
+        tracing::debug!(
+            target: "network",
+            %peer_id,
+            failures,
+            %backoff,
+            "backing off a peer after a failed connection attempt"
+        );
+        inner.peers.insert(
+            peer_id.clone(),
+            Backoff {
+                failures,
+                last_error: format!("{err:#}"),
+                last_failure: clock.now_utc(),
+                backoff,
+            },
+        );
+        metrics::DIALER_PEERS_IN_BACKOFF.set(inner.peers.len() as i64);
+    }
+
+    /// Backoff after the given number of consecutive failures: min_backoff doubled after every
+    /// failure, capped at max_backoff, and randomized to [1/2,1] of that value.
+    fn backoff(&self, failures: u32) -> time::Duration {
+        let max = self.config.max_backoff;
+        let mut backoff = self.config.min_backoff;
+        for _ in 1..failures {
+            if backoff >= max {
+                break;
+            }
+            backoff = backoff * 2;
+        }
+        let backoff = std::cmp::min(backoff, max);
+        backoff * rand::thread_rng().gen_range(0.5..=1.)
+    }
+
+    /// Forgets the peers which have not been dialed for a long time after their backoff has
+    /// expired, so that the state stays bounded. Such peers start again from min_backoff.
+    pub fn update(&self, clock: &time::Clock) {
+        let now = clock.now_utc();
+        let max = self.config.max_backoff;
+        let mut inner = self.inner.lock();
+        inner.peers.retain(|_, b| now < b.retry_at() + max);
+        metrics::DIALER_PEERS_IN_BACKOFF.set(inner.peers.len() as i64);
+    }
+
+    /// Forgets all the failed attempts, e.g. after the operator has fixed the connectivity.
+    pub fn clear_backoffs(&self) {
+        self.inner.lock().peers.clear();
+        metrics::DIALER_PEERS_IN_BACKOFF.set(0);
+    }
+
+    /// Peers backing off after failed connection attempts.
+    pub fn load(&self) -> HashMap<PeerId, Backoff> {
+        self.inner.lock().peers.clone()
+    }
+
+    pub fn view(&self, clock: &time::Clock) -> DialerView {
+        let now = clock.now_utc();
+        let mut peers: Vec<_> = self
+            .load()
+            .into_iter()
+            .map(|(peer_id, b)| {
+                let retry_at = b.retry_at();
+                DialerPeerView {
+                    peer_id,
+                    failures: b.failures,
+                    last_error: b.last_error,
+                    last_failure: b.last_failure.unix_timestamp(),
+                    retry_at: retry_at.unix_timestamp(),
+                    backing_off: now < retry_at,
+                }
+            })
+            .collect();
+        peers.sort_by_key(|p| std::cmp::Reverse(p.last_failure));
+        DialerView {
+            attempts_qps: self.config.attempts_rate_limit.qps,
+            attempts_burst: self.config.attempts_rate_limit.burst,
+            peers,
+        }
+    }
+}
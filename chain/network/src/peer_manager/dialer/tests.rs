@@ -0,0 +1,69 @@
+use super::*;
+use crate::network_protocol::testonly as data;
+use crate::testonly::make_rng;
+
+fn make_dialer(clock: &time::Clock) -> Dialer {
+    Dialer::new(
+        clock,
+        config::Dialer {
+            min_backoff: time::Duration::seconds(10),
+            max_backoff: time::Duration::seconds(100),
+            attempts_rate_limit: rate::Limit { qps: 1., burst: 3 },
+        },
+    )
+}
+
+#[test]
+fn exponential_backoff() {
+    let mut rng = make_rng(921384701);
+    let clock = time::FakeClock::default();
+    let dialer = make_dialer(&clock.clock());
+    let peer_id = data::make_peer_id(&mut rng);
+    let failure = || Err(anyhow::anyhow!("connection refused"));
+
+    assert!(!dialer.is_backing_off(&clock.clock(), &peer_id));
+    for (failures, want_max) in [(1, 10), (2, 20), (3, 40), (4, 80), (5, 100), (6, 100)] {
+        dialer.record_attempt(&clock.clock(), &peer_id, &failure());
+        let b = dialer.load().get(&peer_id).unwrap().clone();
+        assert_eq!(failures, b.failures);
+        let want_max = time::Duration::seconds(want_max);
+        assert!(want_max / 2 <= b.backoff && b.backoff <= want_max, "backoff = {}", b.backoff);
+        assert!(dialer.is_backing_off(&clock.clock(), &peer_id));
+        clock.advance(b.backoff);
+        assert!(!dialer.is_backing_off(&clock.clock(), &peer_id));
+    }
+
+    // A successful attempt resets the backoff.
+    dialer.record_attempt(&clock.clock(), &peer_id, &Ok(()));
+    assert!(dialer.load().is_empty());
+    dialer.record_attempt(&clock.clock(), &peer_id, &failure());
+    assert_eq!(1, dialer.load().get(&peer_id).unwrap().failures);
+
+    // Peers not dialed for long after the backoff expired are forgotten.
+    clock.advance(time::Duration::seconds(10));
+    dialer.update(&clock.clock());
+    assert_eq!(1, dialer.load().len());
+    clock.advance(time::Duration::seconds(100));
+    dialer.update(&clock.clock());
+    assert!(dialer.load().is_empty());
+}
+
+#[test]
+fn attempts_rate_limit() {
+    let clock = time::FakeClock::default();
+    let dialer = make_dialer(&clock.clock());
+    // Burst.
+    for _ in 0..3 {
+        assert!(dialer.try_start_attempt(&clock.clock()));
+    }
+    assert!(!dialer.try_start_attempt(&clock.clock()));
+    // Refill.
+    clock.advance(time::Duration::seconds(1));
+    assert!(dialer.try_start_attempt(&clock.clock()));
+    assert!(!dialer.try_start_attempt(&clock.clock()));
+    clock.advance(time::Duration::seconds(10));
+    for _ in 0..3 {
+        assert!(dialer.try_start_attempt(&clock.clock()));
+    }
+    assert!(!dialer.try_start_attempt(&clock.clock()));
+}
@@ -0,0 +1,131 @@
+//! Limits on the inbound connections from a single IP address.
+//!
+//! Every inbound connection gets a PeerActor before the handshake, so that the peer can introduce
+//! itself. A single host opening many connections would therefore exhaust the permits for the
+//! pending inbound handshakes, shared by all the peers, and lock the honest peers out. To prevent
+//! that, PeerManagerActor checks every accepted connection against the limits of its source IP
+//! before spawning a PeerActor:
+//! * at most `max_connections` connections from the IP can be open at the same time. Every
+//!   connection holds a Permit, which is released when its PeerActor is dropped.
+//! * new connections from the IP are accepted at most at the rate of `connections_rate_limit`.
+//!   An IP exceeding the rate is throttled: its new connections are dropped for
+//!   `throttle_duration`, even if the rate limit would allow them again.
+//!
+//! The connections from the loopback addresses, and from the IPs of the whitelisted nodes, are
+//! not limited.
+use crate::concurrency::rate;
+use crate::config;
+use crate::stats::metrics;
+use near_primitives::time;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of IPs above which the IPs without open connections and limits in effect
+/// are forgotten.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Error {
+    #[error("too many connections from the IP")]
+    TooManyConnections,
+    #[error("the IP is throttled until {0:?}")]
+    Throttled(time::Instant),
+}
+
+impl Error {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::TooManyConnections => "too_many_connections",
+            Self::Throttled(_) => "throttled",
+        }
+    }
+}
+
+struct Entry {
+    connections: usize,
+    bucket: rate::Bucket,
+    throttled_until: Option<time::Instant>,
+}
+
+impl Entry {
+    /// Whether the entry is indistinguishable from a new one.
+    fn is_idle(&self, now: time::Instant, limit: &rate::Limit) -> bool {
+        self.connections == 0
+            && self.throttled_until.map_or(true, |t| t <= now)
+            && self.bucket.is_full(now, limit)
+    }
+}
+
+type Entries = Arc<Mutex<HashMap<IpAddr, Entry>>>;
+
+/// Slot taken by a connection in the limit of its source IP.
+pub(crate) struct Permit {
+    ip: IpAddr,
+    entries: Entries,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entries.lock().get_mut(&self.ip) {
+            entry.connections -= 1;
+        }
+    }
+}
+
+pub(crate) struct Limiter {
+    config: config::InboundIpLimit,
+    entries: Entries,
+}
+
+impl Limiter {
+    pub fn new(config: config::InboundIpLimit) -> Self {
+        Self { config, entries: Default::default() }
+    }
+
+    /// Admits a new connection from `ip`, unless it exceeds the limits. The connections from
+    /// the loopback addresses are admitted without a Permit.
+    pub fn try_acquire(&self, now: time::Instant, ip: IpAddr) -> Result<Option<Permit>, Error> {
+        if ip.is_loopback() {
+            return Ok(None);
+        }
+        let res = self.try_acquire_inner(now, ip).map(Some);
+        if let Err(err) = &res {
+            metrics::PEER_INBOUND_IP_REJECTED.with_label_values(&[err.label()]).inc();
+        }
+        res
+    }
+
+    fn try_acquire_inner(&self, now: time::Instant, ip: IpAddr) -> Result<Permit, Error> {
+        let limit = &self.config.connections_rate_limit;
+        let mut entries = self.entries.lock();
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, e| !e.is_idle(now, limit));
+        }
+        let entry = entries.entry(ip).or_insert_with(|| Entry {
+            connections: 0,
+            bucket: rate::Bucket::new(now, limit),
+            throttled_until: None,
+        });
+        if let Some(until) = entry.throttled_until {
+            if now < until {
+                return Err(Error::Throttled(until));
+            }
+            entry.throttled_until = None;
+        }
+        if entry.connections >= self.config.max_connections {
+            return Err(Error::TooManyConnections);
+        }
+        if entry.bucket.try_acquire(now, limit, 1).is_err() {
+            let until = now + self.config.throttle_duration;
+            entry.throttled_until = Some(until);
+            return Err(Error::Throttled(until));
+        }
+        entry.connections += 1;
+        Ok(Permit { ip, entries: self.entries.clone() })
+    }
+}
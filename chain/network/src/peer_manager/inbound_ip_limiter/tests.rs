@@ -0,0 +1,53 @@
+use super::*;
+
+fn limiter() -> Limiter {
+    Limiter::new(config::InboundIpLimit {
+        max_connections: 2,
+        connections_rate_limit: rate::Limit { qps: 1., burst: 3 },
+        throttle_duration: time::Duration::seconds(60),
+    })
+}
+
+#[test]
+fn limits_concurrent_connections() {
+    let clock = time::FakeClock::default();
+    let limiter = limiter();
+    let ip: IpAddr = "1.2.3.4".parse().unwrap();
+    let p1 = limiter.try_acquire(clock.now(), ip).unwrap();
+    let _p2 = limiter.try_acquire(clock.now(), ip).unwrap();
+    assert_eq!(Error::TooManyConnections, limiter.try_acquire(clock.now(), ip).err().unwrap());
+    // Other IPs are not affected.
+    limiter.try_acquire(clock.now(), "1.2.3.5".parse().unwrap()).unwrap();
+    // Closing a connection releases its slot.
+    drop(p1);
+    limiter.try_acquire(clock.now(), ip).unwrap();
+}
+
+#[test]
+fn throttles_after_exceeding_rate() {
+    let clock = time::FakeClock::default();
+    let limiter = limiter();
+    let ip: IpAddr = "1.2.3.4".parse().unwrap();
+    for _ in 0..3 {
+        drop(limiter.try_acquire(clock.now(), ip).unwrap());
+    }
+    let until = clock.now() + time::Duration::seconds(60);
+    assert_eq!(Error::Throttled(until), limiter.try_acquire(clock.now(), ip).err().unwrap());
+    // The throttling outlasts the refill of the bucket.
+    clock.advance(time::Duration::seconds(10));
+    assert_eq!(Error::Throttled(until), limiter.try_acquire(clock.now(), ip).err().unwrap());
+    clock.advance(time::Duration::seconds(50));
+    limiter.try_acquire(clock.now(), ip).unwrap();
+}
+
+#[test]
+fn loopback_not_limited() {
+    let clock = time::FakeClock::default();
+    let limiter = limiter();
+    for ip in ["127.0.0.1", "::1"] {
+        let ip: IpAddr = ip.parse().unwrap();
+        let permits: Vec<_> =
+            (0..10).map(|_| limiter.try_acquire(clock.now(), ip).unwrap()).collect();
+        assert!(permits.iter().all(Option::is_none));
+    }
+}
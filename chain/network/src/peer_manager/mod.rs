@@ -1,8 +1,14 @@
+pub(crate) mod block_propagation;
 pub(crate) mod connection;
 pub(crate) mod connection_store;
+pub(crate) mod dialer;
+pub(crate) mod inbound_ip_limiter;
 pub(crate) mod network_state;
+pub(crate) mod outbox;
 pub(crate) mod peer_manager_actor;
 pub(crate) mod peer_store;
+pub(crate) mod pinger;
+pub(crate) mod routed_trace;
 
 #[cfg(test)]
 pub(crate) mod testonly;
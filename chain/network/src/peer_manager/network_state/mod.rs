@@ -1,18 +1,28 @@
 use crate::accounts_data;
 use crate::client;
+use crate::concurrency;
+use crate::concurrency::arbiter_pool::ArbiterPool;
 use crate::concurrency::demux;
+use crate::concurrency::rate;
 use crate::concurrency::runtime::Runtime;
 use crate::config;
 use crate::network_protocol::{
-    Edge, EdgeState, PartialEdgeInfo, PeerIdOrHash, PeerInfo, PeerMessage, RawRoutedMessage,
-    RoutedMessageBody, RoutedMessageV2, SignedAccountData,
+    Edge, EdgeState, KeyTransition, PartialEdgeInfo, PeerIdOrHash, PeerInfo, PeerMessage,
+    RawRoutedMessage, RoutedMessageBody, RoutedMessageV2, SignedAccountData,
 };
 use crate::peer::peer_actor::PeerActor;
 use crate::peer::peer_actor::{ClosingReason, ConnectionClosedEvent};
+use crate::peer::RecvLimits;
+use crate::peer_manager::block_propagation;
 use crate::peer_manager::connection;
 use crate::peer_manager::connection_store;
+use crate::peer_manager::dialer;
+use crate::peer_manager::inbound_ip_limiter;
+use crate::peer_manager::outbox;
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_store;
+use crate::peer_manager::pinger;
+use crate::peer_manager::routed_trace;
 use crate::private_actix::RegisterPeerError;
 use crate::routing::route_back_cache::RouteBackCache;
 use crate::shards_manager::ShardsManagerRequestFromNetwork;
@@ -27,19 +37,34 @@ use near_primitives::block::GenesisId;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::time;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockHeight, BlockHeightDelta};
+use near_primitives::views::{PingResultView, PingView, RouteInfoView};
 use parking_lot::Mutex;
-use std::net::SocketAddr;
-use std::sync::atomic::AtomicUsize;
+use rand::Rng as _;
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
+use tokio::sync::oneshot;
 use tracing::Instrument as _;
 
+mod next_epoch;
 mod routing;
 mod tier1;
 
+pub(crate) use next_epoch::NEXT_EPOCH_CONNECT_INTERVAL;
+pub(crate) use tier1::ACCOUNTS_DATA_FULL_SYNC_INTERVAL;
+
 /// Limit number of pending Peer actors to avoid OOM.
 pub(crate) const LIMIT_PENDING_PEERS: usize = 60;
 
+/// Rate of the batches of the routed messages to verify, see
+/// `config::NetworkConfig::batch_verify_routed_messages`. It is high enough not to delay the
+/// verification: the messages are batched anyway while the previous batch is being verified.
+const VERIFY_ROUTED_MESSAGES_RATE_LIMIT: rate::Limit = rate::Limit { qps: 1000., burst: 10 };
+
 /// Send important messages three times.
 /// We send these messages multiple times to reduce the chance that they are lost
 const IMPORTANT_MESSAGE_RESENT_COUNT: usize = 3;
@@ -55,28 +80,39 @@ const PRUNE_UNREACHABLE_PEERS_AFTER: time::Duration = time::Duration::hours(1);
 /// Remove the edges that were created more that this duration ago.
 pub const PRUNE_EDGES_AFTER: time::Duration = time::Duration::minutes(30);
 
+/// The node is considered to be syncing if its head is behind the highest block reported by
+/// the connected peers by more than that many blocks.
+const SYNCING_HEIGHT_LAG: BlockHeight = 50;
+
+/// Number of blocks after which the route back entry of a state request is dropped. The state
+/// sync requests time out much sooner, and are retried then.
+const STATE_REQUEST_ROUTE_BACK_HORIZON: BlockHeightDelta = 100;
+/// Number of blocks after which the route back entry of a chunk request is dropped.
+const CHUNK_REQUEST_ROUTE_BACK_HORIZON: BlockHeightDelta = 20;
+
 /// How long to wait between reconnection attempts to the same peer
 pub(crate) const RECONNECT_ATTEMPT_INTERVAL: time::Duration = time::Duration::seconds(10);
 
 impl WhitelistNode {
-    pub fn from_peer_info(pi: &PeerInfo) -> anyhow::Result<Self> {
-        Ok(Self {
+    pub fn from_peer_info(pi: &PeerInfo) -> Self {
+        Self {
             id: pi.id.clone(),
-            addr: if let Some(addr) = pi.addr {
-                addr
-            } else {
-                anyhow::bail!("addess is missing");
-            },
+            ip: pi.addr.map(|addr| addr.ip()),
             account_id: pi.account_id.clone(),
-        })
+            rotated_from: None,
+        }
     }
 }
 
 #[derive(Clone, PartialEq, Eq)]
 pub(crate) struct WhitelistNode {
     id: PeerId,
-    addr: SocketAddr,
+    /// If set, the node is whitelisted only when connected from this IP.
+    ip: Option<IpAddr>,
     account_id: Option<AccountId>,
+    /// Set for the entries rotated in from the entry of this PeerId, after the node
+    /// has rotated its key.
+    rotated_from: Option<PeerId>,
 }
 
 pub(crate) struct NetworkState {
@@ -102,17 +138,36 @@ pub(crate) struct NetworkState {
     pub chain_info: ArcSwap<Option<ChainInfo>>,
     /// AccountsData for TIER1 accounts.
     pub accounts_data: Arc<accounts_data::Cache>,
+    /// Arbiters running the PeerActors.
+    pub peer_arbiters: ArbiterPool,
+    /// Receive rate limits of the connections, shared by all the PeerActors.
+    pub recv_limits: Arc<RecvLimits>,
     /// Connected peers (inbound and outbound) with their full peer information.
     pub tier2: connection::Pool,
     pub tier1: connection::Pool,
     /// Semaphore limiting inflight inbound handshakes.
     pub inbound_handshake_permits: Arc<tokio::sync::Semaphore>,
+    /// Limits on the inbound connections from a single IP address.
+    pub inbound_ip_limiter: Option<inbound_ip_limiter::Limiter>,
     /// Peer store that provides read/write access to peers.
     pub peer_store: peer_store::PeerStore,
     /// Connection store that provides read/write access to stored connections.
     pub connection_store: connection_store::ConnectionStore,
+    /// Backoff and rate limit of the outbound TIER2 connection attempts.
+    pub dialer: dialer::Dialer,
     /// List of peers to which we should re-establish a connection
     pub pending_reconnect: Mutex<Vec<PeerInfo>>,
+    /// Routed messages which couldn't be delivered to the target account (yet).
+    /// None if config.routed_message_outbox is None.
+    pub outbox: Option<outbox::Outbox>,
+    /// Recent events concerning the traced routed messages.
+    /// None if config.trace_routed_messages is false.
+    pub routed_trace: Option<routed_trace::Tracer>,
+    /// First sightings of the recent blocks.
+    /// None if config.measure_block_propagation is false.
+    pub block_propagation: Option<block_propagation::Recorder>,
+    /// Pings sent on the operator's request, awaiting the pongs.
+    pub pinger: pinger::Pinger,
     /// A graph of the whole NEAR network.
     pub graph: Arc<crate::routing::Graph>,
 
@@ -134,13 +189,18 @@ pub(crate) struct NetworkState {
     pub txns_since_last_block: AtomicUsize,
 
     /// Whitelisted nodes, which are allowed to connect even if the connection limit has been
-    /// reached.
-    whitelist_nodes: Vec<WhitelistNode>,
+    /// reached. Initialized from `config.whitelist_nodes`, can be updated at runtime.
+    whitelist_nodes: Mutex<Vec<WhitelistNode>>,
+    /// Maximal number of TIER2 connections. Initialized from `config.max_num_peers`,
+    /// can be updated at runtime.
+    max_num_peers: AtomicU32,
 
     /// Mutex which prevents overlapping calls to tier1_advertise_proxies.
     tier1_advertise_proxies_mutex: tokio::sync::Mutex<()>,
     /// Demultiplexer aggregating calls to add_edges().
     add_edges_demux: demux::Demux<Vec<Edge>, Result<(), ReasonForBan>>,
+    /// Demultiplexer aggregating calls to verify_routed_message().
+    verify_routed_demux: demux::Demux<Box<RoutedMessageV2>, (Box<RoutedMessageV2>, bool)>,
 
     /// Mutex serializing calls to set_chain_info(), which mutates a bunch of stuff non-atomically.
     /// TODO(gprusak): make it use synchronization primitives in some more canonical way.
@@ -172,11 +232,30 @@ impl NetworkState {
             client,
             shards_manager_adapter,
             chain_info: Default::default(),
+            peer_arbiters: ArbiterPool::new(config.peer_arbiters),
+            recv_limits: Arc::new(RecvLimits::new(
+                config.tier1_recv_limit,
+                config.tier2_recv_limit,
+            )),
             tier2: connection::Pool::new(config.node_id()),
             tier1: connection::Pool::new(config.node_id()),
             inbound_handshake_permits: Arc::new(tokio::sync::Semaphore::new(LIMIT_PENDING_PEERS)),
+            inbound_ip_limiter: config
+                .inbound_ip_limit
+                .clone()
+                .map(inbound_ip_limiter::Limiter::new),
             peer_store,
-            connection_store: connection_store::ConnectionStore::new(store).unwrap(),
+            connection_store: connection_store::ConnectionStore::new(store.clone()).unwrap(),
+            dialer: dialer::Dialer::new(clock, config.dialer.clone()),
+            outbox: config
+                .routed_message_outbox
+                .clone()
+                .map(|cfg| outbox::Outbox::new(cfg, store.clone())),
+            routed_trace: config.trace_routed_messages.then(routed_trace::Tracer::new),
+            block_propagation: config
+                .measure_block_propagation
+                .then(block_propagation::Recorder::new),
+            pinger: pinger::Pinger::new(),
             pending_reconnect: Mutex::new(Vec::<PeerInfo>::new()),
             accounts_data: Arc::new(accounts_data::Cache::new()),
             tier1_route_back: Mutex::new(RouteBackCache::default()),
@@ -184,8 +263,10 @@ impl NetworkState {
                 RECENT_ROUTED_MESSAGES_CACHE_SIZE,
             )),
             txns_since_last_block: AtomicUsize::new(0),
-            whitelist_nodes,
+            whitelist_nodes: Mutex::new(whitelist_nodes),
+            max_num_peers: AtomicU32::new(config.max_num_peers),
             add_edges_demux: demux::Demux::new(config.routing_table_update_rate_limit),
+            verify_routed_demux: demux::Demux::new(VERIFY_ROUTED_MESSAGES_RATE_LIMIT),
             set_chain_info_mutex: Mutex::new(()),
             config,
             created_at: clock.now(),
@@ -226,34 +307,274 @@ impl NetworkState {
         }
     }
 
+    /// Disconnects the peer on the node operator's request and, if `ban` is set, bans it.
+    /// Unlike `disconnect_and_ban`, also closes the TIER1 connection to the peer.
+    /// Returns whether the peer was connected.
+    pub fn disconnect_by_operator(&self, clock: &time::Clock, peer_id: &PeerId, ban: bool) -> bool {
+        let reason = if ban { Some(ReasonForBan::Manual) } else { None };
+        let tier1 = self.tier1.load().ready.get(peer_id).cloned();
+        let tier2 = self.tier2.load().ready.get(peer_id).cloned();
+        let connected = tier1.is_some() || tier2.is_some();
+        for conn in tier1.iter().chain(tier2.iter()) {
+            conn.stop(reason);
+        }
+        // A connected peer gets banned when its connection is unregistered.
+        if ban && !connected {
+            if let Err(err) = self.peer_store.peer_ban(clock, peer_id, ReasonForBan::Manual) {
+                tracing::warn!(target: "network", ?err, "Failed to ban peer");
+            }
+        }
+        tracing::info!(target: "network", peer_id = %peer_id, ban, connected, "Disconnected by operator");
+        connected
+    }
+
+    /// Whether the blacklist and the allowlist permit the connection. The peers which don't
+    /// listen for connections are checked by the IP of the connection.
+    fn is_connection_allowed(&self, conn: &connection::Connection) -> bool {
+        match &conn.peer_info.addr {
+            Some(addr) => self.peer_store.is_allowed(addr),
+            None => self.peer_store.is_allowed_ip(conn.peer_addr.ip()),
+        }
+    }
+
+    /// Closes the connections with the peers which are no longer allowed by the blacklist
+    /// (for example after it has been reloaded). Returns the number of closed connections.
+    pub fn disconnect_disallowed_peers(&self) -> usize {
+        let mut conns: Vec<_> = self.tier2.load().ready.values().cloned().collect();
+        conns.extend(self.tier1.load().ready.values().cloned());
+        let mut closed = 0;
+        for conn in conns {
+            if !self.is_connection_allowed(&conn) {
+                tracing::info!(target: "network", peer_id = %conn.peer_info.id, "Disconnecting blacklisted peer");
+                conn.stop(None);
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Closes all the TIER2 connections (and the TIER1 connections, if `tier1` is set) on the
+    /// node operator's request, and clears the backoffs of the peers we have failed to connect
+    /// to, so that the peer set is rebuilt from scratch. Returns the closed connections.
+    pub fn disconnect_all_by_operator(&self, tier1: bool) -> Vec<Arc<connection::Connection>> {
+        let mut conns: Vec<_> = self.tier2.load().ready.values().cloned().collect();
+        if tier1 {
+            conns.extend(self.tier1.load().ready.values().cloned());
+        }
+        for conn in &conns {
+            conn.stop(None);
+        }
+        self.dialer.clear_backoffs();
+        let closed = conns.len();
+        tracing::info!(target: "network", tier1, closed, "Disconnected all peers by operator");
+        conns
+    }
+
+    /// Whether the connection is still registered in the connection pool of its tier.
+    pub fn is_registered(&self, conn: &Arc<connection::Connection>) -> bool {
+        let pool = match conn.tier {
+            tcp::Tier::T1 => self.tier1.load(),
+            tcp::Tier::T2 => self.tier2.load(),
+        };
+        pool.ready.get(&conn.peer_info.id).map_or(false, |c| Arc::ptr_eq(c, conn))
+    }
+
     /// is_peer_whitelisted checks whether a peer is a whitelisted node.
     /// whitelisted nodes are allowed to connect, even if the inbound connections limit has
-    /// been reached. This predicate should be evaluated AFTER the Handshake.
-    pub fn is_peer_whitelisted(&self, peer_info: &PeerInfo) -> bool {
+    /// been reached, and their connections are never evicted. This predicate should be
+    /// evaluated AFTER the Handshake. The IP of the whitelist entry, if any, is compared
+    /// against the actual remote address of the connection, rather than the advertised one.
+    /// A whitelisted node stays whitelisted after rotating its key, as long as we
+    /// have received the corresponding key transition.
+    pub fn is_peer_whitelisted(&self, conn: &connection::Connection) -> bool {
+        let peer_info = &conn.peer_info;
         self.whitelist_nodes
+            .lock()
             .iter()
-            .filter(|wn| wn.id == peer_info.id)
-            .filter(|wn| Some(wn.addr) == peer_info.addr)
+            .filter(|wn| {
+                wn.id == peer_info.id || self.peer_store.is_key_rotated(&wn.id, &peer_info.id)
+            })
+            .filter(|wn| wn.ip.map_or(true, |ip| ip == conn.peer_addr.ip()))
             .any(|wn| wn.account_id.is_none() || wn.account_id == peer_info.account_id)
     }
 
-    /// predicate checking whether we should allow an inbound connection from peer_info.
-    fn is_inbound_allowed(&self, peer_info: &PeerInfo) -> bool {
+    /// Admits a new inbound connection from `ip`, unless it exceeds the limits of the IP, see
+    /// `inbound_ip_limiter`. The IPs of the whitelisted nodes are not limited, as the peer isn't
+    /// known before the handshake.
+    pub fn try_acquire_inbound_ip_permit(
+        &self,
+        now: time::Instant,
+        ip: IpAddr,
+    ) -> Result<Option<inbound_ip_limiter::Permit>, inbound_ip_limiter::Error> {
+        let limiter = match &self.inbound_ip_limiter {
+            Some(limiter) => limiter,
+            None => return Ok(None),
+        };
+        if self.whitelist_nodes.lock().iter().any(|wn| wn.ip == Some(ip)) {
+            return Ok(None);
+        }
+        limiter.try_acquire(now, ip)
+    }
+
+    /// Replaces the configured whitelist entries with `whitelist_nodes`. The entries rotated in
+    /// from the entries which are still whitelisted are kept, updated with the new settings.
+    pub fn set_whitelist_nodes(&self, mut whitelist_nodes: Vec<WhitelistNode>) {
+        let mut current = self.whitelist_nodes.lock();
+        // The entries rotated in are always added after the entries they were rotated from,
+        // so a single pass is enough, even after multiple key rotations.
+        for rotated in current.iter() {
+            let from = match &rotated.rotated_from {
+                Some(from) => from,
+                None => continue,
+            };
+            let merged: Vec<_> = whitelist_nodes
+                .iter()
+                .filter(|wn| &wn.id == from)
+                .map(|wn| WhitelistNode {
+                    id: rotated.id.clone(),
+                    rotated_from: Some(from.clone()),
+                    ..wn.clone()
+                })
+                .collect();
+            for wn in merged {
+                if !whitelist_nodes.contains(&wn) {
+                    whitelist_nodes.push(wn);
+                }
+            }
+        }
+        *current = whitelist_nodes;
+    }
+
+    /// Whitelists the new PeerId of a whitelisted node which has rotated its key, so that it
+    /// stays whitelisted after the transition is evicted from the peer store.
+    fn rotate_whitelist_nodes(&self, transition: &KeyTransition) {
+        let mut whitelist_nodes = self.whitelist_nodes.lock();
+        let rotated: Vec<_> = whitelist_nodes
+            .iter()
+            .filter(|wn| wn.id == transition.old_peer_id)
+            .map(|wn| WhitelistNode {
+                id: transition.new_peer_id.clone(),
+                rotated_from: Some(transition.old_peer_id.clone()),
+                ..wn.clone()
+            })
+            .collect();
+        for wn in rotated {
+            if !whitelist_nodes.contains(&wn) {
+                whitelist_nodes.push(wn);
+            }
+        }
+    }
+
+    /// Maximal number of TIER2 connections, see `config::NetworkConfig::max_num_peers`.
+    pub fn max_num_peers(&self) -> u32 {
+        self.max_num_peers.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_num_peers(&self, max_num_peers: u32) {
+        self.max_num_peers.store(max_num_peers, Ordering::Relaxed);
+    }
+
+    /// predicate checking whether we should allow an inbound connection `conn`.
+    fn is_inbound_allowed(&self, conn: &connection::Connection) -> bool {
         // Check if we have spare inbound connections capacity.
         let tier2 = self.tier2.load();
-        if tier2.ready.len() + tier2.outbound_handshakes.len() < self.config.max_num_peers as usize
+        if tier2.ready.len() + tier2.outbound_handshakes.len() < self.max_num_peers() as usize
             && !self.config.inbound_disabled
         {
             return true;
         }
         // Whitelisted nodes are allowed to connect, even if the inbound connections limit has
         // been reached.
-        if self.is_peer_whitelisted(peer_info) {
+        if self.is_peer_whitelisted(conn) {
             return true;
         }
         false
     }
 
+    /// Checks whether the head of the chain is far behind the blocks reported by the peers.
+    fn is_syncing(&self) -> bool {
+        let height = match (**self.chain_info.load()).as_ref() {
+            Some(info) => info.block.header().height(),
+            None => return true,
+        };
+        self.tier2
+            .load()
+            .ready
+            .values()
+            .filter_map(|conn| (**conn.last_block.load()).as_ref().map(|b| b.height))
+            .max()
+            .map_or(false, |peers_height| peers_height > height + SYNCING_HEIGHT_LAG)
+    }
+
+    /// Block height after which the response to `msg` won't be useful anymore, so that its
+    /// route back entry can be dropped. None if the entry should be kept until evicted.
+    pub(crate) fn route_back_expires_at(&self, msg: &RoutedMessageV2) -> Option<BlockHeight> {
+        let height = (**self.chain_info.load()).as_ref()?.block.header().height();
+        match msg.body {
+            RoutedMessageBody::StateRequestHeader(..) | RoutedMessageBody::StateRequestPart(..) => {
+                Some(height + STATE_REQUEST_ROUTE_BACK_HORIZON)
+            }
+            RoutedMessageBody::PartialEncodedChunkRequest(_)
+            | RoutedMessageBody::FullChunkRequest(_) => {
+                Some(height + CHUNK_REQUEST_ROUTE_BACK_HORIZON)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drops the route back entries which have expired at `height`.
+    fn remove_expired_route_back(&self, height: BlockHeight) {
+        let (tier1_expired, tier1_size) = {
+            let mut tier1_route_back = self.tier1_route_back.lock();
+            (tier1_route_back.remove_expired(height), tier1_route_back.len())
+        };
+        let (tier2_expired, tier2_size) =
+            self.graph.routing_table.remove_expired_route_back(height);
+        for (tier, expired, size) in
+            [("tier1", tier1_expired, tier1_size), ("tier2", tier2_expired, tier2_size)]
+        {
+            metrics::ROUTE_BACK_CACHE_EXPIRED.with_label_values(&[tier]).inc_by(expired as u64);
+            metrics::ROUTE_BACK_CACHE_SIZE.with_label_values(&[tier]).set(size as i64);
+        }
+    }
+
+    /// Chooses a TIER2 connection to close in favor of the inbound connection `conn`,
+    /// in case the connection limit has been reached.
+    /// Connections to the whitelisted nodes are never evicted, and neither are the outbound
+    /// connections if there are not more than `minimum_outbound_peers` of them.
+    fn eviction_candidate(
+        &self,
+        conn: &connection::Connection,
+    ) -> Option<Arc<connection::Connection>> {
+        if self.config.inbound_disabled {
+            return None;
+        }
+        let tier2 = self.tier2.load();
+        let outbound_peers =
+            tier2.ready.values().filter(|c| c.peer_type == PeerType::Outbound).count();
+        let protect_outbound = outbound_peers + tier2.outbound_handshakes.len()
+            <= self.config.minimum_outbound_peers as usize;
+        let next_epoch_validators = self.next_epoch_validator_peers();
+        let accounts_data = self.accounts_data.load();
+        let archival = self.config.archive || self.is_syncing();
+        let value = |c: &connection::Connection| connection::ConnectionValue {
+            validator: next_epoch_validators.contains(&c.peer_info.id)
+                || c.owned_account
+                    .as_ref()
+                    .map_or(false, |a| accounts_data.keys.contains(&a.account_key)),
+            archival: archival && c.archival,
+            latency: Reverse(c.rtt().unwrap_or(time::Duration::MAX)),
+        };
+        self.tier2.eviction_candidate(value(conn), |c| {
+            if self.is_peer_whitelisted(c)
+                || (protect_outbound && c.peer_type == PeerType::Outbound)
+            {
+                return None;
+            }
+            Some(value(c))
+        })
+    }
+
     /// Register a direct connection to a new peer. This will be called after successfully
     /// establishing a connection with another peer. It becomes part of the connected peers.
     ///
@@ -270,9 +591,9 @@ impl NetworkState {
         let clock = clock.clone();
         self.spawn(async move {
             let peer_info = &conn.peer_info;
-            // Check if this is a blacklisted peer.
-            if peer_info.addr.as_ref().map_or(true, |addr| this.peer_store.is_blacklisted(addr)) {
-                tracing::debug!(target: "network", peer_info = ?peer_info, "Dropping connection from blacklisted peer or unknown address");
+            // Check if this is a blacklisted (or not allowlisted) peer.
+            if !this.is_connection_allowed(&conn) {
+                tracing::debug!(target: "network", peer_info = ?peer_info, "Dropping connection from blacklisted peer");
                 return Err(RegisterPeerError::Blacklisted);
             }
 
@@ -300,15 +621,24 @@ impl NetworkState {
                 }
                 tcp::Tier::T2 => {
                     if conn.peer_type == PeerType::Inbound {
-                        if !this.is_inbound_allowed(&peer_info) {
-                            // TODO(1896): Gracefully drop inbound connection for other peer.
-                            let tier2 = this.tier2.load();
-                            tracing::debug!(target: "network",
-                                tier2 = tier2.ready.len(), outgoing_peers = tier2.outbound_handshakes.len(),
-                                max_num_peers = this.config.max_num_peers,
-                                "Dropping handshake (network at max capacity)."
-                            );
-                            return Err(RegisterPeerError::ConnectionLimitExceeded);
+                        if !this.is_inbound_allowed(&conn) {
+                            if let Some(evicted) = this.eviction_candidate(&conn) {
+                                tracing::debug!(target: "network",
+                                    evicted = ?evicted.peer_info.id, peer = ?peer_info.id,
+                                    "Evicting a connection to make room for a more valuable peer."
+                                );
+                                metrics::PEER_CONNECTION_EVICTIONS.inc();
+                                evicted.stop(None);
+                            } else {
+                                // TODO(1896): Gracefully drop inbound connection for other peer.
+                                let tier2 = this.tier2.load();
+                                tracing::debug!(target: "network",
+                                    tier2 = tier2.ready.len(), outgoing_peers = tier2.outbound_handshakes.len(),
+                                    max_num_peers = this.max_num_peers(),
+                                    "Dropping handshake (network at max capacity)."
+                                );
+                                return Err(RegisterPeerError::ConnectionLimitExceeded);
+                            }
                         }
                     }
                     // First verify and broadcast the edge of the connection, so that in case
@@ -320,6 +650,7 @@ impl NetworkState {
                     this.tier2.insert_ready(conn.clone()).map_err(RegisterPeerError::PoolError)?;
                     // Write to the peer store
                     this.peer_store.peer_connected(&clock, peer_info);
+                    this.peer_store.add_peer_addrs(&peer_info.id, &conn.listen_addrs);
                 }
             }
             Ok(())
@@ -378,8 +709,7 @@ impl NetworkState {
             }
 
             this.config
-                .event_sink
-                .push(Event::ConnectionClosed(ConnectionClosedEvent { stream_id, reason }));
+                .push_event(Event::ConnectionClosed(ConnectionClosedEvent { stream_id, reason }));
         });
     }
 
@@ -395,9 +725,14 @@ impl NetworkState {
             interval.tick(&clock).await;
 
             let result = async {
-                let stream = tcp::Stream::connect(&peer_info, tcp::Tier::T2)
-                    .await
-                    .context("tcp::Stream::connect()")?;
+                let stream = tcp::Stream::connect(
+                    &peer_info,
+                    tcp::Tier::T2,
+                    self.config.socks5_proxy,
+                    self.config.tcp_connect_timeout,
+                )
+                .await
+                .context("tcp::Stream::connect()")?;
                 PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.clone())
                     .await
                     .context("PeerActor::spawn()")?;
@@ -442,6 +777,93 @@ impl NetworkState {
         self.send_message_to_peer(clock, tier, self.sign_message(clock, msg));
     }
 
+    /// Pings the given targets (PeerIds or AccountIds) through the routing layer and waits
+    /// at most `timeout` for the pongs.
+    pub async fn ping_peers(
+        self: &Arc<Self>,
+        clock: &time::Clock,
+        targets: Vec<String>,
+        timeout: time::Duration,
+    ) -> PingView {
+        let this = self.clone();
+        let clock = clock.clone();
+        self.spawn(async move {
+            let deadline = clock.now() + timeout;
+            let pings = targets.into_iter().map(|target| {
+                let this = this.clone();
+                let clock = clock.clone();
+                async move {
+                    let (mut result, nonce, reply) = match this.start_ping(&clock, target) {
+                        Ok(ping) => ping,
+                        Err(result) => return result,
+                    };
+                    tokio::select! {
+                        reply = reply => {
+                            if let Ok(reply) = reply {
+                                result.rtt_ms = Some(reply.rtt.whole_milliseconds() as i64);
+                                // The pong travelled through one hop more than the number of
+                                // the times its TTL has been decreased.
+                                let hops = this.config.routed_message_ttl.saturating_sub(reply.ttl);
+                                result.pong_hops = Some(hops.saturating_add(1));
+                            }
+                        }
+                        _ = clock.sleep_until(deadline) => {}
+                    }
+                    this.pinger.cancel(nonce);
+                    result
+                }
+            });
+            PingView { results: futures_util::future::join_all(pings).await }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Sends a ping to `target` (a PeerId or an AccountId). Returns the result to fill in,
+    /// together with the nonce of the ping and the receiver of the pong.
+    fn start_ping(
+        &self,
+        clock: &time::Clock,
+        target: String,
+    ) -> Result<(PingResultView, u64, oneshot::Receiver<pinger::Reply>), PingResultView> {
+        let mut result = PingResultView {
+            target,
+            peer_id: None,
+            next_hops: vec![],
+            rtt_ms: None,
+            pong_hops: None,
+            error: None,
+        };
+        let peer_id = match self.resolve_target(&result.target) {
+            Ok(peer_id) => peer_id,
+            Err(err) => {
+                result.error = Some(err.to_string());
+                return Err(result);
+            }
+        };
+        result.peer_id = Some(peer_id.clone());
+        result.next_hops = self.graph.routing_table.view_route(&peer_id).unwrap_or_default();
+        let nonce = rand::thread_rng().gen();
+        let reply = match self.pinger.start(clock, nonce, peer_id.clone()) {
+            Some(reply) => reply,
+            None => {
+                result.error = Some("nonce collision".to_string());
+                return Err(result);
+            }
+        };
+        let body = RoutedMessageBody::Ping(crate::network_protocol::Ping {
+            nonce,
+            source: self.config.node_id(),
+        });
+        let msg = RawRoutedMessage { target: PeerIdOrHash::PeerId(peer_id), body };
+        if !self.send_message_to_peer(clock, tcp::Tier::T2, self.sign_message(clock, msg)) {
+            self.pinger.cancel(nonce);
+            result.error = Some("no route".to_string());
+            return Err(result);
+        }
+        Ok((result, nonce, reply))
+    }
+
     pub fn send_pong(&self, clock: &time::Clock, tier: tcp::Tier, nonce: u64, target: CryptoHash) {
         let body = RoutedMessageBody::Pong(crate::network_protocol::Pong {
             nonce,
@@ -459,6 +881,43 @@ impl NetworkState {
         ))
     }
 
+    /// Verifies the signature of a routed message. Concurrent calls are batched together and
+    /// the batches are verified in parallel on the rayon threadpool.
+    /// Returns the message together with the verification result,
+    /// or None if the NetworkState is being dropped.
+    pub async fn verify_routed_message(
+        &self,
+        msg: Box<RoutedMessageV2>,
+    ) -> Option<(Box<RoutedMessageV2>, bool)> {
+        self.verify_routed_demux
+            .call(msg, |msgs: Vec<Box<RoutedMessageV2>>| async move {
+                metrics::ROUTED_MESSAGE_VERIFICATION_BATCH_SIZE.observe(msgs.len() as f64);
+                concurrency::rayon::run(move || {
+                    msgs.into_par_iter()
+                        .map(|msg| {
+                            let ok = msg.verify();
+                            (msg, ok)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await
+            })
+            .await
+            .ok()
+    }
+
+    /// Records an event concerning `msg`, if both the message is traced and tracing is enabled.
+    pub(crate) fn trace_routed_message(
+        &self,
+        clock: &time::Clock,
+        msg: &RoutedMessageV2,
+        event: routed_trace::Event,
+    ) {
+        if let (Some(tracer), Some(trace_id)) = (&self.routed_trace, msg.trace_id) {
+            tracer.record(clock, trace_id, msg.body_variant(), event);
+        }
+    }
+
     /// Route signed message to target peer.
     /// Return whether the message is sent or not.
     pub fn send_message_to_peer(
@@ -485,11 +944,42 @@ impl NetworkState {
                     PeerIdOrHash::Hash(hash) => {
                         match self.tier1_route_back.lock().remove(clock, hash) {
                             Some(peer_id) => peer_id,
-                            None => return false,
+                            None => {
+                                self.trace_routed_message(
+                                    clock,
+                                    &msg,
+                                    routed_trace::Event::Dropped { reason: "no route back" },
+                                );
+                                return false;
+                            }
                         }
                     }
                     PeerIdOrHash::PeerId(peer_id) => peer_id.clone(),
                 };
+                if msg.author != my_peer_id && matches!(msg.target, PeerIdOrHash::PeerId(_)) {
+                    // Messages of other nodes are relayed over TIER1 only by the TIER1 proxies,
+                    // and only to the current TIER1 validators connected directly to the proxy.
+                    // Connections of the validators which are no longer TIER1 are closed
+                    // by tier1_connect(), but only periodically.
+                    if !self.is_tier1_validator_connection(&peer_id) {
+                        metrics::MessageDropped::NotTier1Validator.inc(&msg.body);
+                        self.config.push_event(Event::RoutedMessageDropped);
+                        self.trace_routed_message(
+                            clock,
+                            &msg,
+                            routed_trace::Event::Dropped { reason: "not a TIER1 validator" },
+                        );
+                        return false;
+                    }
+                    metrics::TIER1_PROXY_RELAYED_MESSAGES
+                        .with_label_values(&[msg.body_variant()])
+                        .inc();
+                }
+                self.trace_routed_message(
+                    clock,
+                    &msg,
+                    routed_trace::Event::Routed { to: peer_id.clone(), tier },
+                );
                 return self.tier1.send_message(peer_id, Arc::new(PeerMessage::Routed(msg)));
             }
             tcp::Tier::T2 => match self.graph.routing_table.find_route(&clock, &msg.target) {
@@ -497,13 +987,28 @@ impl NetworkState {
                     // Remember if we expect a response for this message.
                     if msg.author == my_peer_id && msg.expect_response() {
                         tracing::trace!(target: "network", ?msg, "initiate route back");
-                        self.graph.routing_table.add_route_back(&clock, msg.hash(), my_peer_id);
+                        self.graph.routing_table.add_route_back(
+                            &clock,
+                            msg.hash(),
+                            my_peer_id,
+                            self.route_back_expires_at(&msg),
+                        );
                     }
+                    self.trace_routed_message(
+                        clock,
+                        &msg,
+                        routed_trace::Event::Routed { to: peer_id.clone(), tier },
+                    );
                     return self.tier2.send_message(peer_id, Arc::new(PeerMessage::Routed(msg)));
                 }
                 Err(find_route_error) => {
                     // TODO(MarX, #1369): Message is dropped here. Define policy for this case.
                     metrics::MessageDropped::NoRouteFound.inc(&msg.body);
+                    self.trace_routed_message(
+                        clock,
+                        &msg,
+                        routed_trace::Event::Dropped { reason: "no route" },
+                    );
 
                     tracing::debug!(target: "network",
                           account_id = ?self.config.validator.as_ref().map(|v|v.account_id()),
@@ -519,21 +1024,189 @@ impl NetworkState {
         }
     }
 
+    /// Checks whether the TIER1 connection to `peer_id` is owned by a current TIER1 validator.
+    fn is_tier1_validator_connection(&self, peer_id: &PeerId) -> bool {
+        let accounts_data = self.accounts_data.load();
+        self.tier1
+            .load()
+            .ready
+            .get(peer_id)
+            .and_then(|conn| conn.owned_account.as_ref())
+            .map_or(false, |owned_account| accounts_data.keys.contains(&owned_account.account_key))
+    }
+
+    /// Whether the message to an account should be sent over TIER1, if the account is reachable
+    /// over TIER1. Returns `None` if the message should not be sent over TIER1, otherwise
+    /// whether it should still be sent over TIER2 as well.
+    fn tier1_delivery(&self, msg: &RoutedMessageBody) -> Option<bool> {
+        if !tcp::Tier::T1.is_allowed_routed(msg) {
+            return None;
+        }
+        let send_chunk_parts = self.config.tier1.as_ref().map_or(false, |c| c.send_chunk_parts);
+        let send_forward_tx = self.config.tier1.as_ref().map_or(false, |c| c.send_forward_tx);
+        match msg {
+            RoutedMessageBody::PartialEncodedChunkForward(..) => send_chunk_parts.then_some(false),
+            RoutedMessageBody::VersionedPartialEncodedChunk(..) => Some(!send_chunk_parts),
+            RoutedMessageBody::ForwardTx(..) => send_forward_tx.then_some(true),
+            _ => Some(true),
+        }
+    }
+
     /// Send message to specific account.
     /// Return whether the message is sent or not.
     /// The message might be sent over TIER1 and/or TIER2 connection depending on the message type.
+    /// If the message cannot be sent, it might be stored in the outbox to be sent later,
+    /// once the account becomes reachable.
     pub fn send_message_to_account(
         &self,
         clock: &time::Clock,
         account_id: &AccountId,
         msg: RoutedMessageBody,
+    ) -> bool {
+        let outbox = self.outbox.as_ref().filter(|_| outbox::is_eligible(&msg));
+        let outbox_msg = outbox.map(|_| msg.clone());
+        if self.try_send_message_to_account(clock, account_id, msg) {
+            return true;
+        }
+        if let (Some(outbox), Some(msg)) = (outbox, outbox_msg) {
+            outbox.push(clock, account_id, msg);
+        }
+        false
+    }
+
+    /// Resolves a debug request target, which is either a PeerId or an AccountId.
+    fn resolve_target(&self, target: &str) -> Result<PeerId, &'static str> {
+        let peer_id = match target.parse::<near_crypto::PublicKey>() {
+            Ok(key) => Some(PeerId::new(key)),
+            Err(_) => match target.parse::<AccountId>() {
+                Ok(account_id) => self.account_peer_id(&account_id),
+                Err(_) => return Err("neither a PeerId nor an AccountId"),
+            },
+        };
+        peer_id.ok_or("unknown account")
+    }
+
+    /// Current route to `target` (a PeerId or an AccountId), for debugging the delivery of
+    /// the routed messages.
+    pub fn route_info(&self, target: String) -> RouteInfoView {
+        let graph = self.graph.load();
+        let mut view = RouteInfoView {
+            target,
+            peer_id: None,
+            next_hops: vec![],
+            distance: None,
+            routed_message_ttl: self.config.routed_message_ttl,
+            routing_table_updated_at_ms: graph
+                .updated_at
+                .map(|t| (t.unix_timestamp_nanos() / 1_000_000) as i64),
+            error: None,
+        };
+        let peer_id = match self.resolve_target(&view.target) {
+            Ok(peer_id) => peer_id,
+            Err(err) => {
+                view.error = Some(err.to_string());
+                return view;
+            }
+        };
+        view.next_hops = self.graph.routing_table.view_route(&peer_id).unwrap_or_default();
+        view.distance = graph.distance(&self.config.node_id(), &peer_id);
+        view.peer_id = Some(peer_id);
+        view
+    }
+
+    /// Looks up the peer owning the given account:
+    /// - first look it up in self.accounts_data
+    /// - if missing, fall back to lookup in self.graph.routing_table
+    fn account_peer_id(&self, account_id: &AccountId) -> Option<PeerId> {
+        let accounts_data = self.accounts_data.load();
+        let peer_id_from_account_data = accounts_data
+            .keys_by_id
+            .get(account_id)
+            .iter()
+            .flat_map(|keys| keys.iter())
+            .flat_map(|key| accounts_data.data.get(key))
+            .next()
+            .map(|data| data.peer_id.clone());
+        peer_id_from_account_data.or_else(|| self.graph.routing_table.account_owner(account_id))
+    }
+
+    /// Round trip times of the direct connections to the peers owning the known accounts.
+    /// The owners known from the TIER1 accounts data take precedence over the announced ones.
+    pub fn account_rtts(&self) -> HashMap<AccountId, time::Duration> {
+        let tier1 = self.tier1.load();
+        let tier2 = self.tier2.load();
+        let accounts_data = self.accounts_data.load();
+        let tier1_owners = accounts_data.keys_by_id.iter().flat_map(|(account_id, keys)| {
+            keys.iter()
+                .filter_map(|key| accounts_data.data.get(key))
+                .map(move |data| (account_id.clone(), data.peer_id.clone()))
+        });
+        let announced_owners = self
+            .graph
+            .routing_table
+            .get_announce_accounts()
+            .into_iter()
+            .map(|announce_account| (announce_account.account_id, announce_account.peer_id));
+        let mut rtts = HashMap::new();
+        for (account_id, peer_id) in tier1_owners.chain(announced_owners) {
+            if rtts.contains_key(&account_id) {
+                continue;
+            }
+            let conn = tier1.ready.get(&peer_id).or_else(|| tier2.ready.get(&peer_id));
+            if let Some(rtt) = conn.and_then(|conn| conn.rtt()) {
+                rtts.insert(account_id, rtt);
+            }
+        }
+        rtts
+    }
+
+    /// Sends the messages from the outbox to the accounts which have become reachable.
+    pub fn flush_outbox(&self, clock: &time::Clock) {
+        let outbox = match &self.outbox {
+            Some(outbox) => outbox,
+            None => return,
+        };
+        let tier1 = self.tier1.load();
+        outbox.flush(
+            clock,
+            |account_id| match self.account_peer_id(account_id) {
+                Some(peer_id) => {
+                    tier1.ready.contains_key(&peer_id)
+                        || self.graph.routing_table.view_route(&peer_id).is_some()
+                }
+                None => false,
+            },
+            |account_id, msg| self.try_send_message_to_account(clock, account_id, msg.clone()),
+        );
+    }
+
+    /// Sends message to specific account, without falling back to the outbox.
+    fn try_send_message_to_account(
+        &self,
+        clock: &time::Clock,
+        account_id: &AccountId,
+        msg: RoutedMessageBody,
     ) -> bool {
         let mut success = false;
         let accounts_data = self.accounts_data.load();
+        // The same trace id is used for all the copies of the message sent over TIER1 and TIER2.
+        let trace_id = self.routed_trace.as_ref().map(|tracer| {
+            let trace_id = routed_trace::new_trace_id();
+            tracer.record(
+                clock,
+                trace_id,
+                (&msg).into(),
+                routed_trace::Event::Originated { account_id: account_id.clone() },
+            );
+            trace_id
+        });
         // All TIER1 messages are being sent over both TIER1 and TIER2 connections for now,
         // so that we can actually observe the latency/reliability improvements in practice:
         // for each message we track over which network tier it arrived faster?
-        if tcp::Tier::T1.is_allowed_routed(&msg) {
+        // The exception are the chunk parts, which are large and numerous, so once they are
+        // enabled on TIER1, the multi-hop TIER2 routing is used only as a fallback.
+        let tier1_delivery = self.tier1_delivery(&msg);
+        if tier1_delivery.is_some() {
             for key in accounts_data.keys_by_id.get(account_id).iter().flat_map(|keys| keys.iter())
             {
                 let data = match accounts_data.data.get(key) {
@@ -546,16 +1219,30 @@ impl NetworkState {
                 };
                 // TODO(gprusak): in case of PartialEncodedChunk, consider stripping everything
                 // but the header. This will bound the message size
-                conn.send_message(Arc::new(PeerMessage::Routed(self.sign_message(
+                let mut routed = self.sign_message(
                     clock,
                     RawRoutedMessage {
                         target: PeerIdOrHash::PeerId(data.peer_id.clone()),
                         body: msg.clone(),
                     },
-                ))));
+                );
+                routed.trace_id = trace_id;
+                self.trace_routed_message(
+                    clock,
+                    &routed,
+                    routed_trace::Event::Routed {
+                        to: conn.peer_info.id.clone(),
+                        tier: tcp::Tier::T1,
+                    },
+                );
+                conn.send_message(Arc::new(PeerMessage::Routed(routed)));
                 success |= true;
                 break;
             }
+            if success && tier1_delivery == Some(false) {
+                metrics::TIER1_CHUNK_PARTS_SENT.with_label_values(&[(&msg).into()]).inc();
+                return true;
+            }
         }
 
         let peer_id_from_account_data = accounts_data
@@ -579,6 +1266,14 @@ impl NetworkState {
         } else {
             // TODO(MarX, #1369): Message is dropped here. Define policy for this case.
             metrics::MessageDropped::UnknownAccount.inc(&msg);
+            if let (Some(tracer), Some(trace_id)) = (&self.routed_trace, trace_id) {
+                tracer.record(
+                    clock,
+                    trace_id,
+                    (&msg).into(),
+                    routed_trace::Event::Dropped { reason: "unknown account" },
+                );
+            }
             tracing::debug!(target: "network",
                    account_id = ?self.config.validator.as_ref().map(|v|v.account_id()),
                    to = ?account_id,
@@ -589,7 +1284,8 @@ impl NetworkState {
         };
 
         let msg = RawRoutedMessage { target: PeerIdOrHash::PeerId(target), body: msg };
-        let msg = self.sign_message(clock, msg);
+        let mut msg = self.sign_message(clock, msg);
+        msg.trace_id = trace_id;
         if msg.body.is_important() {
             for _ in 0..IMPORTANT_MESSAGE_RESENT_COUNT {
                 success |= self.send_message_to_peer(clock, tcp::Tier::T2, msg.clone());
@@ -707,6 +1403,33 @@ impl NetworkState {
         self.connection_store.update(clock, &self.tier2.load());
     }
 
+    /// Re-resolves the hostnames of the boot nodes given by hostname, so that the addresses of
+    /// the boot nodes with rotating IPs stay up to date in the peer store.
+    pub async fn resolve_boot_node_hosts(&self, clock: &time::Clock) {
+        for boot_node in &self.peer_store.boot_node_hosts() {
+            match tokio::net::lookup_host(&boot_node.host).await.map(|mut addrs| addrs.next()) {
+                Ok(Some(addr)) => {
+                    self.peer_store.set_boot_node_addr(clock, &boot_node.peer_id, addr)
+                }
+                Ok(None) => {
+                    tracing::warn!(
+                        target: "network",
+                        host = %boot_node.host,
+                        "Boot node hostname resolved to no addresses"
+                    )
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        target: "network",
+                        host = %boot_node.host,
+                        %err,
+                        "Failed to resolve boot node"
+                    )
+                }
+            }
+        }
+    }
+
     /// Clears pending_reconnect and returns the cleared values
     pub fn poll_pending_reconnect(&self) -> Vec<PeerInfo> {
         let mut pending_reconnect = self.pending_reconnect.lock();
@@ -722,14 +1445,20 @@ impl NetworkState {
 
     /// Sets the chain info, and updates the set of TIER1 keys.
     /// Returns true iff the set of TIER1 keys has changed.
-    pub fn set_chain_info(self: &Arc<Self>, info: ChainInfo) -> bool {
+    pub fn set_chain_info(self: &Arc<Self>, clock: &time::Clock, info: ChainInfo) -> bool {
         let _mutex = self.set_chain_info_mutex.lock();
 
         // We set state.chain_info and call accounts_data.set_keys
         // synchronously, therefore, assuming actix in-order delivery,
         // there will be no race condition between subsequent SetChainInfo
         // calls.
-        self.chain_info.store(Arc::new(Some(info.clone())));
+        let prev = self.chain_info.swap(Arc::new(Some(info.clone())));
+        self.remove_expired_route_back(info.block.header().height());
+        if let Some(prev) = &*prev {
+            if prev.block.header().epoch_id() != info.block.header().epoch_id() {
+                self.report_epoch_boundary_readiness(&prev.next_epoch_accounts);
+            }
+        }
 
         // If tier1 is not enabled, we skip set_keys() call.
         // This way self.state.accounts_data is always empty, hence no data
@@ -737,6 +1466,7 @@ impl NetworkState {
         if self.config.tier1.is_none() {
             return false;
         }
+        self.accounts_data.set_epoch(clock, info.block.header().epoch_id());
         let has_changed = self.accounts_data.set_keys(info.tier1_accounts);
         // The set of TIER1 accounts has changed, so we might be missing some accounts_data
         // that our peers know about.
@@ -0,0 +1,192 @@
+//! Connections to the validators of the next epoch.
+//!
+//! TIER1 connections to the next epoch validators are established by tier1_connect(), since
+//! ChainInfo::tier1_accounts already contains them. However a validator joining the active set
+//! usually has no TIER2 connections to the other validators, so until the routing table
+//! converges, the messages it sends at the beginning of its first epoch travel through many hops
+//! (or are not delivered at all). To avoid that, a validator of the next epoch establishes direct
+//! TIER2 connections to the other validators of the next epoch in advance.
+use crate::network_protocol::PeerInfo;
+use crate::peer::peer_actor::PeerActor;
+use crate::stats::metrics;
+use crate::tcp;
+use crate::types::AccountKeys;
+use anyhow::Context as _;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Interval between attempts to connect to the validators of the next epoch.
+pub(crate) const NEXT_EPOCH_CONNECT_INTERVAL: time::Duration = time::Duration::seconds(30);
+
+/// Maximal number of new TIER2 connections to the validators of the next epoch,
+/// established every NEXT_EPOCH_CONNECT_INTERVAL.
+const NEXT_EPOCH_NEW_CONNECTIONS_PER_ATTEMPT: usize = 10;
+
+/// Number of the validators to which this node has a TIER1 and a TIER2 connection respectively.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ConnectedValidators {
+    pub total: usize,
+    pub tier1: usize,
+    pub tier2: usize,
+}
+
+impl super::NetworkState {
+    /// Returns the validators of the next epoch, iff this node is one of them.
+    fn next_epoch_accounts_if_validator(&self) -> Option<Arc<AccountKeys>> {
+        let vc = self.config.validator.as_ref()?;
+        let accounts = (**self.chain_info.load()).as_ref()?.next_epoch_accounts.clone();
+        let is_validator = accounts
+            .get(vc.signer.validator_id())
+            .map_or(false, |keys| keys.contains(&vc.signer.public_key()));
+        is_validator.then_some(accounts)
+    }
+
+    /// Counts the validators from `accounts` (except this node) to which this node is connected.
+    pub(crate) fn count_connected_validators(&self, accounts: &AccountKeys) -> ConnectedValidators {
+        let my_account_id = self.config.validator.as_ref().map(|vc| vc.signer.validator_id());
+        let accounts_data = self.accounts_data.load();
+        let tier2 = self.tier2.load();
+        let mut res = ConnectedValidators::default();
+        for (account_id, keys) in accounts {
+            if Some(account_id) == my_account_id {
+                continue;
+            }
+            res.total += 1;
+            if keys
+                .iter()
+                .filter_map(|key| accounts_data.data.get(key))
+                .any(|data| self.get_tier1_proxy(data).is_some())
+            {
+                res.tier1 += 1;
+            }
+            if self.account_peer_id(account_id).map_or(false, |p| tier2.ready.contains_key(&p)) {
+                res.tier2 += 1;
+            }
+        }
+        res
+    }
+
+    /// Peers with TIER2 connections to the validators of the next epoch.
+    /// Empty, unless this node is a validator of the next epoch.
+    pub(crate) fn next_epoch_validator_peers(&self) -> HashSet<PeerId> {
+        let accounts = match self.next_epoch_accounts_if_validator() {
+            Some(accounts) => accounts,
+            None => return HashSet::new(),
+        };
+        let tier2 = self.tier2.load();
+        accounts
+            .keys()
+            .filter_map(|account_id| self.account_peer_id(account_id))
+            .filter(|peer_id| tier2.ready.contains_key(peer_id))
+            .collect()
+    }
+
+    /// Updates the readiness metrics at the epoch boundary.
+    /// `accounts` are the validators of the epoch which has just started.
+    pub(crate) fn report_epoch_boundary_readiness(&self, accounts: &AccountKeys) {
+        let vc = match &self.config.validator {
+            Some(vc) => vc,
+            None => return,
+        };
+        if !accounts.contains_key(vc.signer.validator_id()) {
+            return;
+        }
+        let connected = self.count_connected_validators(accounts);
+        tracing::info!(target: "network", ?connected, "connections to the validators at the epoch boundary");
+        metrics::EPOCH_BOUNDARY_VALIDATORS.set(connected.total as i64);
+        metrics::EPOCH_BOUNDARY_VALIDATORS_CONNECTED
+            .with_label_values(&[tcp::Tier::T1.as_ref()])
+            .set(connected.tier1 as i64);
+        metrics::EPOCH_BOUNDARY_VALIDATORS_CONNECTED
+            .with_label_values(&[tcp::Tier::T2.as_ref()])
+            .set(connected.tier2 as i64);
+    }
+
+    /// If this node is a validator of the next epoch, establishes TIER2 connections to
+    /// the other validators of the next epoch (at most NEXT_EPOCH_NEW_CONNECTIONS_PER_ATTEMPT
+    /// per call) and updates the readiness metrics.
+    pub async fn next_epoch_connect(self: &Arc<Self>, clock: &time::Clock) {
+        let accounts = match self.next_epoch_accounts_if_validator() {
+            Some(accounts) => accounts,
+            None => {
+                metrics::NEXT_EPOCH_VALIDATORS.set(0);
+                metrics::NEXT_EPOCH_VALIDATORS_CONNECTED.reset();
+                return;
+            }
+        };
+        let connected = self.count_connected_validators(&accounts);
+        metrics::NEXT_EPOCH_VALIDATORS.set(connected.total as i64);
+        metrics::NEXT_EPOCH_VALIDATORS_CONNECTED
+            .with_label_values(&[tcp::Tier::T1.as_ref()])
+            .set(connected.tier1 as i64);
+        metrics::NEXT_EPOCH_VALIDATORS_CONNECTED
+            .with_label_values(&[tcp::Tier::T2.as_ref()])
+            .set(connected.tier2 as i64);
+
+        let my_peer_id = self.config.node_id();
+        let accounts_data = self.accounts_data.load();
+        let tier2 = self.tier2.load();
+        let mut handles = vec![];
+        for account_id in accounts.keys() {
+            if handles.len() >= NEXT_EPOCH_NEW_CONNECTIONS_PER_ATTEMPT {
+                break;
+            }
+            let peer_id = match self.account_peer_id(account_id) {
+                Some(peer_id) => peer_id,
+                None => continue,
+            };
+            if peer_id == my_peer_id
+                || tier2.ready.contains_key(&peer_id)
+                || tier2.outbound_handshakes.contains(&peer_id)
+            {
+                continue;
+            }
+            // Prefer the address from the peer store, but fall back to the address advertised
+            // in AccountData, in case the validator is its own TIER1 proxy.
+            let addr = self
+                .peer_store
+                .get_peer_state(&peer_id)
+                .and_then(|state| state.peer_info.addr)
+                .or_else(|| {
+                    accounts_data
+                        .data
+                        .values()
+                        .flat_map(|data| data.proxies.iter())
+                        .find(|proxy| proxy.peer_id == peer_id)
+                        .map(|proxy| proxy.addr)
+                });
+            let peer_info = match addr {
+                Some(addr) => PeerInfo { id: peer_id, addr: Some(addr), account_id: None },
+                None => continue,
+            };
+            handles.push(async move {
+                let result = async {
+                    let stream =
+                        tcp::Stream::connect(
+                        &peer_info,
+                        tcp::Tier::T2,
+                        self.config.socks5_proxy,
+                        self.config.tcp_connect_timeout,
+                    )
+                            .await
+                            .context("tcp::Stream::connect()")?;
+                    PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.clone())
+                        .await
+                        .context("PeerActor::spawn()")?;
+                    anyhow::Ok(())
+                }
+                .await;
+                if let Err(err) = &result {
+                    tracing::info!(target: "network", ?err, "failed to connect to next epoch validator {peer_info}");
+                }
+                if self.peer_store.peer_connection_attempt(clock, &peer_info.id, result).is_err() {
+                    tracing::error!(target: "network", ?peer_info, "Failed to store connection attempt.");
+                }
+            });
+        }
+        tracing::debug!(target: "network", "establishing {} new connections to the next epoch validators", handles.len());
+        futures_util::future::join_all(handles).await;
+    }
+}
@@ -1,6 +1,8 @@
 use super::NetworkState;
-use crate::network_protocol::{Edge, EdgeState, PartialEdgeInfo, PeerMessage, RoutingTableUpdate};
-use crate::peer_manager::peer_manager_actor::Event;
+use crate::network_protocol::{
+    Edge, EdgeState, PartialEdgeInfo, PeerMessage, RoutingTableUpdate, SignedKeyTransition,
+};
+use crate::peer_manager::peer_manager_actor::{Event, EventKind};
 use crate::stats::metrics;
 use crate::types::ReasonForBan;
 use near_primitives::network::{AnnounceAccount, PeerId};
@@ -31,10 +33,44 @@ impl NetworkState {
             this.broadcast_routing_table_update(RoutingTableUpdate::from_accounts(
                 new_accounts.clone(),
             ));
-            this.config.event_sink.push(Event::AccountsAdded(new_accounts));
+            this.config.push_event(Event::AccountsAdded(new_accounts));
         }).await.unwrap()
     }
 
+    /// Verifies the key transitions and records them in the peer store.
+    /// Then it broadcasts all the transitions that haven't been seen before.
+    pub async fn add_key_transitions(
+        self: &Arc<NetworkState>,
+        key_transitions: Vec<SignedKeyTransition>,
+    ) -> Result<(), ReasonForBan> {
+        if key_transitions.is_empty() {
+            return Ok(());
+        }
+        let this = self.clone();
+        self.spawn(async move {
+            let mut new_transitions = vec![];
+            for t in key_transitions {
+                if !t.verify() {
+                    return Err(ReasonForBan::InvalidSignature);
+                }
+                if this.peer_store.add_key_transition(t.clone()) {
+                    this.rotate_whitelist_nodes(&t);
+                    new_transitions.push(t);
+                }
+            }
+            if new_transitions.is_empty() {
+                return Ok(());
+            }
+            this.broadcast_routing_table_update(RoutingTableUpdate::from_key_transitions(
+                new_transitions.clone(),
+            ));
+            this.config.push_event(Event::KeyTransitionsAdded(new_transitions));
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
     /// Constructs a partial edge to the given peer with the nonce specified.
     /// If nonce is None, nonce is selected automatically.
     pub fn propose_edge(
@@ -94,6 +130,8 @@ impl NetworkState {
         self.add_edges_demux
             .call(edges, |edges: Vec<Vec<Edge>>| async move {
                 let (mut edges, oks) = this.graph.update(&clock, edges).await;
+                // New routes might have appeared, so try to deliver the pending messages.
+                this.flush_outbox(&clock);
                 // Don't send tombstones during the initial time.
                 // Most of the network is created during this time, which results
                 // in us sending a lot of tombstones to peers.
@@ -105,7 +143,9 @@ impl NetworkState {
                     }
                 }
                 // Broadcast new edges to all other peers.
-                this.config.event_sink.push(Event::EdgesAdded(edges.clone()));
+                if this.config.event_enabled(EventKind::EdgesAdded) {
+                    this.config.push_event(Event::EdgesAdded(edges.clone()));
+                }
                 this.broadcast_routing_table_update(RoutingTableUpdate::from_edges(edges));
                 // Retu
                 oks.iter()
@@ -1,7 +1,7 @@
 use crate::accounts_data;
 use crate::config;
 use crate::network_protocol::{
-    AccountData, PeerAddr, PeerInfo, PeerMessage, SignedAccountData, SyncAccountsData,
+    AccountData, PeerAddr, PeerInfo, PeerMessage, ProxyHint, SignedAccountData, SyncAccountsData,
 };
 use crate::peer::peer_actor::PeerActor;
 use crate::peer_manager::connection;
@@ -12,11 +12,13 @@ use near_crypto::PublicKey;
 use near_o11y::log_assert;
 use near_primitives::network::PeerId;
 use near_primitives::time;
-use rand::seq::IteratorRandom as _;
 use rand::seq::SliceRandom as _;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Interval between full syncs of the accounts data with the direct TIER2 peers.
+pub(crate) const ACCOUNTS_DATA_FULL_SYNC_INTERVAL: time::Duration = time::Duration::minutes(10);
+
 impl super::NetworkState {
     // Returns ValidatorConfig of this node iff it belongs to TIER1 according to `accounts_data`.
     pub fn tier1_validator_config(
@@ -54,6 +56,8 @@ impl super::NetworkState {
                             account_id: None,
                         },
                         tcp::Tier::T1,
+                        self.config.socks5_proxy,
+                        self.config.tcp_connect_timeout,
                     )
                     .await?;
                     anyhow::Ok(PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.clone()).await?)
@@ -77,6 +81,24 @@ impl super::NetworkState {
         })));
     }
 
+    /// Sends all the accounts data to the direct TIER2 peers, requesting theirs in return.
+    /// Incremental updates may get lost (for example, when a connection is closed while the
+    /// update is being sent), so a periodic full sync makes the accounts data converge anyway.
+    /// Only the outbound side of a connection performs the full sync, so that the data is
+    /// exchanged just once per connection.
+    pub fn accounts_data_full_sync(&self) {
+        let msg = Arc::new(PeerMessage::SyncAccountsData(SyncAccountsData {
+            accounts_data: self.accounts_data.load().data.values().cloned().collect(),
+            incremental: false,
+            requesting_full_sync: true,
+        }));
+        for conn in self.tier2.load().ready.values() {
+            if conn.peer_type == PeerType::Outbound {
+                conn.send_message(msg.clone());
+            }
+        }
+    }
+
     /// Tries to connect to ALL trusted proxies from the config, then broadcasts AccountData with
     /// the set of proxies it managed to connect to. This way other TIER1 nodes can just connect
     /// to ANY proxy of this node.
@@ -140,7 +162,7 @@ impl super::NetworkState {
         // Snapshot tier1 connections again before broadcasting.
         let tier1 = self.tier1.load();
 
-        let my_proxies = match &vc.proxies {
+        let mut my_proxies = match &vc.proxies {
             // In case of dynamic configuration, only the node itself can be its proxy,
             // so we look for a loop connection which would prove our node's address.
             config::ValidatorProxies::Dynamic(_) => match tier1.ready.get(&self.config.node_id()) {
@@ -184,12 +206,30 @@ impl super::NetworkState {
                 connected_proxies
             }
         };
+        // Advertise the latencies to the proxies, so that the other TIER1 nodes can choose
+        // the best one. Listing the proxies from the best to worst additionally helps
+        // the nodes which ignore the hints.
+        let mut proxy_hints: Vec<_> = my_proxies
+            .iter()
+            .filter_map(|proxy| {
+                let latency = tier1.ready.get(&proxy.peer_id)?.rtt()?;
+                Some(ProxyHint { peer_id: proxy.peer_id.clone(), latency })
+            })
+            .collect();
+        proxy_hints.sort_by_key(|h| h.latency);
+        my_proxies.sort_by_key(|p| {
+            proxy_hints.iter().position(|h| h.peer_id == p.peer_id).unwrap_or(usize::MAX)
+        });
         tracing::info!(target:"network","connected to proxies {my_proxies:?}");
         let new_data = self.accounts_data.set_local(
             clock,
             accounts_data::LocalData {
                 signer: vc.signer.clone(),
-                data: Arc::new(AccountData { peer_id: self.config.node_id(), proxies: my_proxies }),
+                data: Arc::new(AccountData {
+                    peer_id: self.config.node_id(),
+                    proxies: my_proxies,
+                    proxy_hints,
+                }),
             },
         );
         // Early exit in case this node is not a TIER1 node any more.
@@ -220,7 +260,10 @@ impl super::NetworkState {
         let mut accounts_by_proxy = HashMap::<_, Vec<_>>::new();
         let mut proxies_by_account = HashMap::<_, Vec<_>>::new();
         for d in accounts_data.data.values() {
-            proxies_by_account.entry(&d.account_key).or_default().extend(d.proxies.iter());
+            proxies_by_account
+                .entry(&d.account_key)
+                .or_default()
+                .extend(d.proxies.iter().map(|p| (p, d.proxy_latency(&p.peer_id))));
             for p in &d.proxies {
                 accounts_by_proxy.entry(&p.peer_id).or_default().push(&d.account_key);
             }
@@ -312,13 +355,10 @@ impl super::NetworkState {
                 if safe.contains_key(account_key) {
                     continue;
                 }
-                // Find addresses of proxies of account_key.
-                let proxies: Vec<&PeerAddr> =
-                    proxies_by_account.get(account_key).into_iter().flatten().map(|x| *x).collect();
-                // Select a random proxy of the account_key and try to connect to it.
-                let proxy = proxies.iter().choose(&mut rand::thread_rng());
-                if let Some(proxy) = proxy {
-                    let proxy = (*proxy).clone();
+                // Select the best proxy of the account_key and try to connect to it.
+                let proxies = proxies_by_account.get(account_key).map_or(&[][..], |p| &p[..]);
+                if let Some(proxy) = select_proxy(proxies) {
+                    let proxy = proxy.clone();
                     handles.push(async move {
                         let stream = tcp::Stream::connect(
                             &PeerInfo {
@@ -327,6 +367,8 @@ impl super::NetworkState {
                                 account_id: None,
                             },
                             tcp::Tier::T1,
+                            self.config.socks5_proxy,
+                            self.config.tcp_connect_timeout,
                         )
                         .await?;
                         PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.clone())
@@ -356,11 +398,24 @@ impl super::NetworkState {
         }
         // In case there is no direct connection and our node is a TIER1 validator, use a proxy.
         // TODO(gprusak): add a check that our node is actually a TIER1 validator.
-        for proxy in &data.proxies {
-            if let Some(conn) = tier1.ready.get(&proxy.peer_id) {
-                return Some(conn.clone());
-            }
-        }
-        None
+        // Among the connected proxies prefer the one with the lowest advertised latency.
+        data.proxies
+            .iter()
+            .filter_map(|proxy| tier1.ready.get(&proxy.peer_id))
+            .min_by_key(|conn| {
+                data.proxy_latency(&conn.peer_info.id).unwrap_or(time::Duration::MAX)
+            })
+            .cloned()
     }
 }
+
+/// Selects the proxy with the lowest advertised latency.
+/// Ties, including the proxies without latency hints, are broken at random.
+fn select_proxy<'a>(proxies: &[(&'a PeerAddr, Option<time::Duration>)]) -> Option<&'a PeerAddr> {
+    let mut proxies = proxies.to_vec();
+    proxies.shuffle(&mut rand::thread_rng());
+    proxies
+        .into_iter()
+        .min_by_key(|(_, latency)| latency.unwrap_or(time::Duration::MAX))
+        .map(|(proxy, _)| proxy)
+}
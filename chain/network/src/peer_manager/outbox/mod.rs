@@ -0,0 +1,195 @@
+//! Outbox of routed messages to validators, which were unreachable at the time of sending.
+//!
+//! Approvals and chunk part forwards are sent only once by the original node, so if the
+//! target validator is briefly disconnected, they are lost and the validator doesn't know
+//! to request them. Instead of dropping such messages, we store them (in memory and in the DB,
+//! so that they survive a restart) and re-send them once the target account becomes reachable,
+//! unless they expire first.
+use crate::config;
+use crate::network_protocol::RoutedMessageBody;
+use crate::stats::metrics;
+use crate::store;
+use near_primitives::time;
+use near_primitives::types::AccountId;
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    /// Identifies the entry in the DB. Increasing in the order in which the entries have
+    /// been pushed.
+    pub id: u64,
+    pub body: RoutedMessageBody,
+    pub enqueued_at: time::Utc,
+}
+
+/// Whether messages with the given body should be stored in the outbox,
+/// in case they cannot be delivered.
+pub(crate) fn is_eligible(body: &RoutedMessageBody) -> bool {
+    matches!(
+        body,
+        RoutedMessageBody::BlockApproval(_) | RoutedMessageBody::PartialEncodedChunkForward(_)
+    )
+}
+
+/// Changes of the outbox, which haven't been written to the DB yet.
+#[derive(Default)]
+struct Changes {
+    inserted: Vec<(AccountId, Entry)>,
+    removed: Vec<(AccountId, u64)>,
+}
+
+struct Inner {
+    messages: HashMap<AccountId, VecDeque<Entry>>,
+    next_id: u64,
+    changes: Changes,
+}
+
+impl Inner {
+    /// Drops the messages older than ttl.
+    fn remove_expired(&mut self, now: time::Utc, ttl: time::Duration) {
+        let removed = &mut self.changes.removed;
+        self.messages.retain(|account_id, entries| {
+            while let Some(e) = entries.front() {
+                if now - e.enqueued_at <= ttl {
+                    break;
+                }
+                metrics::ROUTED_MESSAGE_OUTBOX
+                    .with_label_values(&[(&e.body).into(), "expired"])
+                    .inc();
+                removed.push((account_id.clone(), e.id));
+                entries.pop_front();
+            }
+            !entries.is_empty()
+        });
+    }
+
+    fn len(&self) -> usize {
+        self.messages.values().map(|entries| entries.len()).sum()
+    }
+}
+
+/// Outbox of the routed messages, which couldn't be delivered to the target account.
+pub(crate) struct Outbox {
+    config: config::RoutedMessageOutbox,
+    inner: Mutex<Inner>,
+    /// Only the changes of the outbox are written to the DB, every entry under its own key.
+    /// The writes happen outside of the `inner` lock, but `store` is locked before `inner`
+    /// is unlocked, so that the changes are written in the order in which they were made.
+    store: Mutex<store::Store>,
+}
+
+impl Outbox {
+    /// Constructs the outbox, loading the messages stored in the DB by the previous neard run.
+    pub fn new(config: config::RoutedMessageOutbox, store: store::Store) -> Self {
+        let mut messages: HashMap<AccountId, VecDeque<Entry>> = HashMap::new();
+        let mut next_id = 0;
+        for (account_id, entry) in store.get_routed_message_outbox() {
+            next_id = next_id.max(entry.id + 1);
+            messages.entry(account_id).or_default().push_back(entry);
+        }
+        for entries in messages.values_mut() {
+            entries.make_contiguous().sort_by_key(|e| e.id);
+        }
+        Self {
+            config,
+            inner: Mutex::new(Inner { messages, next_id, changes: Changes::default() }),
+            store: Mutex::new(store),
+        }
+    }
+
+    /// Total number of messages in the outbox.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    /// Writes the pending changes of the outbox to the DB, releasing the `inner` lock first.
+    fn persist(&self, mut inner: MutexGuard<'_, Inner>) {
+        metrics::ROUTED_MESSAGE_OUTBOX_SIZE.set(inner.len() as i64);
+        let changes = std::mem::take(&mut inner.changes);
+        if changes.inserted.is_empty() && changes.removed.is_empty() {
+            return;
+        }
+        let mut store = self.store.lock();
+        drop(inner);
+        if let Err(err) = store.update_routed_message_outbox(&changes.inserted, &changes.removed) {
+            tracing::error!(target: "network", ?err, "Failed to save routed message outbox");
+        }
+    }
+
+    /// Stores a message which couldn't be delivered to `account_id`.
+    /// Returns false if the message has been dropped instead.
+    pub fn push(
+        &self,
+        clock: &time::Clock,
+        account_id: &AccountId,
+        body: RoutedMessageBody,
+    ) -> bool {
+        let now = clock.now_utc();
+        let mut inner = self.inner.lock();
+        inner.remove_expired(now, self.config.ttl);
+        if !inner.messages.contains_key(account_id)
+            && inner.messages.len() >= self.config.max_accounts
+        {
+            metrics::ROUTED_MESSAGE_OUTBOX.with_label_values(&[(&body).into(), "dropped"]).inc();
+            self.persist(inner);
+            return false;
+        }
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let inner_ref = &mut *inner;
+        let entries = inner_ref.messages.entry(account_id.clone()).or_default();
+        while entries.len() >= self.config.max_messages_per_account {
+            match entries.pop_front() {
+                Some(e) => {
+                    metrics::ROUTED_MESSAGE_OUTBOX
+                        .with_label_values(&[(&e.body).into(), "evicted"])
+                        .inc();
+                    inner_ref.changes.removed.push((account_id.clone(), e.id));
+                }
+                None => break,
+            }
+        }
+        metrics::ROUTED_MESSAGE_OUTBOX.with_label_values(&[(&body).into(), "enqueued"]).inc();
+        let entry = Entry { id, body, enqueued_at: now };
+        inner_ref.changes.inserted.push((account_id.clone(), entry.clone()));
+        entries.push_back(entry);
+        self.persist(inner);
+        true
+    }
+
+    /// Tries to deliver the pending messages to accounts for which `is_reachable` holds.
+    /// `send` is called for every such message in the order in which they have been
+    /// pushed, and it should return whether the message has been sent.
+    /// Messages which were sent or expired are removed from the outbox.
+    pub fn flush(
+        &self,
+        clock: &time::Clock,
+        is_reachable: impl Fn(&AccountId) -> bool,
+        mut send: impl FnMut(&AccountId, &RoutedMessageBody) -> bool,
+    ) {
+        let mut inner = self.inner.lock();
+        inner.remove_expired(clock.now_utc(), self.config.ttl);
+        let inner_ref = &mut *inner;
+        let removed = &mut inner_ref.changes.removed;
+        for (account_id, entries) in &mut inner_ref.messages {
+            if !is_reachable(account_id) {
+                continue;
+            }
+            entries.retain(|e| {
+                if !send(account_id, &e.body) {
+                    return true;
+                }
+                metrics::ROUTED_MESSAGE_OUTBOX.with_label_values(&[(&e.body).into(), "sent"]).inc();
+                removed.push((account_id.clone(), e.id));
+                false
+            });
+        }
+        inner.messages.retain(|_, entries| !entries.is_empty());
+        self.persist(inner);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config;
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::RoutedMessageBody;
+use crate::peer_manager::outbox::Outbox;
+use crate::store;
+use crate::testonly::make_rng;
+use near_primitives::block::Approval;
+use near_primitives::time;
+use rand::Rng;
+
+fn make_config() -> config::RoutedMessageOutbox {
+    config::RoutedMessageOutbox {
+        ttl: time::Duration::seconds(10),
+        max_messages_per_account: 3,
+        max_accounts: 2,
+    }
+}
+
+fn make_approval<R: Rng>(rng: &mut R, target_height: u64) -> RoutedMessageBody {
+    let signer = data::make_validator_signer(rng);
+    RoutedMessageBody::BlockApproval(Approval::new(
+        data::make_hash(rng),
+        target_height - 1,
+        target_height,
+        &signer,
+    ))
+}
+
+/// Flushes the outbox, assuming all accounts are reachable and all sends succeed.
+fn flush_all(clock: &time::Clock, outbox: &Outbox) -> Vec<RoutedMessageBody> {
+    let mut sent = vec![];
+    outbox.flush(
+        clock,
+        |_| true,
+        |_, body| {
+            sent.push(body.clone());
+            true
+        },
+    );
+    sent
+}
+
+#[test]
+fn test_flush_reachable_only() {
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let outbox = Outbox::new(make_config(), store::Store::from(near_store::db::TestDB::new()));
+
+    let reachable = data::make_account_id(rng);
+    let unreachable = data::make_account_id(rng);
+    let msg1 = make_approval(rng, 10);
+    let msg2 = make_approval(rng, 11);
+    assert!(outbox.push(&clock.clock(), &reachable, msg1.clone()));
+    assert!(outbox.push(&clock.clock(), &unreachable, msg2.clone()));
+
+    let mut sent = vec![];
+    outbox.flush(
+        &clock.clock(),
+        |a| a == &reachable,
+        |_, body| {
+            sent.push(body.clone());
+            true
+        },
+    );
+    assert_eq!(sent, vec![msg1]);
+    assert_eq!(outbox.len(), 1);
+
+    tracing::debug!(target:"test", "failed sends should stay in the outbox");
+    outbox.flush(&clock.clock(), |_| true, |_, _| false);
+    assert_eq!(outbox.len(), 1);
+    assert_eq!(flush_all(&clock.clock(), &outbox), vec![msg2]);
+    assert_eq!(outbox.len(), 0);
+}
+
+#[test]
+fn test_ttl_and_limits() {
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let cfg = make_config();
+    let outbox = Outbox::new(cfg.clone(), store::Store::from(near_store::db::TestDB::new()));
+
+    tracing::debug!(target:"test", "oldest messages are evicted once the per account limit is reached");
+    let account = data::make_account_id(rng);
+    let msgs: Vec<_> = (10..15).map(|h| make_approval(rng, h)).collect();
+    for msg in &msgs {
+        assert!(outbox.push(&clock.clock(), &account, msg.clone()));
+    }
+    assert_eq!(outbox.len(), cfg.max_messages_per_account);
+
+    tracing::debug!(target:"test", "messages to new accounts are dropped once the account limit is reached");
+    assert!(outbox.push(&clock.clock(), &data::make_account_id(rng), make_approval(rng, 20)));
+    assert!(!outbox.push(&clock.clock(), &data::make_account_id(rng), make_approval(rng, 21)));
+
+    tracing::debug!(target:"test", "expired messages are not sent");
+    clock.advance(cfg.ttl + time::Duration::seconds(1));
+    assert_eq!(flush_all(&clock.clock(), &outbox), vec![]);
+    assert_eq!(outbox.len(), 0);
+}
+
+#[test]
+fn test_reload_from_storage() {
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let store = store::Store::from(near_store::db::TestDB::new());
+
+    let account = data::make_account_id(rng);
+    let msg = make_approval(rng, 10);
+    {
+        tracing::debug!(target:"test", "write a message to storage");
+        let outbox = Outbox::new(make_config(), store.clone());
+        assert!(outbox.push(&clock.clock(), &account, msg.clone()));
+    }
+    {
+        tracing::debug!(target:"test", "read the message from storage");
+        let outbox = Outbox::new(make_config(), store);
+        let mut sent = vec![];
+        outbox.flush(
+            &clock.clock(),
+            |_| true,
+            |a, body| {
+                sent.push((a.clone(), body.clone()));
+                true
+            },
+        );
+        assert_eq!(sent, vec![(account, msg)]);
+    }
+}
+
+#[test]
+fn test_storage_tracks_changes() {
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let clock = time::FakeClock::default();
+    let cfg = make_config();
+    let store = store::Store::from(near_store::db::TestDB::new());
+    let outbox = Outbox::new(cfg.clone(), store.clone());
+
+    let account = data::make_account_id(rng);
+    let msgs: Vec<_> =
+        (0..cfg.max_messages_per_account + 1).map(|i| make_approval(rng, 10 + i as u64)).collect();
+    for msg in &msgs {
+        assert!(outbox.push(&clock.clock(), &account, msg.clone()));
+    }
+    tracing::debug!(target:"test", "the evicted message should be removed from storage");
+    let mut stored = store.get_routed_message_outbox();
+    stored.sort_by_key(|(_, e)| e.id);
+    let stored: Vec<_> = stored.into_iter().map(|(a, e)| (a, e.body)).collect();
+    let want: Vec<_> = msgs[1..].iter().map(|msg| (account.clone(), msg.clone())).collect();
+    assert_eq!(stored, want);
+
+    tracing::debug!(target:"test", "sent messages should be removed from storage");
+    assert_eq!(flush_all(&clock.clock(), &outbox), msgs[1..].to_vec());
+    assert_eq!(store.get_routed_message_outbox(), vec![]);
+}
@@ -1,35 +1,39 @@
 use crate::client;
 use crate::config;
-use crate::debug::{DebugStatus, GetDebugStatus};
+use crate::debug::{DebugStatus, GetDebugStatus, PingPeers};
 use crate::network_protocol::{
     AccountOrPeerIdOrHash, Disconnect, Edge, PeerIdOrHash, PeerMessage, Ping, Pong,
-    RawRoutedMessage, RoutedMessageBody,
+    RawRoutedMessage, RoutedMessageBody, SignedKeyTransition,
 };
-use crate::peer::peer_actor::PeerActor;
+use crate::peer::peer_actor::{ClosingReason, ConnectionClosedEvent, PeerActor};
 use crate::peer_manager::connection;
-use crate::peer_manager::network_state::{NetworkState, WhitelistNode};
+use crate::peer_manager::network_state::{
+    NetworkState, WhitelistNode, ACCOUNTS_DATA_FULL_SYNC_INTERVAL, NEXT_EPOCH_CONNECT_INTERVAL,
+};
 use crate::peer_manager::peer_store;
+use crate::private_actix::RegisterPeerError;
 use crate::shards_manager::ShardsManagerRequestFromNetwork;
 use crate::stats::metrics;
 use crate::store;
 use crate::tcp;
 use crate::types::{
-    ConnectedPeerInfo, HighestHeightPeerInfo, KnownProducer, NetworkInfo, NetworkRequests,
-    NetworkResponses, PeerInfo, PeerManagerMessageRequest, PeerManagerMessageResponse, PeerType,
-    SetChainInfo,
+    AccountIdOrPeerTrackingShard, ConnectedPeerInfo, HighestHeightPeerInfo, KnownPeerState,
+    KnownProducer, NetworkInfo, NetworkRequests, NetworkResponses, PeerInfo,
+    PeerManagerMessageRequest, PeerManagerMessageResponse, PeerType, SetChainInfo,
 };
 use actix::fut::future::wrap_future;
-use actix::{Actor as _, AsyncContext as _};
+use actix::{Actor as _, ActorFutureExt as _, AsyncContext as _};
 use anyhow::Context as _;
 use near_async::messaging::Sender;
 use near_o11y::{handler_debug_span, handler_trace_span, OpenTelemetrySpanExt, WithSpanContext};
 use near_performance_metrics_macros::perf;
 use near_primitives::block::GenesisId;
 use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::sharding::ChunkHash;
 use near_primitives::time;
 use near_primitives::views::{
-    ConnectionInfoView, EdgeView, KnownPeerStateView, NetworkGraphView, PeerStoreView,
-    RecentOutboundConnectionsView,
+    BlockPropagationView, ConnectionInfoView, EdgeView, KnownPeerStateView, NetworkGraphView,
+    PeerStoreView, PingView, RecentOutboundConnectionsView, RoutedMessageTraceView,
 };
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
@@ -82,6 +86,21 @@ pub(crate) const UPDATE_CONNECTION_STORE_INTERVAL: time::Duration = time::Durati
 /// How often to poll the NetworkState for closed connections we'd like to re-establish.
 pub(crate) const POLL_CONNECTION_STORE_INTERVAL: time::Duration = time::Duration::minutes(1);
 
+/// How often to store the edges of the routing graph, to warm-start it after a restart.
+pub(crate) const PERSIST_ROUTING_EDGES_INTERVAL: time::Duration = time::Duration::minutes(5);
+
+/// How often to re-resolve the hostnames of the boot nodes given by hostname.
+const RESOLVE_BOOT_NODE_HOSTS_INTERVAL: time::Duration = time::Duration::minutes(5);
+
+/// How often to try to deliver the messages from the outbox.
+/// The outbox is also flushed every time the routing table is updated.
+pub(crate) const FLUSH_OUTBOX_INTERVAL: time::Duration = time::Duration::seconds(1);
+
+/// After closing all connections on the operator's request, for how long to wait for them to be
+/// unregistered before dialing new peers, and how often to check.
+const RECONNECT_ALL_TIMEOUT: time::Duration = time::Duration::seconds(5);
+const RECONNECT_ALL_POLL_INTERVAL: time::Duration = time::Duration::milliseconds(100);
+
 /// Actor that manages peers connections.
 pub struct PeerManagerActor {
     pub(crate) clock: time::Clock,
@@ -89,6 +108,8 @@ pub struct PeerManagerActor {
     my_peer_id: PeerId,
     /// Flag that track whether we started attempts to establish outbound connections.
     started_connect_attempts: bool,
+    /// When the hostnames of the boot nodes were last resolved.
+    boot_node_hosts_resolved_at: time::Instant,
 
     /// State that is shared between multiple threads (including PeerActors).
     pub(crate) state: Arc<NetworkState>,
@@ -99,13 +120,15 @@ pub struct PeerManagerActor {
 /// Ideally the tests should observe only public API properties, but until
 /// we are at that stage, feel free to add any events that you need to observe.
 /// In particular prefer emitting a new event to polling for a state change.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, strum::EnumDiscriminants)]
+#[strum_discriminants(name(EventKind), derive(Hash))]
 pub enum Event {
     PeerManagerStarted,
     ServerStarted,
     RoutedMessageDropped,
     AccountsAdded(Vec<AnnounceAccount>),
     EdgesAdded(Vec<Edge>),
+    KeyTransitionsAdded(Vec<SignedKeyTransition>),
     Ping(Ping),
     Pong(Pong),
     // Reported once a message has been processed.
@@ -177,10 +200,37 @@ impl actix::Actor for PeerManagerActor {
             }
         }));
 
+        // Periodically store the edges of the routing graph.
+        let clock = self.clock.clone();
+        let state = self.state.clone();
+        ctx.spawn(wrap_future(async move {
+            let mut interval = time::Interval::new(
+                clock.now() + PERSIST_ROUTING_EDGES_INTERVAL,
+                PERSIST_ROUTING_EDGES_INTERVAL,
+            );
+            loop {
+                interval.tick(&clock).await;
+                state.graph.persist().await;
+            }
+        }));
+
+        // Periodically try to deliver the messages from the outbox.
+        if self.state.outbox.is_some() {
+            let clock = self.clock.clone();
+            let state = self.state.clone();
+            ctx.spawn(wrap_future(async move {
+                let mut interval = time::Interval::new(clock.now(), FLUSH_OUTBOX_INTERVAL);
+                loop {
+                    interval.tick(&clock).await;
+                    state.flush_outbox(&clock);
+                }
+            }));
+        }
+
         // Periodically prints bandwidth stats for each peer.
         self.report_bandwidth_stats_trigger(ctx, REPORT_BANDWIDTH_STATS_TRIGGER_INTERVAL);
 
-        self.state.config.event_sink.push(Event::PeerManagerStarted);
+        self.state.config.push_event(Event::PeerManagerStarted);
     }
 
     /// Try to gracefully disconnect from connected peers.
@@ -193,6 +243,7 @@ impl actix::Actor for PeerManagerActor {
     }
 
     fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.state.peer_arbiters.stop();
         actix::Arbiter::current().stop();
     }
 }
@@ -208,21 +259,23 @@ impl PeerManagerActor {
     ) -> anyhow::Result<actix::Addr<Self>> {
         let config = config.verify().context("config")?;
         let store = store::Store::from(store);
-        let peer_store = peer_store::PeerStore::new(&clock, config.peer_store.clone())
-            .context("PeerStore::new")?;
+        let peer_store =
+            peer_store::PeerStore::new(&clock, config.peer_store.clone(), store.clone())
+                .context("PeerStore::new")?;
+        if let Some(key_transition) = &config.key_transition {
+            peer_store.add_key_transition(key_transition.clone());
+        }
         tracing::debug!(target: "network",
                len = peer_store.len(),
                boot_nodes = config.peer_store.boot_nodes.len(),
                banned = peer_store.count_banned(),
                "Found known peers");
-        tracing::debug!(target: "network", blacklist = ?config.peer_store.blacklist, "Blacklist");
-        let whitelist_nodes = {
-            let mut v = vec![];
-            for wn in &config.whitelist_nodes {
-                v.push(WhitelistNode::from_peer_info(wn)?);
-            }
-            v
-        };
+        tracing::debug!(target: "network",
+               blacklist = ?config.peer_store.blacklist,
+               allowlist = ?config.peer_store.allowlist,
+               "Blacklist");
+        let whitelist_nodes =
+            config.whitelist_nodes.iter().map(WhitelistNode::from_peer_info).collect();
         let my_peer_id = config.node_id();
         let arbiter = actix::Arbiter::new().handle();
         let clock = clock;
@@ -236,36 +289,81 @@ impl PeerManagerActor {
             shards_manager_adapter,
             whitelist_nodes,
         ));
+        state.graph.warm_start(&clock);
         arbiter.spawn({
             let arbiter = arbiter.clone();
             let state = state.clone();
             let clock = clock.clone();
             async move {
-                // Start server if address provided.
-                if let Some(server_addr) = &state.config.node_addr {
+                // Start a server for every address provided.
+                let server_addrs: Vec<_> = state.config.listen_addrs().collect();
+                let mut listeners = vec![];
+                for server_addr in &server_addrs {
                     tracing::debug!(target: "network", at = ?server_addr, "starting public server");
-                    let mut listener = match server_addr.listener() {
-                        Ok(it) => it,
+                    // An IPv6 listener sharing the port with an IPv4 listener has to be
+                    // IPv6-only, otherwise the two would conflict.
+                    let v6_only = server_addr.is_ipv6()
+                        && server_addrs
+                            .iter()
+                            .any(|a| a.is_ipv4() && a.port() == server_addr.port());
+                    let listener = if v6_only {
+                        server_addr.v6_only_listener()
+                    } else {
+                        server_addr.listener()
+                    };
+                    match listener {
+                        Ok(it) => listeners.push(it),
                         Err(e) => {
                             panic!("failed to start listening on server_addr={server_addr:?} e={e:?}")
                         }
-                    };
-                    state.config.event_sink.push(Event::ServerStarted);
+                    }
+                }
+                if !listeners.is_empty() {
+                    state.config.push_event(Event::ServerStarted);
+                }
+                for mut listener in listeners {
                     arbiter.spawn({
                         let clock = clock.clone();
                         let state = state.clone();
                         async move {
                             loop {
                                 if let Ok(stream) = listener.accept().await {
+                                    // Drop the connections from the blacklisted IPs right away.
+                                    if !state.peer_store.is_allowed_ip(stream.peer_addr.ip()) {
+                                        tracing::debug!(target: "network", from = ?stream.peer_addr, "dropping connection from blacklisted IP");
+                                        state.config.push_event(Event::ConnectionClosed(
+                                            ConnectionClosedEvent {
+                                                stream_id: stream.id(),
+                                                reason: ClosingReason::RejectedByPeerManager(
+                                                    RegisterPeerError::Blacklisted,
+                                                ),
+                                            },
+                                        ));
+                                        continue;
+                                    }
+                                    let ip_permit = match state.try_acquire_inbound_ip_permit(
+                                        clock.now(),
+                                        stream.peer_addr.ip(),
+                                    ) {
+                                        Ok(permit) => permit,
+                                        Err(err) => {
+                                            tracing::debug!(target: "network", from = ?stream.peer_addr, %err, "dropping connection");
+                                            continue;
+                                        }
+                                    };
                                     // Always let the new peer to send a handshake message.
                                     // Only then we can decide whether we should accept a connection.
                                     // It is expected to be reasonably cheap: eventually, for TIER2 network
                                     // we would like to exchange set of connected peers even without establishing
                                     // a proper connection.
                                     tracing::debug!(target: "network", from = ?stream.peer_addr, "got new connection");
-                                    if let Err(err) =
-                                        PeerActor::spawn(clock.clone(), stream, None, state.clone())
-                                    {
+                                    if let Err(err) = PeerActor::spawn(
+                                        clock.clone(),
+                                        stream,
+                                        None,
+                                        ip_permit,
+                                        state.clone(),
+                                    ) {
                                         tracing::info!(target:"network", ?err, "PeerActor::spawn()");
                                     }
                                 }
@@ -273,6 +371,18 @@ impl PeerManagerActor {
                         }
                     });
                 }
+                // Sync all the accounts data with the direct peers periodically.
+                arbiter.spawn({
+                    let clock = clock.clone();
+                    let state = state.clone();
+                    let mut interval = time::Interval::new(clock.now(), ACCOUNTS_DATA_FULL_SYNC_INTERVAL);
+                    async move {
+                        loop {
+                            interval.tick(&clock).await;
+                            state.accounts_data_full_sync();
+                        }
+                    }
+                });
                 if let Some(cfg) = state.config.tier1.clone() {
                     // Connect to TIER1 proxies and broadcast the list those connections periodically.
                     arbiter.spawn({
@@ -301,6 +411,20 @@ impl PeerManagerActor {
                         }
                     });
                 }
+                if state.config.validator.is_some() && !state.config.outbound_disabled {
+                    // Pre-connect to the validators of the next epoch periodically.
+                    arbiter.spawn({
+                        let clock = clock.clone();
+                        let state = state.clone();
+                        let mut interval = time::Interval::new(clock.now(), NEXT_EPOCH_CONNECT_INTERVAL);
+                        async move {
+                            loop {
+                                interval.tick(&clock).await;
+                                state.next_epoch_connect(&clock).await;
+                            }
+                        }
+                    });
+                }
                 // Periodically poll the connection store for connections we'd like to re-establish
                 arbiter.spawn({
                     let clock = clock.clone();
@@ -323,7 +447,7 @@ impl PeerManagerActor {
                                     }
                                 });
 
-                                state.config.event_sink.push(Event::ReconnectLoopSpawned(peer_info));
+                                state.config.push_event(Event::ReconnectLoopSpawned(peer_info));
                             }
                         }
                     }
@@ -333,6 +457,8 @@ impl PeerManagerActor {
         Ok(Self::start_in_arbiter(&arbiter, move |_ctx| Self {
             my_peer_id: my_peer_id.clone(),
             started_connect_attempts: false,
+            // The hostnames have just been resolved, when parsing the config.
+            boot_node_hosts_resolved_at: clock.now(),
             state,
             clock,
         }))
@@ -393,7 +519,7 @@ impl PeerManagerActor {
                 + tier2.outbound_handshakes.len();
 
         (total_connections < self.state.config.ideal_connections_lo as usize
-            || (total_connections < self.state.config.max_num_peers as usize
+            || (total_connections < self.state.max_num_peers() as usize
                 && potential_outbound_connections
                     < self.state.config.minimum_outbound_peers as usize))
             && !self.state.config.outbound_disabled
@@ -482,7 +608,7 @@ impl PeerManagerActor {
         let mut safe_set = HashSet::new();
 
         // Add whitelisted nodes to the safe set.
-        let whitelisted_peers = filter_peers(&|p| self.state.is_peer_whitelisted(&p.peer_info));
+        let whitelisted_peers = filter_peers(&|p| self.state.is_peer_whitelisted(p));
         safe_set.extend(whitelisted_peers);
 
         // If there is not enough non-whitelisted peers, return without disconnecting anyone.
@@ -490,6 +616,10 @@ impl PeerManagerActor {
             return;
         }
 
+        // Keep the connections to the validators of the next epoch,
+        // which have been established in advance.
+        safe_set.extend(self.state.next_epoch_validator_peers());
+
         // If there is not enough outbound peers, add them to the safe set.
         let outbound_peers = filter_peers(&|p| p.peer_type == PeerType::Outbound);
         if outbound_peers.len() + tier2.outbound_handshakes.len()
@@ -566,45 +696,25 @@ impl PeerManagerActor {
             metrics::PEER_MANAGER_TRIGGER_TIME.with_label_values(&["monitor_peers"]).start_timer();
 
         self.state.peer_store.update(&self.clock);
+        self.state.dialer.update(&self.clock);
+
+        let now = self.clock.now();
+        if !self.state.peer_store.boot_node_hosts().is_empty()
+            && now - self.boot_node_hosts_resolved_at >= RESOLVE_BOOT_NODE_HOSTS_INTERVAL
+        {
+            self.boot_node_hosts_resolved_at = now;
+            let state = self.state.clone();
+            let clock = self.clock.clone();
+            ctx.spawn(wrap_future(async move { state.resolve_boot_node_hosts(&clock).await }));
+        }
 
         if self.is_outbound_bootstrap_needed() {
-            let tier2 = self.state.tier2.load();
-            // With some odds - try picking one of the 'NotConnected' peers -- these are the ones that we were able to connect to in the past.
-            let prefer_previously_connected_peer =
-                thread_rng().gen_bool(PREFER_PREVIOUSLY_CONNECTED_PEER);
-            if let Some(peer_info) = self.state.peer_store.unconnected_peer(
-                |peer_state| {
-                    // Ignore connecting to ourself
-                    self.my_peer_id == peer_state.peer_info.id
-                    || self.state.config.node_addr.as_ref().map(|a|**a) == peer_state.peer_info.addr
-                    // Or to peers we are currently trying to connect to
-                    || tier2.outbound_handshakes.contains(&peer_state.peer_info.id)
-                },
-                prefer_previously_connected_peer,
-            ) {
-                // Start monitor_peers_attempts from start after we discover the first healthy peer
-                if !self.started_connect_attempts {
-                    self.started_connect_attempts = true;
-                    interval = default_interval;
-                }
-                ctx.spawn(wrap_future({
-                    let state = self.state.clone();
-                    let clock = self.clock.clone();
-                    async move {
-                        let result = async {
-                            let stream = tcp::Stream::connect(&peer_info, tcp::Tier::T2).await.context("tcp::Stream::connect()")?;
-                            PeerActor::spawn_and_handshake(clock.clone(),stream,None,state.clone()).await.context("PeerActor::spawn()")?;
-                            anyhow::Ok(())
-                        }.await;
-
-                        if result.is_err() {
-                            tracing::info!(target:"network", ?result, "failed to connect to {peer_info}");
-                        }
-                        if state.peer_store.peer_connection_attempt(&clock, &peer_info.id, result).is_err() {
-                            tracing::error!(target: "network", ?peer_info, "Failed to store connection attempt.");
-                        }
-                    }.instrument(tracing::trace_span!(target: "network", "monitor_peers_trigger_connect"))
-                }));
+            // Start monitor_peers_attempts from start after we discover the first healthy peer
+            if self.connect_to_unconnected_peer(ctx, &HashSet::new()).is_some()
+                && !self.started_connect_attempts
+            {
+                self.started_connect_attempts = true;
+                interval = default_interval;
             }
         }
 
@@ -627,6 +737,95 @@ impl PeerManagerActor {
         );
     }
 
+    /// Picks a random peer from the peer store, which we are neither connected nor trying to
+    /// connect to, and dials it. Returns the dialed peer, if any.
+    fn connect_to_unconnected_peer(
+        &self,
+        ctx: &mut actix::Context<Self>,
+        exclude: &HashSet<PeerId>,
+    ) -> Option<PeerId> {
+        let tier2 = self.state.tier2.load();
+        // With some odds - try picking one of the 'NotConnected' peers -- these are the ones that we were able to connect to in the past.
+        let prefer_previously_connected_peer =
+            thread_rng().gen_bool(PREFER_PREVIOUSLY_CONNECTED_PEER);
+        let ignore_fn = |peer_state: &KnownPeerState| {
+            // Ignore connecting to ourself
+            self.my_peer_id == peer_state.peer_info.id
+                || self.state.config.listen_addrs().any(|a| Some(**a) == peer_state.peer_info.addr)
+                // Or to peers we are currently trying to connect to
+                || tier2.outbound_handshakes.contains(&peer_state.peer_info.id)
+                // Or to peers we have recently failed to connect to
+                || self.state.dialer.is_backing_off(&self.clock, &peer_state.peer_info.id)
+                // Or to peers the caller has already dialed
+                || exclude.contains(&peer_state.peer_info.id)
+        };
+        let peer_info = self
+            .state
+            .peer_store
+            .unconnected_peer(ignore_fn, prefer_previously_connected_peer)
+            .filter(|_| self.state.dialer.try_start_attempt(&self.clock))?;
+        let peer_id = peer_info.id.clone();
+        ctx.spawn(wrap_future({
+            let state = self.state.clone();
+            let clock = self.clock.clone();
+            async move {
+                let result = async {
+                    let stream = tcp::Stream::connect(&peer_info, tcp::Tier::T2, state.config.socks5_proxy, state.config.tcp_connect_timeout).await.context("tcp::Stream::connect()")?;
+                    PeerActor::spawn_and_handshake(clock.clone(),stream,None,state.clone()).await.context("PeerActor::spawn()")?;
+                    anyhow::Ok(())
+                }.await;
+
+                if result.is_err() {
+                    tracing::info!(target:"network", ?result, "failed to connect to {peer_info}");
+                }
+                state.dialer.record_attempt(&clock, &peer_info.id, &result);
+                if state.peer_store.peer_connection_attempt(&clock, &peer_info.id, result).is_err() {
+                    tracing::error!(target: "network", ?peer_info, "Failed to store connection attempt.");
+                }
+            }.instrument(tracing::trace_span!(target: "network", "monitor_peers_trigger_connect"))
+        }));
+        Some(peer_id)
+    }
+
+    /// Dials new peers until the desired number of connections is reached (counting the dialed
+    /// ones) or the dialer runs out of the connection attempts.
+    fn bootstrap_outbound(&mut self, ctx: &mut actix::Context<Self>) {
+        let mut dialed = HashSet::new();
+        while self.is_outbound_bootstrap_needed()
+            && dialed.len() < self.state.config.ideal_connections_lo as usize
+        {
+            match self.connect_to_unconnected_peer(ctx, &dialed) {
+                Some(peer_id) => dialed.insert(peer_id),
+                None => break,
+            };
+        }
+    }
+
+    /// Closes all the TIER2 (and, if `tier1` is set, TIER1) connections on the node operator's
+    /// request. Once they are unregistered, new peers are dialed right away rather than at the
+    /// next monitor_peers_trigger. TIER1 connections are re-established by the TIER1 daemon.
+    /// Returns the number of closed connections.
+    fn reconnect_all(&mut self, ctx: &mut actix::Context<Self>, tier1: bool) -> usize {
+        let conns = self.state.disconnect_all_by_operator(tier1);
+        let closed = conns.len();
+        let state = self.state.clone();
+        let clock = self.clock.clone();
+        ctx.spawn(
+            wrap_future(async move {
+                // The connections are unregistered asynchronously.
+                let deadline = clock.now() + RECONNECT_ALL_TIMEOUT;
+                while clock.now() < deadline && conns.iter().any(|conn| state.is_registered(conn)) {
+                    clock.sleep(RECONNECT_ALL_POLL_INTERVAL).await;
+                }
+            })
+            .then(|(), act: &mut Self, ctx| {
+                act.bootstrap_outbound(ctx);
+                actix::fut::ready(())
+            }),
+        );
+        closed
+    }
+
     /// Re-establish each outbound connection in the connection store (single attempt)
     fn bootstrap_outbound_from_recent_connections(&self, ctx: &mut actix::Context<Self>) {
         for conn_info in self.state.connection_store.get_recent_outbound_connections() {
@@ -639,10 +838,95 @@ impl PeerManagerActor {
                 }
             }));
 
-            self.state
-                .config
-                .event_sink
-                .push(Event::ReconnectLoopSpawned(conn_info.peer_info.clone()));
+            self.state.config.push_event(Event::ReconnectLoopSpawned(conn_info.peer_info.clone()));
+        }
+    }
+
+    /// Sends a chunk request to the target account or, depending on `target.prefer_peer`, to a
+    /// random peer tracking the shard. Falls back to the other option if the first one fails.
+    fn send_chunk_request(
+        &self,
+        target: &AccountIdOrPeerTrackingShard,
+        chunk_hash: &ChunkHash,
+        body: RoutedMessageBody,
+    ) -> NetworkResponses {
+        let mut success = false;
+        // The nodes which don't support the full chunk requests fail to parse them, so these are
+        // sent only directly to the peers which advertised the support. Otherwise the parts listed
+        // in the request are requested instead.
+        let fallback_body = match &body {
+            RoutedMessageBody::FullChunkRequest(request) => {
+                RoutedMessageBody::PartialEncodedChunkRequest(request.clone())
+            }
+            body => body.clone(),
+        };
+
+        // Make two attempts to send the message. First following the preference of `prefer_peer`,
+        // and if it fails, against the preference.
+        for prefer_peer in &[target.prefer_peer, !target.prefer_peer] {
+            if !prefer_peer {
+                if let Some(account_id) = target.account_id.as_ref() {
+                    // The message to an account may be routed through multiple hops.
+                    if self.state.send_message_to_account(
+                        &self.clock,
+                        account_id,
+                        fallback_body.clone(),
+                    ) {
+                        success = true;
+                        break;
+                    }
+                }
+            } else {
+                let mut matching_peers = vec![];
+                for peer in self.state.tier2.load().ready.values() {
+                    let last_block = peer.last_block.load();
+                    if (peer.archival || !target.only_archival)
+                        && last_block.is_some()
+                        && last_block.as_ref().unwrap().height >= target.min_height
+                        && peer.tracked_shards.contains(&target.shard_id)
+                    {
+                        matching_peers.push(peer.clone());
+                    }
+                }
+
+                // Out of two random matching peers pick the one with the lower RTT: the requests
+                // favor the faster peers, but the load is still spread among all of them.
+                let matching_peer = matching_peers
+                    .iter()
+                    .choose_multiple(&mut thread_rng(), 2)
+                    .into_iter()
+                    .min_by_key(|peer| peer.rtt().unwrap_or(time::Duration::MAX));
+                if let Some(matching_peer) = matching_peer {
+                    let body = if matching_peer.supports_full_chunk_requests {
+                        body.clone()
+                    } else {
+                        fallback_body.clone()
+                    };
+                    if self.state.send_message_to_peer(
+                        &self.clock,
+                        tcp::Tier::T2,
+                        self.state.sign_message(
+                            &self.clock,
+                            RawRoutedMessage {
+                                target: PeerIdOrHash::PeerId(matching_peer.peer_info.id.clone()),
+                                body,
+                            },
+                        ),
+                    ) {
+                        success = true;
+                        break;
+                    }
+                } else {
+                    tracing::debug!(target: "network", ?chunk_hash, "Failed to find any matching peer for chunk");
+                }
+            }
+        }
+
+        if success {
+            NetworkResponses::NoResponse
+        } else {
+            tracing::debug!(target: "network", ?chunk_hash, "Failed to find a route for chunk");
+            NetworkResponses::RouteNotFound
         }
     }
 
@@ -684,12 +968,13 @@ impl PeerManagerActor {
                 Some(e) => e.nonce(),
                 None => 0,
             },
+            traffic_by_message_type: cp.stats.traffic_by_message_type.lock().clone(),
         };
         NetworkInfo {
             connected_peers: tier2.ready.values().map(connected_peer).collect(),
             tier1_connections: tier1.ready.values().map(connected_peer).collect(),
             num_connected_peers: tier2.ready.len(),
-            peer_max_count: self.state.config.max_num_peers,
+            peer_max_count: self.state.max_num_peers(),
             highest_height_peers: self.highest_height_peers(),
             sent_bytes_per_sec: tier2
                 .ready
@@ -727,6 +1012,9 @@ impl PeerManagerActor {
             .with_label_values(&["push_network_info"])
             .start_timer();
         // TODO(gprusak): just spawn a loop.
+        self.state
+            .shards_manager_adapter
+            .send(ShardsManagerRequestFromNetwork::UpdateAccountRtts(self.state.account_rtts()));
         let state = self.state.clone();
         ctx.spawn(wrap_future(
             async move { state.client.network_info(network_info).await }.instrument(
@@ -838,67 +1126,41 @@ impl PeerManagerActor {
             NetworkRequests::PartialEncodedChunkRequest { target, request, create_time } => {
                 metrics::PARTIAL_ENCODED_CHUNK_REQUEST_DELAY
                     .observe((self.clock.now() - create_time.0).as_seconds_f64());
-                let mut success = false;
-
-                // Make two attempts to send the message. First following the preference of `prefer_peer`,
-                // and if it fails, against the preference.
-                for prefer_peer in &[target.prefer_peer, !target.prefer_peer] {
-                    if !prefer_peer {
-                        if let Some(account_id) = target.account_id.as_ref() {
-                            if self.state.send_message_to_account(
-                                &self.clock,
-                                account_id,
-                                RoutedMessageBody::PartialEncodedChunkRequest(request.clone()),
-                            ) {
-                                success = true;
-                                break;
-                            }
-                        }
-                    } else {
-                        let mut matching_peers = vec![];
-                        for (peer_id, peer) in &self.state.tier2.load().ready {
-                            let last_block = peer.last_block.load();
-                            if (peer.archival || !target.only_archival)
-                                && last_block.is_some()
-                                && last_block.as_ref().unwrap().height >= target.min_height
-                                && peer.tracked_shards.contains(&target.shard_id)
-                            {
-                                matching_peers.push(peer_id.clone());
-                            }
-                        }
-
-                        if let Some(matching_peer) = matching_peers.iter().choose(&mut thread_rng())
-                        {
-                            if self.state.send_message_to_peer(
-                                &self.clock,
-                                tcp::Tier::T2,
-                                self.state.sign_message(
-                                    &self.clock,
-                                    RawRoutedMessage {
-                                        target: PeerIdOrHash::PeerId(matching_peer.clone()),
-                                        body: RoutedMessageBody::PartialEncodedChunkRequest(
-                                            request.clone(),
-                                        ),
-                                    },
-                                ),
-                            ) {
-                                success = true;
-                                break;
-                            }
-                        } else {
-                            tracing::debug!(target: "network", chunk_hash=?request.chunk_hash, "Failed to find any matching peer for chunk");
-                        }
-                    }
-                }
-
-                if success {
+                let chunk_hash = request.chunk_hash.clone();
+                self.send_chunk_request(
+                    &target,
+                    &chunk_hash,
+                    RoutedMessageBody::PartialEncodedChunkRequest(request),
+                )
+            }
+            NetworkRequests::FullChunkRequest { target, request, create_time } => {
+                metrics::PARTIAL_ENCODED_CHUNK_REQUEST_DELAY
+                    .observe((self.clock.now() - create_time.0).as_seconds_f64());
+                let chunk_hash = request.chunk_hash.clone();
+                self.send_chunk_request(
+                    &target,
+                    &chunk_hash,
+                    RoutedMessageBody::FullChunkRequest(request),
+                )
+            }
+            NetworkRequests::PartialEncodedChunkResponse { route_back, response } => {
+                if self.state.send_message_to_peer(
+                    &self.clock,
+                    tcp::Tier::T2,
+                    self.state.sign_message(
+                        &self.clock,
+                        RawRoutedMessage {
+                            target: PeerIdOrHash::Hash(route_back),
+                            body: RoutedMessageBody::PartialEncodedChunkResponse(response),
+                        },
+                    ),
+                ) {
                     NetworkResponses::NoResponse
                 } else {
-                    tracing::debug!(target: "network", chunk_hash=?request.chunk_hash, "Failed to find a route for chunk");
                     NetworkResponses::RouteNotFound
                 }
             }
-            NetworkRequests::PartialEncodedChunkResponse { route_back, response } => {
+            NetworkRequests::FullChunkResponse { route_back, response } => {
                 if self.state.send_message_to_peer(
                     &self.clock,
                     tcp::Tier::T2,
@@ -906,7 +1168,7 @@ impl PeerManagerActor {
                         &self.clock,
                         RawRoutedMessage {
                             target: PeerIdOrHash::Hash(route_back),
-                            body: RoutedMessageBody::PartialEncodedChunkResponse(response),
+                            body: RoutedMessageBody::FullChunkResponse(response),
                         },
                     ),
                 ) {
@@ -981,12 +1243,78 @@ impl PeerManagerActor {
             PeerManagerMessageRequest::OutboundTcpConnect(stream) => {
                 let peer_addr = stream.peer_addr;
                 if let Err(err) =
-                    PeerActor::spawn(self.clock.clone(), stream, None, self.state.clone())
+                    PeerActor::spawn(self.clock.clone(), stream, None, None, self.state.clone())
                 {
                     tracing::info!(target:"network", ?err, ?peer_addr, "spawn_outbound()");
                 }
                 PeerManagerMessageResponse::OutboundTcpConnect
             }
+            PeerManagerMessageRequest::ReconnectAll { tier1 } => {
+                PeerManagerMessageResponse::ReconnectAll(self.reconnect_all(ctx, tier1))
+            }
+            PeerManagerMessageRequest::SetRecvLimits { tier1, tier2 } => {
+                if let Err(err) = tier1.validate().and_then(|()| tier2.validate()) {
+                    tracing::warn!(target: "network", ?err, ?tier1, ?tier2, "ignoring invalid receive rate limits");
+                    return PeerManagerMessageResponse::SetRecvLimits(false);
+                }
+                tracing::info!(target: "network", ?tier1, ?tier2, "updated receive rate limits");
+                self.state.recv_limits.set(tier1, tier2);
+                PeerManagerMessageResponse::SetRecvLimits(true)
+            }
+            PeerManagerMessageRequest::SetPeersConfig {
+                max_num_peers,
+                whitelist_nodes,
+                blacklist,
+                boot_nodes,
+            } => {
+                let parsed = (|| -> anyhow::Result<_> {
+                    anyhow::ensure!(
+                        self.state.config.ideal_connections_hi <= max_num_peers,
+                        "max_num_peers ({max_num_peers}) is lower than ideal_connections_hi ({})",
+                        self.state.config.ideal_connections_hi
+                    );
+                    let whitelist_nodes = config::parse_whitelist_nodes(&whitelist_nodes)?
+                        .iter()
+                        .map(WhitelistNode::from_peer_info)
+                        .collect::<Vec<_>>();
+                    let blacklist = config::parse_blacklist(&blacklist)?;
+                    let boot_nodes = config::parse_boot_nodes(&boot_nodes).context("boot_nodes")?;
+                    Ok((whitelist_nodes, blacklist, boot_nodes))
+                })();
+                let (whitelist_nodes, blacklist, (boot_nodes, boot_node_hosts)) = match parsed {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        tracing::warn!(target: "network", ?err, "ignoring invalid peers config");
+                        return PeerManagerMessageResponse::SetPeersConfig(false);
+                    }
+                };
+                tracing::info!(target: "network", max_num_peers, whitelist_nodes = whitelist_nodes.len(), boot_nodes = boot_nodes.len(), "updated peers config");
+                self.state.set_max_num_peers(max_num_peers);
+                self.state.set_whitelist_nodes(whitelist_nodes);
+                self.state.peer_store.set_blacklist(blacklist);
+                self.state.disconnect_disallowed_peers();
+                let resolve_hosts = !boot_node_hosts.is_empty();
+                self.state.peer_store.set_boot_nodes(&self.clock, boot_nodes, boot_node_hosts);
+                if resolve_hosts {
+                    self.boot_node_hosts_resolved_at = self.clock.now();
+                    let state = self.state.clone();
+                    let clock = self.clock.clone();
+                    ctx.spawn(wrap_future(
+                        async move { state.resolve_boot_node_hosts(&clock).await },
+                    ));
+                }
+                PeerManagerMessageResponse::SetPeersConfig(true)
+            }
+            PeerManagerMessageRequest::DisconnectPeer { peer_id, ban } => {
+                PeerManagerMessageResponse::DisconnectPeer(self.state.disconnect_by_operator(
+                    &self.clock,
+                    &peer_id,
+                    ban,
+                ))
+            }
+            PeerManagerMessageRequest::GetRouteInfo { target } => {
+                PeerManagerMessageResponse::GetRouteInfo(self.state.route_info(target))
+            }
             // TEST-ONLY
             PeerManagerMessageRequest::FetchRoutingTable => {
                 PeerManagerMessageResponse::FetchRoutingTable(self.state.graph.routing_table.info())
@@ -1005,7 +1333,7 @@ impl actix::Handler<WithSpanContext<SetChainInfo>> for PeerManagerActor {
         // synchronously, therefore, assuming actix in-order delivery,
         // there will be no race condition between subsequent SetChainInfo
         // calls.
-        if !self.state.set_chain_info(info) {
+        if !self.state.set_chain_info(&self.clock, info) {
             // We early exit in case the set of TIER1 account keys hasn't changed.
             return;
         }
@@ -1043,6 +1371,15 @@ impl actix::Handler<WithSpanContext<PeerManagerMessageRequest>> for PeerManagerA
     }
 }
 
+impl actix::Handler<PingPeers> for PeerManagerActor {
+    type Result = actix::ResponseFuture<PingView>;
+    fn handle(&mut self, msg: PingPeers, _ctx: &mut actix::Context<Self>) -> Self::Result {
+        let state = self.state.clone();
+        let clock = self.clock.clone();
+        Box::pin(async move { state.ping_peers(&clock, msg.targets, msg.timeout).await })
+    }
+}
+
 impl actix::Handler<GetDebugStatus> for PeerManagerActor {
     type Result = DebugStatus;
     fn handle(&mut self, msg: GetDebugStatus, _ctx: &mut actix::Context<Self>) -> Self::Result {
@@ -1108,6 +1445,24 @@ impl actix::Handler<GetDebugStatus> for PeerManagerActor {
                         .collect::<Vec<_>>(),
                 })
             }
+            GetDebugStatus::RoutedMessageTrace => {
+                DebugStatus::RoutedMessageTrace(match &self.state.routed_trace {
+                    Some(tracer) => tracer.view(),
+                    None => RoutedMessageTraceView { events: vec![] },
+                })
+            }
+            GetDebugStatus::Dialer => DebugStatus::Dialer(self.state.dialer.view(&self.clock)),
+            GetDebugStatus::BlockPropagation => {
+                DebugStatus::BlockPropagation(match &self.state.block_propagation {
+                    Some(recorder) => recorder.view(),
+                    None => BlockPropagationView {
+                        latency_p50_ms: None,
+                        latency_p90_ms: None,
+                        latency_p99_ms: None,
+                        blocks: vec![],
+                    },
+                })
+            }
         }
     }
 }
@@ -1,5 +1,7 @@
 use crate::blacklist;
-use crate::network_protocol::PeerInfo;
+use crate::network_protocol::{PeerInfo, SignedKeyTransition};
+use crate::store;
+use crate::tcp;
 use crate::types::{KnownPeerState, KnownPeerStatus, ReasonForBan};
 use anyhow::bail;
 use im::hashmap::Entry;
@@ -10,7 +12,7 @@ use near_primitives::time;
 use parking_lot::Mutex;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Not;
 
 #[cfg(test)]
@@ -24,8 +26,9 @@ mod tests;
 ///     - Respond to requests from other peers for known peers (see PeerStore::healthy_peers).
 ///     - Select peers to which we may try to connect directly (see PeerStore::unconnected_peer).
 ///
-/// Contents of the PeerStore are not persisted to the database. Upon starting a node,
-/// the PeerStore is initialized from the boot nodes in its config.
+/// Contents of the PeerStore are mostly not persisted to the database. Upon starting a node,
+/// the PeerStore is initialized from the boot nodes in its config and from the peers banned
+/// before the restart, whose bans haven't expired yet.
 
 /// Level of trust we have about a new (PeerId, Addr) pair.
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
@@ -60,8 +63,15 @@ pub struct Config {
     /// file, but you can modify the boot_nodes field to contain any nodes that
     /// you trust.
     pub boot_nodes: Vec<PeerInfo>,
+    /// The boot nodes given by a hostname rather than an IP address. Their addresses in
+    /// `boot_nodes` are the ones resolved at startup (if any), and are updated by re-resolving
+    /// the hostnames periodically, so that boot nodes with rotating IPs stay reachable.
+    pub boot_node_hosts: Vec<BootNodeHost>,
     /// Nodes will not accept or try to establish connection to such peers.
     pub blacklist: blacklist::Blacklist,
+    /// If set, nodes will accept and try to establish connections only to such peers
+    /// (and only if they are not on the blacklist).
+    pub allowlist: Option<blacklist::Blacklist>,
     /// If true - connect only to the bootnodes.
     pub connect_only_to_boot_nodes: bool,
     /// The maximum number of peers to store. If capacity is exceeded, the peers
@@ -71,11 +81,42 @@ pub struct Config {
     pub peer_expiration_duration: time::Duration,
     /// Duration of the ban for misbehaving peers.
     pub ban_window: time::Duration,
+    /// Duration of the ban for the specific reasons, overriding `ban_window`.
+    pub ban_window_by_reason: std::collections::HashMap<ReasonForBan, time::Duration>,
+}
+
+/// A boot node given by a hostname.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootNodeHost {
+    pub peer_id: PeerId,
+    /// Hostname and port, e.g. "boot.example.com:24567".
+    pub host: String,
+}
+
+impl Config {
+    /// Duration of the ban for the given reason.
+    pub fn ban_duration(&self, reason: ReasonForBan) -> time::Duration {
+        self.ban_window_by_reason.get(&reason).copied().unwrap_or(self.ban_window)
+    }
+
+    /// Whether the blacklist and the allowlist permit connections with the peer at `addr`.
+    fn is_allowed(&self, addr: SocketAddr) -> bool {
+        !self.blacklist.contains(addr) && self.allowlist.as_ref().map_or(true, |l| l.contains(addr))
+    }
+}
+
+/// A ban persisted in the DB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BannedPeer {
+    pub peer_info: PeerInfo,
+    pub reason: ReasonForBan,
+    pub ban_time: time::Utc,
 }
 
 /// Known peers store, maintaining cache of known peers
 struct Inner {
     config: Config,
+    store: store::Store,
     boot_nodes: HashSet<PeerId>,
     // LruCache of the known peer states. Be sure to use peek/peek_mut to access information.
     // Using the get/put methods modifies the cache order.
@@ -83,7 +124,12 @@ struct Inner {
     // This is a reverse index, from physical address to peer_id
     // It can happens that some peers don't have known address, so
     // they will not be present in this list, otherwise they will be present.
+    // A peer listening on multiple addresses (for example IPv4 and IPv6) may be
+    // present multiple times: once for its peer_info.addr and once for each other
+    // address it has advertised (see PeerStore::add_peer_addrs).
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
+    // The newest known key transition from each PeerId, keyed by the old PeerId.
+    key_transitions: LruCache<PeerId, SignedKeyTransition>,
 }
 
 impl Inner {
@@ -99,6 +145,7 @@ impl Inner {
 
     /// Adds a peer into the store with given trust level.
     fn add_peer(&mut self, clock: &time::Clock, peer_info: PeerInfo, trust_level: TrustLevel) {
+        let peer_info = PeerInfo { addr: peer_info.addr.map(tcp::canonical_addr), ..peer_info };
         if let Some(peer_addr) = peer_info.addr {
             match trust_level {
                 TrustLevel::Signed => {
@@ -137,11 +184,9 @@ impl Inner {
                     .peer_states
                     .push(peer_info.id.clone(), KnownPeerState::new(peer_info, clock.now_utc()))
                 {
-                    // If a peer was evicted from peer_states due to the bounded cache size
-                    // and it has an address, remove the corresponding entry from addr_peers
-                    if let Some(popped_peer_addr) = popped_peer_state.peer_info.addr {
-                        self.addr_peers.remove(&popped_peer_addr);
-                    }
+                    // If a peer was evicted from peer_states due to the bounded cache size,
+                    // remove its addresses from addr_peers.
+                    self.remove_addrs(&popped_peer_state.peer_info.id);
                 }
             }
         }
@@ -156,17 +201,57 @@ impl Inner {
         Ok(())
     }
 
+    /// Moves the state of the `old` PeerId to the `new` one, after the peer has rotated its key.
+    /// If `new` is already known, its state is kept, but it inherits the ban and the address
+    /// (if it doesn't have one) of `old`.
+    fn migrate_peer(&mut self, old: &PeerId, new: &PeerId) {
+        if self.boot_nodes.contains(old) {
+            self.boot_nodes.insert(new.clone());
+        }
+        for (_, verified_peer) in self.addr_peers.iter_mut() {
+            if &verified_peer.peer_id == old {
+                verified_peer.peer_id = new.clone();
+            }
+        }
+        let mut state = match self.peer_states.pop(old) {
+            Some(state) => state,
+            None => return,
+        };
+        state.peer_info.id = new.clone();
+        if state.status == KnownPeerStatus::Connected {
+            // The connection (if still alive) is authenticated with the old key.
+            state.status = KnownPeerStatus::NotConnected;
+        }
+        let banned = state.status.is_banned();
+        if let Some(new_state) = self.peer_states.peek_mut(new) {
+            if banned && !new_state.status.is_banned() {
+                new_state.status = state.status;
+            }
+            if new_state.peer_info.addr.is_none() {
+                new_state.peer_info.addr = state.peer_info.addr;
+            }
+        } else if let Some((_, popped_peer_state)) = self.peer_states.push(new.clone(), state) {
+            self.remove_addrs(&popped_peer_state.peer_info.id);
+        }
+        if banned {
+            self.save_banned();
+        }
+    }
+
     /// Deletes peers from the internal cache
     fn delete_peers(&mut self, peer_ids: &[PeerId]) {
         for peer_id in peer_ids {
-            if let Some(peer_state) = self.peer_states.pop(peer_id) {
-                if let Some(addr) = peer_state.peer_info.addr {
-                    self.addr_peers.remove(&addr);
-                }
+            if self.peer_states.pop(peer_id).is_some() {
+                self.remove_addrs(peer_id);
             }
         }
     }
 
+    /// Removes all the addresses of the peer from the addr_peers index.
+    fn remove_addrs(&mut self, peer_id: &PeerId) {
+        self.addr_peers.retain(|_, verified_peer| &verified_peer.peer_id != peer_id);
+    }
+
     /// Find a random subset of peers based on filter.
     fn find_peers<F>(&self, filter: F, count: usize) -> Vec<PeerInfo>
     where
@@ -190,8 +275,12 @@ impl Inner {
         trust_level: TrustLevel,
     ) {
         // If there is a peer associated with current address remove the address from it.
+        // The address might be just an alias of the peer, in which case its peer_info.addr stays.
         if let Some(verified_peer) = self.addr_peers.remove(&peer_addr) {
-            self.peer_states.peek_mut(&verified_peer.peer_id).unwrap().peer_info.addr = None;
+            let peer_state = self.peer_states.peek_mut(&verified_peer.peer_id).unwrap();
+            if peer_state.peer_info.addr == Some(peer_addr) {
+                peer_state.peer_info.addr = None;
+            }
         }
 
         // If this peer already has an address, remove that pair from the index.
@@ -214,11 +303,9 @@ impl Inner {
                 .peer_states
                 .push(peer_info.id.clone(), KnownPeerState::new(peer_info.clone(), now))
             {
-                // If a peer was evicted from peer_states due to the bounded cache size
-                // and it has an address, remove the corresponding entry from addr_peers
-                if let Some(popped_peer_addr) = popped_peer_state.peer_info.addr {
-                    self.addr_peers.remove(&popped_peer_addr);
-                }
+                // If a peer was evicted from peer_states due to the bounded cache size,
+                // remove its addresses from addr_peers.
+                self.remove_addrs(&popped_peer_state.peer_info.id);
             }
         }
     }
@@ -240,11 +327,11 @@ impl Inner {
     fn unban(&mut self, now: time::Utc) {
         let mut to_unban = vec![];
         for (peer_id, peer_state) in &self.peer_states {
-            if let KnownPeerStatus::Banned(_, ban_time) = peer_state.status {
-                if now < ban_time + self.config.ban_window {
+            if let KnownPeerStatus::Banned(reason, ban_time) = peer_state.status {
+                if now < ban_time + self.config.ban_duration(reason) {
                     continue;
                 }
-                tracing::info!(target: "network", unbanned = ?peer_id, ?ban_time, "unbanning a peer");
+                tracing::info!(target: "network", unbanned = ?peer_id, ?reason, ?ban_time, "unbanning a peer");
                 to_unban.push(peer_id.clone());
             }
         }
@@ -253,6 +340,24 @@ impl Inner {
                 tracing::error!(target: "network", ?peer_id, ?err, "Failed to unban a peer");
             }
         }
+        if !to_unban.is_empty() {
+            self.save_banned();
+        }
+    }
+
+    /// Persists the currently banned peers.
+    fn save_banned(&mut self) {
+        let banned: Vec<_> = (self.peer_states.iter())
+            .filter_map(|(_, state)| match state.status {
+                KnownPeerStatus::Banned(reason, ban_time) => {
+                    Some(BannedPeer { peer_info: state.peer_info.clone(), reason, ban_time })
+                }
+                _ => None,
+            })
+            .collect();
+        if let Err(err) = self.store.set_banned_peers(&banned) {
+            tracing::error!(target: "network", ?err, "Failed to save banned peers");
+        }
     }
 
     /// Update the 'last_seen' time for all the peers that we're currently connected to.
@@ -272,7 +377,7 @@ impl Inner {
     }
 
     /// Cleans up the state of the PeerStore, due to passing time.
-    /// * it unbans a peer if config.ban_window has passed
+    /// * it unbans a peer if its ban duration (see Config::ban_duration) has passed
     /// * it updates KnownPeerStatus.last_seen of the connected peers
     /// * it removes peers which were not seen for config.peer_expiration_duration
     /// This function should be called periodically.
@@ -287,8 +392,10 @@ impl Inner {
 pub(crate) struct PeerStore(Mutex<Inner>);
 
 impl PeerStore {
-    pub fn new(clock: &time::Clock, config: Config) -> anyhow::Result<Self> {
-        let boot_nodes: HashSet<_> = config.boot_nodes.iter().map(|p| p.id.clone()).collect();
+    pub fn new(clock: &time::Clock, config: Config, store: store::Store) -> anyhow::Result<Self> {
+        let boot_nodes: HashSet<_> = (config.boot_nodes.iter().map(|p| p.id.clone()))
+            .chain(config.boot_node_hosts.iter().map(|b| b.peer_id.clone()))
+            .collect();
         // A mapping from `PeerId` to `KnownPeerState`.
         let mut peerid_2_state = LruCache::new(config.peer_states_cache_size as usize);
         // Stores mapping from `SocketAddr` to `VerifiedPeer`, which contains `PeerId`.
@@ -334,13 +441,97 @@ impl PeerStore {
             }
         }
 
-        let inner =
-            Inner { config, boot_nodes, peer_states: peerid_2_state, addr_peers: addr_2_peer };
+        // Restore the bans which haven't expired yet.
+        for banned in store.get_banned_peers() {
+            if now >= banned.ban_time + config.ban_duration(banned.reason) {
+                continue;
+            }
+            let status = KnownPeerStatus::Banned(banned.reason, banned.ban_time);
+            if let Some(state) = peerid_2_state.peek_mut(&banned.peer_info.id) {
+                state.status = status;
+                continue;
+            }
+            let mut peer_info = banned.peer_info;
+            if let Some(addr) = peer_info.addr {
+                match addr_2_peer.entry(addr) {
+                    // The address is already taken by a boot node.
+                    Entry::Occupied(_) => peer_info.addr = None,
+                    Entry::Vacant(entry) => {
+                        entry.insert(VerifiedPeer {
+                            peer_id: peer_info.id.clone(),
+                            trust_level: TrustLevel::Indirect,
+                        });
+                    }
+                }
+            }
+            let mut state = KnownPeerState::new(peer_info.clone(), banned.ban_time);
+            state.status = status;
+            if let Some((_, popped_peer_state)) = peerid_2_state.push(peer_info.id, state) {
+                if let Some(popped_peer_addr) = popped_peer_state.peer_info.addr {
+                    addr_2_peer.remove(&popped_peer_addr);
+                }
+            }
+        }
+
+        let key_transitions = LruCache::new(config.peer_states_cache_size as usize);
+        let mut inner = Inner {
+            config,
+            store,
+            boot_nodes,
+            peer_states: peerid_2_state,
+            addr_peers: addr_2_peer,
+            key_transitions,
+        };
+        // Drop the expired bans from the DB.
+        inner.save_banned();
         Ok(PeerStore(Mutex::new(inner)))
     }
 
-    pub fn is_blacklisted(&self, addr: &SocketAddr) -> bool {
-        self.0.lock().config.blacklist.contains(*addr)
+    /// Returns whether the blacklist and the allowlist permit connections with the peer
+    /// listening at `addr`.
+    pub fn is_allowed(&self, addr: &SocketAddr) -> bool {
+        self.0.lock().config.is_allowed(*addr)
+    }
+
+    /// Returns whether the blacklist and the allowlist permit inbound connections from `ip`.
+    /// It is checked before the handshake, when the listening port of the peer is not known yet.
+    pub fn is_allowed_ip(&self, ip: IpAddr) -> bool {
+        let inner = self.0.lock();
+        !inner.config.blacklist.contains_ip(ip)
+            && inner.config.allowlist.as_ref().map_or(true, |l| l.contains_any_port(ip))
+    }
+
+    /// Replaces the blacklist. Connections with the newly blacklisted peers are not closed:
+    /// it is up to the caller.
+    pub fn set_blacklist(&self, blacklist: blacklist::Blacklist) {
+        self.0.lock().config.blacklist = blacklist;
+    }
+
+    /// Replaces the boot nodes. The new boot nodes with known addresses are added to the store,
+    /// so that they can be dialed right away. The boot nodes which have been removed stay
+    /// in the store as regular peers.
+    pub fn set_boot_nodes(
+        &self,
+        clock: &time::Clock,
+        boot_nodes: Vec<PeerInfo>,
+        boot_node_hosts: Vec<BootNodeHost>,
+    ) {
+        let mut inner = self.0.lock();
+        inner.boot_nodes = (boot_nodes.iter().map(|p| p.id.clone()))
+            .chain(boot_node_hosts.iter().map(|b| b.peer_id.clone()))
+            .collect();
+        for peer_info in &boot_nodes {
+            if peer_info.addr.is_some() {
+                inner.add_signed_peer(clock, peer_info.clone());
+            }
+        }
+        inner.config.boot_nodes = boot_nodes;
+        inner.config.boot_node_hosts = boot_node_hosts;
+    }
+
+    /// The boot nodes given by hostname.
+    pub fn boot_node_hosts(&self) -> Vec<BootNodeHost> {
+        self.0.lock().config.boot_node_hosts.clone()
     }
 
     pub fn len(&self) -> usize {
@@ -359,7 +550,6 @@ impl PeerStore {
         self.0.lock().update(clock)
     }
 
-    #[allow(dead_code)]
     /// Returns the state of the current peer in memory.
     pub fn get_peer_state(&self, peer_id: &PeerId) -> Option<KnownPeerState> {
         self.0.lock().peer_states.get(peer_id).cloned()
@@ -423,11 +613,13 @@ impl PeerStore {
         } else {
             bail!("Peer {} is missing in the peer store", peer_id);
         }
+        inner.save_banned();
         Ok(())
     }
 
     /// Return unconnected or peers with unknown status that we can try to connect to.
-    /// Peers with unknown addresses are filtered out.
+    /// Peers with unknown addresses are filtered out, as well as the peers disallowed by
+    /// the blacklist or the allowlist.
     pub fn unconnected_peer(
         &self,
         ignore_fn: impl Fn(&KnownPeerState) -> bool,
@@ -439,7 +631,7 @@ impl PeerStore {
                 |p| {
                     (p.status == KnownPeerStatus::NotConnected)
                         && !ignore_fn(p)
-                        && p.peer_info.addr.is_some()
+                        && p.peer_info.addr.map_or(false, |addr| inner.config.is_allowed(addr))
                         // if we're connecting only to the boot nodes - filter out the nodes that are not bootnodes.
                         && (!inner.config.connect_only_to_boot_nodes || inner.boot_nodes.contains(&p.peer_info.id))
                 },
@@ -457,7 +649,7 @@ impl PeerStore {
             |p| {
                 (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
                     && !ignore_fn(p)
-                    && p.peer_info.addr.is_some()
+                    && p.peer_info.addr.map_or(false, |addr| inner.config.is_allowed(addr))
                     // If we're connecting only to the boot nodes - filter out the nodes that are not boot nodes.
                     && (!inner.config.connect_only_to_boot_nodes || inner.boot_nodes.contains(&p.peer_info.id))
             },
@@ -467,11 +659,16 @@ impl PeerStore {
         .cloned()
     }
 
-    /// Return healthy known peers up to given amount.
+    /// Return healthy known peers up to given amount. The peers with unknown address are
+    /// skipped: either they don't listen for connections, or we cannot tell where they do.
     pub fn healthy_peers(&self, max_count: usize) -> Vec<PeerInfo> {
-        self.0
-            .lock()
-            .find_peers(|p| matches!(p.status, KnownPeerStatus::Banned(_, _)).not(), max_count)
+        self.0.lock().find_peers(
+            |p| {
+                p.peer_info.addr.is_some()
+                    && matches!(p.status, KnownPeerStatus::Banned(_, _)).not()
+            },
+            max_count,
+        )
     }
 
     /// Adds peers we’ve learned about from other peers.
@@ -487,8 +684,8 @@ impl PeerStore {
         let mut blacklisted: usize = 0;
         for peer_info in peers {
             total += 1;
-            let is_blacklisted =
-                peer_info.addr.map_or(false, |addr| inner.config.blacklist.contains(addr));
+            let is_blacklisted = (peer_info.addr)
+                .map_or(false, |addr| !inner.config.is_allowed(tcp::canonical_addr(addr)));
             if is_blacklisted {
                 blacklisted += 1;
             } else {
@@ -512,6 +709,87 @@ impl PeerStore {
         self.0.lock().add_peer(clock, peer_info, TrustLevel::Direct)
     }
 
+    /// Records the addresses on which a connected peer listens, other than its peer_info.addr.
+    ///
+    /// The addresses are not used for connecting to the peer, but they prevent the peer from
+    /// being added again under a different address family, when other peers advertise it
+    /// at one of these addresses. Addresses already known to belong to other peers are ignored.
+    pub fn add_peer_addrs(&self, peer_id: &PeerId, addrs: &[SocketAddr]) {
+        let mut inner = self.0.lock();
+        if !inner.peer_states.contains(peer_id) {
+            return;
+        }
+        for addr in addrs {
+            let addr = tcp::canonical_addr(*addr);
+            if !inner.config.is_allowed(addr) {
+                continue;
+            }
+            if let Entry::Vacant(entry) = inner.addr_peers.entry(addr) {
+                entry.insert(VerifiedPeer {
+                    peer_id: peer_id.clone(),
+                    trust_level: TrustLevel::Indirect,
+                });
+            }
+        }
+    }
+
+    /// Sets the address of a boot node, which has just been resolved from its hostname.
+    /// The boot node is trusted to own the address, just like the boot nodes given by IP.
+    pub fn set_boot_node_addr(&self, clock: &time::Clock, peer_id: &PeerId, addr: SocketAddr) {
+        let mut inner = self.0.lock();
+        let addr = tcp::canonical_addr(addr);
+        let peer_info = match inner.peer_states.peek(peer_id) {
+            Some(state) if state.peer_info.addr == Some(addr) => return,
+            Some(state) => PeerInfo { addr: Some(addr), ..state.peer_info.clone() },
+            None => PeerInfo { id: peer_id.clone(), addr: Some(addr), account_id: None },
+        };
+        tracing::info!(target: "network", peer_id = %peer_id, %addr, "Boot node address updated");
+        inner.add_signed_peer(clock, peer_info);
+    }
+
+    /// Records a key transition (which has to be verified by the caller) and moves
+    /// everything known about the old PeerId to the new one.
+    /// Returns false if this transition, or a newer one from the same PeerId, is already known.
+    pub fn add_key_transition(&self, transition: SignedKeyTransition) -> bool {
+        let mut inner = self.0.lock();
+        if let Some(known) = inner.key_transitions.peek(&transition.old_peer_id) {
+            if known.timestamp >= transition.timestamp {
+                return false;
+            }
+        }
+        tracing::info!(
+            target: "network",
+            old_peer_id = %transition.old_peer_id,
+            new_peer_id = %transition.new_peer_id,
+            "Peer rotated its key"
+        );
+        let (old, new) = (transition.old_peer_id.clone(), transition.new_peer_id.clone());
+        inner.key_transitions.put(old.clone(), transition);
+        inner.migrate_peer(&old, &new);
+        true
+    }
+
+    /// Returns all the known key transitions.
+    pub fn key_transitions(&self) -> Vec<SignedKeyTransition> {
+        self.0.lock().key_transitions.iter().map(|(_, t)| t.clone()).collect()
+    }
+
+    /// Checks whether `new` is the current PeerId of the peer formerly known as `old`,
+    /// possibly after multiple key rotations.
+    pub fn is_key_rotated(&self, old: &PeerId, new: &PeerId) -> bool {
+        let inner = self.0.lock();
+        let mut current = old;
+        // Bounded, so that a cycle of transitions cannot make us loop forever.
+        for _ in 0..inner.key_transitions.len() {
+            match inner.key_transitions.peek(current) {
+                Some(t) if &t.new_peer_id == new => return true,
+                Some(t) => current = &t.new_peer_id,
+                None => return false,
+            }
+        }
+        false
+    }
+
     pub fn load(&self) -> HashMap<PeerId, KnownPeerState> {
         self.0.lock().peer_states.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
     }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::blacklist::Blacklist;
+use crate::network_protocol::KeyTransition;
 use near_crypto::{KeyType, SecretKey};
 use near_primitives::time;
 use std::collections::HashSet;
@@ -25,6 +26,10 @@ fn gen_peer_info(port: u16) -> PeerInfo {
     }
 }
 
+fn make_store() -> store::Store {
+    store::Store::from(near_store::db::TestDB::new())
+}
+
 fn make_config(
     boot_nodes: &[PeerInfo],
     blacklist: blacklist::Blacklist,
@@ -32,10 +37,13 @@ fn make_config(
 ) -> Config {
     Config {
         boot_nodes: boot_nodes.iter().cloned().collect(),
+        boot_node_hosts: vec![],
         blacklist,
+        allowlist: None,
         peer_states_cache_size: 1000,
         connect_only_to_boot_nodes,
         ban_window: time::Duration::seconds(1),
+        ban_window_by_reason: Default::default(),
         peer_expiration_duration: time::Duration::days(1000),
     }
 }
@@ -47,14 +55,53 @@ fn ban_store() {
     let peer_info_to_ban = gen_peer_info(1);
     let boot_nodes = vec![peer_info_a, peer_info_to_ban.clone()];
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
     assert_eq!(peer_store.healthy_peers(3).len(), 2);
     peer_store.peer_ban(&clock.clock(), &peer_info_to_ban.id, ReasonForBan::Abusive).unwrap();
     assert_eq!(peer_store.healthy_peers(3).len(), 1);
 }
 
+#[test]
+fn ban_persists_across_restarts() {
+    let clock = time::FakeClock::default();
+    let mut config = make_config(&[], Blacklist::default(), false);
+    config.ban_window_by_reason.insert(ReasonForBan::Abusive, time::Duration::seconds(100));
+    let store = make_store();
+    let abusive = gen_peer_info(0);
+    let bad_block = gen_peer_info(1);
+
+    let peer_store = PeerStore::new(&clock.clock(), config.clone(), store.clone()).unwrap();
+    peer_store.add_indirect_peers(&clock.clock(), [abusive.clone(), bad_block.clone()].into_iter());
+    peer_store.peer_ban(&clock.clock(), &abusive.id, ReasonForBan::Abusive).unwrap();
+    peer_store.peer_ban(&clock.clock(), &bad_block.id, ReasonForBan::BadBlock).unwrap();
+
+    // Both bans are restored after a restart.
+    let peer_store = PeerStore::new(&clock.clock(), config.clone(), store.clone()).unwrap();
+    assert!(peer_store.is_banned(&abusive.id));
+    assert!(peer_store.is_banned(&bad_block.id));
+    assert_eq!(peer_store.get_peer_state(&abusive.id).unwrap().peer_info, abusive);
+
+    // The default ban window has passed, but the one for Abusive hasn't.
+    clock.advance(config.ban_window);
+    peer_store.update(&clock.clock());
+    assert!(peer_store.is_banned(&abusive.id));
+    assert!(!peer_store.is_banned(&bad_block.id));
+    let peer_store = PeerStore::new(&clock.clock(), config.clone(), store.clone()).unwrap();
+    assert!(peer_store.is_banned(&abusive.id));
+    assert!(!peer_store.is_banned(&bad_block.id));
+
+    // Expired bans are not restored.
+    clock.advance(time::Duration::seconds(100));
+    let peer_store = PeerStore::new(&clock.clock(), config, store).unwrap();
+    assert!(!peer_store.is_banned(&abusive.id));
+    assert_eq!(peer_store.count_banned(), 0);
+}
+
 #[test]
 fn test_unconnected_peer() {
     let clock = time::FakeClock::default();
@@ -62,9 +109,12 @@ fn test_unconnected_peer() {
     let peer_info_to_ban = gen_peer_info(1);
     let boot_nodes = vec![peer_info_a, peer_info_to_ban];
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
 
     assert!(peer_store.unconnected_peer(|_| false, false).is_some());
     assert!(peer_store.unconnected_peer(|_| true, false).is_none());
@@ -85,9 +135,12 @@ fn test_unknown_vs_not_connected() {
         nodes.map(|peer| peer_store.get_peer_state(&peer.id).map(|known_state| known_state.status))
     };
 
-    let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-            .unwrap();
+    let peer_store = PeerStore::new(
+        &clock.clock(),
+        make_config(&boot_nodes, Blacklist::default(), false),
+        make_store(),
+    )
+    .unwrap();
 
     // Check the status of the in-memory store.
     // Boot node should be marked as not-connected, as we've verified it.
@@ -157,9 +210,12 @@ fn test_unconnected_peer_only_boot_nodes() {
     // 1 non-boot (peer_in_store) node peer that is in the store.
     // we should connect to peer_in_store
     {
-        let peer_store =
-            PeerStore::new(&clock.clock(), make_config(&boot_nodes, Blacklist::default(), false))
-                .unwrap();
+        let peer_store = PeerStore::new(
+            &clock.clock(),
+            make_config(&boot_nodes, Blacklist::default(), false),
+            make_store(),
+        )
+        .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_in_store.clone());
         peer_store.peer_connected(&clock.clock(), &peer_info_a);
         assert_eq!(peer_store.unconnected_peer(|_| false, false), Some(peer_in_store.clone()));
@@ -169,9 +225,12 @@ fn test_unconnected_peer_only_boot_nodes() {
     // 1 non-boot (peer_in_store) node peer that is in the store.
     // connect to only boot nodes is enabled - we should not find any peer to connect to.
     {
-        let peer_store =
-            PeerStore::new(&clock.clock(), make_config(&boot_nodes, Default::default(), true))
-                .unwrap();
+        let peer_store = PeerStore::new(
+            &clock.clock(),
+            make_config(&boot_nodes, Default::default(), true),
+            make_store(),
+        )
+        .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_in_store);
         peer_store.peer_connected(&clock.clock(), &peer_info_a);
         assert_eq!(peer_store.unconnected_peer(|_| false, false), None);
@@ -183,6 +242,7 @@ fn test_unconnected_peer_only_boot_nodes() {
         let peer_store = PeerStore::new(
             &clock.clock(),
             make_config(&boot_nodes, Default::default(), connect_to_boot_nodes),
+            make_store(),
         )
         .unwrap();
         peer_store.add_direct_peer(&clock.clock(), peer_info_a.clone());
@@ -235,7 +295,8 @@ fn check_integrity(peer_store: &PeerStore) -> bool {
 fn handle_peer_id_change() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     let peers_id = (0..2).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
     let addr = get_addr(0);
@@ -259,7 +320,8 @@ fn handle_peer_id_change() {
 fn dont_handle_address_change() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     let peers_id = (0..1).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
     let addrs = (0..2).map(get_addr).collect::<Vec<_>>();
@@ -278,7 +340,8 @@ fn dont_handle_address_change() {
 fn check_add_peers_overriding() {
     let clock = time::FakeClock::default();
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     // Five peers: A, B, C, D, X, T
     let peers_id = (0..6).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
@@ -370,7 +433,8 @@ fn check_ignore_blacklisted_peers() {
     let blacklist: blacklist::Blacklist =
         ["127.0.0.1:1"].iter().map(|e| e.parse().unwrap()).collect();
 
-    let peer_store = PeerStore::new(&clock.clock(), make_config(&[], blacklist, false)).unwrap();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], blacklist, false), make_store()).unwrap();
 
     peer_store.add_indirect_peers(
         &clock.clock(),
@@ -386,6 +450,38 @@ fn check_ignore_blacklisted_peers() {
     assert_peers(&peer_store, &[&ids[0], &ids[2]]);
 }
 
+#[test]
+fn check_allowlist() {
+    let clock = time::FakeClock::default();
+    let other_addr = |port| SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 1), port).into();
+    let ids = (0..3).map(|ix| get_peer_id(format!("node{}", ix))).collect::<Vec<_>>();
+
+    // Allow 127.0.0.0/8, except 127.0.0.1:1.
+    let mut config =
+        make_config(&[], ["127.0.0.1:1"].iter().map(|e| e.parse().unwrap()).collect(), false);
+    config.allowlist = Some(["127.0.0.0/8"].iter().map(|e| e.parse().unwrap()).collect());
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
+
+    assert!(peer_store.is_allowed(&get_addr(2)));
+    assert!(!peer_store.is_allowed(&get_addr(1)));
+    assert!(!peer_store.is_allowed(&other_addr(2)));
+    // The blacklist covers only a single port of 127.0.0.1.
+    assert!(peer_store.is_allowed_ip(get_addr(1).ip()));
+    assert!(!peer_store.is_allowed_ip(other_addr(2).ip()));
+
+    peer_store.add_indirect_peers(
+        &clock.clock(),
+        [
+            get_peer_info(ids[0].clone(), Some(get_addr(1))),
+            get_peer_info(ids[1].clone(), Some(get_addr(2))),
+            get_peer_info(ids[2].clone(), Some(other_addr(2))),
+        ]
+        .into_iter(),
+    );
+    assert_peers_in_cache(&peer_store, &[ids[1].clone()], &[get_addr(2)]);
+    assert_eq!(Some(ids[1].clone()), peer_store.unconnected_peer(|_| false, false).map(|p| p.id));
+}
+
 #[track_caller]
 fn assert_peers_in_cache(
     peer_store: &PeerStore,
@@ -415,7 +511,8 @@ fn test_delete_peers() {
     let peer_addresses = peer_infos.iter().map(|info| info.addr.unwrap()).collect::<Vec<_>>();
 
     let peer_store =
-        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false)).unwrap();
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
 
     peer_store.add_indirect_peers(&clock.clock(), peer_infos.into_iter());
     assert_peers_in_cache(&peer_store, &peer_ids, &peer_addresses);
@@ -429,7 +526,7 @@ fn test_lru_eviction() {
     let clock = time::FakeClock::default();
     let mut config = make_config(&[], Default::default(), false);
     config.peer_states_cache_size = 10;
-    let peer_store = PeerStore::new(&clock.clock(), config).unwrap();
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
 
     let (peer_ids, peer_infos): (Vec<_>, Vec<_>) = (0..15)
         .map(|i| {
@@ -460,7 +557,7 @@ fn test_lru_ignore_duplicate_peers() {
     let clock = time::FakeClock::default();
     let mut config = make_config(&[], Default::default(), false);
     config.peer_states_cache_size = 10;
-    let peer_store = PeerStore::new(&clock.clock(), config).unwrap();
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
 
     let (peer_ids, peer_infos): (Vec<_>, Vec<_>) = (0..15)
         .map(|i| {
@@ -483,3 +580,152 @@ fn test_lru_ignore_duplicate_peers() {
     peer_store.add_indirect_peers(&clock.clock(), peer_infos[10..].iter().cloned());
     assert_peers_in_cache(&peer_store, &peer_ids[5..], &peer_addresses[5..]);
 }
+
+#[test]
+fn test_dedupe_dual_stack_peers() {
+    let clock = time::FakeClock::default();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
+    let id = get_peer_id("node".to_string());
+    let other_id = get_peer_id("other".to_string());
+    let v4_addr = get_addr(1);
+    let mapped_addr: SocketAddr = "[::ffff:127.0.0.1]:1".parse().unwrap();
+    let v6_addr: SocketAddr = "[::1]:1".parse().unwrap();
+
+    peer_store.peer_connected(&clock.clock(), &get_peer_info(id.clone(), Some(v4_addr)));
+    peer_store.add_peer_addrs(&id, &[v6_addr]);
+    assert_peers_in_cache(&peer_store, &[id.clone()], &[v4_addr, v6_addr]);
+
+    // Neither the IPv4-mapped address, nor the IPv6 address are assigned to another peer.
+    peer_store.add_indirect_peers(
+        &clock.clock(),
+        [
+            get_peer_info(other_id.clone(), Some(mapped_addr)),
+            get_peer_info(other_id, Some(v6_addr)),
+        ]
+        .into_iter(),
+    );
+    assert_peers_in_cache(&peer_store, &[id.clone()], &[v4_addr, v6_addr]);
+    assert_eq!(Some(v4_addr), peer_store.get_peer_state(&id).unwrap().peer_info.addr);
+
+    // All the addresses are removed together with the peer.
+    peer_store.0.lock().delete_peers(&[id]);
+    assert_peers_in_cache(&peer_store, &[], &[]);
+}
+
+#[test]
+fn test_boot_node_addr_update() {
+    let clock = time::FakeClock::default();
+    let id = get_peer_id("boot".to_string());
+    let mut config = make_config(&[get_peer_info(id.clone(), None)], Default::default(), true);
+    config.boot_node_hosts =
+        vec![BootNodeHost { peer_id: id.clone(), host: "boot.test:24567".to_string() }];
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
+    // The hostname couldn't be resolved at startup.
+    assert_eq!(peer_store.unconnected_peer(|_| false, false), None);
+
+    let old_addr = get_addr(1);
+    peer_store.set_boot_node_addr(&clock.clock(), &id, old_addr);
+    assert_eq!(
+        peer_store.unconnected_peer(|_| false, false),
+        Some(get_peer_info(id.clone(), Some(old_addr)))
+    );
+
+    // The IP of the boot node rotated.
+    let new_addr = get_addr(2);
+    peer_store.set_boot_node_addr(&clock.clock(), &id, new_addr);
+    assert_peers_in_cache(&peer_store, &[id.clone()], &[new_addr]);
+    assert_eq!(Some(new_addr), peer_store.get_peer_state(&id).unwrap().peer_info.addr);
+}
+
+#[test]
+fn test_set_boot_nodes() {
+    let clock = time::FakeClock::default();
+    let old = gen_peer_info(1);
+    let new = gen_peer_info(2);
+    let config = make_config(&[old.clone()], Default::default(), true);
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
+    assert_eq!(peer_store.unconnected_peer(|_| false, false), Some(old.clone()));
+
+    let host = BootNodeHost { peer_id: new.id.clone(), host: "boot.test:24567".to_string() };
+    peer_store.set_boot_nodes(&clock.clock(), vec![new.clone()], vec![host.clone()]);
+    // Only the new boot node is dialed, while the old one is kept as a regular peer.
+    assert_eq!(peer_store.unconnected_peer(|_| false, false), Some(new.clone()));
+    assert_peers_in_cache(&peer_store, &[old.id, new.id], &[old.addr.unwrap(), new.addr.unwrap()]);
+    assert_eq!(vec![host], peer_store.boot_node_hosts());
+}
+
+#[test]
+fn test_set_blacklist() {
+    let clock = time::FakeClock::default();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
+    let addr = get_addr(1);
+    assert!(peer_store.is_allowed(&addr));
+    peer_store.set_blacklist(["127.0.0.1:1"].iter().map(|e| e.parse().unwrap()).collect());
+    assert!(!peer_store.is_allowed(&addr));
+    assert!(peer_store.is_allowed(&get_addr(2)));
+    peer_store.set_blacklist(Default::default());
+    assert!(peer_store.is_allowed(&addr));
+}
+
+#[test]
+fn test_key_transition() {
+    let clock = time::FakeClock::default();
+    let keys: Vec<_> =
+        (0..3).map(|i| SecretKey::from_seed(KeyType::ED25519, &format!("boot{i}"))).collect();
+    let ids: Vec<_> = keys.iter().map(|k| PeerId::new(k.public_key())).collect();
+    let addr = get_addr(1);
+    let config =
+        make_config(&[get_peer_info(ids[0].clone(), Some(addr))], Default::default(), true);
+    let peer_store = PeerStore::new(&clock.clock(), config, make_store()).unwrap();
+    peer_store.peer_ban(&clock.clock(), &ids[0], ReasonForBan::Abusive).unwrap();
+
+    let t01 = SignedKeyTransition::new(&clock.clock(), &keys[0], &keys[1]);
+    assert!(peer_store.add_key_transition(t01.clone()));
+    assert!(!peer_store.add_key_transition(t01.clone()));
+    // The state of the old PeerId has been moved to the new one, including the ban.
+    assert!(peer_store.get_peer_state(&ids[0]).is_none());
+    let state = peer_store.get_peer_state(&ids[1]).unwrap();
+    assert_eq!(Some(addr), state.peer_info.addr);
+    assert!(state.status.is_banned());
+    assert_peers_in_cache(&peer_store, &[ids[1].clone()], &[addr]);
+
+    // The new PeerId is a boot node, so it is allowed to connect once unbanned.
+    clock.advance(time::Duration::seconds(2));
+    peer_store.update(&clock.clock());
+    assert_eq!(
+        peer_store.unconnected_peer(|_| false, false),
+        Some(get_peer_info(ids[1].clone(), Some(addr)))
+    );
+
+    clock.advance(time::Duration::seconds(2));
+    let t12 = SignedKeyTransition::new(&clock.clock(), &keys[1], &keys[2]);
+    assert!(peer_store.add_key_transition(t12));
+    assert!(peer_store.is_key_rotated(&ids[0], &ids[1]));
+    assert!(peer_store.is_key_rotated(&ids[0], &ids[2]));
+    assert!(!peer_store.is_key_rotated(&ids[2], &ids[0]));
+    // A transition older than the known one is ignored.
+    let t02 = KeyTransition {
+        new_peer_id: ids[2].clone(),
+        timestamp: t01.timestamp - 1,
+        ..t01.transition.clone()
+    }
+    .sign(&keys[0], &keys[2]);
+    assert!(!peer_store.add_key_transition(t02));
+}
+
+#[test]
+fn test_healthy_peers_skip_unknown_addr() {
+    let clock = time::FakeClock::default();
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), make_store())
+            .unwrap();
+    let listening = gen_peer_info(1);
+    let not_listening = get_peer_info(get_peer_id("not_listening".to_string()), None);
+    peer_store.peer_connected(&clock.clock(), &listening);
+    peer_store.peer_connected(&clock.clock(), &not_listening);
+    assert_eq!(peer_store.healthy_peers(10), vec![listening]);
+}
@@ -0,0 +1,72 @@
+//! Pings sent on the node operator's request, to measure the round-trip time to the peers
+//! through the routing layer (see the /debug/api/ping endpoint).
+use crate::network_protocol::Pong;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use tokio::sync::oneshot;
+
+#[cfg(test)]
+mod tests;
+
+/// Pong received in response to a ping.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Reply {
+    pub rtt: time::Duration,
+    /// TTL of the routed message carrying the pong, at the time of receipt.
+    pub ttl: u8,
+}
+
+struct Pending {
+    target: PeerId,
+    sent_at: time::Instant,
+    reply: oneshot::Sender<Reply>,
+}
+
+/// Pings awaiting a pong, by nonce.
+pub(crate) struct Pinger {
+    pending: Mutex<HashMap<u64, Pending>>,
+}
+
+impl Pinger {
+    pub fn new() -> Self {
+        Self { pending: Mutex::new(HashMap::new()) }
+    }
+
+    /// Registers a ping to `target` with the given nonce, which is about to be sent.
+    /// Returns None if there is already a pending ping with this nonce.
+    pub fn start(
+        &self,
+        clock: &time::Clock,
+        nonce: u64,
+        target: PeerId,
+    ) -> Option<oneshot::Receiver<Reply>> {
+        let mut pending = self.pending.lock();
+        if pending.contains_key(&nonce) {
+            return None;
+        }
+        let (send, recv) = oneshot::channel();
+        pending.insert(nonce, Pending { target, sent_at: clock.now(), reply: send });
+        Some(recv)
+    }
+
+    /// Stops waiting for the pong to the ping with the given nonce.
+    pub fn cancel(&self, nonce: u64) {
+        self.pending.lock().remove(&nonce);
+    }
+
+    /// Delivers the pong to the matching pending ping, if any.
+    /// Returns false if the pong doesn't match any pending ping.
+    pub fn pong_received(&self, clock: &time::Clock, pong: &Pong, ttl: u8) -> bool {
+        let mut pending = self.pending.lock();
+        match pending.get(&pong.nonce) {
+            Some(p) if p.target == pong.source => {}
+            _ => return false,
+        }
+        let p = pending.remove(&pong.nonce).unwrap();
+        // The receiver might be gone already, if the ping has just timed out.
+        let _ = p.reply.send(Reply { rtt: clock.now() - p.sent_at, ttl });
+        true
+    }
+}
@@ -0,0 +1,35 @@
+use super::*;
+use crate::network_protocol::testonly as data;
+use crate::testonly::make_rng;
+
+#[tokio::test]
+async fn pong_delivered() {
+    let mut rng = make_rng(7283918273);
+    let clock = time::FakeClock::default();
+    let target = data::make_peer_id(&mut rng);
+    let pinger = Pinger::new();
+    let recv = pinger.start(&clock.clock(), 5, target.clone()).unwrap();
+    // Nonces of the pending pings are unique.
+    assert!(pinger.start(&clock.clock(), 5, target.clone()).is_none());
+
+    clock.advance(time::Duration::milliseconds(300));
+    // Pongs from other peers or with other nonces are ignored.
+    let other = data::make_peer_id(&mut rng);
+    assert!(!pinger.pong_received(&clock.clock(), &Pong { nonce: 5, source: other }, 99));
+    assert!(!pinger.pong_received(&clock.clock(), &Pong { nonce: 6, source: target.clone() }, 99));
+    assert!(pinger.pong_received(&clock.clock(), &Pong { nonce: 5, source: target.clone() }, 98));
+    assert_eq!(Reply { rtt: time::Duration::milliseconds(300), ttl: 98 }, recv.await.unwrap());
+    // Only the first pong counts.
+    assert!(!pinger.pong_received(&clock.clock(), &Pong { nonce: 5, source: target }, 98));
+}
+
+#[test]
+fn cancel() {
+    let mut rng = make_rng(1928374659);
+    let clock = time::FakeClock::default();
+    let target = data::make_peer_id(&mut rng);
+    let pinger = Pinger::new();
+    let _recv = pinger.start(&clock.clock(), 1, target.clone()).unwrap();
+    pinger.cancel(1);
+    assert!(!pinger.pong_received(&clock.clock(), &Pong { nonce: 1, source: target }, 99));
+}
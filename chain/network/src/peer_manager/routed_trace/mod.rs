@@ -0,0 +1,87 @@
+//! Tracing of the routed messages targeted at accounts.
+//!
+//! Debugging issues like "my approval never arrived at the block producer" requires following
+//! a single message across all the nodes on its route. When tracing is enabled, the original
+//! node assigns a random trace id to every routed message it sends to an account, and the message
+//! carries it along the route. Every node with tracing enabled logs the events concerning the
+//! traced messages (origin, each hop, delivery, drops) under the "network::routed_trace" target,
+//! and keeps the recent ones for the /debug/api/routed_message_trace endpoint.
+//! Nodes with tracing disabled just forward the trace id.
+use crate::tcp;
+use near_primitives::network::PeerId;
+use near_primitives::time;
+use near_primitives::types::AccountId;
+use near_primitives::views::{RoutedMessageTraceEventView, RoutedMessageTraceView};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+
+#[cfg(test)]
+mod tests;
+
+/// Maximal number of the recent events kept for the debug endpoint.
+const MAX_EVENTS: usize = 10000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// This node has sent the message to the given account.
+    Originated { account_id: AccountId },
+    /// This node has received the message from the previous hop.
+    Received { from: PeerId, tier: tcp::Tier, for_me: bool },
+    /// This node has sent the message to the next hop.
+    Routed { to: PeerId, tier: tcp::Tier },
+    /// This node has dropped the message.
+    Dropped { reason: &'static str },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub trace_id: u64,
+    pub msg_type: &'static str,
+    pub at: time::Utc,
+    pub event: Event,
+}
+
+/// Generates an id for a new trace.
+pub(crate) fn new_trace_id() -> u64 {
+    rand::random()
+}
+
+/// Recent events concerning the traced routed messages.
+pub(crate) struct Tracer {
+    events: Mutex<VecDeque<Entry>>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self { events: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn record(&self, clock: &time::Clock, trace_id: u64, msg_type: &'static str, event: Event) {
+        tracing::info!(target: "network::routed_trace", trace_id, msg_type, ?event);
+        let mut events = self.events.lock();
+        events.push_back(Entry { trace_id, msg_type, at: clock.now_utc(), event });
+        while events.len() > MAX_EVENTS {
+            events.pop_front();
+        }
+    }
+
+    /// Recent events, oldest first.
+    pub fn events(&self) -> Vec<Entry> {
+        self.events.lock().iter().cloned().collect()
+    }
+
+    pub fn view(&self) -> RoutedMessageTraceView {
+        RoutedMessageTraceView {
+            events: self
+                .events()
+                .into_iter()
+                .map(|e| RoutedMessageTraceEventView {
+                    trace_id: e.trace_id,
+                    msg_type: e.msg_type.to_string(),
+                    timestamp_ms: (e.at.unix_timestamp_nanos() / 1_000_000) as i64,
+                    event: format!("{:?}", e.event),
+                })
+                .collect(),
+        }
+    }
+}
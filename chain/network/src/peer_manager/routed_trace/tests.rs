@@ -0,0 +1,24 @@
+use super::*;
+use crate::network_protocol::testonly as data;
+use crate::testonly::make_rng;
+
+#[test]
+fn keeps_recent_events() {
+    let mut rng = make_rng(8329470293);
+    let clock = time::FakeClock::default();
+    let tracer = Tracer::new();
+    let peer_id = data::make_peer_id(&mut rng);
+    for i in 0..MAX_EVENTS as u64 + 10 {
+        tracer.record(
+            &clock.clock(),
+            i,
+            "BlockApproval",
+            Event::Routed { to: peer_id.clone(), tier: tcp::Tier::T2 },
+        );
+    }
+    let events = tracer.events();
+    assert_eq!(MAX_EVENTS, events.len());
+    // The oldest events are dropped first.
+    assert_eq!(10, events[0].trace_id);
+    assert_eq!(MAX_EVENTS as u64 + 9, events.last().unwrap().trace_id);
+}
@@ -1,6 +1,7 @@
 use crate::accounts_data;
 use crate::broadcast;
 use crate::config;
+use crate::debug::PingPeers;
 use crate::network_protocol::testonly as data;
 use crate::network_protocol::{
     EdgeState, Encoding, PeerInfo, PeerMessage, SignedAccountData, SyncAccountsData,
@@ -15,7 +16,7 @@ use crate::testonly::actix::ActixSystem;
 use crate::testonly::fake_client;
 use crate::types::{
     AccountKeys, ChainInfo, KnownPeerStatus, NetworkRequests, PeerManagerMessageRequest,
-    ReasonForBan,
+    PeerManagerMessageResponse, ReasonForBan,
 };
 use crate::PeerManagerActor;
 use near_async::messaging::IntoSender;
@@ -23,6 +24,7 @@ use near_o11y::WithSpanContextExt;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::time;
 use near_primitives::types::AccountId;
+use near_primitives::views::{PingView, RouteInfoView};
 use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
@@ -52,6 +54,7 @@ pub enum Event {
 }
 
 pub(crate) struct ActorHandler {
+    pub clock: time::Clock,
     pub cfg: config::NetworkConfig,
     pub events: broadcast::Receiver<Event>,
     pub actix: ActixSystem<PeerManagerActor>,
@@ -146,7 +149,8 @@ impl ActorHandler {
     pub async fn send_outbound_connect(&self, peer_info: &PeerInfo, tier: tcp::Tier) {
         let addr = self.actix.addr.clone();
         let peer_info = peer_info.clone();
-        let stream = tcp::Stream::connect(&peer_info, tier).await.unwrap();
+        let stream =
+            tcp::Stream::connect(&peer_info, tier, None, tcp::TEST_CONNECT_TIMEOUT).await.unwrap();
         addr.do_send(PeerManagerMessageRequest::OutboundTcpConnect(stream).with_span_context());
     }
 
@@ -159,7 +163,9 @@ impl ActorHandler {
         let events = self.events.clone();
         let peer_info = peer_info.clone();
         async move {
-            let stream = tcp::Stream::connect(&peer_info, tier).await.unwrap();
+            let stream = tcp::Stream::connect(&peer_info, tier, None, tcp::TEST_CONNECT_TIMEOUT)
+                .await
+                .unwrap();
             let mut events = events.from_now();
             let stream_id = stream.id();
             addr.do_send(PeerManagerMessageRequest::OutboundTcpConnect(stream).with_span_context());
@@ -316,7 +322,8 @@ impl ActorHandler {
     }
 
     pub async fn set_chain_info(&self, chain_info: ChainInfo) -> bool {
-        self.with_state(move |s| async move { s.set_chain_info(chain_info) }).await
+        let clock = self.clock.clone();
+        self.with_state(move |s| async move { s.set_chain_info(&clock, chain_info) }).await
     }
 
     pub async fn tier1_advertise_proxies(
@@ -360,6 +367,59 @@ impl ActorHandler {
             .await
     }
 
+    /// Sends the operator's disconnect request. Returns whether the peer was connected.
+    pub async fn disconnect_by_operator(&self, peer_id: &PeerId, ban: bool) -> bool {
+        let req = PeerManagerMessageRequest::DisconnectPeer { peer_id: peer_id.clone(), ban };
+        match self.actix.addr.send(req.with_span_context()).await.unwrap() {
+            PeerManagerMessageResponse::DisconnectPeer(connected) => connected,
+            resp => panic!("unexpected response: {resp:?}"),
+        }
+    }
+
+    /// Sends the operator's request to reconnect all peers. Returns the number of closed
+    /// connections.
+    pub async fn reconnect_all(&self, tier1: bool) -> usize {
+        let req = PeerManagerMessageRequest::ReconnectAll { tier1 };
+        match self.actix.addr.send(req.with_span_context()).await.unwrap() {
+            PeerManagerMessageResponse::ReconnectAll(closed) => closed,
+            resp => panic!("unexpected response: {resp:?}"),
+        }
+    }
+
+    /// Sends the reloaded peer selection settings. Returns whether they have been applied.
+    pub async fn set_peers_config(
+        &self,
+        max_num_peers: u32,
+        whitelist_nodes: &str,
+        blacklist: &[&str],
+        boot_nodes: &str,
+    ) -> bool {
+        let req = PeerManagerMessageRequest::SetPeersConfig {
+            max_num_peers,
+            whitelist_nodes: whitelist_nodes.to_string(),
+            blacklist: blacklist.iter().map(|e| e.to_string()).collect(),
+            boot_nodes: boot_nodes.to_string(),
+        };
+        match self.actix.addr.send(req.with_span_context()).await.unwrap() {
+            PeerManagerMessageResponse::SetPeersConfig(applied) => applied,
+            resp => panic!("unexpected response: {resp:?}"),
+        }
+    }
+
+    pub async fn route_info(&self, target: &str) -> RouteInfoView {
+        let req = PeerManagerMessageRequest::GetRouteInfo { target: target.to_string() };
+        match self.actix.addr.send(req.with_span_context()).await.unwrap() {
+            PeerManagerMessageResponse::GetRouteInfo(view) => view,
+            resp => panic!("unexpected response: {resp:?}"),
+        }
+    }
+
+    /// Pings the targets through the routing layer, as requested via the debug API.
+    pub async fn ping_peers(&self, targets: &[String], timeout: time::Duration) -> PingView {
+        let msg = PingPeers { targets: targets.to_vec(), timeout };
+        self.actix.addr.send(msg).await.unwrap()
+    }
+
     pub async fn peer_store_update(&self, clock: &time::Clock) {
         let clock = clock.clone();
         self.with_state(move |s| async move { s.peer_store.update(&clock) }).await;
@@ -490,6 +550,15 @@ impl ActorHandler {
         .await;
     }
 
+    /// Executes `NetworkState::next_epoch_connect` method.
+    pub async fn next_epoch_connect(&self, clock: &time::Clock) {
+        let clock = clock.clone();
+        self.with_state(move |s| async move {
+            s.next_epoch_connect(&clock).await;
+        })
+        .await;
+    }
+
     /// Executes `NetworkState::update_connection_store` method.
     pub async fn update_connection_store(&self, clock: &time::Clock) {
         let clock = clock.clone();
@@ -508,6 +577,7 @@ pub(crate) async fn start(
 ) -> ActorHandler {
     let (send, mut recv) = broadcast::unbounded_channel();
     let actix = ActixSystem::spawn({
+        let clock = clock.clone();
         let mut cfg = cfg.clone();
         let chain = chain.clone();
         move || {
@@ -519,13 +589,15 @@ pub(crate) async fn start(
         }
     })
     .await;
-    let h = ActorHandler { cfg, actix, events: recv.clone() };
-    // Wait for the server to start.
-    recv.recv_until(|ev| match ev {
-        Event::PeerManager(PME::ServerStarted) => Some(()),
-        _ => None,
-    })
-    .await;
+    let h = ActorHandler { clock, cfg, actix, events: recv.clone() };
+    // Wait for the server to start, unless the node doesn't listen for connections.
+    if h.cfg.node_addr.is_some() {
+        recv.recv_until(|ev| match ev {
+            Event::PeerManager(PME::ServerStarted) => Some(()),
+            _ => None,
+        })
+        .await;
+    }
     h.set_chain_info(chain.get_chain_info()).await;
     h
 }
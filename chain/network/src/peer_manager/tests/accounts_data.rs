@@ -107,6 +107,60 @@ async fn broadcast() {
     assert_eq!(got1.accounts_data.as_set(), want.as_set());
 }
 
+#[tokio::test]
+async fn periodic_full_sync() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+
+    let take_full_sync_request = |ev| match ev {
+        peer::testonly::Event::Network(PME::MessageProcessed(
+            tcp::Tier::T2,
+            PeerMessage::SyncAccountsData(msg),
+        )) if !msg.incremental && msg.requesting_full_sync => Some(msg),
+        _ => None,
+    };
+
+    tracing::info!(target:"test", "Connect to a peer, expect the initial full sync.");
+    let mut peer = pm
+        .start_outbound(chain.clone(), chain.make_config(rng), tcp::Tier::T2)
+        .await
+        .handshake(&clock.clock())
+        .await;
+    let mut events = peer.events.from_now();
+    peer.events.recv_until(take_full_sync_request).await;
+
+    tracing::info!(target:"test", "Send some data.");
+    let data = chain.make_tier1_data(rng, &clock.clock());
+    let want: HashSet<_> = [data[0].clone()].into_iter().collect();
+    peer.send(PeerMessage::SyncAccountsData(SyncAccountsData {
+        accounts_data: want.iter().cloned().collect(),
+        incremental: true,
+        requesting_full_sync: false,
+    }))
+    .await;
+    pm.wait_for_accounts_data(&want).await;
+
+    tracing::info!(target:"test", "Expect a full sync with the data after the interval.");
+    clock.advance(peer_manager::network_state::ACCOUNTS_DATA_FULL_SYNC_INTERVAL);
+    loop {
+        let got = events.recv_until(take_full_sync_request).await;
+        if got.accounts_data.as_set() == want.iter().collect() {
+            break;
+        }
+    }
+}
+
 // Test with 3 peer managers connected sequentially: 1-2-3
 // All of them are validators.
 // No matter what the order of shifting into the epoch,
@@ -0,0 +1,151 @@
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::{
+    Encoding, Handshake, PartialEdgeInfo, PartialEncodedChunkRequestMsg, PeerMessage,
+    RoutedMessageBody,
+};
+use crate::peer_manager;
+use crate::peer_manager::peer_manager_actor::Event as PME;
+use crate::peer_manager::testonly::{ActorHandler, Event};
+use crate::tcp;
+use crate::testonly::make_rng;
+use crate::testonly::stream;
+use crate::types::{
+    AccountIdOrPeerTrackingShard, NetworkRequests, PeerManagerMessageRequest,
+    PeerManagerMessageResponse,
+};
+use near_o11y::testonly::init_test_logger;
+use near_o11y::WithSpanContextExt;
+use near_primitives::network::PeerId;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::time;
+use near_primitives::version;
+use std::sync::Arc;
+
+/// Connects a raw peer tracking shard 0 to `pm` and makes `pm` aware of the peer's head.
+async fn connect_raw_peer(
+    rng: &mut crate::testonly::Rng,
+    chain: &data::Chain,
+    pm: &ActorHandler,
+    supports_full_chunk_requests: bool,
+) -> stream::Stream {
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+    let mut stream = stream::Stream::new(Some(Encoding::Proto), stream);
+    let peer_key = data::make_secret_key(rng);
+    let peer_id = PeerId::new(peer_key.public_key());
+    let mut sender_chain_info = chain.get_peer_chain_info();
+    sender_chain_info.tracked_shards = vec![0];
+    stream
+        .write(&PeerMessage::Tier2Handshake(Handshake {
+            protocol_version: version::PROTOCOL_VERSION,
+            oldest_supported_version: version::PEER_MIN_ALLOWED_PROTOCOL_VERSION,
+            sender_peer_id: peer_id.clone(),
+            target_peer_id: pm.cfg.node_id(),
+            sender_listen_port: Some(24567),
+            sender_chain_info,
+            partial_edge_info: PartialEdgeInfo::new(&peer_id, &pm.cfg.node_id(), 1, &peer_key),
+            owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: Default::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests,
+        }))
+        .await;
+    match stream.read().await {
+        Ok(PeerMessage::Tier2Handshake { .. }) => {}
+        got => panic!("got = {got:?}, want Handshake"),
+    }
+    pm.wait_for_direct_connection(peer_id).await;
+
+    let mut events = pm.events.from_now();
+    let block = chain.blocks.last().unwrap().clone();
+    stream.write(&PeerMessage::Block(block.clone())).await;
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::MessageProcessed(tcp::Tier::T2, PeerMessage::Block(got)))
+                if got == block =>
+            {
+                Some(())
+            }
+            _ => None,
+        })
+        .await;
+    stream
+}
+
+/// Requests a full chunk from the peers tracking shard 0 and returns the body of the request
+/// received by `stream`.
+async fn request_full_chunk(
+    pm: &ActorHandler,
+    stream: &mut stream::Stream,
+    request: PartialEncodedChunkRequestMsg,
+) -> RoutedMessageBody {
+    let req = PeerManagerMessageRequest::NetworkRequests(NetworkRequests::FullChunkRequest {
+        target: AccountIdOrPeerTrackingShard {
+            account_id: None,
+            prefer_peer: true,
+            shard_id: 0,
+            only_archival: false,
+            min_height: 0,
+        },
+        request,
+        create_time: pm.clock.now(),
+    });
+    match pm.actix.addr.send(req.with_span_context()).await.unwrap() {
+        PeerManagerMessageResponse::NetworkResponses(_) => {}
+        resp => panic!("unexpected response: {resp:?}"),
+    }
+    loop {
+        if let PeerMessage::Routed(msg) = stream.read().await.unwrap() {
+            return msg.msg.body;
+        }
+    }
+}
+
+// Full chunk requests are sent only to the peers which advertised the support for them, the
+// other peers get the partial encoded chunk request for the same parts instead.
+#[tokio::test]
+async fn full_chunk_request_only_to_supporting_peers() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+    let request = PartialEncodedChunkRequestMsg {
+        chunk_hash: ChunkHash(data::make_hash(rng)),
+        part_ords: vec![0, 1],
+        tracking_shards: Default::default(),
+    };
+
+    tracing::info!(target:"test", "connect a peer without the full chunk requests support");
+    let mut old_peer = connect_raw_peer(rng, &chain, &pm, false).await;
+    assert_eq!(
+        RoutedMessageBody::PartialEncodedChunkRequest(request.clone()),
+        request_full_chunk(&pm, &mut old_peer, request.clone()).await,
+    );
+    let mut events = pm.events.from_now();
+    drop(old_peer);
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(_)) => Some(()),
+            _ => None,
+        })
+        .await;
+
+    tracing::info!(target:"test", "connect a peer supporting the full chunk requests");
+    let mut new_peer = connect_raw_peer(rng, &chain, &pm, true).await;
+    assert_eq!(
+        RoutedMessageBody::FullChunkRequest(request.clone()),
+        request_full_chunk(&pm, &mut new_peer, request).await,
+    );
+}
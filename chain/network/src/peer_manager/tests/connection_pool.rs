@@ -1,7 +1,11 @@
+use crate::broadcast;
 use crate::network_protocol::testonly as data;
 use crate::network_protocol::PeerMessage;
-use crate::network_protocol::{Encoding, Handshake, OwnedAccount, PartialEdgeInfo};
-use crate::peer::peer_actor::ClosingReason;
+use crate::network_protocol::{
+    Encoding, Handshake, OwnedAccount, PartialEdgeInfo, PeerInfo, SignedKeyTransition,
+};
+use crate::peer;
+use crate::peer::peer_actor::{ClosingReason, HandshakeFailedReason};
 use crate::peer_manager;
 use crate::peer_manager::connection;
 use crate::peer_manager::network_state::LIMIT_PENDING_PEERS;
@@ -11,7 +15,9 @@ use crate::private_actix::RegisterPeerError;
 use crate::tcp;
 use crate::testonly::make_rng;
 use crate::testonly::stream::Stream;
+use crate::types::ReasonForBan;
 use near_o11y::testonly::init_test_logger;
+use near_primitives::network::PeerId;
 use near_primitives::time;
 use near_primitives::version::PROTOCOL_VERSION;
 use std::sync::Arc;
@@ -83,7 +89,10 @@ async fn loop_connection() {
     );
 
     // An inbound connection pretending to be a loop should be rejected.
-    let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
     let stream_id = stream.id();
     let port = stream.local_addr.port();
     let mut events = pm.events.from_now();
@@ -103,6 +112,11 @@ async fn loop_connection() {
                 &pm.cfg.node_key,
             ),
             owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: Default::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
         }))
         .await;
     let reason = events
@@ -141,7 +155,10 @@ async fn owned_account_mismatch() {
     .await;
 
     // An inbound connection pretending to be a loop should be rejected.
-    let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
     let stream_id = stream.id();
     let port = stream.local_addr.port();
     let mut events = pm.events.from_now();
@@ -171,6 +188,11 @@ async fn owned_account_mismatch() {
                 }
                 .sign(vc.signer.as_ref()),
             ),
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: Default::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
         }))
         .await;
     let reason = events
@@ -187,6 +209,67 @@ async fn owned_account_mismatch() {
     assert_eq!(ClosingReason::OwnedAccountMismatch, reason);
 }
 
+#[tokio::test]
+async fn handshake_with_zero_nonce() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+    let stream_id = stream.id();
+    let port = stream.local_addr.port();
+    let mut events = pm.events.from_now();
+    let mut stream = Stream::new(Some(Encoding::Proto), stream);
+    let cfg = chain.make_config(rng);
+    stream
+        .write(&PeerMessage::Tier2Handshake(Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            oldest_supported_version: PROTOCOL_VERSION,
+            sender_peer_id: cfg.node_id(),
+            target_peer_id: pm.cfg.node_id(),
+            sender_listen_port: Some(port),
+            sender_chain_info: chain.get_peer_chain_info(),
+            partial_edge_info: PartialEdgeInfo::new(
+                &cfg.node_id(),
+                &pm.cfg.node_id(),
+                0,
+                &cfg.node_key,
+            ),
+            owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: Default::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
+        }))
+        .await;
+    let reason = events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(ev)) if ev.stream_id == stream_id => {
+                Some(ev.reason)
+            }
+            Event::PeerManager(PME::HandshakeCompleted(ev)) if ev.stream_id == stream_id => {
+                panic!("PeerManager accepted the handshake")
+            }
+            _ => None,
+        })
+        .await;
+    assert_eq!(ClosingReason::HandshakeFailed(HandshakeFailedReason::InvalidNonce), reason);
+}
+
 #[tokio::test]
 async fn owned_account_conflict() {
     init_test_logger();
@@ -262,7 +345,10 @@ async fn invalid_edge() {
     for (name, edge) in &testcases {
         for tier in [tcp::Tier::T1, tcp::Tier::T2] {
             tracing::info!(target:"test","{name} {tier:?}");
-            let stream = tcp::Stream::connect(&pm.peer_info(), tier).await.unwrap();
+            let stream =
+                tcp::Stream::connect(&pm.peer_info(), tier, None, tcp::TEST_CONNECT_TIMEOUT)
+                    .await
+                    .unwrap();
             let stream_id = stream.id();
             let port = stream.local_addr.port();
             let mut events = pm.events.from_now();
@@ -284,6 +370,11 @@ async fn invalid_edge() {
                     }
                     .sign(vc.signer.as_ref()),
                 ),
+                supports_zstd_compression: false,
+                sender_listen_addrs: vec![],
+                supported_encodings: Default::default(),
+                supports_message_fragmentation: false,
+                supports_full_chunk_requests: false,
             };
             let handshake = match tier {
                 tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -310,3 +401,269 @@ async fn invalid_edge() {
         }
     }
 }
+
+#[tokio::test]
+async fn validator_evicts_regular_peer() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.max_num_peers = 1;
+    cfg.ideal_connections_lo = 1;
+    cfg.ideal_connections_hi = 1;
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let cfg1 = chain.make_config(rng);
+    let cfg2 = chain.make_config(rng);
+    pm.set_chain_info(peer_manager::testonly::make_chain_info(&chain, &[&cfg2])).await;
+
+    tracing::info!(target:"test", "connect a regular peer");
+    let conn1 = pm.start_inbound(chain.clone(), cfg1.clone()).await.handshake(&clock.clock()).await;
+
+    tracing::info!(target:"test", "another regular peer should be rejected");
+    let reason = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .manager_fail_handshake(&clock.clock())
+        .await;
+    assert_eq!(
+        ClosingReason::RejectedByPeerManager(RegisterPeerError::ConnectionLimitExceeded),
+        reason
+    );
+
+    tracing::info!(target:"test", "a validator should evict the regular peer");
+    let mut events = pm.events.from_now();
+    let conn2 = pm.start_inbound(chain.clone(), cfg2.clone()).await.handshake(&clock.clock()).await;
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(ev))
+                if ev.reason == ClosingReason::PeerManagerRequest =>
+            {
+                Some(())
+            }
+            _ => None,
+        })
+        .await;
+    pm.wait_for_num_connected_peers(1).await;
+    let ready: Vec<_> =
+        pm.with_state(|s| async move { s.tier2.load().ready.keys().cloned().collect() }).await;
+    assert_eq!(vec![cfg2.node_id()], ready);
+    drop(conn1);
+    drop(conn2);
+}
+
+#[tokio::test]
+async fn whitelisted_peer_bypasses_limit() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let cfg1 = chain.make_config(rng);
+    let cfg2 = chain.make_config(rng);
+    let cfg3 = chain.make_config(rng);
+    let mut cfg = chain.make_config(rng);
+    cfg.max_num_peers = 1;
+    cfg.ideal_connections_lo = 1;
+    cfg.ideal_connections_hi = 1;
+    // Whitelisted by PeerId only.
+    cfg.whitelist_nodes = vec![PeerInfo { id: cfg2.node_id(), addr: None, account_id: None }];
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    pm.set_chain_info(peer_manager::testonly::make_chain_info(&chain, &[&cfg3])).await;
+
+    tracing::info!(target:"test", "connect a regular peer");
+    let conn1 = pm.start_inbound(chain.clone(), cfg1.clone()).await.handshake(&clock.clock()).await;
+
+    tracing::info!(target:"test", "a whitelisted peer should be accepted over the limit");
+    let conn2 = pm.start_inbound(chain.clone(), cfg2.clone()).await.handshake(&clock.clock()).await;
+    pm.wait_for_num_connected_peers(2).await;
+
+    tracing::info!(target:"test", "a validator should evict the regular peer, not the whitelisted one");
+    let conn3 = pm.start_inbound(chain.clone(), cfg3.clone()).await.handshake(&clock.clock()).await;
+    pm.wait_for_direct_connection(cfg3.node_id()).await;
+    pm.wait_for_num_connected_peers(2).await;
+    let mut ready: Vec<_> =
+        pm.with_state(|s| async move { s.tier2.load().ready.keys().cloned().collect() }).await;
+    ready.sort();
+    let mut want = vec![cfg2.node_id(), cfg3.node_id()];
+    want.sort();
+    assert_eq!(want, ready);
+    drop(conn1);
+    drop(conn2);
+    drop(conn3);
+}
+
+#[tokio::test]
+async fn whitelist_reload_keeps_rotated_keys() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let cfg1 = chain.make_config(rng);
+    let cfg2 = chain.make_config(rng);
+    let old_key = data::make_secret_key(rng);
+    let old_id = PeerId::new(old_key.public_key());
+    let mut cfg = chain.make_config(rng);
+    cfg.max_num_peers = 1;
+    cfg.ideal_connections_lo = 1;
+    cfg.ideal_connections_hi = 1;
+    // Only a single key transition is kept in the peer store.
+    cfg.peer_store.peer_states_cache_size = 1;
+    cfg.whitelist_nodes = vec![PeerInfo { id: old_id.clone(), addr: None, account_id: None }];
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+
+    tracing::info!(target:"test", "the whitelisted node rotates its key to the key of cfg2");
+    let transitions = vec![
+        SignedKeyTransition::new(&clock.clock(), &old_key, &cfg2.node_key),
+        // Evicts the first transition from the peer store.
+        data::make_key_transition(rng, &clock.clock()),
+    ];
+    pm.with_state(|s| async move { s.add_key_transitions(transitions).await.unwrap() }).await;
+
+    tracing::info!(target:"test", "reload the whitelist");
+    assert!(pm.set_peers_config(1, &old_id.to_string(), &[], "").await);
+
+    tracing::info!(target:"test", "connect a regular peer");
+    let conn1 = pm.start_inbound(chain.clone(), cfg1.clone()).await.handshake(&clock.clock()).await;
+
+    tracing::info!(target:"test", "the rotated whitelisted peer should be accepted over the limit");
+    let conn2 = pm.start_inbound(chain.clone(), cfg2.clone()).await.handshake(&clock.clock()).await;
+    pm.wait_for_num_connected_peers(2).await;
+    drop(conn1);
+    drop(conn2);
+}
+
+/// Sends `n` transactions over `conn` and waits until the last one is processed.
+async fn send_transactions(
+    rng: &mut impl rand::Rng,
+    conn: &peer::testonly::PeerHandle,
+    events: &mut broadcast::Receiver<Event>,
+    n: usize,
+) {
+    let txs: Vec<_> = (0..n).map(|_| data::make_signed_transaction(rng)).collect();
+    for tx in &txs {
+        conn.send(PeerMessage::Transaction(tx.clone())).await;
+    }
+    let last = txs.last().unwrap();
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::MessageProcessed(_, PeerMessage::Transaction(tx)))
+                if &tx == last =>
+            {
+                Some(())
+            }
+            _ => None,
+        })
+        .await;
+}
+
+/// Check that a peer sending more than max_peer_msg_per_min messages gets banned, and that the
+/// messages used for syncing don't count towards the limit.
+#[tokio::test]
+async fn abusive_peer() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    const MAX_PEER_MSG_PER_MIN: usize = 10;
+    let mut cfg = chain.make_config(rng);
+    cfg.max_peer_msg_per_min = Some(MAX_PEER_MSG_PER_MIN);
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let peer_cfg = chain.make_config(rng);
+    let conn =
+        pm.start_inbound(chain.clone(), peer_cfg.clone()).await.handshake(&clock.clock()).await;
+    let mut events = pm.events.from_now();
+
+    tracing::info!(target:"test", "send sync messages over the limit and other messages up to it");
+    for _ in 0..2 * MAX_PEER_MSG_PER_MIN {
+        conn.send(PeerMessage::BlockHeaders(chain.get_block_headers())).await;
+    }
+    // The handshake and the first peers request count towards the limit as well.
+    send_transactions(rng, &conn, &mut events, MAX_PEER_MSG_PER_MIN - 2).await;
+    clock.advance(pm.cfg.peer_stats_period);
+
+    tracing::info!(target:"test", "exceed the limit");
+    send_transactions(rng, &conn, &mut events, 1).await;
+    clock.advance(pm.cfg.peer_stats_period);
+    let reason = events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(ev)) => Some(ev.reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(ClosingReason::Ban(ReasonForBan::Abusive), reason);
+
+    tracing::info!(target:"test", "the banned peer fails to reconnect");
+    let reason = pm
+        .start_inbound(chain.clone(), peer_cfg)
+        .await
+        .manager_fail_handshake(&clock.clock())
+        .await;
+    assert_eq!(ClosingReason::RejectedByPeerManager(RegisterPeerError::Banned), reason);
+}
+
+#[tokio::test]
+async fn outbound_only_peer() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+    let mut cfg = chain.make_config(rng);
+    cfg.node_addr = None;
+    let outbound_only = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+
+    tracing::info!(target:"test", "connect from the node which doesn't listen");
+    outbound_only.connect_to(&pm.peer_info(), tcp::Tier::T2).await;
+    let id = outbound_only.peer_info().id;
+    pm.wait_for_direct_connection(id.clone()).await;
+
+    tracing::info!(target:"test", "the node is not advertised");
+    let peers = pm.with_state(|s| async move { s.peer_store.healthy_peers(100) }).await;
+    assert!(peers.iter().all(|p| p.id != id));
+}
@@ -1,5 +1,7 @@
 mod accounts_data;
+mod chunk_request;
 mod connection_pool;
+mod next_epoch;
 mod nonce;
 mod routing;
 mod tier1;
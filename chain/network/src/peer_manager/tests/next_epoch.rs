@@ -0,0 +1,55 @@
+use crate::network_protocol::testonly as data;
+use crate::peer_manager;
+use crate::peer_manager::testonly::start as start_pm;
+use crate::tcp;
+use crate::testonly::make_rng;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::time;
+use near_store::db::TestDB;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// A validator of the next epoch should establish TIER2 connections to the other validators of
+// the next epoch in advance, while a node which is not a validator of the next epoch shouldn't.
+#[tokio::test]
+async fn next_epoch_validators_preconnect() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let v0 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let v1 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let v2 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let hub = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    for pm in [&v0, &v1, &v2] {
+        pm.connect_to(&hub.peer_info(), tcp::Tier::T2).await;
+    }
+
+    tracing::info!(target:"test", "TIER1 nodes are {{v0,v1,v2}}, but only {{v0,v1}} are validators of the next epoch");
+    let mut chain_info = peer_manager::testonly::make_chain_info(&chain, &[&v0.cfg, &v1.cfg]);
+    chain_info.next_epoch_accounts = chain_info.tier1_accounts.clone();
+    chain_info.tier1_accounts =
+        peer_manager::testonly::make_chain_info(&chain, &[&v0.cfg, &v1.cfg, &v2.cfg])
+            .tier1_accounts;
+    for pm in [&v0, &v1, &v2, &hub] {
+        pm.set_chain_info(chain_info.clone()).await;
+    }
+    let mut data = HashSet::new();
+    for pm in [&v0, &v1, &v2] {
+        data.extend(pm.tier1_advertise_proxies(&clock.clock()).await);
+    }
+    for pm in [&v0, &v2] {
+        pm.wait_for_accounts_data(&data).await;
+    }
+
+    tracing::info!(target:"test", "v2 is not a validator of the next epoch, so it doesn't pre-connect");
+    v2.next_epoch_connect(&clock.clock()).await;
+    let v2_peers = v2.with_state(|s| async move { s.tier2.load().ready.len() }).await;
+    assert_eq!(v2_peers, 1);
+
+    tracing::info!(target:"test", "v0 pre-connects to v1");
+    v0.next_epoch_connect(&clock.clock()).await;
+    v0.wait_for_direct_connection(v1.cfg.node_id()).await;
+}
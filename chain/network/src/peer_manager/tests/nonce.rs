@@ -60,7 +60,10 @@ async fn test_nonces() {
         )
         .await;
 
-        let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+        let stream =
+            tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+                .await
+                .unwrap();
         let mut stream = stream::Stream::new(Some(Encoding::Proto), stream);
         let peer_key = data::make_secret_key(rng);
         let peer_id = PeerId::new(peer_key.public_key());
@@ -75,6 +78,11 @@ async fn test_nonces() {
             sender_chain_info: chain.get_peer_chain_info(),
             partial_edge_info: PartialEdgeInfo::new(&peer_id, &pm.cfg.node_id(), test.0, &peer_key),
             owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: Default::default(),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
         });
         stream.write(&handshake).await;
         if test.1 {
@@ -1,12 +1,14 @@
 use crate::blacklist;
 use crate::broadcast;
+use crate::concurrency::rate;
 use crate::config::NetworkConfig;
 use crate::network_protocol::testonly as data;
-use crate::network_protocol::{Edge, Encoding, Ping, Pong, RoutedMessageBody, RoutingTableUpdate};
-use crate::peer;
-use crate::peer::peer_actor::{
-    ClosingReason, ConnectionClosedEvent, DROP_DUPLICATED_MESSAGES_PERIOD,
+use crate::network_protocol::{
+    Edge, Encoding, PeerIdOrHash, Ping, Pong, RawRoutedMessage, RoutedMessageBody,
+    RoutingTableUpdate, SignedKeyTransition,
 };
+use crate::peer;
+use crate::peer::peer_actor::{ClosingReason, ConnectionClosedEvent};
 use crate::peer_manager;
 use crate::peer_manager::peer_manager_actor::Event as PME;
 use crate::peer_manager::testonly::start as start_pm;
@@ -417,6 +419,186 @@ async fn ping_jump() {
     wait_for_pong(&mut pm0_ev, Pong { nonce: 0, source: id2.clone() }).await;
 }
 
+// test ping without a direct connection, with the routed messages verified in batches
+#[tokio::test]
+async fn ping_jump_batch_verify() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    tracing::info!(target:"test", "start three nodes");
+    let make_config = |rng: &mut Rng| NetworkConfig {
+        batch_verify_routed_messages: true,
+        ..chain.make_config(rng)
+    };
+    let pm0 = start_pm(clock.clock(), TestDB::new(), make_config(rng), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), make_config(rng), chain.clone()).await;
+    let pm2 = start_pm(clock.clock(), TestDB::new(), make_config(rng), chain.clone()).await;
+
+    let id0 = pm0.cfg.node_id();
+    let id1 = pm1.cfg.node_id();
+    let id2 = pm2.cfg.node_id();
+
+    tracing::info!(target:"test", "connect nodes in a line");
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    pm1.connect_to(&pm2.peer_info(), tcp::Tier::T2).await;
+
+    tracing::info!(target:"test", "wait for {id0} routing table");
+    pm0.wait_for_routing_table(&[
+        (id1.clone(), vec![id1.clone()]),
+        (id2.clone(), vec![id1.clone()]),
+    ])
+    .await;
+    tracing::info!(target:"test", "wait for {id2} routing table");
+    pm2.wait_for_routing_table(&[
+        (id0.clone(), vec![id1.clone()]),
+        (id1.clone(), vec![id1.clone()]),
+    ])
+    .await;
+
+    // capture event streams before pinging
+    let mut pm0_ev = pm0.events.from_now();
+    let mut pm2_ev = pm2.events.from_now();
+
+    tracing::info!(target:"test", "send ping from {id0} to {id2}");
+    pm0.send_ping(&clock.clock(), 0, id2.clone()).await;
+
+    tracing::info!(target:"test", "await ping at {id2}");
+    wait_for_ping(&mut pm2_ev, Ping { nonce: 0, source: id0.clone() }).await;
+
+    tracing::info!(target:"test", "await pong at {id0}");
+    wait_for_pong(&mut pm0_ev, Pong { nonce: 0, source: id2.clone() }).await;
+
+    tracing::info!(target:"test", "verify a batch with an invalid signature");
+    let clock = clock.clock();
+    let got = pm0
+        .with_state(move |s| async move {
+            let make_msg = |nonce| {
+                let body = RoutedMessageBody::Ping(Ping { nonce, source: s.config.node_id() });
+                s.sign_message(
+                    &clock,
+                    RawRoutedMessage { target: PeerIdOrHash::PeerId(id2.clone()), body },
+                )
+            };
+            let valid = make_msg(1);
+            let mut invalid = make_msg(2);
+            invalid.msg.body = make_msg(3).msg.body;
+            let (a, b) =
+                tokio::join!(s.verify_routed_message(valid), s.verify_routed_message(invalid));
+            (a.unwrap().1, b.unwrap().1)
+        })
+        .await;
+    assert_eq!((true, false), got);
+}
+
+// test pinging multiple targets through the routing layer
+#[tokio::test]
+async fn ping_peers() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    tracing::info!(target:"test", "start three nodes");
+    let pm0 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm2 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+
+    let id0 = pm0.cfg.node_id();
+    let id1 = pm1.cfg.node_id();
+    let id2 = pm2.cfg.node_id();
+
+    tracing::info!(target:"test", "connect nodes in a line");
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    pm1.connect_to(&pm2.peer_info(), tcp::Tier::T2).await;
+
+    tracing::info!(target:"test", "wait for {id0} routing table");
+    pm0.wait_for_routing_table(&[
+        (id1.clone(), vec![id1.clone()]),
+        (id2.clone(), vec![id1.clone()]),
+    ])
+    .await;
+    tracing::info!(target:"test", "wait for {id2} routing table");
+    pm2.wait_for_routing_table(&[
+        (id0.clone(), vec![id1.clone()]),
+        (id1.clone(), vec![id1.clone()]),
+    ])
+    .await;
+
+    tracing::info!(target:"test", "ping from {id0}");
+    let unreachable = data::make_peer_id(rng);
+    let targets = [
+        id1.to_string(),
+        id2.to_string(),
+        unreachable.to_string(),
+        "unknown.near".to_string(),
+        "!invalid".to_string(),
+    ];
+    let view = pm0.ping_peers(&targets, time::Duration::seconds(10)).await;
+    let got: Vec<_> = view
+        .results
+        .iter()
+        .map(|r| (r.target.clone(), r.next_hops.clone(), r.pong_hops, r.error.clone()))
+        .collect();
+    let want = vec![
+        (targets[0].clone(), vec![id1.clone()], Some(1), None),
+        (targets[1].clone(), vec![id1.clone()], Some(2), None),
+        (targets[2].clone(), vec![], None, Some("no route".to_string())),
+        (targets[3].clone(), vec![], None, Some("unknown account".to_string())),
+        (targets[4].clone(), vec![], None, Some("neither a PeerId nor an AccountId".to_string())),
+    ];
+    assert_eq!(want, got);
+    assert_eq!(Some(id2), view.results[1].peer_id);
+    assert!(view.results[1].rtt_ms.is_some());
+}
+
+// test inspecting the routes via the debug API
+#[tokio::test]
+async fn route_info() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    tracing::info!(target:"test", "start three nodes");
+    let pm0 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm2 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+
+    let id0 = pm0.cfg.node_id();
+    let id1 = pm1.cfg.node_id();
+    let id2 = pm2.cfg.node_id();
+
+    tracing::info!(target:"test", "connect nodes in a line");
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    pm1.connect_to(&pm2.peer_info(), tcp::Tier::T2).await;
+
+    tracing::info!(target:"test", "wait for {id0} routing table");
+    pm0.wait_for_routing_table(&[
+        (id1.clone(), vec![id1.clone()]),
+        (id2.clone(), vec![id1.clone()]),
+    ])
+    .await;
+
+    let view = pm0.route_info(&id2.to_string()).await;
+    assert_eq!(Some(id2), view.peer_id);
+    assert_eq!(vec![id1.clone()], view.next_hops);
+    assert_eq!(Some(2), view.distance);
+    assert_eq!(pm0.cfg.routed_message_ttl, view.routed_message_ttl);
+    assert!(view.routing_table_updated_at_ms.is_some());
+    assert_eq!(None, view.error);
+
+    let view = pm0.route_info(&data::make_peer_id(rng).to_string()).await;
+    assert_eq!((vec![], None, None), (view.next_hops, view.distance, view.error));
+
+    let view = pm0.route_info("unknown.near").await;
+    assert_eq!((None, Some("unknown account".to_string())), (view.peer_id, view.error));
+}
+
 // test that ping over an indirect connection with ttl=2 is delivered
 #[tokio::test]
 async fn test_dont_drop_after_ttl() {
@@ -594,7 +776,7 @@ async fn test_dropping_duplicate_messages() {
     tracing::info!(target:"test", "await pong at {id0}");
     wait_for_pong(&mut pm0_ev, Pong { nonce: 1, source: id2.clone() }).await;
 
-    clock.advance(DROP_DUPLICATED_MESSAGES_PERIOD + time::Duration::milliseconds(1));
+    clock.advance(pm1.cfg.routed_message_dedup_period + time::Duration::milliseconds(1));
 
     tracing::info!(target:"test", "send ping from {id0} to {id2}");
     pm0.send_ping(&clock.clock(), 1, id2.clone()).await;
@@ -776,6 +958,41 @@ async fn blacklist_all() {
     pm1.wait_for_routing_table(&[]).await;
 }
 
+// test node 0 blacklisting node 1 after the config reload
+#[tokio::test]
+async fn blacklist_reload() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    tracing::info!(target:"test", "start two nodes");
+    let cfgs = make_configs(&chain, rng, 2, 1, true);
+    let pm0 = start_pm(clock.clock(), TestDB::new(), cfgs[0].clone(), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), cfgs[1].clone(), chain.clone()).await;
+
+    let id0 = pm0.cfg.node_id();
+    let id1 = pm1.cfg.node_id();
+
+    tracing::info!(target:"test", "wait for {id0} routing table");
+    pm0.wait_for_routing_table(&[(id1.clone(), vec![id1.clone()])]).await;
+
+    tracing::info!(target:"test", "reject invalid settings");
+    let max_num_peers = pm0.cfg.max_num_peers;
+    assert!(!pm0.set_peers_config(max_num_peers, "", &["not an address"], "").await);
+    assert!(!pm0.set_peers_config(pm0.cfg.ideal_connections_hi - 1, "", &[], "").await);
+
+    tracing::info!(target:"test", "blacklist {id1}");
+    let addr1 = (**cfgs[1].node_addr.as_ref().unwrap()).to_string();
+    assert!(pm0.set_peers_config(max_num_peers, "", &[&addr1], "").await);
+
+    tracing::info!(target:"test", "wait for {id0} routing table");
+    pm0.wait_for_routing_table(&[]).await;
+    tracing::info!(target:"test", "wait for {id1} routing table");
+    pm1.wait_for_routing_table(&[]).await;
+}
+
 // Spawn 3 nodes with max peers configured to 2, then allow them to connect to each other in a triangle.
 // Spawn a fourth node and see it fail to connect since the first three are at max capacity.
 #[tokio::test]
@@ -893,7 +1110,10 @@ async fn ttl() {
         chain,
         force_encoding: Some(Encoding::Proto),
     };
-    let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
     let mut peer = peer::testonly::PeerHandle::start_endpoint(clock.clock(), cfg, stream).await;
     peer.complete_handshake().await;
     pm.wait_for_routing_table(&[(peer.cfg.id(), vec![peer.cfg.id()])]).await;
@@ -948,7 +1168,10 @@ async fn repeated_data_in_sync_routing_table() {
         chain,
         force_encoding: Some(Encoding::Proto),
     };
-    let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
     let mut peer = peer::testonly::PeerHandle::start_endpoint(clock.clock(), cfg, stream).await;
     peer.complete_handshake().await;
 
@@ -998,10 +1221,10 @@ async fn repeated_data_in_sync_routing_table() {
         edges_want.insert(data::make_edge(&peer.cfg.network.node_key, &key, 1));
         accounts_want.insert(data::make_announce_account(rng));
         // Send all the data created so far. PeerManager is expected to discard the duplicates.
-        peer.send(PeerMessage::SyncRoutingTable(RoutingTableUpdate {
-            edges: edges_want.iter().cloned().collect(),
-            accounts: accounts_want.iter().cloned().collect(),
-        }))
+        peer.send(PeerMessage::SyncRoutingTable(RoutingTableUpdate::new(
+            edges_want.iter().cloned().collect(),
+            accounts_want.iter().cloned().collect(),
+        )))
         .await;
     }
 }
@@ -1085,11 +1308,7 @@ async fn no_edge_broadcast_after_restart() {
     let mut total_edges = stored_edges.clone();
     total_edges.extend(fresh_edges.iter().cloned());
     let events = peer.events.from_now();
-    peer.send(PeerMessage::SyncRoutingTable(RoutingTableUpdate {
-        edges: total_edges,
-        accounts: vec![],
-    }))
-    .await;
+    peer.send(PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_edges(total_edges))).await;
 
     // Wait for the fresh edges to be broadcasted back.
     tracing::info!(target: "test", "wait_for_edges(<fresh edges>)");
@@ -1377,3 +1596,138 @@ async fn connect_to_unbanned_peer() {
     drop(pm0);
     drop(pm1);
 }
+
+/// Check that the operator can disconnect a peer, and ban it, at runtime.
+#[tokio::test]
+async fn disconnect_by_operator() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut pm0 =
+        start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let mut pm1 =
+        start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+
+    tracing::info!(target:"test", "pm1 disconnects pm0 without a ban");
+    let stream_id = pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    assert!(pm1.disconnect_by_operator(&pm0.cfg.node_id(), false).await);
+    wait_for_stream_closed(&mut pm0.events, stream_id).await;
+    wait_for_stream_closed(&mut pm1.events, stream_id).await;
+
+    tracing::info!(target:"test", "pm0 reconnects, then pm1 bans it");
+    let stream_id = pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    assert!(pm1.disconnect_by_operator(&pm0.cfg.node_id(), true).await);
+    wait_for_stream_closed(&mut pm0.events, stream_id).await;
+    assert_eq!(
+        ClosingReason::Ban(ReasonForBan::Manual),
+        wait_for_stream_closed(&mut pm1.events, stream_id).await
+    );
+
+    tracing::info!(target:"test", "pm0 fails to reconnect to pm1");
+    let got_reason = pm1
+        .start_inbound(chain.clone(), pm0.cfg.clone())
+        .await
+        .manager_fail_handshake(&clock.clock())
+        .await;
+    assert_eq!(ClosingReason::RejectedByPeerManager(RegisterPeerError::Banned), got_reason);
+
+    tracing::info!(target:"test", "disconnecting a peer which is not connected is a no-op");
+    assert!(!pm1.disconnect_by_operator(&pm0.cfg.node_id(), false).await);
+}
+
+/// Check that the operator can close all the connections at runtime.
+#[tokio::test]
+async fn reconnect_all_by_operator() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut pm0 =
+        start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let mut pm1 =
+        start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let mut pm2 =
+        start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+
+    tracing::info!(target:"test", "connect pm0 and pm2 to pm1");
+    let stream0 = pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    let stream2 = pm2.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+
+    tracing::info!(target:"test", "pm1 closes all its connections");
+    assert_eq!(2, pm1.reconnect_all(false).await);
+    wait_for_stream_closed(&mut pm0.events, stream0).await;
+    wait_for_stream_closed(&mut pm2.events, stream2).await;
+    assert_eq!(
+        ClosingReason::PeerManagerRequest,
+        wait_for_stream_closed(&mut pm1.events, stream0).await
+    );
+
+    tracing::info!(target:"test", "nothing to close anymore");
+    assert_eq!(0, pm1.reconnect_all(true).await);
+}
+
+// Test that the key transitions received from a peer above the rate limit are dropped.
+#[tokio::test]
+async fn key_transitions_rate_limit() {
+    abort_on_panic();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.key_transitions_rate_limit = rate::Limit { qps: 1., burst: 2 };
+    let pm = start_pm(clock.clock(), TestDB::new(), cfg, chain.clone()).await;
+    let cfg = peer::testonly::PeerConfig {
+        network: chain.make_config(rng),
+        chain,
+        force_encoding: Some(Encoding::Proto),
+    };
+    let stream =
+        tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2, None, tcp::TEST_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+    let mut peer = peer::testonly::PeerHandle::start_endpoint(clock.clock(), cfg, stream).await;
+    peer.complete_handshake().await;
+
+    let transitions: Vec<_> =
+        (0..3).map(|_| data::make_key_transition(rng, &clock.clock())).collect();
+    let (pm, peer) = (&pm, &peer);
+    let send = move |key_transitions: Vec<SignedKeyTransition>| {
+        let mut events = pm.events.from_now();
+        async move {
+            peer.send(PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_key_transitions(
+                key_transitions.clone(),
+            )))
+            .await;
+            events
+                .recv_until(|ev| match ev {
+                    Event::PeerManager(PME::MessageProcessed(
+                        tcp::Tier::T2,
+                        PeerMessage::SyncRoutingTable(rtu),
+                    )) if rtu.key_transitions == key_transitions => Some(()),
+                    _ => None,
+                })
+                .await;
+        }
+    };
+    let stored = move || pm.with_state(|s| async move { s.peer_store.key_transitions() });
+
+    tracing::info!(target:"test", "send transitions within the limit");
+    send(transitions[..2].to_vec()).await;
+    tracing::info!(target:"test", "send a transition above the limit");
+    send(transitions[2..].to_vec()).await;
+    let got: HashSet<_> = stored().await.into_iter().collect();
+    assert_eq!(transitions[..2].iter().cloned().collect::<HashSet<_>>(), got);
+
+    tracing::info!(target:"test", "send the dropped transition again once the limit allows it");
+    clock.advance(time::Duration::seconds(1));
+    send(transitions[2..].to_vec()).await;
+    let got: HashSet<_> = stored().await.into_iter().collect();
+    assert_eq!(transitions.iter().cloned().collect::<HashSet<_>>(), got);
+}
@@ -1,6 +1,8 @@
 use crate::config;
 use crate::network_protocol::testonly as data;
-use crate::network_protocol::{PeerAddr, PeerMessage, RoutedMessageBody};
+use crate::network_protocol::{
+    PartialEncodedChunkForwardMsg, PeerAddr, PeerMessage, RoutedMessageBody,
+};
 use crate::peer_manager;
 use crate::peer_manager::peer_manager_actor::Event as PME;
 use crate::peer_manager::testonly::start as start_pm;
@@ -133,11 +135,14 @@ async fn first_proxy_advertisement() {
     // currently returns a validator config with its own server addr in the list of TIER1 proxies.
     // You might want to set it explicitly within this test to not rely on defaults.
     pm.set_chain_info(chain_info).await;
-    let got = pm.tier1_advertise_proxies(&clock.clock()).await;
+    let got = pm.tier1_advertise_proxies(&clock.clock()).await.unwrap();
     assert_eq!(
-        got.unwrap().proxies,
+        got.proxies,
         vec![PeerAddr { peer_id: pm.cfg.node_id(), addr: **pm.cfg.node_addr.as_ref().unwrap() }]
     );
+    // The node has connected to its proxy itself, so it knows the latency.
+    let hinted: Vec<_> = got.proxy_hints.iter().map(|h| h.peer_id.clone()).collect();
+    assert_eq!(hinted, vec![pm.cfg.node_id()]);
 }
 
 #[tokio::test]
@@ -357,6 +362,58 @@ async fn proxy_change() {
     drop(p0);
 }
 
+// A proxy should relay TIER1 messages only to the current TIER1 validators, even if the
+// connection to a former TIER1 validator hasn't been closed yet.
+#[tokio::test]
+async fn proxy_relays_only_to_tier1_validators() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    // v0 has proxy p0.
+    // v1 has no proxies.
+    let p0cfg = chain.make_config(rng);
+    let mut v0cfg = chain.make_config(rng);
+    v0cfg.validator.as_mut().unwrap().proxies = config::ValidatorProxies::Static(vec![PeerAddr {
+        peer_id: p0cfg.node_id(),
+        addr: **p0cfg.node_addr.as_ref().unwrap(),
+    }]);
+    let mut v1cfg = chain.make_config(rng);
+    v1cfg.validator.as_mut().unwrap().proxies = config::ValidatorProxies::Static(vec![]);
+
+    let p0 = start_pm(clock.clock(), TestDB::new(), p0cfg, chain.clone()).await;
+    let v0 = start_pm(clock.clock(), TestDB::new(), v0cfg, chain.clone()).await;
+    let v1 = start_pm(clock.clock(), TestDB::new(), v1cfg, chain.clone()).await;
+    let hub = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    hub.connect_to(&p0.peer_info(), tcp::Tier::T2).await;
+    hub.connect_to(&v0.peer_info(), tcp::Tier::T2).await;
+    hub.connect_to(&v1.peer_info(), tcp::Tier::T2).await;
+
+    tracing::info!(target:"test", "TIER1 connections get established: v0 -> p0 <- v1.");
+    let chain_info = peer_manager::testonly::make_chain_info(&chain, &[&v0.cfg, &v1.cfg]);
+    for pm in [&v0, &v1, &p0, &hub] {
+        pm.set_chain_info(chain_info.clone()).await;
+    }
+    establish_connections(&clock.clock(), &[&v0, &v1, &p0, &hub]).await;
+    tracing::info!(target:"test", "Send message v1 -> v0 over TIER1.");
+    send_and_recv_tier1_message(rng, &clock.clock(), &v1, &v0, tcp::Tier::T1).await;
+
+    tracing::info!(target:"test", "p0 learns that v0 is no longer a TIER1 node.");
+    let chain_info = peer_manager::testonly::make_chain_info(&chain, &[&v1.cfg]);
+    p0.set_chain_info(chain_info).await;
+    tracing::info!(target:"test", "p0 drops the message v1 -> v0 instead of relaying it.");
+    let mut events = p0.events.from_now();
+    send_tier1_message(rng, &clock.clock(), &v1, &v0).await.expect("routing info not available");
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::RoutedMessageDropped) => Some(()),
+            _ => None,
+        })
+        .await;
+}
+
 #[tokio::test]
 async fn tier2_routing_using_accounts_data() {
     init_test_logger();
@@ -417,3 +474,49 @@ async fn stun_self_discovery() {
     stun_server1.close().await;
     stun_server2.close().await;
 }
+
+#[tokio::test]
+async fn chunk_part_forwards() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    tracing::info!(target:"test", "start 2 validators and connect them over TIER1");
+    let pm0 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), chain.make_config(rng), chain.clone()).await;
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    let chain_info = peer_manager::testonly::make_chain_info(&chain, &[&pm0.cfg, &pm1.cfg]);
+    for pm in [&pm0, &pm1] {
+        pm.set_chain_info(chain_info.clone()).await;
+    }
+    establish_connections(&clock.clock(), &[&pm0, &pm1]).await;
+
+    tracing::info!(target:"test", "send a chunk part forward pm0 -> pm1");
+    let chunk = chain.chunks.values().next().unwrap().clone();
+    let want = RoutedMessageBody::PartialEncodedChunkForward(
+        PartialEncodedChunkForwardMsg::from_header_and_parts(
+            &chunk.cloned_header(),
+            data::make_chunk_parts(chunk)[..2].to_vec(),
+        ),
+    );
+    let mut events = pm1.events.from_now();
+    let target = pm1.cfg.validator.as_ref().unwrap().signer.validator_id().clone();
+    let clock = clock.clock();
+    let msg = want.clone();
+    assert!(
+        pm0.with_state(move |s| async move { s.send_message_to_account(&clock, &target, msg) })
+            .await
+    );
+    let got = events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::MessageProcessed(tier, PeerMessage::Routed(got))) => {
+                Some((tier, got))
+            }
+            _ => None,
+        })
+        .await;
+    assert_eq!(tcp::Tier::T1, got.0);
+    assert_eq!(want, got.1.body);
+}
@@ -1,6 +1,6 @@
 use crate::network_protocol::{
-    Encoding, Handshake, HandshakeFailureReason, PartialEdgeInfo, PeerChainInfoV2, PeerIdOrHash,
-    PeerMessage, Ping, Pong, RawRoutedMessage, RoutedMessageBody, RoutingTableUpdate,
+    Encoding, EncodingSet, Handshake, HandshakeFailureReason, PartialEdgeInfo, PeerChainInfoV2,
+    PeerIdOrHash, PeerMessage, Ping, Pong, RawRoutedMessage, RoutedMessageBody, RoutingTableUpdate,
 };
 use crate::types::StateResponseInfo;
 use bytes::buf::{Buf, BufMut};
@@ -164,6 +164,11 @@ impl Connection {
                 &self.secret_key,
             ),
             owned_account: None,
+            supports_zstd_compression: false,
+            sender_listen_addrs: vec![],
+            supported_encodings: EncodingSet::new(&[Encoding::Proto]),
+            supports_message_fragmentation: false,
+            supports_full_chunk_requests: false,
         });
 
         self.write_message(&handshake).await.map_err(ConnectError::IO)?;
@@ -187,7 +192,7 @@ impl Connection {
     }
 
     async fn write_message(&mut self, msg: &PeerMessage) -> io::Result<()> {
-        let mut msg = msg.serialize(Encoding::Proto);
+        let mut msg = msg.serialize(Encoding::Proto).unwrap();
         let mut buf = (msg.len() as u32).to_le_bytes().to_vec();
         buf.append(&mut msg);
         self.stream.write_all(&buf).await
@@ -197,7 +202,7 @@ impl Connection {
     pub async fn send_message(&mut self, msg: DirectMessage) -> io::Result<()> {
         let peer_msg = match msg {
             DirectMessage::AnnounceAccounts(accounts) => {
-                PeerMessage::SyncRoutingTable(RoutingTableUpdate { edges: Vec::new(), accounts })
+                PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_accounts(accounts))
             }
         };
 
@@ -10,7 +10,7 @@ use near_primitives::network::PeerId;
 use near_primitives::time;
 use parking_lot::Mutex;
 use rayon::iter::ParallelBridge;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 #[cfg(test)]
@@ -32,6 +32,37 @@ pub struct GraphSnapshot {
     pub edges: im::HashMap<EdgeKey, Edge>,
     pub local_edges: HashMap<PeerId, Edge>,
     pub next_hops: Arc<NextHopTable>,
+    /// Time the snapshot has been computed. None if the graph has never been updated.
+    pub updated_at: Option<time::Utc>,
+}
+
+impl GraphSnapshot {
+    /// Number of hops on the shortest path from `source` to `target` over the active edges.
+    /// None if `target` is unreachable. O(edges), intended for debugging only.
+    pub fn distance(&self, source: &PeerId, target: &PeerId) -> Option<u32> {
+        let mut adjacency: HashMap<&PeerId, Vec<&PeerId>> = HashMap::new();
+        for (key, edge) in &self.edges {
+            if edge.edge_type() == EdgeState::Active {
+                adjacency.entry(&key.0).or_default().push(&key.1);
+                adjacency.entry(&key.1).or_default().push(&key.0);
+            }
+        }
+        let mut distance = HashMap::from([(source, 0)]);
+        let mut queue = VecDeque::from([source]);
+        while let Some(peer) = queue.pop_front() {
+            let d = distance[peer];
+            if peer == target {
+                return Some(d);
+            }
+            for &neighbor in adjacency.get(peer).into_iter().flatten() {
+                distance.entry(neighbor).or_insert_with(|| {
+                    queue.push_back(neighbor);
+                    d + 1
+                });
+            }
+        }
+        None
+    }
 }
 
 struct Inner {
@@ -268,7 +299,47 @@ impl Inner {
         metrics::PEER_REACHABLE.set(next_hops.len() as i64);
         metrics::EDGE_ACTIVE.set(self.graph.total_active_edges() as i64);
         metrics::EDGE_TOTAL.set(self.edges.len() as i64);
-        GraphSnapshot { edges: self.edges.clone(), local_edges, next_hops }
+        GraphSnapshot {
+            edges: self.edges.clone(),
+            local_edges,
+            next_hops,
+            updated_at: Some(clock.now_utc()),
+        }
+    }
+
+    /// Stores the active edges which are not adjacent to this node in the DB.
+    /// The local edges are skipped, because after a restart they are re-created once the node
+    /// reconnects to its peers.
+    fn persist(&mut self) {
+        let node_id = &self.config.node_id;
+        let edges: Vec<_> = self
+            .edges
+            .values()
+            .filter(|e| e.edge_type() == EdgeState::Active && e.other(node_id).is_none())
+            .cloned()
+            .collect();
+        metrics::EDGE_PERSISTED.set(edges.len() as i64);
+        if let Err(err) = self.store.set_routing_edges(&edges) {
+            tracing::warn!(target: "network", ?err, "Failed to save routing edges");
+        }
+    }
+
+    /// Loads the edges persisted before the restart. They have been verified before being
+    /// stored, so they are not verified again. The peers adjacent to them are considered
+    /// reachable as of now, so that they are not pruned before the node connects to its peers.
+    fn load_persisted(&mut self, clock: &time::Clock) {
+        let now_utc = clock.now_utc();
+        let now = clock.now();
+        let mut loaded = 0;
+        for e in self.store.get_routing_edges() {
+            let key = e.key().clone();
+            if self.update_edge(now_utc, e) {
+                self.peer_reachable_at.insert(key.0, now);
+                self.peer_reachable_at.insert(key.1, now);
+                loaded += 1;
+            }
+        }
+        tracing::info!(target: "network", loaded, "Loaded persisted routing edges");
     }
 }
 
@@ -304,6 +375,24 @@ impl Graph {
         self.snapshot.load_full()
     }
 
+    /// Warm-starts the graph with the edges persisted before the restart, so that the routing
+    /// table is complete as soon as the node connects to its peers, rather than only after they
+    /// send their routing tables.
+    pub fn warm_start(&self, clock: &time::Clock) {
+        let mut inner = self.inner.lock();
+        inner.load_persisted(clock);
+        let snapshot = Arc::new(inner.update(clock, &self.unreliable_peers.load()));
+        self.routing_table.update(snapshot.next_hops.clone());
+        self.snapshot.store(snapshot);
+    }
+
+    /// Persists the current edges of the graph, to warm-start it after a restart.
+    pub async fn persist(self: &Arc<Self>) {
+        // Writing all the edges to DB might take a while, so we execute it on a dedicated thread.
+        let this = self.clone();
+        self.runtime.handle.spawn_blocking(move || this.inner.lock().persist()).await.unwrap()
+    }
+
     pub fn set_unreliable_peers(&self, unreliable_peers: HashSet<PeerId>) {
         self.unreliable_peers.store(Arc::new(unreliable_peers));
     }
@@ -279,3 +279,45 @@ async fn expired_edges() {
     g.simple_update(&clock.clock(), vec![]).await;
     g.check(&[], &[]).await;
 }
+
+#[tokio::test]
+async fn warm_start() {
+    init_test_logger();
+    let clock = time::FakeClock::default();
+    let mut rng = make_rng(87927345);
+    let rng = &mut rng;
+    let node_key = data::make_secret_key(rng);
+    let cfg = GraphConfig {
+        node_id: peer_id(&node_key),
+        prune_unreachable_peers_after: time::Duration::seconds(3),
+        prune_edges_after: None,
+    };
+    let store = store();
+    let g = Arc::new(Graph::new(cfg.clone(), store.clone()));
+
+    let p1 = data::make_secret_key(rng);
+    let p2 = data::make_secret_key(rng);
+    let p3 = data::make_secret_key(rng);
+    let e1 = data::make_edge(&node_key, &p1, 1);
+    let e12 = data::make_edge(&p1, &p2, 1);
+    let e13 = data::make_edge(&p1, &p3, 1);
+    let e23 = data::make_edge_tombstone(&p2, &p3);
+    g.simple_update(&clock.clock(), vec![e1.clone(), e12.clone(), e13.clone(), e23.clone()]).await;
+    g.check(&[e1.clone(), e12.clone(), e13.clone(), e23.clone()], &[]).await;
+    g.persist().await;
+
+    tracing::info!(target:"test", "Restart. Only the non-local active edges are loaded.");
+    let g = Arc::new(Graph::new(cfg.clone(), store));
+    g.warm_start(&clock.clock());
+    g.check(&[e12.clone(), e13.clone()], &[]).await;
+    assert!(g.load().next_hops.is_empty());
+
+    tracing::info!(target:"test", "Once connected to p1, all the peers are routable.");
+    clock.advance(2 * SEC);
+    g.simple_update(&clock.clock(), vec![e1.clone()]).await;
+    g.check(&[e1.clone(), e12.clone(), e13.clone()], &[]).await;
+    let next_hops = g.load().next_hops.clone();
+    for p in [&p1, &p2, &p3] {
+        assert_eq!(next_hops.get(&peer_id(p)), Some(&vec![peer_id(&p1)]));
+    }
+}
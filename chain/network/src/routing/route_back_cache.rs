@@ -1,6 +1,7 @@
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::time;
+use near_primitives::types::BlockHeight;
 use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap};
 
 /// default value for `capacity`
@@ -45,6 +46,10 @@ const DEFAULT_REMOVE_BATCH_SIZE: usize = 100;
 /// - If the cache is not at full capacity, all new records will be stored.
 /// - If a peer try to abuse the system, it will be able to allocate at most
 ///     `capacity / number_of_active_connections` entries.
+///
+/// Additionally a record may be inserted with a block height after which the response
+/// is not useful anymore (see `insert_with_expiry`). Such records are removed by
+/// `remove_expired` as the chain advances, even if the cache is not full.
 pub struct RouteBackCache {
     /// Maximum number of records allowed in the cache.
     capacity: usize,
@@ -64,6 +69,12 @@ pub struct RouteBackCache {
     /// are sorted by the time they arrived from older to newer.
     /// Size: O(capacity)
     record_per_target: BTreeMap<PeerId, BTreeSet<(time::Instant, CryptoHash)>>,
+    /// Block height after which the record expires, for the records which have one.
+    /// Size: O(capacity)
+    expires_at: HashMap<CryptoHash, BlockHeight>,
+    /// Records with expiry height, ordered by the expiry height.
+    /// Size: O(capacity)
+    by_expiry: BTreeSet<(BlockHeight, CryptoHash)>,
 }
 
 impl Default for RouteBackCache {
@@ -87,6 +98,22 @@ impl RouteBackCache {
             main: HashMap::new(),
             size_per_target: BTreeSet::new(),
             record_per_target: BTreeMap::new(),
+            expires_at: HashMap::new(),
+            by_expiry: BTreeSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.main.len()
+    }
+
+    fn forget_expiry(
+        expires_at: &mut HashMap<CryptoHash, BlockHeight>,
+        by_expiry: &mut BTreeSet<(BlockHeight, CryptoHash)>,
+        hash: &CryptoHash,
+    ) {
+        if let Some(height) = expires_at.remove(hash) {
+            by_expiry.remove(&(height, *hash));
         }
     }
 
@@ -110,12 +137,22 @@ impl RouteBackCache {
 
                         for record in to_remove {
                             self.main.remove(&record.1);
+                            Self::forget_expiry(
+                                &mut self.expires_at,
+                                &mut self.by_expiry,
+                                &record.1,
+                            );
                             removed += 1;
                         }
                     }
                     None => {
                         for record in records.iter() {
                             self.main.remove(&record.1);
+                            Self::forget_expiry(
+                                &mut self.expires_at,
+                                &mut self.by_expiry,
+                                &record.1,
+                            );
                             removed += 1;
                         }
                         records.clear();
@@ -154,6 +191,7 @@ impl RouteBackCache {
 
                 for evicted in value.iter() {
                     self.main.remove(&evicted.1);
+                    Self::forget_expiry(&mut self.expires_at, &mut self.by_expiry, &evicted.1);
                 }
 
                 *value = keep;
@@ -184,7 +222,22 @@ impl RouteBackCache {
 
     pub fn remove(&mut self, clock: &time::Clock, hash: &CryptoHash) -> Option<PeerId> {
         self.remove_evicted(clock);
+        self.remove_record(hash)
+    }
+
+    /// Removes the records which expire before `height`.
+    /// Returns the number of removed records.
+    pub fn remove_expired(&mut self, height: BlockHeight) -> usize {
+        let keep = self.by_expiry.split_off(&(height, CryptoHash::default()));
+        let expired = std::mem::replace(&mut self.by_expiry, keep);
+        for (_, hash) in &expired {
+            self.remove_record(hash);
+        }
+        expired.len()
+    }
 
+    fn remove_record(&mut self, hash: &CryptoHash) -> Option<PeerId> {
+        Self::forget_expiry(&mut self.expires_at, &mut self.by_expiry, hash);
         if let Some((time, target)) = self.main.remove(hash) {
             // Number of elements associated with this target
             let mut size = self.record_per_target.get(&target).map(|x| x.len()).unwrap();
@@ -214,7 +267,20 @@ impl RouteBackCache {
         }
     }
 
+    #[cfg(test)]
     pub fn insert(&mut self, clock: &time::Clock, hash: CryptoHash, target: PeerId) {
+        self.insert_with_expiry(clock, hash, target, None)
+    }
+
+    /// Inserts a record, which will be removed once the chain reaches `expires_at` height
+    /// (see `remove_expired`), unless it is removed earlier.
+    pub fn insert_with_expiry(
+        &mut self,
+        clock: &time::Clock,
+        hash: CryptoHash,
+        target: PeerId,
+        expires_at: Option<BlockHeight>,
+    ) {
         if self.main.contains_key(&hash) {
             return;
         }
@@ -224,6 +290,10 @@ impl RouteBackCache {
         let now = clock.now();
 
         self.main.insert(hash, (now, target.clone()));
+        if let Some(height) = expires_at {
+            self.expires_at.insert(hash, height);
+            self.by_expiry.insert((height, hash));
+        }
 
         let mut size = self.record_per_target.get(&target).map_or(0, |x| x.len());
 
@@ -265,6 +335,12 @@ mod test {
         }
 
         assert_eq!(cache.main.len(), total);
+
+        assert_eq!(cache.expires_at.len(), cache.by_expiry.len());
+        for (height, hash) in cache.by_expiry.iter() {
+            assert_eq!(cache.expires_at.get(hash), Some(height));
+            assert!(cache.main.contains_key(hash));
+        }
     }
 
     fn create_message(ix: u8) -> (PeerId, CryptoHash) {
@@ -287,6 +363,33 @@ mod test {
         assert_eq!(cache.get(&hash0), None);
     }
 
+    /// Check records are removed once the chain reaches their expiry height.
+    #[test]
+    fn expired() {
+        let clock = time::FakeClock::default();
+        let mut cache = RouteBackCache::new(100, time::Duration::milliseconds(1000000000), 1);
+        let (peer0, hash0) = create_message(0);
+        let (peer1, hash1) = create_message(1);
+        let (peer2, hash2) = create_message(2);
+
+        cache.insert_with_expiry(&clock.clock(), hash0, peer0, Some(10));
+        cache.insert_with_expiry(&clock.clock(), hash1, peer1.clone(), Some(20));
+        cache.insert(&clock.clock(), hash2, peer2.clone());
+        check_consistency(&cache);
+        assert_eq!(cache.remove_expired(10), 0);
+        assert_eq!(cache.remove_expired(11), 1);
+        check_consistency(&cache);
+        assert_eq!(cache.get(&hash0), None);
+        assert_eq!(cache.get(&hash1), Some(&peer1));
+
+        // Records removed before expiring are forgotten.
+        assert_eq!(cache.remove(&clock.clock(), &hash1), Some(peer1));
+        check_consistency(&cache);
+        assert_eq!(cache.remove_expired(100), 0);
+        assert_eq!(cache.get(&hash2), Some(&peer2));
+        assert_eq!(cache.len(), 1);
+    }
+
     /// Check record is removed after some timeout.
     #[test]
     fn evicted() {
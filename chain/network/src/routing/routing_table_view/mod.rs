@@ -6,7 +6,7 @@ use lru::LruCache;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::time;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockHeight};
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -158,8 +158,22 @@ impl RoutingTableView {
         res
     }
 
-    pub(crate) fn add_route_back(&self, clock: &time::Clock, hash: CryptoHash, peer_id: PeerId) {
-        self.0.lock().route_back.insert(clock, hash, peer_id);
+    pub(crate) fn add_route_back(
+        &self,
+        clock: &time::Clock,
+        hash: CryptoHash,
+        peer_id: PeerId,
+        expires_at: Option<BlockHeight>,
+    ) {
+        self.0.lock().route_back.insert_with_expiry(clock, hash, peer_id, expires_at);
+    }
+
+    /// Removes the route back entries which expire before `height`.
+    /// Returns the number of the removed entries and the number of the remaining ones.
+    pub(crate) fn remove_expired_route_back(&self, height: BlockHeight) -> (usize, usize) {
+        let mut inner = self.0.lock();
+        let removed = inner.route_back.remove_expired(height);
+        (removed, inner.route_back.len())
     }
 
     pub(crate) fn compare_route_back(&self, hash: CryptoHash, peer_id: &PeerId) -> bool {
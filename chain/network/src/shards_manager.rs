@@ -1,23 +1,46 @@
+use std::collections::HashMap;
 use std::time::Instant;
 
 use actix::Message;
-use near_primitives::{hash::CryptoHash, sharding::PartialEncodedChunk};
+use near_primitives::{
+    hash::CryptoHash, network::PeerId, sharding::PartialEncodedChunk, time, types::AccountId,
+};
 
 use crate::types::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    FullChunkResponseMsg, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg,
 };
 
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub enum ShardsManagerRequestFromNetwork {
-    ProcessPartialEncodedChunk(PartialEncodedChunk),
+    ProcessPartialEncodedChunk {
+        partial_encoded_chunk: PartialEncodedChunk,
+        /// Peer from which the chunk has been received, if it came from the network.
+        peer_id: Option<PeerId>,
+    },
     ProcessPartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
     ProcessPartialEncodedChunkResponse {
         partial_encoded_chunk_response: PartialEncodedChunkResponseMsg,
+        /// Peer from which the response has been received.
+        peer_id: PeerId,
         received_time: Instant,
     },
     ProcessPartialEncodedChunkRequest {
         partial_encoded_chunk_request: PartialEncodedChunkRequestMsg,
         route_back: CryptoHash,
     },
+    ProcessFullChunkResponse {
+        full_chunk_response: FullChunkResponseMsg,
+        /// Peer from which the response has been received.
+        peer_id: PeerId,
+        received_time: Instant,
+    },
+    ProcessFullChunkRequest {
+        full_chunk_request: PartialEncodedChunkRequestMsg,
+        route_back: CryptoHash,
+    },
+    /// Round trip times of the direct connections to the owners of the accounts, pushed
+    /// periodically, so that the chunk requests can time out sooner for the faster peers.
+    UpdateAccountRtts(HashMap<AccountId, time::Duration>),
 }
@@ -1,5 +1,5 @@
 use crate::network_protocol::Encoding;
-use crate::network_protocol::{RoutedMessageBody, RoutedMessageV2};
+use crate::network_protocol::{PeerMessage, RoutedMessageBody, RoutedMessageV2};
 use crate::tcp;
 use crate::types::PeerType;
 use near_o11y::metrics::prometheus;
@@ -113,6 +113,61 @@ pub static PEER_CONNECTIONS: Lazy<Gauge<Connection>> =
 pub(crate) static PEER_CONNECTIONS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_peer_connections_total", "Number of connected peers").unwrap()
 });
+pub(crate) static PEER_HANDSHAKES_BY_ENCODING: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_handshakes_by_encoding_total",
+        "Number of handshakes received, by the encoding the peer communicates with, and whether \
+         the peer has been accepted",
+        &["encoding", "result"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_BORSH_HANDSHAKES_BY_PROTOCOL_VERSION: Lazy<IntCounterVec> =
+    Lazy::new(|| {
+        try_create_int_counter_vec(
+        "near_peer_borsh_handshakes_by_protocol_version_total",
+        "Number of handshakes received from the peers communicating using Borsh, by the protocol \
+         version of the peer, or \"unsupported\" if the version is not supported, and whether the \
+         peer has been accepted",
+        &["protocol_version", "result"],
+    )
+    .unwrap()
+    });
+pub(crate) static PEER_HANDSHAKE_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_handshake_failures_total",
+        "Number of connections closed before completing the handshake, by the direction of the \
+         connection and the reason",
+        &["direction", "reason"],
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_CONNECTION_EVICTIONS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_peer_connection_evictions_total",
+        "Number of TIER2 connections closed to make room for a more valuable inbound connection",
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_KEEP_ALIVE_RTT: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_peer_keep_alive_rtt_seconds",
+        "Round trip time of the keep-alive pings sent to the connected peers",
+        exponential_buckets(0.001, 2., 14).unwrap(),
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_KEEP_ALIVE_TIMEOUTS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_peer_keep_alive_timeouts_total",
+        "Number of connections closed because the peer stopped answering the keep-alive pings",
+    )
+    .unwrap()
+});
+
 pub(crate) static PEER_DATA_RECEIVED_BYTES: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter("near_peer_data_received_bytes", "Total data received from peers")
         .unwrap()
@@ -131,6 +186,15 @@ pub(crate) static PEER_MSG_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static ROUTED_MESSAGE_VERIFICATION_BATCH_SIZE: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_routed_message_verification_batch_size",
+        "Number of the routed messages verified together, if batch_verify_routed_messages is enabled",
+        exponential_buckets(1., 2., 12).unwrap(),
+    )
+    .unwrap()
+});
+
 pub(crate) static PEER_MSG_READ_LATENCY: Lazy<Histogram> = Lazy::new(|| {
     try_create_histogram_with_buckets(
         "near_peer_msg_read_latency",
@@ -140,6 +204,15 @@ pub(crate) static PEER_MSG_READ_LATENCY: Lazy<Histogram> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static BLOCK_PROPAGATION_LATENCY: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_block_propagation_latency",
+        "Time between the production of a block (according to its header) and receiving it for the first time, in seconds. Recorded only if measure_block_propagation is enabled",
+        exponential_buckets(0.01, 1.3, 30).unwrap(),
+    )
+    .unwrap()
+});
+
 pub(crate) static PEER_DATA_SENT_BYTES: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter("near_peer_data_sent_bytes", "Total data sent to peers").unwrap()
 });
@@ -152,6 +225,76 @@ pub(crate) static PEER_DATA_READ_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub(crate) static PEER_FRAGMENTED_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_fragmented_messages_total",
+        "Number of messages sent or received split into multiple frames",
+        &["direction"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_MESSAGE_FRAGMENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_fragments_total",
+        "Number of frames of the messages sent or received split into multiple frames",
+        &["direction"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_INJECTED_FAULTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_injected_faults_total",
+        "Number of received messages dropped or delayed by the network fault injection",
+        &["fault"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_RECV_THROTTLED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_recv_throttled_total",
+        "Number of received frames delayed by the per-tier receive rate limit",
+        &["tier"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_INBOUND_IP_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_inbound_ip_rejected_total",
+        "Number of inbound connections dropped because of the limits of their source IP",
+        &["reason"],
+    )
+    .unwrap()
+});
+pub(crate) static DIALER_PEERS_IN_BACKOFF: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_dialer_peers_in_backoff",
+        "Number of peers backing off after failed outbound connection attempts",
+    )
+    .unwrap()
+});
+pub(crate) static DIALER_RATE_LIMITED_ATTEMPTS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_dialer_rate_limited_attempts_total",
+        "Number of outbound connection attempts postponed by the dialer rate limit",
+    )
+    .unwrap()
+});
+pub(crate) static TIER1_PROXY_RELAYED_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_tier1_proxy_relayed_messages_total",
+        "Number of TIER1 messages relayed by this node, acting as a TIER1 proxy",
+        &["type"],
+    )
+    .unwrap()
+});
+pub(crate) static TIER1_CHUNK_PARTS_SENT: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_tier1_chunk_parts_sent_total",
+        "Number of chunk parts and forwards sent over TIER1 only, without the TIER2 copy",
+        &["type"],
+    )
+    .unwrap()
+});
 pub(crate) static PEER_DATA_WRITE_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     try_create_int_gauge_vec(
         "near_peer_write_buffer_size",
@@ -160,6 +303,23 @@ pub(crate) static PEER_DATA_WRITE_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(||
     )
     .unwrap()
 });
+pub(crate) static PEER_MESSAGE_COMPRESSION_RATIO: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_peer_message_compression_ratio",
+        "Ratio of the compressed to the uncompressed size of the compressed sent messages, by message type",
+        &["type"],
+        Some(prometheus::linear_buckets(0.05, 0.05, 20).unwrap()),
+    )
+    .unwrap()
+});
+pub(crate) static PEER_SEND_QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_peer_send_queue_depth",
+        "Number of messages waiting in the send queues of all the connections, by priority",
+        &["priority"],
+    )
+    .unwrap()
+});
 pub(crate) static PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_peer_message_received_by_type_bytes",
@@ -200,6 +360,29 @@ pub(crate) static PEER_MESSAGE_SENT_BY_TYPE_TOTAL: Lazy<IntCounterVec> = Lazy::n
     )
     .unwrap()
 });
+pub(crate) static PEER_ABUSIVE_BANS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_peer_abusive_bans_total",
+        "Number of peers banned for sending more messages per minute than max_peer_msg_per_min",
+    )
+    .unwrap()
+});
+pub(crate) static ACCOUNTS_DATA_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_accounts_data_cache_size",
+        "Number of AccountData entries stored in the accounts data cache",
+    )
+    .unwrap()
+});
+pub(crate) static ACCOUNTS_DATA_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_accounts_data_rejected_total",
+        "Number of received AccountData entries which were not accepted, because they were stale \
+         or the cache was full",
+        &["reason"],
+    )
+    .unwrap()
+});
 pub(crate) static SYNC_ACCOUNTS_DATA: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_sync_accounts_data",
@@ -246,6 +429,14 @@ pub(crate) static EDGE_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
         .unwrap()
 });
 
+pub(crate) static EDGE_PERSISTED: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_edge_persisted",
+        "Number of edges stored in DB to warm-start the routing table after a restart",
+    )
+    .unwrap()
+});
+
 pub(crate) static EDGE_TOMBSTONE_SENDING_SKIPPED: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter(
         "near_edge_tombstone_sending_skip",
@@ -295,6 +486,73 @@ pub(crate) static ROUTED_MESSAGE_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub(crate) static ROUTE_BACK_CACHE_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_route_back_cache_size",
+        "Number of entries in the route back cache, by tier",
+        &["tier"],
+    )
+    .unwrap()
+});
+pub(crate) static ROUTE_BACK_CACHE_EXPIRED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_route_back_cache_expired_total",
+        "Number of route back cache entries dropped, because the response wouldn't be useful \
+         anymore at the current block height, by tier",
+        &["tier"],
+    )
+    .unwrap()
+});
+
+pub(crate) static ROUTED_MESSAGE_OUTBOX: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_routed_message_outbox",
+        "Number of routed messages to unreachable accounts, by routed message type and what happened to them in the outbox",
+        &["type", "event"],
+    )
+    .unwrap()
+});
+pub(crate) static ROUTED_MESSAGE_OUTBOX_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_routed_message_outbox_size",
+        "Number of routed messages waiting in the outbox for the target account to become reachable",
+    )
+    .unwrap()
+});
+
+pub(crate) static NEXT_EPOCH_VALIDATORS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_next_epoch_validators",
+        "Number of the other validators of the next epoch. Reported only by the validators of the next epoch",
+    )
+    .unwrap()
+});
+
+pub(crate) static NEXT_EPOCH_VALIDATORS_CONNECTED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_next_epoch_validators_connected",
+        "Number of the other validators of the next epoch, to which this node has a connection of the given tier",
+        &["tier"],
+    )
+    .unwrap()
+});
+
+pub(crate) static EPOCH_BOUNDARY_VALIDATORS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_epoch_boundary_validators",
+        "Number of the other validators of the epoch, as of the moment this node observed its start",
+    )
+    .unwrap()
+});
+
+pub(crate) static EPOCH_BOUNDARY_VALIDATORS_CONNECTED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_epoch_boundary_validators_connected",
+        "Number of the other validators of the epoch, to which this node had a connection of the given tier at the moment it observed the start of the epoch",
+        &["tier"],
+    )
+    .unwrap()
+});
 
 pub(crate) static PEER_REACHABLE: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge(
@@ -434,6 +692,11 @@ pub(crate) enum MessageDropped {
     MaxCapacityExceeded,
     TransactionsPerBlockExceeded,
     Duplicate,
+    ExpensiveRequestsLimitExceeded,
+    KeyTransitionsRateLimitExceeded,
+    SendQueueLaneFull,
+    NotTier1Validator,
+    UnsupportedEncoding,
 }
 
 impl MessageDropped {
@@ -441,6 +704,10 @@ impl MessageDropped {
         self.inc_msg_type(msg.into())
     }
 
+    pub fn inc_peer_msg(self, msg: &PeerMessage) {
+        self.inc_msg_type(msg.msg_variant())
+    }
+
     pub fn inc_unknown_msg(self) {
         self.inc_msg_type("unknown")
     }
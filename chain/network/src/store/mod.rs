@@ -2,6 +2,8 @@
 /// All transactions should be implemented within this module,
 /// in particular schema::StoreUpdate is not exported.
 use crate::network_protocol::Edge;
+use crate::peer_manager::outbox;
+use crate::peer_manager::peer_store;
 use crate::types::ConnectionInfo;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::types::AccountId;
@@ -118,6 +120,19 @@ impl Store {
     }
 }
 
+// Routing graph storage.
+impl Store {
+    pub fn set_routing_edges(&mut self, edges: &Vec<Edge>) -> Result<(), Error> {
+        let mut update = self.0.new_update();
+        update.set::<schema::RoutingEdges>(&(), edges);
+        self.0.commit(update).map_err(Error)
+    }
+
+    pub fn get_routing_edges(&self) -> Vec<Edge> {
+        self.0.get::<schema::RoutingEdges>(&()).unwrap_or(Some(vec![])).unwrap_or(vec![])
+    }
+}
+
 // ConnectionStore storage.
 impl Store {
     pub fn set_recent_outbound_connections(
@@ -137,6 +152,47 @@ impl Store {
     }
 }
 
+// Outbox storage.
+impl Store {
+    pub fn update_routed_message_outbox(
+        &mut self,
+        inserted: &[(AccountId, outbox::Entry)],
+        removed: &[(AccountId, u64)],
+    ) -> Result<(), Error> {
+        let mut update = self.0.new_update();
+        for (account_id, entry) in inserted {
+            update.set::<schema::RoutedMessageOutbox>(&(account_id.clone(), entry.id), entry);
+        }
+        for key in removed {
+            update.delete::<schema::RoutedMessageOutbox>(key);
+        }
+        self.0.commit(update).map_err(Error)
+    }
+
+    /// Returns all the entries of the outbox, in no particular order.
+    /// Entries which cannot be decoded are skipped.
+    pub fn get_routed_message_outbox(&self) -> Vec<(AccountId, outbox::Entry)> {
+        self.0
+            .iter::<schema::RoutedMessageOutbox>()
+            .filter_map(|item| item.ok())
+            .map(|((account_id, _), entry)| (account_id, entry))
+            .collect()
+    }
+}
+
+// Banned peers storage.
+impl Store {
+    pub fn set_banned_peers(&mut self, banned: &Vec<peer_store::BannedPeer>) -> Result<(), Error> {
+        let mut update = self.0.new_update();
+        update.set::<schema::BannedPeers>(&(), banned);
+        self.0.commit(update).map_err(Error)
+    }
+
+    pub fn get_banned_peers(&self) -> Vec<peer_store::BannedPeer> {
+        self.0.get::<schema::BannedPeers>(&()).unwrap_or(Some(vec![])).unwrap_or(vec![])
+    }
+}
+
 // TODO(mina86): Get rid of it.
 #[cfg(test)]
 impl From<near_store::NodeStorage> for Store {
@@ -1,3 +1,6 @@
+use crate::network_protocol::RoutedMessageBody;
+use crate::peer_manager::outbox;
+use crate::peer_manager::peer_store;
 use crate::types as primitives;
 /// Schema module defines a type-safe access to the DB.
 /// It is a concise definition of key and value types
@@ -11,8 +14,6 @@ use near_store::DBCol;
 use std::io;
 use std::sync::Arc;
 
-#[cfg(test)]
-mod testonly;
 #[cfg(test)]
 mod tests;
 
@@ -86,6 +87,64 @@ impl BorshRepr for EdgeRepr {
     }
 }
 
+/// A Borsh representation of the outbox::Entry.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(super) struct OutboxEntryRepr {
+    id: u64,
+    body: RoutedMessageBody,
+    /// UNIX timestamp in nanos.
+    enqueued_at: u64,
+}
+
+impl BorshRepr for OutboxEntryRepr {
+    type T = outbox::Entry;
+    fn to_repr(e: &outbox::Entry) -> Self {
+        Self {
+            id: e.id,
+            body: e.body.clone(),
+            enqueued_at: e.enqueued_at.unix_timestamp_nanos() as u64,
+        }
+    }
+
+    fn from_repr(e: Self) -> Result<outbox::Entry, Error> {
+        Ok(outbox::Entry {
+            id: e.id,
+            body: e.body,
+            enqueued_at: time::Utc::from_unix_timestamp_nanos(e.enqueued_at as i128)
+                .map_err(invalid_data)?,
+        })
+    }
+}
+
+/// A Borsh representation of the peer_store::BannedPeer.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(super) struct BannedPeerRepr {
+    peer_info: primitives::PeerInfo,
+    reason: primitives::ReasonForBan,
+    /// UNIX timestamp in nanos.
+    ban_time: u64,
+}
+
+impl BorshRepr for BannedPeerRepr {
+    type T = peer_store::BannedPeer;
+    fn to_repr(b: &peer_store::BannedPeer) -> Self {
+        Self {
+            peer_info: b.peer_info.clone(),
+            reason: b.reason,
+            ban_time: b.ban_time.unix_timestamp_nanos() as u64,
+        }
+    }
+
+    fn from_repr(b: Self) -> Result<peer_store::BannedPeer, Error> {
+        Ok(peer_store::BannedPeer {
+            peer_info: b.peer_info,
+            reason: b.reason,
+            ban_time: time::Utc::from_unix_timestamp_nanos(b.ban_time as i128)
+                .map_err(invalid_data)?,
+        })
+    }
+}
+
 /////////////////////////////////////////////
 // Columns
 
@@ -103,6 +162,27 @@ impl Column for RecentOutboundConnections {
     type Value = Vec<ConnectionInfoRepr>;
 }
 
+pub(super) struct RoutedMessageOutbox;
+impl Column for RoutedMessageOutbox {
+    const COL: DBCol = DBCol::RoutedMessageOutbox;
+    type Key = Borsh<(AccountId, u64)>;
+    type Value = OutboxEntryRepr;
+}
+
+pub(super) struct BannedPeers;
+impl Column for BannedPeers {
+    const COL: DBCol = DBCol::BannedPeers;
+    type Key = Borsh<()>;
+    type Value = Vec<BannedPeerRepr>;
+}
+
+pub(super) struct RoutingEdges;
+impl Column for RoutingEdges {
+    const COL: DBCol = DBCol::RoutingEdges;
+    type Key = Borsh<()>;
+    type Value = Vec<EdgeRepr>;
+}
+
 pub(super) struct PeerComponent;
 impl Column for PeerComponent {
     const COL: DBCol = DBCol::PeerComponent;
@@ -243,6 +323,17 @@ impl Store {
             None => None,
         })
     }
+
+    pub fn iter<C: Column>(
+        &self,
+    ) -> impl Iterator<Item = Result<(<C::Key as Format>::T, <C::Value as Format>::T), Error>> + '_
+    {
+        debug_assert!(!C::COL.is_rc());
+        self.0.iter_raw_bytes(C::COL).map(|item| {
+            let (k, v) = item?;
+            Ok((C::Key::decode(&k)?, C::Value::decode(&v)?))
+        })
+    }
 }
 
 impl From<Arc<dyn near_store::db::Database>> for Store {
@@ -1,13 +1,24 @@
 use crate::network_protocol::PeerInfo;
 use anyhow::{anyhow, Context as _};
 use near_primitives::network::PeerId;
+use near_primitives::time;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::Mutex;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
 
 const LISTENER_BACKLOG: u32 = 128;
 
+/// Timeout of the SOCKS5 handshake. It is much longer than the TCP connect timeout, because
+/// the proxy establishes the connection to the target during the handshake, which (in case of
+/// Tor) may involve building a circuit.
+const SOCKS5_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// TEST-ONLY: timeout of establishing the outbound connections in tests.
+#[cfg(test)]
+pub(crate) const TEST_CONNECT_TIMEOUT: time::Duration = time::Duration::seconds(1);
+
 /// TEST-ONLY: guards ensuring that OS considers the given TCP listener port to be in use until
 /// this OS process is terminated.
 static RESERVED_LISTENER_ADDRS: Lazy<Mutex<HashMap<std::net::SocketAddr, tokio::net::TcpSocket>>> =
@@ -33,6 +44,19 @@ pub(crate) enum StreamType {
     Outbound { peer_id: PeerId, tier: Tier },
 }
 
+/// Converts an IPv4-mapped IPv6 address (which is how a dual-stack socket reports IPv4 peers)
+/// to the plain IPv4 address, so that a peer has the same address regardless of the
+/// address family of the socket it has connected to.
+pub(crate) fn canonical_addr(addr: std::net::SocketAddr) -> std::net::SocketAddr {
+    match addr {
+        std::net::SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => std::net::SocketAddr::new(ip.into(), v6.port()),
+            None => addr,
+        },
+        addr => addr,
+    }
+}
+
 #[derive(Debug)]
 pub struct Stream {
     pub(crate) stream: tokio::net::TcpStream,
@@ -79,27 +103,44 @@ impl Socket {
 
 impl Stream {
     fn new(stream: tokio::net::TcpStream, type_: StreamType) -> std::io::Result<Self> {
-        Ok(Self { peer_addr: stream.peer_addr()?, local_addr: stream.local_addr()?, stream, type_ })
+        Ok(Self {
+            peer_addr: canonical_addr(stream.peer_addr()?),
+            local_addr: canonical_addr(stream.local_addr()?),
+            stream,
+            type_,
+        })
     }
 
-    pub async fn connect(peer_info: &PeerInfo, tier: Tier) -> anyhow::Result<Stream> {
+    /// Establishes an outbound TCP connection to the peer.
+    /// If `socks5_proxy` is set, the connection is established through that SOCKS5 proxy.
+    pub async fn connect(
+        peer_info: &PeerInfo,
+        tier: Tier,
+        socks5_proxy: Option<std::net::SocketAddr>,
+        connect_timeout: time::Duration,
+    ) -> anyhow::Result<Stream> {
         let addr =
             peer_info.addr.ok_or(anyhow!("Trying to connect to peer with no public address"))?;
         // The `connect` may take several minutes. This happens when the
         // `SYN` packet for establishing a TCP connection gets silently
         // dropped, in which case the default TCP timeout is applied. That's
-        // too long for us, so we shorten it to one second.
-        //
-        // Why exactly a second? It was hard-coded in a library we used
-        // before, so we keep it to preserve behavior. Removing the timeout
-        // completely was observed to break stuff for real on the testnet.
-        let stream = tokio::time::timeout(
-            std::time::Duration::from_secs(1),
-            tokio::net::TcpStream::connect(addr),
+        // too long for us, so we shorten it (see `NetworkConfig::tcp_connect_timeout`).
+        // Removing the timeout completely was observed to break stuff for real on the testnet.
+        let mut stream = tokio::time::timeout(
+            connect_timeout.try_into()?,
+            tokio::net::TcpStream::connect(socks5_proxy.unwrap_or(addr)),
         )
         .await?
         .context("TcpStream::connect()")?;
-        Ok(Stream::new(stream, StreamType::Outbound { peer_id: peer_info.id.clone(), tier })?)
+        let type_ = StreamType::Outbound { peer_id: peer_info.id.clone(), tier };
+        if socks5_proxy.is_none() {
+            return Ok(Stream::new(stream, type_)?);
+        }
+        tokio::time::timeout(SOCKS5_HANDSHAKE_TIMEOUT, socks5_connect(&mut stream, addr))
+            .await?
+            .context("socks5_connect()")?;
+        // stream.peer_addr() is the address of the proxy, so we use the address of the peer instead.
+        Ok(Self { local_addr: stream.local_addr()?, peer_addr: addr, stream, type_ })
     }
 
     /// Establishes a loopback TCP connection to localhost with random ports.
@@ -109,8 +150,10 @@ impl Stream {
         let listener_addr = ListenerAddr::reserve_for_test();
         let peer_info = PeerInfo { id: peer_id, addr: Some(*listener_addr), account_id: None };
         let mut listener = listener_addr.listener().unwrap();
-        let (outbound, inbound) =
-            tokio::join!(Stream::connect(&peer_info, tier), listener.accept());
+        let (outbound, inbound) = tokio::join!(
+            Stream::connect(&peer_info, tier, None, TEST_CONNECT_TIMEOUT),
+            listener.accept()
+        );
         (outbound.unwrap(), inbound.unwrap())
     }
 
@@ -125,6 +168,56 @@ impl Stream {
     }
 }
 
+/// Asks the SOCKS5 proxy on the other end of `stream` to connect to `addr` (see RFC 1928).
+/// Once it returns, `stream` is connected to `addr`.
+async fn socks5_connect(
+    stream: &mut tokio::net::TcpStream,
+    addr: std::net::SocketAddr,
+) -> anyhow::Result<()> {
+    const VERSION: u8 = 5;
+    const NO_AUTH: u8 = 0;
+    const CMD_CONNECT: u8 = 1;
+    const ATYP_IPV4: u8 = 1;
+    const ATYP_DOMAIN: u8 = 3;
+    const ATYP_IPV6: u8 = 4;
+
+    // Negotiate the authentication method. Only "no authentication" is supported.
+    stream.write_all(&[VERSION, 1, NO_AUTH]).await?;
+    let mut resp = [0u8; 2];
+    stream.read_exact(&mut resp).await?;
+    anyhow::ensure!(resp[0] == VERSION, "unexpected SOCKS version {}", resp[0]);
+    anyhow::ensure!(resp[1] == NO_AUTH, "proxy requires an unsupported authentication method");
+
+    let mut req = vec![VERSION, CMD_CONNECT, 0];
+    match addr.ip() {
+        std::net::IpAddr::V4(ip) => {
+            req.push(ATYP_IPV4);
+            req.extend_from_slice(&ip.octets());
+        }
+        std::net::IpAddr::V6(ip) => {
+            req.push(ATYP_IPV6);
+            req.extend_from_slice(&ip.octets());
+        }
+    }
+    req.extend_from_slice(&addr.port().to_be_bytes());
+    stream.write_all(&req).await?;
+
+    let mut resp = [0u8; 4];
+    stream.read_exact(&mut resp).await?;
+    anyhow::ensure!(resp[0] == VERSION, "unexpected SOCKS version {}", resp[0]);
+    anyhow::ensure!(resp[1] == 0, "proxy failed to connect to {addr}: reply code {}", resp[1]);
+    // Skip the address which the proxy bound to connect to the target.
+    let bound_addr_len = match resp[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream.read_u8().await? as usize,
+        atyp => anyhow::bail!("unexpected SOCKS address type {atyp}"),
+    };
+    let mut bound_addr = vec![0u8; bound_addr_len + 2];
+    stream.read_exact(&mut bound_addr).await?;
+    Ok(())
+}
+
 /// ListenerAddr is isomorphic to std::net::SocketAddr, but it should be used
 /// solely for opening a TCP listener socket on it.
 ///
@@ -207,6 +300,23 @@ impl ListenerAddr {
         socket.bind(self.0)?;
         Ok(Listener(socket.listen(LISTENER_BACKLOG)?))
     }
+
+    /// Constructs a Listener accepting only IPv6 connections.
+    /// By default an IPv6 socket accepts also IPv4 connections (as IPv4-mapped IPv6 addresses),
+    /// so it cannot share a port with an IPv4 listener.
+    pub(crate) fn v6_only_listener(&self) -> std::io::Result<Listener> {
+        use socket2::{Domain, Protocol, Socket, Type};
+        let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_only_v6(true)?;
+        if RESERVED_LISTENER_ADDRS.lock().unwrap().contains_key(&self.0) {
+            socket.set_reuse_port(true)?;
+        }
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&self.0.into())?;
+        socket.listen(LISTENER_BACKLOG as i32)?;
+        Ok(Listener(tokio::net::TcpListener::from_std(socket.into())?))
+    }
 }
 
 pub(crate) struct Listener(tokio::net::TcpListener);
@@ -217,3 +327,64 @@ impl Listener {
         Stream::new(stream, StreamType::Inbound)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::make_rng;
+
+    #[test]
+    fn canonical_addr_unmaps_ipv4() {
+        let addr = |s: &str| -> std::net::SocketAddr { s.parse().unwrap() };
+        assert_eq!(addr("1.2.3.4:5"), canonical_addr(addr("[::ffff:1.2.3.4]:5")));
+        assert_eq!(addr("[::1]:5"), canonical_addr(addr("[::1]:5")));
+        assert_eq!(addr("1.2.3.4:5"), canonical_addr(addr("1.2.3.4:5")));
+    }
+
+    /// Minimal SOCKS5 proxy, serving a single CONNECT request to an IPv6 address.
+    async fn serve_socks5(listener: tokio::net::TcpListener) {
+        let (mut client, _) = listener.accept().await.unwrap();
+        let mut greeting = [0u8; 3];
+        client.read_exact(&mut greeting).await.unwrap();
+        assert_eq!([5, 1, 0], greeting);
+        client.write_all(&[5, 0]).await.unwrap();
+        let mut req = [0u8; 4 + 16 + 2];
+        client.read_exact(&mut req).await.unwrap();
+        assert_eq!([5, 1, 0, 4], req[..4]);
+        let ip: [u8; 16] = req[4..20].try_into().unwrap();
+        let port = u16::from_be_bytes([req[20], req[21]]);
+        let mut target =
+            tokio::net::TcpStream::connect((std::net::Ipv6Addr::from(ip), port)).await.unwrap();
+        client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).await.unwrap();
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut target).await;
+    }
+
+    #[tokio::test]
+    async fn connect_through_socks5_proxy() {
+        let mut rng = make_rng(921734802);
+        let proxy = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(serve_socks5(proxy));
+
+        let listener_addr = ListenerAddr::reserve_for_test();
+        let mut listener = listener_addr.listener().unwrap();
+        let peer_info = PeerInfo {
+            id: data::make_peer_id(&mut rng),
+            addr: Some(*listener_addr),
+            account_id: None,
+        };
+        let (outbound, inbound) = tokio::join!(
+            Stream::connect(&peer_info, Tier::T2, Some(proxy_addr), TEST_CONNECT_TIMEOUT),
+            listener.accept()
+        );
+        let mut outbound = outbound.unwrap();
+        let mut inbound = inbound.unwrap();
+        // The stream reports the address of the peer, rather than the address of the proxy.
+        assert_eq!(*listener_addr, outbound.peer_addr);
+        outbound.stream.write_all(b"hello").await.unwrap();
+        let mut got = [0u8; 5];
+        inbound.stream.read_exact(&mut got).await.unwrap();
+        assert_eq!(b"hello", &got);
+    }
+}
@@ -53,10 +53,12 @@ impl Stream {
 
     pub async fn write(&mut self, msg: &PeerMessage) {
         if let Some(enc) = self.encoding() {
-            self.write_encoded(&msg.serialize(enc)).await;
+            self.write_encoded(&msg.serialize(enc).unwrap()).await;
         } else {
-            self.write_encoded(&msg.serialize(Encoding::Proto)).await;
-            self.write_encoded(&msg.serialize(Encoding::Borsh)).await;
+            self.write_encoded(&msg.serialize(Encoding::Proto).unwrap()).await;
+            if let Some(bytes) = msg.serialize(Encoding::Borsh) {
+                self.write_encoded(&bytes).await;
+            }
         }
     }
 
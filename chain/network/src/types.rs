@@ -1,3 +1,4 @@
+use crate::concurrency::rate;
 /// Type that belong to the network protocol.
 pub use crate::network_protocol::{
     AccountOrPeerIdOrHash, Disconnect, Encoding, Handshake, HandshakeFailureReason, PeerMessage,
@@ -17,6 +18,7 @@ use near_primitives::time;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::BlockHeight;
 use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views::RouteInfoView;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::net::SocketAddr;
@@ -24,9 +26,9 @@ use std::sync::Arc;
 
 /// Exported types, which are part of network protocol.
 pub use crate::network_protocol::{
-    Edge, PartialEdgeInfo, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo, StateResponseInfo,
-    StateResponseInfoV1, StateResponseInfoV2,
+    Edge, FullChunkResponseMsg, KeyTransition, PartialEdgeInfo, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo,
+    SignedKeyTransition, StateResponseInfo, StateResponseInfoV1, StateResponseInfoV2,
 };
 
 /// Number of hops a message is allowed to travel before being dropped.
@@ -52,7 +54,18 @@ pub struct KnownProducer {
 }
 
 /// Ban reason.
-#[derive(borsh::BorshSerialize, borsh::BorshDeserialize, Debug, Clone, PartialEq, Eq, Copy)]
+#[derive(
+    borsh::BorshSerialize,
+    borsh::BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Copy,
+)]
 pub enum ReasonForBan {
     None = 0,
     BadBlock = 1,
@@ -66,6 +79,8 @@ pub enum ReasonForBan {
     InvalidHash = 9,
     InvalidEdge = 10,
     Blacklisted = 14,
+    /// Banned by the node operator via the admin API.
+    Manual = 15,
 }
 
 /// Banning signal sent from Peer instance to PeerManager
@@ -147,6 +162,10 @@ pub struct ChainInfo {
     // Peers acting on behalf of these accounts have a higher
     // priority on the NEAR network than other peers.
     pub tier1_accounts: Arc<AccountKeys>,
+    // Public keys of the validators of the next epoch (a subset of tier1_accounts).
+    // If this node is one of them, it establishes connections to the other ones
+    // in advance, so that they are ready once the epoch starts.
+    pub next_epoch_accounts: Arc<AccountKeys>,
 }
 
 #[derive(Debug, actix::Message)]
@@ -162,6 +181,37 @@ pub enum PeerManagerMessageRequest {
     /// Used in tests and internally by PeerManager.
     /// TODO: replace it with AsyncContext::spawn/run_later for internal use.
     OutboundTcpConnect(crate::tcp::Stream),
+    /// Disconnects the peer (on all tiers) and, if `ban` is set, bans it for
+    /// `ReasonForBan::Manual`. Issued by the node operator via the admin API.
+    DisconnectPeer {
+        peer_id: PeerId,
+        ban: bool,
+    },
+    /// Closes all the TIER2 connections (and the TIER1 connections, if `tier1` is set) and
+    /// immediately dials new peers. Issued by the node operator via the admin API.
+    ReconnectAll {
+        tier1: bool,
+    },
+    /// Updates the receive rate limits of all the connections, including the established ones.
+    /// Issued when the config is reloaded. Invalid limits are ignored.
+    SetRecvLimits {
+        tier1: rate::Limit,
+        tier2: rate::Limit,
+    },
+    /// Updates the peer selection settings, in the format of the corresponding config.json fields.
+    /// Issued when the config is reloaded. The connections with newly blacklisted peers are
+    /// closed. Invalid settings are ignored altogether.
+    SetPeersConfig {
+        max_num_peers: u32,
+        whitelist_nodes: String,
+        blacklist: Vec<String>,
+        boot_nodes: String,
+    },
+    /// Returns the current route to the given PeerId or AccountId. Issued via the debug API,
+    /// to investigate why the routed messages to the target are dropped.
+    GetRouteInfo {
+        target: String,
+    },
     /// The following types of requests are used to trigger actions in the Peer Manager for testing.
     /// TEST-ONLY: Fetch current routing table.
     FetchRoutingTable,
@@ -191,6 +241,15 @@ pub enum PeerManagerMessageResponse {
     NetworkResponses(NetworkResponses),
     /// TEST-ONLY
     OutboundTcpConnect,
+    /// Whether the peer was connected.
+    DisconnectPeer(bool),
+    /// Number of the closed connections.
+    ReconnectAll(usize),
+    /// Whether the limits were valid and have been applied.
+    SetRecvLimits(bool),
+    /// Whether the settings were valid and have been applied.
+    SetPeersConfig(bool),
+    GetRouteInfo(RouteInfoView),
     FetchRoutingTable(RoutingTableInfo),
 }
 
@@ -246,6 +305,14 @@ pub enum NetworkRequests {
     },
     /// Information about chunk such as its header, some subset of parts and/or incoming receipts
     PartialEncodedChunkResponse { route_back: CryptoHash, response: PartialEncodedChunkResponseMsg },
+    /// Request the whole chunk body, falling back to the parts and/or receipts in `request`
+    FullChunkRequest {
+        target: AccountIdOrPeerTrackingShard,
+        request: PartialEncodedChunkRequestMsg,
+        create_time: time::Instant,
+    },
+    /// Transactions and receipts of the chunk
+    FullChunkResponse { route_back: CryptoHash, response: FullChunkResponseMsg },
     /// Information about chunk such as its header, some subset of parts and/or incoming receipts
     PartialEncodedChunkMessage {
         account_id: AccountId,
@@ -342,6 +409,28 @@ pub struct ConnectedPeerInfo {
     pub peer_type: PeerType,
     /// Nonce used for the connection with the peer.
     pub nonce: u64,
+    /// Traffic over the connection since it has been established, by PeerMessage variant.
+    pub traffic_by_message_type: HashMap<&'static str, MessageTraffic>,
+}
+
+/// Traffic of a single PeerMessage variant over a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageTraffic {
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub received_messages: u64,
+    pub received_bytes: u64,
+}
+
+impl From<MessageTraffic> for near_primitives::views::MessageTrafficView {
+    fn from(t: MessageTraffic) -> Self {
+        Self {
+            sent_messages: t.sent_messages,
+            sent_bytes: t.sent_bytes,
+            received_messages: t.received_messages,
+            received_bytes: t.received_bytes,
+        }
+    }
 }
 
 #[derive(Debug, Clone, actix::MessageResponse)]
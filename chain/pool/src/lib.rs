@@ -1,44 +1,130 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 use crate::types::{PoolIterator, PoolKey, TransactionGroup};
 use borsh::BorshSerialize;
+use near_chain_configs::{TransactionPoolLimits, TransactionPoolOrdering};
 use near_crypto::PublicKey;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Gas, Nonce};
 use std::ops::Bound;
 
 mod metrics;
 pub mod types;
 
+/// Outcome of inserting a transaction into the pool.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// The transaction was added to the pool.
+    Success,
+    /// The transaction was added to the pool in place of the pooled transactions with the same
+    /// signer, access key and nonce, which all had less gas attached.
+    Replaced,
+    /// The transaction was in the pool already.
+    Duplicate,
+    /// The transaction was evicted right away because of the limits of the pool, or it would
+    /// have replaced pooled transactions but didn't fit in their place.
+    NoSpaceLeft,
+}
+
+/// What the pool keeps about each of its transactions to account for them in its limits.
+struct PooledTransaction {
+    signer_id: AccountId,
+    size: u64,
+    /// Number of the transactions inserted into the pool before this one.
+    seq: u64,
+}
+
+/// Number and total size of the transactions of the pool, or of a signer.
+#[derive(Default)]
+struct PoolUsage {
+    num_transactions: u64,
+    size: u64,
+}
+
+impl PoolUsage {
+    fn exceeds(
+        &self,
+        max_transactions: Option<u64>,
+        max_size: Option<u64>,
+    ) -> Option<&'static str> {
+        if max_transactions.map_or(false, |limit| self.num_transactions > limit) {
+            Some("transactions")
+        } else if max_size.map_or(false, |limit| self.size > limit) {
+            Some("size")
+        } else {
+            None
+        }
+    }
+}
+
+/// Gas attached to the actions of the transaction.
+fn attached_gas(tx: &SignedTransaction) -> Gas {
+    tx.transaction
+        .actions
+        .iter()
+        .fold(0, |gas: Gas, action| gas.saturating_add(action.get_prepaid_gas()))
+}
+
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    unique_transactions: HashSet<CryptoHash>,
+    /// Hashes of all the transactions to quickly check if the given transaction is in the pool.
+    unique_transactions: HashMap<CryptoHash, PooledTransaction>,
+    /// Number and size of all the transactions in the pool.
+    usage: PoolUsage,
+    /// Number and size of the transactions in the pool, by signer.
+    signer_usage: HashMap<AccountId, PoolUsage>,
+    /// Number of the transactions inserted into the pool so far.
+    num_inserted: u64,
+    /// The transactions to evict, oldest first: the one with the highest nonce of every group,
+    /// as its `seq` and the key of its group.
+    eviction_order: BTreeSet<(u64, PoolKey)>,
+    /// The same as `eviction_order`, by signer.
+    signer_eviction_order: HashMap<AccountId, BTreeSet<(u64, PoolKey)>>,
+    /// Signer and `seq` of the entry of every group in the eviction order.
+    eviction_candidates: HashMap<PoolKey, (AccountId, u64)>,
+    /// Caps on the transactions in the pool, enforced by evicting transactions.
+    limits: TransactionPoolLimits,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: RngSeed,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
     last_used_key: PoolKey,
+    /// Order in which the pool iterator visits the transaction groups.
+    ordering: TransactionPoolOrdering,
 }
 
 impl TransactionPool {
-    pub fn new(key_seed: RngSeed) -> Self {
+    pub fn new(
+        key_seed: RngSeed,
+        ordering: TransactionPoolOrdering,
+        limits: TransactionPoolLimits,
+    ) -> Self {
         Self {
             key_seed,
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
+            usage: PoolUsage::default(),
+            signer_usage: HashMap::new(),
+            num_inserted: 0,
+            eviction_order: BTreeSet::new(),
+            signer_eviction_order: HashMap::new(),
+            eviction_candidates: HashMap::new(),
+            limits,
             last_used_key: CryptoHash::default(),
+            ordering,
         }
     }
 
     pub fn init_metrics() {
         // A `get()` call initializes a metric even if its value is zero.
         metrics::TRANSACTION_POOL_TOTAL.get();
+        metrics::TRANSACTION_POOL_SIZE.get();
     }
 
     fn key(&self, account_id: &AccountId, public_key: &PublicKey) -> PoolKey {
@@ -49,22 +135,233 @@ impl TransactionPool {
     }
 
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
-            // The hash of this transaction was already seen, skip it.
-            return false;
+    ///
+    /// If the pool has transactions with the same signer, access key and nonce, and all of them
+    /// have strictly less gas attached, they are replaced by the new one. Otherwise the new
+    /// transaction is added next to them, and at most one of them can be included.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let hash = signed_transaction.get_hash();
+        // The hash of this transaction was already seen, skip it.
+        if self.unique_transactions.contains_key(&hash) {
+            return InsertTransactionResult::Duplicate;
+        }
+        let signer_id = signed_transaction.transaction.signer_id.clone();
+        let key = self.key(&signer_id, &signed_transaction.transaction.public_key);
+        let size = signed_transaction.get_size();
+        // A transaction which doesn't fit in the pool on its own would only push out the other
+        // transactions before being evicted itself.
+        if !self.fits_alone(size) {
+            return InsertTransactionResult::NoSpaceLeft;
         }
+        let to_replace = self.transactions_to_replace(&key, &signed_transaction);
+        let replaced = !to_replace.is_empty();
+        if replaced {
+            // The replacement doesn't evict anything, so that a signer can't push out other
+            // transactions by replacing its own ones.
+            if !self.fits_after_replacing(&signer_id, size, &to_replace) {
+                return InsertTransactionResult::NoSpaceLeft;
+            }
+            self.replace(&key, &signed_transaction, &to_replace);
+        }
+
+        self.unique_transactions.insert(
+            hash,
+            PooledTransaction { signer_id: signer_id.clone(), size, seq: self.num_inserted },
+        );
+        self.num_inserted += 1;
+        self.usage.num_transactions += 1;
+        self.usage.size += size;
+        let signer_usage = self.signer_usage.entry(signer_id.clone()).or_default();
+        signer_usage.num_transactions += 1;
+        signer_usage.size += size;
         metrics::TRANSACTION_POOL_TOTAL.inc();
+        metrics::TRANSACTION_POOL_SIZE.add(size as i64);
+
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+        self.update_eviction_candidate(&key);
+        self.enforce_limits(&signer_id);
+        if !self.unique_transactions.contains_key(&hash) {
+            InsertTransactionResult::NoSpaceLeft
+        } else if replaced {
+            InsertTransactionResult::Replaced
+        } else {
+            InsertTransactionResult::Success
+        }
+    }
+
+    /// Returns the hashes of the transactions of the group with the same nonce as the given
+    /// transaction, if they all have strictly less gas attached than it. Otherwise, or if there
+    /// are none, returns an empty list.
+    fn transactions_to_replace(&self, key: &PoolKey, tx: &SignedTransaction) -> Vec<CryptoHash> {
+        let transactions = match self.transactions.get(key) {
+            Some(transactions) => transactions,
+            None => return vec![],
+        };
+        let nonce = tx.transaction.nonce;
+        let gas = attached_gas(tx);
+        let mut to_replace = vec![];
+        for pooled in transactions.iter().filter(|pooled| pooled.transaction.nonce == nonce) {
+            if attached_gas(pooled) >= gas {
+                return vec![];
+            }
+            to_replace.push(pooled.get_hash());
+        }
+        to_replace
+    }
+
+    /// Whether a transaction of the given size is within the limits of an empty pool.
+    fn fits_alone(&self, size: u64) -> bool {
+        let usage = PoolUsage { num_transactions: 1, size };
+        usage
+            .exceeds(self.limits.max_transactions_per_signer, self.limits.max_size_per_signer)
+            .is_none()
+            && usage.exceeds(self.limits.max_transactions, self.limits.max_size).is_none()
+    }
+
+    /// Whether both the signer and the whole pool would be within the limits, if the given
+    /// transactions of the signer were replaced by a transaction of the given size.
+    fn fits_after_replacing(
+        &self,
+        signer_id: &AccountId,
+        size: u64,
+        to_replace: &[CryptoHash],
+    ) -> bool {
+        let replaced_size: u64 = to_replace
+            .iter()
+            .filter_map(|hash| self.unique_transactions.get(hash))
+            .map(|tx| tx.size)
+            .sum();
+        let after = |usage: &PoolUsage| PoolUsage {
+            num_transactions: usage.num_transactions + 1 - to_replace.len() as u64,
+            size: usage.size + size - replaced_size,
+        };
+        let signer_usage = match self.signer_usage.get(signer_id) {
+            Some(usage) => after(usage),
+            None => return false,
+        };
+        signer_usage
+            .exceeds(self.limits.max_transactions_per_signer, self.limits.max_size_per_signer)
+            .is_none()
+            && after(&self.usage)
+                .exceeds(self.limits.max_transactions, self.limits.max_size)
+                .is_none()
+    }
 
-        let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+    /// Removes the transactions to be replaced by the given transaction from its group.
+    fn replace(&mut self, key: &PoolKey, tx: &SignedTransaction, to_replace: &[CryptoHash]) {
+        if let Some(transactions) = self.transactions.get_mut(key) {
+            // The group isn't removed even if it's empty now, the new transaction is added to it.
+            transactions.retain(|pooled| pooled.transaction.nonce != tx.transaction.nonce);
+        }
+        for hash in to_replace {
+            self.forget_transaction(hash);
+            metrics::TRANSACTION_POOL_REPLACED.inc();
+        }
+    }
+
+    /// Forgets the transaction with the given hash, once it's no longer in its group.
+    fn forget_transaction(&mut self, hash: &CryptoHash) {
+        let tx = match self.unique_transactions.remove(hash) {
+            Some(tx) => tx,
+            None => return,
+        };
+        metrics::TRANSACTION_POOL_TOTAL.dec();
+        metrics::TRANSACTION_POOL_SIZE.sub(tx.size as i64);
+        self.usage.num_transactions -= 1;
+        self.usage.size -= tx.size;
+        if let Entry::Occupied(mut entry) = self.signer_usage.entry(tx.signer_id) {
+            let signer_usage = entry.get_mut();
+            signer_usage.num_transactions -= 1;
+            signer_usage.size -= tx.size;
+            if signer_usage.num_transactions == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Evicts transactions until both the signer and the whole pool are within the limits.
+    /// The signer's own transactions are evicted first, so that a signer flooding the pool only
+    /// pushes out its own transactions until it reaches the per signer caps.
+    fn enforce_limits(&mut self, signer_id: &AccountId) {
+        loop {
+            let signer_limit = self.signer_usage.get(signer_id).and_then(|usage| {
+                usage.exceeds(
+                    self.limits.max_transactions_per_signer,
+                    self.limits.max_size_per_signer,
+                )
+            });
+            let evicted = if let Some(limit) = signer_limit {
+                self.evict(Some(signer_id), &format!("signer_{limit}"))
+            } else if let Some(limit) =
+                self.usage.exceeds(self.limits.max_transactions, self.limits.max_size)
+            {
+                self.evict(None, &format!("pool_{limit}"))
+            } else {
+                return;
+            };
+            if !evicted {
+                return;
+            }
+        }
+    }
+
+    /// Evicts a transaction of the signer, or of any signer if it's None. Only the transaction
+    /// with the highest nonce of each access key is considered, so that the remaining ones can
+    /// still be included. Among those, the oldest one is evicted. Returns false if there was
+    /// none.
+    fn evict(&mut self, signer_id: Option<&AccountId>, limit: &str) -> bool {
+        let eviction_order = match signer_id {
+            Some(signer_id) => self.signer_eviction_order.get(signer_id),
+            None => Some(&self.eviction_order),
+        };
+        let key = match eviction_order.and_then(|order| order.iter().next()) {
+            Some((_, key)) => *key,
+            None => return false,
+        };
+        let transactions =
+            self.transactions.get_mut(&key).expect("the groups in the eviction order are pooled");
+        let index = (0..transactions.len())
+            .max_by_key(|&index| transactions[index].transaction.nonce)
+            .expect("the pooled groups aren't empty");
+        let tx = transactions.swap_remove(index);
+        if transactions.is_empty() {
+            self.transactions.remove(&key);
+        }
+        self.forget_transaction(&tx.get_hash());
+        self.update_eviction_candidate(&key);
+        metrics::TRANSACTION_POOL_EVICTED.with_label_values(&[limit]).inc();
         true
     }
 
+    /// Updates the entry of the group in the eviction order, after the group has changed.
+    fn update_eviction_candidate(&mut self, key: &PoolKey) {
+        if let Some((signer_id, seq)) = self.eviction_candidates.remove(key) {
+            self.eviction_order.remove(&(seq, *key));
+            if let Entry::Occupied(mut entry) = self.signer_eviction_order.entry(signer_id) {
+                entry.get_mut().remove(&(seq, *key));
+                if entry.get().is_empty() {
+                    entry.remove();
+                }
+            }
+        }
+        let tx = match self
+            .transactions
+            .get(key)
+            .and_then(|transactions| transactions.iter().max_by_key(|tx| tx.transaction.nonce))
+        {
+            Some(tx) => tx,
+            None => return,
+        };
+        let seq = self.unique_transactions.get(&tx.get_hash()).map_or(0, |tx| tx.seq);
+        let signer_id = tx.transaction.signer_id.clone();
+        self.eviction_order.insert((seq, *key));
+        self.signer_eviction_order.entry(signer_id.clone()).or_default().insert((seq, *key));
+        self.eviction_candidates.insert(*key, (signer_id, seq));
+    }
+
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
     /// transaction groups in the proper order defined by the protocol.
     /// When the iterator is dropped, all remaining groups are inserted back into the pool.
@@ -77,7 +374,7 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
+            if self.unique_transactions.contains_key(&tx.get_hash()) {
                 let signer_id = &tx.transaction.signer_id;
                 let signer_public_key = &tx.transaction.public_key;
                 grouped_transactions
@@ -96,10 +393,9 @@ impl TransactionPool {
                 self.transactions.remove(&key);
             }
             for hash in &hashes {
-                if self.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.forget_transaction(hash);
             }
+            self.update_eviction_candidate(&key);
         }
     }
 
@@ -113,6 +409,21 @@ impl TransactionPool {
     pub fn len(&self) -> usize {
         self.unique_transactions.len()
     }
+
+    /// Iterates over the pooled transactions, in no particular order.
+    pub fn transactions(&self) -> impl Iterator<Item = &SignedTransaction> {
+        self.transactions.values().flatten()
+    }
+
+    /// Nonces of the pooled transactions signed with the given access key, in increasing order.
+    pub fn nonces(&self, account_id: &AccountId, public_key: &PublicKey) -> Vec<Nonce> {
+        let mut nonces: Vec<_> = self
+            .transactions
+            .get(&self.key(account_id, public_key))
+            .map_or(vec![], |txs| txs.iter().map(|tx| tx.transaction.nonce).collect());
+        nonces.sort();
+        nonces
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -124,11 +435,65 @@ pub struct PoolIteratorWrapper<'a> {
 
     /// Queue of transaction groups. Each group there is sorted by nonce.
     sorted_groups: VecDeque<TransactionGroup>,
+
+    /// Queue of the signers, each with the queue of its transaction groups. Only used with
+    /// `TransactionPoolOrdering::SignerRoundRobin`.
+    signer_groups: VecDeque<VecDeque<TransactionGroup>>,
 }
 
 impl<'a> PoolIteratorWrapper<'a> {
     pub fn new(pool: &'a mut TransactionPool) -> Self {
-        Self { pool, sorted_groups: Default::default() }
+        Self { pool, sorted_groups: Default::default(), signer_groups: Default::default() }
+    }
+
+    /// Removes a group whose transactions have all been pulled.
+    fn discard_group(&mut self, group: TransactionGroup) {
+        for hash in group.removed_transaction_hashes {
+            self.pool.forget_transaction(&hash);
+        }
+        self.pool.update_eviction_candidate(&group.key);
+    }
+
+    /// Implements `next()` for `TransactionPoolOrdering::SignerRoundRobin`.
+    ///
+    /// All the groups are taken from the pool at once, starting after the last used key, and
+    /// queued by signer, the signers in the order of their first group. Every call takes the
+    /// signer at the front of the queue, rotates its groups and returns its next non-empty one.
+    fn next_by_signer(&mut self) -> Option<&mut TransactionGroup> {
+        if !self.pool.transactions.is_empty() {
+            let last_used_key = self.pool.last_used_key;
+            let (after, before): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pool.transactions)
+                .into_iter()
+                .partition(|(key, _)| *key > last_used_key);
+            let mut signer_index = HashMap::new();
+            for (key, mut transactions) in after.into_iter().chain(before) {
+                transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
+                let signer_id = transactions.first().map(|tx| tx.transaction.signer_id.clone());
+                let index = *signer_index.entry(signer_id).or_insert_with(|| {
+                    self.signer_groups.push_back(VecDeque::new());
+                    self.signer_groups.len() - 1
+                });
+                self.signer_groups[index].push_back(TransactionGroup {
+                    key,
+                    transactions,
+                    removed_transaction_hashes: vec![],
+                });
+            }
+        }
+        while let Some(mut groups) = self.signer_groups.pop_front() {
+            while let Some(group) = groups.pop_front() {
+                if group.transactions.is_empty() {
+                    self.discard_group(group);
+                } else {
+                    self.pool.last_used_key = group.key;
+                    groups.push_back(group);
+                    self.signer_groups.push_back(groups);
+                    let groups = self.signer_groups.back_mut().expect("just pushed");
+                    return Some(groups.back_mut().expect("just pushed"));
+                }
+            }
+        }
+        None
     }
 }
 
@@ -149,8 +514,13 @@ impl<'a> PoolIteratorWrapper<'a> {
 ///
 /// When the iterator is dropped, `unique_transactions` in the pool is updated for every group.
 /// And all non-empty group from the sorted groups queue are inserted back into the pool.
+///
+/// With `TransactionPoolOrdering::SignerRoundRobin`, the groups are returned by `next_by_signer`.
 impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
     fn next(&mut self) -> Option<&mut TransactionGroup> {
+        if self.pool.ordering == TransactionPoolOrdering::SignerRoundRobin {
+            return self.next_by_signer();
+        }
         if !self.pool.transactions.is_empty() {
             let key = *self
                 .pool
@@ -178,11 +548,7 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
         } else {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
-                    for hash in sorted_group.removed_transaction_hashes {
-                        if self.pool.unique_transactions.remove(&hash) {
-                            metrics::TRANSACTION_POOL_TOTAL.dec();
-                        }
-                    }
+                    self.discard_group(sorted_group);
                 } else {
                     self.sorted_groups.push_back(sorted_group);
                     return Some(self.sorted_groups.back_mut().expect("just pushed"));
@@ -195,18 +561,18 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
 
 /// When a pool iterator is dropped, all remaining non empty transaction groups from the sorted
 /// groups queue are inserted back into the pool. And removed transactions hashes from groups are
-/// removed from the pool's unique_transactions.
+/// removed from the pool's unique_transactions, and the groups from its eviction order.
 impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
-        for group in self.sorted_groups.drain(..) {
+        let signer_groups = self.signer_groups.drain(..).flatten();
+        for group in self.sorted_groups.drain(..).chain(signer_groups) {
             for hash in group.removed_transaction_hashes {
-                if self.pool.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.pool.forget_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
             }
+            self.pool.update_eviction_candidate(&group.key);
         }
     }
 }
@@ -222,7 +588,7 @@ mod tests {
     use near_crypto::{InMemorySigner, KeyType};
 
     use near_primitives::hash::CryptoHash;
-    use near_primitives::types::Balance;
+    use near_primitives::types::{Balance, Gas};
 
     const TEST_SEED: RngSeed = [3; 32];
 
@@ -253,7 +619,7 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), Default::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
@@ -326,6 +692,75 @@ mod tests {
         assert_eq!(nonces, (1..=5).map(|a| vec![a, a + 20]).flatten().collect::<Vec<u64>>());
     }
 
+    /// Pulls all the transactions of alice, signed with two access keys, and of bob, signed with
+    /// one, and returns their signers and nonces in the order of the given strategy.
+    fn order_by(ordering: TransactionPoolOrdering) -> Vec<(String, u64)> {
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        transactions.extend(generate_transactions("alice.near", "alice2.near", 11, 13));
+        transactions.extend(generate_transactions("bob.near", "bob.near", 1, 3));
+        transactions.shuffle(&mut thread_rng());
+        let mut pool = TransactionPool::new(TEST_SEED, ordering, Default::default());
+        for tx in transactions {
+            pool.insert_transaction(tx);
+        }
+        let order = prepare_transactions(&mut pool, 10)
+            .iter()
+            .map(|tx| (tx.transaction.signer_id.to_string(), tx.transaction.nonce))
+            .collect();
+        assert_eq!(pool.len(), 0);
+        order
+    }
+
+    /// Check that each access key gets a transaction in turn by default, so alice, with two
+    /// access keys, gets twice as many transactions as bob.
+    #[test]
+    fn test_access_key_round_robin() {
+        let order = order_by(TransactionPoolOrdering::AccessKeyRoundRobin);
+        assert_eq!(order.len(), 9);
+        for turn in order.chunks(3) {
+            let mut turn = turn.to_vec();
+            turn.sort();
+            assert_eq!(turn[0].0, "alice.near");
+            assert_eq!(turn[1].0, "alice.near");
+            assert_eq!(turn[1].1, turn[0].1 + 10);
+            assert_eq!(turn[2], ("bob.near".to_string(), turn[0].1));
+        }
+    }
+
+    /// Check that with the signer ordering each signer gets a transaction in turn, alice
+    /// rotating over its access keys, with the nonces of each access key increasing.
+    #[test]
+    fn test_signer_round_robin() {
+        let order = order_by(TransactionPoolOrdering::SignerRoundRobin);
+        assert_eq!(order.len(), 9);
+        for turn in order[..6].chunks(2) {
+            let mut signers: Vec<_> =
+                turn.iter().map(|(signer_id, _)| signer_id.as_str()).collect();
+            signers.sort();
+            assert_eq!(signers, vec!["alice.near", "bob.near"]);
+        }
+        let alice: Vec<_> = order
+            .iter()
+            .filter(|(signer_id, _)| signer_id == "alice.near")
+            .map(|(_, nonce)| *nonce)
+            .collect();
+        let bob: Vec<_> = order
+            .iter()
+            .filter(|(signer_id, _)| signer_id == "bob.near")
+            .map(|(_, nonce)| *nonce)
+            .collect();
+        assert_eq!(bob, vec![1, 2, 3]);
+        // Alice's access keys alternate.
+        for pair in alice[..4].chunks(2) {
+            let mut pair = pair.to_vec();
+            pair.sort();
+            assert_eq!(pair[1], pair[0] + 10);
+        }
+        let mut alice = alice;
+        alice.sort();
+        assert_eq!(alice, vec![1, 2, 3, 11, 12, 13]);
+    }
+
     /// Add transactions of nonce from 1..=3 and transactions with nonce 21..=31. Pull 10.
     /// Then try to get another 10.
     #[test]
@@ -364,7 +799,7 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), Default::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
@@ -465,4 +900,212 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    fn function_call(signer_id: &str, nonce: u64, gas: Gas) -> SignedTransaction {
+        let signer_id: AccountId = signer_id.parse().unwrap();
+        let signer =
+            InMemorySigner::from_seed(signer_id.clone(), KeyType::ED25519, signer_id.as_ref());
+        SignedTransaction::call(
+            nonce,
+            signer_id,
+            "bob.near".parse().unwrap(),
+            &signer,
+            0,
+            "method".to_string(),
+            vec![],
+            gas,
+            CryptoHash::default(),
+        )
+    }
+
+    #[test]
+    fn test_pool_limits() {
+        let limits = TransactionPoolLimits {
+            max_transactions: Some(3),
+            max_transactions_per_signer: Some(2),
+            ..Default::default()
+        };
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), limits);
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let alice_key = PublicKey::from_seed(KeyType::ED25519, "alice.near");
+        let evicted =
+            |limit: &str| metrics::TRANSACTION_POOL_EVICTED.with_label_values(&[limit]).get();
+        let signer_evicted = evicted("signer_transactions");
+        let pool_evicted = evicted("pool_transactions");
+
+        // Only the transaction with the highest nonce of the signer is evicted.
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 2, 10)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 3, 10)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 1, 10)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1, 2]);
+        assert_eq!(evicted("signer_transactions"), signer_evicted + 1);
+
+        // Then the oldest transaction is evicted, regardless of the gas attached.
+        assert_eq!(
+            pool.insert_transaction(function_call("bob.near", 1, 0)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            pool.insert_transaction(function_call("carol.near", 1, 0)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1]);
+        assert_eq!(
+            pool.insert_transaction(function_call("dave.near", 1, 0)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.nonces(&alice, &alice_key), Vec::<Nonce>::new());
+        assert_eq!(
+            pool.insert_transaction(function_call("erin.near", 1, 20)),
+            InsertTransactionResult::Success
+        );
+        let signers: HashSet<_> =
+            pool.transactions().map(|tx| tx.transaction.signer_id.to_string()).collect();
+        assert_eq!(signers, ["carol.near", "dave.near", "erin.near"].map(String::from).into());
+        assert_eq!(pool.len(), 3);
+        assert_eq!(evicted("pool_transactions"), pool_evicted + 3);
+
+        // The transactions taken by the pool iterator are no longer evicted, and the ones it
+        // returns to the pool still are.
+        assert_eq!(prepare_transactions(&mut pool, 1).len(), 1);
+        assert_eq!(pool.len(), 2);
+        for signer_id in ["frank.near", "george.near"] {
+            assert_eq!(
+                pool.insert_transaction(function_call(signer_id, 1, 0)),
+                InsertTransactionResult::Success
+            );
+        }
+        assert_eq!(pool.len(), 3);
+        assert_eq!(evicted("pool_transactions"), pool_evicted + 4);
+
+        // The size of the pool is accounted for as well.
+        let tx = function_call("harry.near", 1, 30);
+        let limits = TransactionPoolLimits { max_size: Some(tx.get_size()), ..Default::default() };
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), limits);
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        let tx = function_call("ivan.near", 1, 20);
+        let hash = tx.get_hash();
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        assert_eq!(pool.transactions().map(|tx| tx.get_hash()).collect::<Vec<_>>(), vec![hash]);
+
+        // A transaction which doesn't fit in the pool on its own is refused, without evicting
+        // anything.
+        let signer = InMemorySigner::from_seed(alice.clone(), KeyType::ED25519, alice.as_ref());
+        let larger = SignedTransaction::call(
+            1,
+            alice,
+            "bob.near".parse().unwrap(),
+            &signer,
+            0,
+            "method".to_string(),
+            vec![0; 100],
+            30,
+            CryptoHash::default(),
+        );
+        assert_eq!(pool.insert_transaction(larger), InsertTransactionResult::NoSpaceLeft);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_same_nonce() {
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), Default::default());
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let alice_key = PublicKey::from_seed(KeyType::ED25519, "alice.near");
+        let tx = function_call("alice.near", 1, 10);
+        assert_eq!(pool.insert_transaction(tx.clone()), InsertTransactionResult::Success);
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Duplicate);
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 2, 10)),
+            InsertTransactionResult::Success
+        );
+
+        // A transaction with more gas attached replaces the one with the same nonce.
+        let replacement = function_call("alice.near", 1, 20);
+        assert_eq!(pool.insert_transaction(replacement.clone()), InsertTransactionResult::Replaced);
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1, 2]);
+        assert!(pool.transactions().any(|tx| tx.get_hash() == replacement.get_hash()));
+
+        // Without strictly more gas, it's added next to it.
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 1, 15)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1, 1, 2]);
+        assert_eq!(
+            pool.insert_transaction(function_call("alice.near", 1, 30)),
+            InsertTransactionResult::Replaced
+        );
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1, 2]);
+        assert_eq!(pool.len(), 2);
+    }
+
+    /// Check that a replacement which wouldn't fit in the pool is dropped, and the transaction
+    /// it would replace is kept.
+    #[test]
+    fn test_replace_in_full_pool() {
+        let tx = function_call("alice.near", 1, 10);
+        let limits = TransactionPoolLimits {
+            max_transactions: Some(2),
+            max_size: Some(2 * tx.get_size()),
+            ..Default::default()
+        };
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), limits);
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let alice_key = PublicKey::from_seed(KeyType::ED25519, "alice.near");
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        assert_eq!(
+            pool.insert_transaction(function_call("bob.near", 1, 10)),
+            InsertTransactionResult::Success
+        );
+
+        // A replacement of the same size fits in the full pool.
+        let replacement = function_call("alice.near", 1, 20);
+        assert_eq!(pool.insert_transaction(replacement.clone()), InsertTransactionResult::Replaced);
+        assert_eq!(pool.len(), 2);
+
+        // A larger one doesn't, and nothing is removed then.
+        let signer = InMemorySigner::from_seed(alice.clone(), KeyType::ED25519, alice.as_ref());
+        let larger = SignedTransaction::call(
+            1,
+            alice.clone(),
+            "bob.near".parse().unwrap(),
+            &signer,
+            0,
+            "method".to_string(),
+            vec![0; 100],
+            30,
+            CryptoHash::default(),
+        );
+        assert_eq!(pool.insert_transaction(larger), InsertTransactionResult::NoSpaceLeft);
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![1]);
+        assert!(pool.transactions().any(|tx| tx.get_hash() == replacement.get_hash()));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_nonces() {
+        let mut transactions = generate_transactions("alice.near", "alice.near", 5, 7);
+        transactions.extend(generate_transactions("alice.near", "alice.near", 10, 10));
+        transactions.extend(generate_transactions("bob.near", "bob.near", 1, 3));
+        transactions.shuffle(&mut thread_rng());
+        let mut pool = TransactionPool::new(TEST_SEED, Default::default(), Default::default());
+        for tx in transactions {
+            pool.insert_transaction(tx);
+        }
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let alice_key = PublicKey::from_seed(KeyType::ED25519, "alice.near");
+        let bob_key = PublicKey::from_seed(KeyType::ED25519, "bob.near");
+        assert_eq!(pool.nonces(&alice, &alice_key), vec![5, 6, 7, 10]);
+        assert_eq!(pool.nonces(&alice, &bob_key), Vec::<Nonce>::new());
+        assert_eq!(pool.transactions().count(), 7);
+    }
 }
@@ -158,6 +158,7 @@ pub(crate) async fn convert_block_to_transactions(
                     near_primitives::views::StateChangesRequestView::AccountChanges {
                         account_ids: touched_account_ids,
                     },
+                filter: Default::default(),
             }
             .with_span_context(),
         )
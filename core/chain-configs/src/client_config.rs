@@ -1,9 +1,11 @@
 //! Chain Client Configuration
-use crate::MutableConfigValue;
+use crate::{MutableConfigValue, RolloutFlag};
+use near_primitives::errors::{ActionsValidationError, InvalidTxError};
+use near_primitives::transaction::{Action, SignedTransaction};
 use near_primitives::types::{
     AccountId, BlockHeight, BlockHeightDelta, Gas, NumBlocks, NumSeats, ShardId,
 };
-use near_primitives::version::Version;
+use near_primitives::version::{ProtocolVersion, Version};
 use std::cmp::{max, min};
 use std::time::Duration;
 
@@ -69,6 +71,433 @@ impl GCConfig {
     }
 }
 
+/// Configuration of the height horizons within which ShardsManager keeps the chunks it
+/// receives. Unrequested chunks outside of
+/// [head - height_horizon, head + max_heights_ahead] are discarded. Additionally the memory
+/// held by the chunks still being assembled can be capped.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ChunkHorizonsConfig {
+    /// Chunks which are more than this many heights behind the head are dropped.
+    #[serde(default = "default_chunk_height_horizon")]
+    pub height_horizon: BlockHeightDelta,
+
+    /// Chunks which are more than this many heights ahead of the head are dropped.
+    #[serde(default = "default_chunk_max_heights_ahead")]
+    pub max_heights_ahead: BlockHeightDelta,
+
+    /// If set, the front horizon is widened beyond `max_heights_ahead` based on the
+    /// observed jitter of block arrival, but never past this many heights ahead of the head.
+    /// Useful on high-latency links, where chunks tend to arrive well before their blocks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_max_heights_ahead: Option<BlockHeightDelta>,
+
+    /// If set, once the parts and receipts held by the cache exceed this many bytes, the least
+    /// recently updated incomplete chunks are dropped. Complete chunks are never dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cache_bytes: Option<u64>,
+
+    /// If set, at most this many incomplete chunks are kept per shard, the least recently
+    /// updated ones are dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_incomplete_chunks_per_shard: Option<usize>,
+}
+
+impl Default for ChunkHorizonsConfig {
+    fn default() -> Self {
+        Self {
+            height_horizon: 1024,
+            max_heights_ahead: 5,
+            adaptive_max_heights_ahead: None,
+            max_cache_bytes: None,
+            max_incomplete_chunks_per_shard: None,
+        }
+    }
+}
+
+fn default_chunk_height_horizon() -> BlockHeightDelta {
+    ChunkHorizonsConfig::default().height_horizon
+}
+
+fn default_chunk_max_heights_ahead() -> BlockHeightDelta {
+    ChunkHorizonsConfig::default().max_heights_ahead
+}
+
+/// Configuration of the data availability sampling of the chunks of the shards the node
+/// doesn't track. For each such chunk, a few random parts are requested from their owners, and
+/// the chunk counts as unavailable if any of them doesn't arrive in time.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ChunkAvailabilitySamplingConfig {
+    /// Number of the parts sampled per chunk.
+    #[serde(default = "default_chunk_availability_sampling_num_parts")]
+    pub num_parts: u64,
+
+    /// Time within which the sampled parts have to arrive.
+    #[serde(default = "default_chunk_availability_sampling_timeout")]
+    pub timeout: Duration,
+}
+
+impl Default for ChunkAvailabilitySamplingConfig {
+    fn default() -> Self {
+        Self { num_parts: 3, timeout: Duration::from_secs(3) }
+    }
+}
+
+fn default_chunk_availability_sampling_num_parts() -> u64 {
+    ChunkAvailabilitySamplingConfig::default().num_parts
+}
+
+fn default_chunk_availability_sampling_timeout() -> Duration {
+    ChunkAvailabilitySamplingConfig::default().timeout
+}
+
+/// Configuration of the requests for the whole chunk bodies. A node which needs all of a chunk
+/// normally collects its parts from their owners, which for the nodes tracking the shard
+/// without producing its chunks (e.g. RPC nodes) costs many round trips. Instead, such a node
+/// can ask a single peer for the chunk body, which the peer serves if it has reconstructed the
+/// chunk already.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct FullChunkRequestsConfig {
+    /// Whether to request the whole chunk bodies rather than the parts, whenever the node needs
+    /// all of the chunk.
+    #[serde(default)]
+    pub request_full_chunks: bool,
+
+    /// Maximal size in bytes of a chunk body served to the peers asking for it. Larger chunks
+    /// are served as parts. Zero disables serving the chunk bodies.
+    #[serde(default = "default_max_full_chunk_response_size")]
+    pub max_response_size: u64,
+}
+
+impl Default for FullChunkRequestsConfig {
+    fn default() -> Self {
+        Self { request_full_chunks: false, max_response_size: 4 * 1024 * 1024 }
+    }
+}
+
+fn default_max_full_chunk_response_size() -> u64 {
+    FullChunkRequestsConfig::default().max_response_size
+}
+
+/// Configuration of the hedged chunk part requests. If the parts requested from their owners
+/// haven't arrived shortly after the request, the parts still missing are requested once more
+/// from another node tracking the shard, instead of waiting for the regular retry.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ChunkRequestHedgingConfig {
+    /// Time after sending a request for the chunk parts before it is hedged.
+    #[serde(default = "default_chunk_request_hedging_delay")]
+    pub delay: Duration,
+
+    /// Maximal number of the hedged requests sent for a single chunk.
+    #[serde(default = "default_chunk_request_max_hedges")]
+    pub max_hedges: u32,
+}
+
+impl Default for ChunkRequestHedgingConfig {
+    fn default() -> Self {
+        Self { delay: Duration::from_millis(50), max_hedges: 1 }
+    }
+}
+
+fn default_chunk_request_hedging_delay() -> Duration {
+    ChunkRequestHedgingConfig::default().delay
+}
+
+fn default_chunk_request_max_hedges() -> u32 {
+    ChunkRequestHedgingConfig::default().max_hedges
+}
+
+/// Configuration of the cache of the responses to the `view_account` and `view_access_key`
+/// queries, kept per block by the view client.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ViewQueryCacheConfig {
+    /// Responses are cached at most for this long.
+    #[serde(default = "default_view_query_cache_ttl")]
+    pub ttl: Duration,
+
+    /// Approximate limit on the memory taken by the cached responses.
+    /// The responses at the lowest blocks are evicted first.
+    #[serde(default = "default_view_query_cache_max_bytes")]
+    pub max_bytes: usize,
+}
+
+impl Default for ViewQueryCacheConfig {
+    fn default() -> Self {
+        Self { ttl: Duration::from_secs(10), max_bytes: 64 * 1024 * 1024 }
+    }
+}
+
+fn default_view_query_cache_ttl() -> Duration {
+    ViewQueryCacheConfig::default().ttl
+}
+
+fn default_view_query_cache_max_bytes() -> usize {
+    ViewQueryCacheConfig::default().max_bytes
+}
+
+/// Configuration of the background task which periodically re-verifies the recent chain
+/// data in the store, to detect on-disk corruption early.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct IntegrityScanConfig {
+    /// Time between the scans.
+    #[serde(default = "default_integrity_scan_period")]
+    pub period: Duration,
+
+    /// Number of blocks, counting back from the head, verified in each scan.
+    #[serde(default = "default_integrity_scan_num_blocks")]
+    pub num_blocks: NumBlocks,
+}
+
+impl Default for IntegrityScanConfig {
+    fn default() -> Self {
+        Self { period: Duration::from_secs(600), num_blocks: 1000 }
+    }
+}
+
+fn default_integrity_scan_period() -> Duration {
+    IntegrityScanConfig::default().period
+}
+
+fn default_integrity_scan_num_blocks() -> NumBlocks {
+    IntegrityScanConfig::default().num_blocks
+}
+
+/// Configuration of the periodic persisting of the selected counters, which makes them survive
+/// restarts of the node.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct MetricsSnapshotConfig {
+    /// Time between the snapshots. The increments since the last snapshot are lost if the node
+    /// crashes; on a graceful shutdown a final snapshot is taken.
+    #[serde(default = "default_metrics_snapshot_period")]
+    pub period: Duration,
+}
+
+impl Default for MetricsSnapshotConfig {
+    fn default() -> Self {
+        Self { period: Duration::from_secs(60) }
+    }
+}
+
+fn default_metrics_snapshot_period() -> Duration {
+    MetricsSnapshotConfig::default().period
+}
+
+/// Limits on the transactions accepted by the node, from RPC or forwarded by other nodes.
+/// They are checked before any other validation, so that the node doesn't spend resources on
+/// oversized transactions. Transactions within these limits are still subject to the (possibly
+/// stricter) limits of the protocol.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct TransactionLimitsConfig {
+    /// Maximal size in bytes of a serialized signed transaction.
+    #[serde(default = "default_max_transaction_size")]
+    pub max_transaction_size: u64,
+
+    /// Maximal number of actions in a transaction.
+    #[serde(default = "default_max_transaction_actions")]
+    pub max_actions: u64,
+
+    /// Maximal size in bytes of the arguments of a single FunctionCall action.
+    #[serde(default = "default_max_function_call_args_size")]
+    pub max_args_size: u64,
+
+    /// Maximal number of transactions signed with the same access key kept in the pool. The
+    /// pooled transactions all have nonces ahead of the on-chain one, so this bounds how many
+    /// future-nonce transactions a single key can park on the node. Checked when inserting
+    /// into the pool, after the validation against the state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pooled_per_key: Option<u64>,
+}
+
+impl Default for TransactionLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_transaction_size: 4 * 1024 * 1024,
+            max_actions: 100,
+            max_args_size: 4 * 1024 * 1024,
+            max_pooled_per_key: None,
+        }
+    }
+}
+
+fn default_max_transaction_size() -> u64 {
+    TransactionLimitsConfig::default().max_transaction_size
+}
+
+fn default_max_transaction_actions() -> u64 {
+    TransactionLimitsConfig::default().max_actions
+}
+
+fn default_max_function_call_args_size() -> u64 {
+    TransactionLimitsConfig::default().max_args_size
+}
+
+impl TransactionLimitsConfig {
+    /// Returns the error the protocol would report if `tx` exceeded its limits.
+    pub fn check(&self, tx: &SignedTransaction) -> Result<(), InvalidTxError> {
+        let size = tx.get_size();
+        if size > self.max_transaction_size {
+            return Err(InvalidTxError::TransactionSizeExceeded {
+                size,
+                limit: self.max_transaction_size,
+            });
+        }
+        let actions = &tx.transaction.actions;
+        if actions.len() as u64 > self.max_actions {
+            return Err(InvalidTxError::ActionsValidation(
+                ActionsValidationError::TotalNumberOfActionsExceeded {
+                    total_number_of_actions: actions.len() as u64,
+                    limit: self.max_actions,
+                },
+            ));
+        }
+        for action in actions {
+            if let Action::FunctionCall(function_call) = action {
+                let length = function_call.args.len() as u64;
+                if length > self.max_args_size {
+                    return Err(InvalidTxError::ActionsValidation(
+                        ActionsValidationError::FunctionCallArgumentsLengthExceeded {
+                            length,
+                            limit: self.max_args_size,
+                        },
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Order in which the transactions are taken from the pool when producing a chunk. Either way,
+/// the transactions signed with the same access key are taken by increasing nonce.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionPoolOrdering {
+    /// One transaction per access key in turn. The access keys are visited in the order of the
+    /// pool keys, which are randomized per node.
+    #[default]
+    AccessKeyRoundRobin,
+    /// One transaction per signer in turn, each signer rotating over its access keys, so that a
+    /// signer can't take a larger part of a chunk by spreading its transactions over many keys.
+    SignerRoundRobin,
+}
+
+/// Caps on the transaction pool of every shard, protecting the memory of the node from floods of
+/// transactions. Once a cap is exceeded, transactions are evicted from the pool, the oldest ones
+/// first. Only the transaction with the highest nonce of an access key is ever evicted, so that
+/// the other transactions of the key can still be included. Transactions don't set a gas price,
+/// and the gas they attach is prepaid and partly refunded, so neither is used to rank them.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct TransactionPoolLimits {
+    /// Maximal number of transactions in the pool of a shard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_transactions: Option<u64>,
+
+    /// Maximal total size in bytes of the transactions in the pool of a shard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+
+    /// Maximal number of transactions of a single signer in the pool of a shard. When exceeded,
+    /// the transactions of the signer are evicted rather than the ones of other signers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_transactions_per_signer: Option<u64>,
+
+    /// Maximal total size in bytes of the transactions of a single signer in the pool of a shard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_per_signer: Option<u64>,
+}
+
+/// Configuration of the forwarding of the transactions to the chunk producers of their shard,
+/// done by the nodes which don't produce its chunks soon, e.g. RPC nodes.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct TransactionForwardingConfig {
+    /// A transaction is forwarded to the chunk producers of the heights up to this many blocks
+    /// ahead of the head, and to the one twice as far ahead.
+    #[serde(default = "default_transaction_forwarding_horizon")]
+    pub horizon: BlockHeightDelta,
+
+    /// Number of blocks after forwarding a transaction without observing its inclusion in a
+    /// chunk after which it is forwarded again, to the chunk producers of the new heights.
+    #[serde(default = "default_transaction_forwarding_retry_after")]
+    pub retry_after: BlockHeightDelta,
+
+    /// Maximal number of times a transaction is forwarded again. The inclusion can only be
+    /// observed for the shards tracked by the node, so the transactions of the other shards are
+    /// always forwarded this many times again.
+    #[serde(default = "default_transaction_forwarding_max_retries")]
+    pub max_retries: u32,
+}
+
+impl Default for TransactionForwardingConfig {
+    fn default() -> Self {
+        Self { horizon: 4, retry_after: 8, max_retries: 2 }
+    }
+}
+
+fn default_transaction_forwarding_horizon() -> BlockHeightDelta {
+    TransactionForwardingConfig::default().horizon
+}
+
+fn default_transaction_forwarding_retry_after() -> BlockHeightDelta {
+    TransactionForwardingConfig::default().retry_after
+}
+
+fn default_transaction_forwarding_max_retries() -> u32 {
+    TransactionForwardingConfig::default().max_retries
+}
+
+/// Configuration of the caches which prevent the client from processing the same challenge,
+/// or forwarding the same transaction, more than once.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct DedupCachesConfig {
+    /// Maximal number of processed challenges remembered.
+    #[serde(default = "default_dedup_challenges_capacity")]
+    pub challenges_capacity: usize,
+
+    /// For how long a processed challenge is remembered.
+    #[serde(default = "default_dedup_challenges_ttl")]
+    pub challenges_ttl: Duration,
+
+    /// Maximal number of forwarded transactions remembered.
+    #[serde(default = "default_dedup_forwarded_txs_capacity")]
+    pub forwarded_txs_capacity: usize,
+
+    /// For how long a forwarded transaction is remembered. Within that time, the transaction
+    /// is not forwarded again, even if it is resubmitted.
+    #[serde(default = "default_dedup_forwarded_txs_ttl")]
+    pub forwarded_txs_ttl: Duration,
+
+    /// Whether the caches are persisted in the store, so that they survive a restart.
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+impl Default for DedupCachesConfig {
+    fn default() -> Self {
+        Self {
+            challenges_capacity: 10_000,
+            challenges_ttl: Duration::from_secs(3600),
+            forwarded_txs_capacity: 100_000,
+            forwarded_txs_ttl: Duration::from_secs(60),
+            persistent: false,
+        }
+    }
+}
+
+fn default_dedup_challenges_capacity() -> usize {
+    DedupCachesConfig::default().challenges_capacity
+}
+
+fn default_dedup_challenges_ttl() -> Duration {
+    DedupCachesConfig::default().challenges_ttl
+}
+
+fn default_dedup_forwarded_txs_capacity() -> usize {
+    DedupCachesConfig::default().forwarded_txs_capacity
+}
+
+fn default_dedup_forwarded_txs_ttl() -> Duration {
+    DedupCachesConfig::default().forwarded_txs_ttl
+}
+
 /// ClientConfig where some fields can be updated at runtime.
 #[derive(Clone, serde::Serialize)]
 pub struct ClientConfig {
@@ -108,6 +537,12 @@ pub struct ClientConfig {
     pub state_sync_timeout: Duration,
     /// Minimum number of peers to start syncing.
     pub min_num_peers: usize,
+    /// Minimum number of healthy peers, i.e. connected peers we've heard from recently, required
+    /// to produce a block. 0 disables the check.
+    pub min_block_production_peers: usize,
+    /// Minimum number of other validators whose approvals for the height are required to produce
+    /// a block at it. 0 disables the check.
+    pub min_block_production_approvers: usize,
     /// Period between logging summary information.
     pub log_summary_period: Duration,
     /// Enable coloring of the logs
@@ -132,12 +567,43 @@ pub struct ClientConfig {
     pub doosmslug_step_period: Duration,
     /// Behind this horizon header fetch kicks in.
     pub block_header_fetch_horizon: BlockHeightDelta,
+    /// Horizons within which received chunks are kept.
+    pub chunk_horizons: ChunkHorizonsConfig,
+    /// Whether to check the receipt proofs and, for the tracked shards, the transactions and
+    /// receipts roots of a chunk before including it in a produced block.
+    pub pre_validate_chunks: bool,
+    /// Number of the threads fully validating the headers of the incomplete chunks once their
+    /// previous block is accepted. 0 validates them on the ShardsManager thread.
+    pub chunk_header_validation_threads: usize,
+    /// If set, the produced blocks vote for this protocol version, regardless of the upgrade
+    /// schedule of the binary.
+    pub protocol_version_vote_override: Option<ProtocolVersion>,
+    /// If set, the chunks of the shards this node doesn't track are sampled for availability.
+    pub chunk_availability_sampling: Option<ChunkAvailabilitySamplingConfig>,
+    /// Requesting and serving the whole chunk bodies.
+    pub full_chunk_requests: FullChunkRequestsConfig,
+    /// If set, the chunk part requests which aren't answered quickly are repeated to another node.
+    pub chunk_request_hedging: Option<ChunkRequestHedgingConfig>,
+    /// If set, recent chain data is periodically re-verified in the background.
+    pub integrity_scan: Option<IntegrityScanConfig>,
+    /// If set, the selected counters are periodically persisted and restored on startup.
+    pub metrics_snapshot: Option<MetricsSnapshotConfig>,
+    /// Limits on the transactions accepted by the node.
+    pub transaction_limits: TransactionLimitsConfig,
+    /// Caps on the transaction pools, enforced by evicting transactions.
+    pub transaction_pool_limits: TransactionPoolLimits,
+    /// Order in which the pooled transactions are included in the produced chunks.
+    pub transaction_pool_ordering: TransactionPoolOrdering,
+    /// Forwarding of the transactions to the upcoming chunk producers.
+    pub transaction_forwarding: TransactionForwardingConfig,
     /// Garbage collection configuration.
     pub gc: GCConfig,
     /// Accounts that this client tracks
     pub tracked_accounts: Vec<AccountId>,
     /// Shards that this client tracks
     pub tracked_shards: Vec<ShardId>,
+    /// Only track the shards the node produces chunks for while it is a chunk-only producer.
+    pub chunk_only_producer_lightweight_mode: bool,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
     /// save_trie_changes should be set to true iff
@@ -147,6 +613,11 @@ pub struct ClientConfig {
     pub save_trie_changes: bool,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
+    /// Number of threads executing the heavy queries (view_state, call_function,
+    /// view_access_key_list) on behalf of the ViewClientActor pool.
+    pub view_client_query_threads: usize,
+    /// If set, the view client caches the responses to the cheap view queries per block.
+    pub view_query_cache: Option<ViewQueryCacheConfig>,
     /// Run Epoch Sync on the start.
     pub epoch_sync_enabled: bool,
     /// Number of seconds between state requests for view client.
@@ -175,6 +646,28 @@ pub struct ClientConfig {
     /// Whether to use the State Sync mechanism.
     /// If disabled, the node will do Block Sync instead of State Sync.
     pub state_sync_enabled: bool,
+    /// Whether to journal the signed blocks, chunks and approvals in the store before
+    /// broadcasting them, so that the node doesn't sign conflicting ones after a restart.
+    pub signing_journal: bool,
+    /// Whether to stop signing when blocks or approvals signed with our validator key, but not by
+    /// this node, are received, i.e. when another node runs with the same key.
+    pub double_sign_detection: bool,
+    /// Caches of the processed challenges and forwarded transactions.
+    pub dedup_caches: DedupCachesConfig,
+    /// If set, the node refuses to switch to a fork reverting more than this many blocks,
+    /// until the operator allows it via the admin API.
+    pub max_reorg_depth: Option<BlockHeightDelta>,
+    /// Whether to index the outcomes of the tracked shards by the account which executed them,
+    /// to serve the recent activity of an account via RPC.
+    pub index_account_activity: bool,
+    /// Number of threads applying the chunks of the tracked shards in parallel. Zero means one
+    /// per CPU core.
+    pub apply_chunks_threads: usize,
+    /// Whether to compute and persist the state sync headers of the tracked shards as soon as
+    /// the sync block of an epoch is final, rather than when they are first requested.
+    pub precompute_state_sync_headers: bool,
+    /// Effective states of the rollout flags, see `crate::rollout`.
+    pub rollout_flags: Vec<RolloutFlag>,
 }
 
 impl ClientConfig {
@@ -215,6 +708,8 @@ impl ClientConfig {
             state_sync_timeout: Duration::from_secs(TEST_STATE_SYNC_TIMEOUT),
             header_sync_expected_height_per_second: 1,
             min_num_peers: 1,
+            min_block_production_peers: 0,
+            min_block_production_approvers: 0,
             log_summary_period: Duration::from_secs(10),
             produce_empty_blocks: true,
             epoch_length: 10,
@@ -229,13 +724,29 @@ impl ClientConfig {
             ),
             doosmslug_step_period: Duration::from_millis(100),
             block_header_fetch_horizon: 50,
+            chunk_horizons: ChunkHorizonsConfig::default(),
+            pre_validate_chunks: false,
+            chunk_header_validation_threads: 0,
+            protocol_version_vote_override: None,
+            chunk_availability_sampling: None,
+            full_chunk_requests: FullChunkRequestsConfig::default(),
+            chunk_request_hedging: None,
+            integrity_scan: None,
+            metrics_snapshot: None,
+            transaction_limits: TransactionLimitsConfig::default(),
+            transaction_pool_limits: TransactionPoolLimits::default(),
+            transaction_pool_ordering: TransactionPoolOrdering::default(),
+            transaction_forwarding: TransactionForwardingConfig::default(),
             gc: GCConfig { gc_blocks_limit: 100, ..GCConfig::default() },
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            chunk_only_producer_lightweight_mode: false,
             archive,
             save_trie_changes,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
+            view_client_query_threads: 1,
+            view_query_cache: None,
             epoch_sync_enabled,
             view_client_throttle_period: Duration::from_secs(1),
             trie_viewer_state_size_limit: None,
@@ -248,6 +759,92 @@ impl ClientConfig {
             state_sync_s3_region: String::new(),
             state_sync_restart_dump_for_shards: vec![],
             state_sync_enabled: true,
+            signing_journal: false,
+            double_sign_detection: false,
+            dedup_caches: DedupCachesConfig::default(),
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
+            precompute_state_sync_headers: false,
+            rollout_flags: vec![],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionLimitsConfig;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_primitives::errors::{ActionsValidationError, InvalidTxError};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::{
+        Action, FunctionCallAction, SignedTransaction, TransferAction,
+    };
+
+    fn tx(actions: Vec<Action>) -> SignedTransaction {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        SignedTransaction::from_actions(
+            1,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &signer,
+            actions,
+            CryptoHash::default(),
+        )
+    }
+
+    fn function_call(args_size: usize) -> Action {
+        Action::FunctionCall(FunctionCallAction {
+            method_name: "main".to_string(),
+            args: vec![0; args_size],
+            gas: 1,
+            deposit: 0,
+        })
+    }
+
+    #[test]
+    fn test_transaction_size_limit() {
+        let tx = tx(vec![function_call(100)]);
+        let size = tx.get_size();
+        let limits = TransactionLimitsConfig { max_transaction_size: size, ..Default::default() };
+        assert_eq!(limits.check(&tx), Ok(()));
+        let limits =
+            TransactionLimitsConfig { max_transaction_size: size - 1, ..Default::default() };
+        assert_eq!(
+            limits.check(&tx),
+            Err(InvalidTxError::TransactionSizeExceeded { size, limit: size - 1 })
+        );
+    }
+
+    #[test]
+    fn test_number_of_actions_limit() {
+        let limits = TransactionLimitsConfig { max_actions: 2, ..Default::default() };
+        let transfer = || Action::Transfer(TransferAction { deposit: 1 });
+        assert_eq!(limits.check(&tx(vec![transfer(), transfer()])), Ok(()));
+        assert_eq!(
+            limits.check(&tx(vec![transfer(), transfer(), transfer()])),
+            Err(InvalidTxError::ActionsValidation(
+                ActionsValidationError::TotalNumberOfActionsExceeded {
+                    total_number_of_actions: 3,
+                    limit: 2
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_function_call_args_size_limit() {
+        let limits = TransactionLimitsConfig { max_args_size: 10, ..Default::default() };
+        assert_eq!(limits.check(&tx(vec![function_call(10), function_call(10)])), Ok(()));
+        // Each action is checked, not only the first one.
+        assert_eq!(
+            limits.check(&tx(vec![function_call(10), function_call(11)])),
+            Err(InvalidTxError::ActionsValidation(
+                ActionsValidationError::FunctionCallArgumentsLengthExceeded {
+                    length: 11,
+                    limit: 10
+                }
+            ))
+        );
+    }
+}
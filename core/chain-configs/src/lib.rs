@@ -2,14 +2,19 @@ mod client_config;
 mod genesis_config;
 pub mod genesis_validate;
 mod metrics;
+mod rollout;
 mod updateable_config;
 
 pub use client_config::{
-    ClientConfig, GCConfig, LogSummaryStyle, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
-    MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
+    ChunkAvailabilitySamplingConfig, ChunkHorizonsConfig, ChunkRequestHedgingConfig, ClientConfig,
+    DedupCachesConfig, FullChunkRequestsConfig, GCConfig, IntegrityScanConfig, LogSummaryStyle,
+    MetricsSnapshotConfig, TransactionForwardingConfig, TransactionLimitsConfig,
+    TransactionPoolLimits, TransactionPoolOrdering, ViewQueryCacheConfig,
+    DEFAULT_GC_NUM_EPOCHS_TO_KEEP, MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
 };
 pub use genesis_config::{
     get_initial_supply, stream_records_from_file, Genesis, GenesisChangeConfig, GenesisConfig,
     GenesisRecords, GenesisValidationMode, ProtocolConfig, ProtocolConfigView,
 };
-pub use updateable_config::{MutableConfigValue, UpdateableClientConfig};
+pub use rollout::{export_rollout_flags_metrics, RolloutFlag, RolloutState};
+pub use updateable_config::{MutableConfigValue, UpdateableClientConfig, UpdateableNetworkConfig};
@@ -9,3 +9,12 @@ pub static CONFIG_MUTABLE_FIELD: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static ROLLOUT_FLAG_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_rollout_flag_state",
+        "Whether the rollout flag is in the given state",
+        &["flag", "state"],
+    )
+    .unwrap()
+});
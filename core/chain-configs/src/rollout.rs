@@ -0,0 +1,57 @@
+//! Rollout flags of the node-level features.
+//!
+//! A feature which is risky to switch on across the whole network at once is guarded by a named
+//! rollout flag, set in the `rollout` section of config.json, for example
+//! `"rollout": {"tier1": "shadow"}`. Each flag is in one of the states:
+//! * `disabled` - the feature is off.
+//! * `shadow` - the feature runs alongside the old behavior, but its results are only observed
+//!   (logged and exported as metrics) and do not affect the node.
+//! * `enabled` - the feature is on.
+//!
+//! The flags not set in the config take the state derived from the underlying config fields.
+//! The effective states are exported as the `near_rollout_flag_state` metric and are available
+//! at the /debug/api/rollout_flags endpoint.
+use crate::metrics;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutState {
+    Disabled,
+    Shadow,
+    Enabled,
+}
+
+impl RolloutState {
+    pub const ALL: [RolloutState; 3] =
+        [RolloutState::Disabled, RolloutState::Shadow, RolloutState::Enabled];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RolloutState::Disabled => "disabled",
+            RolloutState::Shadow => "shadow",
+            RolloutState::Enabled => "enabled",
+        }
+    }
+}
+
+/// Effective state of a rollout flag.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RolloutFlag {
+    pub name: String,
+    pub state: RolloutState,
+    /// Whether the state has been set explicitly in the `rollout` config section.
+    pub overridden: bool,
+}
+
+/// Sets `near_rollout_flag_state{flag, state}` to 1 for the current state of every flag and to
+/// 0 for the other states, so that the transitions are visible on the dashboards.
+pub fn export_rollout_flags_metrics(flags: &[RolloutFlag]) {
+    for flag in flags {
+        for state in RolloutState::ALL {
+            metrics::ROLLOUT_FLAG_STATE
+                .with_label_values(&[&flag.name, state.as_str()])
+                .set((flag.state == state) as i64);
+        }
+    }
+}
@@ -5,6 +5,12 @@ use near_primitives::types::BlockHeight;
 use serde::{Deserialize, Serialize, Serializer};
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Lowest `min_block_production_delay` which can be set while the node is running.
+const MIN_BLOCK_PRODUCTION_DELAY: Duration = Duration::from_millis(100);
+/// Highest `max_block_wait_delay` which can be set while the node is running.
+const MAX_BLOCK_WAIT_DELAY: Duration = Duration::from_secs(60);
 
 /// A wrapper for a config value that can be updated while the node is running.
 /// When initializing sub-objects (e.g. `ShardsManager`), please make sure to
@@ -85,4 +91,85 @@ impl<T: Copy + PartialEq + Debug> MutableConfigValue<T> {
 pub struct UpdateableClientConfig {
     /// Graceful shutdown at expected block height.
     pub expected_shutdown: Option<BlockHeight>,
+    /// Minimum duration before producing a block, also the delay before sending an endorsement.
+    pub min_block_production_delay: Duration,
+    /// Delay before skipping a height once the previous block is final, increasing as more
+    /// heights are skipped.
+    pub max_block_production_delay: Duration,
+    /// Maximum delay before skipping a height.
+    pub max_block_wait_delay: Duration,
+}
+
+impl UpdateableClientConfig {
+    /// Checks that the block production delays are within bounds which keep Doomslug working,
+    /// and returns the reason if they aren't.
+    pub fn validate_block_production_delays(&self) -> Result<(), String> {
+        if self.min_block_production_delay < MIN_BLOCK_PRODUCTION_DELAY {
+            return Err(format!(
+                "min_block_production_delay {:?} is lower than {:?}",
+                self.min_block_production_delay, MIN_BLOCK_PRODUCTION_DELAY
+            ));
+        }
+        // Doomslug skips a height only after having sent the endorsement for the previous one.
+        if self.max_block_production_delay < 2 * self.min_block_production_delay {
+            return Err(format!(
+                "max_block_production_delay {:?} is lower than twice min_block_production_delay {:?}",
+                self.max_block_production_delay, self.min_block_production_delay
+            ));
+        }
+        if self.max_block_wait_delay < self.max_block_production_delay {
+            return Err(format!(
+                "max_block_wait_delay {:?} is lower than max_block_production_delay {:?}",
+                self.max_block_wait_delay, self.max_block_production_delay
+            ));
+        }
+        if self.max_block_wait_delay > MAX_BLOCK_WAIT_DELAY {
+            return Err(format!(
+                "max_block_wait_delay {:?} is greater than {:?}",
+                self.max_block_wait_delay, MAX_BLOCK_WAIT_DELAY
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+/// A subset of the network config that can be updated while the node is running.
+pub struct UpdateableNetworkConfig {
+    /// Receive rate limit of a single TIER1 connection, in bytes per second.
+    pub tier1_recv_bytes_per_second: f64,
+    pub tier1_recv_burst_bytes: u64,
+    /// Receive rate limit of a single TIER2 connection, in bytes per second.
+    pub tier2_recv_bytes_per_second: f64,
+    pub tier2_recv_burst_bytes: u64,
+    /// Maximal number of TIER2 connections.
+    pub max_num_peers: u32,
+    /// Comma-separated list of nodes allowed to connect even above `max_num_peers`.
+    pub whitelist_nodes: String,
+    /// Addresses of the peers which we don't connect to.
+    pub blacklist: Vec<String>,
+    /// Comma-separated list of the boot nodes.
+    pub boot_nodes: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UpdateableClientConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_validate_block_production_delays() {
+        let config = |min: u64, max: u64, wait: u64| UpdateableClientConfig {
+            expected_shutdown: None,
+            min_block_production_delay: Duration::from_millis(min),
+            max_block_production_delay: Duration::from_millis(max),
+            max_block_wait_delay: Duration::from_millis(wait),
+        };
+        assert!(config(600, 2000, 6000).validate_block_production_delays().is_ok());
+        assert!(config(600, 1200, 1200).validate_block_production_delays().is_ok());
+        assert!(config(50, 2000, 6000).validate_block_production_delays().is_err());
+        assert!(config(600, 1000, 6000).validate_block_production_delays().is_err());
+        assert!(config(600, 2000, 1500).validate_block_production_delays().is_err());
+        assert!(config(600, 2000, 120_000).validate_block_production_delays().is_err());
+    }
 }
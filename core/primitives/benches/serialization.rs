@@ -69,6 +69,7 @@ fn create_block() -> Block {
         CryptoHash::default(),
         CryptoHash::default(),
         None,
+        None,
     )
 }
 
@@ -210,6 +210,7 @@ impl Block {
         next_bp_hash: CryptoHash,
         block_merkle_root: CryptoHash,
         timestamp_override: Option<DateTime<chrono::Utc>>,
+        protocol_version_vote_override: Option<ProtocolVersion>,
     ) -> Self {
         // Collect aggregate of validators and gas usage/limits from chunks.
         let mut validator_proposals = vec![];
@@ -292,6 +293,7 @@ impl Block {
             next_bp_hash,
             block_merkle_root,
             prev.height(),
+            protocol_version_vote_override,
         );
 
         Self::block_from_protocol_version(
@@ -356,6 +356,7 @@ impl BlockHeader {
         next_bp_hash: CryptoHash,
         block_merkle_root: CryptoHash,
         prev_height: BlockHeight,
+        protocol_version_vote_override: Option<ProtocolVersion>,
     ) -> Self {
         let inner_lite = BlockHeaderInnerLite {
             height,
@@ -449,7 +450,8 @@ impl BlockHeader {
                 prev_height,
                 epoch_sync_data_hash,
                 approvals,
-                latest_protocol_version: get_protocol_version(next_epoch_protocol_version),
+                latest_protocol_version: protocol_version_vote_override
+                    .unwrap_or_else(|| get_protocol_version(next_epoch_protocol_version)),
             };
             let (hash, signature) = signer.sign_block_header_parts(
                 prev_hash,
@@ -15,6 +15,7 @@ pub mod epoch_manager;
 pub mod errors;
 pub mod merkle;
 pub mod network;
+pub mod proof;
 pub mod rand;
 pub mod receipt;
 pub mod runtime;
@@ -0,0 +1,197 @@
+//! Verification of the merkle proofs served by the nodes.
+//!
+//! Light clients and bridges don't keep the chain, so they have to check that the data served
+//! to them (an execution outcome, a set of receipts, a block) is committed to by a block they
+//! already trust. The functions below verify the proofs the same way the node itself does, so
+//! that the external verifiers don't need to reimplement the hashing scheme.
+//!
+//! The commitments form a chain of merkle trees:
+//! * a block is committed to by the `block_merkle_root` of every later block,
+//! * an execution outcome is committed to by the `outcome_root` of the block which contains
+//!   the chunk that executed it,
+//! * the receipts sent from one shard to another are committed to by the `chunk_receipts_root`
+//!   of the block which contains the chunk that produced them.
+use crate::hash::CryptoHash;
+use crate::merkle::{compute_root_from_path_and_item, verify_hash, verify_path, MerklePath};
+use crate::sharding::{ReceiptList, ReceiptProof};
+use crate::syncing::RootProof;
+use crate::types::ShardId;
+use crate::views::{ExecutionOutcomeWithIdView, LightClientBlockLiteView};
+
+/// Verifies that the block with `block_hash` is an ancestor of the block with the given
+/// `block_merkle_root`, as proven by `block_proof`.
+///
+/// `block_merkle_root` of a block commits to all of its ancestors, but not to the block itself.
+pub fn verify_block_proof(
+    block_hash: &CryptoHash,
+    block_proof: &MerklePath,
+    block_merkle_root: &CryptoHash,
+) -> bool {
+    verify_hash(*block_merkle_root, block_proof, *block_hash)
+}
+
+/// Verifies that `outcome` is included in the block with the given `outcome_root`.
+///
+/// `outcome.proof` proves that the outcome is included in the outcome root of its chunk,
+/// and `outcome_root_proof` proves that the outcome root of the chunk is included in the
+/// outcome root of the block.
+pub fn verify_outcome_proof(
+    outcome: &ExecutionOutcomeWithIdView,
+    outcome_root_proof: &MerklePath,
+    block_outcome_root: &CryptoHash,
+) -> bool {
+    let chunk_outcome_root = compute_root_from_path_and_item(&outcome.proof, outcome.to_hashes());
+    verify_path(*block_outcome_root, outcome_root_proof, chunk_outcome_root)
+}
+
+/// Verifies the proof returned by the `light_client_proof` RPC method: `outcome` is included
+/// in the block `block_header_lite`, which in turn is an ancestor of the block with the given
+/// `block_merkle_root` (typically the latest block known to the light client).
+pub fn verify_light_client_execution_proof(
+    outcome: &ExecutionOutcomeWithIdView,
+    outcome_root_proof: &MerklePath,
+    block_header_lite: &LightClientBlockLiteView,
+    block_proof: &MerklePath,
+    block_merkle_root: &CryptoHash,
+) -> bool {
+    verify_outcome_proof(outcome, outcome_root_proof, &block_header_lite.inner_lite.outcome_root)
+        && verify_block_proof(&block_header_lite.hash(), block_proof, block_merkle_root)
+}
+
+/// Verifies that the receipts in `receipt_proof` are exactly the receipts sent to the shard
+/// `to_shard_id` by a chunk included in the block with the given `chunk_receipts_root`.
+///
+/// `RootProof` consists of the outgoing receipts root of the chunk and the proof of its
+/// inclusion in the block. `to_shard_id` should come from the verifier rather than from the
+/// proof itself, otherwise the receipts sent to another shard would pass the check.
+pub fn verify_receipt_proof(
+    to_shard_id: ShardId,
+    receipt_proof: &ReceiptProof,
+    root_proof: &RootProof,
+    chunk_receipts_root: &CryptoHash,
+) -> bool {
+    let ReceiptProof(receipts, shard_proof) = receipt_proof;
+    let RootProof(outgoing_receipts_root, block_proof) = root_proof;
+    let receipts_hash = CryptoHash::hash_borsh(ReceiptList(to_shard_id, receipts));
+    verify_path(*outgoing_receipts_root, &shard_proof.proof, &receipts_hash)
+        && verify_path(*chunk_receipts_root, block_proof, outgoing_receipts_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::merklize;
+    use crate::receipt::Receipt;
+    use crate::sharding::ShardProof;
+    use crate::transaction::{
+        ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof,
+    };
+
+    fn make_outcomes(ids: std::ops::Range<u64>) -> Vec<ExecutionOutcomeWithIdView> {
+        ids.map(|i| {
+            ExecutionOutcomeWithIdAndProof {
+                proof: vec![],
+                block_hash: CryptoHash::default(),
+                outcome_with_id: ExecutionOutcomeWithId {
+                    id: CryptoHash::hash_borsh(i),
+                    outcome: ExecutionOutcome { gas_burnt: i, ..Default::default() },
+                },
+            }
+            .into()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_block_proof() {
+        // merklize() hashes the items, so the leaves of the tree (i.e. the block hashes)
+        // are the hashes of the items.
+        let items: Vec<_> = (0..10u64).collect();
+        let (root, paths) = merklize(&items);
+        let blocks: Vec<_> = items.iter().map(CryptoHash::hash_borsh).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            assert!(verify_block_proof(block, &paths[i], &root));
+            assert!(!verify_block_proof(block, &paths[(i + 1) % blocks.len()], &root));
+        }
+    }
+
+    #[test]
+    fn test_outcome_proof() {
+        // Two chunks with 3 different outcomes each.
+        let chunks: Vec<_> = (0..2).map(|i| make_outcomes(i * 3..(i + 1) * 3)).collect();
+        let mut chunk_roots = vec![];
+        let mut outcomes = vec![];
+        for chunk in chunks {
+            let (root, paths) =
+                merklize(&chunk.iter().map(|outcome| outcome.to_hashes()).collect::<Vec<_>>());
+            chunk_roots.push(root);
+            outcomes.push(
+                chunk
+                    .into_iter()
+                    .zip(paths)
+                    .map(|(outcome, proof)| ExecutionOutcomeWithIdView { proof, ..outcome })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        let (block_outcome_root, chunk_paths) = merklize(&chunk_roots);
+        for (shard, chunk) in outcomes.iter().enumerate() {
+            for outcome in chunk {
+                assert!(verify_outcome_proof(outcome, &chunk_paths[shard], &block_outcome_root));
+                assert!(!verify_outcome_proof(
+                    outcome,
+                    &chunk_paths[1 - shard],
+                    &block_outcome_root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_receipt_proof() {
+        let account_id = "test".parse().unwrap();
+        // Receipts sent by a single chunk to shards 0 and 1.
+        let receipts: Vec<Vec<Receipt>> = (0..2)
+            .map(|shard| {
+                (0..3).map(|i| Receipt::new_balance_refund(&account_id, shard * 10 + i)).collect()
+            })
+            .collect();
+        let (outgoing_receipts_root, paths) = merklize(
+            &receipts
+                .iter()
+                .enumerate()
+                .map(|(shard, receipts)| {
+                    CryptoHash::hash_borsh(ReceiptList(shard as ShardId, receipts))
+                })
+                .collect::<Vec<_>>(),
+        );
+        // The block includes chunks of 2 shards, the receipts are sent by the 2nd one.
+        let (chunk_receipts_root, block_paths) =
+            merklize(&[CryptoHash::default(), outgoing_receipts_root]);
+        let root_proof = RootProof(outgoing_receipts_root, block_paths[1].clone());
+        for (shard, receipts) in receipts.into_iter().enumerate() {
+            let receipt_proof = ReceiptProof(
+                receipts,
+                ShardProof {
+                    from_shard_id: 1,
+                    to_shard_id: shard as ShardId,
+                    proof: paths[shard].clone(),
+                },
+            );
+            let shard = shard as ShardId;
+            assert!(verify_receipt_proof(shard, &receipt_proof, &root_proof, &chunk_receipts_root));
+            assert!(!verify_receipt_proof(
+                1 - shard,
+                &receipt_proof,
+                &root_proof,
+                &chunk_receipts_root
+            ));
+            let wrong_root_proof = RootProof(outgoing_receipts_root, block_paths[0].clone());
+            assert!(!verify_receipt_proof(
+                shard,
+                &receipt_proof,
+                &wrong_root_proof,
+                &chunk_receipts_root
+            ));
+        }
+    }
+}
@@ -396,6 +396,7 @@ impl TestBlockBuilder {
             self.next_bp_hash,
             self.block_merkle_root,
             None,
+            None,
         )
     }
 }
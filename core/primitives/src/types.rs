@@ -243,6 +243,60 @@ impl StateChangeValue {
             | StateChangeValue::ContractCodeDeletion { account_id } => account_id,
         }
     }
+
+    pub fn kind(&self) -> StateChangeValueKind {
+        match &self {
+            StateChangeValue::AccountUpdate { .. } | StateChangeValue::AccountDeletion { .. } => {
+                StateChangeValueKind::Account
+            }
+            StateChangeValue::AccessKeyUpdate { .. }
+            | StateChangeValue::AccessKeyDeletion { .. } => StateChangeValueKind::AccessKey,
+            StateChangeValue::DataUpdate { .. } | StateChangeValue::DataDeletion { .. } => {
+                StateChangeValueKind::Data
+            }
+            StateChangeValue::ContractCodeUpdate { .. }
+            | StateChangeValue::ContractCodeDeletion { .. } => StateChangeValueKind::ContractCode,
+        }
+    }
+}
+
+/// Kind of the state (Trie) entry affected by a state change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateChangeValueKind {
+    Account,
+    AccessKey,
+    Data,
+    ContractCode,
+}
+
+/// Filter applied to state changes before they are returned to the caller.
+/// The default filter accepts all state changes.
+#[derive(Debug, Default)]
+pub struct StateChangesFilter {
+    /// If non-empty, only the state changes of the listed kinds are accepted.
+    pub kinds: Vec<StateChangeValueKind>,
+    /// If set, only the data changes with keys starting with `key_prefix` are accepted.
+    /// State changes of other kinds are not affected.
+    pub key_prefix: Option<StoreKey>,
+}
+
+impl StateChangesFilter {
+    pub fn matches(&self, value: &StateChangeValue) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&value.kind()) {
+            return false;
+        }
+        match (&self.key_prefix, value) {
+            (
+                Some(key_prefix),
+                StateChangeValue::DataUpdate { key, .. }
+                | StateChangeValue::DataDeletion { key, .. },
+            ) => {
+                let (key, key_prefix): (&[u8], &[u8]) = (key.as_ref(), key_prefix.as_ref());
+                key.starts_with(key_prefix)
+            }
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -30,10 +30,11 @@ use crate::transaction::{
     SignedTransaction, StakeAction, TransferAction,
 };
 use crate::types::{
-    AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
-    EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
-    StateChangeValue, StateChangeWithCause, StateChangesRequest, StateRoot, StorageUsage, StoreKey,
-    StoreValue, ValidatorKickoutReason,
+    AccountId, AccountWithPublicKey, Balance, BlockHeight, BlockHeightDelta, CompiledContractCache,
+    EpochHeight, EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause,
+    StateChangeKind, StateChangeValue, StateChangeValueKind, StateChangeWithCause,
+    StateChangesFilter, StateChangesRequest, StateRoot, StorageUsage, StoreKey, StoreValue,
+    ValidatorKickoutReason,
 };
 use crate::version::{ProtocolVersion, Version};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -43,7 +44,7 @@ use near_o11y::pretty;
 use near_primitives_core::config::{ActionCosts, ExtCosts, ParameterCost, VMConfig};
 use near_primitives_core::runtime::fees::Fee;
 use num_rational::Rational32;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::ops::Range;
 use std::sync::Arc;
@@ -349,6 +350,22 @@ pub struct StatusSyncInfo {
     pub earliest_block_time: Option<DateTime<chrono::Utc>>,
     pub epoch_id: Option<EpochId>,
     pub epoch_start_height: Option<BlockHeight>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finality: Option<FinalityStatusView>,
+}
+
+/// Progress of finality, as observed by the node.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FinalityStatusView {
+    pub final_block_hash: CryptoHash,
+    pub final_block_height: BlockHeight,
+    /// Difference between the heights of the head and the last final block.
+    pub final_head_lag: BlockHeightDelta,
+    /// Number of known blocks at the height of the head. More than 1 means that the chain
+    /// has forked at the head.
+    pub num_forks_at_head: usize,
+    /// Time since the node has observed the last final block advancing.
+    pub seconds_since_finality_advanced: u64,
 }
 
 // TODO: add more information to ValidatorInfo
@@ -376,6 +393,18 @@ pub struct PeerInfoView {
     pub is_outbound_peer: bool,
     /// Connection nonce.
     pub nonce: u64,
+    /// Traffic since the connection has been established, by message type.
+    #[serde(default)]
+    pub traffic_by_message_type: BTreeMap<String, MessageTrafficView>,
+}
+
+/// Traffic of a single type of network message exchanged with a peer.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageTrafficView {
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub received_messages: u64,
+    pub received_bytes: u64,
 }
 
 /// Information about a Producer: its account name, peer_id and a list of connected peers that
@@ -446,6 +475,107 @@ pub struct RecentOutboundConnectionsView {
     pub recent_outbound_connections: Vec<ConnectionInfoView>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct DialerView {
+    /// Limit on the outbound connection attempts.
+    pub attempts_qps: f64,
+    pub attempts_burst: u64,
+    /// Peers to which the recent connection attempts have failed, most recent failure first.
+    pub peers: Vec<DialerPeerView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct DialerPeerView {
+    pub peer_id: PeerId,
+    /// Number of consecutive failed connection attempts.
+    pub failures: u32,
+    pub last_error: String,
+    pub last_failure: i64,
+    /// The peer won't be dialed again before this time.
+    pub retry_at: i64,
+    pub backing_off: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct RoutedMessageTraceEventView {
+    pub trace_id: u64,
+    pub msg_type: String,
+    pub timestamp_ms: i64,
+    pub event: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct RoutedMessageTraceView {
+    /// Recent events concerning the traced routed messages, oldest first.
+    pub events: Vec<RoutedMessageTraceEventView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct BlockPropagationEntryView {
+    pub block_hash: CryptoHash,
+    pub height: BlockHeight,
+    /// Peer from which the block has been received first.
+    pub source: PeerId,
+    /// Timestamp from the block header.
+    pub produced_at_ms: i64,
+    pub first_seen_ms: i64,
+    pub latency_ms: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct BlockPropagationView {
+    /// Percentiles of the propagation latency of the recent blocks.
+    /// None if no blocks have been recorded.
+    pub latency_p50_ms: Option<i64>,
+    pub latency_p90_ms: Option<i64>,
+    pub latency_p99_ms: Option<i64>,
+    /// Recent blocks, in the order of the first sighting.
+    pub blocks: Vec<BlockPropagationEntryView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct PingResultView {
+    /// The target as requested: a PeerId or an AccountId.
+    pub target: String,
+    /// PeerId of the target. None if the account is not known to the routing layer.
+    pub peer_id: Option<PeerId>,
+    /// Direct peers through which the ping has been routed, according to the routing table.
+    pub next_hops: Vec<PeerId>,
+    /// Round-trip time. None if no pong has been received before the timeout.
+    pub rtt_ms: Option<i64>,
+    /// Number of hops the pong has travelled, assuming that the target uses the same
+    /// `routed_message_ttl` as this node.
+    pub pong_hops: Option<u8>,
+    /// The reason why the ping couldn't be sent, if any.
+    pub error: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct RouteInfoView {
+    /// The target as requested: a PeerId or an AccountId.
+    pub target: String,
+    /// PeerId of the target. None if the account is not known to the routing layer.
+    pub peer_id: Option<PeerId>,
+    /// Direct peers on the shortest paths to the target, according to the routing table.
+    /// Empty if the target is unreachable.
+    pub next_hops: Vec<PeerId>,
+    /// Number of hops on the shortest path to the target in the known network graph.
+    pub distance: Option<u32>,
+    /// TTL of the messages routed by this node. The messages to targets further than that
+    /// are dropped on the way.
+    pub routed_message_ttl: u8,
+    /// Unix timestamp of the last recomputation of the routing table, in milliseconds.
+    pub routing_table_updated_at_ms: Option<i64>,
+    /// The reason why the target couldn't be resolved, if any.
+    pub error: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct PingView {
+    /// Results in the order of the requested targets.
+    pub results: Vec<PingResultView>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct EdgeView {
     pub peer0: PeerId,
@@ -635,6 +765,52 @@ pub enum ChunkProcessingStatus {
     Completed,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct ShardsManagerView {
+    /// Chunks whose parts are being requested, ordered by height.
+    pub requests: Vec<ChunkRequestView>,
+    /// Number of the incomplete chunks in the cache, per shard.
+    pub num_incomplete_chunks: HashMap<ShardId, usize>,
+    /// Headers of the chunks in the cache, grouped by their previous block.
+    pub chunk_headers: Vec<PrevBlockChunkHeadersView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct ChunkRequestView {
+    pub chunk_hash: ChunkHash,
+    pub height_created: BlockHeight,
+    pub shard_id: ShardId,
+    pub prev_block_hash: CryptoHash,
+    /// The first approved block up the chain of the block missing the chunk.
+    pub ancestor_hash: CryptoHash,
+    /// Time (in millis) since the chunk was first requested.
+    pub requested_ms_ago: i64,
+    /// Time (in millis) since the request was last sent.
+    pub last_requested_ms_ago: i64,
+    /// Time (in millis) after which the request is resent.
+    pub retry_after_ms: i64,
+    pub num_hedges: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct PrevBlockChunkHeadersView {
+    pub prev_block_hash: CryptoHash,
+    /// Ordered by shard.
+    pub chunks: Vec<CachedChunkView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct CachedChunkView {
+    pub chunk_hash: ChunkHash,
+    pub height_created: BlockHeight,
+    pub shard_id: ShardId,
+    pub num_parts: usize,
+    pub num_receipts: usize,
+    pub complete: bool,
+    pub ready_for_inclusion: bool,
+    pub header_fully_validated: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct DetailedDebugStatus {
     pub network_info: NetworkInfoView,
@@ -656,6 +832,10 @@ pub struct StatusResponse {
     pub protocol_version: u32,
     /// Latest protocol version that this client supports.
     pub latest_protocol_version: u32,
+    /// Protocol version which the blocks produced by this node vote for.
+    /// None if the node is not a validator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version_vote: Option<u32>,
     /// Address for RPC server.  None if node doesn’t have RPC endpoint enabled.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rpc_addr: Option<String>,
@@ -2056,6 +2236,56 @@ impl From<StateChangesRequestView> for StateChangesRequest {
     }
 }
 
+/// It is a [serializable view] of [`StateChangeValueKind`].
+///
+/// [serializable view]: ./index.html
+/// [`StateChangeValueKind`]: ../types/enum.StateChangeValueKind.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChangeValueKindView {
+    Account,
+    AccessKey,
+    Data,
+    ContractCode,
+}
+
+impl From<StateChangeValueKindView> for StateChangeValueKind {
+    fn from(kind: StateChangeValueKindView) -> Self {
+        match kind {
+            StateChangeValueKindView::Account => Self::Account,
+            StateChangeValueKindView::AccessKey => Self::AccessKey,
+            StateChangeValueKindView::Data => Self::Data,
+            StateChangeValueKindView::ContractCode => Self::ContractCode,
+        }
+    }
+}
+
+/// It is a [serializable view] of [`StateChangesFilter`].
+///
+/// [serializable view]: ./index.html
+/// [`StateChangesFilter`]: ../types/struct.StateChangesFilter.html
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StateChangesFilterView {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<StateChangeValueKindView>,
+    #[serde(
+        default,
+        rename = "key_prefix_base64",
+        with = "option_base64_format",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub key_prefix: Option<Vec<u8>>,
+}
+
+impl From<StateChangesFilterView> for StateChangesFilter {
+    fn from(filter: StateChangesFilterView) -> Self {
+        Self {
+            kinds: filter.kinds.into_iter().map(Into::into).collect(),
+            key_prefix: filter.key_prefix.map(Into::into),
+        }
+    }
+}
+
 /// It is a [serializable view] of [`StateChangeKind`].
 ///
 /// [serializable view]: ./index.html
@@ -2345,6 +2575,130 @@ pub struct StorageUsageConfigView {
     pub num_extra_bytes_record: u64,
 }
 
+/// Gas price recommendation for transactions submitted now, computed from the recent blocks.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeHintView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    /// Gas price of the latest block.
+    #[serde(with = "dec_format")]
+    pub gas_price: Balance,
+    pub shards: Vec<ShardFeeHintView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ShardFeeHintView {
+    pub shard_id: ShardId,
+    /// Average ratio of the gas used to the gas limit of the recent chunks of the shard.
+    pub gas_utilization: f64,
+    /// Number of the transactions of the shard in the transaction pool of this node.
+    /// None if the node doesn't track the shard.
+    pub pool_size: Option<u64>,
+    /// Expected number of blocks until a transaction submitted now gets included in a chunk.
+    /// None if it cannot be estimated, because the recent chunks didn't contain transactions.
+    pub expected_inclusion_delay: Option<BlockHeightDelta>,
+    /// Highest gas price the transaction may have to pay if it gets included within the
+    /// expected delay, assuming the gas price grows at the maximal rate until then.
+    #[serde(with = "dec_format")]
+    pub recommended_gas_price: Balance,
+}
+
+/// A change of the chain head.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HeadChangeView {
+    /// Sequence number of the change. Consecutive changes have consecutive ids, so that a gap
+    /// means that some changes have been missed.
+    pub id: u64,
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub prev_block_hash: CryptoHash,
+    /// Set if the new head is not a descendant of the previous head.
+    pub reorg: Option<ReorgView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReorgView {
+    /// The latest block which is an ancestor of both the previous and the new head.
+    pub common_ancestor_hash: CryptoHash,
+    pub common_ancestor_height: BlockHeight,
+    /// Blocks of the previous canonical chain above the common ancestor, which are no longer
+    /// canonical, from the previous head down.
+    pub abandoned_block_hashes: Vec<CryptoHash>,
+}
+
+/// An outcome of a transaction or receipt executed by an account.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountActivityView {
+    /// Hash of the transaction or the id of the receipt.
+    pub outcome_id: CryptoHash,
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+}
+
+/// Transactions of an access key waiting in the pool of a node, compared with the nonce of the
+/// key on chain.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccessKeyNonceGapsView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    /// Nonce of the access key at the head of the chain.
+    pub chain_nonce: Nonce,
+    /// Nonces of the pooled transactions signed with the key, in increasing order.
+    pub pooled_nonces: Vec<Nonce>,
+    /// Ranges of the nonces missing between the on-chain nonce and the pooled ones. The pooled
+    /// transactions don't wait for them, but once a higher nonce is included, transactions with
+    /// the missing nonces can no longer be.
+    pub gaps: Vec<NonceGapView>,
+}
+
+/// Inclusive range of nonces.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NonceGapView {
+    pub start: Nonce,
+    pub end: Nonce,
+}
+
+/// Transactions waiting in the pool of a node, by the shard of the next block.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxPoolStatusView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub shards: Vec<ShardTxPoolView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShardTxPoolView {
+    pub shard_id: ShardId,
+    pub num_transactions: u64,
+    /// Number of the signers with pooled transactions.
+    pub num_accounts: u64,
+    /// Number of the pooled transactions by signer, with the busiest signers first.
+    /// Only the requested number of the busiest signers is listed.
+    pub accounts: Vec<AccountPooledTransactionsView>,
+    /// Summaries of the pooled transactions, by signer and nonce, only present if requested.
+    /// Only the requested number of the first transactions is listed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transactions: Option<Vec<PooledTransactionView>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountPooledTransactionsView {
+    pub account_id: AccountId,
+    pub num_transactions: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PooledTransactionView {
+    pub hash: CryptoHash,
+    pub signer_id: AccountId,
+    pub public_key: PublicKey,
+    pub nonce: Nonce,
+    pub receiver_id: AccountId,
+    pub num_actions: u64,
+    /// Gas attached to the function calls of the transaction.
+    pub prepaid_gas: Gas,
+}
+
 /// Contains the split storage information.
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct SplitStorageInfoView {
@@ -2828,10 +3182,11 @@ impl From<ExtCostsConfigView> for near_primitives_core::config::ExtCostsConfig {
 mod tests {
     #[cfg(not(feature = "nightly"))]
     use super::ExecutionMetadataView;
-    use super::RuntimeConfigView;
+    use super::{RuntimeConfigView, StateChangesFilterView};
     use crate::runtime::config::RuntimeConfig;
     #[cfg(not(feature = "nightly"))]
     use crate::transaction::ExecutionMetadata;
+    use crate::types::{AccountId, StateChangeValue, StateChangesFilter};
     #[cfg(not(feature = "nightly"))]
     use near_primitives_core::profile::{ProfileDataV2, ProfileDataV3};
 
@@ -2855,6 +3210,31 @@ mod tests {
         assert_eq!(config, reconstructed_config);
     }
 
+    /// `StateChangesFilterView` parsed from JSON should accept only the requested changes.
+    #[test]
+    fn test_state_changes_filter_view() {
+        let filter: StateChangesFilterView = serde_json::from_value(serde_json::json!({
+            "kinds": ["data", "contract_code"],
+            "key_prefix_base64": "YWI=",
+        }))
+        .unwrap();
+        let filter = StateChangesFilter::from(filter);
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let data_change = |key: &[u8]| StateChangeValue::DataDeletion {
+            account_id: account_id.clone(),
+            key: key.to_vec().into(),
+        };
+        assert!(filter.matches(&data_change(b"abc")));
+        assert!(!filter.matches(&data_change(b"ba")));
+        assert!(filter
+            .matches(&StateChangeValue::ContractCodeDeletion { account_id: account_id.clone() }));
+        assert!(
+            !filter.matches(&StateChangeValue::AccountDeletion { account_id: account_id.clone() })
+        );
+        assert!(StateChangesFilter::default()
+            .matches(&StateChangeValue::AccountDeletion { account_id: account_id.clone() }));
+    }
+
     /// `ExecutionMetadataView` with profile V1 displayed on the RPC should not change.
     #[test]
     #[cfg(not(feature = "nightly"))]
@@ -257,6 +257,50 @@ pub enum DBCol {
     /// *Rows*: OutcomeId (CryptoHash) || BlockHash (CryptoHash)
     /// *Column type*: ExecutionOutcomeWithProof
     TransactionResultForBlock,
+    /// Routed messages to validators which were unreachable at the time of sending.
+    /// They are re-sent once a route to the target account appears, unless they expire first.
+    /// See near_network::peer_manager::outbox for details.
+    /// - *Rows*: target AccountId || entry ID (u64), Borsh-encoded
+    /// - *Column type*: outbox entry
+    RoutedMessageOutbox,
+    /// Peers banned by this node, together with the reason and the time of the ban.
+    /// Loaded into the peer store on startup, so that bans survive a restart.
+    /// See near_network::peer_manager::peer_store for details.
+    /// - *Rows*: single row (empty row name)
+    /// - *Column type*: Vec of banned peers
+    BannedPeers,
+    /// Active edges of the routing graph which aren't adjacent to this node.
+    /// Loaded into the routing graph on startup, so that the node can route messages as soon as
+    /// it connects to the first peer, without waiting for the full routing table sync.
+    /// See near_network::routing::graph for details.
+    /// - *Rows*: single row (empty row name)
+    /// - *Column type*: Vec of edges
+    RoutingEdges,
+    /// Outcomes of the transactions and receipts, indexed by the account which executed them.
+    /// Maintained only for the tracked shards, and only if enabled in the config.
+    /// See near_chain::store::ChainStore::get_account_activity for details.
+    /// - *Rows*: AccountId + ':' + inverted BlockHeight (!u64 big-endian, latest first)
+    ///   + OutcomeId (CryptoHash)
+    /// - *Column type*: CryptoHash (hash of the block the outcome belongs to)
+    AccountActivity,
+    /// Hashes of the blocks, chunks and approvals signed by this node, recorded before they are
+    /// broadcast. Used to refuse conflicting signatures after a restart.
+    /// See near_client::signing_journal for details.
+    /// - *Rows*: kind (single byte) + height (u64 big-endian) + shard (u64 big-endian)
+    ///   + previous block hash (only for chunks)
+    /// - *Column type*: CryptoHash
+    SigningJournal,
+    /// Hashes of the challenges and transactions recently processed by the client, together
+    /// with the time they were seen at. Used to avoid reprocessing them after a restart.
+    /// See near_client::dedup_cache for details.
+    /// - *Rows*: cache name + ':' + hash of the challenge or transaction
+    /// - *Column type*: i64 (unix timestamp in milliseconds)
+    DedupCache,
+    /// Values of the selected counters, persisted periodically so that they survive a restart.
+    /// See near_client::metrics_snapshot for details.
+    /// - *Rows*: counter name + ':' + shard id (empty for the counters without a shard label)
+    /// - *Column type*: u64
+    MetricsSnapshot,
     /// Flat state contents. Used to get `ValueRef` by trie key faster than doing a trie lookup.
     /// - *Rows*: `shard_uid` + trie key (Vec<u8>)
     /// - *Column type*: ValueRef
@@ -286,7 +330,8 @@ pub enum DBCol {
 /// Currently only used in cold storage continuous migration.
 #[derive(PartialEq, Copy, Clone, Debug, Hash, Eq, strum::EnumIter)]
 pub enum DBKeyType {
-    /// Empty row name. Used in DBCol::LastComponentNonce and DBCol::RecentOutboundConnections
+    /// Empty row name. Used in DBCol::LastComponentNonce, DBCol::RecentOutboundConnections,
+    /// DBCol::BannedPeers and DBCol::RoutingEdges
     Empty,
     /// Set of predetermined strings. Used, for example, in DBCol::BlockMisc
     StringLiteral,
@@ -474,6 +519,17 @@ impl DBCol {
             DBCol::HeaderHashesByHeight => &[DBKeyType::BlockHeight],
             DBCol::StateChangesForSplitStates => &[DBKeyType::BlockHash, DBKeyType::ShardId],
             DBCol::TransactionResultForBlock => &[DBKeyType::OutcomeId, DBKeyType::BlockHash],
+            DBCol::RoutedMessageOutbox => &[DBKeyType::AccountId, DBKeyType::Nonce],
+            DBCol::BannedPeers => &[DBKeyType::Empty],
+            DBCol::RoutingEdges => &[DBKeyType::Empty],
+            DBCol::AccountActivity => {
+                &[DBKeyType::AccountId, DBKeyType::BlockHeight, DBKeyType::OutcomeId]
+            }
+            DBCol::SigningJournal => {
+                &[DBKeyType::StringLiteral, DBKeyType::BlockHeight, DBKeyType::ShardId]
+            }
+            DBCol::DedupCache => &[DBKeyType::StringLiteral, DBKeyType::TransactionHash],
+            DBCol::MetricsSnapshot => &[DBKeyType::StringLiteral, DBKeyType::ShardId],
             #[cfg(feature = "protocol_feature_flat_state")]
             DBCol::FlatState => &[DBKeyType::ShardUId, DBKeyType::TrieKey],
             #[cfg(feature = "protocol_feature_flat_state")]
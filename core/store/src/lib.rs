@@ -13,7 +13,7 @@ use strum;
 pub use columns::DBCol;
 pub use db::{
     CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
-    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, TAIL_KEY,
+    LARGEST_TARGET_HEIGHT_KEY, LAST_SIGNED_HEIGHT_KEY, LATEST_KNOWN_KEY, TAIL_KEY,
 };
 use near_crypto::PublicKey;
 use near_o11y::pretty;
@@ -35,8 +35,8 @@ pub use crate::trie::update::{TrieUpdate, TrieUpdateIterator, TrieUpdateValuePtr
 pub use crate::trie::{
     estimator, split_state, ApplyStatePartResult, KeyForStateChanges, KeyLookupMode, NibbleSlice,
     PartialStorage, PrefetchApi, PrefetchError, RawTrieNode, RawTrieNodeWithSize, ShardTries, Trie,
-    TrieAccess, TrieCache, TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage, TrieStorage,
-    WrappedTrieChanges,
+    TrieAccess, TrieCache, TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage,
+    TrieDifference, TrieStorage, WrappedTrieChanges,
 };
 
 pub mod cold_storage;
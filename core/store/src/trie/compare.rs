@@ -0,0 +1,165 @@
+//! Comparison of two tries, possibly stored in different databases.
+//!
+//! Identical subtrees have identical hashes, so the comparison descends from the roots only into
+//! the children whose hashes differ. Finding a difference in the state of a whole shard thus
+//! requires reading only a few nodes of each trie, unless the tries diverge close to the root.
+use crate::trie::iterator::TrieItem;
+use crate::trie::nibble_slice::NibbleSlice;
+use crate::trie::{RawTrieNode, Trie};
+use crate::StorageError;
+use near_primitives::hash::CryptoHash;
+
+/// The first difference between two tries, in the key order.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TrieDifference {
+    /// Nibbles of the path from the roots to the topmost differing subtrees.
+    pub path: Vec<u8>,
+    /// Hashes of the differing subtrees. `Trie::EMPTY_ROOT` stands for a missing subtree.
+    pub hashes: (CryptoHash, CryptoHash),
+    /// The smallest key with different values in the two tries, if any.
+    pub key: Option<Vec<u8>>,
+    /// Values of `key` in the two tries, None if the key is missing.
+    pub values: (Option<Vec<u8>>, Option<Vec<u8>>),
+}
+
+impl Trie {
+    /// Finds the first difference between this trie and `other`.
+    /// Returns None if the tries are equal.
+    pub fn find_first_difference(
+        &self,
+        other: &Trie,
+    ) -> Result<Option<TrieDifference>, StorageError> {
+        let mut hashes = (self.root, other.root);
+        if hashes.0 == hashes.1 {
+            return Ok(None);
+        }
+        let mut path = vec![];
+        while hashes.0 != hashes.1 {
+            let nodes = (self.retrieve_raw_node(&hashes.0)?, other.retrieve_raw_node(&hashes.1)?);
+            let (a, b) = match nodes {
+                (Some((_, a)), Some((_, b))) => (a.node, b.node),
+                // One of the subtrees is missing.
+                _ => break,
+            };
+            let (nibbles, child_hashes) = match (a, b) {
+                (RawTrieNode::Branch(a, a_value), RawTrieNode::Branch(b, b_value))
+                    if a_value == b_value =>
+                {
+                    match (0..16).find(|&i| a[i] != b[i]) {
+                        Some(i) => (
+                            vec![i],
+                            (a[i].unwrap_or(Trie::EMPTY_ROOT), b[i].unwrap_or(Trie::EMPTY_ROOT)),
+                        ),
+                        None => break,
+                    }
+                }
+                (RawTrieNode::Extension(a_key, a), RawTrieNode::Extension(b_key, b))
+                    if a_key == b_key =>
+                {
+                    (NibbleSlice::from_encoded(&a_key).0.iter().collect(), (a, b))
+                }
+                // The nodes differ in their own content or in their kind.
+                _ => break,
+            };
+            path.extend(nibbles);
+            hashes = child_hashes;
+        }
+        let (key, values) = match self.first_different_key(other, &path)? {
+            Some((key, a, b)) => (Some(key), (a, b)),
+            None => (None, (None, None)),
+        };
+        Ok(Some(TrieDifference { path, hashes, key, values }))
+    }
+
+    /// Iterates both tries over the keys starting with the given nibbles, until the first
+    /// key with different values.
+    fn first_different_key(
+        &self,
+        other: &Trie,
+        path: &[u8],
+    ) -> Result<Option<(Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)>, StorageError> {
+        // A trailing odd nibble is not a part of the byte prefix, which just makes the iteration
+        // cover a slightly larger subtree.
+        let prefix: Vec<u8> = path.chunks_exact(2).map(|n| n[0] * 16 + n[1]).collect();
+        let mut a = self.iter()?;
+        a.seek_prefix(&prefix)?;
+        let mut b = other.iter()?;
+        b.seek_prefix(&prefix)?;
+        let next = |it: &mut dyn Iterator<Item = Result<TrieItem, StorageError>>| {
+            Ok::<_, StorageError>(
+                it.next().transpose()?.filter(|(key, _)| key.starts_with(&prefix)),
+            )
+        };
+        let (mut next_a, mut next_b) = (next(&mut a)?, next(&mut b)?);
+        loop {
+            match (next_a, next_b) {
+                (Some(a_item), Some(b_item)) if a_item == b_item => {
+                    next_a = next(&mut a)?;
+                    next_b = next(&mut b)?;
+                }
+                (None, None) => return Ok(None),
+                (Some((key, value)), None) => return Ok(Some((key, Some(value), None))),
+                (None, Some((key, value))) => return Ok(Some((key, None, Some(value)))),
+                (Some((a_key, a_value)), Some((b_key, b_value))) => {
+                    return Ok(Some(match a_key.cmp(&b_key) {
+                        std::cmp::Ordering::Less => (a_key, Some(a_value), None),
+                        std::cmp::Ordering::Greater => (b_key, None, Some(b_value)),
+                        std::cmp::Ordering::Equal => (a_key, Some(a_value), Some(b_value)),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_tries, test_populate_trie};
+    use crate::ShardUId;
+
+    fn populate(changes: &[(&[u8], &[u8])]) -> (crate::ShardTries, CryptoHash) {
+        let tries = create_tries();
+        let changes = changes.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))).collect();
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, ShardUId::single_shard(), changes);
+        (tries, root)
+    }
+
+    #[test]
+    fn find_first_difference() {
+        let base: &[(&[u8], &[u8])] =
+            &[(b"alice", b"1"), (b"alice.near", b"2"), (b"bob", b"3"), (b"carol", b"4")];
+        let (tries_a, root_a) = populate(base);
+        let trie_a = tries_a.get_trie_for_shard(ShardUId::single_shard(), root_a);
+
+        // Same content in another database.
+        let (tries_b, root_b) = populate(base);
+        let trie_b = tries_b.get_trie_for_shard(ShardUId::single_shard(), root_b);
+        assert_eq!(None, trie_a.find_first_difference(&trie_b).unwrap());
+
+        // Different value.
+        let (tries_b, root_b) =
+            populate(&[(b"alice", b"1"), (b"alice.near", b"2"), (b"bob", b"5"), (b"carol", b"6")]);
+        let trie_b = tries_b.get_trie_for_shard(ShardUId::single_shard(), root_b);
+        let diff = trie_a.find_first_difference(&trie_b).unwrap().unwrap();
+        assert_eq!(Some(b"bob".to_vec()), diff.key);
+        assert_eq!((Some(b"3".to_vec()), Some(b"5".to_vec())), diff.values);
+        assert_ne!(diff.hashes.0, diff.hashes.1);
+
+        // Missing key.
+        let (tries_b, root_b) = populate(&[(b"alice", b"1"), (b"bob", b"3"), (b"carol", b"4")]);
+        let trie_b = tries_b.get_trie_for_shard(ShardUId::single_shard(), root_b);
+        let diff = trie_a.find_first_difference(&trie_b).unwrap().unwrap();
+        assert_eq!(Some(b"alice.near".to_vec()), diff.key);
+        assert_eq!((Some(b"2".to_vec()), None), diff.values);
+        let diff = trie_b.find_first_difference(&trie_a).unwrap().unwrap();
+        assert_eq!(Some(b"alice.near".to_vec()), diff.key);
+        assert_eq!((None, Some(b"2".to_vec())), diff.values);
+
+        // Empty trie.
+        let trie_b = tries_b.get_trie_for_shard(ShardUId::single_shard(), Trie::EMPTY_ROOT);
+        let diff = trie_a.find_first_difference(&trie_b).unwrap().unwrap();
+        assert_eq!(Vec::<u8>::new(), diff.path);
+        assert_eq!(Some(b"alice".to_vec()), diff.key);
+    }
+}
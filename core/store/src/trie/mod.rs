@@ -17,6 +17,7 @@ use near_primitives::trie_key::TrieKey;
 use near_primitives::types::{StateRoot, StateRootNode};
 
 use crate::flat::{FlatStateChanges, FlatStorageChunkView};
+pub use crate::trie::compare::TrieDifference;
 pub use crate::trie::config::TrieConfig;
 pub(crate) use crate::trie::config::DEFAULT_SHARD_CACHE_TOTAL_SIZE_LIMIT;
 use crate::trie::insert_delete::NodesStorage;
@@ -29,6 +30,7 @@ use crate::trie::trie_storage::{TrieMemoryPartialStorage, TrieRecordingStorage};
 use crate::StorageError;
 pub use near_primitives::types::TrieNodesCount;
 
+mod compare;
 mod config;
 mod insert_delete;
 pub mod iterator;
@@ -126,6 +126,7 @@ fn test_verify_block_double_sign_challenge() {
         *b1.header().next_bp_hash(),
         block_merkle_tree.root(),
         None,
+        None,
     );
     let epoch_id = b1.header().epoch_id().clone();
     let valid_challenge = Challenge::produce(
@@ -424,6 +425,7 @@ fn test_verify_chunk_invalid_state_challenge() {
         *last_block.header().next_bp_hash(),
         block_merkle_tree.root(),
         None,
+        None,
     );
 
     let challenge_body =
@@ -535,8 +537,12 @@ fn test_receive_invalid_chunk_as_chunk_producer() {
         one_part_receipt_proofs,
         &[merkle_paths[0].clone()],
     );
-    env.shards_manager_adapters[1]
-        .send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(partial_encoded_chunk));
+    env.shards_manager_adapters[1].send(
+        ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+            partial_encoded_chunk,
+            peer_id: None,
+        },
+    );
     env.process_block(1, block, Provenance::NONE);
 
     // At this point we should create a challenge and send it out.
@@ -656,9 +656,10 @@ impl ChunkForwardingOptimizationTestData {
                 self.num_part_ords_sent_as_partial_encoded_chunk +=
                     partial_encoded_chunk.parts.len();
                 self.env.shards_manager(&account_id).send(
-                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-                        partial_encoded_chunk.into(),
-                    ),
+                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                        partial_encoded_chunk: partial_encoded_chunk.into(),
+                        peer_id: None,
+                    },
                 );
             }
             NetworkRequests::PartialEncodedChunkForward { account_id, forward } => {
@@ -82,9 +82,10 @@ impl AdversarialBehaviorTestData {
             }
             NetworkRequests::PartialEncodedChunkMessage { account_id, partial_encoded_chunk } => {
                 self.env.shards_manager(&account_id).send(
-                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk(
-                        partial_encoded_chunk.into(),
-                    ),
+                    ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunk {
+                        partial_encoded_chunk: partial_encoded_chunk.into(),
+                        peer_id: None,
+                    },
                 );
             }
             NetworkRequests::PartialEncodedChunkForward { account_id, forward } => {
@@ -333,7 +334,7 @@ fn test_banning_chunk_producer_when_seeing_invalid_chunk_base(
 fn test_banning_chunk_producer_when_seeing_invalid_chunk() {
     init_test_logger();
     let mut test = AdversarialBehaviorTestData::new();
-    test.env.clients[7].produce_invalid_chunks = true;
+    test.env.clients[7].adv_config.produce_invalid_chunks = true;
     test_banning_chunk_producer_when_seeing_invalid_chunk_base(test);
 }
 
@@ -342,6 +343,21 @@ fn test_banning_chunk_producer_when_seeing_invalid_chunk() {
 fn test_banning_chunk_producer_when_seeing_invalid_tx_in_chunk() {
     init_test_logger();
     let mut test = AdversarialBehaviorTestData::new();
-    test.env.clients[7].produce_invalid_tx_in_chunks = true;
+    test.env.clients[7].adv_config.produce_invalid_tx_in_chunks = true;
     test_banning_chunk_producer_when_seeing_invalid_chunk_base(test);
 }
+
+#[test]
+#[cfg(feature = "test_features")]
+fn test_double_signed_block() {
+    init_test_logger();
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    let client = &mut env.clients[0];
+    let block = client.produce_block(1).unwrap().unwrap();
+    let conflicting = client.adv_make_conflicting_block(&block).unwrap();
+    assert_eq!(block.header().height(), conflicting.header().height());
+    assert_eq!(block.header().prev_hash(), conflicting.header().prev_hash());
+    assert_ne!(block.hash(), conflicting.hash());
+    let public_key = client.validator_signer.as_ref().unwrap().public_key();
+    assert!(conflicting.header().signature().verify(conflicting.hash().as_ref(), &public_key));
+}
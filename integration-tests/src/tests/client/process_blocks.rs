@@ -58,7 +58,9 @@ use near_primitives::sharding::{
     ShardChunkHeaderV3,
 };
 use near_primitives::state_part::PartId;
-use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader, StatePartKey};
+use near_primitives::syncing::{
+    get_num_state_parts, ShardStateSyncResponseHeader, StateHeaderKey, StatePartKey,
+};
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::test_utils::TestBlockBuilder;
 use near_primitives::transaction::{
@@ -411,6 +413,7 @@ fn receive_network_block() {
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
                 None,
+                None,
             );
             actor_handles.client_actor.do_send(
                 BlockResponse { block, peer_id: PeerInfo::random().id, was_requested: false }
@@ -492,6 +495,7 @@ fn produce_block_with_approvals() {
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
                 None,
+                None,
             );
             actor_handles.client_actor.do_send(
                 BlockResponse {
@@ -704,6 +708,7 @@ fn invalid_blocks_common(is_requested: bool) {
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
                 None,
+                None,
             );
             // Send block with invalid chunk mask
             let mut block = valid_block.clone();
@@ -1045,6 +1050,7 @@ fn client_sync_headers() {
                     connection_established_time: near_primitives::time::Instant::now(),
                     peer_type: PeerType::Outbound,
                     nonce: 1,
+                    traffic_by_message_type: Default::default(),
                 }],
                 num_connected_peers: 1,
                 peer_max_count: 1,
@@ -2601,6 +2607,48 @@ fn test_catchup_gas_price_change() {
     assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
 }
 
+#[test]
+fn test_precompute_state_sync_headers() {
+    use borsh::BorshSerialize;
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    env.clients[0].config.precompute_state_sync_headers = true;
+    let mut blocks = vec![];
+    for i in 1..=4 * epoch_length {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        blocks.push(block);
+    }
+
+    // The headers should be persisted for the first blocks of the epochs, once they are final.
+    let client = &env.clients[0];
+    let final_height = client.chain.final_head().unwrap().height;
+    let store = client.runtime_adapter.store();
+    let mut precomputed = 0;
+    for block in &blocks {
+        let key = StateHeaderKey(0, *block.hash()).try_to_vec().unwrap();
+        let header: Option<ShardStateSyncResponseHeader> =
+            store.get_ser(DBCol::StateHeaders, &key).unwrap();
+        // The first epoch starts at the genesis block, which can't be synced to.
+        let height = block.header().height();
+        let is_sync_block = height > 1 && height % epoch_length == 1;
+        if !is_sync_block || height > final_height {
+            assert_eq!(None, header);
+            continue;
+        }
+        let want = client.chain.compute_state_response_header(0, *block.hash()).unwrap();
+        assert_eq!(Some(want), header);
+        precomputed += 1;
+    }
+    assert!(precomputed >= 2);
+}
+
 #[test]
 fn test_block_execution_outcomes() {
     init_test_logger();
@@ -0,0 +1,46 @@
+use crate::test_helpers::heavy_test;
+use actix::System;
+use near_actix_test_utils::run_actix;
+use near_client::GetBlock;
+use near_network::test_utils::wait_or_timeout;
+use near_o11y::testonly::init_integration_logger;
+use near_o11y::WithSpanContextExt;
+use nearcore::localnet::{Localnet, LocalnetConfig};
+use std::ops::ControlFlow;
+
+/// Runs a local network with 2 shards in a single process and checks that all the nodes follow
+/// the chain.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn localnet_in_process() {
+    init_integration_logger();
+    heavy_test(|| {
+        let dir = tempfile::tempdir().unwrap();
+        run_actix(async {
+            let config = LocalnetConfig {
+                num_shards: 2,
+                num_validators: 2,
+                num_non_validators: 1,
+                epoch_length: 10,
+                ..LocalnetConfig::default()
+            };
+            let localnet = Localnet::start(dir.path(), config).unwrap();
+            assert_eq!(localnet.validators().count(), 2);
+            actix::spawn(async move {
+                wait_or_timeout(100, 60000, || async {
+                    for node in &localnet.nodes {
+                        let view_client = &node.handle.view_client;
+                        match view_client.send(GetBlock::latest().with_span_context()).await {
+                            Ok(Ok(block)) if block.header.height > 20 => {}
+                            _ => return ControlFlow::Continue(()),
+                        }
+                    }
+                    ControlFlow::Break(())
+                })
+                .await
+                .unwrap();
+                System::current().stop()
+            });
+        });
+    });
+}
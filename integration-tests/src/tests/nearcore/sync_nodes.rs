@@ -87,6 +87,7 @@ fn add_blocks(
             next_bp_hash,
             block_merkle_tree.root(),
             None,
+            None,
         );
         block_merkle_tree.insert(*block.hash());
         let _ = client.do_send(
@@ -0,0 +1,56 @@
+//! Measures the cost of constructing the network events, which clone the messages they
+//! contain, compared to disabling them with `NetworkConfig::disabled_events`.
+use near_network::config;
+use near_network::sink::Sink;
+use near_network::tcp;
+use near_network::types::Edge;
+use near_network::{Event, EventKind};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::network::PeerId;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const EDGES: usize = 1000;
+const ITERATIONS: usize = 1000;
+
+/// Pushes EdgesAdded the same way NetworkState does. Returns the time it took.
+fn push_edges_added(cfg: &config::NetworkConfig, edges: &Vec<Edge>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        if cfg.event_enabled(EventKind::EdgesAdded) {
+            cfg.push_event(Event::EdgesAdded(edges.clone()));
+        }
+    }
+    start.elapsed()
+}
+
+#[test]
+fn event_filter_benchmark() {
+    init_test_logger();
+    let edges: Vec<_> = (0..EDGES)
+        .map(|i| Edge::make_fake_edge(PeerId::random(), PeerId::random(), i as u64))
+        .collect();
+    let received = Arc::new(AtomicUsize::new(0));
+    let mut cfg = config::NetworkConfig::from_seed("test", tcp::ListenerAddr::reserve_for_test());
+    cfg.event_sink = Sink::new({
+        let received = received.clone();
+        move |ev| {
+            if let Event::EdgesAdded(_) = ev {
+                received.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let enabled = push_edges_added(&cfg, &edges);
+    assert_eq!(ITERATIONS, received.swap(0, Ordering::Relaxed));
+    cfg.disabled_events.insert(EventKind::EdgesAdded);
+    let disabled = push_edges_added(&cfg, &edges);
+    assert_eq!(0, received.load(Ordering::Relaxed));
+    tracing::info!(
+        target: "test",
+        ?enabled,
+        ?disabled,
+        "pushed {ITERATIONS} EdgesAdded events with {EDGES} edges each"
+    );
+}
@@ -1,4 +1,5 @@
 mod churn_attack;
+mod event_filter;
 mod full_network;
 mod peer_handshake;
 mod runner;
@@ -6,6 +6,7 @@ use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
 use near_chain::types::RuntimeAdapter;
 use near_chain::{Chain, ChainGenesis};
 use near_chain_configs::ClientConfig;
+use near_chunks::client::TransactionPoolSizes;
 use near_chunks::shards_manager_actor::start_shards_manager;
 use near_client::{start_client, start_view_client};
 use near_network::actix::ActixSystem;
@@ -67,6 +68,8 @@ fn setup_network_node(
     let network_adapter = Arc::new(LateBoundSender::default());
     let shards_manager_adapter = Arc::new(LateBoundSender::default());
     let adv = near_client::adversarial::Controls::default();
+    let approval_queue = near_client::ApprovalQueue::default();
+    let pool_sizes = TransactionPoolSizes::default();
     let client_actor = start_client(
         client_config.clone(),
         chain_genesis.clone(),
@@ -79,6 +82,8 @@ fn setup_network_node(
         None,
         adv.clone(),
         None,
+        approval_queue.clone(),
+        pool_sizes.clone(),
     )
     .0;
     let view_client_actor = start_view_client(
@@ -87,6 +92,7 @@ fn setup_network_node(
         runtime.clone(),
         network_adapter.clone().into(),
         client_config.clone(),
+        pool_sizes,
         adv,
     );
     let (shards_manager_actor, _) = start_shards_manager(
@@ -96,13 +102,24 @@ fn setup_network_node(
         Some(signer.validator_id().clone()),
         runtime.store().clone(),
         client_config.chunk_request_retry_period,
+        client_config.chunk_horizons.clone(),
+        client_config.chunk_availability_sampling.clone(),
+        client_config.pre_validate_chunks,
+        client_config.full_chunk_requests.clone(),
+        client_config.chunk_request_hedging.clone(),
+        client_config.chunk_only_producer_lightweight_mode,
+        client_config.chunk_header_validation_threads,
     );
     shards_manager_adapter.bind(shards_manager_actor);
     let peer_manager = PeerManagerActor::spawn(
         time::Clock::real(),
         db.clone(),
         config,
-        Arc::new(near_client::adapter::Adapter::new(client_actor, view_client_actor)),
+        Arc::new(near_client::adapter::client_sender_for_network(
+            client_actor,
+            view_client_actor,
+            approval_queue,
+        )),
         shards_manager_adapter.as_sender(),
         genesis_id,
     )
@@ -174,7 +191,7 @@ impl StateMachine {
                     debug!(target: "test", num_prev_actions, action = ?action_clone, "runner.rs: Action");
                     let pm = info.get_node(from)?.actix.addr.clone();
                     let peer_info = info.runner.test_config[to].peer_info();
-                    match tcp::Stream::connect(&peer_info, tcp::Tier::T2).await {
+                    match tcp::Stream::connect(&peer_info, tcp::Tier::T2, None, time::Duration::seconds(1)).await {
                         Ok(stream) => { pm.send(PeerManagerMessageRequest::OutboundTcpConnect(stream).with_span_context()).await?; },
                         Err(err) => tracing::debug!("tcp::Stream::connect({peer_info}): {err}"),
                     }
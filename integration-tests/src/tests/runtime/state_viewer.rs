@@ -194,8 +194,9 @@ fn assert_view_state(
         .map(|(key, value)| StateItem { key: key.to_vec(), value: value.to_vec(), proof: vec![] })
         .collect::<Vec<_>>();
 
-    let view_state =
-        |include_proof| trie_viewer.view_state(&state_update, &alice, prefix, include_proof);
+    let view_state = |include_proof| {
+        trie_viewer.view_state(&state_update, &alice, prefix, include_proof, &|| false)
+    };
 
     // Test without proof
     let result = view_state(false).unwrap();
@@ -354,10 +355,40 @@ fn test_view_state_too_large() {
         &Account::new(0, 0, CryptoHash::default(), 50_001),
     );
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false, &|| false);
     assert!(matches!(result, Err(errors::ViewStateError::AccountStateTooLarge { .. })));
 }
 
+#[test]
+fn test_view_state_cancelled() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let shard_uid = TEST_SHARD_UID;
+    let mut state_update = tries.new_trie_update(shard_uid, root);
+    for key in [b"test123", b"test321"] {
+        state_update.set(
+            TrieKey::ContractData { account_id: alice_account(), key: key.to_vec() },
+            b"123".to_vec(),
+        );
+    }
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().1;
+    let mut db_changes = tries.store_update();
+    let new_root = tries.apply_all(&trie_changes, shard_uid, &mut db_changes);
+    db_changes.commit().unwrap();
+
+    let state_update = tries.new_trie_update(shard_uid, new_root);
+    let trie_viewer = TrieViewer::default();
+    // The query is cancelled while iterating, after the first item has been read.
+    let checks = std::cell::Cell::new(0);
+    let is_cancelled = || {
+        checks.set(checks.get() + 1);
+        checks.get() > 1
+    };
+    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false, &is_cancelled);
+    assert!(matches!(result, Err(errors::ViewStateError::Cancelled)));
+    assert_eq!(checks.get(), 2);
+}
+
 #[test]
 fn test_view_state_with_large_contract() {
     let (_, tries, root) = get_runtime_and_trie();
@@ -370,7 +401,7 @@ fn test_view_state_with_large_contract() {
     );
     state_update.set(TrieKey::ContractCode { account_id: alice_account() }, contract_code);
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false, &|| false);
     assert!(result.is_ok());
 }
 
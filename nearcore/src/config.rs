@@ -2,6 +2,7 @@ use anyhow::{anyhow, bail, Context};
 use near_primitives::static_clock::StaticClock;
 use near_primitives::test_utils::create_test_signer;
 use num_rational::Rational32;
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -17,15 +18,20 @@ use tempfile::tempdir;
 use tracing::{info, warn};
 
 use crate::download_file::{run_download_file, FileDownloadError};
+use crate::rollout;
 use near_chain_configs::{
-    get_initial_supply, ClientConfig, GCConfig, Genesis, GenesisConfig, GenesisValidationMode,
-    LogSummaryStyle, MutableConfigValue,
+    get_initial_supply, ChunkAvailabilitySamplingConfig, ChunkHorizonsConfig,
+    ChunkRequestHedgingConfig, ClientConfig, DedupCachesConfig, FullChunkRequestsConfig, GCConfig,
+    Genesis, GenesisConfig, GenesisValidationMode, IntegrityScanConfig, LogSummaryStyle,
+    MetricsSnapshotConfig, MutableConfigValue, RolloutState, TransactionForwardingConfig,
+    TransactionLimitsConfig, TransactionPoolLimits, TransactionPoolOrdering, ViewQueryCacheConfig,
 };
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 #[cfg(feature = "json_rpc")]
 use near_jsonrpc::RpcConfig;
 use near_network::config::NetworkConfig;
 use near_network::tcp;
+use near_network::types::SignedKeyTransition;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::hash::CryptoHash;
 #[cfg(test)]
@@ -38,7 +44,7 @@ use near_primitives::types::{
 };
 use near_primitives::utils::{generate_random_string, get_num_seats_per_shard};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
-use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
 use near_telemetry::TelemetryConfig;
@@ -124,6 +130,9 @@ pub const CONFIG_FILENAME: &str = "config.json";
 pub const GENESIS_CONFIG_FILENAME: &str = "genesis.json";
 pub const NODE_KEY_FILE: &str = "node_key.json";
 pub const VALIDATOR_KEY_FILE: &str = "validator_key.json";
+/// Transition from the previous node key to the current one, see `neard rotate-node-key`.
+/// The node gossips it to the network if the file exists.
+pub const KEY_TRANSITION_FILE: &str = "key_transition.json";
 
 pub const MAINNET: &str = "mainnet";
 pub const TESTNET: &str = "testnet";
@@ -178,10 +187,18 @@ fn default_sync_height_threshold() -> u64 {
     1
 }
 
+fn default_signing_journal() -> bool {
+    true
+}
+
 fn default_view_client_threads() -> usize {
     4
 }
 
+fn default_view_client_query_threads() -> usize {
+    4
+}
+
 fn default_doomslug_step_period() -> Duration {
     Duration::from_millis(100)
 }
@@ -244,6 +261,33 @@ pub struct Consensus {
     pub doomslug_step_period: Duration,
     #[serde(default = "default_sync_height_threshold")]
     pub sync_height_threshold: u64,
+    /// Horizons within which received chunks are kept.
+    #[serde(default)]
+    pub chunk_horizons: ChunkHorizonsConfig,
+    /// Minimum number of healthy peers required to produce a block. Guards a validator
+    /// partitioned away with a few peers from producing blocks on a dead fork.
+    /// 0 disables the check.
+    #[serde(default)]
+    pub min_block_production_peers: usize,
+    /// Minimum number of other validators whose approvals for the height are required to
+    /// produce a block at it. 0 disables the check.
+    #[serde(default)]
+    pub min_block_production_approvers: usize,
+    /// If true, a chunk is included in the produced blocks only once its receipt proofs have
+    /// been checked and, for the tracked shards, its transactions and receipts have been
+    /// checked against the roots in the header.
+    #[serde(default)]
+    pub pre_validate_chunks: bool,
+    /// Number of the threads fully validating the headers of the incomplete chunks once their
+    /// previous block is accepted, concurrently for every shard. 0 disables the pool, the
+    /// headers are then validated on the ShardsManager thread.
+    #[serde(default)]
+    pub chunk_header_validation_threads: usize,
+    /// If set, the produced blocks vote for this protocol version instead of the one chosen by
+    /// the binary according to its upgrade schedule. Meant for rehearsing the protocol upgrades
+    /// on test networks, refused for the mainnet genesis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version_vote_override: Option<ProtocolVersion>,
 }
 
 impl Default for Consensus {
@@ -270,13 +314,50 @@ impl Default for Consensus {
             sync_step_period: default_sync_step_period(),
             doomslug_step_period: default_doomslug_step_period(),
             sync_height_threshold: default_sync_height_threshold(),
+            chunk_horizons: ChunkHorizonsConfig::default(),
+            min_block_production_peers: 0,
+            min_block_production_approvers: 0,
+            pre_validate_chunks: false,
+            chunk_header_validation_threads: 0,
+            protocol_version_vote_override: None,
         }
     }
 }
 
+/// Preset of the node configuration.
+/// Selecting a mode sets consistent defaults across the client, store and network sections
+/// of the config (see `Config::apply_mode`), and makes the config validation reject
+/// settings contradicting the mode.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NodeMode {
+    /// Produces blocks and chunks. Tracks all shards and keeps only recent data.
+    Validator,
+    /// Serves RPC queries. Tracks all shards and keeps only recent data.
+    Rpc,
+    /// Tracks all shards and keeps all the historical data.
+    Archival,
+    /// Doesn't track any shards; follows the chain by block headers and blocks only.
+    Light,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(default)]
 pub struct Config {
+    /// Preset which this config was generated from, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<NodeMode>,
     pub genesis_file: String,
     pub genesis_records_file: Option<String>,
     pub validator_key_file: String,
@@ -292,6 +373,10 @@ pub struct Config {
     pub consensus: Consensus,
     pub tracked_accounts: Vec<AccountId>,
     pub tracked_shards: Vec<ShardId>,
+    /// If set, a node which is a chunk-only producer in the epoch only tracks and requests
+    /// the parts of the shards it produces chunks for, instead of `tracked_shards`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub chunk_only_producer_lightweight_mode: bool,
     #[serde(skip_serializing_if = "is_false")]
     pub archive: bool,
     /// If save_trie_changes is not set it will get inferred from the `archive` field as follows:
@@ -308,6 +393,12 @@ pub struct Config {
     pub gc: GCConfig,
     #[serde(default = "default_view_client_threads")]
     pub view_client_threads: usize,
+    #[serde(default = "default_view_client_query_threads")]
+    pub view_client_query_threads: usize,
+    /// If set, the responses to the `view_account` and `view_access_key` queries are cached
+    /// per block, which saves the repeated trie reads on busy RPC nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_query_cache: Option<ViewQueryCacheConfig>,
     pub epoch_sync_enabled: bool,
     #[serde(default = "default_view_client_throttle_period")]
     pub view_client_throttle_period: Duration,
@@ -344,6 +435,81 @@ pub struct Config {
     /// Whether to use state sync (unreliable and corrupts the DB if fails) or do a block sync instead.
     #[serde(skip_serializing_if = "is_false")]
     pub state_sync_enabled: bool,
+    /// If set, the node periodically re-verifies the recent blocks, chunks and outcomes in its
+    /// store and reports corruption via metrics and the health endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity_scan: Option<IntegrityScanConfig>,
+    /// If set, the node periodically persists the counters of the produced and skipped blocks
+    /// and chunks, and restores them on startup, so that the per-epoch statistics survive
+    /// planned restarts. The restored values are exported with the `restarted="true"` label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_snapshot: Option<MetricsSnapshotConfig>,
+    /// If set, the node samples a few random parts of each chunk of the shards it doesn't track
+    /// and reports their availability via metrics, as an early warning of data withholding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_availability_sampling: Option<ChunkAvailabilitySamplingConfig>,
+    /// Requesting the whole chunk bodies from the peers instead of the parts, and serving them
+    /// to the peers which ask for them.
+    #[serde(default)]
+    pub full_chunk_requests: FullChunkRequestsConfig,
+    /// If set, the chunk parts which haven't arrived shortly after being requested from their
+    /// owners are requested once more from another node tracking the shard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_request_hedging: Option<ChunkRequestHedgingConfig>,
+    /// Limits on the transactions accepted via RPC or forwarded by other nodes.
+    #[serde(default)]
+    pub transaction_limits: TransactionLimitsConfig,
+    /// Caps on the number and size of the pooled transactions, per shard and per signer.
+    /// The transactions beyond them are evicted from the pool.
+    #[serde(default)]
+    pub transaction_pool_limits: TransactionPoolLimits,
+    /// Order in which the pooled transactions are included in the produced chunks: one per
+    /// access key in turn with `access_key_round_robin`, or one per signer with
+    /// `signer_round_robin`.
+    #[serde(default)]
+    pub transaction_pool_ordering: TransactionPoolOrdering,
+    /// Forwarding of the transactions to the chunk producers of the next few heights, and
+    /// forwarding them again if their inclusion isn't observed.
+    #[serde(default)]
+    pub transaction_forwarding: TransactionForwardingConfig,
+    /// Whether the blocks, chunks and approvals signed by the validator are journaled in the
+    /// store before being broadcast. Protects against double signing after a crash.
+    #[serde(default = "default_signing_journal")]
+    pub signing_journal: bool,
+    /// Whether the validator stops signing once it notices another node signing with its key.
+    /// If not set, it is enabled on the test networks and disabled on mainnet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub double_sign_detection: Option<bool>,
+    /// Caches preventing reprocessing of the same challenges and forwarded transactions.
+    #[serde(default)]
+    pub dedup_caches: DedupCachesConfig,
+    /// If set, the node doesn't switch to a fork which reverts more than this many blocks of its
+    /// canonical chain, and raises an alert instead. Meant for archival and exchange nodes, which
+    /// would rather stall than follow a deep malicious fork. The operator can allow such a fork
+    /// via the `/admin/allow_deep_reorg/{block_hash}` endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reorg_depth: Option<BlockHeightDelta>,
+    /// If set, the node indexes the outcomes of the transactions and receipts of the tracked
+    /// shards by the account which executed them, and serves the recent activity of an account
+    /// via the `EXPERIMENTAL_account_activity` RPC. Useful for simple use cases not warranting
+    /// a full indexer.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub index_account_activity: bool,
+    /// Number of threads applying the chunks of a block, one job per tracked shard, so that
+    /// a node tracking several shards applies them in parallel. Zero means one per CPU core.
+    #[serde(default)]
+    pub apply_chunks_threads: usize,
+    /// Whether the state sync headers of the tracked shards are computed and persisted as soon
+    /// as the first block of an epoch is final. Otherwise they are computed when requested by
+    /// a peer for the first time, which delays the responses at the epoch boundaries.
+    /// Disabled by default, as the headers are computed on the client thread, which delays
+    /// the processing of the blocks at the epoch boundaries instead.
+    #[serde(default)]
+    pub precompute_state_sync_headers: bool,
+    /// States of the rollout flags, overriding the config fields controlling the guarded
+    /// features. The flags are listed in `crate::rollout`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rollout: BTreeMap<String, RolloutState>,
 }
 
 fn is_false(value: &bool) -> bool {
@@ -352,6 +518,7 @@ fn is_false(value: &bool) -> bool {
 impl Default for Config {
     fn default() -> Self {
         Config {
+            mode: None,
             genesis_file: GENESIS_CONFIG_FILENAME.to_string(),
             genesis_records_file: None,
             validator_key_file: VALIDATOR_KEY_FILE.to_string(),
@@ -365,12 +532,15 @@ impl Default for Config {
             consensus: Consensus::default(),
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            chunk_only_producer_lightweight_mode: false,
             archive: false,
             save_trie_changes: None,
             log_summary_style: LogSummaryStyle::Colored,
             gc: GCConfig::default(),
             epoch_sync_enabled: true,
             view_client_threads: default_view_client_threads(),
+            view_client_query_threads: default_view_client_query_threads(),
+            view_query_cache: None,
             view_client_throttle_period: default_view_client_throttle_period(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
@@ -382,6 +552,23 @@ impl Default for Config {
             expected_shutdown: None,
             state_sync: None,
             state_sync_enabled: false,
+            integrity_scan: None,
+            metrics_snapshot: None,
+            chunk_availability_sampling: None,
+            full_chunk_requests: FullChunkRequestsConfig::default(),
+            chunk_request_hedging: None,
+            transaction_limits: TransactionLimitsConfig::default(),
+            transaction_pool_limits: TransactionPoolLimits::default(),
+            transaction_pool_ordering: TransactionPoolOrdering::default(),
+            transaction_forwarding: TransactionForwardingConfig::default(),
+            signing_journal: default_signing_journal(),
+            double_sign_detection: None,
+            dedup_caches: DedupCachesConfig::default(),
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 0,
+            precompute_state_sync_headers: false,
+            rollout: BTreeMap::new(),
         }
     }
 }
@@ -430,6 +617,49 @@ impl Default for SplitStorageConfig {
 }
 
 impl Config {
+    /// Sets the defaults of the given operating mode.
+    /// Settings not covered by the mode are left intact.
+    pub fn apply_mode(&mut self, mode: NodeMode) {
+        self.mode = Some(mode);
+        match mode {
+            NodeMode::Validator | NodeMode::Rpc | NodeMode::Archival => {
+                // Make sure node tracks all shards, see
+                // https://github.com/near/nearcore/issues/7388
+                self.tracked_shards = vec![0];
+            }
+            NodeMode::Light => {
+                self.tracked_shards = vec![];
+                self.tracked_accounts = vec![];
+            }
+        }
+        self.archive = mode == NodeMode::Archival;
+        self.save_trie_changes = None;
+        if mode != NodeMode::Archival {
+            self.cold_store = None;
+            self.split_storage = None;
+        }
+        match mode {
+            NodeMode::Validator => {
+                self.store.enable_receipt_prefetching = true;
+            }
+            NodeMode::Rpc => {
+                #[cfg(feature = "json_rpc")]
+                if self.rpc.is_none() {
+                    self.rpc = Some(RpcConfig::default());
+                }
+                self.view_client_threads = 8;
+                self.view_client_query_threads = 8;
+                self.view_query_cache = Some(ViewQueryCacheConfig::default());
+            }
+            NodeMode::Archival => {}
+            NodeMode::Light => {
+                self.network.max_num_peers = 20;
+                self.network.ideal_connections_lo = 10;
+                self.network.ideal_connections_hi = 15;
+            }
+        }
+    }
+
     /// load Config from config.json without panic. Do semantic validation on field values.
     /// If config file issues occur, a ValidationError::ConfigFileError will be returned;
     /// If config semantic checks failed, a ValidationError::ConfigSemanticError will be returned
@@ -444,6 +674,32 @@ impl Config {
     /// Skips semantic validation on field values.
     /// This function should only return error for file issues.
     pub fn from_file_skip_validation(path: &Path) -> Result<Self, ValidationError> {
+        let (config, unrecognised_fields) = Self::from_file_with_unrecognised_fields(path)?;
+        if !unrecognised_fields.is_empty() {
+            let s = if unrecognised_fields.len() > 1 { "s" } else { "" };
+            let fields = unrecognised_fields.join(", ");
+            warn!(
+                target: "neard",
+                "{}: encountered unrecognised field{s}: {fields}",
+                path.display(),
+            );
+        }
+        Ok(config)
+    }
+
+    /// Loads Config from config.json and validates it strictly: unlike `from_file`, it also
+    /// rejects the fields which don't match any field of Config, e.g. because of a typo.
+    pub fn from_file_strict(path: &Path) -> Result<Self, ValidationError> {
+        let (config, unrecognised_fields) = Self::from_file_with_unrecognised_fields(path)?;
+        crate::config_validate::validate_config_strict(&config, &unrecognised_fields)?;
+        Ok(config)
+    }
+
+    /// Loads Config from config.json without semantic validation.
+    /// Returns also the fields of the file which don't match any field of Config.
+    fn from_file_with_unrecognised_fields(
+        path: &Path,
+    ) -> Result<(Self, Vec<String>), ValidationError> {
         let json_str =
             std::fs::read_to_string(path).map_err(|_| ValidationError::ConfigFileError {
                 error_message: format!("Failed to read config from {}", path.display()),
@@ -470,21 +726,10 @@ impl Config {
                 }
             },
         )
-        .map_err(|_| ValidationError::ConfigFileError {
-            error_message: format!("Failed to deserialize config from {}", path.display()),
+        .map_err(|err| ValidationError::ConfigFileError {
+            error_message: format!("Failed to deserialize config from {}: {err}", path.display()),
         })?;
-
-        if !unrecognised_fields.is_empty() {
-            let s = if unrecognised_fields.len() > 1 { "s" } else { "" };
-            let fields = unrecognised_fields.join(", ");
-            warn!(
-                target: "neard",
-                "{}: encountered unrecognised field{s}: {fields}",
-                path.display(),
-            );
-        }
-
-        Ok(config)
+        Ok((config, unrecognised_fields))
     }
 
     fn validate(&self) -> Result<(), ValidationError> {
@@ -635,8 +880,13 @@ impl NearConfig {
         network_key_pair: KeyFile,
         validator_signer: Option<Arc<dyn ValidatorSigner>>,
     ) -> anyhow::Result<Self> {
+        // The rollout overrides are applied to a copy, so that the stored config keeps the
+        // original field values.
+        let stored_config = config.clone();
+        let mut config = config;
+        let rollout_flags = rollout::apply_rollout_flags(&mut config);
         Ok(NearConfig {
-            config: config.clone(),
+            config: stored_config,
             client_config: ClientConfig {
                 version: Default::default(),
                 chain_id: genesis.config.chain_id.clone(),
@@ -661,6 +911,8 @@ impl NearConfig {
                     .header_sync_expected_height_per_second,
                 state_sync_timeout: config.consensus.state_sync_timeout,
                 min_num_peers: config.consensus.min_num_peers,
+                min_block_production_peers: config.consensus.min_block_production_peers,
+                min_block_production_approvers: config.consensus.min_block_production_approvers,
                 log_summary_period: Duration::from_secs(10),
                 produce_empty_blocks: config.consensus.produce_empty_blocks,
                 epoch_length: genesis.config.epoch_length,
@@ -670,16 +922,32 @@ impl NearConfig {
                 block_fetch_horizon: config.consensus.block_fetch_horizon,
                 state_fetch_horizon: config.consensus.state_fetch_horizon,
                 block_header_fetch_horizon: config.consensus.block_header_fetch_horizon,
+                chunk_horizons: config.consensus.chunk_horizons,
+                pre_validate_chunks: config.consensus.pre_validate_chunks,
+                chunk_header_validation_threads: config.consensus.chunk_header_validation_threads,
+                protocol_version_vote_override: config.consensus.protocol_version_vote_override,
+                integrity_scan: config.integrity_scan,
+                metrics_snapshot: config.metrics_snapshot,
+                chunk_availability_sampling: config.chunk_availability_sampling,
+                full_chunk_requests: config.full_chunk_requests,
+                chunk_request_hedging: config.chunk_request_hedging,
+                transaction_limits: config.transaction_limits,
+                transaction_pool_limits: config.transaction_pool_limits,
+                transaction_pool_ordering: config.transaction_pool_ordering,
+                transaction_forwarding: config.transaction_forwarding,
                 catchup_step_period: config.consensus.catchup_step_period,
                 chunk_request_retry_period: config.consensus.chunk_request_retry_period,
                 doosmslug_step_period: config.consensus.doomslug_step_period,
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
+                chunk_only_producer_lightweight_mode: config.chunk_only_producer_lightweight_mode,
                 archive: config.archive,
                 save_trie_changes: config.save_trie_changes.unwrap_or(!config.archive),
                 log_summary_style: config.log_summary_style,
                 gc: config.gc,
                 view_client_threads: config.view_client_threads,
+                view_client_query_threads: config.view_client_query_threads,
+                view_query_cache: config.view_query_cache,
                 epoch_sync_enabled: config.epoch_sync_enabled,
                 view_client_throttle_period: config.view_client_throttle_period,
                 trie_viewer_state_size_limit: config.trie_viewer_state_size_limit,
@@ -704,6 +972,16 @@ impl NearConfig {
                     .as_ref()
                     .map_or(vec![], |x| x.drop_state_of_dump.clone().unwrap_or(vec![])),
                 state_sync_enabled: config.state_sync_enabled,
+                signing_journal: config.signing_journal,
+                double_sign_detection: config
+                    .double_sign_detection
+                    .unwrap_or(genesis.config.chain_id != MAINNET),
+                dedup_caches: config.dedup_caches,
+                max_reorg_depth: config.max_reorg_depth,
+                index_account_activity: config.index_account_activity,
+                apply_chunks_threads: config.apply_chunks_threads,
+                precompute_state_sync_headers: config.precompute_state_sync_headers,
+                rollout_flags,
             },
             network_config: NetworkConfig::new(
                 config.network,
@@ -945,6 +1223,7 @@ pub fn init_configs(
     download_config_url: Option<&str>,
     boot_nodes: Option<&str>,
     max_gas_burnt_view: Option<Gas>,
+    mode: Option<NodeMode>,
 ) -> anyhow::Result<()> {
     fs::create_dir_all(dir).with_context(|| anyhow!("Failed to create directory {:?}", dir))?;
 
@@ -993,6 +1272,10 @@ pub fn init_configs(
         config.max_gas_burnt_view = max_gas_burnt_view;
     }
 
+    if let Some(mode) = mode {
+        config.apply_mode(mode);
+    }
+
     // Before finalizing the Config and Genesis, make sure the node and validator keys exist.
     generate_or_load_keys(dir, &config, &chain_id, account_id, test_seed)?;
     match chain_id.as_ref() {
@@ -1001,9 +1284,11 @@ pub fn init_configs(
                 bail!("Test seed is not supported for {chain_id}");
             }
 
-            // Make sure node tracks all shards, see
+            // Make sure node tracks all shards (unless the mode says otherwise), see
             // https://github.com/near/nearcore/issues/7388
-            config.tracked_shards = vec![0];
+            if config.mode.is_none() {
+                config.tracked_shards = vec![0];
+            }
 
             config.telemetry.endpoints.push(MAINNET_TELEMETRY_URL.to_string());
             config.write_to_file(&dir.join(CONFIG_FILENAME)).with_context(|| {
@@ -1020,9 +1305,11 @@ pub fn init_configs(
                 bail!("Test seed is not supported for {chain_id}");
             }
 
-            // Make sure node tracks all shards, see
+            // Make sure node tracks all shards (unless the mode says otherwise), see
             // https://github.com/near/nearcore/issues/7388
-            config.tracked_shards = vec![0];
+            if config.mode.is_none() {
+                config.tracked_shards = vec![0];
+            }
 
             config.telemetry.endpoints.push(NETWORK_TELEMETRY_URL.replace("{}", &chain_id));
             config.write_to_file(&dir.join(CONFIG_FILENAME)).with_context(|| {
@@ -1457,6 +1744,23 @@ pub fn load_config(
         }
     };
 
+    let key_transition_file = dir.join(KEY_TRANSITION_FILE);
+    let key_transition = if key_transition_file.exists() {
+        match load_key_transition(&key_transition_file) {
+            Ok(key_transition) => Some(key_transition),
+            Err(err) => {
+                let error_message = format!(
+                    "Failed reading key transition from {}: {err:#}",
+                    key_transition_file.display()
+                );
+                validation_errors.push_node_key_file_error(error_message);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let genesis_file = dir.join(&config.genesis_file);
     let genesis_result = match &config.genesis_records_file {
         // only load Genesis from file. Skip test for now.
@@ -1483,6 +1787,12 @@ pub fn load_config(
                 let error_message = format!("The `chain_id` field specified in genesis is among mainnet/betanet/testnet, so validator must track all shards. Please change `tracked_shards` field in config.json to be any non-empty vector");
                 validation_errors.push_cross_file_semantics_error(error_message);
             }
+            if genesis.config.chain_id == MAINNET
+                && config.consensus.protocol_version_vote_override.is_some()
+            {
+                let error_message = format!("`consensus.protocol_version_vote_override` is set in config.json, but the `chain_id` field specified in genesis is mainnet. Overriding the protocol version vote is allowed only on test networks");
+                validation_errors.push_cross_file_semantics_error(error_message);
+            }
             Some(genesis)
         }
         Err(error) => {
@@ -1496,15 +1806,24 @@ pub fn load_config(
     if genesis.is_none() || network_signer.is_none() {
         panic!("Genesis and network_signer should not be None by now.")
     }
-    let near_config = NearConfig::new(
+    let mut near_config = NearConfig::new(
         config,
         genesis.unwrap(),
         network_signer.unwrap().into(),
         validator_signer,
     )?;
+    near_config.network_config.key_transition = key_transition;
     Ok(near_config)
 }
 
+/// Loads the key transition generated by `neard rotate-node-key`.
+pub fn load_key_transition(path: &Path) -> anyhow::Result<SignedKeyTransition> {
+    let json = fs::read_to_string(path)?;
+    let key_transition: SignedKeyTransition = serde_json::from_str(&json)?;
+    anyhow::ensure!(key_transition.verify(), "invalid signatures");
+    Ok(key_transition)
+}
+
 pub fn load_test_config(seed: &str, addr: tcp::ListenerAddr, genesis: Genesis) -> NearConfig {
     let mut config = Config::default();
     config.network.addr = addr.to_string();
@@ -1556,6 +1875,7 @@ fn test_init_config_localnet() {
         None,
         None,
         None,
+        None,
     )
     .unwrap();
     let genesis =
@@ -1612,6 +1932,7 @@ fn test_init_config_localnet_keep_config_create_node_key() {
         None,
         None,
         None,
+        None,
     )
     .unwrap();
 
@@ -1645,6 +1966,7 @@ fn test_init_config_localnet_keep_config_create_node_key() {
         None,
         None,
         None,
+        None,
     )
     .unwrap();
 
@@ -0,0 +1,91 @@
+//! Comparison of config.json with the default config, see `neard config diff`.
+use crate::config::Config;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A setting whose value differs from its default.
+#[derive(Debug, PartialEq)]
+pub struct ConfigDiff {
+    /// Dot-separated path of the setting, e.g. "network.max_num_peers".
+    pub path: String,
+    /// None if the setting is absent from the default config.
+    pub default: Option<Value>,
+    /// None if the setting is absent from the config.
+    pub value: Option<Value>,
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: &Option<Value>| v.as_ref().map_or("<absent>".to_string(), |v| v.to_string());
+        write!(f, "{}: {} -> {}", self.path, show(&self.default), show(&self.value))
+    }
+}
+
+/// Returns the settings of the config which differ from the defaults, ordered by path.
+/// If the config selects an operating mode, it is compared with the defaults of the mode.
+pub fn diff_from_defaults(config: &Config) -> Vec<ConfigDiff> {
+    let mut default = Config::default();
+    if let Some(mode) = config.mode {
+        default.apply_mode(mode);
+    }
+    let default = serde_json::to_value(&default).expect("Config is serializable");
+    let value = serde_json::to_value(config).expect("Config is serializable");
+    let mut diffs = vec![];
+    diff_values("", &default, &value, &mut diffs);
+    diffs
+}
+
+/// Objects are compared field by field, all the other values as a whole.
+fn diff_values(path: &str, default: &Value, value: &Value, diffs: &mut Vec<ConfigDiff>) {
+    match (default, value) {
+        (Value::Object(default), Value::Object(value)) => {
+            let keys: BTreeSet<&String> = default.keys().chain(value.keys()).collect();
+            for key in keys {
+                let path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                match (default.get(key), value.get(key)) {
+                    (Some(default), Some(value)) => diff_values(&path, default, value, diffs),
+                    (default, value) => diffs.push(ConfigDiff {
+                        path,
+                        default: default.cloned(),
+                        value: value.cloned(),
+                    }),
+                }
+            }
+        }
+        _ if default != value => diffs.push(ConfigDiff {
+            path: path.to_string(),
+            default: Some(default.clone()),
+            value: Some(value.clone()),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_from_defaults() {
+        let mut config = Config::default();
+        assert_eq!(diff_from_defaults(&config), vec![]);
+
+        config.network.max_num_peers += 1;
+        config.archive = true;
+        let diffs: Vec<String> =
+            diff_from_defaults(&config).iter().map(|d| d.to_string()).collect();
+        // `archive` isn't serialized when false, so it's absent from the default config.
+        assert_eq!(
+            diffs,
+            vec![
+                "archive: <absent> -> true".to_string(),
+                format!(
+                    "network.max_num_peers: {} -> {}",
+                    config.network.max_num_peers - 1,
+                    config.network.max_num_peers
+                ),
+            ]
+        );
+    }
+}
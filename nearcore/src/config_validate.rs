@@ -1,6 +1,11 @@
 use near_config_utils::{ValidationError, ValidationErrors};
 
-use crate::config::Config;
+use crate::config::{Config, NodeMode};
+use crate::rollout;
+
+/// Maximal number of heights ahead of the head whose chunk producers a transaction is forwarded
+/// to. The chunk producers are only looked up in the current and in the next epoch.
+const MAX_TRANSACTION_FORWARDING_HORIZON: u64 = 32;
 
 /// Validate Config extracted from config.json.
 /// This function does not panic. It returns the error if any validation fails.
@@ -11,6 +16,20 @@ pub fn validate_config(config: &Config) -> Result<(), ValidationError> {
     config_validator.validate()
 }
 
+/// Validate Config extracted from config.json, treating the fields of the file which don't match
+/// any field of Config as errors, rather than ignoring them.
+pub fn validate_config_strict(
+    config: &Config,
+    unrecognised_fields: &[String],
+) -> Result<(), ValidationError> {
+    let mut validation_errors = ValidationErrors::new();
+    for field in unrecognised_fields {
+        validation_errors.push_config_semantics_error(format!("unknown field {field}"));
+    }
+    let mut config_validator = ConfigValidator::new(config, &mut validation_errors);
+    config_validator.validate()
+}
+
 struct ConfigValidator<'a> {
     config: &'a Config,
     validation_errors: &'a mut ValidationErrors,
@@ -40,6 +59,11 @@ impl<'a> ConfigValidator<'a> {
             self.validation_errors.push_config_semantics_error(error_message)
         }
 
+        if self.config.cold_store.is_some() && !self.config.archive {
+            let error_message = format!("cold_store is configured, but archive is false. Split storage is supported only by archival nodes.");
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
+
         if self.config.consensus.min_block_production_delay
             > self.config.consensus.max_block_production_delay
         {
@@ -75,6 +99,91 @@ impl<'a> ConfigValidator<'a> {
             let error_message = format!("gc config values should all be greater than 0, but gc_blocks_limit is {:?}, gc_fork_clean_step is {}, gc_num_epochs_to_keep is {}.", self.config.gc.gc_blocks_limit, self.config.gc.gc_fork_clean_step, self.config.gc.gc_num_epochs_to_keep);
             self.validation_errors.push_config_semantics_error(error_message)
         }
+
+        if let Some(integrity_scan) = &self.config.integrity_scan {
+            if integrity_scan.period.is_zero() || integrity_scan.num_blocks == 0 {
+                let error_message = format!("integrity_scan config values should all be greater than 0, but period is {:?}, num_blocks is {}.", integrity_scan.period, integrity_scan.num_blocks);
+                self.validation_errors.push_config_semantics_error(error_message)
+            }
+        }
+
+        if self.config.view_client_threads == 0 {
+            let error_message = format!("view_client_threads should not be 0");
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
+
+        let network = &self.config.network;
+        if network.ideal_connections_lo > network.ideal_connections_hi
+            || network.ideal_connections_hi > network.max_num_peers
+        {
+            let error_message = format!("network config values should satisfy ideal_connections_lo <= ideal_connections_hi <= max_num_peers, but ideal_connections_lo is {}, ideal_connections_hi is {}, max_num_peers is {}.", network.ideal_connections_lo, network.ideal_connections_hi, network.max_num_peers);
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
+
+        let transaction_limits = &self.config.transaction_limits;
+        if transaction_limits.max_transaction_size == 0
+            || transaction_limits.max_actions == 0
+            || transaction_limits.max_args_size == 0
+        {
+            let error_message = format!("transaction_limits values should all be greater than 0, but max_transaction_size is {}, max_actions is {}, max_args_size is {}.", transaction_limits.max_transaction_size, transaction_limits.max_actions, transaction_limits.max_args_size);
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
+
+        let transaction_forwarding = &self.config.transaction_forwarding;
+        if transaction_forwarding.horizon < 2
+            || transaction_forwarding.horizon > MAX_TRANSACTION_FORWARDING_HORIZON
+            || transaction_forwarding.retry_after == 0
+        {
+            let error_message = format!("transaction_forwarding values should satisfy 2 <= horizon <= {} and retry_after > 0, but horizon is {}, retry_after is {}.", MAX_TRANSACTION_FORWARDING_HORIZON, transaction_forwarding.horizon, transaction_forwarding.retry_after);
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
+
+        for name in self.config.rollout.keys() {
+            if !rollout::is_known_flag(name) {
+                let error_message = format!("rollout flag {name} is unknown");
+                self.validation_errors.push_config_semantics_error(error_message)
+            }
+        }
+
+        if let Some(mode) = self.config.mode {
+            self.validate_mode(mode);
+        }
+    }
+
+    /// Checks that the settings don't contradict the selected operating mode.
+    fn validate_mode(&mut self, mode: NodeMode) {
+        let config = self.config;
+        let mut errors = vec![];
+        if config.archive != (mode == NodeMode::Archival) {
+            errors.push(format!("archive is {}", config.archive));
+        }
+        if mode != NodeMode::Archival && config.cold_store.is_some() {
+            errors.push("cold_store is configured".to_string());
+        }
+        match mode {
+            NodeMode::Light => {
+                if !config.tracked_shards.is_empty() || !config.tracked_accounts.is_empty() {
+                    errors.push(format!(
+                        "tracked_shards is {:?} and tracked_accounts is {:?}, but light nodes don't track shards",
+                        config.tracked_shards, config.tracked_accounts
+                    ));
+                }
+            }
+            NodeMode::Rpc | NodeMode::Archival => {
+                if config.tracked_shards.is_empty() {
+                    errors.push("tracked_shards is empty".to_string());
+                }
+            }
+            NodeMode::Validator => {}
+        }
+        #[cfg(feature = "json_rpc")]
+        if mode == NodeMode::Rpc && config.rpc.is_none() {
+            errors.push("rpc is not configured".to_string());
+        }
+        for error in errors {
+            let error_message = format!("mode is {mode}, but {error}");
+            self.validation_errors.push_config_semantics_error(error_message)
+        }
     }
 
     fn result_with_full_error(&self) -> Result<(), ValidationError> {
@@ -90,6 +199,7 @@ impl<'a> ConfigValidator<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use near_chain_configs::RolloutState;
 
     #[test]
     #[should_panic(expected = "gc config values should all be greater than 0")]
@@ -101,6 +211,37 @@ mod test {
         validate_config(&config).unwrap();
     }
 
+    #[test]
+    #[should_panic(expected = "integrity_scan config values should all be greater than 0")]
+    fn test_integrity_scan_config_value_nonzero() {
+        let mut config = Config::default();
+        config.integrity_scan =
+            Some(near_chain_configs::IntegrityScanConfig { num_blocks: 0, ..Default::default() });
+        // set tracked_shards to be non-empty
+        config.tracked_shards.push(20);
+        validate_config(&config).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "transaction_limits values should all be greater than 0")]
+    fn test_transaction_limits_value_nonzero() {
+        let mut config = Config::default();
+        config.transaction_limits.max_actions = 0;
+        // set tracked_shards to be non-empty
+        config.tracked_shards.push(20);
+        validate_config(&config).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "transaction_forwarding values should satisfy 2 <= horizon")]
+    fn test_transaction_forwarding_horizon_too_small() {
+        let mut config = Config::default();
+        config.transaction_forwarding.horizon = 1;
+        // set tracked_shards to be non-empty
+        config.tracked_shards.push(20);
+        validate_config(&config).unwrap();
+    }
+
     #[test]
     #[should_panic(
         expected = "Configuration with archive = false and save_trie_changes = false is not supported"
@@ -148,4 +289,57 @@ mod test {
         config.save_trie_changes = Some(false);
         validate_config(&config).unwrap();
     }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: cold_store is configured, but archive is false."
+    )]
+    fn test_cold_store_without_archive() {
+        let mut config = Config::default();
+        config.cold_store = Some(config.store.clone());
+        config.save_trie_changes = Some(true);
+        validate_config(&config).unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: unknown field network.max_num_peer\\n"
+    )]
+    fn test_strict_unknown_field() {
+        let config = Config::default();
+        validate_config_strict(&config, &["network.max_num_peer".to_string()]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: rollout flag flat_storage is unknown"
+    )]
+    fn test_unknown_rollout_flag() {
+        let mut config = Config::default();
+        config.rollout.insert("tier1".to_string(), RolloutState::Shadow);
+        config.rollout.insert("flat_storage".to_string(), RolloutState::Enabled);
+        validate_config(&config).unwrap();
+    }
+
+    #[test]
+    fn test_mode_presets_are_valid() {
+        for mode in [NodeMode::Validator, NodeMode::Rpc, NodeMode::Archival, NodeMode::Light] {
+            let mut config = Config::default();
+            config.apply_mode(mode);
+            validate_config(&config).unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: mode is rpc, but archive is true\\nconfig.json semantic issue: mode is rpc, but tracked_shards is empty"
+    )]
+    fn test_mode_contradicting_overrides() {
+        let mut config = Config::default();
+        config.apply_mode(NodeMode::Rpc);
+        config.archive = true;
+        config.save_trie_changes = Some(true);
+        config.tracked_shards = vec![];
+        validate_config(&config).unwrap();
+    }
 }
@@ -1,5 +1,5 @@
 use crate::config::Config;
-use near_chain_configs::UpdateableClientConfig;
+use near_chain_configs::{UpdateableClientConfig, UpdateableNetworkConfig};
 use near_dyn_configs::{UpdateableConfigLoaderError, UpdateableConfigs};
 use near_o11y::log_config::LogConfig;
 use serde::Deserialize;
@@ -19,22 +19,23 @@ pub fn read_updateable_configs(
             None
         }
     };
-    let updateable_client_config =
-        match Config::from_file(&home_dir.join(crate::config::CONFIG_FILENAME))
-            .map(get_updateable_client_config)
-        {
-            Ok(config) => Some(config),
+    let (network_config, client_config) =
+        match Config::from_file(&home_dir.join(crate::config::CONFIG_FILENAME)) {
+            Ok(config) => (
+                Some(get_updateable_network_config(&config)),
+                Some(get_updateable_client_config(config)),
+            ),
             Err(err) => {
                 errs.push(UpdateableConfigLoaderError::ConfigFileError {
                     file: PathBuf::from(crate::config::CONFIG_FILENAME),
                     err: err.into(),
                 });
-                None
+                (None, None)
             }
         };
     if errs.is_empty() {
         crate::metrics::CONFIG_CORRECT.set(1);
-        Ok(UpdateableConfigs { log_config, client_config: updateable_client_config })
+        Ok(UpdateableConfigs { log_config, client_config, network_config })
     } else {
         tracing::warn!(target: "neard", "Dynamically updateable configs are not valid. Please fix this ASAP otherwise the node will be unable to restart: {:?}", &errs);
         crate::metrics::CONFIG_CORRECT.set(0);
@@ -45,7 +46,27 @@ pub fn read_updateable_configs(
 pub fn get_updateable_client_config(config: Config) -> UpdateableClientConfig {
     // All fields that can be updated while the node is running should be explicitly set here.
     // Keep this list in-sync with `core/dyn-configs/README.md`.
-    UpdateableClientConfig { expected_shutdown: config.expected_shutdown }
+    UpdateableClientConfig {
+        expected_shutdown: config.expected_shutdown,
+        min_block_production_delay: config.consensus.min_block_production_delay,
+        max_block_production_delay: config.consensus.max_block_production_delay,
+        max_block_wait_delay: config.consensus.max_block_wait_delay,
+    }
+}
+
+pub fn get_updateable_network_config(config: &Config) -> UpdateableNetworkConfig {
+    // Keep this list in-sync with `core/dyn-configs/README.md`.
+    let experimental = &config.network.experimental;
+    UpdateableNetworkConfig {
+        tier1_recv_bytes_per_second: experimental.tier1_recv_bytes_per_second,
+        tier1_recv_burst_bytes: experimental.tier1_recv_burst_bytes,
+        tier2_recv_bytes_per_second: experimental.tier2_recv_bytes_per_second,
+        tier2_recv_burst_bytes: experimental.tier2_recv_burst_bytes,
+        max_num_peers: config.network.max_num_peers,
+        whitelist_nodes: config.network.whitelist_nodes.clone(),
+        blacklist: config.network.blacklist.clone(),
+        boot_nodes: config.network.boot_nodes.clone(),
+    }
 }
 
 fn read_log_config(home_dir: &Path) -> Result<Option<LogConfig>, UpdateableConfigLoaderError> {
@@ -10,6 +10,7 @@ use cold_storage::ColdStoreLoopHandle;
 use near_async::actix::AddrWithAutoSpanContextExt;
 use near_async::messaging::{IntoSender, LateBoundSender};
 use near_chain::{Chain, ChainGenesis};
+use near_chunks::client::TransactionPoolSizes;
 use near_chunks::shards_manager_actor::start_shards_manager;
 use near_client::{start_client, start_view_client, ClientActor, ConfigUpdater, ViewClientActor};
 use near_network::PeerManagerActor;
@@ -26,11 +27,14 @@ use tracing::info;
 pub mod append_only_map;
 mod cold_storage;
 pub mod config;
+pub mod config_diff;
 mod config_validate;
 mod download_file;
 pub mod dyn_config;
+pub mod localnet;
 mod metrics;
 pub mod migrations;
+mod rollout;
 mod runtime;
 mod state_sync;
 
@@ -205,6 +209,7 @@ pub fn start_with_config_and_synchronization(
     config_updater: Option<ConfigUpdater>,
 ) -> anyhow::Result<NearNode> {
     let store = open_storage(home_dir, &mut config)?;
+    near_chain_configs::export_rollout_flags_metrics(&config.client_config.rollout_flags);
 
     let runtime = NightshadeRuntime::from_config(home_dir, store.get_hot_store(), &config);
 
@@ -232,6 +237,7 @@ pub fn start_with_config_and_synchronization(
     let shards_manager_adapter = Arc::new(LateBoundSender::default());
     let client_adapter_for_shards_manager = Arc::new(LateBoundSender::default());
     let adv = near_client::adversarial::Controls::new(config.client_config.archive);
+    let pool_sizes = TransactionPoolSizes::default();
 
     let view_client = start_view_client(
         config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
@@ -239,8 +245,10 @@ pub fn start_with_config_and_synchronization(
         view_runtime,
         network_adapter.clone().into(),
         config.client_config.clone(),
+        pool_sizes.clone(),
         adv.clone(),
     );
+    let approval_queue = near_client::ApprovalQueue::default();
     let (client_actor, client_arbiter_handle) = start_client(
         config.client_config.clone(),
         chain_genesis.clone(),
@@ -253,6 +261,8 @@ pub fn start_with_config_and_synchronization(
         shutdown_signal,
         adv,
         config_updater,
+        approval_queue.clone(),
+        pool_sizes,
     );
     client_adapter_for_shards_manager.bind(client_actor.clone().with_auto_span_context());
     let (shards_manager_actor, shards_manager_arbiter_handle) = start_shards_manager(
@@ -262,8 +272,15 @@ pub fn start_with_config_and_synchronization(
         config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
         store.get_hot_store(),
         config.client_config.chunk_request_retry_period,
+        config.client_config.chunk_horizons.clone(),
+        config.client_config.chunk_availability_sampling.clone(),
+        config.client_config.pre_validate_chunks,
+        config.client_config.full_chunk_requests.clone(),
+        config.client_config.chunk_request_hedging.clone(),
+        config.client_config.chunk_only_producer_lightweight_mode,
+        config.client_config.chunk_header_validation_threads,
     );
-    shards_manager_adapter.bind(shards_manager_actor);
+    shards_manager_adapter.bind(shards_manager_actor.clone());
 
     let state_sync_dump_handle = spawn_state_sync_dump(
         &config,
@@ -278,7 +295,11 @@ pub fn start_with_config_and_synchronization(
         time::Clock::real(),
         store.into_inner(near_store::Temperature::Hot),
         config.network_config,
-        Arc::new(near_client::adapter::Adapter::new(client_actor.clone(), view_client.clone())),
+        Arc::new(near_client::adapter::client_sender_for_network(
+            client_actor.clone(),
+            view_client.clone(),
+            approval_queue,
+        )),
         shards_manager_adapter.as_sender(),
         genesis_id,
     )
@@ -290,9 +311,11 @@ pub fn start_with_config_and_synchronization(
         rpc_servers.extend(near_jsonrpc::start_http(
             rpc_config,
             config.genesis.config.clone(),
+            config.client_config.transaction_limits.clone(),
             client_actor.clone(),
             view_client.clone(),
             Some(network_actor),
+            Some(shards_manager_actor),
         ));
     }
 
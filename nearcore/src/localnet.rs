@@ -0,0 +1,130 @@
+//! Running all the nodes of a local network within a single process.
+//!
+//! `neard localnet` generates a home directory per node, each of which is then run by a separate
+//! neard process. That is heavy for CI and inconvenient for tests and examples, which need to
+//! talk to the nodes. `Localnet` instead runs N full nodes (client, view client, network, RPC)
+//! within the current actix System. Each node has its own home directory, and hence its own
+//! store, and listens on its own loopback ports.
+use crate::config::{create_testnet_configs, NearConfig, FAST_EPOCH_LENGTH};
+use crate::{start_with_config, NearNode};
+use anyhow::Context;
+use near_chain_configs::Genesis;
+use near_primitives::network::PeerId;
+use near_primitives::types::{AccountId, BlockHeightDelta, NumSeats, NumShards, ShardId};
+use near_primitives::validator_signer::ValidatorSigner;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct LocalnetConfig {
+    pub num_shards: NumShards,
+    pub num_validators: NumSeats,
+    pub num_non_validators: NumSeats,
+    /// The account of the i-th node is `{prefix}{i}`. It is also the name of its home directory.
+    pub prefix: String,
+    pub epoch_length: BlockHeightDelta,
+    /// Shards tracked by all the nodes, on top of the ones they validate.
+    pub tracked_shards: Vec<ShardId>,
+    pub archive: bool,
+}
+
+impl Default for LocalnetConfig {
+    fn default() -> Self {
+        Self {
+            num_shards: 1,
+            num_validators: 1,
+            num_non_validators: 0,
+            prefix: "node".to_string(),
+            epoch_length: FAST_EPOCH_LENGTH,
+            tracked_shards: vec![],
+            archive: false,
+        }
+    }
+}
+
+/// A node of the local network.
+pub struct LocalnetNode {
+    pub account_id: AccountId,
+    pub is_validator: bool,
+    pub home_dir: PathBuf,
+    pub peer_id: PeerId,
+    pub network_addr: Option<SocketAddr>,
+    pub rpc_addr: Option<String>,
+    pub handle: NearNode,
+}
+
+pub struct Localnet {
+    pub genesis: Genesis,
+    pub nodes: Vec<LocalnetNode>,
+}
+
+impl Localnet {
+    /// Creates the home directories of the nodes within `dir` and starts the nodes.
+    /// Has to be called within an actix System.
+    pub fn start(dir: &Path, config: LocalnetConfig) -> anyhow::Result<Self> {
+        Self::start_with(dir, config, |_, _| {})
+    }
+
+    /// Same as `start()`, but `customize` can adjust the config of every node (identified by its
+    /// index) before it is started.
+    pub fn start_with(
+        dir: &Path,
+        config: LocalnetConfig,
+        mut customize: impl FnMut(usize, &mut NearConfig),
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(config.num_validators > 0, "a local network needs at least one validator");
+        let (configs, validator_signers, network_signers, mut genesis, _) = create_testnet_configs(
+            config.num_shards,
+            config.num_validators,
+            config.num_non_validators,
+            &config.prefix,
+            /*local_ports=*/ true,
+            config.archive,
+            /*fixed_shards=*/ false,
+            config.tracked_shards,
+        );
+        genesis.config.epoch_length = config.epoch_length;
+
+        let mut nodes = vec![];
+        for (i, node_config) in configs.into_iter().enumerate() {
+            let account_id = validator_signers[i].validator_id().clone();
+            let is_validator = i < config.num_validators as usize;
+            let home_dir = dir.join(account_id.as_str());
+            std::fs::create_dir_all(&home_dir)
+                .with_context(|| format!("failed to create {}", home_dir.display()))?;
+            let validator_signer = if is_validator {
+                Some(Arc::new(validator_signers[i].clone()) as Arc<dyn ValidatorSigner>)
+            } else {
+                None
+            };
+            let mut near_config = NearConfig::new(
+                node_config,
+                genesis.clone(),
+                (&network_signers[i]).into(),
+                validator_signer,
+            )?;
+            customize(i, &mut near_config);
+            let peer_id = PeerId::new(near_config.network_config.node_key.public_key());
+            let network_addr = near_config.network_config.node_addr.map(|addr| *addr);
+            let rpc_addr = near_config.rpc_addr();
+            let handle = start_with_config(&home_dir, near_config)
+                .with_context(|| format!("failed to start {account_id}"))?;
+            tracing::info!(target: "near", account_id = %account_id, ?network_addr, ?rpc_addr, "Started localnet node");
+            nodes.push(LocalnetNode {
+                account_id,
+                is_validator,
+                home_dir,
+                peer_id,
+                network_addr,
+                rpc_addr,
+                handle,
+            });
+        }
+        Ok(Self { genesis, nodes })
+    }
+
+    pub fn validators(&self) -> impl Iterator<Item = &LocalnetNode> {
+        self.nodes.iter().filter(|node| node.is_validator)
+    }
+}
@@ -0,0 +1,109 @@
+//! Registry of the rollout flags, see `near_chain_configs::RolloutState` for the states.
+//!
+//! Every flag maps its state onto the config fields controlling the feature. The flags set in
+//! the `rollout` config section override those fields, the other flags report the state implied
+//! by the fields, so that the effective state is always visible.
+use crate::config::Config;
+use near_chain_configs::{RolloutFlag, RolloutState};
+
+struct FlagDef {
+    name: &'static str,
+    /// Adjusts the config to the given state.
+    apply: fn(&mut Config, RolloutState),
+    /// State implied by the config.
+    state: fn(&Config) -> RolloutState,
+}
+
+const FLAGS: &[FlagDef] = &[
+    // In shadow state the node accepts TIER1 connections from the other validators,
+    // but doesn't establish its own, so it doesn't depend on TIER1 for its messages.
+    FlagDef {
+        name: "tier1",
+        apply: |config, state| {
+            let experimental = &mut config.network.experimental;
+            experimental.tier1_enable_inbound = state != RolloutState::Disabled;
+            experimental.tier1_enable_outbound = state == RolloutState::Enabled;
+        },
+        state: |config| {
+            let experimental = &config.network.experimental;
+            if experimental.tier1_enable_outbound {
+                RolloutState::Enabled
+            } else if experimental.tier1_enable_inbound {
+                RolloutState::Shadow
+            } else {
+                RolloutState::Disabled
+            }
+        },
+    },
+    // In shadow state the Borsh peers are still accepted; how many of them would be rejected
+    // is visible in `near_peer_handshakes_by_encoding`.
+    FlagDef {
+        name: "proto_only_encoding",
+        apply: |config, state| {
+            config.network.experimental.reject_borsh_peers = state == RolloutState::Enabled;
+        },
+        state: |config| {
+            if config.network.experimental.reject_borsh_peers {
+                RolloutState::Enabled
+            } else {
+                RolloutState::Disabled
+            }
+        },
+    },
+];
+
+/// Whether a rollout flag with the given name exists.
+pub(crate) fn is_known_flag(name: &str) -> bool {
+    FLAGS.iter().any(|flag| flag.name == name)
+}
+
+/// Applies the states set in the `rollout` section to the config.
+/// Returns the effective states of all the flags.
+pub(crate) fn apply_rollout_flags(config: &mut Config) -> Vec<RolloutFlag> {
+    FLAGS
+        .iter()
+        .map(|flag| match config.rollout.get(flag.name).copied() {
+            Some(state) => {
+                (flag.apply)(config, state);
+                RolloutFlag { name: flag.name.to_string(), state, overridden: true }
+            }
+            None => RolloutFlag {
+                name: flag.name.to_string(),
+                state: (flag.state)(config),
+                overridden: false,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_rollout_flags() {
+        let mut config = Config::default();
+        config.network.experimental.reject_borsh_peers = true;
+        config.rollout.insert("tier1".to_string(), RolloutState::Shadow);
+        let flags = apply_rollout_flags(&mut config);
+        assert_eq!(
+            flags,
+            vec![
+                RolloutFlag {
+                    name: "tier1".to_string(),
+                    state: RolloutState::Shadow,
+                    overridden: true
+                },
+                RolloutFlag {
+                    name: "proto_only_encoding".to_string(),
+                    state: RolloutState::Enabled,
+                    overridden: false
+                },
+            ]
+        );
+        assert!(config.network.experimental.tier1_enable_inbound);
+        assert!(!config.network.experimental.tier1_enable_outbound);
+        // The overridden flags report the state they have been set to.
+        assert_eq!(apply_rollout_flags(&mut config), flags);
+    }
+}
@@ -106,6 +106,7 @@ impl NightshadeRuntime {
             store,
             &config.genesis,
             TrackedConfig::from_config(&config.client_config),
+            config.client_config.chunk_only_producer_lightweight_mode,
             config.client_config.trie_viewer_state_size_limit,
             config.client_config.max_gas_burnt_view,
             None,
@@ -119,6 +120,7 @@ impl NightshadeRuntime {
         store: Store,
         genesis: &Genesis,
         tracked_config: TrackedConfig,
+        chunk_only_producer_lightweight_mode: bool,
         trie_viewer_state_size_limit: Option<u64>,
         max_gas_burnt_view: Option<Gas>,
         runtime_config_store: Option<RuntimeConfigStore>,
@@ -153,7 +155,8 @@ impl NightshadeRuntime {
             EpochManager::new_from_genesis_config(store.clone().into(), &genesis_config)
                 .expect("Failed to start Epoch Manager")
                 .into_handle();
-        let shard_tracker = ShardTracker::new(tracked_config, Arc::new(epoch_manager.clone()));
+        let shard_tracker = ShardTracker::new(tracked_config, Arc::new(epoch_manager.clone()))
+            .with_chunk_only_producer_lightweight_mode(chunk_only_producer_lightweight_mode);
         Arc::new_cyclic(|myself| NightshadeRuntime {
             genesis_config,
             runtime_config_store,
@@ -183,6 +186,7 @@ impl NightshadeRuntime {
             store,
             genesis,
             tracked_config,
+            false,
             None,
             None,
             Some(runtime_config_store),
@@ -1095,6 +1099,31 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_hash: &CryptoHash,
         epoch_id: &EpochId,
         request: &QueryRequest,
+    ) -> Result<QueryResponse, near_chain::near_chain_primitives::error::QueryError> {
+        self.query_with_cancellation(
+            shard_uid,
+            state_root,
+            block_height,
+            block_timestamp,
+            prev_block_hash,
+            block_hash,
+            epoch_id,
+            request,
+            &|| false,
+        )
+    }
+
+    fn query_with_cancellation(
+        &self,
+        shard_uid: ShardUId,
+        state_root: &StateRoot,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_id: &EpochId,
+        request: &QueryRequest,
+        is_cancelled: &dyn Fn() -> bool,
     ) -> Result<QueryResponse, near_chain::near_chain_primitives::error::QueryError> {
         match request {
             QueryRequest::ViewAccount { account_id } => {
@@ -1172,6 +1201,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                         account_id,
                         prefix.as_ref(),
                         *include_proof,
+                        is_cancelled,
                     )
                     .map_err(|err| {
                         near_chain::near_chain_primitives::error::QueryError::from_view_state_error(
@@ -1593,9 +1623,10 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         account_id: &AccountId,
         prefix: &[u8],
         include_proof: bool,
+        is_cancelled: &dyn Fn() -> bool,
     ) -> Result<ViewStateResult, node_runtime::state_viewer::errors::ViewStateError> {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
-        self.trie_viewer.view_state(&state_update, account_id, prefix, include_proof)
+        self.trie_viewer.view_state(&state_update, account_id, prefix, include_proof, is_cancelled)
     }
 }
 
@@ -1809,6 +1840,7 @@ mod test {
                 store,
                 &genesis,
                 tracked_config,
+                false,
                 None,
                 None,
                 Some(RuntimeConfigStore::free()),
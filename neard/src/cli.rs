@@ -4,11 +4,13 @@ use near_amend_genesis::AmendGenesisCommand;
 use near_chain_configs::GenesisValidationMode;
 use near_client::ConfigUpdater;
 use near_cold_store_tool::ColdStoreCommand;
+use near_crypto::{KeyFile, KeyType, SecretKey};
 use near_dyn_configs::{UpdateableConfigLoader, UpdateableConfigLoaderError, UpdateableConfigs};
 use near_flat_storage::commands::FlatStorageCommand;
 use near_jsonrpc_primitives::types::light_client::RpcLightClientExecutionProofResponse;
 use near_mirror::MirrorCommand;
 use near_network::tcp;
+use near_network::types::SignedKeyTransition;
 use near_o11y::tracing_subscriber::EnvFilter;
 use near_o11y::{
     default_subscriber, default_subscriber_with_opentelemetry, BuildEnvFilterError,
@@ -17,6 +19,7 @@ use near_o11y::{
 use near_ping::PingCommand;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::compute_root_from_path;
+use near_primitives::time;
 use near_primitives::types::{Gas, NumSeats, NumShards};
 use near_state_parts::cli::StatePartsCommand;
 use near_state_viewer::StateViewerSubCommand;
@@ -123,6 +126,12 @@ impl NeardCmd {
             NeardSubCommand::ValidateConfig(cmd) => {
                 cmd.run(&home_dir)?;
             }
+            NeardSubCommand::Config(cmd) => {
+                cmd.run(&home_dir)?;
+            }
+            NeardSubCommand::RotateNodeKey(cmd) => {
+                cmd.run(&home_dir)?;
+            }
         };
         Ok(())
     }
@@ -239,6 +248,14 @@ pub(super) enum NeardSubCommand {
 
     /// validate config files including genesis.json and config.json
     ValidateConfig(ValidateConfigCommand),
+
+    /// Checks config.json for mistakes and compares it with the defaults.
+    Config(ConfigCommand),
+
+    /// Replaces the node key with a freshly generated one, without losing the identity of the
+    /// node on the network: the peers learn about the rotation from a key transition signed by
+    /// both keys, which the node gossips once restarted.
+    RotateNodeKey(RotateNodeKeyCommand),
 }
 
 #[derive(clap::Parser)]
@@ -284,6 +301,10 @@ pub(super) struct InitCmd {
     /// from genesis configuration will be taken.
     #[clap(long)]
     max_gas_burnt_view: Option<Gas>,
+    /// Operating mode of the node: validator, rpc, archival or light.
+    /// Sets consistent defaults across the config sections for the given mode.
+    #[clap(long)]
+    mode: Option<nearcore::config::NodeMode>,
 }
 
 /// Warns if unsupported build of the executable is used on mainnet or testnet.
@@ -346,6 +367,7 @@ impl InitCmd {
             self.download_config_url.as_deref(),
             self.boot_nodes.as_deref(),
             self.max_gas_burnt_view,
+            self.mode,
         )
         .context("Failed to initialize configs")
     }
@@ -794,6 +816,84 @@ impl ValidateConfigCommand {
     }
 }
 
+#[derive(clap::Parser)]
+pub(super) struct ConfigCommand {
+    #[clap(subcommand)]
+    subcmd: ConfigSubCommand,
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigSubCommand {
+    /// Validates config.json: reports the unknown fields (e.g. misspelled ones), the values out
+    /// of range and the settings inconsistent with each other.
+    Validate,
+    /// Prints the settings of config.json which differ from the defaults, or from the defaults
+    /// of the operating mode if the config selects one.
+    Diff,
+}
+
+impl ConfigCommand {
+    pub(super) fn run(&self, home_dir: &Path) -> anyhow::Result<()> {
+        let path = home_dir.join(nearcore::config::CONFIG_FILENAME);
+        match self.subcmd {
+            ConfigSubCommand::Validate => {
+                nearcore::config::Config::from_file_strict(&path)?;
+                println!("{} is valid", path.display());
+            }
+            ConfigSubCommand::Diff => {
+                let config = nearcore::config::Config::from_file_skip_validation(&path)?;
+                for diff in nearcore::config_diff::diff_from_defaults(&config) {
+                    println!("{diff}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::Parser)]
+pub(super) struct RotateNodeKeyCommand {}
+
+impl RotateNodeKeyCommand {
+    pub(super) fn run(&self, home_dir: &Path) -> anyhow::Result<()> {
+        let config = nearcore::config::Config::from_file_skip_validation(
+            &home_dir.join(nearcore::config::CONFIG_FILENAME),
+        )?;
+        let node_key_path = home_dir.join(&config.node_key_file);
+        let mut old_key_path = node_key_path.clone().into_os_string();
+        old_key_path.push(".old");
+        let old_key_path = PathBuf::from(old_key_path);
+        if old_key_path.exists() {
+            anyhow::bail!(
+                "{} already exists, remove it if the previous rotation has propagated",
+                old_key_path.display()
+            );
+        }
+        let old_key = KeyFile::from_file(&node_key_path)
+            .map_err(|err| anyhow::anyhow!("failed reading {}: {err}", node_key_path.display()))?;
+        let new_key = SecretKey::from_random(KeyType::ED25519);
+        let key_transition =
+            SignedKeyTransition::new(&time::Clock::real(), &old_key.secret_key, &new_key);
+
+        old_key.write_to_file(&old_key_path)?;
+        std::fs::write(
+            home_dir.join(nearcore::config::KEY_TRANSITION_FILE),
+            serde_json::to_string_pretty(&key_transition)?,
+        )?;
+        KeyFile {
+            account_id: old_key.account_id,
+            public_key: new_key.public_key(),
+            secret_key: new_key,
+        }
+        .write_to_file(&node_key_path)?;
+        println!(
+            "Rotated the node key from {} to {}. Restart the node to start using the new key.",
+            key_transition.old_peer_id, key_transition.new_peer_id
+        );
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{CryptoHash, NeardCmd, NeardSubCommand, VerifyProofError, VerifyProofSubCommand};
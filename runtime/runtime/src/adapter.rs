@@ -65,5 +65,6 @@ pub trait ViewRuntimeAdapter {
         account_id: &AccountId,
         prefix: &[u8],
         include_proof: bool,
+        is_cancelled: &dyn Fn() -> bool,
     ) -> Result<ViewStateResult, crate::state_viewer::errors::ViewStateError>;
 }
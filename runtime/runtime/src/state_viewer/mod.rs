@@ -113,12 +113,15 @@ impl TrieViewer {
         access_keys
     }
 
+    /// Returns the contract data of the account under the `prefix`. The iteration over the state
+    /// is abandoned once `is_cancelled` returns true.
     pub fn view_state(
         &self,
         state_update: &TrieUpdate,
         account_id: &AccountId,
         prefix: &[u8],
         include_proof: bool,
+        is_cancelled: &dyn Fn() -> bool,
     ) -> Result<ViewStateResult, errors::ViewStateError> {
         match get_account(state_update, account_id)? {
             Some(account) => {
@@ -147,6 +150,9 @@ impl TrieViewer {
         iter.remember_visited_nodes(include_proof);
         iter.seek_prefix(&query)?;
         for item in &mut iter {
+            if is_cancelled() {
+                return Err(errors::ViewStateError::Cancelled);
+            }
             let (key, value) = item?;
             values.push(StateItem {
                 key: key[acc_sep_len..].to_vec(),
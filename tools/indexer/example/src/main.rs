@@ -276,6 +276,7 @@ fn main() -> Result<()> {
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
                 await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
                 validate_genesis: true,
+                state_changes_filter: Default::default(),
             };
             let system = actix::System::new();
             system.block_on(async move {
@@ -214,10 +214,10 @@ impl crate::ChainAccess for ChainAccess {
         match self
             .view_client
             .send(
-                Query {
-                    block_reference: BlockReference::BlockId(BlockId::Hash(*block_hash)),
-                    request: QueryRequest::ViewAccessKeyList { account_id: account_id.clone() },
-                }
+                Query::new(
+                    BlockReference::BlockId(BlockId::Hash(*block_hash)),
+                    QueryRequest::ViewAccessKeyList { account_id: account_id.clone() },
+                )
                 .with_span_context(),
             )
             .await
@@ -251,6 +251,7 @@ impl MockPeerManagerActor {
                 connection_established_time: time::Instant::now(),
                 peer_type: PeerType::Outbound,
                 nonce: 1,
+                traffic_by_message_type: Default::default(),
             }],
             num_connected_peers: 1,
             peer_max_count: 1,
@@ -392,52 +393,66 @@ impl Handler<WithSpanContext<PeerManagerMessageRequest>> for MockPeerManagerActo
     ) -> Self::Result {
         let (_span, msg) = handler_debug_span!(target: "mock-node", msg);
         match msg {
-            PeerManagerMessageRequest::NetworkRequests(request) => {
-                match request {
-                    NetworkRequests::BlockRequest { hash, peer_id } => {
-                        run_later(ctx, self.network_delay, move |act, _ctx| {
-                            let block = act.chain_history_access.retrieve_block(&hash).unwrap();
-                            actix::spawn({
-                                let client = act.client.clone();
-                                async move { client.block(block, peer_id, true).await }
-                            });
+            PeerManagerMessageRequest::NetworkRequests(request) => match request {
+                NetworkRequests::BlockRequest { hash, peer_id } => {
+                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                        let block = act.chain_history_access.retrieve_block(&hash).unwrap();
+                        actix::spawn({
+                            let client = act.client.clone();
+                            async move { client.block(block, peer_id, true).await }
                         });
-                    }
-                    NetworkRequests::BlockHeadersRequest { hashes, peer_id } => {
-                        run_later(ctx, self.network_delay, move |act, _ctx| {
-                            let headers = act
-                                .chain_history_access
-                                .retrieve_block_headers(hashes.clone())
-                                .unwrap();
-                            actix::spawn({
-                                let client = act.client.clone();
-                                async move { client.block_headers(headers, peer_id).await }
-                            });
-                        });
-                    }
-                    NetworkRequests::PartialEncodedChunkRequest { request, .. } => {
-                        run_later(ctx, self.network_delay, move |act, _ctx| {
-                            let response = act
-                                .chain_history_access
-                                .retrieve_partial_encoded_chunk(&request)
-                                .unwrap();
-                            act.shards_manager_adapter.send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse { partial_encoded_chunk_response: response, received_time: StaticClock::instant().into() });
+                    });
+                }
+                NetworkRequests::BlockHeadersRequest { hashes, peer_id } => {
+                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                        let headers = act
+                            .chain_history_access
+                            .retrieve_block_headers(hashes.clone())
+                            .unwrap();
+                        actix::spawn({
+                            let client = act.client.clone();
+                            async move { client.block_headers(headers, peer_id).await }
                         });
-                    }
-                    NetworkRequests::PartialEncodedChunkResponse { .. } => {}
-                    NetworkRequests::Block { .. } => {}
-                    NetworkRequests::StateRequestHeader { .. } => {
-                        panic!(
-                            "MockPeerManagerActor receives state sync request. \
+                    });
+                }
+                NetworkRequests::PartialEncodedChunkRequest { request, .. } => {
+                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                        let response = act
+                            .chain_history_access
+                            .retrieve_partial_encoded_chunk(&request)
+                            .unwrap();
+                        let peer_id = match act.network_info.connected_peers.first() {
+                            Some(peer) => peer.full_peer_info.peer_info.id.clone(),
+                            None => {
+                                tracing::warn!(
+                                    target: "mock-node",
+                                    "No connected peer to respond to the chunk request from"
+                                );
+                                return;
+                            }
+                        };
+                        act.shards_manager_adapter.send(
+                            ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkResponse {
+                                partial_encoded_chunk_response: response,
+                                peer_id,
+                                received_time: StaticClock::instant().into(),
+                            },
+                        );
+                    });
+                }
+                NetworkRequests::PartialEncodedChunkResponse { .. } => {}
+                NetworkRequests::Block { .. } => {}
+                NetworkRequests::StateRequestHeader { .. } => {
+                    panic!(
+                        "MockPeerManagerActor receives state sync request. \
                             It doesn't support state sync now. Try setting start_height \
                             and target_height to be at the same epoch to avoid state sync"
-                        );
-                    }
-                    _ => {
-                        panic!("MockPeerManagerActor receives unexpected message {:?}", request);
-                    }
+                    );
                 }
-            }
+                _ => {
+                    panic!("MockPeerManagerActor receives unexpected message {:?}", request);
+                }
+            },
             _ => {
                 panic!("MockPeerManagerActor receives unexpected message {:?}", msg);
             }
@@ -9,6 +9,7 @@ use near_chain::types::RuntimeAdapter;
 use near_chain::ChainStoreUpdate;
 use near_chain::{Chain, ChainGenesis, ChainStore, ChainStoreAccess, DoomslugThresholdMode};
 use near_chain_configs::GenesisConfig;
+use near_chunks::client::TransactionPoolSizes;
 use near_chunks::shards_manager_actor::start_shards_manager;
 use near_client::{start_client, start_view_client, ClientActor, ViewClientActor};
 use near_epoch_manager::{EpochManager, EpochManagerAdapter};
@@ -240,6 +241,8 @@ pub fn setup_mock_node(
     }
 
     let block_production_delay = config.client_config.min_block_production_delay;
+    let approval_queue = near_client::ApprovalQueue::default();
+    let pool_sizes = TransactionPoolSizes::default();
     let (client, _) = start_client(
         config.client_config.clone(),
         chain_genesis.clone(),
@@ -252,6 +255,8 @@ pub fn setup_mock_node(
         None,
         adv.clone(),
         None,
+        approval_queue.clone(),
+        pool_sizes.clone(),
     );
 
     let view_client = start_view_client(
@@ -260,6 +265,7 @@ pub fn setup_mock_node(
         client_runtime.clone(),
         network_adapter.clone().into(),
         config.client_config.clone(),
+        pool_sizes,
         adv,
     );
 
@@ -270,6 +276,13 @@ pub fn setup_mock_node(
         config.validator_signer.map(|signer| signer.validator_id().clone()),
         client_runtime.store().clone(),
         config.client_config.chunk_request_retry_period,
+        config.client_config.chunk_horizons.clone(),
+        config.client_config.chunk_availability_sampling.clone(),
+        config.client_config.pre_validate_chunks,
+        config.client_config.full_chunk_requests.clone(),
+        config.client_config.chunk_request_hedging.clone(),
+        config.client_config.chunk_only_producer_lightweight_mode,
+        config.client_config.chunk_header_validation_threads,
     );
     shards_manager_adapter.bind(shards_manager_actor);
 
@@ -293,7 +306,11 @@ pub fn setup_mock_node(
         MockPeerManagerActor::start_in_arbiter(&arbiter.handle(), move |_ctx| {
             setup_mock_peer_manager_actor(
                 chain,
-                Arc::new(near_client::adapter::Adapter::new(client1, view_client1)),
+                Arc::new(near_client::adapter::client_sender_for_network(
+                    client1,
+                    view_client1,
+                    approval_queue,
+                )),
                 shards_manager_adapter.as_sender(),
                 &genesis_config,
                 block_production_delay,
@@ -235,6 +235,9 @@ fn load_snapshot(load_cmd: LoadCmd) {
         ChainConfig {
             save_trie_changes: config.client_config.save_trie_changes,
             background_migration_threads: 1,
+            max_reorg_depth: None,
+            index_account_activity: false,
+            apply_chunks_threads: 1,
         },
     )
     .unwrap();
@@ -37,6 +37,10 @@ pub enum StateViewerSubCommand {
     CheckBlock,
     /// Looks up a certain chunk.
     Chunks(ChunksCmd),
+    /// Compares the state of a shard with the state in another database and prints the first
+    /// difference, e.g. to verify a restored backup.
+    #[clap(alias = "compare_state")]
+    CompareState(CompareStateCmd),
     /// List account names with contracts deployed.
     #[clap(alias = "contract_accounts")]
     ContractAccounts(ContractAccountsCmd),
@@ -118,6 +122,7 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::Chain(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::CheckBlock => check_block_chunk_existence(near_config, store),
             StateViewerSubCommand::Chunks(cmd) => cmd.run(near_config, store),
+            StateViewerSubCommand::CompareState(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::ContractAccounts(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpAccountStorage(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpCode(cmd) => cmd.run(home_dir, near_config, store),
@@ -266,6 +271,32 @@ impl ChunksCmd {
     }
 }
 
+#[derive(clap::Parser)]
+pub struct CompareStateCmd {
+    /// Path to the database to compare with, e.g. the `data` directory of another node.
+    #[clap(long)]
+    other_db: PathBuf,
+    /// The state of the shard after applying the block at this height is compared.
+    #[clap(long)]
+    height: BlockHeight,
+    #[clap(long, alias = "shard")]
+    shard_id: ShardId,
+}
+
+impl CompareStateCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let other_config = near_store::StoreConfig {
+            path: Some(self.other_db.clone()),
+            ..near_config.config.store.clone()
+        };
+        let other_store = NodeStorage::opener(home_dir, false, &other_config, None)
+            .open_in_mode(Mode::ReadOnly)
+            .unwrap_or_else(|e| panic!("Error opening {}: {e}", self.other_db.display()))
+            .get_hot_store();
+        compare_state(self.height, self.shard_id, &near_config, store, other_store).unwrap();
+    }
+}
+
 #[derive(clap::Parser)]
 pub struct ContractAccountsCmd {
     #[clap(flatten)]
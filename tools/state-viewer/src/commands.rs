@@ -774,6 +774,80 @@ fn get_trie(store: Store, hash: CryptoHash, shard_id: u32, shard_version: u32) -
     Trie::new(Box::new(trie_storage), hash, None)
 }
 
+/// Compares the state of the shard after applying the block at the given height in `store` and
+/// `other_store`, and prints the first difference.
+pub(crate) fn compare_state(
+    height: BlockHeight,
+    shard_id: ShardId,
+    near_config: &NearConfig,
+    store: Store,
+    other_store: Store,
+) -> anyhow::Result<()> {
+    let chain_store = |store: &Store| {
+        ChainStore::new(
+            store.clone(),
+            near_config.genesis.config.genesis_height,
+            near_config.client_config.save_trie_changes,
+        )
+    };
+    let (chain_store, other_chain_store) = (chain_store(&store), chain_store(&other_store));
+    let block_hash = chain_store.get_block_hash_by_height(height)?;
+    let other_block_hash = other_chain_store.get_block_hash_by_height(height)?;
+    if block_hash != other_block_hash {
+        println!(
+            "{}",
+            Red.paint(format!(
+                "The databases have different blocks at height {height}: \
+                 {block_hash} vs {other_block_hash}"
+            ))
+        );
+    }
+
+    let epoch_manager =
+        EpochManager::new_from_genesis_config(store.clone(), &near_config.genesis.config)?;
+    let epoch_id = chain_store.get_block_header(&block_hash)?.epoch_id().clone();
+    let shard_layout = epoch_manager.get_shard_layout(&epoch_id)?;
+    let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+    let state_root = *chain_store.get_chunk_extra(&block_hash, &shard_uid)?.state_root();
+    let other_state_root =
+        *other_chain_store.get_chunk_extra(&other_block_hash, &shard_uid)?.state_root();
+    println!("State roots of shard {shard_uid}: {state_root} vs {other_state_root}");
+
+    let trie = get_trie(store, state_root, shard_uid.shard_id, shard_uid.version);
+    let other_trie = get_trie(other_store, other_state_root, shard_uid.shard_id, shard_uid.version);
+    let diff = match trie.find_first_difference(&other_trie)? {
+        Some(diff) => diff,
+        None => {
+            println!("The states are identical");
+            return Ok(());
+        }
+    };
+    let path: String = diff.path.iter().map(|nibble| format!("{nibble:x}")).collect();
+    println!(
+        "First differing subtree: path 0x{path}, hashes {} vs {}",
+        diff.hashes.0, diff.hashes.1
+    );
+    let key = match diff.key {
+        Some(key) => key,
+        None => {
+            println!("No key differs in the subtree");
+            return Ok(());
+        }
+    };
+    let key_hex: String = key.iter().map(|byte| format!("{byte:02x}")).collect();
+    println!("First differing key: 0x{key_hex}");
+    let print_value = |name: &str, value: Option<Vec<u8>>| match value {
+        Some(value) => match StateRecord::from_raw_key_value(key.clone(), value.clone()) {
+            Some(record) => println!("  {name}: {record}"),
+            None => println!("  {name}: {} bytes", value.len()),
+        },
+        None => println!("  {name}: missing"),
+    };
+    print_value("this database", diff.values.0);
+    print_value("other database", diff.values.1);
+    Ok(())
+}
+
 pub(crate) fn view_trie(
     store: Store,
     hash: CryptoHash,